@@ -27,9 +27,11 @@ fn prog(args: ProgArgs) {
     let opts = query::ProgInfoQueryOptions::default().include_all();
     for prog in query::ProgInfoIter::with_query_opts(opts) {
         println!(
-            "name={:<16} type={:<15?} run_count={:<2} runtime_ns={} recursion_misses={:<2}",
+            "name={:<16} type={:<15?} tag={} maps={:?} run_count={:<2} runtime_ns={} recursion_misses={:<2}",
             prog.name.to_string_lossy(),
             prog.ty,
+            prog.tag,
+            prog.map_ids,
             prog.run_cnt,
             prog.run_time_ns,
             prog.recursion_misses,
@@ -67,10 +69,11 @@ fn map() {
 fn btf() {
     for btf in query::BtfInfoIter::default() {
         println!(
-            "id={:4} name={} size={}",
+            "id={:4} name={} size={} kernel={}",
             btf.id,
             btf.name.to_string_lossy(),
-            btf.btf.len()
+            btf.size(),
+            btf.kernel_btf,
         );
     }
 }
@@ -83,7 +86,8 @@ fn link() {
             query::LinkTypeInfo::Cgroup(_) => "cgroup",
             query::LinkTypeInfo::Iter => "iter",
             query::LinkTypeInfo::NetNs(_) => "netns",
-            query::LinkTypeInfo::Unknown => "unknown",
+            query::LinkTypeInfo::Netfilter(_) => "netfilter",
+            _ => "unknown",
         };
 
         println!(
@@ -19,6 +19,7 @@ fn main() {
 
     SkeletonBuilder::new()
         .source(SRC)
+        .plain(true)
         .clang_args([
             OsStr::new("-I"),
             vmlinux::include_path_root().join(arch).as_os_str(),
@@ -7,7 +7,6 @@ use core::time::Duration;
 use std::str;
 use std::str::FromStr;
 
-use anyhow::bail;
 use anyhow::Result;
 use clap::Parser;
 use libbpf_rs::skel::OpenSkel;
@@ -15,7 +14,6 @@ use libbpf_rs::skel::Skel;
 use libbpf_rs::skel::SkelBuilder;
 use libbpf_rs::PerfBufferBuilder;
 use phf::phf_map;
-use plain::Plain;
 use time::macros::format_description;
 use time::OffsetDateTime;
 
@@ -107,21 +105,6 @@ struct Command {
     debug: bool,
 }
 
-unsafe impl Plain for capable_types::event {}
-
-fn bump_memlock_rlimit() -> Result<()> {
-    let rlimit = libc::rlimit {
-        rlim_cur: 128 << 20,
-        rlim_max: 128 << 20,
-    };
-
-    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) } != 0 {
-        bail!("Failed to increase rlimit");
-    }
-
-    Ok(())
-}
-
 fn print_banner(extra_fields: bool) {
     #[allow(clippy::print_literal)]
     if extra_fields {
@@ -186,7 +169,7 @@ fn main() -> Result<()> {
         skel_builder.obj_builder.debug(true);
     }
 
-    bump_memlock_rlimit()?;
+    libbpf_rs::bump_memlock_rlimit()?;
 
     let mut open_skel = skel_builder.open()?;
     //Pass configuration to BPF
@@ -196,11 +179,7 @@ fn main() -> Result<()> {
         .tool_config
         .verbose
         .write(opts.verbose);
-    open_skel
-        .rodata_mut()
-        .tool_config
-        .unique_type
-        .write(opts.unique_type);
+    open_skel.rodata_mut().tool_config.unique_type = opts.unique_type;
 
     let mut skel = open_skel.load()?;
     skel.attach()?;
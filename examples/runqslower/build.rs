@@ -1,29 +1,23 @@
 use std::env;
 use std::ffi::OsStr;
-use std::path::PathBuf;
 
 use libbpf_cargo::SkeletonBuilder;
 
 const SRC: &str = "src/bpf/runqslower.bpf.c";
 
 fn main() {
-    let out = PathBuf::from(
-        env::var_os("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR must be set in build script"),
-    )
-    .join("src")
-    .join("bpf")
-    .join("runqslower.skel.rs");
-
     let arch = env::var("CARGO_CFG_TARGET_ARCH")
         .expect("CARGO_CFG_TARGET_ARCH must be set in build script");
 
-    SkeletonBuilder::new()
+    let mut builder = SkeletonBuilder::new();
+    builder
         .source(SRC)
+        .plain(true)
         .clang_args([
             OsStr::new("-I"),
             vmlinux::include_path_root().join(arch).as_os_str(),
         ])
-        .build_and_generate(&out)
+        .build_and_generate_out_dir()
         .unwrap();
-    println!("cargo:rerun-if-changed={SRC}");
+    builder.emit_rerun_if_changed();
 }
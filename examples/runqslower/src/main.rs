@@ -3,23 +3,16 @@
 use std::str;
 use std::time::Duration;
 
-use anyhow::bail;
 use anyhow::Result;
 use clap::Parser;
 use libbpf_rs::skel::OpenSkel;
 use libbpf_rs::skel::Skel;
 use libbpf_rs::skel::SkelBuilder;
 use libbpf_rs::PerfBufferBuilder;
-use plain::Plain;
 use time::macros::format_description;
 use time::OffsetDateTime;
 
-mod runqslower {
-    include!(concat!(
-        env!("CARGO_MANIFEST_DIR"),
-        "/src/bpf/runqslower.skel.rs"
-    ));
-}
+libbpf_rs::include_skel!(runqslower);
 
 use runqslower::*;
 
@@ -40,21 +33,6 @@ struct Command {
     verbose: bool,
 }
 
-unsafe impl Plain for runqslower_types::event {}
-
-fn bump_memlock_rlimit() -> Result<()> {
-    let rlimit = libc::rlimit {
-        rlim_cur: 128 << 20,
-        rlim_max: 128 << 20,
-    };
-
-    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) } != 0 {
-        bail!("Failed to increase rlimit");
-    }
-
-    Ok(())
-}
-
 fn handle_event(_cpu: i32, data: &[u8]) {
     let mut event = runqslower_types::event::default();
     plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short");
@@ -90,7 +68,7 @@ fn main() -> Result<()> {
         skel_builder.obj_builder.debug(true);
     }
 
-    bump_memlock_rlimit()?;
+    libbpf_rs::bump_memlock_rlimit()?;
     let mut open_skel = skel_builder.open()?;
 
     // Write arguments into prog
@@ -0,0 +1,39 @@
+// SPDX-License-Identifier: GPL-2.0
+
+#include "vmlinux.h"
+
+#include <bpf/bpf_helpers.h>
+
+#define SOL_IP 0
+#define IP_TOS 1
+#define PAGE_SIZE 4096
+
+char _license[] SEC("license") = "GPL";
+
+const volatile __u8 forced_tos = 0;
+__u64 intercepted = 0;
+
+SEC("cgroup/setsockopt")
+int intercept_setsockopt(struct bpf_sockopt *ctx)
+{
+    int *optval = ctx->optval;
+    int *optval_end = ctx->optval_end;
+
+    if (ctx->level != SOL_IP || ctx->optname != IP_TOS) {
+        /* Only the first page of large option values is made
+         * available to the program. Setting the length to zero tells
+         * the kernel to use the original value as-is.
+         */
+        if (ctx->optlen > PAGE_SIZE)
+            ctx->optlen = 0;
+        return 1;
+    }
+
+    if (optval + 1 > optval_end)
+        return 1;
+
+    *optval = forced_tos;
+    __sync_fetch_and_add(&intercepted, 1);
+    /* Let the kernel apply the (modified) option. */
+    return 1;
+}
@@ -0,0 +1,103 @@
+use std::io;
+use std::mem::size_of;
+use std::net::Ipv4Addr;
+use std::net::UdpSocket;
+use std::os::fd::AsRawFd as _;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::Parser;
+
+use libbpf_rs::cgroup;
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::SkelBuilder;
+
+mod sockopt {
+    include!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/bpf/sockopt.skel.rs"
+    ));
+}
+use sockopt::*;
+
+/// Force the type of service of sockets in a cgroup by intercepting
+/// `setsockopt(IP_TOS)` calls.
+#[derive(Debug, Parser)]
+struct Command {
+    /// The cgroup to attach to.
+    #[arg(short, long, default_value = cgroup::CGROUP_ROOT)]
+    cgroup: PathBuf,
+    /// The type of service value to force.
+    #[arg(short, long, default_value_t = 0x10)]
+    tos: u8,
+    /// Verbose debug output.
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+fn ip_tos(socket: &UdpSocket) -> Result<i32> {
+    let mut tos = 0i32;
+    let mut len = size_of::<i32>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_IP,
+            libc::IP_TOS,
+            (&mut tos as *mut i32).cast(),
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error()).context("failed to retrieve IP_TOS");
+    }
+    Ok(tos)
+}
+
+fn set_ip_tos(socket: &UdpSocket, tos: i32) -> Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_IP,
+            libc::IP_TOS,
+            (&tos as *const i32).cast(),
+            size_of::<i32>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error()).context("failed to set IP_TOS");
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let opts = Command::parse();
+
+    let mut builder = SockoptSkelBuilder::default();
+    if opts.verbose {
+        builder.obj_builder.debug(true);
+    }
+    let mut open = builder.open()?;
+    open.rodata_mut().forced_tos = opts.tos;
+
+    let mut skel = open.load()?;
+    // The program's section name makes it a `CgroupSockopt` program
+    // using the `CgroupSetsockopt` attach type. The link detaches the
+    // program once dropped.
+    let _link = skel
+        .progs_mut()
+        .intercept_setsockopt()
+        .attach_cgroup_path(&opts.cgroup)
+        .with_context(|| format!("failed to attach to `{}`", opts.cgroup.display()))?;
+
+    // If we are part of the cgroup (as is the case for the root one) our
+    // own setsockopt calls get intercepted as well.
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let () = set_ip_tos(&socket, 0)?;
+    println!("Requested TOS 0x00, got TOS {:#04x}", ip_tos(&socket)?);
+    println!(
+        "Intercepted {} setsockopt(IP_TOS) call(s)",
+        skel.bss().intercepted
+    );
+    Ok(())
+}
@@ -1,7 +1,6 @@
 use std::net::Ipv4Addr;
-use std::os::fd::AsFd;
-use std::os::fd::IntoRawFd;
-use std::os::unix::fs::OpenOptionsExt;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -11,21 +10,15 @@ use libc::SOCK_CLOEXEC;
 use libc::SOCK_NONBLOCK;
 use libc::SOCK_RAW;
 
-use libc::c_int;
-use libc::c_void;
-use libc::socklen_t;
-use std::mem::size_of_val;
-
-use anyhow::bail;
 use anyhow::Result;
 use clap::Parser;
-use std::fs::OpenOptions;
 use std::io::Error;
 use std::result::Result::Ok;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::thread;
 
+use libbpf_rs::cgroup;
 use libbpf_rs::skel::OpenSkel;
 use libbpf_rs::skel::SkelBuilder;
 
@@ -37,8 +30,6 @@ mod tcp_option {
 }
 use tcp_option::*;
 
-const SOL_SOCKET: c_int = 1;
-const SO_ATTACH_BPF: c_int = 50;
 const ETH_P_ALL: u16 = 0x0003;
 
 #[derive(Debug, Parser)]
@@ -53,20 +44,7 @@ struct Command {
     verbose: bool,
 }
 
-fn bump_memlock_rlimit() -> Result<()> {
-    let rlimit = libc::rlimit {
-        rlim_cur: 128 << 20,
-        rlim_max: 128 << 20,
-    };
-
-    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) } != 0 {
-        bail!("Failed to increase rlimit");
-    }
-
-    Ok(())
-}
-
-fn open_fd() -> Result<i32> {
+fn open_fd() -> Result<OwnedFd> {
     unsafe {
         match socket(
             AF_PACKET,
@@ -74,14 +52,14 @@ fn open_fd() -> Result<i32> {
             ETH_P_ALL.to_be() as i32,
         ) {
             -1 => Err(Error::last_os_error().into()),
-            fd => Ok(fd),
+            fd => Ok(OwnedFd::from_raw_fd(fd)),
         }
     }
 }
 
 fn main() -> Result<()> {
     let opts = Command::parse();
-    bump_memlock_rlimit()?;
+    libbpf_rs::bump_memlock_rlimit()?;
 
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
@@ -106,40 +84,18 @@ fn main() -> Result<()> {
 
     let mut skel = open.load()?;
 
-    let cgroup_fd = OpenOptions::new()
-        .read(true)
-        .custom_flags(libc::O_DIRECTORY)
-        .open("/sys/fs/cgroup")
-        .unwrap()
-        .into_raw_fd();
-
     let _kprobe = skel
         .progs_mut()
         .sockops_write_tcp_options()
-        .attach_cgroup(cgroup_fd)
+        .attach_cgroup_path(cgroup::CGROUP_ROOT)
         .unwrap();
 
-    let target_socket_fd = open_fd()?;
+    let target_socket = open_fd()?;
     let progs = skel.progs();
-    let socket_handler = progs.socket_handler();
-    let prog_fd = socket_handler.as_fd();
-    match unsafe {
-        libc::setsockopt(
-            target_socket_fd as c_int,
-            SOL_SOCKET,
-            SO_ATTACH_BPF,
-            &prog_fd as *const _ as *const c_void,
-            size_of_val(&prog_fd) as socklen_t,
-        )
-    } {
-        0 => {
-            println!("BPF Attached Successfully!");
-        }
-        _ => {
-            println!("Failed to Attach BPF, Reason: ");
-            return Err(Error::last_os_error().into());
-        }
-    };
+    let () = progs
+        .socket_handler()
+        .attach_socket_filter(&target_socket)?;
+    println!("BPF Attached Successfully!");
 
     while running.load(Ordering::SeqCst) {
         thread::sleep(Duration::new(1, 0));
@@ -0,0 +1,609 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use clap::ValueEnum;
+
+use libbpf_rs::btf::types;
+use libbpf_rs::btf::BtfBuilder;
+use libbpf_rs::btf::BtfKind;
+use libbpf_rs::btf::BtfType;
+use libbpf_rs::btf::HasSize as _;
+use libbpf_rs::btf::ReferencesType as _;
+use libbpf_rs::btf::TypeId;
+use libbpf_rs::Btf;
+use libbpf_rs::CoreRelocation;
+use libbpf_rs::CoreRelocationKind;
+use libbpf_rs::ObjectInspector;
+
+/// The format to dump BTF in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BtfFormat {
+    /// C declarations, as found in `vmlinux.h`
+    #[default]
+    C,
+    /// A JSON array of all types
+    Json,
+}
+
+/// Render the BTF contained in `object` in the given format.
+pub fn dump_btf(object: &Path, format: BtfFormat) -> Result<String> {
+    let btf = Btf::from_path(object)
+        .with_context(|| format!("failed to load BTF from `{}`", object.display()))?;
+
+    let output = match format {
+        BtfFormat::C => btf.dump_c().context("failed to dump BTF as C")?,
+        BtfFormat::Json => {
+            let types = (0..btf.len() as u32)
+                .filter_map(|id| btf.type_by_id::<BtfType<'_>>(TypeId::from(id)))
+                .collect::<Vec<_>>();
+            serde_json::to_string_pretty(&types).context("failed to serialize BTF")?
+        }
+    };
+    Ok(output)
+}
+
+/// Dump the BTF of the BPF object file `object` to stdout.
+pub fn dump(debug: bool, object: &Path, format: BtfFormat) -> Result<()> {
+    if debug {
+        println!("Dumping BTF of {} as {format:?}", object.display());
+    }
+
+    let output = dump_btf(object, format)?;
+    println!("{output}");
+    Ok(())
+}
+
+/// The BTF to generate a `vmlinux.h` header from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VmlinuxBtf {
+    /// The BTF of the running kernel.
+    Kernel,
+    /// The BTF contained in the given file, e.g., a raw BTF file as
+    /// found at `/sys/kernel/btf/vmlinux` or a `vmlinux` ELF image.
+    Path(PathBuf),
+}
+
+/// Generate the contents of a `vmlinux.h` header from `btf`, equivalent
+/// to what `bpftool btf dump file <BTF> format c` produces.
+pub fn gen_vmlinux_header(btf: &VmlinuxBtf) -> Result<String> {
+    let btf = match btf {
+        VmlinuxBtf::Kernel => Btf::from_vmlinux().context("failed to load kernel BTF")?,
+        VmlinuxBtf::Path(path) => Btf::from_path(path)
+            .with_context(|| format!("failed to load BTF from `{}`", path.display()))?,
+    };
+    let types = btf.dump_c().context("failed to dump BTF as C")?;
+
+    let header = format!(
+        r#"#ifndef __VMLINUX_H__
+#define __VMLINUX_H__
+
+#ifndef BPF_NO_PRESERVE_ACCESS_INDEX
+#pragma clang attribute push (__attribute__((preserve_access_index)), apply_to = record)
+#endif
+
+{types}
+#ifndef BPF_NO_PRESERVE_ACCESS_INDEX
+#pragma clang attribute pop
+#endif
+
+#endif /* __VMLINUX_H__ */
+"#
+    );
+    Ok(header)
+}
+
+/// Strip the "flavor" suffix (`___<flavor>`) from a type name, as done
+/// by libbpf when matching local types against target ones.
+fn essential_name(name: &str) -> &str {
+    match name.rfind("___") {
+        Some(idx) if idx > 0 => &name[..idx],
+        _ => name,
+    }
+}
+
+/// Check whether a local and a target type are of compatible kinds for
+/// the purpose of CO-RE relocations.
+fn is_kind_compatible(local: BtfKind, target: BtfKind) -> bool {
+    match (local, target) {
+        (BtfKind::Enum | BtfKind::Enum64, BtfKind::Enum | BtfKind::Enum64) => true,
+        (local, target) => local == target,
+    }
+}
+
+/// The members of a composite type to keep.
+#[derive(Clone, Debug)]
+enum Members {
+    All,
+    Some(BTreeSet<usize>),
+}
+
+/// A minimizer of (target) BTF, keeping only the types and members that
+/// are required for the CO-RE relocations of a set of BPF objects.
+struct Minimizer<'btf> {
+    target: &'btf Btf<'static>,
+    /// An index of the target's types by their name.
+    by_name: HashMap<&'btf OsStr, Vec<TypeId>>,
+    /// The types to keep, along with the members to keep, for
+    /// composite types.
+    marked: BTreeMap<TypeId, Members>,
+}
+
+impl<'btf> Minimizer<'btf> {
+    fn new(target: &'btf Btf<'static>) -> Self {
+        let mut by_name = HashMap::<_, Vec<_>>::new();
+        for id in 1..target.len() as u32 {
+            let id = TypeId::from(id);
+            if let Some(name) = target
+                .type_by_id::<BtfType<'_>>(id)
+                .and_then(|ty| ty.name())
+            {
+                let () = by_name.entry(name).or_default().push(id);
+            }
+        }
+
+        Self {
+            target,
+            by_name,
+            marked: BTreeMap::new(),
+        }
+    }
+
+    fn type_by_id(&self, id: TypeId) -> Result<BtfType<'btf>> {
+        self.target
+            .type_by_id::<BtfType<'_>>(id)
+            .ok_or_else(|| anyhow!("failed to find target BTF type with ID {id}"))
+    }
+
+    /// Find the target types a local type may be relocated against.
+    fn candidates(&self, local: &BtfType<'_>) -> Vec<BtfType<'btf>> {
+        let name = match local.name().and_then(OsStr::to_str) {
+            Some(name) => essential_name(name),
+            None => return Vec::new(),
+        };
+
+        self.by_name
+            .get(OsStr::new(name))
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.target.type_by_id::<BtfType<'_>>(*id))
+            .filter(|ty| is_kind_compatible(local.kind(), ty.kind()))
+            .collect()
+    }
+
+    /// Mark a type as required. Types referenced by it are marked as
+    /// well, with the exception of pointed-to types and members of
+    /// composite types.
+    fn mark_type(&mut self, ty: BtfType<'btf>) -> Result<()> {
+        if self.marked.contains_key(&ty.type_id()) {
+            return Ok(());
+        }
+        let _prev = self
+            .marked
+            .insert(ty.type_id(), Members::Some(BTreeSet::new()));
+
+        match ty.kind() {
+            BtfKind::Typedef
+            | BtfKind::Volatile
+            | BtfKind::Const
+            | BtfKind::Restrict
+            | BtfKind::TypeTag => {
+                if let Some(next) = ty.next_type() {
+                    let () = self.mark_type(next)?;
+                }
+            }
+            BtfKind::Array => {
+                let array = types::Array::try_from(ty).unwrap();
+                let () = self.mark_type(array.contained_type())?;
+                let () = self.mark_type(self.type_by_id(array.index_ty())?)?;
+            }
+            BtfKind::Void
+            | BtfKind::Int
+            | BtfKind::Float
+            | BtfKind::Ptr
+            | BtfKind::Struct
+            | BtfKind::Union
+            | BtfKind::Enum
+            | BtfKind::Enum64
+            | BtfKind::Fwd => (),
+            kind => bail!(
+                "unexpected BTF type kind {kind:?} referenced by CO-RE relocation (type ID {})",
+                ty.type_id()
+            ),
+        }
+        Ok(())
+    }
+
+    /// Mark the member with index `idx` of the composite type `comp` as
+    /// required, returning the member's type.
+    fn mark_member(&mut self, comp: TypeId, idx: usize) -> Result<BtfType<'btf>> {
+        let ty = self.type_by_id(comp)?;
+        let () = self.mark_type(ty)?;
+        if let Some(Members::Some(members)) = self.marked.get_mut(&comp) {
+            let _new = members.insert(idx);
+        }
+
+        let member = types::Composite::try_from(ty)
+            .ok()
+            .and_then(|comp| comp.get(idx))
+            .ok_or_else(|| anyhow!("member {idx} of type {comp} not found"))?;
+        let member_ty = self.type_by_id(member.ty)?;
+        let () = self.mark_type(member_ty)?;
+        Ok(member_ty)
+    }
+
+    /// Mark a type and, if it is a composite, all of its members as
+    /// required.
+    fn mark_type_fully(&mut self, ty: BtfType<'btf>) -> Result<()> {
+        let () = self.mark_type(ty)?;
+        if let Ok(comp) = types::Composite::try_from(ty.skip_mods_and_typedefs()) {
+            let () = self.mark_type(*comp)?;
+            let _prev = self.marked.insert(comp.type_id(), Members::All);
+            for idx in 0..comp.len() {
+                let member = comp.get(idx).unwrap();
+                let () = self.mark_type(self.type_by_id(member.ty)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the member called `name` in `comp`, descending into
+    /// anonymous members, and return the path to it as pairs of
+    /// composite types and member indices.
+    fn find_member(
+        &self,
+        comp: &types::Composite<'btf>,
+        name: &OsStr,
+    ) -> Option<Vec<(TypeId, usize)>> {
+        for idx in 0..comp.len() {
+            let member = comp.get(idx)?;
+            match member.name {
+                Some(member_name) if member_name == name => {
+                    return Some(vec![(comp.type_id(), idx)]);
+                }
+                Some(_) => (),
+                None => {
+                    let ty = self.target.type_by_id::<BtfType<'_>>(member.ty)?;
+                    if let Ok(inner) = types::Composite::try_from(ty.skip_mods_and_typedefs()) {
+                        if let Some(mut path) = self.find_member(&inner, name) {
+                            let () = path.insert(0, (comp.type_id(), idx));
+                            return Some(path);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Mark the members of `target` accessed by the local access
+    /// specification `access`, starting at the local type `local`.
+    ///
+    /// Returns `false` if the access could not be matched against the
+    /// target type.
+    fn mark_access(
+        &mut self,
+        local_btf: &Btf<'_>,
+        local: BtfType<'_>,
+        target: BtfType<'btf>,
+        access: &[usize],
+    ) -> Result<bool> {
+        let mut local = local.skip_mods_and_typedefs();
+        let mut target = target.skip_mods_and_typedefs();
+
+        for &idx in access {
+            if let Ok(local_comp) = types::Composite::try_from(local) {
+                let member = local_comp
+                    .get(idx)
+                    .ok_or_else(|| anyhow!("local member index {idx} out of bounds"))?;
+                let member_ty = local_btf
+                    .type_by_id::<BtfType<'_>>(member.ty)
+                    .ok_or_else(|| anyhow!("failed to find local type {}", member.ty))?;
+                local = member_ty.skip_mods_and_typedefs();
+
+                // Anonymous local members are matched implicitly, as part
+                // of finding the next named member.
+                let name = match member.name {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                let target_comp = match types::Composite::try_from(target) {
+                    Ok(comp) => comp,
+                    Err(_) => return Ok(false),
+                };
+                let path = match self.find_member(&target_comp, name) {
+                    Some(path) => path,
+                    None => return Ok(false),
+                };
+
+                for (comp, idx) in path {
+                    target = self.mark_member(comp, idx)?.skip_mods_and_typedefs();
+                }
+            } else if let Ok(local_array) = types::Array::try_from(local) {
+                let target_array = match types::Array::try_from(target) {
+                    Ok(array) => array,
+                    Err(_) => return Ok(false),
+                };
+                let () = self.mark_type(*target_array)?;
+                local = local_array.contained_type().skip_mods_and_typedefs();
+                target = target_array.contained_type().skip_mods_and_typedefs();
+            } else {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Record the target types required for satisfying `relo`.
+    fn add_relocation(&mut self, local_btf: &Btf<'_>, relo: &CoreRelocation) -> Result<()> {
+        let local = local_btf
+            .type_by_id::<BtfType<'_>>(relo.type_id)
+            .ok_or_else(|| anyhow!("failed to find local type {}", relo.type_id))?;
+
+        match relo.kind {
+            CoreRelocationKind::FieldByteOffset
+            | CoreRelocationKind::FieldByteSize
+            | CoreRelocationKind::FieldExists
+            | CoreRelocationKind::FieldSigned
+            | CoreRelocationKind::FieldLshiftU64
+            | CoreRelocationKind::FieldRshiftU64 => {
+                let access = relo
+                    .access
+                    .split(':')
+                    .map(str::parse::<usize>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("invalid CO-RE access string `{}`", relo.access))?;
+
+                for candidate in self.candidates(&local) {
+                    // Candidates the access cannot be matched against are
+                    // not kept.
+                    let marked = self.marked.clone();
+                    let () = self.mark_type(candidate)?;
+                    // The first access index merely indexes into an array of
+                    // the root type.
+                    let access = access.get(1..).unwrap_or_default();
+                    if !self.mark_access(local_btf, local, candidate, access)? {
+                        self.marked = marked;
+                    }
+                }
+            }
+            CoreRelocationKind::TypeExists
+            | CoreRelocationKind::TypeSize
+            | CoreRelocationKind::TypeMatches
+            | CoreRelocationKind::TypeIdTarget
+            | CoreRelocationKind::EnumvalExists
+            | CoreRelocationKind::EnumvalValue => {
+                for candidate in self.candidates(&local) {
+                    let () = self.mark_type_fully(candidate)?;
+                }
+            }
+            CoreRelocationKind::TypeIdLocal | CoreRelocationKind::Unknown(_) => (),
+        }
+        Ok(())
+    }
+
+    /// Construct the minimized BTF out of all marked types.
+    fn build(&self) -> Result<Btf<'static>> {
+        // Marked types get renumbered consecutively, in their original
+        // order. References to unmarked types (only possible for pointers)
+        // are turned into references to `void`.
+        let ids = self
+            .marked
+            .keys()
+            .filter(|id| u32::from(**id) != 0)
+            .enumerate()
+            .map(|(idx, id)| (*id, TypeId::from(idx as u32 + 1)))
+            .collect::<HashMap<_, _>>();
+        let resolve = |id: TypeId| ids.get(&id).copied().unwrap_or(TypeId::from(0));
+
+        let mut builder = BtfBuilder::new()?;
+        for (id, members) in &self.marked {
+            if u32::from(*id) == 0 {
+                continue;
+            }
+
+            let ty = self.type_by_id(*id)?;
+            let name = ty
+                .name()
+                .map(|name| {
+                    name.to_str()
+                        .ok_or_else(|| anyhow!("type name {name:?} is not valid unicode"))
+                })
+                .transpose()?;
+            let named = || name.ok_or_else(|| anyhow!("type {id} is unexpectedly unnamed"));
+
+            let new_id = match ty.kind() {
+                BtfKind::Int => {
+                    let int = types::Int::try_from(ty).unwrap();
+                    builder.add_int(named()?, int.size(), int.encoding)?
+                }
+                BtfKind::Float => {
+                    let float = types::Float::try_from(ty).unwrap();
+                    builder.add_float(named()?, float.size())?
+                }
+                BtfKind::Ptr => {
+                    let ptr = types::Ptr::try_from(ty).unwrap();
+                    builder.add_ptr(resolve(ptr.referenced_type_id()))?
+                }
+                BtfKind::Array => {
+                    let array = types::Array::try_from(ty).unwrap();
+                    builder.add_array(
+                        resolve(array.index_ty()),
+                        resolve(array.ty()),
+                        array.capacity() as u32,
+                    )?
+                }
+                BtfKind::Struct | BtfKind::Union => {
+                    let comp = types::Composite::try_from(ty).unwrap();
+                    let new_id = if comp.is_struct {
+                        builder.add_struct(name, comp.size() as u32)?
+                    } else {
+                        builder.add_union(name, comp.size() as u32)?
+                    };
+
+                    let indices = match members {
+                        Members::All => (0..comp.len()).collect(),
+                        Members::Some(indices) => indices.clone(),
+                    };
+                    for idx in indices {
+                        // SANITY: Only valid member indices are ever marked.
+                        let member = comp.get(idx).unwrap();
+                        let (offset, size) = match member.attr {
+                            types::MemberAttr::Normal { offset } => (offset, 0),
+                            types::MemberAttr::BitField { size, offset } => (offset, size.into()),
+                        };
+                        let member_name = member
+                            .name
+                            .map(|name| {
+                                name.to_str().ok_or_else(|| {
+                                    anyhow!("member name {name:?} is not valid unicode")
+                                })
+                            })
+                            .transpose()?;
+                        let () =
+                            builder.add_field(member_name, resolve(member.ty), offset, size)?;
+                    }
+                    new_id
+                }
+                BtfKind::Enum => {
+                    let enum_ = types::Enum::try_from(ty).unwrap();
+                    let new_id = builder.add_enum(name, enum_.size() as u32)?;
+                    for idx in 0..enum_.len() {
+                        let value = enum_.get(idx).unwrap();
+                        let value_name = value
+                            .name
+                            .and_then(OsStr::to_str)
+                            .ok_or_else(|| anyhow!("enum value of type {id} has invalid name"))?;
+                        let v = if enum_.is_signed() {
+                            i64::from(value.value)
+                        } else {
+                            i64::from(value.value as u32)
+                        };
+                        let () = builder.add_enum_value(value_name, v)?;
+                    }
+                    new_id
+                }
+                BtfKind::Enum64 => {
+                    let enum_ = types::Enum64::try_from(ty).unwrap();
+                    let new_id =
+                        builder.add_enum64(name, enum_.size() as u32, enum_.is_signed())?;
+                    for idx in 0..enum_.len() {
+                        let value = enum_.get(idx).unwrap();
+                        let value_name = value
+                            .name
+                            .and_then(OsStr::to_str)
+                            .ok_or_else(|| anyhow!("enum value of type {id} has invalid name"))?;
+                        let () = builder.add_enum64_value(value_name, value.value)?;
+                    }
+                    new_id
+                }
+                BtfKind::Fwd => {
+                    let fwd = types::Fwd::try_from(ty).unwrap();
+                    builder.add_fwd(named()?, fwd.kind())?
+                }
+                BtfKind::Typedef => {
+                    let typedef = types::Typedef::try_from(ty).unwrap();
+                    builder.add_typedef(named()?, resolve(typedef.referenced_type_id()))?
+                }
+                BtfKind::Volatile => {
+                    let volatile = types::Volatile::try_from(ty).unwrap();
+                    builder.add_volatile(resolve(volatile.referenced_type_id()))?
+                }
+                BtfKind::Const => {
+                    let const_ = types::Const::try_from(ty).unwrap();
+                    builder.add_const(resolve(const_.referenced_type_id()))?
+                }
+                BtfKind::Restrict => {
+                    let restrict = types::Restrict::try_from(ty).unwrap();
+                    builder.add_restrict(resolve(restrict.referenced_type_id()))?
+                }
+                BtfKind::TypeTag => {
+                    let tag = types::TypeTag::try_from(ty).unwrap();
+                    builder.add_type_tag(named()?, resolve(tag.referenced_type_id()))?
+                }
+                kind => bail!("unexpected BTF type kind {kind:?} (type ID {id})"),
+            };
+
+            if new_id != resolve(*id) {
+                bail!("BTF type {id} was assigned unexpected ID {new_id}");
+            }
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Generate minimized BTF for `target`, containing only the types
+/// required for the CO-RE relocations of `objects`.
+fn min_core_btf(objects: &[PathBuf], target: &Path) -> Result<Vec<u8>> {
+    let target_btf = Btf::from_path(target)
+        .with_context(|| format!("failed to load target BTF from `{}`", target.display()))?;
+    let mut minimizer = Minimizer::new(&target_btf);
+
+    for object in objects {
+        let local_btf = Btf::from_path(object)
+            .with_context(|| format!("failed to load BTF from `{}`", object.display()))?;
+        let inspector = ObjectInspector::from_path(object)
+            .with_context(|| format!("failed to inspect `{}`", object.display()))?;
+
+        for relo in inspector.core_relocations() {
+            let () = minimizer
+                .add_relocation(&local_btf, relo)
+                .with_context(|| {
+                    format!(
+                        "failed to process CO-RE relocation of `{}` in `{}`",
+                        relo.access,
+                        object.display()
+                    )
+                })?;
+        }
+    }
+
+    let btf = minimizer.build()?;
+    let raw = btf.to_raw().context("failed to serialize minimized BTF")?;
+    Ok(raw)
+}
+
+/// Generate minimized BTF for each of the `targets`, containing only the
+/// types required for the CO-RE relocations of `objects`, and store it
+/// under the target's file name in `output_dir`.
+pub fn min_core(
+    debug: bool,
+    objects: &[PathBuf],
+    targets: &[PathBuf],
+    output_dir: &Path,
+) -> Result<()> {
+    if objects.is_empty() {
+        bail!("no BPF object files provided");
+    }
+
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create `{}`", output_dir.display()))?;
+
+    for target in targets {
+        let file_name = target
+            .file_name()
+            .ok_or_else(|| anyhow!("target BTF path `{}` has no file name", target.display()))?;
+        let output = output_dir.join(file_name);
+        if debug {
+            println!(
+                "Generating minimized BTF for {} in {}",
+                target.display(),
+                output.display()
+            );
+        }
+
+        let raw = min_core_btf(objects, target)?;
+        let () = fs::write(&output, raw)
+            .with_context(|| format!("failed to write `{}`", output.display()))?;
+    }
+    Ok(())
+}
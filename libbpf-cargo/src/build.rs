@@ -1,23 +1,59 @@
 use std::collections::HashSet;
+use std::env;
 use std::env::consts::ARCH;
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::fs;
+use std::iter;
+use std::num::NonZeroUsize;
+use std::os::unix::ffi::OsStrExt as _;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
+use libbpf_rs::ObjectInspector;
 use regex::Regex;
 use semver::Version;
+use serde::Serialize;
 use tempfile::tempdir;
 
+use crate::btf;
+use crate::btf::VmlinuxBtf;
 use crate::metadata;
 use crate::metadata::UnprocessedObj;
 
+/// Options controlling the compilation of BPF objects, beyond the
+/// compiler and its arguments.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BuildOpts {
+    /// The BPF target to compile for, i.e., `bpfel` or `bpfeb`.
+    pub bpf_target: Option<String>,
+    /// The BPF CPU, i.e., the instruction set level, to compile for.
+    pub bpf_cpu: Option<String>,
+    /// The BTF to generate a `vmlinux.h` from, if any.
+    pub vmlinux: Option<VmlinuxBtf>,
+    /// Build objects reproducibly.
+    pub reproducible: bool,
+}
+
+impl BuildOpts {
+    /// Append the compiler arguments for the configured BPF target and
+    /// CPU to `clang_args`.
+    fn clang_args(&self, clang_args: Vec<OsString>) -> Vec<OsString> {
+        with_bpf_cpu(
+            with_bpf_target(clang_args, self.bpf_target.as_deref()),
+            self.bpf_cpu.as_deref(),
+        )
+    }
+}
+
 fn check_progs(objs: &[UnprocessedObj]) -> Result<()> {
     let mut set = HashSet::with_capacity(objs.len());
     for obj in objs {
@@ -37,8 +73,69 @@ fn check_progs(objs: &[UnprocessedObj]) -> Result<()> {
     Ok(())
 }
 
-fn extract_version(output: &str) -> Result<&str> {
-    let re = Regex::new(r"clang\s+version\s+(?P<version_str>\d+\.\d+\.\d+)")?;
+/// The kind of compiler used for building BPF objects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CompilerKind {
+    Clang,
+    /// GCC's BPF backend, e.g., `bpf-gcc` or `bpf-unknown-none-gcc`.
+    Gcc,
+}
+
+impl CompilerKind {
+    /// Determine the kind of compiler from the output of its `--version`
+    /// invocation.
+    fn from_version_output(output: &str) -> Self {
+        if output.contains("Free Software Foundation") {
+            Self::Gcc
+        } else {
+            Self::Clang
+        }
+    }
+
+    /// The minimum supported version of the compiler.
+    fn min_version(&self) -> Version {
+        match self {
+            Self::Clang => Version::new(10, 0, 0),
+            // GCC 13 is the first release capable of compiling the BPF
+            // programs libbpf expects, including BTF and CO-RE support.
+            Self::Gcc => Version::new(13, 1, 0),
+        }
+    }
+}
+
+/// A compiler usable for building BPF objects.
+struct Compiler {
+    path: PathBuf,
+    kind: CompilerKind,
+    /// The compiler's version, `None` if version checks are skipped.
+    version: Option<Version>,
+}
+
+impl Compiler {
+    /// Check that the compiler is able to compile `source` with
+    /// `clang_args`.
+    ///
+    /// Unless version checks are skipped, `source` is preprocessed to
+    /// detect the use of features the compiler's version lacks support
+    /// for, so the headers it includes have to be available at this
+    /// point.
+    fn check(&self, source: &Path, clang_args: &[OsString]) -> Result<()> {
+        let () = check_cpu(self.kind, clang_args)?;
+        if let Some(version) = &self.version {
+            let preprocessed = preprocess(&self.path, self.kind, source, clang_args)?;
+            let () = check_features(self.kind, version, source, &preprocessed, clang_args)?;
+        }
+        Ok(())
+    }
+}
+
+fn extract_version(output: &str, kind: CompilerKind) -> Result<&str> {
+    let re = match kind {
+        CompilerKind::Clang => Regex::new(r"clang\s+version\s+(?P<version_str>\d+\.\d+\.\d+)")?,
+        // GCC reports its version following the (parenthesized) package
+        // name, e.g., `bpf-unknown-none-gcc (GCC) 13.2.0`.
+        CompilerKind::Gcc => Regex::new(r"\)\s+(?P<version_str>\d+\.\d+\.\d+)")?,
+    };
     let captures = re
         .captures(output)
         .ok_or_else(|| anyhow!("Failed to run regex on version string"))?;
@@ -79,7 +176,35 @@ fn extract_libbpf_headers_to_disk(_target_dir: &Path) -> Result<Option<PathBuf>>
     Ok(None)
 }
 
-fn check_clang(debug: bool, clang: &Path, skip_version_checks: bool) -> Result<()> {
+/// Generate a `vmlinux.h` header from `btf` in `dir`, creating the
+/// directory if necessary.
+///
+/// An existing header is only overwritten if its contents changed.
+fn write_vmlinux_header(btf: &VmlinuxBtf, dir: &Path) -> Result<()> {
+    let header = btf::gen_vmlinux_header(btf)?;
+    let path = dir.join("vmlinux.h");
+    if fs::read_to_string(&path).ok().as_deref() == Some(header.as_str()) {
+        return Ok(());
+    }
+
+    let () = fs::create_dir_all(dir)?;
+    let () =
+        fs::write(&path, header).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Prepend `dir` to the include path of `clang_args`, so that headers
+/// contained in it take precedence over those in user provided
+/// directories.
+fn prepend_include_dir(clang_args: &mut Vec<OsString>, dir: &Path) {
+    let () = clang_args.insert(0, dir.as_os_str().to_os_string());
+    let () = clang_args.insert(0, OsString::from("-I"));
+}
+
+/// Check that `clang` is usable for compiling BPF programs, returning
+/// the kind of compiler it is along with its version, unless version
+/// checks are skipped.
+fn check_clang(debug: bool, clang: &Path, skip_version_checks: bool) -> Result<Compiler> {
     let output = Command::new(clang.as_os_str())
         .arg("--version")
         .output()
@@ -89,8 +214,14 @@ fn check_clang(debug: bool, clang: &Path, skip_version_checks: bool) -> Result<(
         bail!("Failed to execute clang binary");
     }
 
+    let output = String::from_utf8_lossy(&output.stdout);
+    let kind = CompilerKind::from_version_output(&output);
     if skip_version_checks {
-        return Ok(());
+        return Ok(Compiler {
+            path: clang.to_path_buf(),
+            kind,
+            version: None,
+        });
     }
 
     // Example output:
@@ -100,20 +231,160 @@ fn check_clang(debug: bool, clang: &Path, skip_version_checks: bool) -> Result<(
     //     Thread model: posix
     //     InstalledDir: /bin
     //
-    let output = String::from_utf8_lossy(&output.stdout);
-    let version_str = extract_version(&output)?;
+    // or, for GCC:
+    //
+    //     bpf-unknown-none-gcc (GCC) 13.2.0
+    //     Copyright (C) 2023 Free Software Foundation, Inc.
+    //
+    let version_str = extract_version(&output, kind)?;
     let version = Version::parse(version_str)?;
     if debug {
         println!("{} is version {}", clang.display(), version);
     }
 
-    if version < Version::parse("10.0.0").unwrap() {
+    if version < kind.min_version() {
         bail!(
             "version {} is too old. Use --skip-clang-version-checks to skip version check",
             version
         );
     }
 
+    Ok(Compiler {
+        path: clang.to_path_buf(),
+        kind,
+        version: Some(version),
+    })
+}
+
+/// Identifiers indicating the use of CO-RE type and enum value
+/// relocations in preprocessed source code. Helper macros such as
+/// `bpf_core_type_exists` expand to these.
+const TYPE_RELOCATION_IDENTS: [&str; 2] = [
+    "__builtin_preserve_type_info",
+    "__builtin_preserve_enum_value",
+];
+
+/// The BPF CPUs supported by GCC's BPF backend. Contrary to `clang`, it
+/// knows neither `generic` nor `probe`.
+const GCC_BPF_CPUS: [&str; 4] = ["v1", "v2", "v3", "v4"];
+
+/// Retrieve the BPF CPU requested via `-mcpu` in `clang_args`, if any.
+fn requested_cpu(clang_args: &[OsString]) -> Option<&str> {
+    clang_args
+        .iter()
+        .rev()
+        .find_map(|arg| arg.to_str()?.strip_prefix("-mcpu="))
+}
+
+/// Check that a compiler of kind `kind` supports the BPF CPU requested
+/// in `clang_args`, if any.
+fn check_cpu(kind: CompilerKind, clang_args: &[OsString]) -> Result<()> {
+    match (kind, requested_cpu(clang_args)) {
+        (CompilerKind::Gcc, Some(cpu)) if !GCC_BPF_CPUS.contains(&cpu) => bail!(
+            "GCC does not support BPF CPU `{cpu}`; supported are: {}",
+            GCC_BPF_CPUS.join(", ")
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Run the preprocessor over `source`, returning the result.
+fn preprocess(
+    clang: &Path,
+    kind: CompilerKind,
+    source: &Path,
+    clang_args: &[OsString],
+) -> Result<String> {
+    let mut args = clang_args.to_vec();
+    let () = args.extend(target_args(clang_args));
+    let () = args.extend(["-E", "-P"].map(OsString::from));
+    let () = args.push(source.as_os_str().to_os_string());
+    let args = match kind {
+        CompilerKind::Clang => args,
+        CompilerKind::Gcc => gcc_args(args),
+    };
+
+    let mut cmd = Command::new(clang.as_os_str());
+    cmd.args(args);
+    let output = cmd.output().context("Failed to execute clang")?;
+    if !output.status.success() {
+        let err = Err(anyhow!(String::from_utf8_lossy(&output.stderr).to_string()))
+            .with_context(|| {
+                format!(
+                    "Command `{}` failed ({})",
+                    format_command(&cmd),
+                    output.status
+                )
+            })
+            .with_context(|| format!("Failed to preprocess {}", source.display()));
+        return err;
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// A feature requested for the compilation of a BPF program along with
+/// the minimum versions of `clang` and GCC supporting it.
+struct Feature {
+    description: &'static str,
+    clang: Version,
+    gcc: Version,
+}
+
+/// Retrieve the features requested for compiling the source code
+/// `preprocessed` (as output by the preprocessor, so that the contents
+/// of included headers are covered) with `clang_args` that older compiler
+/// versions lack support for.
+///
+/// Such compilers may silently produce objects that fail to load, e.g.,
+/// because they do not recognize the requested BPF ISA level.
+fn requested_features(preprocessed: &str, clang_args: &[OsString]) -> Vec<Feature> {
+    let mut features = Vec::new();
+
+    if requested_cpu(clang_args) == Some("v4") {
+        let () = features.push(Feature {
+            description: "BPF ISA v4 (`-mcpu=v4`)",
+            clang: Version::new(18, 0, 0),
+            gcc: Version::new(14, 1, 0),
+        });
+    }
+
+    if TYPE_RELOCATION_IDENTS
+        .iter()
+        .any(|ident| preprocessed.contains(ident))
+    {
+        let () = features.push(Feature {
+            description: "CO-RE type and enum value relocations",
+            clang: Version::new(12, 0, 0),
+            gcc: Version::new(13, 1, 0),
+        });
+    }
+    features
+}
+
+/// Check that version `version` of the compiler of kind `kind` supports
+/// all features requested for compiling `source`, preprocessed into
+/// `preprocessed`, with `clang_args`.
+fn check_features(
+    kind: CompilerKind,
+    version: &Version,
+    source: &Path,
+    preprocessed: &str,
+    clang_args: &[OsString],
+) -> Result<()> {
+    for feature in requested_features(preprocessed, clang_args) {
+        let (compiler, min_version) = match kind {
+            CompilerKind::Clang => ("clang", &feature.clang),
+            CompilerKind::Gcc => ("GCC", &feature.gcc),
+        };
+
+        if version < min_version {
+            bail!(
+                "{} uses {}, which requires {compiler} {min_version} or later, but found version {version}. Use --skip-clang-version-checks to skip version check",
+                source.display(),
+                feature.description,
+            );
+        }
+    }
     Ok(())
 }
 
@@ -129,9 +400,14 @@ fn strip_dwarf_info(file: &Path) -> Result<()> {
 
     let mut linker =
         libbpf_rs::Linker::new(file).context("Failed to instantiate libbpf object file linker")?;
-    linker
-        .add_file(temp_file)
-        .context("Failed to add object file to BPF linker")?;
+    linker.add_file(&temp_file).with_context(|| {
+        let ident = fs::read(&temp_file).unwrap_or_default();
+        if is_foreign_endian(&ident) {
+            "Failed to add object file of foreign endianness to BPF linker (requires libbpf 1.5 or later)"
+        } else {
+            "Failed to add object file to BPF linker"
+        }
+    })?;
     linker.link().context("Failed to link object file")?;
     Ok(())
 }
@@ -159,51 +435,347 @@ fn format_command(command: &Command) -> String {
     concat_command(prog, args).to_string_lossy().to_string()
 }
 
+/// Parse a make style dependency file as emitted by `clang -MD` into the
+/// list of prerequisites it contains.
+///
+/// Such a file has the form:
+///
+///   runqslower.bpf.o: src/bpf/runqslower.bpf.c vmlinux.h \
+///     src/bpf/runqslower.h
+pub(crate) fn parse_depfile(contents: &str) -> Vec<PathBuf> {
+    let contents = contents.replace("\\\r\n", " ").replace("\\\n", " ");
+    // Skip over the target (`<target>: `).
+    let prereqs = contents
+        .split_once(": ")
+        .map(|(_target, prereqs)| prereqs)
+        .unwrap_or_default();
+
+    let mut deps = Vec::new();
+    let mut dep = String::new();
+    let mut chars = prereqs.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(' ') => dep.push(' '),
+                Some(c) => {
+                    dep.push('\\');
+                    dep.push(c);
+                }
+                None => dep.push('\\'),
+            },
+            c if c.is_whitespace() => {
+                if !dep.is_empty() {
+                    let () = deps.push(PathBuf::from(&dep));
+                    let () = dep.clear();
+                }
+            }
+            c => dep.push(c),
+        }
+    }
+
+    if !dep.is_empty() {
+        let () = deps.push(PathBuf::from(dep));
+    }
+    deps
+}
+
+/// Arguments to `clang` pinning the date and time related builtin macros
+/// to the Unix epoch.
+const REPRODUCIBLE_ARGS: [&str; 4] = [
+    "-Wno-builtin-macro-redefined",
+    "-D__DATE__=\"Jan  1 1970\"",
+    "-D__TIME__=\"00:00:00\"",
+    "-D__TIMESTAMP__=\"Thu Jan  1 00:00:00 1970\"",
+];
+
+/// Determine the path prefixes to remap for a reproducible build of
+/// `source`: the current working directory, `dir`, which gets replaced
+/// with `replacement`, and the directory containing `source`.
+///
+/// More specific prefixes come last, as later mappings take precedence
+/// with recent versions of `clang`.
+fn reproducible_prefix_map(
+    source: &Path,
+    dir: &Path,
+    replacement: &'static str,
+) -> Result<Vec<(PathBuf, &'static str)>> {
+    let cwd = env::current_dir().context("Failed to retrieve current directory")?;
+    let source_dir = cwd.join(source.parent().unwrap_or_else(|| Path::new("")));
+    Ok(vec![
+        (cwd, "."),
+        (dir.to_path_buf(), replacement),
+        (source_dir, "."),
+    ])
+}
+
+/// Retrieve the arguments to pass to `clang` for reproducible builds.
+///
+/// Besides pinning timestamps, paths below each of the prefixes in
+/// `prefix_map` are replaced with the associated replacement in the
+/// output (`-ffile-prefix-map` covers debug information, from which BTF
+/// line information is derived, as well as macros such as `__FILE__`).
+fn reproducible_args(prefix_map: &[(PathBuf, &str)]) -> Vec<OsString> {
+    let mut args = REPRODUCIBLE_ARGS.map(OsString::from).to_vec();
+    for (prefix, replacement) in prefix_map {
+        let mut arg = OsString::from("-ffile-prefix-map=");
+        let () = arg.push(prefix);
+        let () = arg.push("=");
+        let () = arg.push(replacement);
+        let () = args.push(arg);
+    }
+    args
+}
+
+/// Verify that the object at `path` is free of information specific to
+/// the build environment, i.e., that the linker stripped all DWARF debug
+/// information and that none of the prefixes in `prefix_map` made it into
+/// the object.
+fn verify_reproducible(path: &Path, prefix_map: &[(PathBuf, &str)]) -> Result<()> {
+    let inspector = ObjectInspector::from_path(path)
+        .with_context(|| format!("Failed to inspect {}", path.display()))?;
+    if let Some(section) = inspector
+        .section_names()
+        .iter()
+        .find(|name| name.starts_with(".debug"))
+    {
+        bail!(
+            "{} contains unstable DWARF section `{section}`",
+            path.display()
+        );
+    }
+
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if let Some(prefix) = find_prefix(&data, prefix_map) {
+        bail!(
+            "{} contains build specific path `{}`",
+            path.display(),
+            prefix.display()
+        );
+    }
+    Ok(())
+}
+
+/// Find the first of the prefixes in `prefix_map` contained in `data`.
+///
+/// The root directory is ignored: it is not specific to the build
+/// environment and any absolute path, e.g., of a system header, would
+/// match it.
+fn find_prefix<'map>(data: &[u8], prefix_map: &'map [(PathBuf, &str)]) -> Option<&'map Path> {
+    prefix_map
+        .iter()
+        .map(|(prefix, _replacement)| prefix.as_path())
+        .filter(|prefix| prefix.parent().is_some())
+        .find(|prefix| {
+            let prefix = prefix.to_string_lossy();
+            data.windows(prefix.len())
+                .any(|window| window == prefix.as_bytes())
+        })
+}
+
+/// Check whether `clang_args` explicitly specify a compilation target.
+fn has_target(clang_args: &[OsString]) -> bool {
+    clang_args.iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        arg == "-target" || arg == "--target" || arg.starts_with("--target=")
+    })
+}
+
+/// Append the arguments for compiling for the BPF target `bpf_target`,
+/// e.g., `bpfeb`, to `clang_args`, if provided.
+fn with_bpf_target(mut clang_args: Vec<OsString>, bpf_target: Option<&str>) -> Vec<OsString> {
+    if let Some(target) = bpf_target {
+        let () = clang_args.push(OsString::from("-target"));
+        let () = clang_args.push(OsString::from(target));
+    }
+    clang_args
+}
+
+/// Append the argument for compiling for the BPF CPU (i.e., ISA level)
+/// `bpf_cpu`, e.g., `v3`, to `clang_args`, if provided.
+fn with_bpf_cpu(mut clang_args: Vec<OsString>, bpf_cpu: Option<&str>) -> Vec<OsString> {
+    if let Some(cpu) = bpf_cpu {
+        let () = clang_args.push(OsString::from(format!("-mcpu={cpu}")));
+    }
+    clang_args
+}
+
+/// Check whether the ELF file starting with `ident` is of a different
+/// endianness than the host.
+///
+/// libbpf versions before 1.5 can neither link nor open such objects.
+pub(crate) fn is_foreign_endian(ident: &[u8]) -> bool {
+    // `EI_DATA` is one of `ELFDATA2LSB` (1) or `ELFDATA2MSB` (2).
+    match ident.get(5) {
+        Some(1) => cfg!(target_endian = "big"),
+        Some(2) => cfg!(target_endian = "little"),
+        _ => false,
+    }
+}
+
+/// Retrieve the arguments describing the compilation target to pass to
+/// `clang` in addition to `clang_args`, unless those already cover them.
+///
+/// We may end up being invoked by a build script, in which case the
+/// `CARGO_CFG_TARGET_*` variables describe the target being compiled for,
+/// which may differ from the host when cross-compiling. BPF objects have
+/// to be of the endianness of the system loading them, so we pick the BPF
+/// target accordingly.
+fn target_args(clang_args: &[OsString]) -> Vec<OsString> {
+    let mut args = Vec::new();
+
+    if !clang_args
+        .iter()
+        .any(|arg| arg.to_string_lossy().contains("__TARGET_ARCH_"))
+    {
+        let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_else(|_| ARCH.to_string());
+        let arch = match arch.as_str() {
+            "x86_64" => "x86",
+            "aarch64" => "arm64",
+            "powerpc64" => "powerpc",
+            "s390x" => "s390",
+            x => x,
+        };
+        let () = args.push(OsString::from(format!("-D__TARGET_ARCH_{arch}")));
+    }
+
+    if !has_target(clang_args) {
+        let target = match env::var("CARGO_CFG_TARGET_ENDIAN").as_deref() {
+            Ok("big") => "bpfeb",
+            Ok("little") => "bpfel",
+            _ if cfg!(target_endian = "big") => "bpfeb",
+            _ => "bpfel",
+        };
+        let () = args.push(OsString::from("-target"));
+        let () = args.push(OsString::from(target));
+    }
+    args
+}
+
+/// Translate the `clang` arguments `args` into their GCC equivalents.
+///
+/// GCC's BPF backend selects the endianness via `-mlittle-endian` and
+/// `-mbig-endian` instead of a target triple and only emits BTF when asked
+/// for via `-gbtf`. Other arguments, including `-mcpu`, are shared.
+fn gcc_args(args: Vec<OsString>) -> Vec<OsString> {
+    let endian_arg = |target: &str| match target {
+        "bpfeb" => Some(OsString::from("-mbig-endian")),
+        "bpfel" => Some(OsString::from("-mlittle-endian")),
+        _ => None,
+    };
+
+    let mut translated = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-target" | "--target") => {
+                if let Some(target) = args.next() {
+                    let () = translated.extend(endian_arg(&target.to_string_lossy()));
+                }
+            }
+            Some(arg) if arg.starts_with("--target=") => {
+                let () = translated.extend(endian_arg(&arg["--target=".len()..]));
+            }
+            Some("-g") => translated.push(OsString::from("-gbtf")),
+            _ => translated.push(arg),
+        }
+    }
+    translated
+}
+
+/// Retrieve the arguments to pass to `clang` for compiling `source` into
+/// `out`, excluding the ones for emitting a dependency file.
+fn compile_args(
+    source: &Path,
+    out: &Path,
+    clang_args: &[OsString],
+    kind: CompilerKind,
+) -> Vec<OsString> {
+    let mut args = clang_args.to_vec();
+    let () = args.extend(target_args(clang_args));
+    let () = args.extend(["-g", "-O2", "-c"].map(OsString::from));
+    let () = args.push(source.as_os_str().to_os_string());
+    let () = args.push(OsString::from("-o"));
+    let () = args.push(out.as_os_str().to_os_string());
+
+    match kind {
+        CompilerKind::Clang => args,
+        CompilerKind::Gcc => gcc_args(args),
+    }
+}
+
+/// An entry of a clang compilation database, as described at
+/// <https://clang.llvm.org/docs/JSONCompilationDatabase.html>.
+#[derive(Debug, Serialize)]
+struct CompileCommand {
+    directory: PathBuf,
+    arguments: Vec<String>,
+    file: PathBuf,
+    output: PathBuf,
+}
+
+/// Write a compilation database describing the compilation `jobs` to
+/// `path`, for consumption by `clangd` and similar tooling.
+fn write_compile_commands(
+    path: &Path,
+    clang: &Path,
+    kind: CompilerKind,
+    jobs: &[(&Path, PathBuf, Vec<OsString>)],
+) -> Result<()> {
+    let directory = env::current_dir().context("Failed to retrieve current directory")?;
+    let commands = jobs
+        .iter()
+        .map(|(source, out, clang_args)| {
+            let arguments = iter::once(clang.as_os_str().to_os_string())
+                .chain(compile_args(source, out, clang_args, kind))
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            CompileCommand {
+                directory: directory.clone(),
+                arguments,
+                file: source.to_path_buf(),
+                output: out.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let json = serde_json::to_string_pretty(&commands)
+        .context("Failed to serialize compilation database")?;
+    if let Some(dir) = path.parent() {
+        let () = fs::create_dir_all(dir)?;
+    }
+    let () =
+        fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
 /// We're essentially going to run:
 ///
 ///   clang -g -O2 -target bpf -c -D__TARGET_ARCH_$(ARCH) runqslower.bpf.c -o runqslower.bpf.o
 ///
 /// for each prog.
+///
+/// If `depfile` is provided, clang is asked to additionally write the list
+/// of files the compilation depends on to it.
 fn compile_one(
     debug: bool,
     source: &Path,
     out: &Path,
     clang: &Path,
+    kind: CompilerKind,
     clang_args: &[OsString],
+    depfile: Option<&Path>,
 ) -> Result<()> {
     if debug {
         println!("Building {}", source.display());
     }
 
     let mut cmd = Command::new(clang.as_os_str());
-    cmd.args(clang_args);
+    cmd.args(compile_args(source, out, clang_args, kind));
 
-    if !clang_args
-        .iter()
-        .any(|arg| arg.to_string_lossy().contains("__TARGET_ARCH_"))
-    {
-        // We may end up being invoked by a build script, in which case
-        // `CARGO_CFG_TARGET_ARCH` would represent the target architecture.
-        let arch = option_env!("CARGO_CFG_TARGET_ARCH").unwrap_or(ARCH);
-        let arch = match arch {
-            "x86_64" => "x86",
-            "aarch64" => "arm64",
-            "powerpc64" => "powerpc",
-            "s390x" => "s390",
-            x => x,
-        };
-        cmd.arg(format!("-D__TARGET_ARCH_{arch}"));
+    if let Some(depfile) = depfile {
+        cmd.arg("-MD").arg("-MF").arg(depfile);
     }
 
-    cmd.arg("-g")
-        .arg("-O2")
-        .arg("-target")
-        .arg("bpf")
-        .arg("-c")
-        .arg(source.as_os_str())
-        .arg("-o")
-        .arg(out);
-
     let output = cmd.output().context("Failed to execute clang")?;
     if !output.status.success() {
         let err = Err(anyhow!(String::from_utf8_lossy(&output.stderr).to_string()))
@@ -231,36 +803,188 @@ fn compile_one(
     strip_dwarf_info(out).with_context(|| format!("Failed to strip object file {}", out.display()))
 }
 
+/// A 64 bit FNV-1a hasher.
+///
+/// Contrary to `DefaultHasher`, the produced hashes are guaranteed to be
+/// stable across Rust releases, which matters as they are persisted.
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    /// Feed `data`, prefixed by its length, into the hasher, so that
+    /// adjacent inputs can't be confused with each other.
+    fn write(&mut self, data: &[u8]) {
+        let len = data.len() as u64;
+        for byte in len.to_le_bytes().iter().chain(data) {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Compute a hash over the inputs of a compilation: the compiler and its
+/// version, its arguments, and the contents of all files listed in
+/// `depfile`.
+///
+/// Returns `None` if any of the inputs could not be read.
+fn hash_inputs(clang: &Path, clang_args: &[OsString], depfile: &Path) -> Option<u64> {
+    let contents = fs::read_to_string(depfile).ok()?;
+    // The compiler binary may get updated in place, so the path by
+    // itself is not sufficient for identifying it.
+    let version = Command::new(clang)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    let mut hasher = StableHasher::new();
+    let () = hasher.write(clang.as_os_str().as_bytes());
+    let () = hasher.write(&version.stdout);
+    for arg in clang_args.iter().chain(&target_args(clang_args)) {
+        let () = hasher.write(arg.as_bytes());
+    }
+    for dep in parse_depfile(&contents) {
+        let () = hasher.write(dep.as_os_str().as_bytes());
+        let () = hasher.write(&fs::read(&dep).ok()?);
+    }
+    Some(hasher.finish())
+}
+
+/// Compile `source` into `out`, unless `out` is up-to-date with respect
+/// to the inputs used for creating it.
+///
+/// Alongside `out`, a dependency file (`.d`) and a file containing the
+/// hash of all inputs (`.hash`) are stored, which are used for detecting
+/// whether recompilation is necessary on subsequent invocations.
+fn compile_cached(
+    debug: bool,
+    source: &Path,
+    out: &Path,
+    compiler: &Compiler,
+    clang_args: &[OsString],
+) -> Result<()> {
+    let clang = &compiler.path;
+    let kind = compiler.kind;
+    let depfile = out.with_extension("d");
+    let stamp = out.with_extension("hash");
+
+    let stored = fs::read_to_string(&stamp)
+        .ok()
+        .and_then(|hash| u64::from_str_radix(hash.trim(), 16).ok());
+    if let Some(stored) = stored {
+        if out.exists() && hash_inputs(clang, clang_args, &depfile) == Some(stored) {
+            if debug {
+                println!("{} is up to date", out.display());
+            }
+            return Ok(());
+        }
+    }
+
+    // Make sure to never consider a stale object up-to-date, should
+    // compilation fail.
+    let _result = fs::remove_file(&stamp);
+    let () = compiler.check(source, clang_args)?;
+    let () = compile_one(debug, source, out, clang, kind, clang_args, Some(&depfile))?;
+
+    if let Some(hash) = hash_inputs(clang, clang_args, &depfile) {
+        let () = fs::write(&stamp, format!("{hash:016x}"))
+            .with_context(|| format!("Failed to write {}", stamp.display()))?;
+    }
+    Ok(())
+}
+
+/// Determine the path of the object file `obj` gets compiled into.
+///
+/// The dependency file recorded during compilation is located alongside
+/// it, with a `.d` extension.
+pub(crate) fn object_path(obj: &UnprocessedObj) -> Result<PathBuf> {
+    let stem = obj.path.file_stem().with_context(|| {
+        format!(
+            "Could not calculate destination name for obj={}",
+            obj.path.display()
+        )
+    })?;
+
+    let mut dest_name = stem.to_os_string();
+    dest_name.push(".o");
+    Ok(obj.out.join(dest_name))
+}
+
 fn compile(
     debug: bool,
     objs: &[UnprocessedObj],
-    clang: &Path,
+    compiler: &Compiler,
     mut clang_args: Vec<OsString>,
     target_dir: &Path,
+    opts: &BuildOpts,
 ) -> Result<()> {
+    if let Some(btf) = &opts.vmlinux {
+        let dir = target_dir.join("bpf").join("vmlinux");
+        let () = write_vmlinux_header(btf, &dir).context("Failed to generate vmlinux.h")?;
+        let () = prepend_include_dir(&mut clang_args, &dir);
+    }
+
     let header_dir = extract_libbpf_headers_to_disk(target_dir)?;
     if let Some(dir) = header_dir {
         clang_args.push(OsString::from("-I"));
         clang_args.push(dir.into_os_string());
     }
 
+    let mut jobs = Vec::with_capacity(objs.len());
+    let mut prefix_maps = Vec::new();
     for obj in objs {
-        let stem = obj.path.file_stem().with_context(|| {
-            format!(
-                "Could not calculate destination name for obj={}",
-                obj.path.display()
-            )
-        })?;
-
-        let mut dest_name = stem.to_os_string();
-        dest_name.push(".o");
-
-        let mut dest_path = obj.out.to_path_buf();
-        dest_path.push(&dest_name);
+        let dest_path = object_path(obj)?;
         fs::create_dir_all(&obj.out)?;
-        compile_one(debug, &obj.path, &dest_path, clang, &clang_args)?;
+
+        let mut obj_clang_args = clang_args.clone();
+        if opts.reproducible {
+            let prefix_map = reproducible_prefix_map(&obj.path, target_dir, "target")?;
+            let () = obj_clang_args.extend(reproducible_args(&prefix_map));
+            let () = prefix_maps.push(prefix_map);
+        }
+        let () = obj_clang_args.extend(obj.clang_args.iter().cloned());
+        let () = jobs.push((obj.path.as_path(), dest_path, obj_clang_args));
     }
 
+    let compile_commands = target_dir.join("bpf").join("compile_commands.json");
+    let () = write_compile_commands(&compile_commands, &compiler.path, compiler.kind, &jobs)?;
+
+    // Compile objects in parallel, with one worker per available CPU.
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(jobs.len());
+    let next = AtomicUsize::new(0);
+    let () = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some((source, out, clang_args)) =
+                        jobs.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let () = compile_cached(debug, source, out, compiler, clang_args)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter().try_for_each(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(anyhow!("BPF compilation thread panicked")))
+        })
+    })?;
+
+    for ((_source, out, _clang_args), prefix_map) in jobs.iter().zip(&prefix_maps) {
+        let () = verify_reproducible(out, prefix_map)?;
+    }
     Ok(())
 }
 
@@ -272,13 +996,13 @@ fn extract_clang_or_default(clang: Option<&PathBuf>) -> PathBuf {
     }
 }
 
-#[allow(clippy::too_many_arguments)]
 pub fn build(
     debug: bool,
     manifest_path: Option<&PathBuf>,
     clang: Option<&PathBuf>,
     clang_args: Vec<OsString>,
     skip_clang_version_checks: bool,
+    opts: &BuildOpts,
 ) -> Result<()> {
     let (target_dir, to_compile) = metadata::get(debug, manifest_path)?;
 
@@ -293,15 +1017,22 @@ pub fn build(
 
     check_progs(&to_compile)?;
 
+    let clang_args = opts.clang_args(clang_args);
     let clang = extract_clang_or_default(clang);
-    check_clang(debug, &clang, skip_clang_version_checks)
+    let compiler = check_clang(debug, &clang, skip_clang_version_checks)
         .with_context(|| anyhow!("{} is invalid", clang.display()))?;
-    compile(debug, &to_compile, &clang, clang_args, &target_dir)
+    compile(debug, &to_compile, &compiler, clang_args, &target_dir, opts)
         .context("Failed to compile progs")?;
 
     Ok(())
 }
 
+/// Build a single BPF object file, returning the list of files (such as
+/// the source itself and included headers) the compilation depended on.
+///
+/// Headers extracted from the vendored libbpf as well as a generated
+/// `vmlinux.h` are not part of the list, as they are only present
+/// temporarily. A BTF file the latter got generated from is, though.
 // Only used in libbpf-cargo library
 #[allow(dead_code)]
 pub fn build_single(
@@ -310,11 +1041,28 @@ pub fn build_single(
     out: &Path,
     clang: Option<&PathBuf>,
     skip_clang_version_checks: bool,
-    mut clang_args: Vec<OsString>,
-) -> Result<()> {
+    clang_args: Vec<OsString>,
+    opts: &BuildOpts,
+) -> Result<Vec<PathBuf>> {
+    let mut clang_args = opts.clang_args(clang_args);
     let clang = extract_clang_or_default(clang);
-    check_clang(debug, &clang, skip_clang_version_checks)?;
+    let compiler = check_clang(debug, &clang, skip_clang_version_checks)?;
     let header_parent_dir = tempdir()?;
+
+    let prefix_map = if opts.reproducible {
+        let prefix_map = reproducible_prefix_map(source, header_parent_dir.path(), "libbpf-cargo")?;
+        let () = clang_args.extend(reproducible_args(&prefix_map));
+        prefix_map
+    } else {
+        Vec::new()
+    };
+
+    if let Some(btf) = &opts.vmlinux {
+        let dir = header_parent_dir.path().join("vmlinux");
+        let () = write_vmlinux_header(btf, &dir).context("Failed to generate vmlinux.h")?;
+        let () = prepend_include_dir(&mut clang_args, &dir);
+    }
+
     let header_dir = extract_libbpf_headers_to_disk(header_parent_dir.path())?;
 
     if let Some(dir) = header_dir {
@@ -326,9 +1074,32 @@ pub fn build_single(
     // BPF. See https://lkml.org/lkml/2020/2/21/1000.
     clang_args.push(OsString::from("-fno-stack-protector"));
 
-    compile_one(debug, source, out, &clang, &clang_args)?;
+    let () = compiler.check(source, &clang_args)?;
 
-    Ok(())
+    let depfile = header_parent_dir.path().join("deps.d");
+    compile_one(
+        debug,
+        source,
+        out,
+        &clang,
+        compiler.kind,
+        &clang_args,
+        Some(&depfile),
+    )?;
+    if opts.reproducible {
+        let () = verify_reproducible(out, &prefix_map)?;
+    }
+
+    let contents = fs::read_to_string(&depfile)
+        .with_context(|| format!("failed to read dependency file `{}`", depfile.display()))?;
+    let mut deps = parse_depfile(&contents)
+        .into_iter()
+        .filter(|dep| !dep.starts_with(header_parent_dir.path()))
+        .collect::<Vec<_>>();
+    if let Some(VmlinuxBtf::Path(path)) = &opts.vmlinux {
+        let () = deps.push(path.clone());
+    }
+    Ok(deps)
 }
 
 #[test]
@@ -338,15 +1109,192 @@ Target: x86_64-pc-linux-gnu
 Thread model: posix
 InstalledDir: /bin
 ";
-    assert_eq!(extract_version(upstream_format).unwrap(), "10.0.0");
+    assert_eq!(
+        extract_version(upstream_format, CompilerKind::Clang).unwrap(),
+        "10.0.0"
+    );
 
     let ubuntu_format = r"Ubuntu clang version 11.0.1-++20201121072624+973b95e0a84-1~exp1~20201121063303.19
 Target: x86_64-pc-linux-gnu
 Thread model: posix
 InstalledDir: /bin
 ";
-    assert_eq!(extract_version(ubuntu_format).unwrap(), "11.0.1");
+    assert_eq!(
+        extract_version(ubuntu_format, CompilerKind::Clang).unwrap(),
+        "11.0.1"
+    );
+
+    assert!(extract_version("askldfjwe", CompilerKind::Clang).is_err());
+    assert!(extract_version("my clang version 1.5", CompilerKind::Clang).is_err());
+
+    let gcc_format = r"bpf-unknown-none-gcc (GCC) 13.2.0
+Copyright (C) 2023 Free Software Foundation, Inc.
+";
+    assert_eq!(
+        CompilerKind::from_version_output(gcc_format),
+        CompilerKind::Gcc
+    );
+    assert_eq!(
+        extract_version(gcc_format, CompilerKind::Gcc).unwrap(),
+        "13.2.0"
+    );
 
-    assert!(extract_version("askldfjwe").is_err());
-    assert!(extract_version("my clang version 1.5").is_err());
+    let distro_format = r"bpf-gcc (Debian 14.2.0-1) 14.2.0
+Copyright (C) 2024 Free Software Foundation, Inc.
+";
+    assert_eq!(
+        extract_version(distro_format, CompilerKind::Gcc).unwrap(),
+        "14.2.0"
+    );
+    assert_eq!(
+        CompilerKind::from_version_output(ubuntu_format),
+        CompilerKind::Clang
+    );
+}
+
+#[test]
+fn test_gcc_args() {
+    let args = [
+        "-target",
+        "bpfeb",
+        "--target=bpfel",
+        "-g",
+        "-O2",
+        "-mcpu=v3",
+        "-target",
+        "bpf",
+    ]
+    .map(OsString::from)
+    .to_vec();
+    let expected = [
+        "-mbig-endian",
+        "-mlittle-endian",
+        "-gbtf",
+        "-O2",
+        "-mcpu=v3",
+    ]
+    .map(OsString::from)
+    .to_vec();
+    assert_eq!(gcc_args(args), expected);
+}
+
+#[test]
+fn test_parse_depfile() {
+    let depfile = r"prog.o: src/bpf/prog.bpf.c /usr/include/vmlinux.h \
+  src/bpf/with\ space.h \
+  /tmp/.tmpXyz/bpf/src/bpf/bpf_helpers.h
+";
+    assert_eq!(
+        parse_depfile(depfile),
+        vec![
+            PathBuf::from("src/bpf/prog.bpf.c"),
+            PathBuf::from("/usr/include/vmlinux.h"),
+            PathBuf::from("src/bpf/with space.h"),
+            PathBuf::from("/tmp/.tmpXyz/bpf/src/bpf/bpf_helpers.h"),
+        ]
+    );
+
+    assert_eq!(parse_depfile(""), Vec::<PathBuf>::new());
+}
+
+#[test]
+fn test_target_args() {
+    let args = target_args(&[]);
+    assert!(args.iter().any(|arg| arg == "-target"));
+    assert!(args
+        .iter()
+        .any(|arg| arg.to_string_lossy().starts_with("-D__TARGET_ARCH_")));
+
+    let explicit = [
+        OsString::from("-D__TARGET_ARCH_s390"),
+        OsString::from("-target"),
+        OsString::from("bpfeb"),
+    ];
+    assert_eq!(target_args(&explicit), Vec::<OsString>::new());
+    assert_eq!(
+        target_args(&[
+            OsString::from("--target=bpfeb"),
+            OsString::from("-D__TARGET_ARCH_s390")
+        ]),
+        Vec::<OsString>::new()
+    );
+
+    assert_eq!(
+        with_bpf_target(Vec::new(), Some("bpfeb")),
+        vec![OsString::from("-target"), OsString::from("bpfeb")]
+    );
+    assert_eq!(with_bpf_target(Vec::new(), None), Vec::<OsString>::new());
+}
+
+#[test]
+fn test_is_foreign_endian() {
+    let le = b"\x7fELF\x02\x01";
+    let be = b"\x7fELF\x02\x02";
+    assert_eq!(is_foreign_endian(le), cfg!(target_endian = "big"));
+    assert_eq!(is_foreign_endian(be), cfg!(target_endian = "little"));
+    assert!(!is_foreign_endian(b""));
+}
+
+#[test]
+fn test_find_prefix() {
+    let data = b"\0/usr/include/linux/types.h\0/home/user/proj/src/prog.bpf.c\0";
+    let prefix_map = [
+        (PathBuf::from("/"), "."),
+        (PathBuf::from("/tmp/target"), "target"),
+    ];
+    assert_eq!(find_prefix(data, &prefix_map), None);
+
+    let prefix_map = [
+        (PathBuf::from("/"), "."),
+        (PathBuf::from("/home/user/proj/src"), "."),
+    ];
+    assert_eq!(
+        find_prefix(data, &prefix_map),
+        Some(Path::new("/home/user/proj/src"))
+    );
+}
+
+#[test]
+fn test_check_features() {
+    let source = Path::new("prog.bpf.c");
+    let relo = "int x = __builtin_preserve_enum_value(*(typeof(enum foo) *)BAR, 1);";
+    let check = |kind, version, preprocessed, clang_args: &[OsString]| {
+        check_features(kind, &version, source, preprocessed, clang_args)
+    };
+
+    let clang = |major| Version::new(major, 0, 0);
+    assert!(check(CompilerKind::Clang, clang(11), relo, &[]).is_err());
+    assert!(check(CompilerKind::Clang, clang(12), relo, &[]).is_ok());
+    // Helper macros get expanded by the preprocessor, so their names by
+    // themselves do not indicate any use.
+    let mention = "int bpf_core_enum_value;";
+    assert!(check(CompilerKind::Clang, clang(11), mention, &[]).is_ok());
+
+    let v4 = with_bpf_cpu(Vec::new(), Some("v4"));
+    assert!(check(CompilerKind::Clang, clang(17), "", &v4).is_err());
+    assert!(check(CompilerKind::Clang, clang(18), "", &v4).is_ok());
+    assert!(check(CompilerKind::Gcc, Version::new(13, 2, 0), "", &v4).is_err());
+    assert!(check(CompilerKind::Gcc, Version::new(14, 1, 0), "", &v4).is_ok());
+
+    // The last `-mcpu` argument takes precedence.
+    let v3 = with_bpf_cpu(v4, Some("v3"));
+    assert!(check(CompilerKind::Clang, clang(17), "", &v3).is_ok());
+}
+
+#[test]
+fn test_check_cpu() {
+    for cpu in ["generic", "probe", "v1", "v4"] {
+        let args = with_bpf_cpu(Vec::new(), Some(cpu));
+        assert!(check_cpu(CompilerKind::Clang, &args).is_ok());
+    }
+
+    for cpu in ["v1", "v2", "v3", "v4"] {
+        let args = with_bpf_cpu(Vec::new(), Some(cpu));
+        assert!(check_cpu(CompilerKind::Gcc, &args).is_ok());
+    }
+    for cpu in ["generic", "probe"] {
+        let args = with_bpf_cpu(Vec::new(), Some(cpu));
+        assert!(check_cpu(CompilerKind::Gcc, &args).is_err());
+    }
+    assert!(check_cpu(CompilerKind::Gcc, &[]).is_ok());
 }
@@ -0,0 +1,163 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+
+use libbpf_rs::ObjectInspector;
+
+use crate::build;
+use crate::metadata;
+
+/// The maximum stack size of a BPF program, in bytes.
+const MAX_STACK_SIZE: u32 = 512;
+
+/// The severity of a [`Problem`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The object will likely fail to load.
+    Error,
+    /// The object may not behave as intended.
+    Warning,
+}
+
+/// A problem detected in a BPF object file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}", self.message)
+    }
+}
+
+/// Inspect the BPF object file at `path`, returning a human readable
+/// report of its contents along with the problems found.
+pub fn check_object(path: &Path) -> Result<(String, Vec<Problem>)> {
+    let inspector = ObjectInspector::from_path(path)
+        .with_context(|| format!("failed to inspect `{}`", path.display()))?;
+    let mut report = String::new();
+    let mut problems = Vec::new();
+
+    writeln!(report, "{}:", path.display())?;
+    match inspector.license() {
+        Some(license) if !license.is_empty() => writeln!(report, "  license: {license}")?,
+        _ => problems.push(Problem {
+            severity: Severity::Error,
+            message: "object lacks a license; add `char LICENSE[] SEC(\"license\") = \"GPL\";`"
+                .to_string(),
+        }),
+    }
+
+    writeln!(report, "  programs:")?;
+    for prog in inspector.programs() {
+        writeln!(
+            report,
+            "    {} ({}, {:?}): {} insns, ~{} bytes of stack",
+            prog.name, prog.section, prog.prog_type, prog.insn_cnt, prog.stack_size
+        )?;
+
+        if prog.stack_size > MAX_STACK_SIZE {
+            problems.push(Problem {
+                severity: Severity::Error,
+                message: format!(
+                    "program `{}` uses ~{} bytes of stack, exceeding the limit of {MAX_STACK_SIZE} bytes",
+                    prog.name, prog.stack_size
+                ),
+            });
+        }
+    }
+    if inspector.programs().is_empty() {
+        problems.push(Problem {
+            severity: Severity::Warning,
+            message: "object contains no programs".to_string(),
+        });
+    }
+
+    writeln!(report, "  maps:")?;
+    for map in inspector.maps() {
+        writeln!(
+            report,
+            "    {}: {:?}, key size {}, value size {}, max entries {}",
+            map.name, map.map_type, map.key_size, map.value_size, map.max_entries
+        )?;
+    }
+
+    let helpers = inspector
+        .helpers()
+        .iter()
+        .map(|helper| helper.to_string())
+        .collect::<Vec<_>>();
+    writeln!(report, "  helpers: {}", helpers.join(", "))?;
+
+    let map_types = inspector
+        .map_types()
+        .iter()
+        .map(|ty| format!("{ty:?}"))
+        .collect::<Vec<_>>();
+    writeln!(report, "  map types: {}", map_types.join(", "))?;
+
+    writeln!(report, "  CO-RE relocations:")?;
+    for relo in inspector.core_relocations() {
+        writeln!(
+            report,
+            "    {}+{:#x}: {:?} of {} [{}]",
+            relo.section,
+            relo.insn_off,
+            relo.kind,
+            relo.type_name.as_deref().unwrap_or("<anon>"),
+            relo.access
+        )?;
+    }
+
+    Ok((report, problems))
+}
+
+/// Check the provided BPF object file or, if none is provided, all
+/// objects of the project, reporting an error if any problem is found.
+pub fn check(debug: bool, manifest_path: Option<&PathBuf>, object: Option<&PathBuf>) -> Result<()> {
+    if manifest_path.is_some() && object.is_some() {
+        bail!("--manifest-path and --object cannot be used together");
+    }
+
+    let objects = if let Some(object) = object {
+        vec![object.clone()]
+    } else {
+        let (_target_dir, objs) = metadata::get(debug, manifest_path)?;
+        if objs.is_empty() {
+            bail!("Did not find any bpf objects to check");
+        }
+        objs.iter()
+            .map(build::object_path)
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut errors = 0;
+    for object in objects {
+        let (report, problems) = check_object(&object)?;
+        print!("{report}");
+        for problem in problems {
+            println!("  {problem}");
+            if problem.severity == Severity::Error {
+                errors += 1;
+            }
+        }
+    }
+
+    if errors > 0 {
+        bail!("found {errors} problem(s) in BPF objects");
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
@@ -30,6 +31,8 @@ use super::canonicalize_internal_map_name;
 use super::InternalMapType;
 
 const ANON_PREFIX: &str = "__anon_";
+/// The prefix of BTF declaration tags carrying documentation.
+const DOC_TAG_PREFIX: &str = "doc:";
 
 /// Check whether the provided type is "unsafe" to use.
 ///
@@ -40,11 +43,157 @@ fn is_unsafe(ty: BtfType<'_>) -> bool {
 
     btf_type_match!(match ty {
         BtfKind::Int(t) => matches!(t.encoding, types::IntEncoding::Bool),
-        BtfKind::Enum | BtfKind::Enum64 => true,
+        BtfKind::Enum64 => true,
         _ => false,
     })
 }
 
+/// Determine the integer type representing the given enum.
+fn enum_repr(t: &types::Enum<'_>) -> Result<String> {
+    let repr_size = match t.size() {
+        1 => "8",
+        2 => "16",
+        4 => "32",
+        8 => "64",
+        16 => "128",
+        _ => bail!("Invalid enum size: {}", t.size()),
+    };
+
+    let signed = if t.iter().any(|value| value.value < 0) {
+        "i"
+    } else {
+        "u"
+    };
+    Ok(format!("{signed}{repr_size}"))
+}
+
+/// Check whether the provided type is "plain old data", i.e., valid for
+/// any bit pattern and free of pointers as well as implicit padding.
+///
+/// Types satisfying this property can soundly implement marker traits
+/// such as `plain::Plain` or `bytemuck::Pod`.
+fn is_pod(ty: BtfType<'_>, btf: &Btf<'_>) -> Result<bool> {
+    let ty = ty.skip_mods_and_typedefs();
+
+    let pod = btf_type_match!(match ty {
+        BtfKind::Int(t) => !matches!(t.encoding, types::IntEncoding::Bool),
+        BtfKind::Float | BtfKind::Enum => true,
+        BtfKind::Array(t) => is_pod(t.contained_type(), btf)?,
+        BtfKind::Composite(t) => {
+            let packed = is_struct_packed(&t, btf)?;
+            let mut offset = 0;
+            let mut bitfields = false;
+            for m in t.iter() {
+                let member_ty = btf.type_by_id::<BtfType<'_>>(m.ty).unwrap();
+                let member_offset = match m.attr {
+                    MemberAttr::Normal { offset } => offset as usize / 8,
+                    // Bitfields are backed by byte array storage.
+                    MemberAttr::BitField { .. } if t.is_struct => {
+                        bitfields = true;
+                        continue;
+                    }
+                    MemberAttr::BitField { .. } => return Ok(false),
+                };
+
+                if !is_pod(member_ty, btf)? {
+                    return Ok(false);
+                }
+
+                if !t.is_struct {
+                    // Bytes of a union not covered by all of its members
+                    // constitute padding.
+                    if size_of_type(member_ty, btf)? != t.size() {
+                        return Ok(false);
+                    }
+                    continue;
+                }
+
+                // Gaps that we don't fill with explicit padding members
+                // end up as implicit padding.
+                if member_offset > offset
+                    && storage_padding(offset, member_offset, &member_ty, packed, bitfields)? == 0
+                {
+                    return Ok(false);
+                }
+                offset = member_offset + size_of_type(member_ty, btf)?;
+                bitfields = false;
+            }
+
+            !(t.is_struct
+                && t.size() > offset
+                && storage_padding(offset, t.size(), &t, packed, bitfields)? == 0)
+        }
+        _ => false,
+    });
+    Ok(pod)
+}
+
+/// Check whether user requested derives can be applied to `ty`, i.e.,
+/// whether it is (transitively) free of pointers, unions, members
+/// wrapped in `MaybeUninit`, and arrays or padding of more than 32
+/// elements, none of which support most derives (e.g., `Default` or
+/// `serde::Serialize`).
+fn is_derivable(ty: BtfType<'_>, btf: &Btf<'_>) -> Result<bool> {
+    let ty = ty.skip_mods_and_typedefs();
+
+    let derivable = btf_type_match!(match ty {
+        BtfKind::Ptr => false,
+        BtfKind::Array(t) => t.capacity() <= 32 && is_derivable(t.contained_type(), btf)?,
+        BtfKind::Composite(t) => {
+            if !t.is_struct {
+                return Ok(false);
+            }
+
+            let packed = is_struct_packed(&t, btf)?;
+            let mut offset = 0;
+            let mut bitfields = false;
+            for m in t.iter() {
+                let member_ty = btf.type_by_id::<BtfType<'_>>(m.ty).unwrap();
+                let member_offset = match m.attr {
+                    MemberAttr::Normal { offset } => offset as usize / 8,
+                    // Bitfields are backed by byte array storage.
+                    MemberAttr::BitField { .. } => {
+                        bitfields = true;
+                        continue;
+                    }
+                };
+
+                if storage_padding(offset, member_offset, &member_ty, packed, bitfields)? > 32
+                    || is_unsafe(member_ty)
+                    || !is_derivable(member_ty, btf)?
+                {
+                    return Ok(false);
+                }
+                offset = member_offset + size_of_type(member_ty, btf)?;
+                bitfields = false;
+            }
+
+            storage_padding(offset, t.size(), &t, packed, bitfields)? <= 32
+        }
+        _ => true,
+    });
+    Ok(derivable)
+}
+
+/// Determine which members of the union `t` can be read safely, i.e.,
+/// which ones are initialized and valid no matter the member written last.
+///
+/// That is the case for members not larger than any other member,
+/// provided that all members are plain old data.
+fn readable_union_members(t: &types::Composite<'_>, btf: &Btf<'_>) -> Result<Vec<bool>> {
+    let mut sizes = Vec::new();
+    for member in t.iter() {
+        let member_ty = btf.type_by_id::<BtfType<'_>>(member.ty).unwrap();
+        if matches!(member.attr, MemberAttr::BitField { .. }) || !is_pod(member_ty, btf)? {
+            return Ok(vec![false; t.iter().len()]);
+        }
+        let () = sizes.push(size_of_type(member_ty, btf)?);
+    }
+
+    let min_size = sizes.iter().copied().min().unwrap_or_default();
+    Ok(sizes.into_iter().map(|size| size <= min_size).collect())
+}
+
 fn is_struct_packed(composite: &types::Composite<'_>, btf: &Btf<'_>) -> Result<bool> {
     if !composite.is_struct {
         return Ok(false);
@@ -112,6 +261,152 @@ fn required_padding(
     }
 }
 
+/// Like [`required_padding`], but forces explicit padding for any gap if
+/// `bitfields` is set, as the bits of bitfields placed in the gap have to
+/// be backed by actual storage.
+fn storage_padding(
+    current_offset: usize,
+    required_offset: usize,
+    ty: &BtfType<'_>,
+    packed: bool,
+    bitfields: bool,
+) -> Result<usize> {
+    if bitfields && required_offset > current_offset {
+        Ok(required_offset - current_offset)
+    } else {
+        required_padding(current_offset, required_offset, ty, packed)
+    }
+}
+
+/// A named bitfield member of a struct.
+struct BitField<'s> {
+    name: Cow<'s, str>,
+    ty: BtfType<'s>,
+    /// The size of the bitfield, in bits.
+    size: u8,
+    /// The offset of the bitfield from the start of the struct, in bits.
+    offset: u32,
+}
+
+impl BitField<'_> {
+    /// Generate getter and setter methods for the bitfield, backed by
+    /// the byte array member `storage`, which is located at byte offset
+    /// `storage_offset` and is `storage_len` bytes long.
+    fn accessors(
+        &self,
+        anon_types: &AnonTypes,
+        storage: &str,
+        storage_offset: usize,
+        storage_len: usize,
+    ) -> Result<String> {
+        let name = &self.name;
+        let bitfield_ty = self.ty;
+        let size = usize::from(self.size);
+        ensure!(
+            (1..=64).contains(&size),
+            "bitfield `{name}` has unsupported size of {size} bits"
+        );
+
+        let (ty, to_value, from_value) = btf_type_match!(match bitfield_ty {
+            BtfKind::Int(t) => match t.encoding {
+                types::IntEncoding::Bool => (
+                    "bool".to_string(),
+                    "value != 0".to_string(),
+                    "u128::from(value)",
+                ),
+                types::IntEncoding::Signed => {
+                    let ty = type_declaration(bitfield_ty, anon_types)?;
+                    // Sign extend the raw value.
+                    let shift = 128 - size;
+                    let to = format!("((value << {shift}) as i128 >> {shift}) as {ty}");
+                    (ty, to, "value as u128")
+                }
+                types::IntEncoding::Char | types::IntEncoding::None => {
+                    let ty = type_declaration(bitfield_ty, anon_types)?;
+                    let to = format!("value as {ty}");
+                    (ty, to, "value as u128")
+                }
+            },
+            BtfKind::Enum(t) => {
+                let ty = anon_types.type_name_or_anon(&t).into_owned();
+                let to = format!("{ty}(value as {})", enum_repr(&t)?);
+                (ty, to, "value.0 as u128")
+            }
+            // 64 bit enums are not valid for arbitrary bit patterns, so
+            // we expose the raw value instead.
+            BtfKind::Enum64(t) => {
+                let ty = format!("u{}", t.size() * 8);
+                let to = format!("value as {ty}");
+                (ty, to, "value as u128")
+            }
+            _ => bail!("bitfield `{name}` has unsupported type: {bitfield_ty:?}"),
+        });
+
+        let bit_offset = self.offset as usize - storage_offset * 8;
+        let start = bit_offset / 8;
+        let end = (bit_offset + size + 7) / 8;
+        ensure!(
+            end <= storage_len,
+            "bitfield `{name}` exceeds its storage ({end} > {storage_len} bytes)"
+        );
+
+        // The shift to apply to the little respectively big endian
+        // integer made up of the bytes `start..end` to get to the bitfield.
+        let le_shift = bit_offset % 8;
+        let be_shift = (end - start) * 8 - le_shift - size;
+        let shift = |expr: &str, op, shift| -> String {
+            if shift == 0 {
+                expr.to_string()
+            } else {
+                format!("({expr} {op} {shift})")
+            }
+        };
+        let mask = (1u128 << size) - 1;
+
+        let mut def = String::new();
+        write!(
+            def,
+            r#"
+    pub fn {name}(&self) -> {ty} {{
+        let bytes = &self.{storage}[{start}..{end}];
+        let mut buf = [0u8; 16];
+        let value = if cfg!(target_endian = "little") {{
+            buf[..bytes.len()].copy_from_slice(bytes);
+            {le_shr} & {mask:#x}
+        }} else {{
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            {be_shr} & {mask:#x}
+        }};
+        {to_value}
+    }}
+
+    pub fn set_{setter_name}(&mut self, value: {ty}) {{
+        let value = {from_value} & {mask:#x};
+        let bytes = &mut self.{storage}[{start}..{end}];
+        let mut buf = [0u8; 16];
+        if cfg!(target_endian = "little") {{
+            buf[..bytes.len()].copy_from_slice(bytes);
+            let value = (u128::from_le_bytes(buf) & !{le_mask:#x}) | {le_shl};
+            bytes.copy_from_slice(&value.to_le_bytes()[..bytes.len()]);
+        }} else {{
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            let value = (u128::from_be_bytes(buf) & !{be_mask:#x}) | {be_shl};
+            bytes.copy_from_slice(&value.to_be_bytes()[16 - bytes.len()..]);
+        }}
+    }}
+"#,
+            setter_name = name.trim_start_matches("r#"),
+            le_shr = shift("u128::from_le_bytes(buf)", ">>", le_shift),
+            be_shr = shift("u128::from_be_bytes(buf)", ">>", be_shift),
+            le_shl = shift("value", "<<", le_shift),
+            be_shl = shift("value", "<<", be_shift),
+            le_mask = mask << le_shift,
+            be_mask = mask << be_shift,
+        )?;
+        Ok(def)
+    }
+}
+
 struct TypeDeclOpts {
     func_type: &'static str,
 }
@@ -124,7 +419,7 @@ fn type_declaration_impl(
     let ty = ty.skip_mods_and_typedefs();
 
     let s = btf_type_match!(match ty {
-        BtfKind::Void => "std::ffi::c_void".to_string(),
+        BtfKind::Void => "core::ffi::c_void".to_string(),
         BtfKind::Int(t) => {
             let width = match (t.bits + 7) / 8 {
                 1 => "8",
@@ -169,7 +464,7 @@ fn type_declaration_impl(
         BtfKind::Struct | BtfKind::Union | BtfKind::Enum | BtfKind::Enum64 =>
             anon_types.type_name_or_anon(&ty).into_owned(),
         BtfKind::Func | BtfKind::FuncProto => opts.func_type.to_string(),
-        BtfKind::Fwd => "std::ffi::c_void".to_string(),
+        BtfKind::Fwd => "core::ffi::c_void".to_string(),
         BtfKind::Var(t) => type_declaration_impl(t.referenced_type(), anon_types, opts)?,
         _ => bail!("Invalid type: {ty:?}"),
     });
@@ -178,7 +473,7 @@ fn type_declaration_impl(
 
 fn type_declaration(ty: BtfType<'_>, anon_types: &AnonTypes) -> Result<String> {
     let opts = TypeDeclOpts {
-        func_type: "std::ffi::c_void",
+        func_type: "core::ffi::c_void",
     };
     type_declaration_impl(ty, anon_types, &opts)
 }
@@ -197,7 +492,7 @@ fn type_default(ty: BtfType<'_>, anon_types: &AnonTypes) -> Result<String> {
     Ok(btf_type_match!(match ty {
         BtfKind::Int => format!("{}::default()", type_declaration(ty, anon_types)?),
         BtfKind::Float => format!("{}::default()", type_declaration(ty, anon_types)?),
-        BtfKind::Ptr => "std::ptr::null_mut()".to_string(),
+        BtfKind::Ptr => "core::ptr::null_mut()".to_string(),
         BtfKind::Array(t) => {
             format!(
                 "[{}; {}]",
@@ -261,17 +556,163 @@ fn escape_reserved_keyword(identifier: Cow<'_, str>) -> Cow<'_, str> {
     }
 }
 
+/// Skip array, pointer, and modifier types, but not typedefs, to get to
+/// the type actually defining a member.
+fn skip_to_definition(mut ty: BtfType<'_>) -> BtfType<'_> {
+    loop {
+        let next = btf_type_match!(match ty {
+            BtfKind::Array(t) => t.contained_type(),
+            BtfKind::Ptr(t) => t.referenced_type(),
+            BtfKind::Const(t) => t.referenced_type(),
+            BtfKind::Volatile(t) => t.referenced_type(),
+            BtfKind::Restrict(t) => t.referenced_type(),
+            BtfKind::TypeTag(t) => t.referenced_type(),
+            _ => return ty,
+        });
+        ty = next;
+    }
+}
+
+/// Check whether `ty` is an anonymous type that we emit a definition
+/// for and, hence, have to name.
+fn is_anon_definition(ty: &BtfType<'_>) -> bool {
+    ty.name().is_none()
+        && matches!(
+            ty.kind(),
+            BtfKind::Struct | BtfKind::Union | BtfKind::Enum | BtfKind::Enum64
+        )
+}
+
+/// Naming of anonymous types.
+///
+/// Anonymous types are named deterministically after the place they are
+/// defined at, so that names don't change when unrelated types are added
+/// or removed:
+/// - an anonymous type referenced by a typedef is named after the typedef
+/// - an anonymous type of a member is named after the enclosing type and
+///   the member, i.e., `<type>__<member>`, or `<type>__anon_<n>` for the
+///   `n`-th (1 based) unnamed member
+/// - an anonymous type of a global variable is named after the data
+///   section and the variable, i.e., `<section>__<variable>`
+///
+/// Remaining anonymous types, as well as those for which the derived name
+/// would clash with another type's, get numbered consecutively instead.
 #[derive(Debug, Default)]
 pub(crate) struct AnonTypes {
-    /// A mapping from type to number, allowing us to assign numbers to types
-    /// consistently.
+    /// Names derived for anonymous types.
+    names: HashMap<TypeId, String>,
+    /// A mapping from type to number, allowing us to assign numbers to
+    /// the remaining anonymous types consistently.
     types: RefCell<HashMap<TypeId, usize>>,
 }
 
 impl AnonTypes {
+    pub fn new(btf: &Btf<'_>) -> Self {
+        let mut slf = Self::default();
+        // The names of the types we emit definitions for.
+        let mut taken = btf
+            .type_by_kind::<BtfType<'_>>()
+            .filter(|ty| {
+                matches!(
+                    ty.kind(),
+                    BtfKind::Struct | BtfKind::Union | BtfKind::Enum | BtfKind::Enum64
+                )
+            })
+            .filter_map(|ty| ty.name().map(|name| name.to_string_lossy().into_owned()))
+            .collect::<HashSet<_>>();
+
+        for ty in btf.type_by_kind::<types::Typedef<'_>>() {
+            let target = skip_to_definition(ty.referenced_type());
+            if is_anon_definition(&target) {
+                // SANITY: Typedefs always have a name.
+                let name = ty.name().unwrap().to_string_lossy().into_owned();
+                let _named = slf.assign(target, name, &mut taken);
+            }
+        }
+
+        for ty in btf.type_by_kind::<types::DataSec<'_>>() {
+            let sec_name = match ty.name().map(|s| s.to_string_lossy()) {
+                Some(s) if s.starts_with('.') => s[1..].replace('.', "_"),
+                _ => continue,
+            };
+
+            for var in ty.iter() {
+                let var = match btf.type_by_id::<types::Var<'_>>(var.ty) {
+                    Some(var) => var,
+                    None => continue,
+                };
+                let target = skip_to_definition(var.referenced_type());
+                if let Some(var_name) = var.name() {
+                    let name = format!("{sec_name}__{}", var_name.to_string_lossy());
+                    let _named = slf.assign(target, name, &mut taken);
+                }
+            }
+        }
+
+        for ty in btf.type_by_kind::<types::Composite<'_>>() {
+            let () = slf.assign_members(btf, ty, &mut taken);
+        }
+        slf
+    }
+
+    /// Assign `name` to `ty` if it is anonymous and not named yet,
+    /// returning whether it got named.
+    fn assign(&mut self, ty: BtfType<'_>, name: String, taken: &mut HashSet<String>) -> bool {
+        if !is_anon_definition(&ty) || self.names.contains_key(&ty.type_id()) {
+            return false;
+        }
+        if !taken.insert(name.clone()) {
+            return false;
+        }
+        let _prev = self.names.insert(ty.type_id(), name);
+        true
+    }
+
+    /// Name anonymous types of the members of `ty`, if `ty` itself is
+    /// named, recursively.
+    fn assign_members(
+        &mut self,
+        btf: &Btf<'_>,
+        ty: types::Composite<'_>,
+        taken: &mut HashSet<String>,
+    ) {
+        let parent = match ty.name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => match self.names.get(&ty.type_id()) {
+                Some(name) => name.clone(),
+                None => return,
+            },
+        };
+
+        let mut anon_idx = 0;
+        for member in ty.iter() {
+            let name = match (member.name, member.attr) {
+                (Some(name), _) => format!("{parent}__{}", name.to_string_lossy()),
+                (None, MemberAttr::Normal { .. }) => {
+                    anon_idx += 1;
+                    format!("{parent}__anon_{anon_idx}")
+                }
+                // Unnamed bitfields are mere padding.
+                (None, MemberAttr::BitField { .. }) => continue,
+            };
+            // SANITY: Members always reference a valid type.
+            let member_ty = btf.type_by_id::<BtfType<'_>>(member.ty).unwrap();
+            let target = skip_to_definition(member_ty);
+            if self.assign(target, name, taken) {
+                if let Ok(composite) = types::Composite::try_from(target) {
+                    let () = self.assign_members(btf, composite, taken);
+                }
+            }
+        }
+    }
+
     pub fn type_name_or_anon<'s>(&self, ty: &BtfType<'s>) -> Cow<'s, str> {
         match ty.name() {
             None => {
+                if let Some(name) = self.names.get(&ty.type_id()) {
+                    return Cow::Owned(name.clone());
+                }
+
                 let mut anon_table = self.types.borrow_mut();
                 let len = anon_table.len() + 1; // use 1 index anon ids for backwards compat
                 let anon_id = anon_table.entry(ty.type_id()).or_insert(len);
@@ -282,9 +723,25 @@ impl AnonTypes {
     }
 }
 
+/// Options controlling the generation of Rust types.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct TypeOpts {
+    /// Emit `plain::Plain` implementations for plain old data types.
+    pub plain: bool,
+    /// Emit `bytemuck::Zeroable` and `bytemuck::Pod` implementations for
+    /// plain old data types.
+    pub bytemuck: bool,
+    /// Additional derives for generated structs and enums.
+    pub derives: Vec<String>,
+    /// Additional attributes (without the surrounding `#[...]`) for
+    /// generated structs and enums.
+    pub attributes: Vec<String>,
+}
+
 pub struct GenBtf<'s> {
     btf: Btf<'s>,
     anon_types: AnonTypes,
+    type_opts: TypeOpts,
 }
 
 impl Debug for GenBtf<'_> {
@@ -298,8 +755,9 @@ impl Debug for GenBtf<'_> {
 impl<'s> From<Btf<'s>> for GenBtf<'s> {
     fn from(btf: Btf<'s>) -> GenBtf<'s> {
         Self {
+            anon_types: AnonTypes::new(&btf),
             btf,
-            anon_types: Default::default(),
+            type_opts: Default::default(),
         }
     }
 }
@@ -312,6 +770,88 @@ impl<'s> Deref for GenBtf<'s> {
 }
 
 impl<'s> GenBtf<'s> {
+    /// Check whether `ty` is plain old data, i.e., valid for any bit
+    /// pattern and free of pointers as well as implicit padding.
+    pub(crate) fn is_pod(&self, ty: BtfType<'_>) -> Result<bool> {
+        is_pod(ty, &self.btf)
+    }
+
+    /// Set the options controlling the generation of types.
+    pub(crate) fn set_type_opts(&mut self, type_opts: TypeOpts) {
+        self.type_opts = type_opts;
+    }
+
+    /// Format the documentation attached to `ty`, or to its member or
+    /// parameter `component`, as doc comment lines indented by `indent`.
+    ///
+    /// Documentation is provided by BTF declaration tags of the form
+    /// `doc:<text>`, e.g., `__attribute__((btf_decl_tag("doc:...")))`.
+    pub(crate) fn doc_comment(&self, ty: TypeId, component: Option<u32>, indent: &str) -> String {
+        let mut doc = String::new();
+        for tag in self.decl_tags(ty) {
+            if tag.component_index() != component {
+                continue;
+            }
+            let text = match tag.name() {
+                Some(name) => name.to_string_lossy(),
+                None => continue,
+            };
+            let text = match text.strip_prefix(DOC_TAG_PREFIX) {
+                Some(text) => text.trim(),
+                None => continue,
+            };
+
+            for line in text.lines() {
+                let line = line.trim_end();
+                if line.is_empty() {
+                    let () = doc.push_str(&format!("{indent}///\n"));
+                } else {
+                    let () = doc.push_str(&format!("{indent}/// {line}\n"));
+                }
+            }
+        }
+        doc
+    }
+
+    /// Format the documentation attached to the function or global
+    /// variable called `name`, as per [`GenBtf::doc_comment`].
+    pub(crate) fn doc_comment_by_name(&self, name: &str, indent: &str) -> String {
+        let name = Some(OsStr::new(name));
+        let ty = self
+            .type_by_kind::<types::Func<'_>>()
+            .find(|func| func.name() == name)
+            .map(|func| func.type_id())
+            .or_else(|| {
+                self.type_by_kind::<types::Var<'_>>()
+                    .find(|var| var.name() == name)
+                    .map(|var| var.type_id())
+            });
+
+        ty.map(|ty| self.doc_comment(ty, None, indent))
+            .unwrap_or_default()
+    }
+
+    /// Write a `#[derive(...)]` attribute for `derives`, followed by the
+    /// user requested derives and attributes if `custom` is set.
+    fn write_derives(&self, def: &mut String, derives: &str, custom: bool) -> Result<()> {
+        if custom && !self.type_opts.derives.is_empty() {
+            writeln!(
+                def,
+                r#"#[derive({derives}, {})]"#,
+                self.type_opts.derives.join(", ")
+            )?;
+        } else {
+            writeln!(def, r#"#[derive({derives})]"#)?;
+        }
+
+        if custom {
+            for attribute in &self.type_opts.attributes {
+                writeln!(def, r#"#[{attribute}]"#)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the rust-ified type declaration of `ty` in string format.
     ///
     /// Rule of thumb is `ty` must be a type a variable can have.
@@ -391,13 +931,10 @@ impl<'s> GenBtf<'s> {
         Ok(def)
     }
 
-    pub fn struct_ops_type_definition(&self, processed: &mut HashSet<TypeId>) -> Result<String> {
-        let mut def = String::new();
-        let mut dependent_types = vec![];
+    /// Collect the non-static variables of all struct_ops datasecs.
+    fn struct_ops_vars(&self) -> Result<Vec<BtfType<'_>>> {
         let mut vars = vec![];
 
-        // Take all the struct_ops datasec entries and collect their variables
-        // (and dependent types).
         for ty in self.type_by_kind::<types::DataSec<'_>>() {
             let name = match ty.name() {
                 Some(s) => s.to_str().context("datasec has invalid name")?,
@@ -422,12 +959,35 @@ impl<'s> GenBtf<'s> {
                 }
 
                 let () = vars.push(*var);
+            }
+        }
+        Ok(vars)
+    }
 
-                if let Some(next_ty) = next_type(*var)? {
-                    let () = dependent_types.push(next_ty);
+    /// Check whether `ty` is the type of a struct_ops variable.
+    fn is_struct_ops_type(&self, ty: TypeId) -> Result<bool> {
+        for var in self.struct_ops_vars()? {
+            if let Some(var_ty) = next_type(var)? {
+                if var_ty.type_id() == ty {
+                    return Ok(true);
                 }
             }
         }
+        Ok(false)
+    }
+
+    pub fn struct_ops_type_definition(&self, processed: &mut HashSet<TypeId>) -> Result<String> {
+        let mut def = String::new();
+        let mut dependent_types = vec![];
+
+        // Take all the struct_ops datasec entries and collect their variables
+        // (and dependent types).
+        let vars = self.struct_ops_vars()?;
+        for var in vars.iter() {
+            if let Some(next_ty) = next_type(*var)? {
+                let () = dependent_types.push(next_ty);
+            }
+        }
 
         // Emit a single struct_ops definition containing all variables
         // discovered earlier.
@@ -522,7 +1082,7 @@ impl struct_ops {{
         t: types::Composite<'_>,
     ) -> Result<()> {
         let opts = TypeDeclOpts {
-            func_type: "std::ffi::c_void",
+            func_type: "core::ffi::c_void",
         };
         self.type_definition_for_composites_with_opts(def, dependent_types, t, &opts)
     }
@@ -544,14 +1104,51 @@ impl struct_ops {{
         let mut impl_default: Vec<String> = Vec::new(); // output for impl Default
         let mut gen_impl_default = false; // whether to output impl Default or use #[derive]
 
+        // bitfields not yet backed by storage
+        let mut bitfields: Vec<BitField<'_>> = Vec::new();
+        // getters and setters of bitfields
+        let mut accessors = String::new();
+
+        // number of unnamed (non-bitfield) members
+        let mut anon_members = 0;
+
+        // union members that can be read safely, and the ones to include
+        // in the union's `Debug` representation
+        let readable = if t.is_struct {
+            Vec::new()
+        } else {
+            readable_union_members(&t, &self.btf)?
+        };
+        let mut debug_fields = Vec::new();
+        // the enum member preceding the current member, a potential
+        // discriminant of a union member following it
+        let mut discriminant = None;
+        // tagged views of union members
+        let mut views = String::new();
+
         let mut offset = 0; // In bytes
-        for member in t.iter() {
+        for (idx, member) in t.iter().enumerate() {
             let member_offset = match member.attr {
                 MemberAttr::Normal { offset } => offset,
-                // Bitfields are tricky to get correct, if at all possible. For
-                // now we just skip them, which results in them being covered by
-                // padding bytes.
-                MemberAttr::BitField { .. } => continue,
+                // Bitfields are backed by opaque byte array storage (which
+                // is emitted as padding) and accessed via generated getters
+                // and setters. Unnamed bitfields only serve as padding and
+                // bitfields in unions are not supported.
+                MemberAttr::BitField { size, offset } => {
+                    if let (true, Some(name)) = (t.is_struct, member.name) {
+                        let ty = self
+                            .type_by_id::<BtfType<'_>>(member.ty)
+                            .unwrap()
+                            .skip_mods_and_typedefs();
+                        let () = bitfields.push(BitField {
+                            name: escape_reserved_keyword(name.to_string_lossy()),
+                            ty,
+                            size,
+                            offset,
+                        });
+                    }
+                    continue;
+                }
             };
 
             let field_ty = self
@@ -564,22 +1161,29 @@ impl struct_ops {{
             let field_name = if let Some(name) = member.name {
                 escape_reserved_keyword(name.to_string_lossy())
             } else {
-                // Only anonymous unnamed unions should ever have no name set.
-                // We just name them the same as their anonymous type. As there
-                // can only be one member of this very type, there can't be a
-                // conflict.
-                self.anon_types.type_name_or_anon(&field_ty)
+                // Only anonymous unnamed structs and unions should ever have
+                // no name set. We number them, the same way their types are
+                // named.
+                anon_members += 1;
+                Cow::Owned(format!("{ANON_PREFIX}{anon_members}"))
             };
 
             // Add padding as necessary
             if t.is_struct {
-                let padding = required_padding(
+                let padding = storage_padding(
                     offset,
                     member_offset as usize / 8,
                     &self.type_by_id::<BtfType<'_>>(member.ty).unwrap(),
                     packed,
+                    !bitfields.is_empty(),
                 )?;
 
+                for bitfield in bitfields.drain(..) {
+                    let storage = format!("__pad_{offset}");
+                    let acc = bitfield.accessors(&self.anon_types, &storage, offset, padding)?;
+                    let () = accessors.push_str(&acc);
+                }
+
                 if padding != 0 {
                     agg_content.push(format!(r#"    pub __pad_{offset}: [u8; {padding}],"#,));
 
@@ -613,7 +1217,7 @@ impl struct_ops {{
             match self.type_default(field_ty) {
                 Ok(mut def) => {
                     if is_unsafe(field_ty) {
-                        def = format!("std::mem::MaybeUninit::new({def})")
+                        def = format!("core::mem::MaybeUninit::new({def})")
                     }
 
                     impl_default.push(format!(
@@ -633,17 +1237,65 @@ impl struct_ops {{
 
             let field_ty_str = type_declaration_impl(field_ty, &self.anon_types, opts)?;
             let field_ty_str = if is_unsafe(field_ty) {
-                Cow::Owned(format!("std::mem::MaybeUninit<{field_ty_str}>"))
+                Cow::Owned(format!("core::mem::MaybeUninit<{field_ty_str}>"))
             } else {
                 Cow::Borrowed(field_ty_str.as_str())
             };
 
-            agg_content.push(format!(r#"    pub {field_name}: {field_ty_str},"#));
+            if !t.is_struct {
+                let name = field_name.trim_start_matches("r#");
+                if readable[idx] {
+                    write!(
+                        accessors,
+                        r#"
+    pub fn {field_name}(&self) -> {field_ty_str} {{
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe {{ self.{field_name} }}
+    }}
+"#
+                    )?;
+                    let () = debug_fields.push((name.to_string(), field_name.to_string()));
+                }
+                if !is_unsafe(field_ty) {
+                    write!(
+                        accessors,
+                        r#"
+    pub fn set_{name}(&mut self, value: {field_ty_str}) {{
+        self.{field_name} = value;
+    }}
+"#
+                    )?;
+                }
+            } else if !packed {
+                if let (Some((tag_name, tag)), Some(union)) = (
+                    discriminant.take(),
+                    self.type_by_id::<types::Union<'_>>(field_ty.type_id()),
+                ) {
+                    if let Some((view, accessor)) =
+                        self.tagged_union_view(&t, tag_name, tag, &field_name, union, opts)?
+                    {
+                        let () = views.push_str(&view);
+                        let () = accessors.push_str(&accessor);
+                    }
+                }
+                discriminant = self
+                    .type_by_id::<types::Enum<'_>>(field_ty.type_id())
+                    .map(|tag| (field_name.clone(), tag));
+            }
+
+            let doc = self.doc_comment(t.type_id(), Some(idx as u32), "    ");
+            agg_content.push(format!(r#"{doc}    pub {field_name}: {field_ty_str},"#));
         }
 
         if t.is_struct {
             let struct_size = t.size();
-            let padding = required_padding(offset, struct_size, &t, packed)?;
+            let padding = storage_padding(offset, struct_size, &t, packed, !bitfields.is_empty())?;
+            for bitfield in bitfields.drain(..) {
+                let storage = format!("__pad_{offset}");
+                let acc = bitfield.accessors(&self.anon_types, &storage, offset, padding)?;
+                let () = accessors.push_str(&acc);
+            }
             if padding != 0 {
                 agg_content.push(format!(r#"    pub __pad_{offset}: [u8; {padding}],"#,));
                 impl_default.push(format!(
@@ -656,13 +1308,18 @@ impl struct_ops {{
             }
         }
 
-        if !gen_impl_default && t.is_struct {
-            writeln!(def, r#"#[derive(Debug, Default, Copy, Clone)]"#)?;
+        let derives = if !gen_impl_default && t.is_struct {
+            "Debug, Default, Copy, Clone"
         } else if t.is_struct {
-            writeln!(def, r#"#[derive(Debug, Copy, Clone)]"#)?;
+            "Debug, Copy, Clone"
         } else {
-            writeln!(def, r#"#[derive(Copy, Clone)]"#)?;
-        }
+            "Copy, Clone"
+        };
+        write!(def, "{}", self.doc_comment(t.type_id(), None, ""))?;
+        // Custom derives are not applied to types most derives can't
+        // support (see `is_derivable`) and struct_ops types.
+        let custom = is_derivable(*t, &self.btf)? && !self.is_struct_ops_type(t.type_id())?;
+        let () = self.write_derives(def, derives, custom)?;
 
         let aggregate_type = if t.is_struct { "struct" } else { "union" };
         let packed_repr = if packed { ", packed" } else { "" };
@@ -703,14 +1360,27 @@ impl struct_ops {{
             // write a Debug implementation for a union
             writeln!(
                 def,
-                r#"impl std::fmt::Debug for {} {{"#,
+                r#"impl core::fmt::Debug for {} {{"#,
                 self.anon_types.type_name_or_anon(&t),
             )?;
             writeln!(
                 def,
-                r#"    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{"#
+                r#"    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {{"#
+            )?;
+            writeln!(
+                def,
+                r#"        f.debug_struct("{}")"#,
+                self.anon_types.type_name_or_anon(&t),
             )?;
-            writeln!(def, r#"        write!(f, "(???)")"#)?;
+            let finish = if debug_fields.len() == t.iter().len() {
+                "finish"
+            } else {
+                "finish_non_exhaustive"
+            };
+            for (name, field_name) in debug_fields {
+                writeln!(def, r#"            .field("{name}", &self.{field_name}())"#)?;
+            }
+            writeln!(def, r#"            .{finish}()"#)?;
             writeln!(def, r#"    }}"#)?;
             writeln!(def, r#"}}"#)?;
 
@@ -731,51 +1401,188 @@ impl struct_ops {{
             writeln!(def, r#"    }}"#)?;
             writeln!(def, r#"}}"#)?;
         }
+
+        if !accessors.is_empty() {
+            writeln!(
+                def,
+                r#"impl {} {{{accessors}}}"#,
+                self.anon_types.type_name_or_anon(&t),
+            )?;
+        }
+        write!(def, "{views}")?;
+
+        if (self.type_opts.plain || self.type_opts.bytemuck) && is_pod(*t, &self.btf)? {
+            let name = self.anon_types.type_name_or_anon(&t);
+            if self.type_opts.plain {
+                writeln!(def, r#"unsafe impl plain::Plain for {name} {{}}"#)?;
+            }
+            if self.type_opts.bytemuck {
+                writeln!(def, r#"unsafe impl bytemuck::Zeroable for {name} {{}}"#)?;
+                writeln!(def, r#"unsafe impl bytemuck::Pod for {name} {{}}"#)?;
+            }
+        }
         Ok(())
     }
 
-    fn type_definition_for_enums(&self, def: &mut String, t: types::Enum<'_>) -> Result<()> {
-        let repr_size = match t.size() {
-            1 => "8",
-            2 => "16",
-            4 => "32",
-            8 => "64",
-            16 => "128",
-            _ => bail!("Invalid enum size: {}", t.size()),
-        };
-
-        let mut signed = "u";
-        for value in t.iter() {
-            if value.value < 0 {
-                signed = "i";
-                break;
+    /// Generate a tagged view of the union member `union_name` of struct
+    /// `t`, discriminated by the directly preceding enum member `tag_name`.
+    ///
+    /// A view is only generated if every member of the union can be read
+    /// safely and corresponds to exactly one enum value, by the enum
+    /// value's name being the member's name, optionally prefixed (e.g.,
+    /// `EVENT_EXEC` for member `exec`). The type definition of the view
+    /// and an accessor method producing it are returned.
+    fn tagged_union_view(
+        &self,
+        t: &types::Composite<'_>,
+        tag_name: Cow<'_, str>,
+        tag: types::Enum<'_>,
+        union_name: &str,
+        union: types::Union<'_>,
+        opts: &TypeDeclOpts,
+    ) -> Result<Option<(String, String)>> {
+        let union = types::Composite::from(union);
+        let readable = readable_union_members(&union, &self.btf)?;
+
+        let mut variants = Vec::new();
+        let mut values = HashSet::new();
+        for (member, readable) in union.iter().zip(readable) {
+            let name = match member.name {
+                Some(name) if readable => name.to_string_lossy(),
+                _ => return Ok(None),
+            };
+            let suffix = format!("_{name}");
+            let mut matching = tag
+                .iter()
+                .filter(|value| {
+                    let value_name = value.name.unwrap().to_string_lossy().to_lowercase();
+                    value_name == name || value_name.ends_with(&suffix)
+                })
+                .map(|value| value.value)
+                .collect::<Vec<_>>();
+            let () = matching.sort_unstable();
+            let () = matching.dedup();
+
+            match matching.as_slice() {
+                [value] if values.insert(*value) && name != "Unknown" => {
+                    let member_ty = self
+                        .type_by_id::<BtfType<'_>>(member.ty)
+                        .unwrap()
+                        .skip_mods_and_typedefs();
+                    let ty = type_declaration_impl(member_ty, &self.anon_types, opts)?;
+                    let () = variants.push((*value, escape_reserved_keyword(name), ty));
+                }
+                _ => return Ok(None),
             }
         }
 
+        let struct_name = self.anon_types.type_name_or_anon(t);
+        let tag_ty = self.anon_types.type_name_or_anon(&tag);
+        let union_field = union_name.trim_start_matches("r#");
+        let view_name = format!("{struct_name}__{union_field}_view");
+
+        let mut view = String::new();
         writeln!(
-            def,
-            r#"#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]"#
+            view,
+            r#"/// A view of `{struct_name}::{union_field}`, as discriminated by `{struct_name}::{tag_field}`."#,
+            tag_field = tag_name.trim_start_matches("r#"),
         )?;
-        writeln!(def, r#"#[repr({signed}{repr_size})]"#)?;
-        writeln!(
-            def,
-            r#"pub enum {name} {{"#,
-            name = self.anon_types.type_name_or_anon(&t),
+        writeln!(view, r#"#[derive(Debug, Copy, Clone)]"#)?;
+        writeln!(view, r#"pub enum {view_name} {{"#)?;
+        for (_value, name, ty) in &variants {
+            writeln!(view, r#"    {name}({ty}),"#)?;
+        }
+        writeln!(view, r#"    Unknown({tag_ty}),"#)?;
+        writeln!(view, r#"}}"#)?;
+
+        let mut accessor = String::new();
+        write!(
+            accessor,
+            r#"
+    pub fn {union_field}_view(&self) -> {view_name} {{
+        match self.{tag_name}.0 {{
+"#
+        )?;
+        for (value, name, _ty) in &variants {
+            writeln!(
+                accessor,
+                r#"            {value} => {view_name}::{name}(self.{union_name}.{name}()),"#
+            )?;
+        }
+        write!(
+            accessor,
+            r#"            _ => {view_name}::Unknown(self.{tag_name}),
+        }}
+    }}
+"#
         )?;
 
-        for (i, value) in t.iter().enumerate() {
-            if i == 0 {
-                writeln!(def, r#"    #[default]"#)?;
-            }
+        Ok(Some((view, accessor)))
+    }
+
+    fn type_definition_for_enums(&self, def: &mut String, t: types::Enum<'_>) -> Result<()> {
+        let repr = enum_repr(&t)?;
+        let name = self.anon_types.type_name_or_anon(&t);
+
+        // Enums are represented as newtypes with associated constants and
+        // not as Rust enums, because the latter would be undefined
+        // behavior for values not covered by any variant, which BPF
+        // programs or the kernel may very well produce.
+        let () = self.write_derives(def, "Debug, Copy, Clone, PartialEq, Eq", true)?;
+        writeln!(def, r#"#[repr(transparent)]"#)?;
+        writeln!(def, r#"pub struct {name}(pub {repr});"#)?;
+
+        writeln!(def, r#"#[allow(non_upper_case_globals)]"#)?;
+        writeln!(def, r#"impl {name} {{"#)?;
+        for value in t.iter() {
             writeln!(
                 def,
-                r#"    {name} = {value},"#,
-                name = value.name.unwrap().to_string_lossy(),
+                r#"    pub const {value_name}: {name} = {name}({value});"#,
+                value_name = escape_reserved_keyword(value.name.unwrap().to_string_lossy()),
                 value = value.value,
             )?;
         }
-
         writeln!(def, "}}")?;
+
+        let default = match t.iter().next() {
+            Some(value) => format!(
+                "{name}::{}",
+                escape_reserved_keyword(value.name.unwrap().to_string_lossy())
+            ),
+            None => format!("{name}(0)"),
+        };
+        writeln!(def, r#"impl Default for {name} {{"#)?;
+        writeln!(def, r#"    fn default() -> Self {{"#)?;
+        writeln!(def, r#"        {default}"#)?;
+        writeln!(def, r#"    }}"#)?;
+        writeln!(def, r#"}}"#)?;
+
+        writeln!(def, r#"impl core::fmt::Display for {name} {{"#)?;
+        writeln!(
+            def,
+            r#"    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {{"#
+        )?;
+        writeln!(def, r#"        match self.0 {{"#)?;
+        let mut seen = HashSet::new();
+        for value in t.iter() {
+            // Multiple names may share the same value; the first one
+            // wins.
+            if seen.insert(value.value) {
+                writeln!(
+                    def,
+                    r#"            {value} => write!(f, "{value_name}"),"#,
+                    value = value.value,
+                    value_name = value.name.unwrap().to_string_lossy(),
+                )?;
+            }
+        }
+        writeln!(
+            def,
+            r#"            value => write!(f, "{name}({{value}})"),"#
+        )?;
+        writeln!(def, r#"        }}"#)?;
+        writeln!(def, r#"    }}"#)?;
+        writeln!(def, r#"}}"#)?;
         Ok(())
     }
 
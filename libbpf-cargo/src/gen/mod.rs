@@ -1,4 +1,5 @@
 pub mod btf;
+mod pretty;
 
 use std::borrow::Cow;
 use std::collections::BTreeMap;
@@ -17,6 +18,7 @@ use std::io::Write;
 use std::mem::size_of;
 use std::ops::Deref;
 use std::os::raw::c_ulong;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
@@ -29,16 +31,21 @@ use anyhow::Context;
 use anyhow::Result;
 
 use libbpf_rs::btf::types;
+use libbpf_rs::btf::BtfKind;
+use libbpf_rs::btf::BtfType;
 use libbpf_rs::btf::TypeId;
 use libbpf_rs::libbpf_sys;
 use libbpf_rs::Btf;
+use libbpf_rs::MapType;
 
 use memmap2::Mmap;
 
+use crate::build;
 use crate::metadata;
 use crate::metadata::UnprocessedObj;
 
 use self::btf::GenBtf;
+use self::btf::TypeOpts;
 
 /// Escape certain characters in a "raw" name of a section, for example.
 fn escape_raw_name(name: &str) -> String {
@@ -150,19 +157,92 @@ gen_bpf_object_iter!(
     libbpf_sys::bpf_object__next_program
 );
 
-/// Try running `rustfmt` over `s` and return result.
-///
-/// If no `rustfmt` binary could be found the content is left untouched, as
-/// it's only meant as a cosmetic brush up, without change of semantics.
-fn try_rustfmt<'code>(s: &'code str, rustfmt_path: Option<&PathBuf>) -> Result<Cow<'code, [u8]>> {
-    let result = if let Some(r) = rustfmt_path {
-        Command::new(r)
-    } else {
-        Command::new("rustfmt")
+/// The way generated code gets formatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Formatting {
+    /// Format code using `rustfmt`.
+    ///
+    /// If a path to the binary is provided, it has to exist. Otherwise
+    /// `rustfmt` is searched for in `$PATH` and, if it can't be found,
+    /// the built-in pretty-printer is used instead.
+    Rustfmt(Option<PathBuf>),
+    /// Format code using a minimal built-in pretty-printer, which only
+    /// takes care of indentation.
+    Builtin,
+    /// Do not format code at all.
+    Disabled,
+}
+
+impl Default for Formatting {
+    fn default() -> Self {
+        Self::Rustfmt(None)
     }
-    .stdin(Stdio::piped())
-    .stdout(Stdio::piped())
-    .spawn();
+}
+
+/// The way generated skeletons get hold of the contents of the BPF object
+/// file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ObjectData {
+    /// Embed the contents of the object file into the skeleton.
+    #[default]
+    Embed,
+    /// Reference the object file via `include_bytes!`, so that its contents
+    /// are not part of the generated source code.
+    ///
+    /// The object is referenced by its path relative to the skeleton,
+    /// which consequently has to be written to a file.
+    Include,
+    /// Read the object file at runtime from the provided directory, where
+    /// it is expected under its original file name.
+    ///
+    /// The path can be overridden through the `obj_path` member of the
+    /// generated skeleton builder.
+    Runtime(PathBuf),
+}
+
+/// The directory maps get pinned in by default, matching libbpf's.
+const DEFAULT_PIN_ROOT: &str = "/sys/fs/bpf";
+
+/// Defaults applied to maps and programs when opening a generated
+/// skeleton, as configured in the package metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SkelDefaults {
+    /// The directory to pin maps in, `/sys/fs/bpf` if not set.
+    pub pin_root: Option<PathBuf>,
+    /// The names of the maps to pin, by name, in the pin root.
+    pub pinned_maps: Vec<String>,
+    /// The autoattach setting of programs, by program name.
+    pub autoattach: BTreeMap<String, bool>,
+}
+
+/// Options controlling the generation of skeletons.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct GenOpts {
+    /// Generate a subskeleton instead of a skeleton.
+    pub subskel: bool,
+    /// Generate helpers for asynchronously consuming ring buffers.
+    pub async_helpers: bool,
+    /// Generate only type definitions, usable in `no_std` crates.
+    pub no_std: bool,
+    /// The way the skeleton gets hold of the object file's contents.
+    pub object_data: ObjectData,
+    /// Defaults applied to maps and programs when opening the skeleton.
+    pub skel_defaults: SkelDefaults,
+    /// Omit the `types` and `progs` aliases for the object specific
+    /// modules, as they would be ambiguous when re-exported alongside
+    /// other skeletons.
+    pub no_aliases: bool,
+    /// Options controlling the generation of Rust types.
+    pub type_opts: TypeOpts,
+}
+
+/// Try running `rustfmt` over `s` and return result.
+fn rustfmt<'code>(s: &'code str, rustfmt_path: Option<&Path>) -> Result<Cow<'code, [u8]>> {
+    let rustfmt = rustfmt_path.unwrap_or_else(|| Path::new("rustfmt"));
+    let result = Command::new(rustfmt)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn();
 
     match result {
         Ok(mut cmd) => {
@@ -181,11 +261,21 @@ fn try_rustfmt<'code>(s: &'code str, rustfmt_path: Option<&PathBuf>) -> Result<C
 
             Ok(output.stdout.into())
         }
-        Err(err) if err.kind() == ErrorKind::NotFound => {
-            // No `rustfmt` is present. Just skip formatting altogether.
-            Ok(Cow::Borrowed(s.as_bytes()))
+        Err(err) if err.kind() == ErrorKind::NotFound && rustfmt_path.is_none() => {
+            // No `rustfmt` is present. Formatting is only meant as a
+            // cosmetic brush up, so fall back to our own pretty-printer.
+            Ok(Cow::Owned(pretty::pretty_print(s).into_bytes()))
         }
-        Err(err) => panic!("failed to spawn rustfmt: {err}"),
+        Err(err) => Err(err).with_context(|| format!("failed to spawn `{}`", rustfmt.display())),
+    }
+}
+
+/// Format generated code `s` as requested by `formatting`.
+fn format_code<'code>(s: &'code str, formatting: &Formatting) -> Result<Cow<'code, [u8]>> {
+    match formatting {
+        Formatting::Rustfmt(rustfmt_path) => rustfmt(s, rustfmt_path.as_deref()),
+        Formatting::Builtin => Ok(Cow::Owned(pretty::pretty_print(s).into_bytes())),
+        Formatting::Disabled => Ok(Cow::Borrowed(s.as_bytes())),
     }
 }
 
@@ -279,13 +369,23 @@ fn map_is_readonly(map: *const libbpf_sys::bpf_map) -> bool {
     (unsafe { libbpf_sys::bpf_map__map_flags(map) } & libbpf_sys::BPF_F_RDONLY_PROG) > 0
 }
 
-fn gen_skel_c_skel_constructor(skel: &mut String, object: &BpfObj, name: &str) -> Result<()> {
+fn gen_skel_c_skel_constructor(
+    skel: &mut String,
+    object: &BpfObj,
+    name: &str,
+    object_data: &ObjectData,
+) -> Result<()> {
+    let (param, builder) = match object_data {
+        ObjectData::Embed | ObjectData::Include => ("", "new(DATA)"),
+        ObjectData::Runtime(..) => ("data: Vec<u8>", "from_owned(data)"),
+    };
+
     write!(
         skel,
         r#"
-        fn build_skel_config() -> libbpf_rs::Result<libbpf_rs::__internal_skel::ObjectSkeletonConfig<'static>>
+        fn build_skel_config({param}) -> libbpf_rs::Result<libbpf_rs::__internal_skel::ObjectSkeletonConfig<'static>>
         {{
-            let mut builder = libbpf_rs::__internal_skel::ObjectSkeletonConfigBuilder::new(DATA);
+            let mut builder = libbpf_rs::__internal_skel::ObjectSkeletonConfigBuilder::{builder};
             builder
                 .name("{name}")
         "#,
@@ -331,9 +431,122 @@ fn gen_skel_c_skel_constructor(skel: &mut String, object: &BpfObj, name: &str) -
     Ok(())
 }
 
-fn gen_skel_map_defs(skel: &mut String, object: &BpfObj, obj_name: &str, open: bool) -> Result<()> {
+/// Determine the key and value types of `map` if it supports typed
+/// access, i.e., if it is neither per-CPU nor keyless and both types
+/// are plain old data.
+fn typed_map_types<'b>(
+    btf: &'b GenBtf<'_>,
+    map: *const libbpf_sys::bpf_map,
+) -> Result<Option<(BtfType<'b>, BtfType<'b>)>> {
+    let map_type = MapType::from(unsafe { libbpf_sys::bpf_map__type(map) });
+    if map_type.is_percpu()
+        || matches!(
+            map_type,
+            MapType::Queue | MapType::Stack | MapType::BloomFilter
+        )
+    {
+        return Ok(None);
+    }
+
+    // A type ID of 0 signals that no BTF type is associated.
+    let key_id = unsafe { libbpf_sys::bpf_map__btf_key_type_id(map) };
+    let val_id = unsafe { libbpf_sys::bpf_map__btf_value_type_id(map) };
+    if key_id == 0 || val_id == 0 {
+        return Ok(None);
+    }
+
+    let key_type = btf
+        .type_by_id::<BtfType<'_>>(TypeId::from(key_id))
+        .with_context(|| format!("failed to look up BTF map key type with ID `{key_id}`"))?;
+    let val_type = btf
+        .type_by_id::<BtfType<'_>>(TypeId::from(val_id))
+        .with_context(|| format!("failed to look up BTF map value type with ID `{val_id}`"))?;
+
+    if btf.is_pod(key_type)? && btf.is_pod(val_type)? {
+        Ok(Some((key_type, val_type)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Generate the path of the plain old data type `ty`, as seen from the
+/// skeleton's `imp` module, and collect the named types it is made up of
+/// in `named`.
+fn pod_type_path<'b>(
+    btf: &'b GenBtf<'_>,
+    ty: BtfType<'b>,
+    raw_obj_name: &str,
+    named: &mut Vec<BtfType<'b>>,
+) -> Result<String> {
+    let ty = ty.skip_mods_and_typedefs();
+    if let Ok(array) = types::Array::try_from(ty) {
+        let elem = pod_type_path(btf, array.contained_type(), raw_obj_name, named)?;
+        return Ok(format!("[{elem}; {}]", array.capacity()));
+    }
+
+    let decl = btf.type_declaration(ty)?;
+    match ty.kind() {
+        BtfKind::Struct | BtfKind::Union | BtfKind::Enum => {
+            let () = named.push(ty);
+            Ok(format!("{raw_obj_name}_types::{decl}"))
+        }
+        _ => Ok(decl),
+    }
+}
+
+/// Generate `libbpf_rs::Pod` implementations for the named types that
+/// typed map accessors refer to.
+fn gen_skel_map_pod_impls(skel: &mut String, object: &BpfObj, raw_obj_name: &str) -> Result<()> {
+    let btf = match Btf::from_bpf_object(object)? {
+        Some(btf) => GenBtf::from(btf),
+        None => return Ok(()),
+    };
+
+    let mut named = Vec::new();
+    for map in MapIter::new(object.as_ptr()) {
+        if let Some((key_type, val_type)) = typed_map_types(&btf, map)? {
+            for ty in [key_type, val_type] {
+                // We are only interested in the named types here.
+                let _path = pod_type_path(&btf, ty, raw_obj_name, &mut named)?;
+            }
+        }
+    }
+
+    let mut processed = HashSet::new();
+    for ty in named {
+        if processed.insert(ty.type_id()) {
+            writeln!(
+                skel,
+                r#"unsafe impl libbpf_rs::Pod for {raw_obj_name}_types::{} {{}}"#,
+                btf.type_declaration(ty)?
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Iterate over the maps of `object` that a skeleton provides accessors
+/// for.
+///
+/// Subskeletons exclude internal maps, as their names are derived from
+/// the name of the final object.
+fn skel_maps(object: &BpfObj, subskel: bool) -> impl Iterator<Item = *mut libbpf_sys::bpf_map> {
+    MapIter::new(object.as_ptr())
+        .filter(move |map| !subskel || unsafe { !libbpf_sys::bpf_map__is_internal(*map) })
+}
+
+fn gen_skel_map_defs(
+    skel: &mut String,
+    object: &BpfObj,
+    obj_name: &str,
+    raw_obj_name: &str,
+    open: bool,
+    subskel: bool,
+) -> Result<()> {
+    let btf = Btf::from_bpf_object(object)?.map(GenBtf::from);
+
     let mut gen = |mutable| -> Result<()> {
-        if MapIter::new(object.as_ptr()).next().is_none() {
+        if skel_maps(object, subskel).next().is_none() {
             return Ok(());
         }
 
@@ -368,25 +581,52 @@ fn gen_skel_map_defs(skel: &mut String, object: &BpfObj, obj_name: &str, open: b
             "#,
         )?;
 
-        for map in MapIter::new(object.as_ptr()) {
+        for map in skel_maps(object, subskel) {
             let map_name = match get_map_name(map)? {
                 Some(n) => n,
                 None => continue,
             };
 
+            let raw_map_name = get_raw_map_name(map)?;
+            let doc = btf
+                .as_ref()
+                .map(|btf| btf.doc_comment_by_name(&raw_map_name, ""))
+                .unwrap_or_default();
             write!(
                 skel,
                 r#"
+                {doc}
                 pub fn {map_name}(&{mut_prefix}self) -> &{mut_prefix}{return_ty} {{
                     self.inner.{map_fn}("{raw_map_name}").unwrap()
                 }}
                 "#,
                 map_name = map_name,
-                raw_map_name = get_raw_map_name(map)?,
+                raw_map_name = raw_map_name,
                 return_ty = return_ty,
                 mut_prefix = mut_prefix,
                 map_fn = map_fn
             )?;
+
+            // Typed accessors are only provided for loaded maps, as only
+            // those can be accessed.
+            let btf = match (&btf, open, mutable) {
+                (Some(btf), false, false) => btf,
+                _ => continue,
+            };
+
+            if let Some((key_type, val_type)) = typed_map_types(btf, map)? {
+                let mut named = Vec::new();
+                write!(
+                    skel,
+                    r#"
+                    pub fn {map_name}_typed(&self) -> libbpf_rs::Result<libbpf_rs::TypedMap<'_, {key_ty}, {val_ty}>> {{
+                        libbpf_rs::TypedMap::new(self.{map_name}())
+                    }}
+                    "#,
+                    key_ty = pod_type_path(btf, key_type, raw_obj_name, &mut named)?,
+                    val_ty = pod_type_path(btf, val_type, raw_obj_name, &mut named)?,
+                )?;
+            }
         }
 
         writeln!(skel, "}}")?;
@@ -399,10 +639,78 @@ fn gen_skel_map_defs(skel: &mut String, object: &BpfObj, obj_name: &str, open: b
     Ok(())
 }
 
+fn get_prog_section(prog: *const libbpf_sys::bpf_program) -> Result<String> {
+    let section_ptr = unsafe { libbpf_sys::bpf_program__section_name(prog) };
+    ensure!(!section_ptr.is_null(), "Prog section unknown");
+
+    Ok(unsafe { CStr::from_ptr(section_ptr) }.to_str()?.to_string())
+}
+
+/// Generate constants for the names and sections of the object's
+/// programs.
+fn gen_skel_prog_consts(skel: &mut String, object: &BpfObj, raw_obj_name: &str) -> Result<()> {
+    // Program names become constants in the `names` and `sections`
+    // modules, where they can't clash with anything else. Names only
+    // differing in case would map to the same constant, though.
+    let mut progs = BTreeMap::<String, String>::new();
+    let mut names = String::new();
+    let mut sections = String::new();
+    let mut all = Vec::new();
+    for prog in ProgIter::new(object.as_ptr()) {
+        let prog_name = get_prog_name(prog)?;
+        let section = get_prog_section(prog)?;
+        let const_name = prog_name.to_uppercase();
+        if let Some(other) = progs.insert(const_name.clone(), prog_name.clone()) {
+            bail!("programs `{other}` and `{prog_name}` would both map to constant `{const_name}`");
+        }
+
+        write!(
+            names,
+            r#"
+            /// The name of the `{prog_name}` program.
+            pub const {const_name}: &str = "{prog_name}";
+            "#
+        )?;
+        write!(
+            sections,
+            r#"
+            /// The section of the `{prog_name}` program.
+            pub const {const_name}: &str = {section:?};
+            "#
+        )?;
+        let () = all.push(format!("names::{const_name}"));
+    }
+
+    write!(
+        skel,
+        r#"
+        /// Names and sections of the object's programs.
+        pub mod {raw_obj_name}_progs {{
+            /// The names of the object's programs.
+            pub mod names {{
+                {names}
+            }}
+
+            /// The sections of the object's programs.
+            pub mod sections {{
+                {sections}
+            }}
+
+            /// The names of all programs, in the order they appear in the
+            /// object.
+            pub const ALL: &[&str] = &[{all}];
+        }}
+        "#,
+        all = all.join(", "),
+    )?;
+    Ok(())
+}
+
 fn gen_skel_prog_defs(
     skel: &mut String,
     object: &BpfObj,
     obj_name: &str,
+    raw_obj_name: &str,
     open: bool,
     mutable: bool,
 ) -> Result<()> {
@@ -416,15 +724,17 @@ fn gen_skel_prog_defs(
         ("", "", "prog")
     };
 
-    let (struct_name, inner_ty, return_ty) = if open {
+    let (struct_name, iter_name, inner_ty, return_ty) = if open {
         (
             format!("Open{obj_name}Progs{struct_suffix}"),
+            format!("Open{obj_name}ProgramsIter"),
             "libbpf_rs::OpenObject",
             "libbpf_rs::OpenProgram",
         )
     } else {
         (
             format!("{obj_name}Progs{struct_suffix}"),
+            format!("{obj_name}ProgramsIter"),
             "libbpf_rs::Object",
             "libbpf_rs::Program",
         )
@@ -441,22 +751,59 @@ fn gen_skel_prog_defs(
         "#,
     )?;
 
+    let btf = Btf::from_bpf_object(object)?.map(GenBtf::from);
     for prog in ProgIter::new(object.as_ptr()) {
+        let prog_name = get_prog_name(prog)?;
+        let doc = btf
+            .as_ref()
+            .map(|btf| btf.doc_comment_by_name(&prog_name, ""))
+            .unwrap_or_default();
         write!(
             skel,
             r#"
+            {doc}
             pub fn {prog_name}(&{mut_prefix}self) -> &{mut_prefix}{return_ty} {{
                 self.inner.{prog_fn}("{prog_name}").unwrap()
             }}
             "#,
-            prog_name = get_prog_name(prog)?,
             return_ty = return_ty,
             mut_prefix = mut_prefix,
             prog_fn = prog_fn
         )?;
     }
 
-    writeln!(skel, "}}")?;
+    if mutable {
+        writeln!(skel, "}}")?;
+        return Ok(());
+    }
+
+    write!(
+        skel,
+        r#"
+            /// Iterate over all programs along with their names.
+            pub fn iter(&self) -> {iter_name}<'_> {{
+                {iter_name} {{
+                    inner: self.inner,
+                    names: {raw_obj_name}_progs::ALL.iter(),
+                }}
+            }}
+        }}
+
+        pub struct {iter_name}<'a> {{
+            inner: &'a {inner_ty},
+            names: core::slice::Iter<'static, &'static str>,
+        }}
+
+        impl<'a> Iterator for {iter_name}<'a> {{
+            type Item = (&'static str, &'a {return_ty});
+
+            fn next(&mut self) -> Option<Self::Item> {{
+                let name = *self.names.next()?;
+                Some((name, self.inner.{prog_fn}(name).unwrap()))
+            }}
+        }}
+        "#,
+    )?;
 
     Ok(())
 }
@@ -465,13 +812,15 @@ fn gen_skel_datasec_types(
     skel: &mut String,
     object: &BpfObj,
     processed: &mut HashSet<TypeId>,
+    type_opts: &TypeOpts,
 ) -> Result<()> {
     let btf = if let Some(btf) = Btf::from_bpf_object(object)? {
         btf
     } else {
         return Ok(());
     };
-    let btf = GenBtf::from(btf);
+    let mut btf = GenBtf::from(btf);
+    let () = btf.set_type_opts(type_opts.clone());
 
     for ty in btf.type_by_kind::<types::DataSec<'_>>() {
         let name = match ty.name() {
@@ -496,9 +845,11 @@ fn gen_skel_struct_ops_types(
     skel: &mut String,
     object: &BpfObj,
     processed: &mut HashSet<TypeId>,
+    type_opts: &TypeOpts,
 ) -> Result<()> {
     if let Some(btf) = Btf::from_bpf_object(object)? {
-        let btf = GenBtf::from(btf);
+        let mut btf = GenBtf::from(btf);
+        let () = btf.set_type_opts(type_opts.clone());
 
         let def = btf.struct_ops_type_definition(processed)?;
         write!(skel, "{def}")?;
@@ -519,13 +870,15 @@ fn gen_skel_map_types(
     skel: &mut String,
     object: &BpfObj,
     processed: &mut HashSet<TypeId>,
+    type_opts: &TypeOpts,
 ) -> Result<()> {
     let btf = if let Some(btf) = Btf::from_bpf_object(object)? {
         btf
     } else {
         return Ok(());
     };
-    let btf = GenBtf::from(btf);
+    let mut btf = GenBtf::from(btf);
+    let () = btf.set_type_opts(type_opts.clone());
 
     for map in MapIter::new(object.as_ptr()) {
         // If not defined or on error, the reported BTF type ID will be 0, which
@@ -554,9 +907,10 @@ fn gen_skel_map_getters(
     object: &BpfObj,
     obj_name: &str,
     open: bool,
+    subskel: bool,
 ) -> Result<()> {
     let mut gen = |mutable| -> Result<()> {
-        if MapIter::new(object.as_ptr()).next().is_none() {
+        if skel_maps(object, subskel).next().is_none() {
             return Ok(());
         }
 
@@ -702,8 +1056,27 @@ fn gen_skel_datasec_getters(
     Ok(())
 }
 
+/// Retrieve the names of the object's `struct_ops` maps.
+fn struct_ops_map_names(object: &BpfObj) -> Result<Vec<String>> {
+    MapIter::new(object.as_ptr())
+        .filter(
+            |map| unsafe { libbpf_sys::bpf_map__type(*map) } == libbpf_sys::BPF_MAP_TYPE_STRUCT_OPS,
+        )
+        .map(|map| get_raw_map_name(map))
+        .collect()
+}
+
+/// Check whether the skeleton of `object` has links, i.e., whether the
+/// object contains programs or `struct_ops` maps.
+fn has_links(object: &BpfObj) -> Result<bool> {
+    Ok(
+        ProgIter::new(object.as_ptr()).next().is_some()
+            || !struct_ops_map_names(object)?.is_empty(),
+    )
+}
+
 fn gen_skel_link_defs(skel: &mut String, object: &BpfObj, obj_name: &str) -> Result<()> {
-    if ProgIter::new(object.as_ptr()).next().is_none() {
+    if !has_links(object)? {
         return Ok(());
     }
 
@@ -724,13 +1097,23 @@ fn gen_skel_link_defs(skel: &mut String, object: &BpfObj, obj_name: &str) -> Res
         )?;
     }
 
+    // `struct_ops` maps are global variables and, hence, share a name
+    // space with programs.
+    for map_name in struct_ops_map_names(object)? {
+        write!(
+            skel,
+            r#"pub {map_name}: Option<libbpf_rs::Link>,
+            "#,
+        )?;
+    }
+
     writeln!(skel, "}}")?;
 
     Ok(())
 }
 
 fn gen_skel_link_getter(skel: &mut String, object: &BpfObj, obj_name: &str) -> Result<()> {
-    if ProgIter::new(object.as_ptr()).next().is_none() {
+    if !has_links(object)? {
         return Ok(());
     }
 
@@ -757,15 +1140,20 @@ fn open_bpf_object(name: &str, data: &[u8]) -> Result<BpfObj> {
             &obj_opts,
         )
     };
+    ensure!(
+        !object.is_null() || !build::is_foreign_endian(data),
+        "Failed to bpf_object__open_mem(): opening objects of foreign endianness requires libbpf 1.5 or later"
+    );
     ensure!(!object.is_null(), "Failed to bpf_object__open_mem()");
 
     Ok(BpfObj(ptr::NonNull::new(object).unwrap()))
 }
 
 fn gen_skel_attach(skel: &mut String, object: &BpfObj, obj_name: &str) -> Result<()> {
-    if ProgIter::new(object.as_ptr()).next().is_none() {
+    if !has_links(object)? {
         return Ok(());
     }
+    let struct_ops_maps = struct_ops_map_names(object)?;
 
     write!(
         skel,
@@ -791,19 +1179,211 @@ fn gen_skel_attach(skel: &mut String, object: &BpfObj, obj_name: &str) -> Result
         )?;
     }
 
+    for map_name in &struct_ops_maps {
+        write!(
+            skel,
+            r#"{map_name}: self.links.{map_name}.take(),
+            "#
+        )?;
+    }
+
     write!(
         skel,
         r#"
             }};
+        "#,
+    )?;
 
+    for map_name in &struct_ops_maps {
+        write!(
+            skel,
+            r#"
+            if self.links.{map_name}.is_none() {{
+                self.links.{map_name} = Some(self.obj.map("{map_name}").unwrap().attach_struct_ops()?);
+            }}
+            "#
+        )?;
+    }
+
+    write!(
+        skel,
+        r#"
             Ok(())
         }}
+
+        fn attach_partial(&mut self) -> libbpf_rs::skel::AttachReport {{
+            let mut report = libbpf_rs::skel::AttachReport::default();
+        "#,
+    )?;
+
+    for prog in ProgIter::new(object.as_ptr()) {
+        let prog_name = get_prog_name(prog)?;
+
+        write!(
+            skel,
+            r#"libbpf_rs::__internal_skel::attach_prog_partial(
+                &mut report,
+                self.obj.prog_mut("{prog_name}").unwrap(),
+                &mut self.links.{prog_name},
+            );
+            "#
+        )?;
+    }
+
+    for map_name in &struct_ops_maps {
+        write!(
+            skel,
+            r#"libbpf_rs::__internal_skel::attach_struct_ops_partial(
+                &mut report,
+                self.obj.map("{map_name}").unwrap(),
+                &mut self.links.{map_name},
+            );
+            "#
+        )?;
+    }
+
+    write!(
+        skel,
+        r#"
+            report
+        }}
         "#,
     )?;
 
     Ok(())
 }
 
+/// Generate helpers for asynchronously consuming the events submitted to
+/// each of the object's ring buffers.
+///
+/// The generated code requires the `async` feature of `libbpf-rs`.
+fn gen_skel_async_helpers(skel: &mut String, object: &BpfObj) -> Result<()> {
+    for map in MapIter::new(object.as_ptr()) {
+        let type_ = unsafe { libbpf_sys::bpf_map__type(map) };
+        if type_ != libbpf_sys::BPF_MAP_TYPE_RINGBUF {
+            continue;
+        }
+
+        let map_name = match get_map_name(map)? {
+            Some(n) => n,
+            None => continue,
+        };
+
+        write!(
+            skel,
+            r#"
+            pub fn consume_{map_name}_events<T: libbpf_rs::Pod>(&self) -> libbpf_rs::Result<libbpf_rs::RingBufferStream<T>> {{
+                libbpf_rs::RingBufferStream::new(self.maps().{map_name}())
+            }}
+            "#,
+        )?;
+    }
+    Ok(())
+}
+
+/// Find pairs of ring buffer and perf buffer maps declaring the same
+/// events, following the `<name>_ringbuf` and `<name>_perfbuf` naming
+/// convention.
+fn event_source_pairs(object: &BpfObj) -> Result<Vec<String>> {
+    let mut ringbufs = Vec::new();
+    let mut perfbufs = HashSet::new();
+
+    for map in MapIter::new(object.as_ptr()) {
+        let type_ = unsafe { libbpf_sys::bpf_map__type(map) };
+        let raw_name = get_raw_map_name(map)?;
+
+        if type_ == libbpf_sys::BPF_MAP_TYPE_RINGBUF {
+            if let Some(base) = raw_name.strip_suffix("_ringbuf") {
+                ringbufs.push(base.to_string());
+            }
+        } else if type_ == libbpf_sys::BPF_MAP_TYPE_PERF_EVENT_ARRAY {
+            if let Some(base) = raw_name.strip_suffix("_perfbuf") {
+                let _inserted = perfbufs.insert(base.to_string());
+            }
+        }
+    }
+
+    ringbufs.retain(|base| perfbufs.contains(base));
+    Ok(ringbufs)
+}
+
+fn gen_skel_event_source_helpers(skel: &mut String, object: &BpfObj, open: bool) -> Result<()> {
+    for base in event_source_pairs(object)? {
+        if open {
+            write!(
+                skel,
+                r#"
+                pub fn configure_{base}_events(&mut self, kind: libbpf_rs::EventSourceKind) -> libbpf_rs::Result<()> {{
+                    let () = kind.configure(self.obj.map_mut("{base}_ringbuf").unwrap())?;
+                    kind.configure(self.obj.map_mut("{base}_perfbuf").unwrap())
+                }}
+                "#,
+            )?;
+        } else {
+            write!(
+                skel,
+                r#"
+                #[allow(single_use_lifetimes)]
+                pub fn {base}_events<'cb>(&self) -> libbpf_rs::EventSourceBuilder<'_, 'cb> {{
+                    let map = self
+                        .obj
+                        .map("{base}_ringbuf")
+                        .or_else(|| self.obj.map("{base}_perfbuf"))
+                        .unwrap();
+                    libbpf_rs::EventSourceBuilder::new(map)
+                }}
+                "#,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Generate the code applying `defaults` to a freshly opened skeleton.
+fn gen_skel_open_defaults(object: &BpfObj, defaults: &SkelDefaults) -> Result<String> {
+    let mut def = String::new();
+
+    let pin_root = defaults
+        .pin_root
+        .as_deref()
+        .unwrap_or_else(|| Path::new(DEFAULT_PIN_ROOT));
+    for name in &defaults.pinned_maps {
+        let found = MapIter::new(object.as_ptr())
+            .map(|map| get_raw_map_name(map))
+            .any(|raw_name| raw_name.is_ok_and(|raw_name| &raw_name == name));
+        ensure!(found, "map `{name}` configured to be pinned does not exist");
+
+        let path = pin_root.join(name);
+        let path = path
+            .to_str()
+            .with_context(|| format!("pin path `{}` is not valid UTF-8", path.display()))?;
+        write!(
+            def,
+            r#"
+            let () = skel.obj.map_mut("{name}").unwrap().set_pin_path({path:?})?;
+            "#,
+        )?;
+    }
+
+    for (name, autoattach) in &defaults.autoattach {
+        let found = ProgIter::new(object.as_ptr())
+            .map(|prog| get_prog_name(prog))
+            .any(|prog_name| prog_name.is_ok_and(|prog_name| &prog_name == name));
+        ensure!(
+            found,
+            "program `{name}` configured for autoattach does not exist"
+        );
+
+        write!(
+            def,
+            r#"
+            let () = skel.obj.prog_mut("{name}").unwrap().set_autoattach({autoattach});
+            "#,
+        )?;
+    }
+    Ok(def)
+}
+
 fn gen_skel_struct_ops_init(object: &BpfObj) -> Result<String> {
     let mut def = String::new();
 
@@ -825,8 +1405,38 @@ fn gen_skel_struct_ops_init(object: &BpfObj) -> Result<String> {
     Ok(def)
 }
 
-/// Generate contents of a single skeleton
-fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) -> Result<String> {
+/// Compute the path of `path` relative to the directory `base`, both of
+/// which have to be absolute and free of `.` and `..` components.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let mut path = path.components().peekable();
+    let mut base = base.components().peekable();
+    while let (Some(p), Some(b)) = (path.peek(), base.peek()) {
+        if p != b {
+            break;
+        }
+        let _p = path.next();
+        let _b = base.next();
+    }
+    base.map(|_| Component::ParentDir).chain(path).collect()
+}
+
+/// Generate contents of a single skeleton, to be written to `skel_path`,
+/// if known
+fn gen_skel_contents(
+    _debug: bool,
+    raw_obj_name: &str,
+    obj_file_path: &Path,
+    skel_path: Option<&Path>,
+    opts: &GenOpts,
+) -> Result<String> {
+    let GenOpts {
+        async_helpers,
+        object_data,
+        skel_defaults,
+        no_aliases,
+        type_opts,
+        ..
+    } = opts;
     let mut skel = String::new();
 
     write!(
@@ -867,7 +1477,25 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
     let mmap = unsafe { Mmap::map(&file)? };
     let object = open_bpf_object(&libbpf_obj_name, &mmap)?;
 
-    gen_skel_c_skel_constructor(&mut skel, &object, &libbpf_obj_name)?;
+    gen_skel_c_skel_constructor(&mut skel, &object, &libbpf_obj_name, object_data)?;
+
+    let (obj_path_field, skel_config_init) = match object_data {
+        ObjectData::Embed | ObjectData::Include => ("", "build_skel_config()?"),
+        ObjectData::Runtime(..) => (
+            r#"
+            /// The path to read the BPF object file from, instead of the
+            /// one the skeleton got generated for.
+            pub obj_path: Option<std::path::PathBuf>,
+            "#,
+            r#"{
+                let path = self
+                    .obj_path
+                    .as_deref()
+                    .unwrap_or_else(|| std::path::Path::new(OBJ_PATH));
+                build_skel_config(std::fs::read(path)?)?
+            }"#,
+        ),
+    };
 
     #[allow(clippy::uninlined_format_args)]
     write!(
@@ -876,6 +1504,7 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
         #[derive(Default)]
         pub struct {name}SkelBuilder {{
             pub obj_builder: libbpf_rs::ObjectBuilder,
+            {obj_path_field}
         }}
 
         impl<'a> SkelBuilder<'a> for {name}SkelBuilder {{
@@ -886,7 +1515,7 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
             }}
 
             fn open_opts(self, open_opts: libbpf_sys::bpf_object_open_opts) -> libbpf_rs::Result<Open{name}Skel<'a>> {{
-                let mut skel_config = build_skel_config()?;
+                let mut skel_config = {skel_config_init};
 
                 let ret = unsafe {{ libbpf_sys::bpf_object__open_skeleton(skel_config.get(), &open_opts) }};
                 if ret != 0 {{
@@ -906,6 +1535,7 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
                     skel_config
                 }};
                 {struct_ops_init}
+                {open_defaults}
                 Ok(skel)
             }}
 
@@ -919,11 +1549,12 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
         "#,
         name = obj_name,
         struct_ops_init = gen_skel_struct_ops_init(&object)?,
+        open_defaults = gen_skel_open_defaults(&object, skel_defaults)?,
     )?;
 
-    gen_skel_map_defs(&mut skel, &object, &obj_name, true)?;
-    gen_skel_prog_defs(&mut skel, &object, &obj_name, true, false)?;
-    gen_skel_prog_defs(&mut skel, &object, &obj_name, true, true)?;
+    gen_skel_map_defs(&mut skel, &object, &obj_name, raw_obj_name, true, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, true, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, true, true)?;
     write!(
         skel,
         r#"
@@ -934,11 +1565,26 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
     )?;
 
     let mut processed = HashSet::new();
-    gen_skel_datasec_types(&mut skel, &object, &mut processed)?;
-    gen_skel_struct_ops_types(&mut skel, &object, &mut processed)?;
-    gen_skel_map_types(&mut skel, &object, &mut processed)?;
+    gen_skel_datasec_types(&mut skel, &object, &mut processed, type_opts)?;
+    gen_skel_struct_ops_types(&mut skel, &object, &mut processed, type_opts)?;
+    gen_skel_map_types(&mut skel, &object, &mut processed, type_opts)?;
     writeln!(skel, "}}")?;
 
+    gen_skel_prog_consts(&mut skel, &object, raw_obj_name)?;
+
+    if !no_aliases {
+        write!(
+            skel,
+            r#"
+            #[allow(unused_imports)]
+            pub use self::{raw_obj_name}_types as types;
+            #[allow(unused_imports)]
+            pub use self::{raw_obj_name}_progs as progs;
+            "#
+        )?;
+    }
+    gen_skel_map_pod_impls(&mut skel, &object, raw_obj_name)?;
+
     write!(
         skel,
         r#"
@@ -975,7 +1621,7 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
             }}
         "#,
         name = &obj_name,
-        links = if ProgIter::new(object.as_ptr()).next().is_some() {
+        links = if has_links(&object)? {
             format!(r#"links: {obj_name}Links::default()"#)
         } else {
             "".to_string()
@@ -985,13 +1631,14 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
     writeln!(skel, "impl Open{name}Skel<'_> {{", name = &obj_name)?;
 
     gen_skel_prog_getters(&mut skel, &object, &obj_name, true)?;
-    gen_skel_map_getters(&mut skel, &object, &obj_name, true)?;
+    gen_skel_map_getters(&mut skel, &object, &obj_name, true, false)?;
     gen_skel_datasec_getters(&mut skel, &object, raw_obj_name, false)?;
+    gen_skel_event_source_helpers(&mut skel, &object, true)?;
     writeln!(skel, "}}")?;
 
-    gen_skel_map_defs(&mut skel, &object, &obj_name, false)?;
-    gen_skel_prog_defs(&mut skel, &object, &obj_name, false, false)?;
-    gen_skel_prog_defs(&mut skel, &object, &obj_name, false, true)?;
+    gen_skel_map_defs(&mut skel, &object, &obj_name, raw_obj_name, false, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, false, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, false, true)?;
     gen_skel_link_defs(&mut skel, &object, &obj_name)?;
 
     write!(
@@ -1029,22 +1676,385 @@ fn gen_skel_contents(_debug: bool, raw_obj_name: &str, obj_file_path: &Path) ->
 
     write!(skel, "impl {name}Skel<'_> {{", name = &obj_name)?;
     gen_skel_prog_getters(&mut skel, &object, &obj_name, false)?;
-    gen_skel_map_getters(&mut skel, &object, &obj_name, false)?;
+    gen_skel_map_getters(&mut skel, &object, &obj_name, false, false)?;
     gen_skel_struct_ops_getters(&mut skel, &object, raw_obj_name)?;
     gen_skel_datasec_getters(&mut skel, &object, raw_obj_name, true)?;
+    gen_skel_event_source_helpers(&mut skel, &object, false)?;
+    if *async_helpers {
+        gen_skel_async_helpers(&mut skel, &object)?;
+    }
+    writeln!(skel, "}}")?;
+
+    match object_data {
+        ObjectData::Embed => {
+            // Coerce to &[u8] just to be safe, as we'll be using debug formatting
+            let bytes: &[u8] = &mmap;
+            write!(
+                skel,
+                r#"
+                const DATA: &[u8] = &{bytes:?};
+                "#
+            )?;
+        }
+        ObjectData::Include => {
+            // `include_bytes!` resolves relative paths with respect to the
+            // file it is used in, which is the skeleton even if it got
+            // `include!`d elsewhere. That way the skeleton keeps working
+            // if the tree it is part of is moved.
+            let skel_dir = skel_path.and_then(Path::parent).context(
+                "referencing the object file requires the skeleton to be written to a file",
+            )?;
+            let skel_dir = match skel_dir {
+                dir if dir.as_os_str().is_empty() => Path::new("."),
+                dir => dir,
+            };
+            let skel_dir = skel_dir
+                .canonicalize()
+                .with_context(|| format!("failed to canonicalize `{}`", skel_dir.display()))?;
+            let path = obj_file_path
+                .canonicalize()
+                .with_context(|| format!("failed to canonicalize `{}`", obj_file_path.display()))?;
+            let path = relative_path(&path, &skel_dir);
+            let path = path.to_str().with_context(|| {
+                format!("object file path `{}` is not valid UTF-8", path.display())
+            })?;
+            write!(
+                skel,
+                r#"
+                const DATA: &[u8] = include_bytes!({path:?});
+                "#
+            )?;
+        }
+        ObjectData::Runtime(dir) => {
+            let file_name = obj_file_path
+                .file_name()
+                .context("object file path has no file name")?;
+            let path = dir.join(file_name);
+            let path = path.to_str().with_context(|| {
+                format!("object file path `{}` is not valid UTF-8", path.display())
+            })?;
+            write!(
+                skel,
+                r#"
+                const OBJ_PATH: &str = {path:?};
+                "#
+            )?;
+        }
+    }
+
     writeln!(skel, "}}")?;
 
-    // Coerce to &[u8] just to be safe, as we'll be using debug formatting
-    let bytes: &[u8] = &mmap;
+    Ok(skel)
+}
+
+/// A global variable exposed by a subskeleton.
+struct SubskelVar {
+    name: String,
+    ty: String,
+    /// The index of the datasec map containing the variable.
+    map_idx: usize,
+    readonly: bool,
+}
+
+/// Collect the data section maps and the global variables contained in
+/// them, as they should be exposed by a subskeleton.
+fn subskel_datasec_vars(object: &BpfObj) -> Result<(Vec<String>, Vec<SubskelVar>)> {
+    let mut secs = Vec::new();
+    let mut vars = Vec::new();
+
+    let btf = if let Some(btf) = Btf::from_bpf_object(object)? {
+        btf
+    } else {
+        return Ok((secs, vars));
+    };
+    let btf = GenBtf::from(btf);
+
+    for ty in btf.type_by_kind::<types::DataSec<'_>>() {
+        let sec_name = match ty.name() {
+            Some(s) => s.to_str().context("datasec has invalid name")?,
+            None => continue,
+        };
+
+        let readonly = match canonicalize_internal_map_name(sec_name) {
+            Some(InternalMapType::Rodata | InternalMapType::CustomRodata(_)) => true,
+            Some(
+                InternalMapType::Data
+                | InternalMapType::CustomData(_)
+                | InternalMapType::Bss
+                | InternalMapType::CustomBss(_),
+            ) => false,
+            Some(InternalMapType::Kconfig | InternalMapType::StructOps) | None => continue,
+        };
+
+        let map_idx = secs.len();
+        secs.push(sec_name.to_string());
+
+        for datasec_var in ty.iter() {
+            let var = btf
+                .type_by_id::<types::Var<'_>>(datasec_var.ty)
+                .context("BTF is invalid! Datasec var does not point to a var")?;
+
+            if var.linkage() == types::Linkage::Static {
+                continue;
+            }
+
+            vars.push(SubskelVar {
+                name: var.name().unwrap().to_string_lossy().into_owned(),
+                ty: btf.type_declaration(*var)?,
+                map_idx,
+                readonly,
+            });
+        }
+    }
+
+    Ok((secs, vars))
+}
+
+fn gen_subskel_var_getters(skel: &mut String, vars: &[SubskelVar], loaded: bool) -> Result<()> {
+    for (idx, var) in vars.iter().enumerate() {
+        let SubskelVar { name, ty, .. } = var;
+
+        write!(
+            skel,
+            r#"
+            pub fn {name}(&self) -> &{ty} {{
+                unsafe {{ &*self.skel_config.var_ptr({idx}).unwrap().cast::<{ty}>() }}
+            }}
+            "#
+        )?;
+
+        if !(loaded && var.readonly) {
+            write!(
+                skel,
+                r#"
+                pub fn {name}_mut(&mut self) -> &mut {ty} {{
+                    unsafe {{ &mut *self.skel_config.var_ptr_mut({idx}).unwrap().cast::<{ty}>() }}
+                }}
+                "#
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Generate contents of a single subskeleton
+///
+/// A subskeleton provides access to the parts of a BPF object that
+/// originate from a BPF library, after the library's object file got
+/// statically linked into the final object.
+fn gen_subskel_contents(
+    _debug: bool,
+    raw_obj_name: &str,
+    obj_file_path: &Path,
+    type_opts: &TypeOpts,
+    no_aliases: bool,
+) -> Result<String> {
+    let mut skel = String::new();
+
+    write!(
+        skel,
+        r#"// SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+           //
+           // THIS FILE IS AUTOGENERATED BY CARGO-LIBBPF-GEN!
+
+           pub use self::imp::*;
+
+           #[allow(dead_code)]
+           #[allow(non_snake_case)]
+           #[allow(non_camel_case_types)]
+           #[allow(clippy::absolute_paths)]
+           #[allow(clippy::upper_case_acronyms)]
+           #[warn(single_use_lifetimes)]
+           mod imp {{
+           #[allow(unused_imports)]
+           use super::*;
+           #[allow(unused_imports)]
+           use self::{raw_obj_name}_types::*;
+           use libbpf_rs::libbpf_sys;
+        "#
+    )?;
+
+    let libbpf_obj_name = format!("{raw_obj_name}_bpf");
+    let obj_name = capitalize_first_letter(raw_obj_name);
+
+    let file = File::open(obj_file_path)
+        .with_context(|| format!("failed to open BPF object `{}`", obj_file_path.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let object = open_bpf_object(&libbpf_obj_name, &mmap)?;
+    let (secs, vars) = subskel_datasec_vars(&object)?;
+
+    write!(
+        skel,
+        r#"
+        fn build_subskel_config(
+            obj: core::ptr::NonNull<libbpf_sys::bpf_object>,
+        ) -> libbpf_rs::Result<libbpf_rs::__internal_skel::ObjectSubskeletonConfig>
+        {{
+            let mut builder = libbpf_rs::__internal_skel::ObjectSubskeletonConfigBuilder::new(obj);
+            builder
+        "#,
+    )?;
+
+    // Data section maps are looked up by their section name, as the name of
+    // the map itself is derived from the name of the final object.
+    for sec in &secs {
+        write!(
+            skel,
+            r#"
+            .map("{sec}", true)
+            "#,
+        )?;
+    }
+
+    for prog in ProgIter::new(object.as_ptr()) {
+        let name = get_prog_name(prog)?;
+
+        write!(
+            skel,
+            r#"
+            .prog("{name}")
+            "#,
+        )?;
+    }
+
+    for SubskelVar { name, map_idx, .. } in &vars {
+        write!(
+            skel,
+            r#"
+            .var("{name}", {map_idx})
+            "#,
+        )?;
+    }
+
+    write!(
+        skel,
+        r#";
+            builder.build()
+        }}
+        "#
+    )?;
+
+    gen_skel_map_defs(&mut skel, &object, &obj_name, raw_obj_name, true, true)?;
+    gen_skel_map_defs(&mut skel, &object, &obj_name, raw_obj_name, false, true)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, true, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, true, true)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, false, false)?;
+    gen_skel_prog_defs(&mut skel, &object, &obj_name, raw_obj_name, false, true)?;
+
     write!(
         skel,
         r#"
-        const DATA: &[u8] = &{bytes:?};
+            pub mod {raw_obj_name}_types {{
+                #[allow(unused_imports)]
+                use super::*;
+            "#
+    )?;
+
+    let mut processed = HashSet::new();
+    gen_skel_datasec_types(&mut skel, &object, &mut processed, type_opts)?;
+    gen_skel_map_types(&mut skel, &object, &mut processed, type_opts)?;
+    writeln!(skel, "}}")?;
+
+    gen_skel_prog_consts(&mut skel, &object, raw_obj_name)?;
+
+    if !no_aliases {
+        write!(
+            skel,
+            r#"
+            #[allow(unused_imports)]
+            pub use self::{raw_obj_name}_types as types;
+            #[allow(unused_imports)]
+            pub use self::{raw_obj_name}_progs as progs;
+            "#
+        )?;
+    }
+
+    for (prefix, inner_ty, loaded) in [
+        ("Open", "libbpf_rs::OpenObject", false),
+        ("", "libbpf_rs::Object", true),
+    ] {
+        write!(
+            skel,
+            r#"
+            pub struct {prefix}{obj_name}Subskel<'obj> {{
+                pub obj: &'obj mut {inner_ty},
+                skel_config: libbpf_rs::__internal_skel::ObjectSubskeletonConfig,
+            }}
+
+            impl<'obj> {prefix}{obj_name}Subskel<'obj> {{
+                pub fn open(obj: &'obj mut {inner_ty}) -> libbpf_rs::Result<Self> {{
+                    let ptr = libbpf_rs::AsRawLibbpf::as_libbpf_object(&*obj);
+                    let mut skel_config = build_subskel_config(ptr)?;
+
+                    let ret = unsafe {{ libbpf_sys::bpf_object__open_subskeleton(skel_config.get()) }};
+                    if ret != 0 {{
+                        return Err(libbpf_rs::Error::from_raw_os_error(-ret));
+                    }}
+
+                    Ok(Self {{ obj, skel_config }})
+                }}
+            "#,
+        )?;
+
+        gen_skel_map_getters(&mut skel, &object, &obj_name, !loaded, true)?;
+        gen_skel_prog_getters(&mut skel, &object, &obj_name, !loaded)?;
+        gen_subskel_var_getters(&mut skel, &vars, loaded)?;
+        writeln!(skel, "}}")?;
+    }
+
+    writeln!(skel, "}}")?;
+
+    Ok(skel)
+}
+
+/// Generate the contents of a skeleton consisting of only the type
+/// definitions of an object, which depend on neither `libbpf-rs` nor
+/// `std`.
+fn gen_types_contents(
+    _debug: bool,
+    raw_obj_name: &str,
+    obj_file_path: &Path,
+    type_opts: &TypeOpts,
+    no_aliases: bool,
+) -> Result<String> {
+    let mut skel = String::new();
+
+    write!(
+        skel,
+        r#"// SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+           //
+           // THIS FILE IS AUTOGENERATED BY CARGO-LIBBPF-GEN!
+
+           #[allow(dead_code)]
+           #[allow(non_snake_case)]
+           #[allow(non_camel_case_types)]
+           #[allow(clippy::upper_case_acronyms)]
+           pub mod {raw_obj_name}_types {{
         "#
     )?;
 
+    let libbpf_obj_name = format!("{raw_obj_name}_bpf");
+    let file = File::open(obj_file_path)
+        .with_context(|| format!("failed to open BPF object `{}`", obj_file_path.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let object = open_bpf_object(&libbpf_obj_name, &mmap)?;
+
+    // `struct_ops` types refer to `libbpf-rs` and are, hence, not
+    // generated.
+    let mut processed = HashSet::new();
+    gen_skel_datasec_types(&mut skel, &object, &mut processed, type_opts)?;
+    gen_skel_map_types(&mut skel, &object, &mut processed, type_opts)?;
     writeln!(skel, "}}")?;
 
+    if !no_aliases {
+        write!(
+            skel,
+            r#"
+            #[allow(unused_imports)]
+            pub use self::{raw_obj_name}_types as types;
+            "#
+        )?;
+    }
     Ok(skel)
 }
 
@@ -1054,33 +2064,50 @@ fn gen_skel(
     name: &str,
     obj: &Path,
     out: OutputDest<'_>,
-    rustfmt_path: Option<&PathBuf>,
+    formatting: &Formatting,
+    opts: &GenOpts,
 ) -> Result<()> {
     ensure!(!name.is_empty(), "Object file has no name");
+    ensure!(
+        !opts.subskel || opts.object_data == ObjectData::Embed,
+        "Subskeletons do not support object data settings, as they do not carry the object"
+    );
 
-    let skel = gen_skel_contents(debug, name, obj)?;
-    let skel = try_rustfmt(&skel, rustfmt_path)?;
+    let suffix = if opts.subskel && !opts.no_std {
+        "subskel"
+    } else {
+        "skel"
+    };
+    let path = match out {
+        OutputDest::Stdout => None,
+        OutputDest::Directory(dir) => Some(dir.join(format!("{name}.{suffix}.rs"))),
+        OutputDest::File(file) => Some(file.to_path_buf()),
+    };
 
-    match out {
-        OutputDest::Stdout => stdout().write_all(&skel)?,
-        OutputDest::Directory(dir) => {
-            let path = dir.join(format!("{name}.skel.rs"));
+    let skel = if opts.no_std {
+        gen_types_contents(debug, name, obj, &opts.type_opts, opts.no_aliases)?
+    } else if opts.subskel {
+        gen_subskel_contents(debug, name, obj, &opts.type_opts, opts.no_aliases)?
+    } else {
+        gen_skel_contents(debug, name, obj, path.as_deref(), opts)?
+    };
+    let skel = format_code(&skel, formatting)?;
+
+    match path {
+        None => stdout().write_all(&skel)?,
+        Some(path) => {
             let mut file = File::create(path)?;
             file.write_all(&skel)?;
         }
-        OutputDest::File(file) => {
-            let mut file = File::create(file)?;
-            file.write_all(&skel)?;
-        }
     };
 
     Ok(())
 }
 
-/// Generate mod.rs in src/bpf directory of each project.
+/// Generate mod.rs in each prog directory of a project.
 ///
-/// Each `UnprocessedObj` in `objs` must belong to same project.
-pub fn gen_mods(objs: &[UnprocessedObj], rustfmt_path: Option<&PathBuf>) -> Result<()> {
+/// Each `UnprocessedObj` in `objs` must belong to the same directory.
+pub fn gen_mods(objs: &[UnprocessedObj], formatting: &Formatting) -> Result<()> {
     if objs.is_empty() {
         return Ok(());
     }
@@ -1122,7 +2149,7 @@ pub fn gen_mods(objs: &[UnprocessedObj], rustfmt_path: Option<&PathBuf>) -> Resu
     }
 
     let mut file = File::create(path)?;
-    file.write_all(&try_rustfmt(&contents, rustfmt_path)?)?;
+    file.write_all(&format_code(&contents, formatting)?)?;
 
     Ok(())
 }
@@ -1131,7 +2158,8 @@ pub fn gen_single(
     debug: bool,
     obj_file: &Path,
     output: OutputDest<'_>,
-    rustfmt_path: Option<&PathBuf>,
+    formatting: &Formatting,
+    opts: &GenOpts,
 ) -> Result<()> {
     let filename = match obj_file.file_name() {
         Some(n) => n,
@@ -1156,7 +2184,7 @@ pub fn gen_single(
         ),
     };
 
-    let () = gen_skel(debug, name, obj_file, output, rustfmt_path).with_context(|| {
+    let () = gen_skel(debug, name, obj_file, output, formatting, opts).with_context(|| {
         format!(
             "Failed to generate skeleton for {}",
             obj_file.to_string_lossy(),
@@ -1169,7 +2197,8 @@ pub fn gen_single(
 fn gen_project(
     debug: bool,
     manifest_path: Option<&PathBuf>,
-    rustfmt_path: Option<&PathBuf>,
+    formatting: &Formatting,
+    opts: &GenOpts,
 ) -> Result<()> {
     let (_target_dir, to_gen) = metadata::get(debug, manifest_path)?;
     if debug && !to_gen.is_empty() {
@@ -1181,41 +2210,60 @@ fn gen_project(
         bail!("Did not find any bpf objects to generate skeleton");
     }
 
-    // Map to store package_name -> [UnprocessedObj]
-    let mut package_objs: BTreeMap<String, Vec<UnprocessedObj>> = BTreeMap::new();
+    // Map to store prog_dir -> [UnprocessedObj]
+    let mut dir_objs: BTreeMap<PathBuf, Vec<UnprocessedObj>> = BTreeMap::new();
 
     for obj in to_gen {
-        let mut obj_file_path = obj.out.clone();
-        obj_file_path.push(format!("{}.bpf.o", obj.name));
-
         let mut skel_path = obj.path.clone();
         skel_path.pop();
 
-        let () = gen_skel(
-            debug,
-            &obj.name,
-            obj_file_path.as_path(),
-            OutputDest::Directory(skel_path.as_path()),
-            rustfmt_path,
-        )
-        .with_context(|| {
-            format!(
-                "Failed to generate project skeleton for {}",
-                obj.path.as_path().display()
-            )
-        })?;
+        let () = dir_objs.entry(skel_path).or_default().push(obj);
+    }
 
-        match package_objs.get_mut(&obj.package) {
-            Some(v) => v.push(obj.clone()),
-            None => {
-                package_objs.insert(obj.package.clone(), vec![obj.clone()]);
-            }
-        };
+    for (skel_path, objs) in &dir_objs {
+        for obj in objs {
+            let mut obj_file_path = obj.out.clone();
+            obj_file_path.push(format!("{}.bpf.o", obj.name));
+
+            let mut obj_opts = opts.clone();
+            obj_opts.skel_defaults = obj.skel_defaults.clone();
+            // The aliases of multiple skeletons would be ambiguous in the
+            // glob re-exports of the generated mod.rs.
+            obj_opts.no_aliases = objs.len() > 1;
+            let () = obj_opts
+                .type_opts
+                .derives
+                .extend(obj.derives.iter().cloned());
+            let () = obj_opts
+                .type_opts
+                .attributes
+                .extend(obj.attributes.iter().cloned());
+
+            let () = gen_skel(
+                debug,
+                &obj.name,
+                obj_file_path.as_path(),
+                OutputDest::Directory(skel_path.as_path()),
+                formatting,
+                &obj_opts,
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to generate project skeleton for {}",
+                    obj.path.as_path().display()
+                )
+            })?;
+        }
     }
 
-    for (package, objs) in package_objs {
-        let () = gen_mods(&objs, rustfmt_path)
-            .with_context(|| format!("Failed to generate mod.rs for package={package}"))?;
+    for (dir, objs) in dir_objs {
+        let () = gen_mods(&objs, formatting).with_context(|| {
+            format!(
+                "Failed to generate mod.rs for package={} in {}",
+                objs[0].package,
+                dir.display()
+            )
+        })?;
     }
 
     Ok(())
@@ -1224,16 +2272,28 @@ fn gen_project(
 pub fn gen(
     debug: bool,
     manifest_path: Option<&PathBuf>,
-    rustfmt_path: Option<&PathBuf>,
+    formatting: &Formatting,
     object: Option<&PathBuf>,
+    opts: &GenOpts,
 ) -> Result<()> {
     if manifest_path.is_some() && object.is_some() {
         bail!("--manifest-path and --object cannot be used together");
     }
 
     if let Some(obj_file) = object {
-        gen_single(debug, obj_file, OutputDest::Stdout, rustfmt_path)
+        gen_single(debug, obj_file, OutputDest::Stdout, formatting, opts)
+    } else if opts.subskel {
+        bail!("--subskel can only be used together with --object")
     } else {
-        gen_project(debug, manifest_path, rustfmt_path)
+        gen_project(debug, manifest_path, formatting, opts)
     }
 }
+
+#[test]
+fn test_relative_path() {
+    let rel = |path, base| relative_path(Path::new(path), Path::new(base));
+    assert_eq!(rel("/a/b/c.o", "/a/b"), Path::new("c.o"));
+    assert_eq!(rel("/a/b/c.o", "/a/d/e"), Path::new("../../b/c.o"));
+    assert_eq!(rel("/a/b/c.o", "/"), Path::new("a/b/c.o"));
+    assert_eq!(rel("/c.o", "/a"), Path::new("../c.o"));
+}
@@ -0,0 +1,178 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The lexical context at a given position in the code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Code,
+    /// Inside a string literal.
+    Str,
+    /// Inside a block comment of the given nesting level.
+    BlockComment(usize),
+}
+
+/// Skip over the remainder of a character literal, if the `'` just
+/// consumed started one (as opposed to a lifetime).
+fn skip_char_literal(chars: &mut Peekable<Chars<'_>>) {
+    let mut lookahead = chars.clone();
+    match (lookahead.next(), lookahead.next()) {
+        (Some('\\'), _) => {
+            let _ = chars.next();
+            let _ = chars.next();
+            for c in chars.by_ref() {
+                if c == '\'' {
+                    break;
+                }
+            }
+        }
+        (Some(_), Some('\'')) => {
+            let _ = chars.next();
+            let _ = chars.next();
+        }
+        _ => (),
+    }
+}
+
+/// Scan `line`, starting out in `state`, and adjust `depth` for every
+/// opening and closing delimiter encountered in code.
+///
+/// Returns the state at the end of the line.
+fn scan(line: &str, mut state: State, depth: &mut usize) -> State {
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        match state {
+            State::Code => match (c, next) {
+                ('/', Some('/')) => break,
+                ('/', Some('*')) => {
+                    let _ = chars.next();
+                    state = State::BlockComment(1);
+                }
+                ('"', _) => state = State::Str,
+                ('\'', _) => skip_char_literal(&mut chars),
+                ('{' | '(' | '[', _) => *depth += 1,
+                ('}' | ')' | ']', _) => *depth = depth.saturating_sub(1),
+                _ => (),
+            },
+            State::Str => match c {
+                '\\' => {
+                    let _ = chars.next();
+                }
+                '"' => state = State::Code,
+                _ => (),
+            },
+            State::BlockComment(nesting) => match (c, next) {
+                ('*', Some('/')) => {
+                    let _ = chars.next();
+                    state = if nesting == 1 {
+                        State::Code
+                    } else {
+                        State::BlockComment(nesting - 1)
+                    };
+                }
+                ('/', Some('*')) => {
+                    let _ = chars.next();
+                    state = State::BlockComment(nesting + 1);
+                }
+                _ => (),
+            },
+        }
+    }
+    state
+}
+
+/// A minimal pretty-printer for generated code, used in lieu of `rustfmt`.
+///
+/// Every line is stripped of surrounding whitespace and indented by four
+/// spaces per level of bracket nesting. Runs of blank lines are collapsed
+/// into one. Lines do not get split or joined, and the content of
+/// multi-line string literals is left untouched.
+pub(crate) fn pretty_print(code: &str) -> String {
+    let mut output = String::with_capacity(code.len());
+    let mut state = State::Code;
+    let mut depth = 0;
+    // Pretend a blank line was just emitted, so that leading blank lines
+    // get dropped.
+    let mut blank = true;
+
+    for line in code.lines() {
+        if state == State::Str {
+            let () = output.push_str(line);
+            let () = output.push('\n');
+            blank = false;
+            state = scan(line, state, &mut depth);
+            continue;
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            if !blank {
+                let () = output.push('\n');
+                blank = true;
+            }
+            continue;
+        }
+
+        let closing = if state == State::Code {
+            line.chars()
+                .take_while(|c| matches!(c, '}' | ')' | ']'))
+                .count()
+        } else {
+            0
+        };
+        for _ in 0..depth.saturating_sub(closing) {
+            let () = output.push_str("    ");
+        }
+        let () = output.push_str(line);
+        let () = output.push('\n');
+        blank = false;
+        state = scan(line, state, &mut depth);
+    }
+
+    while output.ends_with("\n\n") {
+        let _ = output.pop();
+    }
+    output
+}
+
+#[test]
+fn test_pretty_print() {
+    let code = r#"
+
+        // SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+
+
+        pub struct Foo<'a> {
+        pub bar: &'a [u8],
+        }
+
+        impl Foo<'_> {
+            fn baz(&self) -> (char, &str) {
+                    let s = "{ not a block
+  (
+";
+            /* { */
+            ('}', s)
+        }
+        }
+
+
+"#;
+    let expected = r#"// SPDX-License-Identifier: (LGPL-2.1 OR BSD-2-Clause)
+
+pub struct Foo<'a> {
+    pub bar: &'a [u8],
+}
+
+impl Foo<'_> {
+    fn baz(&self) -> (char, &str) {
+        let s = "{ not a block
+  (
+";
+        /* { */
+        ('}', s)
+    }
+}
+"#;
+    assert_eq!(pretty_print(code), expected);
+}
@@ -19,11 +19,32 @@
 //! ```text
 //! [package.metadata.libbpf]
 //! prog_dir = "src/other_bpf_dir"  # default: <manifest_directory>/src/bpf
+//! prog_dirs = ["src/more_bpf"]    # default: []
 //! target_dir = "other_target_dir" # default: <target_dir>/bpf
+//! derives = ["serde::Serialize"]  # default: []
+//! attributes = ["serde(rename_all = \"camelCase\")"] # default: []
+//!
+//! [package.metadata.libbpf.objects.<NAME>]
+//! clang_args = ["-DFOO=1"]        # default: []
+//! pin_root = "/sys/fs/bpf/myapp"  # default: /sys/fs/bpf
+//! maps.<MAP> = { pin = true }     # default: false
+//! progs.<PROG> = { autoattach = false } # default: true
 //! ```
 //!
 //! * `prog_dir`: path relative to package Cargo.toml to search for bpf progs
+//! * `prog_dirs`: additional paths relative to package Cargo.toml to search for bpf progs; the
+//!   default `src/bpf` is only searched if neither `prog_dir` nor `prog_dirs` is set
 //! * `target_dir`: path relative to workspace target directory to place compiled bpf progs
+//! * `derives`: additional derives for structs and enums in generated skeletons
+//! * `attributes`: additional attributes for structs and enums in generated skeletons
+//! * `objects.<NAME>.clang_args`: additional arguments to pass to clang when compiling
+//!   `<NAME>.bpf.c`, after the ones provided on the command line
+//! * `objects.<NAME>.pin_root`: directory in which the maps of `<NAME>.bpf.c` configured to be
+//!   pinned get pinned, by name
+//! * `objects.<NAME>.maps.<MAP>.pin`: whether the generated skeleton's `SkelBuilder::open` sets
+//!   the pin path of `<MAP>`, causing it to be pinned or, if already pinned, reused on load
+//! * `objects.<NAME>.progs.<PROG>.autoattach`: the autoattach setting the generated skeleton's
+//!   `SkelBuilder::open` applies to `<PROG>`
 //!
 //! # Subcommands
 //!
@@ -40,15 +61,123 @@
 //! * there may not be any two identical `<NAME>.bpf.c` file names in any two projects in a
 //!   cargo workspace
 //!
+//! With `--reproducible`, objects are built to be byte-identical regardless of the time and the
+//! directory the build happens in, e.g., for supply-chain attestation: date and time related
+//! macros are pinned to the Unix epoch and the current working directory as well as the target
+//! directory are remapped via `-ffile-prefix-map`. The build fails if a resulting object still
+//! contains DWARF debug information or any of these paths.
+//!
+//! Along with the objects, a [compilation
+//! database](https://clang.llvm.org/docs/JSONCompilationDatabase.html) is written to
+//! `<target_dir>/bpf/compile_commands.json`. Point `clangd` or similar tooling at it (e.g., via
+//! `--compile-commands-dir`) to get correct include paths and defines for `.bpf.c` files.
+//!
+//! BPF objects have to be of the endianness of the system loading them. They are compiled for the
+//! BPF target matching the endianness of the host or, when invoked from a build script, of the
+//! target being compiled for. Use `--bpf-target bpfeb` (or `bpfel`) to override it, e.g., when
+//! cross-compiling for a big-endian system such as s390x. Building and generating skeletons for
+//! objects of an endianness different from the host's requires libbpf 1.5 or later.
+//!
+//! Programs commonly include a `vmlinux.h` header containing the kernel's type definitions. With
+//! `--gen-vmlinux`, such a header is generated from the BTF of the running kernel as part of the
+//! build, whereas `--vmlinux-btf <BTF>` generates it from the provided BTF file instead. The
+//! generated header takes precedence over other `vmlinux.h` files in the include path.
+//!
+//! Objects are compiled in parallel. An object is only recompiled if its source file, any of
+//! the headers it includes, or the compiler flags changed since it was last built. To that end,
+//! a `<NAME>.bpf.d` dependency file and a `<NAME>.bpf.hash` file are stored alongside it.
+//!
+//! `--bpf-cpu <CPU>` selects the BPF instruction set level to compile for (e.g., `v3` or `v4`).
+//! Unless version checks are skipped, the build fails upfront if the compiler is too old for the
+//! requested level or for CO-RE features used by a program (such as `bpf_core_enum_value`),
+//! instead of producing objects that fail to load.
+//!
+//! GCC's BPF backend can be used in place of `clang` by passing it via `--clang-path`, e.g.,
+//! `--clang-path bpf-unknown-none-gcc`. It is detected automatically and arguments are translated
+//! as necessary: the BPF target maps to `-mlittle-endian` or `-mbig-endian` and BTF is emitted
+//! via `-gbtf`. Options such as `-mcpu` are passed through as is. GCC 13.1 or later is required.
+//!
+//! With `--watch`, `cargo libbpf build` keeps running after the build and rebuilds whenever a
+//! BPF source file or any of the headers it includes changes. `--exec <COMMAND>` additionally
+//! runs the given command using the shell after each successful rebuild. `cargo libbpf make`
+//! supports the same options.
+//!
 //! ## gen
 //!
 //! `cargo libbpf gen` generates a skeleton module for each BPF object file in the project.  Each
-//! `<NAME>.bpf.o` object file will have its own module. One `mod.rs` file is also generated. All
-//! output files are placed into `package.metadata.libbpf.prog_dir`.
+//! `<NAME>.bpf.o` object file will have its own module. One `mod.rs` file is also generated for
+//! each prog directory. All output files are placed into the prog directory the corresponding
+//! source file resides in. Skeletons contain `types` and `progs` aliases for their
+//! `<NAME>_types` and `<NAME>_progs` modules, unless their prog directory contains multiple
+//! objects, in which case the aliases would be ambiguous in `mod.rs`.
 //!
 //! Be careful to run cargo-libbpf-build before running cargo-libbpf-gen. cargo-libbpf-gen reads
 //! object files from `package.metadata.libbpf.target_dir`.
 //!
+//! Generated code is formatted with `rustfmt` (see `--rustfmt-path`) if it can be found in
+//! `$PATH`, and with a minimal built-in pretty-printer otherwise. `--formatter builtin` and
+//! `--formatter none` select the built-in pretty-printer or disable formatting altogether,
+//! respectively, which is useful in hermetic build environments.
+//!
+//! Documentation can be attached to structs, unions and their members, as well as to programs
+//! and maps, by means of BTF declaration tags of the form `doc:<TEXT>`, e.g.,
+//! `__attribute__((btf_decl_tag("doc:Counts received packets.")))`. The text is emitted as doc
+//! comment on the corresponding generated type, member, or accessor.
+//!
+//! Generated unions provide a getter for each member that can be read without risking to observe
+//! uninitialized bytes, i.e., members not larger than any other member of a union made up of
+//! plain old data only, as well as a setter for each member. If a struct contains an enum member
+//! directly followed by a union member, and each of the union's members corresponds to an enum
+//! value of the same name, optionally prefixed (e.g., `EVENT_EXEC` for member `exec`), the struct
+//! additionally provides a `<MEMBER>_view` method, returning a Rust enum holding the member
+//! selected by the enum value.
+//!
+//! For looking up programs dynamically without relying on string literals, skeletons contain a
+//! `<NAME>_progs` module (aliased as `progs`) with `names::<PROG>` and `sections::<PROG>`
+//! constants holding the name and section of each program, as well as `ALL`, listing all program
+//! names. Programs whose names only differ in case are rejected, as they would map to the same
+//! constant. The generated program accessors additionally provide an `iter` method for iterating
+//! over all programs along with their names.
+//!
+//! `struct_ops` maps can be adjusted through the typed `struct_ops` member of the open skeleton
+//! before load. Generated `attach` and `attach_partial` methods attach them along with the
+//! programs, storing the resulting links in the `links` member of the skeleton.
+//!
+//! By default, the contents of the object file are embedded into the skeleton as a byte array.
+//! `--include-object` references the object via `include_bytes!` instead, keeping the generated
+//! source small, while `--runtime-object-dir <DIR>` generates a skeleton reading the object from
+//! `<DIR>` when opened, which allows for shipping it separately, e.g., in distribution packages.
+//! The path can be overridden at runtime through the `obj_path` member of the skeleton builder.
+//!
+//! Programs that should work on kernels with and without ring buffer support may declare the
+//! same events twice: once as a `BPF_MAP_TYPE_RINGBUF` map named `<EVENTS>_ringbuf` and once as
+//! a `BPF_MAP_TYPE_PERF_EVENT_ARRAY` map named `<EVENTS>_perfbuf`. For each such pair, the
+//! generated open skeleton provides a `configure_<EVENTS>_events` method, which arranges for
+//! only the map matching a given `libbpf_rs::EventSourceKind` to be created, and the loaded
+//! skeleton provides an `<EVENTS>_events` method returning a `libbpf_rs::EventSourceBuilder` for
+//! consuming the events through whichever map got created. The BPF side is expected to pick the
+//! map to write to based on the same criterion, e.g., by checking
+//! `bpf_core_type_exists(struct bpf_ringbuf)`.
+//!
+//! ## btf
+//!
+//! `cargo libbpf btf dump <OBJECT>` prints the BTF of a BPF object file, either as C declarations
+//! (`--format c`, the default) or as JSON (`--format json`).
+//!
+//! `cargo libbpf btf min-core --object <OBJECT>... --target <BTF>... --output-dir <DIR>`
+//! generates minimized BTF for each of the target kernel BTF files, containing only the types
+//! required for the CO-RE relocations of the provided objects. Such files are small enough to be
+//! shipped along with an application and can be used for loading its objects on kernels lacking
+//! BTF support, by providing them to libbpf via the `btf_custom_path` object open option.
+//!
+//! ## check
+//!
+//! `cargo libbpf check` statically inspects the `<NAME>.bpf.o` object files of the project (or
+//! the one provided via `--object`), without requiring any privileges. It reports the programs,
+//! maps, used BPF helpers and map types, as well as the CO-RE relocations of each object, and
+//! flags common mistakes such as a missing license section or programs exceeding the stack size
+//! limit. The command fails if any such problem is found, making it suitable for use in CI.
+//!
 //! ## make
 //!
 //! `cargo libbpf make` sequentially runs cargo-libbpf-build, cargo-libbpf-gen, and `cargo
@@ -64,6 +193,8 @@
 )]
 #![deny(unsafe_op_in_unsafe_fn)]
 
+use std::borrow::Cow;
+use std::env;
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::path::Path;
@@ -79,17 +210,26 @@ use tempfile::TempDir;
 // libbpf-cargo binary is the primary consumer of the following modules. As such,
 // we do not use all the symbols. Silence any unused code warnings.
 #[allow(dead_code)]
+mod btf;
+#[allow(dead_code)]
 mod build;
 #[allow(dead_code)]
+mod check;
+#[allow(dead_code)]
 mod gen;
 #[allow(dead_code)]
 mod make;
 #[allow(dead_code)]
 mod metadata;
+#[allow(dead_code)]
+mod watch;
 
 #[cfg(test)]
 mod test;
 
+pub use crate::gen::Formatting;
+pub use crate::gen::ObjectData;
+
 /// `SkeletonBuilder` builds and generates a single skeleton.
 ///
 /// This interface is meant to be used in build scripts.
@@ -113,7 +253,10 @@ pub struct SkeletonBuilder {
     clang: Option<PathBuf>,
     clang_args: Vec<OsString>,
     skip_clang_version_check: bool,
-    rustfmt: PathBuf,
+    build_opts: build::BuildOpts,
+    formatting: Formatting,
+    gen_opts: gen::GenOpts,
+    deps: Vec<PathBuf>,
     dir: Option<TempDir>,
 }
 
@@ -132,7 +275,10 @@ impl SkeletonBuilder {
             clang: None,
             clang_args: Vec::new(),
             skip_clang_version_check: false,
-            rustfmt: "rustfmt".into(),
+            build_opts: build::BuildOpts::default(),
+            formatting: Formatting::default(),
+            gen_opts: gen::GenOpts::default(),
+            deps: Vec::new(),
             dir: None,
         }
     }
@@ -163,6 +309,10 @@ impl SkeletonBuilder {
 
     /// Specify which `clang` binary to use
     ///
+    /// GCC's BPF backend (e.g., `bpf-gcc`) may be used instead, in which
+    /// case `clang` specific arguments such as the BPF target are
+    /// translated accordingly.
+    ///
     /// Default searches `$PATH` for `clang`
     pub fn clang<P: AsRef<Path>>(&mut self, clang: P) -> &mut SkeletonBuilder {
         self.clang = Some(clang.as_ref().to_path_buf());
@@ -197,6 +347,29 @@ impl SkeletonBuilder {
         self
     }
 
+    /// Specify the BPF target to compile for, i.e., `bpfel` or `bpfeb`
+    ///
+    /// BPF objects have to be of the endianness of the system loading them.
+    /// By default, the target matching the endianness of the target being
+    /// compiled for is used, as reported by `cargo` to build scripts.
+    /// Note that building objects of an endianness different from the
+    /// host's requires libbpf 1.5 or later.
+    pub fn bpf_target<S: AsRef<str>>(&mut self, target: S) -> &mut SkeletonBuilder {
+        self.build_opts.bpf_target = Some(target.as_ref().to_string());
+        self
+    }
+
+    /// Specify the BPF CPU, i.e., the instruction set level, to compile
+    /// for, e.g., `v3` or `v4`
+    ///
+    /// The build fails if the compiler is too old to support the requested
+    /// level, unless version checks are skipped. GCC only supports `v1`
+    /// through `v4`.
+    pub fn bpf_cpu<S: AsRef<str>>(&mut self, cpu: S) -> &mut SkeletonBuilder {
+        self.build_opts.bpf_cpu = Some(cpu.as_ref().to_string());
+        self
+    }
+
     /// Specify whether or not to skip clang version check
     ///
     /// Default is `false`
@@ -205,11 +378,216 @@ impl SkeletonBuilder {
         self
     }
 
+    /// Generate a `vmlinux.h` from the BTF of the running kernel and make
+    /// it available to the BPF program
+    ///
+    /// The header takes precedence over any `vmlinux.h` contained in
+    /// directories provided via [`SkeletonBuilder::clang_args`], removing
+    /// the need for a pre-generated one.
+    ///
+    /// Default is `false`
+    pub fn gen_vmlinux(&mut self, generate: bool) -> &mut SkeletonBuilder {
+        self.build_opts.vmlinux = generate.then_some(btf::VmlinuxBtf::Kernel);
+        self
+    }
+
+    /// Generate a `vmlinux.h` from the BTF contained in the file at `btf`
+    /// and make it available to the BPF program
+    ///
+    /// Contrary to [`SkeletonBuilder::gen_vmlinux`], the header is
+    /// independent of the kernel running on the build host. `btf` may be a
+    /// raw BTF file, such as `/sys/kernel/btf/vmlinux` of the target
+    /// system, or an ELF file containing a `.BTF` section. It is reported
+    /// as part of [`SkeletonBuilder::generated_deps`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libbpf_cargo::SkeletonBuilder;
+    ///
+    /// SkeletonBuilder::new()
+    ///     .source("myobject.bpf.c")
+    ///     .vmlinux_btf("btf/vmlinux")
+    ///     .build_and_generate("/output/path")
+    ///     .unwrap();
+    /// ```
+    pub fn vmlinux_btf<P: AsRef<Path>>(&mut self, btf: P) -> &mut SkeletonBuilder {
+        self.build_opts.vmlinux = Some(btf::VmlinuxBtf::Path(btf.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Build the BPF object reproducibly
+    ///
+    /// Date and time related macros are pinned to the Unix epoch and the
+    /// current working directory as well as temporary directories are
+    /// remapped in the object (via `-ffile-prefix-map`), making it
+    /// byte-identical across machines. The build fails if the object
+    /// still contains DWARF debug information or any of these paths.
+    ///
+    /// Default is `false`
+    pub fn reproducible(&mut self, reproducible: bool) -> &mut SkeletonBuilder {
+        self.build_opts.reproducible = reproducible;
+        self
+    }
+
     /// Specify which `rustfmt` binary to use
     ///
+    /// Generation fails if the binary can't be executed. This is a shorthand
+    /// for [`SkeletonBuilder::formatting`] with [`Formatting::Rustfmt`].
+    ///
     /// Default searches `$PATH` for `rustfmt`
     pub fn rustfmt<P: AsRef<Path>>(&mut self, rustfmt: P) -> &mut SkeletonBuilder {
-        self.rustfmt = rustfmt.as_ref().to_path_buf();
+        self.formatting = Formatting::Rustfmt(Some(rustfmt.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Specify how to format the generated skeleton
+    ///
+    /// Hermetic build environments lacking `rustfmt` may want to use
+    /// [`Formatting::Builtin`] or [`Formatting::Disabled`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libbpf_cargo::Formatting;
+    /// use libbpf_cargo::SkeletonBuilder;
+    ///
+    /// SkeletonBuilder::new()
+    ///     .source("myobject.bpf.c")
+    ///     .formatting(Formatting::Builtin)
+    ///     .build_and_generate("/output/path")
+    ///     .unwrap();
+    /// ```
+    ///
+    /// Default is `Formatting::Rustfmt(None)`, i.e., `rustfmt` is searched
+    /// for in `$PATH`, falling back to the built-in pretty-printer
+    pub fn formatting(&mut self, formatting: Formatting) -> &mut SkeletonBuilder {
+        self.formatting = formatting;
+        self
+    }
+
+    /// Generate a subskeleton instead of a skeleton
+    ///
+    /// A subskeleton is meant for BPF libraries: it provides access to the
+    /// maps, programs, and global variables of the library's object file
+    /// after it got linked into the object of the consumer.
+    ///
+    /// Default is `false`
+    pub fn subskeleton(&mut self, subskel: bool) -> &mut SkeletonBuilder {
+        self.gen_opts.subskel = subskel;
+        self
+    }
+
+    /// Generate helpers for asynchronously consuming the events submitted to
+    /// the object's ring buffers
+    ///
+    /// For each ring buffer map, a `consume_<map>_events<T>` method returning
+    /// a `libbpf_rs::RingBufferStream<T>`, which decodes samples as values of
+    /// the `libbpf_rs::Pod` type `T`, is added to the skeleton. The generated
+    /// code requires the `async` feature of `libbpf-rs` to be enabled.
+    ///
+    /// Default is `false`
+    pub fn async_helpers(&mut self, async_helpers: bool) -> &mut SkeletonBuilder {
+        self.gen_opts.async_helpers = async_helpers;
+        self
+    }
+
+    /// Generate only the type definitions of the object, for use in
+    /// `no_std` crates
+    ///
+    /// The generated file contains the `<object>_types` module (and its
+    /// `types` alias) only, which depends on neither `libbpf-rs` nor `std`,
+    /// but lacks the skeleton itself. `struct_ops` types are not included.
+    ///
+    /// Default is `false`
+    pub fn no_std(&mut self, no_std: bool) -> &mut SkeletonBuilder {
+        self.gen_opts.no_std = no_std;
+        self
+    }
+
+    /// Specify how the generated skeleton gets hold of the contents of the
+    /// BPF object file
+    ///
+    /// By default, the contents are embedded into the skeleton. Referencing
+    /// the object via `include_bytes!` or reading it at runtime instead
+    /// allows for shipping it separately, e.g., in distribution packages.
+    ///
+    /// Default is [`ObjectData::Embed`]
+    pub fn object_data(&mut self, object_data: ObjectData) -> &mut SkeletonBuilder {
+        self.gen_opts.object_data = object_data;
+        self
+    }
+
+    /// Implement `plain::Plain` for generated types that are plain old data,
+    /// i.e., that are valid for any bit pattern and contain neither pointers
+    /// nor implicit padding
+    ///
+    /// This allows for reading such types from raw bytes, e.g., as received
+    /// from a ring buffer, via `plain::from_bytes` and similar. The crate
+    /// using the skeleton has to depend on `plain`.
+    ///
+    /// Default is `false`
+    pub fn plain(&mut self, plain: bool) -> &mut SkeletonBuilder {
+        self.gen_opts.type_opts.plain = plain;
+        self
+    }
+
+    /// Implement `bytemuck::Zeroable` and `bytemuck::Pod` for generated types
+    /// that are plain old data
+    ///
+    /// See [`SkeletonBuilder::plain`] for details. The crate using the
+    /// skeleton has to depend on `bytemuck`.
+    ///
+    /// Default is `false`
+    pub fn bytemuck(&mut self, bytemuck: bool) -> &mut SkeletonBuilder {
+        self.gen_opts.type_opts.bytemuck = bytemuck;
+        self
+    }
+
+    /// Add derives to generated structs and enums
+    ///
+    /// The derives are added in addition to the ones always present, e.g.,
+    /// `Debug` and `Clone`. Unions, struct_ops types, and structs
+    /// (transitively) containing pointers, unions, `MaybeUninit` wrapped
+    /// members, or arrays and padding of more than 32 elements, for which
+    /// most derives are not available, are left alone.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libbpf_cargo::SkeletonBuilder;
+    ///
+    /// SkeletonBuilder::new()
+    ///     .source("myobject.bpf.c")
+    ///     .derive(["serde::Serialize", "Hash"])
+    ///     .build_and_generate("/output/path")
+    ///     .unwrap();
+    /// ```
+    pub fn derive<D, S>(&mut self, derives: D) -> &mut SkeletonBuilder
+    where
+        D: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.gen_opts.type_opts.derives = derives
+            .into_iter()
+            .map(|derive| derive.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Add attributes to generated structs and enums
+    ///
+    /// Attributes are provided without the surrounding `#[...]`, e.g.,
+    /// `serde(rename_all = "camelCase")`, and are placed after the derives.
+    pub fn attributes<A, S>(&mut self, attributes: A) -> &mut SkeletonBuilder
+    where
+        A: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.gen_opts.type_opts.attributes = attributes
+            .into_iter()
+            .map(|attribute| attribute.as_ref().to_string())
+            .collect();
         self
     }
 
@@ -221,6 +599,44 @@ impl SkeletonBuilder {
         Ok(())
     }
 
+    /// Build BPF programs and generate the skeleton into `OUT_DIR`
+    ///
+    /// For a source file `<NAME>.bpf.c`, the skeleton is placed at
+    /// `$OUT_DIR/<NAME>.skel.rs`, from where it can be included using
+    /// `libbpf_rs::include_skel!`. That way generated code never has to be
+    /// placed in the source tree. Returns the path of the skeleton.
+    ///
+    /// `OUT_DIR` is set by `cargo` for build scripts only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libbpf_cargo::SkeletonBuilder;
+    ///
+    /// // In build.rs:
+    /// SkeletonBuilder::new()
+    ///     .source("src/bpf/myobject.bpf.c")
+    ///     .build_and_generate_out_dir()
+    ///     .unwrap();
+    ///
+    /// // In the crate itself:
+    /// // libbpf_rs::include_skel!(myobject);
+    /// ```
+    pub fn build_and_generate_out_dir(&mut self) -> Result<PathBuf> {
+        let out_dir = env::var_os("OUT_DIR")
+            .ok_or_else(|| anyhow!("`OUT_DIR` is not set; not running in a build script?"))?;
+
+        self.build()?;
+        // `build` succeeded, so we know that a source with a valid file
+        // name is present.
+        let filename = self.source.as_ref().unwrap().file_name().unwrap();
+        let name = filename.to_str().unwrap().split('.').next().unwrap();
+        let output = Path::new(&out_dir).join(format!("{name}.skel.rs"));
+        self.generate(&output)?;
+
+        Ok(output)
+    }
+
     // Build BPF programs without generating a skeleton.
     //
     // [`SkeletonBuilder::source`] must be set for this to succeed.
@@ -252,7 +668,7 @@ impl SkeletonBuilder {
             self.dir = Some(dir);
         }
 
-        build::build_single(
+        self.deps = build::build_single(
             self.debug,
             source,
             // Unwrap is safe here since we guarantee that obj.is_some() above
@@ -260,23 +676,82 @@ impl SkeletonBuilder {
             self.clang.as_ref(),
             self.skip_clang_version_check,
             self.clang_args.clone(),
+            &self.build_opts,
         )
         .with_context(|| format!("failed to build `{}`", source.display()))?;
 
         Ok(())
     }
 
+    /// Retrieve the files the last [`SkeletonBuilder::build`] depended on
+    ///
+    /// The list comprises the source file itself as well as all headers it
+    /// included, e.g., `vmlinux.h`, as reported by `clang`. It is empty if
+    /// nothing has been built yet.
+    pub fn generated_deps(&self) -> &[PathBuf] {
+        &self.deps
+    }
+
+    /// Instruct `cargo` to rerun the build script if any of the files the
+    /// last [`SkeletonBuilder::build`] depended on changes
+    ///
+    /// This helper is meant to be invoked from build scripts after
+    /// building. It emits a `cargo:rerun-if-changed` line for each of the
+    /// [`SkeletonBuilder::generated_deps`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use libbpf_cargo::SkeletonBuilder;
+    ///
+    /// let mut builder = SkeletonBuilder::new();
+    /// builder
+    ///     .source("myobject.bpf.c")
+    ///     .build_and_generate("/output/path")
+    ///     .unwrap();
+    /// builder.emit_rerun_if_changed();
+    /// ```
+    pub fn emit_rerun_if_changed(&self) {
+        for dep in &self.deps {
+            println!("cargo:rerun-if-changed={}", dep.display());
+        }
+    }
+
     // Generate a skeleton at path `output` without building BPF programs.
     //
     // [`SkeletonBuilder::obj`] must be set for this to succeed.
+    //
+    // When invoked from a build script, the `pin_root`, `maps.<MAP>.pin`,
+    // and `progs.<PROG>.autoattach` settings of the package metadata for
+    // the object are applied, just as `cargo libbpf gen` does.
     pub fn generate<P: AsRef<Path>>(&mut self, output: P) -> Result<()> {
         let objfile = self.obj.as_ref().ok_or_else(|| anyhow!("No object file"))?;
 
+        let mut gen_opts = Cow::Borrowed(&self.gen_opts);
+        if let Some(dir) = env::var_os("CARGO_MANIFEST_DIR") {
+            // Objects are named after the first component of their file
+            // name, e.g., `<NAME>.bpf.o`.
+            let name = objfile
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(|name| name.split('.').next())
+                .unwrap_or_default();
+            let manifest = Path::new(&dir).join("Cargo.toml");
+            gen_opts.to_mut().skel_defaults = metadata::skel_defaults(&manifest, name)
+                .with_context(|| {
+                    format!(
+                        "failed to read package metadata from `{}`",
+                        manifest.display()
+                    )
+                })?;
+        }
+
         gen::gen_single(
             self.debug,
             objfile,
             gen::OutputDest::File(output.as_ref()),
-            Some(&self.rustfmt),
+            &self.formatting,
+            &gen_opts,
         )
         .with_context(|| format!("failed to generate `{}`", objfile.display()))?;
 
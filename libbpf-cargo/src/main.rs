@@ -4,16 +4,21 @@
 use std::ffi::OsString;
 use std::path::PathBuf;
 
+use anyhow::bail;
 use anyhow::Result;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
 
+mod btf;
 #[doc(hidden)]
 mod build;
+mod check;
 mod gen;
 mod make;
 mod metadata;
+mod watch;
 
 #[doc(hidden)]
 #[derive(Debug, Parser)]
@@ -48,6 +53,9 @@ enum Wrapper {
 #[derive(Debug, Args)]
 pub struct ClangOpts {
     /// Path to clang binary
+    ///
+    /// GCC's BPF backend (e.g., `bpf-gcc` or `bpf-unknown-none-gcc`) is
+    /// supported as well and detected automatically.
     #[arg(long, value_parser)]
     clang_path: Option<PathBuf>,
     /// Additional arguments to pass to `clang`.
@@ -56,6 +64,135 @@ pub struct ClangOpts {
     /// Skip clang version checks
     #[arg(long)]
     skip_clang_version_checks: bool,
+    /// The BPF target to compile for, i.e., `bpfel` or `bpfeb`
+    ///
+    /// When invoked from a build script, defaults to the target matching
+    /// the endianness reported by `CARGO_CFG_TARGET_ENDIAN`, i.e., that of
+    /// the crate's compilation target. Otherwise the endianness of the
+    /// host is used, in which case the BPF target has to be provided
+    /// explicitly when cross-compiling for a target of different
+    /// endianness.
+    #[arg(long, value_parser)]
+    bpf_target: Option<String>,
+    /// The BPF CPU, i.e., the instruction set level, to compile for
+    ///
+    /// Compilation fails if the compiler is too old to support the
+    /// requested level, unless version checks are skipped. GCC only
+    /// supports `v1` through `v4`.
+    #[arg(long, value_parser = ["generic", "probe", "v1", "v2", "v3", "v4"])]
+    bpf_cpu: Option<String>,
+    /// Generate a `vmlinux.h` from the BTF of the running kernel and make
+    /// it available to BPF programs
+    #[arg(long)]
+    gen_vmlinux: bool,
+    /// Generate a `vmlinux.h` from the BTF contained in the provided file
+    /// instead of the one of the running kernel (implies --gen-vmlinux)
+    #[arg(long, value_parser)]
+    vmlinux_btf: Option<PathBuf>,
+    /// Build objects reproducibly, i.e., byte-identical regardless of the
+    /// time and the directory the build happens in
+    #[arg(long)]
+    reproducible: bool,
+}
+
+/// Determine the BTF to generate a `vmlinux.h` from, if any.
+fn vmlinux_btf(gen_vmlinux: bool, vmlinux_btf: Option<PathBuf>) -> Option<btf::VmlinuxBtf> {
+    match (vmlinux_btf, gen_vmlinux) {
+        (Some(path), _) => Some(btf::VmlinuxBtf::Path(path)),
+        (None, true) => Some(btf::VmlinuxBtf::Kernel),
+        (None, false) => None,
+    }
+}
+
+/// Determine how generated skeletons get hold of object file contents.
+fn object_data(include_object: bool, runtime_object_dir: Option<PathBuf>) -> gen::ObjectData {
+    match (runtime_object_dir, include_object) {
+        (Some(dir), _) => gen::ObjectData::Runtime(dir),
+        (None, true) => gen::ObjectData::Include,
+        (None, false) => gen::ObjectData::Embed,
+    }
+}
+
+/// The formatter to use for generated code.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+enum Formatter {
+    /// Use rustfmt, falling back to the built-in pretty-printer if it can't be found
+    #[default]
+    Rustfmt,
+    /// Use the built-in pretty-printer
+    Builtin,
+    /// Do not format generated code
+    None,
+}
+
+/// A grouping of formatting specific options.
+#[derive(Debug, Args)]
+pub struct FormatOpts {
+    /// Path to rustfmt binary
+    #[arg(long, value_parser)]
+    rustfmt_path: Option<PathBuf>,
+    /// How to format generated code
+    #[arg(long, value_enum, default_value_t)]
+    formatter: Formatter,
+}
+
+impl FormatOpts {
+    fn formatting(self) -> Result<gen::Formatting> {
+        let formatting = match self.formatter {
+            Formatter::Rustfmt => gen::Formatting::Rustfmt(self.rustfmt_path),
+            Formatter::Builtin | Formatter::None if self.rustfmt_path.is_some() => {
+                bail!("--rustfmt-path can only be used with `--formatter rustfmt`")
+            }
+            Formatter::Builtin => gen::Formatting::Builtin,
+            Formatter::None => gen::Formatting::Disabled,
+        };
+        Ok(formatting)
+    }
+}
+
+/// A grouping of options for rebuilding on changes.
+#[derive(Debug, Args)]
+pub struct WatchOpts {
+    /// Keep running and rebuild whenever a BPF source file or any of the
+    /// headers it includes changes
+    #[arg(long)]
+    watch: bool,
+    /// Command to run using the shell after each successful rebuild
+    ///
+    /// Example: cargo libbpf build --watch --exec 'sudo ./target/debug/tool'
+    #[arg(long, value_name = "COMMAND", requires = "watch")]
+    exec: Option<String>,
+}
+
+/// BTF specific subcommands.
+#[derive(Debug, Subcommand)]
+enum BtfCommand {
+    /// Dump the BTF of a BPF object file
+    Dump {
+        /// Path to the BPF object file
+        object: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        /// The format to dump the BTF in
+        format: btf::BtfFormat,
+    },
+    /// Generate minimized BTF for BPF objects, for use on kernels without BTF
+    ///
+    /// For each target BTF file, a file of the same name is created in the
+    /// output directory, containing only the types and members of the
+    /// target that are required for the CO-RE relocations of the provided
+    /// objects.
+    MinCore {
+        #[arg(long = "object", value_name = "OBJECT", required = true)]
+        /// Path to a BPF object file (may be repeated)
+        objects: Vec<PathBuf>,
+        #[arg(long = "target", value_name = "TARGET", required = true)]
+        /// Path to the BTF of a target kernel, e.g., as obtained from
+        /// BTFHub (may be repeated)
+        targets: Vec<PathBuf>,
+        #[arg(long, value_parser)]
+        /// Directory to write minimized BTF files to
+        output_dir: PathBuf,
+    },
 }
 
 /// cargo-libbpf is a cargo subcommand that helps develop and build eBPF (BPF) programs.
@@ -69,20 +206,89 @@ enum Command {
         manifest_path: Option<PathBuf>,
         #[command(flatten)]
         clang_opts: ClangOpts,
+        #[command(flatten)]
+        watch_opts: WatchOpts,
     },
     /// Generate skeleton files
     Gen {
         #[arg(long, value_parser)]
         /// Path to top level Cargo.toml
         manifest_path: Option<PathBuf>,
-        #[arg(long, value_parser)]
-        /// Path to rustfmt binary
-        rustfmt_path: Option<PathBuf>,
+        #[command(flatten)]
+        format_opts: FormatOpts,
         #[arg(long, value_parser)]
         /// Generate skeleton for the specified object file and print results to stdout
         ///
         /// When specified, skeletons for the rest of the project will not be generated
         object: Option<PathBuf>,
+        #[arg(long)]
+        /// Generate a subskeleton instead of a skeleton
+        ///
+        /// A subskeleton provides access to the maps, programs, and global
+        /// variables of a BPF library after it got linked into another BPF
+        /// object. Only supported together with --object.
+        subskel: bool,
+        #[arg(long)]
+        /// Generate helpers for consuming ring buffer events asynchronously
+        ///
+        /// The generated code requires the `async` feature of libbpf-rs.
+        async_helpers: bool,
+        #[arg(long)]
+        /// Implement `plain::Plain` for generated types that are plain old data
+        ///
+        /// The generated code requires a dependency on the `plain` crate.
+        plain: bool,
+        #[arg(long)]
+        /// Implement `bytemuck::Pod` for generated types that are plain old data
+        ///
+        /// The generated code requires a dependency on the `bytemuck` crate.
+        bytemuck: bool,
+        #[arg(long = "derive", value_name = "DERIVE")]
+        /// Add a derive to generated structs and enums (may be repeated)
+        ///
+        /// Unions, structs containing pointers, and struct_ops types are
+        /// left alone.
+        ///
+        /// Example: cargo libbpf gen --derive serde::Serialize --derive Hash
+        derives: Vec<String>,
+        #[arg(long = "attribute", value_name = "ATTRIBUTE")]
+        /// Add an attribute, without the surrounding `#[...]`, to generated
+        /// structs and enums (may be repeated)
+        attributes: Vec<String>,
+        #[arg(long)]
+        /// Generate only the object's type definitions, for use in `no_std` crates
+        ///
+        /// The generated code depends on neither `libbpf-rs` nor `std`.
+        no_std: bool,
+        #[arg(long, conflicts_with_all = ["runtime_object_dir", "object", "subskel"])]
+        /// Reference object files via `include_bytes!` instead of embedding
+        /// their contents into generated skeletons
+        ///
+        /// Objects are referenced relative to the generated skeletons, so
+        /// this option can't be used together with --object, for which the
+        /// skeleton is written to stdout.
+        include_object: bool,
+        #[arg(long, value_name = "DIR", conflicts_with = "subskel")]
+        /// Make generated skeletons read object files from DIR at runtime
+        /// instead of embedding their contents
+        ///
+        /// The path can be overridden through the `obj_path` member of the
+        /// generated skeleton builder.
+        runtime_object_dir: Option<PathBuf>,
+    },
+    /// Dump or minimize BTF
+    Btf {
+        #[command(subcommand)]
+        cmd: BtfCommand,
+    },
+    /// Statically check built bpf objects for common problems
+    Check {
+        #[arg(long, value_parser)]
+        /// Path to top level Cargo.toml
+        manifest_path: Option<PathBuf>,
+        #[arg(long, value_parser)]
+        /// Check the specified object file instead of the objects of the project
+        object: Option<PathBuf>,
     },
     /// Build project
     Make {
@@ -98,9 +304,10 @@ enum Command {
         ///
         /// Example: cargo libbpf build -- --package mypackage
         cargo_build_args: Vec<String>,
-        #[arg(long, value_parser)]
-        /// Path to rustfmt binary
-        rustfmt_path: Option<PathBuf>,
+        #[command(flatten)]
+        format_opts: FormatOpts,
+        #[command(flatten)]
+        watch_opts: WatchOpts,
     },
 }
 
@@ -118,24 +325,82 @@ fn main() -> Result<()> {
                         clang_path,
                         clang_args,
                         skip_clang_version_checks,
+                        bpf_target,
+                        bpf_cpu,
+                        gen_vmlinux,
+                        vmlinux_btf: btf_path,
+                        reproducible,
                     },
-            } => build::build(
-                debug,
-                manifest_path.as_ref(),
-                clang_path.as_ref(),
-                clang_args,
-                skip_clang_version_checks,
-            ),
+                watch_opts: WatchOpts { watch, exec },
+            } => {
+                let build_opts = build::BuildOpts {
+                    bpf_target,
+                    bpf_cpu,
+                    vmlinux: vmlinux_btf(gen_vmlinux, btf_path),
+                    reproducible,
+                };
+                let build = || {
+                    build::build(
+                        debug,
+                        manifest_path.as_ref(),
+                        clang_path.as_ref(),
+                        clang_args.clone(),
+                        skip_clang_version_checks,
+                        &build_opts,
+                    )
+                };
+
+                if watch {
+                    watch::watch(debug, manifest_path.as_ref(), exec.as_deref(), build)
+                } else {
+                    build()
+                }
+            }
             Command::Gen {
                 manifest_path,
-                rustfmt_path,
+                format_opts,
                 object,
+                subskel,
+                async_helpers,
+                plain,
+                bytemuck,
+                derives,
+                attributes,
+                no_std,
+                include_object,
+                runtime_object_dir,
             } => gen::gen(
                 debug,
                 manifest_path.as_ref(),
-                rustfmt_path.as_ref(),
+                &format_opts.formatting()?,
                 object.as_ref(),
+                &gen::GenOpts {
+                    subskel,
+                    async_helpers,
+                    no_std,
+                    object_data: object_data(include_object, runtime_object_dir),
+                    skel_defaults: Default::default(),
+                    no_aliases: false,
+                    type_opts: gen::btf::TypeOpts {
+                        plain,
+                        bytemuck,
+                        derives,
+                        attributes,
+                    },
+                },
             ),
+            Command::Btf { cmd } => match cmd {
+                BtfCommand::Dump { object, format } => btf::dump(debug, &object, format),
+                BtfCommand::MinCore {
+                    objects,
+                    targets,
+                    output_dir,
+                } => btf::min_core(debug, &objects, &targets, &output_dir),
+            },
+            Command::Check {
+                manifest_path,
+                object,
+            } => check::check(debug, manifest_path.as_ref(), object.as_ref()),
             Command::Make {
                 manifest_path,
                 clang_opts:
@@ -143,20 +408,44 @@ fn main() -> Result<()> {
                         clang_path,
                         clang_args,
                         skip_clang_version_checks,
+                        bpf_target,
+                        bpf_cpu,
+                        gen_vmlinux,
+                        vmlinux_btf: btf_path,
+                        reproducible,
                     },
                 quiet,
                 cargo_build_args,
-                rustfmt_path,
-            } => make::make(
-                debug,
-                manifest_path.as_ref(),
-                clang_path.as_ref(),
-                clang_args,
-                skip_clang_version_checks,
-                quiet,
-                cargo_build_args,
-                rustfmt_path.as_ref(),
-            ),
+                format_opts,
+                watch_opts: WatchOpts { watch, exec },
+            } => {
+                let build_opts = build::BuildOpts {
+                    bpf_target,
+                    bpf_cpu,
+                    vmlinux: vmlinux_btf(gen_vmlinux, btf_path),
+                    reproducible,
+                };
+                let formatting = format_opts.formatting()?;
+                let make = || {
+                    make::make(
+                        debug,
+                        manifest_path.as_ref(),
+                        clang_path.as_ref(),
+                        clang_args.clone(),
+                        skip_clang_version_checks,
+                        quiet,
+                        cargo_build_args.clone(),
+                        &formatting,
+                        &build_opts,
+                    )
+                };
+
+                if watch {
+                    watch::watch(debug, manifest_path.as_ref(), exec.as_deref(), make)
+                } else {
+                    make()
+                }
+            }
         },
     }
 }
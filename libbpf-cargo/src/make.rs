@@ -7,6 +7,7 @@ use anyhow::Context;
 use anyhow::Result;
 
 use crate::build;
+use crate::build::BuildOpts;
 use crate::gen;
 
 #[allow(clippy::too_many_arguments)]
@@ -18,7 +19,8 @@ pub fn make(
     skip_clang_version_checks: bool,
     quiet: bool,
     cargo_build_args: Vec<String>,
-    rustfmt_path: Option<&PathBuf>,
+    formatting: &gen::Formatting,
+    build_opts: &BuildOpts,
 ) -> Result<()> {
     if !quiet {
         println!("Compiling BPF objects");
@@ -29,13 +31,21 @@ pub fn make(
         clang,
         clang_args,
         skip_clang_version_checks,
+        build_opts,
     )
     .context("Failed to compile BPF objects")?;
 
     if !quiet {
         println!("Generating skeletons");
     }
-    gen::gen(debug, manifest_path, None, rustfmt_path).context("Failed to generate skeletons")?;
+    gen::gen(
+        debug,
+        manifest_path,
+        formatting,
+        None,
+        &gen::GenOpts::default(),
+    )
+    .context("Failed to generate skeletons")?;
 
     let mut cmd = Command::new("cargo");
     cmd.arg("build");
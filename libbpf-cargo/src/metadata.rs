@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
@@ -10,10 +13,62 @@ use cargo_metadata::Package;
 use serde::Deserialize;
 use serde_json::value::Value;
 
+use crate::gen::SkelDefaults;
+
+#[derive(Clone, Default, Deserialize)]
+struct LibbpfMapMetadata {
+    #[serde(default)]
+    pin: bool,
+}
+
+#[derive(Clone, Default, Deserialize)]
+struct LibbpfProgMetadata {
+    autoattach: Option<bool>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+struct LibbpfObjectMetadata {
+    #[serde(default)]
+    clang_args: Vec<String>,
+    pin_root: Option<PathBuf>,
+    #[serde(default)]
+    maps: BTreeMap<String, LibbpfMapMetadata>,
+    #[serde(default)]
+    progs: BTreeMap<String, LibbpfProgMetadata>,
+}
+
+impl LibbpfObjectMetadata {
+    /// Gather the defaults to apply when opening the object's skeleton.
+    fn skel_defaults(&self) -> SkelDefaults {
+        SkelDefaults {
+            pin_root: self.pin_root.clone(),
+            pinned_maps: self
+                .maps
+                .iter()
+                .filter(|(_name, map)| map.pin)
+                .map(|(name, _map)| name.clone())
+                .collect(),
+            autoattach: self
+                .progs
+                .iter()
+                .filter_map(|(name, prog)| Some((name.clone(), prog.autoattach?)))
+                .collect(),
+        }
+    }
+}
+
 #[derive(Default, Deserialize)]
 struct LibbpfPackageMetadata {
     prog_dir: Option<PathBuf>,
+    #[serde(default)]
+    prog_dirs: Vec<PathBuf>,
     target_dir: Option<PathBuf>,
+    #[serde(default)]
+    objects: HashMap<String, LibbpfObjectMetadata>,
+    #[serde(default)]
+    derives: Vec<String>,
+    #[serde(default)]
+    attributes: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -33,6 +88,24 @@ pub struct UnprocessedObj {
     pub out: PathBuf,
     /// Object name (eg: `runqslower.bpf.c` -> `runqslower`)
     pub name: String,
+    /// Additional derives for generated types
+    pub derives: Vec<String>,
+    /// Additional attributes for generated types
+    pub attributes: Vec<String>,
+    /// Additional arguments to pass to clang when compiling this object
+    pub clang_args: Vec<OsString>,
+    /// Defaults to apply when opening the generated skeleton
+    pub skel_defaults: SkelDefaults,
+}
+
+/// Parse the `libbpf` specific metadata of `package`.
+fn libbpf_metadata(package: &Package) -> Result<LibbpfPackageMetadata> {
+    if package.metadata != Value::Null {
+        let PackageMetadata { libbpf } = serde_json::from_value(package.metadata.clone())?;
+        Ok(libbpf)
+    } else {
+        Ok(LibbpfPackageMetadata::default())
+    }
 }
 
 fn get_package(
@@ -45,26 +118,32 @@ fn get_package(
         println!("\t{}", package.metadata);
     }
 
-    let package_metadata = if package.metadata != Value::Null {
-        let PackageMetadata { libbpf } = serde_json::from_value(package.metadata.clone())?;
-        libbpf
-    } else {
-        LibbpfPackageMetadata::default()
-    };
+    let package_metadata = libbpf_metadata(package)?;
 
-    // Respect custom target directories specified by package
     let mut package_root = package.manifest_path.clone().into_std_path_buf();
     // Remove "Cargo.toml"
     package_root.pop();
-    if let Some(d) = package_metadata.prog_dir {
-        if debug {
-            println!("Custom prog_dir={}", d.to_string_lossy());
-        }
-        // Add requested path
-        package_root.push(d);
-    } else {
+
+    // Respect custom prog directories specified by package
+    let prog_dirs = package_metadata
+        .prog_dir
+        .iter()
+        .chain(package_metadata.prog_dirs.iter())
+        .collect::<Vec<_>>();
+    let prog_dirs = if prog_dirs.is_empty() {
         // Add default path
-        package_root.push("src/bpf");
+        vec![package_root.join("src/bpf")]
+    } else {
+        prog_dirs
+            .into_iter()
+            .map(|d| {
+                if debug {
+                    println!("Custom prog_dir={}", d.to_string_lossy());
+                }
+                // Add requested path
+                package_root.join(d)
+            })
+            .collect()
     };
 
     // Respect custom target directories specified by package
@@ -81,30 +160,27 @@ fn get_package(
         target_dir.push("bpf");
     };
 
-    // Get an iterator to the input directory. If directory is missing,
-    // skip the current project
-    let dir_iter = match fs::read_dir(&package_root) {
-        Ok(d) => d,
-        Err(e) => {
-            if let Some(ec) = e.raw_os_error() {
-                // ENOENT == 2
-                if ec == 2 {
-                    return Ok(vec![]);
+    let mut objs = Vec::new();
+    for prog_dir in prog_dirs {
+        // Get an iterator to the input directory. If directory is missing,
+        // skip it
+        let dir_iter = match fs::read_dir(&prog_dir) {
+            Ok(d) => d,
+            Err(e) => {
+                if let Some(ec) = e.raw_os_error() {
+                    // ENOENT == 2
+                    if ec == 2 {
+                        continue;
+                    } else {
+                        bail!("Invalid directory: {}: {}", prog_dir.to_string_lossy(), e);
+                    }
                 } else {
-                    bail!(
-                        "Invalid directory: {}: {}",
-                        package_root.to_string_lossy(),
-                        e
-                    );
+                    return Err(e.into());
                 }
-            } else {
-                return Err(e.into());
             }
-        }
-    };
+        };
 
-    Ok(dir_iter
-        .filter_map(|file| {
+        let () = objs.extend(dir_iter.filter_map(|file| {
             let path = match file {
                 Ok(f) => f.path(),
                 Err(_) => return None,
@@ -127,18 +203,48 @@ fn get_package(
                         .unwrap() // Already know it has enough '.'s
                         .to_string();
 
+                    let object_metadata = package_metadata
+                        .objects
+                        .get(&name)
+                        .cloned()
+                        .unwrap_or_default();
+
                     return Some(UnprocessedObj {
                         package: package.name.clone(),
                         out: target_dir.clone(),
                         path,
                         name,
+                        derives: package_metadata.derives.clone(),
+                        attributes: package_metadata.attributes.clone(),
+                        skel_defaults: object_metadata.skel_defaults(),
+                        clang_args: object_metadata
+                            .clang_args
+                            .into_iter()
+                            .map(OsString::from)
+                            .collect(),
                     });
                 }
             }
 
             None
-        })
-        .collect())
+        }));
+    }
+
+    // Objects are named after their source files, so equally named
+    // sources in different prog directories would clobber each other.
+    let mut names = HashMap::<&str, &Path>::new();
+    for obj in &objs {
+        if let Some(other) = names.insert(&obj.name, &obj.path) {
+            bail!(
+                "BPF programs {} and {} would both be compiled to {}.bpf.o",
+                other.display(),
+                obj.path.display(),
+                obj.name
+            );
+        }
+    }
+
+    Ok(objs)
 }
 
 /// Returns the `target_directory` and a list of objects to compile.
@@ -168,3 +274,40 @@ pub fn get(debug: bool, manifest_path: Option<&PathBuf>) -> Result<(PathBuf, Vec
 
     Ok((metadata.target_directory.into_std_path_buf(), v))
 }
+
+/// Retrieve the defaults to apply when opening the skeleton of the object
+/// `name` (as in `<NAME>.bpf.c`), as configured in the metadata of the
+/// package with the manifest at `manifest_path`.
+// Only used in libbpf-cargo library
+#[allow(dead_code)]
+pub fn skel_defaults(manifest_path: &Path, name: &str) -> Result<SkelDefaults> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .exec()
+        .context("Failed to get cargo metadata")?;
+
+    let manifest_path = manifest_path
+        .canonicalize()
+        .with_context(|| format!("Failed to canonicalize {}", manifest_path.display()))?;
+    let package = metadata
+        .packages
+        .iter()
+        .find(|package| {
+            package
+                .manifest_path
+                .as_std_path()
+                .canonicalize()
+                .is_ok_and(|path| path == manifest_path)
+        })
+        .with_context(|| format!("Failed to find package of {}", manifest_path.display()))?;
+
+    let package_metadata = libbpf_metadata(package)
+        .with_context(|| format!("Failed to process package={}", package.name))?;
+    let defaults = package_metadata
+        .objects
+        .get(name)
+        .map(LibbpfObjectMetadata::skel_defaults)
+        .unwrap_or_default();
+    Ok(defaults)
+}
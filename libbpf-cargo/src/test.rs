@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 use std::fs::create_dir;
+use std::fs::create_dir_all;
 use std::fs::read;
 use std::fs::read_to_string;
 use std::fs::write;
@@ -9,19 +10,33 @@ use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use std::slice;
 
 use goblin::Object;
 use libbpf_rs::btf::types;
 use libbpf_rs::btf::BtfType;
+use libbpf_rs::btf::HasSize as _;
 use libbpf_rs::Btf;
 use memmap2::Mmap;
 use tempfile::tempdir;
 use tempfile::NamedTempFile;
 use tempfile::TempDir;
 
+use crate::btf::dump_btf;
+use crate::btf::min_core;
+use crate::btf::BtfFormat;
+use crate::btf::VmlinuxBtf;
 use crate::build::build;
+use crate::build::BuildOpts;
+use crate::check::check_object;
+use crate::check::Severity;
 use crate::gen::btf::GenBtf;
+use crate::gen::btf::TypeOpts;
 use crate::make::make;
+use crate::metadata;
+use crate::watch::watched_files;
+use crate::Formatting;
+use crate::ObjectData;
 use crate::SkeletonBuilder;
 
 /// Creates a temporary directory and initializes a default cargo project inside.
@@ -138,22 +153,344 @@ fn test_build_default() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
     // No bpf progs yet
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     // Add prog dir
     create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     // Add a prog
     let _prog_file =
         File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
 
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
 
     // Validate generated object file
     validate_bpf_o(proj_dir.as_path().join("target/bpf/prog.bpf.o").as_path());
 }
 
+/// Check that a compilation database is emitted for built objects.
+#[test]
+fn test_build_compile_commands() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    let _prog_file =
+        File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
+
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        vec!["-DFOO".into()],
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let db = read_to_string(proj_dir.join("target/bpf/compile_commands.json"))
+        .expect("failed to read compilation database");
+    let db = serde_json::from_str::<serde_json::Value>(&db).unwrap();
+    let commands = db.as_array().unwrap();
+    assert_eq!(commands.len(), 1);
+
+    let command = &commands[0];
+    assert!(command["file"]
+        .as_str()
+        .unwrap()
+        .ends_with("src/bpf/prog.bpf.c"));
+    assert!(command["output"]
+        .as_str()
+        .unwrap()
+        .ends_with("target/bpf/prog.bpf.o"));
+    let arguments = command["arguments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|arg| arg.as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(arguments.contains(&"-DFOO"));
+    assert!(arguments.contains(&"-target"));
+}
+
+/// Check that reproducible builds of the same program in different
+/// project directories result in byte-identical objects.
+#[test]
+fn test_build_reproducible() {
+    let build_proj = || {
+        let (dir, proj_dir, cargo_toml) = setup_temp_project();
+        create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+        add_vmlinux_header(&proj_dir);
+        write(
+            proj_dir.join("src/bpf/prog.bpf.c"),
+            r#"
+                #include "vmlinux.h"
+                #include <bpf/bpf_helpers.h>
+
+                int value = 0;
+
+                SEC("kprobe/foo")
+                int handle__foo(void *ctx) {
+                    value = __LINE__;
+                    return 0;
+                }
+
+                char LICENSE[] SEC("license") = "GPL";
+            "#,
+        )
+        .expect("failed to write prog.bpf.c");
+
+        build(
+            true,
+            Some(&cargo_toml),
+            None,
+            Vec::new(),
+            true,
+            &BuildOpts {
+                reproducible: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let obj = read(proj_dir.join("target/bpf/prog.bpf.o")).expect("failed to read object");
+        let proj_dir = proj_dir.to_string_lossy().into_owned();
+        assert!(!obj
+            .windows(proj_dir.len())
+            .any(|window| window == proj_dir.as_bytes()));
+        (dir, obj)
+    };
+
+    let (_dir1, obj1) = build_proj();
+    let (_dir2, obj2) = build_proj();
+    assert_eq!(obj1, obj2);
+}
+
+/// Check that the files watched for changes include all sources and
+/// headers in the prog directory as well as headers included from
+/// elsewhere.
+#[test]
+fn test_watched_files() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(proj_dir.join("src/common.h"), "#define FOO 1\n").expect("failed to write common.h");
+    write(proj_dir.join("src/bpf/prog.h"), "").expect("failed to write prog.h");
+    write(proj_dir.join("src/bpf/mod.rs"), "").expect("failed to write mod.rs");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        "#include \"../common.h\"\n#include \"prog.h\"\n",
+    )
+    .expect("failed to write prog.bpf.c");
+
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let files = watched_files(true, Some(&cargo_toml)).unwrap();
+    let bpf_dir = proj_dir.join("src/bpf").canonicalize().unwrap();
+    let files = files
+        .iter()
+        .filter_map(|file| file.canonicalize().ok())
+        .collect::<HashSet<_>>();
+    assert!(files.contains(&bpf_dir));
+    assert!(files.contains(&bpf_dir.join("prog.bpf.c")));
+    assert!(files.contains(&bpf_dir.join("prog.h")));
+    assert!(files.contains(&proj_dir.join("src/common.h").canonicalize().unwrap()));
+    // Generated skeletons in the prog directory are not of interest.
+    assert!(!files.contains(&bpf_dir.join("mod.rs")));
+}
+
+/// Check that dependencies are picked up from the dependency file stored
+/// alongside the object file by a previous build.
+#[test]
+fn test_watched_files_depfile() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(proj_dir.join("src/common.h"), "").expect("failed to write common.h");
+    write(proj_dir.join("src/bpf/prog.bpf.c"), "").expect("failed to write prog.bpf.c");
+
+    let out_dir = proj_dir.join("target/bpf");
+    let () = create_dir_all(&out_dir).expect("failed to create output dir");
+    let common = proj_dir.join("src/common.h");
+    write(
+        out_dir.join("prog.bpf.d"),
+        format!(
+            "{}: {}\n",
+            out_dir.join("prog.bpf.o").display(),
+            common.display()
+        ),
+    )
+    .expect("failed to write prog.bpf.d");
+
+    let files = watched_files(true, Some(&cargo_toml)).unwrap();
+    assert!(files.contains(&common));
+}
+
+/// Check that unchanged objects are not recompiled, while changes to
+/// included headers trigger recompilation.
+#[test]
+fn test_build_cached() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(proj_dir.join("src/bpf/prog.h"), "#define VALUE 1\n").expect("failed to write prog.h");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        "#include \"prog.h\"\nint value = VALUE;\n",
+    )
+    .expect("failed to write prog.bpf.c");
+    let _prog_file =
+        File::create(proj_dir.join("src/bpf/other.bpf.c")).expect("failed to create prog file");
+
+    let obj = proj_dir.join("target/bpf/prog.bpf.o");
+    let modified = || obj.metadata().unwrap().modified().unwrap();
+
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+    validate_bpf_o(&obj);
+    validate_bpf_o(&proj_dir.join("target/bpf/other.bpf.o"));
+    let first = modified();
+
+    // Nothing changed, so the object should be left alone.
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+    assert_eq!(modified(), first);
+
+    // Different flags require recompilation.
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        vec!["-DFOO".into()],
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+    let second = modified();
+    assert_ne!(second, first);
+
+    // As does a change to an included header.
+    write(proj_dir.join("src/bpf/prog.h"), "#define VALUE 2\n").expect("failed to write prog.h");
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        vec!["-DFOO".into()],
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+    assert_ne!(modified(), second);
+}
+
+/// Check that we can generate a `vmlinux.h` as part of the build.
+#[test]
+fn test_build_gen_vmlinux() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    let source = proj_dir.join("src/bpf/prog.bpf.c");
+    write(
+        &source,
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+
+            SEC("kprobe/foo")
+            int this_is_my_prog(struct pt_regs *ctx)
+            {
+                struct task_struct *task = (void *)bpf_get_current_task();
+                return task != NULL;
+            }
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Without a `vmlinux.h` compilation fails...
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
+    // ... unless we generate one.
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts {
+            vmlinux: Some(VmlinuxBtf::Kernel),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    validate_bpf_o(&proj_dir.join("target/bpf/prog.bpf.o"));
+    let header = read_to_string(proj_dir.join("target/bpf/vmlinux/vmlinux.h")).unwrap();
+    assert!(header.contains("struct task_struct {"));
+
+    let btf = Path::new("/sys/kernel/btf/vmlinux");
+    let skel = NamedTempFile::new().unwrap();
+    let mut builder = SkeletonBuilder::new();
+    builder
+        .source(&source)
+        .clang("clang")
+        .vmlinux_btf(btf)
+        .build_and_generate(skel.path())
+        .unwrap();
+    assert!(builder.generated_deps().iter().any(|dep| dep == btf));
+}
+
 #[test]
 fn test_build_invalid_prog() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
@@ -166,7 +503,15 @@ fn test_build_invalid_prog() {
         File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
     writeln!(prog_file, "1").expect("write to prog file failed");
 
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 }
 
 #[test]
@@ -185,14 +530,30 @@ fn test_build_custom() {
         .expect("write to Cargo.toml failed");
 
     // No bpf progs yet
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     // Add a prog
     create_dir(proj_dir.join("src/other_bpf_dir")).expect("failed to create prog dir");
     let _prog_file = File::create(proj_dir.join("src/other_bpf_dir/prog.bpf.c"))
         .expect("failed to create prog file");
 
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
 
     // Validate generated object file
     validate_bpf_o(
@@ -203,6 +564,102 @@ fn test_build_custom() {
     );
 }
 
+/// Check that progs from multiple directories get built, with per-object
+/// clang arguments applied.
+#[test]
+fn test_build_multiple_dirs_object_args() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add custom build rules
+    let mut cargo_toml_file = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+    writeln!(
+        cargo_toml_file,
+        r#"
+        [package.metadata.libbpf]
+        prog_dirs = ["src/bpf", "src/other_bpf_dir"]
+
+        [package.metadata.libbpf.objects.other]
+        clang_args = ["-DPURPOSE=you_pass_the_butter"]
+        "#
+    )
+    .expect("write to Cargo.toml failed");
+
+    // Add progs
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    create_dir(proj_dir.join("src/other_bpf_dir")).expect("failed to create prog dir");
+    let _prog_file =
+        File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
+    let mut prog_file = File::create(proj_dir.join("src/other_bpf_dir/other.bpf.c"))
+        .expect("failed to create prog file");
+    writeln!(
+        prog_file,
+        r#"
+        #ifndef PURPOSE
+        #error "what is my purpose?"
+        #endif
+        "#
+    )
+    .expect("write to prog file failed");
+
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    // Validate generated object files
+    validate_bpf_o(proj_dir.as_path().join("target/bpf/prog.bpf.o").as_path());
+    validate_bpf_o(proj_dir.as_path().join("target/bpf/other.bpf.o").as_path());
+}
+
+/// Check that we fail building progs of the same name located in
+/// different directories.
+#[test]
+fn test_build_multiple_dirs_name_collision() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    let mut cargo_toml_file = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+    writeln!(
+        cargo_toml_file,
+        r#"
+        [package.metadata.libbpf]
+        prog_dirs = ["src/bpf", "src/other_bpf_dir"]
+        "#
+    )
+    .expect("write to Cargo.toml failed");
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    create_dir(proj_dir.join("src/other_bpf_dir")).expect("failed to create prog dir");
+    let _prog_file =
+        File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
+    let _prog_file = File::create(proj_dir.join("src/other_bpf_dir/prog.bpf.c"))
+        .expect("failed to create prog file");
+
+    let err = build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("would both be compiled to prog.bpf.o"),
+        "{err:#}"
+    );
+}
+
 #[test]
 fn test_unknown_metadata_section() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
@@ -222,13 +679,29 @@ fn test_unknown_metadata_section() {
 
     // Add prog dir
     create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     // Add a prog
     let _prog_file =
         File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
 
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
 
     // Validate generated object file
     validate_bpf_o(proj_dir.as_path().join("target/bpf/prog.bpf.o").as_path());
@@ -240,23 +713,55 @@ fn test_enforce_file_extension() {
 
     // Add prog dir
     create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     let _prog_file = File::create(proj_dir.join("src/bpf/prog_BAD_EXTENSION.c"))
         .expect("failed to create prog file");
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     let _prog_file_again = File::create(proj_dir.join("src/bpf/prog_GOOD_EXTENSION.bpf.c"))
         .expect("failed to create prog file");
-    build(true, Some(&cargo_toml), None, Vec::new(), true).unwrap();
-}
-
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
+}
+
 #[test]
 fn test_build_workspace() {
     let (_dir, _, workspace_cargo_toml, proj_one_dir, proj_two_dir) = setup_temp_workspace();
 
     // No bpf progs yet
-    build(true, Some(&workspace_cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&workspace_cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 
     // Create bpf prog for project one
     create_dir(proj_one_dir.join("src/bpf")).expect("failed to create prog dir");
@@ -268,7 +773,15 @@ fn test_build_workspace() {
     let _prog_file_2 = File::create(proj_two_dir.join("src/bpf/prog2.bpf.c"))
         .expect("failed to create prog file 2");
 
-    build(true, Some(&workspace_cargo_toml), None, Vec::new(), true).unwrap();
+    build(
+        true,
+        Some(&workspace_cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap();
 }
 
 #[test]
@@ -285,7 +798,15 @@ fn test_build_workspace_collision() {
     let _prog_file_2 = File::create(proj_two_dir.join("src/bpf/prog.bpf.c"))
         .expect("failed to create prog file 2");
 
-    build(true, Some(&workspace_cargo_toml), None, Vec::new(), true).unwrap_err();
+    build(
+        true,
+        Some(&workspace_cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .unwrap_err();
 }
 
 #[test]
@@ -307,7 +828,8 @@ fn test_make_basic() {
         true,
         true,
         Vec::new(),
-        None,
+        &Default::default(),
+        &BuildOpts::default(),
     )
     .unwrap();
 
@@ -345,7 +867,8 @@ fn test_make_workspace() {
         true,
         true,
         Vec::new(),
-        None,
+        &Default::default(),
+        &BuildOpts::default(),
     )
     .unwrap();
 
@@ -376,28 +899,740 @@ fn test_make_workspace() {
         .exists());
 }
 
-#[test]
-fn test_skeleton_empty_source() {
-    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+#[test]
+fn test_skeleton_empty_source() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let _prog_file =
+        File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
+
+    make(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        true,
+        Vec::new(),
+        &Default::default(),
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #![warn(elided_lifetimes_in_paths)]
+        mod bpf;
+        use bpf::*;
+        use libbpf_rs::skel::SkelBuilder;
+        use libbpf_rs::skel::OpenSkel;
+
+        fn main() {{
+            let builder = ProgSkelBuilder::default();
+            let _skel = builder
+                .open()
+                .expect("failed to open skel")
+                .load()
+                .expect("failed to load skel");
+        }}
+        "#,
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+#[test]
+fn test_skeleton_basic() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 1024);
+                __type(key, u32);
+                __type(value, u64);
+        }} mymap SEC(".maps");
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    make(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        true,
+        Vec::new(),
+        &Default::default(),
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #![warn(elided_lifetimes_in_paths)]
+        mod bpf;
+        use bpf::*;
+        use libbpf_rs::skel::SkelBuilder;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::Skel;
+
+        fn main() {{
+            let builder = ProgSkelBuilder::default();
+            let mut open_skel = builder
+                .open()
+                .expect("failed to open skel");
+
+            // Check that we can grab handles to open maps/progs
+            let _open_map = open_skel.maps().mymap();
+            let _open_prog = open_skel.progs().this_is_my_prog();
+            let _open_map_mut = open_skel.maps_mut().mymap();
+            let _open_prog_mut = open_skel.progs_mut().this_is_my_prog();
+
+            // Check that we can control auto-attachment
+            open_skel.progs_mut().this_is_my_prog().set_autoattach(true);
+
+            // Check that we can conditionally load programs
+            let _loaded = open_skel
+                .progs_mut()
+                .this_is_my_prog()
+                .load_if(|| Ok(true))
+                .expect("failed to set autoload");
+
+            let mut skel = open_skel
+                .load()
+                .expect("failed to load skel");
+
+            // Check that we can grab handles to loaded maps/progs
+            let _map = skel.maps().mymap();
+            let _prog = skel.progs().this_is_my_prog();
+            let _map_mut = skel.maps_mut().mymap();
+            let _prog_mut = skel.progs_mut().this_is_my_prog();
+
+            // Check that attach() is generated
+            skel.attach().expect("failed to attach progs");
+
+            // Check that attach_partial() is generated and skips
+            // already attached programs
+            let report = skel.attach_partial();
+            assert_eq!(report.skipped, vec!["this_is_my_prog".to_string()]);
+
+            // Check that Option<Link> field is generated
+            let _mylink = skel.links.this_is_my_prog.unwrap();
+        }}
+        "#,
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+#[test]
+fn test_skeleton_multiple_objects() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add two progs, each with its own types, to the same directory
+    for name in ["one", "two"] {
+        let mut prog = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(proj_dir.join(format!("src/bpf/{name}.bpf.c")))
+            .expect("failed to open prog");
+
+        write!(
+            prog,
+            r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+
+            int {name}_counter = 0;
+
+            SEC("kprobe/foo")
+            int {name}_prog(u64 *ctx)
+            {{
+                    {name}_counter++;
+                    return 0;
+            }}
+            "#,
+        )
+        .expect("failed to write prog");
+    }
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    make(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        true,
+        Vec::new(),
+        &Default::default(),
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    // Publicly export the generated mod.rs from a library, so that
+    // ambiguous glob re-exports get reported.
+    write(proj_dir.join("src/lib.rs"), "pub mod bpf;\n").expect("failed to write lib.rs");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        use proj::bpf::*;
+
+        fn main() {{
+            let _one = OneSkelBuilder::default();
+            let _two = TwoSkelBuilder::default();
+
+            let _bss: Option<one_types::bss> = None;
+            let _bss: Option<two_types::bss> = None;
+
+            assert_eq!(one_progs::names::ONE_PROG, "one_prog");
+            assert_eq!(two_progs::names::TWO_PROG, "two_prog");
+        }}
+        "#,
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+
+    let one = read_to_string(proj_dir.join("src/bpf/one.skel.rs")).unwrap();
+    assert!(!one.contains("as types;"));
+    assert!(!one.contains("as progs;"));
+}
+
+#[test]
+fn test_skeleton_generate_datasec_static() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                bpf_printk("this should not cause an error");
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    make(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        true,
+        Vec::new(),
+        &Default::default(),
+        &BuildOpts::default(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_skeleton_datasec() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        int myglobal = 0;
+        void * const myconst = 0;
+        int mycustomdata SEC(".data.custom");
+        int mycustombss SEC(".bss.custom");
+        const int mycustomrodata SEC(".rodata.custom.1") = 43;
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    make(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        true,
+        Vec::new(),
+        &Default::default(),
+        &BuildOpts::default(),
+    )
+    .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #![warn(elided_lifetimes_in_paths)]
+        mod bpf;
+        use bpf::*;
+        use libbpf_rs::skel::SkelBuilder;
+        use libbpf_rs::skel::OpenSkel;
+
+        fn main() {{
+            let builder = ProgSkelBuilder::default();
+            let mut open_skel = builder
+                .open()
+                .expect("failed to open skel");
+
+            // Check that we set rodata vars before load
+            open_skel.rodata_mut().myconst = std::ptr::null_mut();
+
+            // We can always set bss vars
+            open_skel.bss_mut().myglobal = 42;
+
+            open_skel.data_custom_mut().mycustomdata = 1337;
+            open_skel.bss_custom_mut().mycustombss = 12;
+            assert_eq!(open_skel.rodata_custom_1().mycustomrodata, 43);
+
+            let mut skel = open_skel
+                .load()
+                .expect("failed to load skel");
+
+            // We can always set bss vars
+            skel.bss_mut().myglobal = 24;
+            skel.data_custom_mut().mycustomdata += 1;
+            skel.bss_custom_mut().mycustombss += 1;
+            assert_eq!(skel.rodata_custom_1().mycustomrodata, 43);
+
+            // Read only for rodata after load
+            let _rodata: &prog_types::rodata = skel.rodata();
+        }}
+        "#,
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+#[test]
+fn test_skeleton_builder_basic() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct unique_key {{
+            int cap;
+            u32 tgid;
+            u64 cgroupid;
+        }};
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 1024);
+                __type(key, struct unique_key);
+                __type(value, u64);
+        }} mymap SEC(".maps");
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 1024);
+                __uint(key_size, 8);
+                __type(value, u64);
+        }} mymap2 SEC(".maps");
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
+        use libbpf_rs::skel::SkelBuilder;
+        use libbpf_rs::skel::OpenSkel;
+        use libbpf_rs::skel::Skel;
+
+        fn main() {{
+            let builder = ProgSkelBuilder::default();
+            let mut open_skel = builder
+                .open()
+                .expect("failed to open skel");
+
+            // Check that we can grab handles to open maps/progs
+            let _open_map = open_skel.maps().mymap();
+            let _open_map2 = open_skel.maps().mymap2();
+            let _open_prog = open_skel.progs().this_is_my_prog();
+            let _open_map_mut = open_skel.maps_mut().mymap();
+            let _open_map2_mut = open_skel.maps_mut().mymap2();
+            let _open_prog_mut = open_skel.progs_mut().this_is_my_prog();
+
+            // Check that programs can be looked up by their generated names
+            assert_eq!(progs::names::THIS_IS_MY_PROG, "this_is_my_prog");
+            assert_eq!(prog_progs::sections::THIS_IS_MY_PROG, "kprobe/foo");
+            assert_eq!(progs::ALL, &[progs::names::THIS_IS_MY_PROG]);
+            for (name, prog) in open_skel.progs().iter() {{
+                assert_eq!(prog.name(), name);
+            }}
+
+            let mut skel = open_skel
+                .load()
+                .expect("failed to load skel");
+
+            // Check that we can grab handles to loaded maps/progs
+            let _map = skel.maps().mymap();
+            let _map2 = skel.maps().mymap2();
+            let _prog = skel.progs().this_is_my_prog();
+            let _map_mut = skel.maps_mut().mymap();
+            let _map2_mut = skel.maps_mut().mymap2();
+            let _prog_mut = skel.progs_mut().this_is_my_prog();
+            assert_eq!(skel.progs().iter().count(), progs::ALL.len());
+
+            // Check that attach() is generated
+            skel.attach().expect("failed to attach progs");
+
+            // Check that Option<Link> field is generated
+            let _mylink = skel.links.this_is_my_prog.unwrap();
+
+            let _key = prog_types::unique_key::default();
+        }}
+        "#,
+        skel_path = skel.path().display(),
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+/// Check that generated code with custom derives compiles, i.e., that
+/// the derives are only applied to types supporting them.
+#[test]
+fn test_skeleton_builder_custom_derives() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        enum kind {{
+            KIND_A,
+            KIND_B,
+        }};
+
+        union value {{
+            u32 x;
+            u64 y;
+        }};
 
-    // Add prog dir
-    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+        struct with_union {{
+            enum kind kind;
+            union value value;
+        }};
 
-    // Add a prog
-    let _prog_file =
-        File::create(proj_dir.join("src/bpf/prog.bpf.c")).expect("failed to create prog file");
+        struct nested {{
+            struct with_union inner;
+        }};
 
-    make(
-        true,
-        Some(&cargo_toml),
-        None,
-        Vec::new(),
-        true,
-        true,
-        Vec::new(),
-        None,
+        struct with_bool {{
+            bool flag;
+        }};
+
+        struct with_array {{
+            u8 data[64];
+        }};
+
+        struct derivable {{
+            enum kind kind;
+            u32 data[4];
+        }};
+
+        struct nested nested = {{0}};
+        struct with_bool with_bool = {{0}};
+        struct with_array with_array = {{0}};
+        struct derivable derivable = {{0}};
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                return 0;
+        }}
+        "#,
     )
-    .unwrap();
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .derive(["Hash"])
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
 
     let mut cargo = OpenOptions::new()
         .append(true)
@@ -423,21 +1658,23 @@ fn test_skeleton_empty_source() {
     write!(
         source,
         r#"
-        #![warn(elided_lifetimes_in_paths)]
-        mod bpf;
-        use bpf::*;
-        use libbpf_rs::skel::SkelBuilder;
-        use libbpf_rs::skel::OpenSkel;
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
+        use std::hash::Hash;
+
+        fn hashable<T: Hash>(_value: &T) {{}}
 
         fn main() {{
-            let builder = ProgSkelBuilder::default();
-            let _skel = builder
-                .open()
-                .expect("failed to open skel")
-                .load()
-                .expect("failed to load skel");
+            let _nested = prog_types::nested::default();
+            let _with_bool = prog_types::with_bool::default();
+            let _with_array = prog_types::with_array::default();
+
+            let () = hashable(&prog_types::derivable::default());
+            let () = hashable(&prog_types::kind::KIND_B);
         }}
         "#,
+        skel_path = skel.path().display(),
     )
     .expect("failed to write to main.rs");
 
@@ -453,7 +1690,7 @@ fn test_skeleton_empty_source() {
 }
 
 #[test]
-fn test_skeleton_basic() {
+fn test_skeleton_builder_out_dir() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
     // Add prog dir
@@ -492,17 +1729,175 @@ fn test_skeleton_basic() {
     // Lay down the necessary header files
     add_vmlinux_header(&proj_dir);
 
-    make(
-        true,
-        Some(&cargo_toml),
-        None,
-        Vec::new(),
-        true,
-        true,
-        Vec::new(),
-        None,
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs and libbpf-cargo
+    // versions
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+
+        [build-dependencies]
+        libbpf-cargo = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display(),
+        env!("CARGO_MANIFEST_DIR"),
     )
-    .unwrap();
+    .expect("failed to write to Cargo.toml");
+
+    // Generate the skeleton into `OUT_DIR` from a build script...
+    write(
+        proj_dir.join("build.rs"),
+        r#"
+        use libbpf_cargo::SkeletonBuilder;
+
+        fn main() {
+            let skel = SkeletonBuilder::new()
+                .source("src/bpf/prog.bpf.c")
+                .build_and_generate_out_dir()
+                .expect("failed to generate skeleton");
+            let out_dir = std::env::var_os("OUT_DIR").unwrap();
+            assert_eq!(skel, std::path::Path::new(&out_dir).join("prog.skel.rs"));
+            println!("cargo:rerun-if-changed=src/bpf/prog.bpf.c");
+        }
+        "#,
+    )
+    .expect("failed to write build.rs");
+
+    // ... and include it in both of the supported ways.
+    write(
+        proj_dir.join("src/main.rs"),
+        r#"
+        use libbpf_rs::skel::SkelBuilder;
+        use libbpf_rs::skel::OpenSkel;
+
+        libbpf_rs::include_skel!(pub(crate) prog);
+
+        mod skel {
+            libbpf_rs::include_skel!("prog");
+        }
+
+        fn main() {
+            let open_skel = prog::ProgSkelBuilder::default()
+                .open()
+                .expect("failed to open skel");
+            let _open_map = open_skel.maps().mymap();
+            let _open_prog = open_skel.progs().this_is_my_prog();
+
+            let _builder = skel::ProgSkelBuilder::default();
+        }
+        "#,
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
+
+/// Check that program constants don't clash, regardless of program names.
+#[test]
+fn test_skeleton_prog_consts() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    let write_prog = |progs: &[&str]| {
+        let progs = progs
+            .iter()
+            .map(|name| format!("SEC(\"kprobe/{name}\") int {name}(u64 *ctx) {{ return 0; }}\n"))
+            .collect::<String>();
+        write(
+            proj_dir.join("src/bpf/prog.bpf.c"),
+            format!("#include \"vmlinux.h\"\n#include <bpf/bpf_helpers.h>\n{progs}"),
+        )
+        .expect("failed to write prog.bpf.c");
+    };
+    add_vmlinux_header(&proj_dir);
+
+    let skel = proj_dir.join("src/bpf/prog.skel.rs");
+    let generate = || {
+        SkeletonBuilder::new()
+            .source(proj_dir.join("src/bpf/prog.bpf.c"))
+            .build_and_generate(&skel)
+    };
+
+    let () = write_prog(&["names", "all", "foo", "foo_section"]);
+    let () = generate().unwrap();
+    let skel = read_to_string(&skel).unwrap();
+    assert!(skel.contains("pub const NAMES: &str = \"names\";"));
+    assert!(skel.contains("pub const FOO: &str = \"kprobe/foo\";"));
+    assert!(skel.contains("pub const FOO_SECTION: &str = \"foo_section\";"));
+    let all = skel
+        .lines()
+        .find(|line| line.contains("pub const ALL: &[&str]"))
+        .unwrap();
+    for name in ["NAMES", "ALL", "FOO", "FOO_SECTION"] {
+        assert!(all.contains(&format!("names::{name}")));
+    }
+
+    // Names only differing in case can't be told apart.
+    let () = write_prog(&["foo", "FOO"]);
+    let err = generate().unwrap_err();
+    assert!(format!("{err:#}").contains("would both map to constant `FOO`"));
+}
+
+#[test]
+fn test_skeleton_builder_async_helpers() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_RINGBUF);
+                __uint(max_entries, 4096);
+        }} events SEC(".maps");
+
+        SEC("kprobe/foo")
+        int this_is_my_prog(u64 *ctx)
+        {{
+                bpf_ringbuf_output(&events, ctx, sizeof(*ctx), 0);
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .async_helpers(true)
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
 
     let mut cargo = OpenOptions::new()
         .append(true)
@@ -513,7 +1908,7 @@ fn test_skeleton_basic() {
     writeln!(
         cargo,
         r#"
-        libbpf-rs = {{ path = "{}" }}
+        libbpf-rs = {{ path = "{}", features = ["async"] }}
         "#,
         get_libbpf_rs_path().as_path().display()
     )
@@ -528,42 +1923,26 @@ fn test_skeleton_basic() {
     write!(
         source,
         r#"
-        #![warn(elided_lifetimes_in_paths)]
-        mod bpf;
-        use bpf::*;
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
         use libbpf_rs::skel::SkelBuilder;
         use libbpf_rs::skel::OpenSkel;
-        use libbpf_rs::skel::Skel;
 
         fn main() {{
             let builder = ProgSkelBuilder::default();
-            let mut open_skel = builder
+            let skel = builder
                 .open()
-                .expect("failed to open skel");
-
-            // Check that we can grab handles to open maps/progs
-            let _open_map = open_skel.maps().mymap();
-            let _open_prog = open_skel.progs().this_is_my_prog();
-            let _open_map_mut = open_skel.maps_mut().mymap();
-            let _open_prog_mut = open_skel.progs_mut().this_is_my_prog();
-
-            let mut skel = open_skel
+                .expect("failed to open skel")
                 .load()
                 .expect("failed to load skel");
 
-            // Check that we can grab handles to loaded maps/progs
-            let _map = skel.maps().mymap();
-            let _prog = skel.progs().this_is_my_prog();
-            let _map_mut = skel.maps_mut().mymap();
-            let _prog_mut = skel.progs_mut().this_is_my_prog();
-
-            // Check that attach() is generated
-            skel.attach().expect("failed to attach progs");
-
-            // Check that Option<Link> field is generated
-            let _mylink = skel.links.this_is_my_prog.unwrap();
+            let _stream: libbpf_rs::RingBufferStream<u64> = skel
+                .consume_events_events()
+                .expect("failed to create event stream");
         }}
         "#,
+        skel_path = skel.path().display(),
     )
     .expect("failed to write to main.rs");
 
@@ -578,8 +1957,10 @@ fn test_skeleton_basic() {
     assert!(status.success());
 }
 
+/// Check that skeletons generated in `no_std` mode can be used in a
+/// `no_std` crate.
 #[test]
-fn test_skeleton_generate_datasec_static() {
+fn test_skeleton_builder_no_std() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
     // Add prog dir
@@ -599,10 +1980,23 @@ fn test_skeleton_generate_datasec_static() {
         #include "vmlinux.h"
         #include <bpf/bpf_helpers.h>
 
+        enum kind {{
+                KIND_A,
+                KIND_B,
+        }};
+
+        struct event {{
+                enum kind kind;
+                void *ptr;
+                u32 value;
+        }};
+
+        struct event dummy_event = {{0}};
+        const volatile bool enabled = false;
+
         SEC("kprobe/foo")
         int this_is_my_prog(u64 *ctx)
         {{
-                bpf_printk("this should not cause an error");
                 return 0;
         }}
         "#,
@@ -612,21 +2006,58 @@ fn test_skeleton_generate_datasec_static() {
     // Lay down the necessary header files
     add_vmlinux_header(&proj_dir);
 
-    make(
-        true,
-        Some(&cargo_toml),
-        None,
-        Vec::new(),
-        true,
-        true,
-        Vec::new(),
-        None,
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .no_std(true)
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/lib.rs"))
+        .expect("failed to open lib.rs");
+
+    write!(
+        source,
+        r#"
+        #![no_std]
+
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::types;
+
+        pub fn event() -> types::event {{
+            types::event {{
+                kind: types::kind::KIND_B,
+                ..Default::default()
+            }}
+        }}
+        "#,
+        skel_path = skel.path().display(),
     )
-    .unwrap();
+    .expect("failed to write to lib.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--lib")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
 }
 
+/// Check that typed accessors are generated for maps with plain old data
+/// keys and values.
 #[test]
-fn test_skeleton_datasec() {
+fn test_skeleton_builder_typed_maps() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
     // Add prog dir
@@ -646,15 +2077,37 @@ fn test_skeleton_datasec() {
         #include "vmlinux.h"
         #include <bpf/bpf_helpers.h>
 
-        int myglobal = 0;
-        void * const myconst = 0;
-        int mycustomdata SEC(".data.custom");
-        int mycustombss SEC(".bss.custom");
-        const int mycustomrodata SEC(".rodata.custom.1") = 43;
+        struct key {{
+                u32 pid;
+                u32 cpu;
+        }};
+
+        struct value {{
+                u64 count;
+                u64 ts[2];
+        }};
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_HASH);
+                __uint(max_entries, 128);
+                __type(key, struct key);
+                __type(value, struct value);
+        }} counts SEC(".maps");
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+                __uint(max_entries, 1);
+                __type(key, u32);
+                __type(value, u64);
+        }} percpu SEC(".maps");
 
         SEC("kprobe/foo")
         int this_is_my_prog(u64 *ctx)
         {{
+                struct key key = {{0}};
+                struct value *value = bpf_map_lookup_elem(&counts, &key);
+                if (value)
+                        value->count++;
                 return 0;
         }}
         "#,
@@ -664,17 +2117,17 @@ fn test_skeleton_datasec() {
     // Lay down the necessary header files
     add_vmlinux_header(&proj_dir);
 
-    make(
-        true,
-        Some(&cargo_toml),
-        None,
-        Vec::new(),
-        true,
-        true,
-        Vec::new(),
-        None,
-    )
-    .unwrap();
+    // Generate skeleton file
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let skel_contents = read_to_string(skel.path()).unwrap();
+    // Per-CPU maps don't get typed accessors.
+    assert!(!skel_contents.contains("percpu_typed"));
 
     let mut cargo = OpenOptions::new()
         .append(true)
@@ -700,42 +2153,27 @@ fn test_skeleton_datasec() {
     write!(
         source,
         r#"
-        #![warn(elided_lifetimes_in_paths)]
-        mod bpf;
-        use bpf::*;
+        #[path = "{skel_path}"]
+        mod skel;
+        use skel::*;
         use libbpf_rs::skel::SkelBuilder;
         use libbpf_rs::skel::OpenSkel;
 
         fn main() {{
             let builder = ProgSkelBuilder::default();
-            let mut open_skel = builder
+            let skel = builder
                 .open()
-                .expect("failed to open skel");
-
-            // Check that we set rodata vars before load
-            open_skel.rodata_mut().myconst = std::ptr::null_mut();
-
-            // We can always set bss vars
-            open_skel.bss_mut().myglobal = 42;
-
-            open_skel.data_custom_mut().mycustomdata = 1337;
-            open_skel.bss_custom_mut().mycustombss = 12;
-            assert_eq!(open_skel.rodata_custom_1().mycustomrodata, 43);
-
-            let mut skel = open_skel
+                .expect("failed to open skel")
                 .load()
                 .expect("failed to load skel");
 
-            // We can always set bss vars
-            skel.bss_mut().myglobal = 24;
-            skel.data_custom_mut().mycustomdata += 1;
-            skel.bss_custom_mut().mycustombss += 1;
-            assert_eq!(skel.rodata_custom_1().mycustomrodata, 43);
-
-            // Read only for rodata after load
-            let _rodata: &prog_types::rodata = skel.rodata();
+            let maps = skel.maps();
+            let counts = maps.counts_typed().expect("failed to create typed map");
+            let key = types::key {{ pid: 1, cpu: 0 }};
+            let _value: Option<types::value> = counts.lookup(&key).expect("failed to look up key");
         }}
         "#,
+        skel_path = skel.path().display(),
     )
     .expect("failed to write to main.rs");
 
@@ -751,7 +2189,7 @@ fn test_skeleton_datasec() {
 }
 
 #[test]
-fn test_skeleton_builder_basic() {
+fn test_skeleton_builder_event_source() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
     // Add prog dir
@@ -771,29 +2209,26 @@ fn test_skeleton_builder_basic() {
         #include "vmlinux.h"
         #include <bpf/bpf_helpers.h>
 
-        struct unique_key {{
-            int cap;
-            u32 tgid;
-            u64 cgroupid;
-        }};
+        #include <bpf/bpf_core_read.h>
 
         struct {{
-                __uint(type, BPF_MAP_TYPE_HASH);
-                __uint(max_entries, 1024);
-                __type(key, struct unique_key);
-                __type(value, u64);
-        }} mymap SEC(".maps");
+                __uint(type, BPF_MAP_TYPE_RINGBUF);
+                __uint(max_entries, 4096);
+        }} events_ringbuf SEC(".maps");
 
         struct {{
-                __uint(type, BPF_MAP_TYPE_HASH);
-                __uint(max_entries, 1024);
-                __uint(key_size, 8);
-                __type(value, u64);
-        }} mymap2 SEC(".maps");
+                __uint(type, BPF_MAP_TYPE_PERF_EVENT_ARRAY);
+                __uint(key_size, sizeof(u32));
+                __uint(value_size, sizeof(u32));
+        }} events_perfbuf SEC(".maps");
 
         SEC("kprobe/foo")
         int this_is_my_prog(u64 *ctx)
         {{
+                if (bpf_core_type_exists(struct bpf_ringbuf))
+                        bpf_ringbuf_output(&events_ringbuf, ctx, sizeof(*ctx), 0);
+                else
+                        bpf_perf_event_output(ctx, &events_perfbuf, BPF_F_CURRENT_CPU, ctx, sizeof(*ctx));
                 return 0;
         }}
         "#,
@@ -820,7 +2255,7 @@ fn test_skeleton_builder_basic() {
     writeln!(
         cargo,
         r#"
-        libbpf-rs = {{ path = "{}" }}
+        libbpf-rs = {{ path = "{}", }}
         "#,
         get_libbpf_rs_path().as_path().display()
     )
@@ -840,45 +2275,155 @@ fn test_skeleton_builder_basic() {
         use skel::*;
         use libbpf_rs::skel::SkelBuilder;
         use libbpf_rs::skel::OpenSkel;
-        use libbpf_rs::skel::Skel;
 
         fn main() {{
             let builder = ProgSkelBuilder::default();
-            let mut open_skel = builder
-                .open()
-                .expect("failed to open skel");
+            let mut open_skel = builder.open().expect("failed to open skel");
+            let kind = libbpf_rs::EventSourceKind::detect().expect("failed to detect event source kind");
+            open_skel
+                .configure_events_events(kind)
+                .expect("failed to configure event maps");
+            let skel = open_skel.load().expect("failed to load skel");
+
+            let source = skel
+                .events_events()
+                .sample_cb(|data: &[u8]| println!("{{data:?}}"))
+                .build()
+                .expect("failed to build event source");
+            assert_eq!(source.kind(), kind);
+        }}
+        "#,
+        skel_path = skel.path().display(),
+    )
+    .expect("failed to write to main.rs");
 
-            // Check that we can grab handles to open maps/progs
-            let _open_map = open_skel.maps().mymap();
-            let _open_map2 = open_skel.maps().mymap2();
-            let _open_prog = open_skel.progs().this_is_my_prog();
-            let _open_map_mut = open_skel.maps_mut().mymap();
-            let _open_map2_mut = open_skel.maps_mut().mymap2();
-            let _open_prog_mut = open_skel.progs_mut().this_is_my_prog();
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
 
+#[test]
+fn test_subskeleton_builder_basic() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
 
-            let mut skel = open_skel
-                .load()
-                .expect("failed to load skel");
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
 
-            // Check that we can grab handles to loaded maps/progs
-            let _map = skel.maps().mymap();
-            let _map2 = skel.maps().mymap2();
-            let _prog = skel.progs().this_is_my_prog();
-            let _map_mut = skel.maps_mut().mymap();
-            let _map2_mut = skel.maps_mut().mymap2();
-            let _prog_mut = skel.progs_mut().this_is_my_prog();
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/lib.bpf.c"))
+        .expect("failed to open lib.bpf.c");
 
-            // Check that attach() is generated
-            skel.attach().expect("failed to attach progs");
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
 
-            // Check that Option<Link> field is generated
-            let _mylink = skel.links.this_is_my_prog.unwrap();
+        struct lib_config {{
+            u32 flags;
+            u64 threshold;
+        }};
 
-            let _key = prog_types::unique_key::default();
+        const volatile struct lib_config lib_cfg = {{}};
+        u64 lib_counter = 0;
+
+        struct {{
+            __uint(type, BPF_MAP_TYPE_HASH);
+            __uint(max_entries, 16);
+            __type(key, u32);
+            __type(value, u64);
+        }} lib_map SEC(".maps");
+
+        SEC("kprobe/foo")
+        int lib_prog(u64 *ctx)
+        {{
+                lib_counter += lib_cfg.threshold;
+                return 0;
+        }}
+        "#,
+    )
+    .expect("failed to write lib.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    // Generate subskeleton file
+    let skel = NamedTempFile::new().unwrap();
+    let obj = proj_dir.join("src/bpf/lib.bpf.o");
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/lib.bpf.c"))
+        .obj(&obj)
+        .subskeleton(true)
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    // Make test project use our development libbpf-rs version
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    let mut source = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(proj_dir.join("src/main.rs"))
+        .expect("failed to open main.rs");
+
+    write!(
+        source,
+        r#"
+        #![warn(elided_lifetimes_in_paths)]
+        #[path = "{skel_path}"]
+        mod subskel;
+        use subskel::*;
+
+        fn main() {{
+            let mut open_obj = libbpf_rs::ObjectBuilder::default()
+                .open_file("{obj_path}")
+                .expect("failed to open object");
+
+            {{
+                let mut open_subskel = OpenLibSubskel::open(&mut open_obj)
+                    .expect("failed to open subskel");
+                open_subskel.lib_cfg_mut().threshold = 42;
+                *open_subskel.lib_counter_mut() = 1;
+                let _open_map = open_subskel.maps().lib_map();
+                let _open_map_mut = open_subskel.maps_mut().lib_map();
+                let _open_prog = open_subskel.progs().lib_prog();
+                let _open_prog_mut = open_subskel.progs_mut().lib_prog();
+            }}
+
+            let mut obj = open_obj.load().expect("failed to load object");
+            let mut subskel = LibSubskel::open(&mut obj).expect("failed to open subskel");
+            let _threshold = subskel.lib_cfg().threshold;
+            *subskel.lib_counter_mut() += 1;
+            let _map = subskel.maps().lib_map();
+            let _prog = subskel.progs().lib_prog();
+            let _config = lib_types::lib_config::default();
         }}
         "#,
         skel_path = skel.path().display(),
+        obj_path = obj.display(),
     )
     .expect("failed to write to main.rs");
 
@@ -893,6 +2438,49 @@ fn test_skeleton_builder_basic() {
     assert!(status.success());
 }
 
+/// Check that subskeletons can be generated for BPF libraries that do
+/// not contain any programs.
+#[test]
+fn test_subskeleton_builder_no_progs() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    add_vmlinux_header(&proj_dir);
+    write(
+        proj_dir.join("src/bpf/lib.bpf.c"),
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        u64 lib_counter = 0;
+
+        struct {
+            __uint(type, BPF_MAP_TYPE_ARRAY);
+            __uint(max_entries, 1);
+            __type(key, u32);
+            __type(value, u64);
+        } lib_map SEC(".maps");
+        "#,
+    )
+    .expect("failed to write lib.bpf.c");
+
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/lib.bpf.c"))
+        .obj(proj_dir.join("src/bpf/lib.bpf.o"))
+        .subskeleton(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let contents = read_to_string(skel.path()).expect("failed to read subskeleton");
+    assert!(contents.contains("fn lib_map("));
+    assert!(contents.contains("fn lib_counter("));
+    // Internal maps are named after the final object and, hence, not
+    // exposed.
+    assert!(!contents.contains("fn bss("));
+    assert!(!contents.contains("fn progs("));
+}
+
 #[test]
 fn test_skeleton_builder_clang_opts() {
     let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
@@ -938,6 +2526,81 @@ fn test_skeleton_builder_clang_opts() {
         .unwrap();
 }
 
+/// Check that the headers included by a BPF program are reported as
+/// dependencies of the build.
+#[test]
+fn test_skeleton_builder_deps() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    // Add prog dir
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+
+    // Add a header
+    let mut header = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.h"))
+        .expect("failed to open prog.h");
+
+    write!(
+        header,
+        r#"
+        #define MAX_ENTRIES 16
+        "#,
+    )
+    .expect("failed to write prog.h");
+
+    // Add a prog
+    let mut prog = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(proj_dir.join("src/bpf/prog.bpf.c"))
+        .expect("failed to open prog.bpf.c");
+
+    write!(
+        prog,
+        r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+        #include "prog.h"
+
+        struct {{
+                __uint(type, BPF_MAP_TYPE_ARRAY);
+                __uint(max_entries, MAX_ENTRIES);
+                __type(key, u32);
+                __type(value, u64);
+        }} mymap SEC(".maps");
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    // Lay down the necessary header files
+    add_vmlinux_header(&proj_dir);
+
+    let skel = NamedTempFile::new().unwrap();
+    let mut builder = SkeletonBuilder::new();
+    assert!(builder.generated_deps().is_empty());
+
+    builder
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .debug(true)
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let deps = builder
+        .generated_deps()
+        .iter()
+        .map(|dep| dep.file_name().unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(deps.contains(&"prog.bpf.c"), "{deps:?}");
+    assert!(deps.contains(&"prog.h"), "{deps:?}");
+    assert!(deps.contains(&"vmlinux.h"), "{deps:?}");
+    // Vendored libbpf headers only exist temporarily and are not reported.
+    assert!(!deps.contains(&"bpf_helpers.h"), "{deps:?}");
+}
+
 #[test]
 fn test_skeleton_builder_arrays_ptrs() {
     let (_dir, proj_dir, cargo_toml) = setup_temp_project();
@@ -988,7 +2651,8 @@ fn test_skeleton_builder_arrays_ptrs() {
         true,
         true,
         Vec::new(),
-        None,
+        &Default::default(),
+        &BuildOpts::default(),
     )
     .unwrap();
 
@@ -1090,7 +2754,8 @@ fn test_skeleton_generate_struct_with_pointer() {
         true,
         true,
         Vec::new(),
-        None,
+        &Default::default(),
+        &BuildOpts::default(),
     )
     .unwrap();
 
@@ -1197,6 +2862,137 @@ fn test_skeleton_builder_deterministic() {
     assert_eq!(skel1, skel2);
 }
 
+/// Check that we can dump the BTF of an object and minimize BTF based on
+/// the CO-RE relocations of an object.
+#[test]
+fn test_btf_dump_and_min_core() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+            #include <bpf/bpf_core_read.h>
+
+            struct my_foo {
+                int a;
+                long b;
+                struct my_foo *next;
+            } __attribute__((preserve_access_index));
+
+            struct my_foo *foo_ptr;
+            long result;
+
+            SEC("kprobe/foo")
+            int this_is_my_prog(u64 *ctx)
+            {
+                result = BPF_CORE_READ(foo_ptr, b);
+                return 0;
+            }
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    add_vmlinux_header(&proj_dir);
+
+    let obj = proj_dir.join("prog.bpf.o");
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .obj(&obj)
+        .debug(true)
+        .build()
+        .unwrap();
+
+    let c = dump_btf(&obj, BtfFormat::C).unwrap();
+    assert!(c.contains("struct my_foo {"), "{c}");
+    let json = dump_btf(&obj, BtfFormat::Json).unwrap();
+    assert!(json.contains(r#""name": "my_foo""#), "{json}");
+
+    // Use the object's own BTF as the target, for lack of a better one.
+    let out_dir = proj_dir.join("min");
+    let () = min_core(true, slice::from_ref(&obj), slice::from_ref(&obj), &out_dir).unwrap();
+
+    let btf = Btf::from_path(out_dir.join("prog.bpf.o")).unwrap();
+    let foo = btf
+        .type_by_name::<types::Struct<'_>>("my_foo")
+        .unwrap()
+        .unwrap();
+    // Only the accessed member is retained, while the size stays the same.
+    assert_eq!(foo.size(), 24);
+    let names = foo
+        .iter()
+        .map(|member| member.name.unwrap().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b"]);
+    // Unrelated types are not part of the minimized BTF.
+    assert!(btf
+        .type_by_name::<types::Struct<'_>>("task_struct")
+        .unwrap()
+        .is_none());
+}
+
+/// Check that we flag common problems in BPF objects.
+#[test]
+fn test_check_object() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    add_vmlinux_header(&proj_dir);
+
+    let check = |license: &str| {
+        write(
+            proj_dir.join("src/bpf/prog.bpf.c"),
+            format!(
+                r#"
+                #include "vmlinux.h"
+                #include <bpf/bpf_helpers.h>
+
+                struct {{
+                    __uint(type, BPF_MAP_TYPE_ARRAY);
+                    __uint(max_entries, 1);
+                    __type(key, u32);
+                    __type(value, u64);
+                }} counts SEC(".maps");
+
+                SEC("kprobe/foo")
+                int this_is_my_prog(u64 *ctx)
+                {{
+                    u32 key = 0;
+                    u64 *value = bpf_map_lookup_elem(&counts, &key);
+                    if (value)
+                        *value += 1;
+                    return 0;
+                }}
+
+                {license}
+                "#
+            ),
+        )
+        .expect("failed to write prog.bpf.c");
+
+        let obj = proj_dir.join("prog.bpf.o");
+        SkeletonBuilder::new()
+            .source(proj_dir.join("src/bpf/prog.bpf.c"))
+            .obj(&obj)
+            .build()
+            .unwrap();
+        check_object(&obj).unwrap()
+    };
+
+    let (report, problems) = check(r#"char LICENSE[] SEC("license") = "GPL";"#);
+    assert!(report.contains("license: GPL"), "{report}");
+    assert!(report.contains("this_is_my_prog (kprobe/foo"), "{report}");
+    assert!(report.contains("counts: Array"), "{report}");
+    assert_eq!(problems, Vec::new());
+
+    let (_report, problems) = check("");
+    assert_eq!(problems.len(), 1, "{problems:?}");
+    assert_eq!(problems[0].severity, Severity::Error);
+    assert!(problems[0].message.contains("license"));
+}
+
 // -- TEST RUST GENERATION OF BTF PROGRAMS --
 
 /// Searches the Btf struct for a BtfType
@@ -1293,7 +3089,15 @@ fn build_btf_mmap(prog_text: &str) -> Mmap {
     add_vmlinux_header(&proj_dir);
 
     // Build the .bpf.o
-    build(true, Some(&cargo_toml), None, Vec::new(), true).expect("failed to compile");
+    build(
+        true,
+        Some(&cargo_toml),
+        None,
+        Vec::new(),
+        true,
+        &BuildOpts::default(),
+    )
+    .expect("failed to compile");
 
     let obj = OpenOptions::new()
         .read(true)
@@ -1360,13 +3164,13 @@ struct Foo foo = {{0}};
 #[repr(C)]
 pub struct Foo {
     pub r#type: u64,
-    pub r#mod: *mut std::ffi::c_void,
+    pub r#mod: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             r#type: u64::default(),
-            r#mod: std::ptr::null_mut(),
+            r#mod: core::ptr::null_mut(),
         }
     }
 }
@@ -1402,14 +3206,14 @@ struct Foo foo = {{0}};
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 10],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 10],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -1436,6 +3240,298 @@ impl Default for Foo {
     assert_definition(&btf, &struct_foo, expected_output);
 }
 
+/// Check that `plain::Plain` and `bytemuck::Pod` implementations are
+/// emitted for plain old data types only.
+#[test]
+fn test_btf_dump_pod_impls() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct Foo {
+    u32 x;
+    u32 y[4];
+};
+
+struct Bar {
+    u8 x;
+    u32 y;
+};
+
+struct Foo foo = {0};
+struct Bar bar = {0};
+"#;
+
+    let expected_foo_output = r#"
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct Foo {
+    pub x: u32,
+    pub y: [u32; 4],
+}
+unsafe impl plain::Plain for Foo {}
+unsafe impl bytemuck::Zeroable for Foo {}
+unsafe impl bytemuck::Pod for Foo {}
+"#;
+
+    // `Bar` contains implicit padding between its members and,
+    // hence, must not be treated as plain old data.
+    let expected_bar_output = r#"
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct Bar {
+    pub x: u8,
+    pub y: u32,
+}
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let mut btf = btf_from_mmap(&mmap);
+    let () = btf.set_type_opts(TypeOpts {
+        plain: true,
+        bytemuck: true,
+        ..Default::default()
+    });
+
+    let struct_foo = find_type_in_btf!(btf, types::Struct<'_>, "Foo");
+    let struct_bar = find_type_in_btf!(btf, types::Struct<'_>, "Bar");
+
+    assert_definition(&btf, &struct_foo, expected_foo_output);
+    assert_definition(&btf, &struct_bar, expected_bar_output);
+}
+
+/// Check that custom derives and attributes are applied to structs and
+/// enums, but not to unions.
+#[test]
+fn test_btf_dump_custom_derives() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+enum Kind {
+    A,
+};
+
+union Value {
+    u32 x;
+    u64 y;
+};
+
+struct Foo {
+    enum Kind kind;
+    union Value value;
+};
+
+struct Foo foo = {0};
+"#;
+
+    // `Foo` contains a union and, hence, doesn't get the custom derives
+    // either.
+    let expected_output = r#"
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct Foo {
+    pub kind: Kind,
+    pub __pad_4: [u8; 4],
+    pub value: Value,
+}
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+#[repr(transparent)]
+pub struct Kind(pub u32);
+#[allow(non_upper_case_globals)]
+impl Kind {
+    pub const A: Kind = Kind(0);
+}
+impl Default for Kind {
+    fn default() -> Self {
+        Kind::A
+    }
+}
+impl core::fmt::Display for Kind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            0 => write!(f, "A"),
+            value => write!(f, "Kind({value})"),
+        }
+    }
+}
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub union Value {
+    pub x: u32,
+    pub y: u64,
+}
+impl core::fmt::Debug for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Value")
+            .field("x", &self.x())
+            .finish_non_exhaustive()
+    }
+}
+impl Default for Value {
+    fn default() -> Self {
+        Value {
+            x: u32::default(),
+        }
+    }
+}
+impl Value {
+    pub fn x(&self) -> u32 {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.x }
+    }
+
+    pub fn set_x(&mut self, value: u32) {
+        self.x = value;
+    }
+
+    pub fn set_y(&mut self, value: u64) {
+        self.y = value;
+    }
+}
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let mut btf = btf_from_mmap(&mmap);
+    let () = btf.set_type_opts(TypeOpts {
+        derives: vec!["serde::Serialize".to_string(), "Hash".to_string()],
+        attributes: vec![r#"serde(rename_all = "camelCase")"#.to_string()],
+        ..Default::default()
+    });
+
+    let struct_foo = find_type_in_btf!(btf, types::Struct<'_>, "Foo");
+
+    assert_definition(&btf, &struct_foo, expected_output);
+}
+
+/// Check that custom derives and attributes are not applied to structs
+/// (transitively) containing members most derives can't support.
+#[test]
+fn test_btf_dump_custom_derives_underivable() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+union Value {
+    u32 x;
+    u64 y;
+};
+
+struct WithUnion {
+    union Value value;
+};
+
+struct Nested {
+    struct WithUnion inner;
+};
+
+struct WithBool {
+    bool flag;
+};
+
+struct WithArray {
+    u8 data[33];
+};
+
+struct WithPadding {
+    u8 x;
+    u8 y __attribute__((aligned(64)));
+};
+
+struct Derivable {
+    u32 x;
+    u8 data[32];
+};
+
+struct WithUnion with_union = {0};
+struct Nested nested = {0};
+struct WithBool with_bool = {0};
+struct WithArray with_array = {0};
+struct WithPadding with_padding = {0};
+struct Derivable derivable = {0};
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let mut btf = btf_from_mmap(&mmap);
+    let () = btf.set_type_opts(TypeOpts {
+        derives: vec!["serde::Serialize".to_string()],
+        ..Default::default()
+    });
+
+    // The definition of a type comes first, followed by those of the
+    // types it depends on.
+    let derives = |ty: BtfType<'_>| {
+        let def = btf
+            .type_definition(ty, &mut HashSet::new())
+            .expect("failed to generate definition");
+        def.lines()
+            .find(|line| line.starts_with("#[derive("))
+            .unwrap()
+            .to_string()
+    };
+
+    let with_union = find_type_in_btf!(btf, types::Struct<'_>, "WithUnion");
+    assert!(!derives(*with_union).contains("serde::Serialize"));
+    let nested = find_type_in_btf!(btf, types::Struct<'_>, "Nested");
+    assert!(!derives(*nested).contains("serde::Serialize"));
+    let with_bool = find_type_in_btf!(btf, types::Struct<'_>, "WithBool");
+    assert!(!derives(*with_bool).contains("serde::Serialize"));
+    let with_array = find_type_in_btf!(btf, types::Struct<'_>, "WithArray");
+    assert!(!derives(*with_array).contains("serde::Serialize"));
+    let with_padding = find_type_in_btf!(btf, types::Struct<'_>, "WithPadding");
+    assert!(!derives(*with_padding).contains("serde::Serialize"));
+    let derivable = find_type_in_btf!(btf, types::Struct<'_>, "Derivable");
+    assert!(derives(*derivable).contains("serde::Serialize"));
+}
+
+/// Check that custom derives and attributes are not applied to structs
+/// containing pointers.
+#[test]
+fn test_btf_dump_custom_derives_pointer() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct Foo {
+    int x;
+    void *z;
+};
+
+struct Foo foo = {0};
+"#;
+
+    let expected_output = r#"
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Foo {
+    pub x: i32,
+    pub z: *mut core::ffi::c_void,
+}
+impl Default for Foo {
+    fn default() -> Self {
+        Foo {
+            x: i32::default(),
+            z: core::ptr::null_mut(),
+        }
+    }
+}
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let mut btf = btf_from_mmap(&mmap);
+    let () = btf.set_type_opts(TypeOpts {
+        derives: vec!["serde::Serialize".to_string()],
+        attributes: vec![r#"serde(rename_all = "camelCase")"#.to_string()],
+        ..Default::default()
+    });
+
+    let struct_foo = find_type_in_btf!(btf, types::Struct<'_>, "Foo");
+
+    assert_definition(&btf, &struct_foo, expected_output);
+}
+
 #[test]
 fn test_btf_dump_fwd() {
     let prog_text = r#"
@@ -1451,7 +3547,7 @@ struct sometypethatdoesnotexist *m;
     let m = find_type_in_btf!(btf, types::Var<'_>, "m");
 
     assert_eq!(
-        "*mut std::ffi::c_void",
+        "*mut core::ffi::c_void",
         btf.type_declaration(*m)
             .expect("Failed to generate foo decl")
     );
@@ -1546,14 +3642,14 @@ struct Foo foo = {{0}};
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 33],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 33],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -1622,14 +3718,14 @@ pub struct Foo {
 impl Default for Foo {
     fn default() -> Self {
         Foo {
-            ip: std::ptr::null_mut(),
-            ipp: std::ptr::null_mut(),
+            ip: core::ptr::null_mut(),
+            ipp: core::ptr::null_mut(),
             bar: Bar::default(),
             __pad_18: [u8::default(); 6],
-            pb: std::ptr::null_mut(),
+            pb: core::ptr::null_mut(),
             v: u64::default(),
             cv: i64::default(),
-            r: std::ptr::null_mut(),
+            r: core::ptr::null_mut(),
         }
     }
 }
@@ -1668,13 +3764,13 @@ struct with_func_proto w;
 #[repr(C)]
 pub struct with_func_proto {
     pub next: *mut with_func_proto,
-    pub func: *mut std::ffi::c_void,
+    pub func: *mut core::ffi::c_void,
 }
 impl Default for with_func_proto {
     fn default() -> Self {
         with_func_proto {
-            next: std::ptr::null_mut(),
-            func: std::ptr::null_mut(),
+            next: core::ptr::null_mut(),
+            func: core::ptr::null_mut(),
         }
     }
 }
@@ -1731,14 +3827,14 @@ pub struct Foo {
 impl Default for Foo {
     fn default() -> Self {
         Foo {
-            ip: std::ptr::null_mut(),
-            ipp: std::ptr::null_mut(),
+            ip: core::ptr::null_mut(),
+            ipp: core::ptr::null_mut(),
             bar: Bar::default(),
             __pad_84: [u8::default(); 4],
-            pb: std::ptr::null_mut(),
+            pb: core::ptr::null_mut(),
             v: u64::default(),
             cv: i64::default(),
-            r: std::ptr::null_mut(),
+            r: core::ptr::null_mut(),
         }
     }
 }
@@ -1864,6 +3960,63 @@ struct Foo foo;
 pub struct Foo {
     pub __pad_0: [u8; 2],
 }
+impl Foo {
+    pub fn x(&self) -> u16 {
+        let bytes = &self.__pad_0[0..1];
+        let mut buf = [0u8; 16];
+        let value = if cfg!(target_endian = "little") {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            u128::from_le_bytes(buf) & 0x3
+        } else {
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            (u128::from_be_bytes(buf) >> 6) & 0x3
+        };
+        value as u16
+    }
+
+    pub fn set_x(&mut self, value: u16) {
+        let value = value as u128 & 0x3;
+        let bytes = &mut self.__pad_0[0..1];
+        let mut buf = [0u8; 16];
+        if cfg!(target_endian = "little") {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            let value = (u128::from_le_bytes(buf) & !0x3) | value;
+            bytes.copy_from_slice(&value.to_le_bytes()[..bytes.len()]);
+        } else {
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            let value = (u128::from_be_bytes(buf) & !0xc0) | (value << 6);
+            bytes.copy_from_slice(&value.to_be_bytes()[16 - bytes.len()..]);
+        }
+    }
+
+    pub fn y(&self) -> u16 {
+        let bytes = &self.__pad_0[0..1];
+        let mut buf = [0u8; 16];
+        let value = if cfg!(target_endian = "little") {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            (u128::from_le_bytes(buf) >> 2) & 0x7
+        } else {
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            (u128::from_be_bytes(buf) >> 3) & 0x7
+        };
+        value as u16
+    }
+
+    pub fn set_y(&mut self, value: u16) {
+        let value = value as u128 & 0x7;
+        let bytes = &mut self.__pad_0[0..1];
+        let mut buf = [0u8; 16];
+        if cfg!(target_endian = "little") {
+            buf[..bytes.len()].copy_from_slice(bytes);
+            let value = (u128::from_le_bytes(buf) & !0x1c) | (value << 2);
+            bytes.copy_from_slice(&value.to_le_bytes()[..bytes.len()]);
+        } else {
+            buf[16 - bytes.len()..].copy_from_slice(bytes);
+            let value = (u128::from_be_bytes(buf) & !0x38) | (value << 3);
+            bytes.copy_from_slice(&value.to_be_bytes()[16 - bytes.len()..]);
+        }
+    }
+}
 "#;
 
     let mmap = build_btf_mmap(prog_text);
@@ -1898,18 +4051,18 @@ struct Foo foo;
 #[repr(C)]
 pub struct Foo {
     pub name: *mut i8,
-    pub kset: *mut std::ffi::c_void,
+    pub kset: *mut core::ffi::c_void,
     pub __pad_16: [u8; 1],
-    pub flag: std::mem::MaybeUninit<bool>,
+    pub flag: core::mem::MaybeUninit<bool>,
     pub __pad_18: [u8; 6],
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
-            name: std::ptr::null_mut(),
-            kset: std::ptr::null_mut(),
+            name: core::ptr::null_mut(),
+            kset: core::ptr::null_mut(),
             __pad_16: [u8::default(); 1],
-            flag: std::mem::MaybeUninit::new(bool::default()),
+            flag: core::mem::MaybeUninit::new(bool::default()),
             __pad_18: [u8::default(); 6],
         }
     }
@@ -1920,7 +4073,24 @@ impl Default for Foo {
     let btf = btf_from_mmap(&mmap);
     let struct_foo = find_type_in_btf!(btf, types::Struct<'_>, "Foo");
 
-    assert_definition(&btf, &struct_foo, expected_output);
+    let actual_output = btf
+        .type_definition(*struct_foo, &mut HashSet::new())
+        .expect("Failed to generate struct Foo defn");
+    let (struct_output, accessor_output) = actual_output
+        .split_once("impl Foo {")
+        .expect("no bitfield accessors found");
+    assert_output(struct_output, expected_output);
+
+    for name in [
+        "state_initialized",
+        "state_in_sysfs",
+        "state_add_uevent_sent",
+        "state_remove_uevent_sent",
+        "uevent_suppress",
+    ] {
+        assert!(accessor_output.contains(&format!("pub fn {name}(&self) -> u32 {{")));
+        assert!(accessor_output.contains(&format!("pub fn set_{name}(&mut self, value: u32) {{")));
+    }
 }
 
 #[test]
@@ -1939,13 +4109,29 @@ enum Foo foo;
 "#;
 
     let expected_output = r#"
-#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
-#[repr(u32)]
-pub enum Foo {
-    #[default]
-    Zero = 0,
-    One = 1,
-    seven = 7,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Foo(pub u32);
+#[allow(non_upper_case_globals)]
+impl Foo {
+    pub const Zero: Foo = Foo(0);
+    pub const One: Foo = Foo(1);
+    pub const seven: Foo = Foo(7);
+}
+impl Default for Foo {
+    fn default() -> Self {
+        Foo::Zero
+    }
+}
+impl core::fmt::Display for Foo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            0 => write!(f, "Zero"),
+            1 => write!(f, "One"),
+            7 => write!(f, "seven"),
+            value => write!(f, "Foo({value})"),
+        }
+    }
 }
 "#;
 
@@ -1981,9 +4167,12 @@ pub union Foo {
     pub y: u32,
     pub z: [i8; 128],
 }
-impl std::fmt::Debug for Foo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo")
+            .field("x", &self.x())
+            .field("y", &self.y())
+            .finish_non_exhaustive()
     }
 }
 impl Default for Foo {
@@ -1993,6 +4182,31 @@ impl Default for Foo {
         }
     }
 }
+impl Foo {
+    pub fn x(&self) -> i32 {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.x }
+    }
+
+    pub fn set_x(&mut self, value: i32) {
+        self.x = value;
+    }
+
+    pub fn y(&self) -> u32 {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.y }
+    }
+
+    pub fn set_y(&mut self, value: u32) {
+        self.y = value;
+    }
+
+    pub fn set_z(&mut self, value: [i8; 128]) {
+        self.z = value;
+    }
+}
 "#;
 
     let mmap = build_btf_mmap(prog_text);
@@ -2004,6 +4218,82 @@ impl Default for Foo {
     assert_definition(&btf, &union_foo, expected_output);
 }
 
+/// Check that a tagged view is generated for a union discriminated by an
+/// enum with matching value names.
+#[test]
+fn test_btf_dump_definition_tagged_union() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+enum event_kind {
+    EVENT_EXEC,
+    EVENT_EXIT,
+};
+
+struct exec_event {
+    u32 pid;
+    u32 ppid;
+};
+
+struct exit_event {
+    u32 pid;
+    s32 code;
+};
+
+struct event {
+    enum event_kind kind;
+    union {
+        struct exec_event exec;
+        struct exit_event exit;
+    } data;
+    enum event_kind other_kind;
+    union {
+        u32 small;
+        u64 large;
+    } other;
+};
+
+struct event event = {0};
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let btf = btf_from_mmap(&mmap);
+    let struct_event = find_type_in_btf!(btf, types::Struct<'_>, "event");
+
+    let output = btf
+        .type_definition(*struct_event, &mut HashSet::new())
+        .expect("Failed to generate struct event defn");
+
+    let view = r#"
+#[derive(Debug, Copy, Clone)]
+pub enum event__data_view {
+    exec(exec_event),
+    exit(exit_event),
+    Unknown(event_kind),
+}
+"#;
+    assert!(output.contains(view.trim_start()));
+
+    let accessor = r#"
+    pub fn data_view(&self) -> event__data_view {
+        match self.kind.0 {
+            0 => event__data_view::exec(self.data.exec()),
+            1 => event__data_view::exit(self.data.exit()),
+            _ => event__data_view::Unknown(self.kind),
+        }
+    }
+"#;
+    assert!(output.contains(accessor));
+
+    // Members of `other` are not all of the same size and so `large` can't
+    // be read safely, preventing a view from being generated.
+    assert!(output.contains("pub fn small(&self) -> u32 {"));
+    assert!(!output.contains("pub fn large(&self)"));
+    assert!(output.contains("pub fn set_large(&mut self, value: u64) {"));
+    assert!(!output.contains("other_view"));
+}
+
 #[test]
 fn test_btf_dump_definition_shared_dependent_types() {
     let prog_text = r#"
@@ -2073,14 +4363,14 @@ pub struct bss {
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 10],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 10],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -2179,14 +4469,14 @@ pub struct bss {
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 33],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 33],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -2245,14 +4535,14 @@ pub struct bss {
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 10],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 10],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -2313,14 +4603,14 @@ pub struct bss {
 pub struct Foo {
     pub x: i32,
     pub y: [i8; 33],
-    pub z: *mut std::ffi::c_void,
+    pub z: *mut core::ffi::c_void,
 }
 impl Default for Foo {
     fn default() -> Self {
         Foo {
             x: i32::default(),
             y: [i8::default(); 33],
-            z: std::ptr::null_mut(),
+            z: core::ptr::null_mut(),
         }
     }
 }
@@ -2374,48 +4664,75 @@ struct Foo foo;
 #[repr(C)]
 pub struct Foo {
     pub x: i32,
-    pub bar: __anon_1,
+    pub bar: Foo__bar,
     pub __pad_36: [u8; 4],
-    pub baz: __anon_2,
+    pub baz: Foo__baz,
     pub w: i32,
     pub __pad_52: [u8; 4],
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_1 {
+pub union Foo__bar {
     pub y: [u8; 10],
     pub z: [u16; 16],
 }
-impl std::fmt::Debug for __anon_1 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo__bar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo__bar")
+            .field("y", &self.y())
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_1 {
+impl Default for Foo__bar {
     fn default() -> Self {
-        __anon_1 {
+        Foo__bar {
             y: [u8::default(); 10],
         }
     }
 }
+impl Foo__bar {
+    pub fn y(&self) -> [u8; 10] {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.y }
+    }
+
+    pub fn set_y(&mut self, value: [u8; 10]) {
+        self.y = value;
+    }
+
+    pub fn set_z(&mut self, value: [u16; 16]) {
+        self.z = value;
+    }
+}
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_2 {
+pub union Foo__baz {
     pub w: u32,
     pub u: *mut u64,
 }
-impl std::fmt::Debug for __anon_2 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo__baz {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo__baz")
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_2 {
+impl Default for Foo__baz {
     fn default() -> Self {
-        __anon_2 {
+        Foo__baz {
             w: u32::default(),
         }
     }
 }
+impl Foo__baz {
+    pub fn set_w(&mut self, value: u32) {
+        self.w = value;
+    }
+
+    pub fn set_u(&mut self, value: *mut u64) {
+        self.u = value;
+    }
+}
 "#;
 
     let mmap = build_btf_mmap(prog_text);
@@ -2454,30 +4771,30 @@ struct Foo foo;
 #[repr(C)]
 pub struct Foo {
     pub x: i32,
-    pub bar: __anon_1,
-    pub baz: __anon_2,
+    pub bar: Foo__bar,
+    pub baz: Foo__baz,
     pub w: i32,
     pub __pad_68: [u8; 4],
 }
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_1 {
+pub struct Foo__bar {
     pub y: [u8; 10],
     pub z: [u16; 16],
 }
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_2 {
+pub struct Foo__baz {
     pub w: u32,
     pub __pad_4: [u8; 4],
     pub u: *mut u64,
 }
-impl Default for __anon_2 {
+impl Default for Foo__baz {
     fn default() -> Self {
-        __anon_2 {
+        Foo__baz {
             w: u32::default(),
             __pad_4: [u8::default(); 4],
-            u: std::ptr::null_mut(),
+            u: core::ptr::null_mut(),
         }
     }
 }
@@ -2527,71 +4844,98 @@ struct Foo foo;
 #[repr(C)]
 pub struct Foo {
     pub x: i32,
-    pub bar: __anon_1,
-    pub zerg: __anon_2,
-    pub baz: __anon_3,
+    pub bar: Foo__bar,
+    pub zerg: Foo__zerg,
+    pub baz: Foo__baz,
     pub w: i32,
     pub __pad_76: [u8; 4],
-    pub flarg: __anon_4,
+    pub flarg: Foo__flarg,
 }
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_1 {
+pub struct Foo__bar {
     pub y: [u8; 10],
     pub z: [u16; 16],
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_2 {
+pub union Foo__zerg {
     pub a: *mut i8,
     pub b: i32,
 }
-impl std::fmt::Debug for __anon_2 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo__zerg {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo__zerg")
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_2 {
+impl Default for Foo__zerg {
     fn default() -> Self {
-        __anon_2 {
-            a: std::ptr::null_mut(),
+        Foo__zerg {
+            a: core::ptr::null_mut(),
         }
     }
 }
+impl Foo__zerg {
+    pub fn set_a(&mut self, value: *mut i8) {
+        self.a = value;
+    }
+
+    pub fn set_b(&mut self, value: i32) {
+        self.b = value;
+    }
+}
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_3 {
+pub struct Foo__baz {
     pub w: u32,
     pub __pad_4: [u8; 4],
     pub u: *mut u64,
 }
-impl Default for __anon_3 {
+impl Default for Foo__baz {
     fn default() -> Self {
-        __anon_3 {
+        Foo__baz {
             w: u32::default(),
             __pad_4: [u8::default(); 4],
-            u: std::ptr::null_mut(),
+            u: core::ptr::null_mut(),
         }
     }
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_4 {
+pub union Foo__flarg {
     pub c: u8,
     pub d: [u64; 5],
 }
-impl std::fmt::Debug for __anon_4 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo__flarg {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo__flarg")
+            .field("c", &self.c())
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_4 {
+impl Default for Foo__flarg {
     fn default() -> Self {
-        __anon_4 {
+        Foo__flarg {
             c: u8::default(),
         }
     }
 }
+impl Foo__flarg {
+    pub fn c(&self) -> u8 {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.c }
+    }
+
+    pub fn set_c(&mut self, value: u8) {
+        self.c = value;
+    }
+
+    pub fn set_d(&mut self, value: [u64; 5]) {
+        self.d = value;
+    }
+}
 "#;
 
     let mmap = build_btf_mmap(prog_text);
@@ -2623,26 +4967,36 @@ struct Foo foo = {{0}};
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
 pub struct Foo {
-    pub __anon_1: __anon_1,
+    pub __anon_1: Foo__anon_1,
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_1 {
+pub union Foo__anon_1 {
     pub name: *mut i8,
-    pub tp: *mut std::ffi::c_void,
+    pub tp: *mut core::ffi::c_void,
 }
-impl std::fmt::Debug for __anon_1 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for Foo__anon_1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Foo__anon_1")
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_1 {
+impl Default for Foo__anon_1 {
     fn default() -> Self {
-        __anon_1 {
-            name: std::ptr::null_mut(),
+        Foo__anon_1 {
+            name: core::ptr::null_mut(),
         }
     }
 }
+impl Foo__anon_1 {
+    pub fn set_name(&mut self, value: *mut i8) {
+        self.name = value;
+    }
+
+    pub fn set_tp(&mut self, value: *mut core::ffi::c_void) {
+        self.tp = value;
+    }
+}
 "#;
 
     let mmap = build_btf_mmap(prog_text);
@@ -2668,23 +5022,30 @@ struct Foo foo;
 "#;
 
     let expected_output = r#"
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
 pub struct Foo {
-    pub test: std::mem::MaybeUninit<__anon_1>,
+    pub test: test_t,
 }
-impl Default for Foo {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct test_t(pub u32);
+#[allow(non_upper_case_globals)]
+impl test_t {
+    pub const FOO: test_t = test_t(1);
+}
+impl Default for test_t {
     fn default() -> Self {
-        Foo {
-            test: std::mem::MaybeUninit::new(__anon_1::default()),
-        }
+        test_t::FOO
     }
 }
-#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
-#[repr(u32)]
-pub enum __anon_1 {
-    #[default]
-    FOO = 1,
+impl core::fmt::Display for test_t {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            1 => write!(f, "FOO"),
+            value => write!(f, "test_t({value})"),
+        }
+    }
 }
 "#;
 
@@ -2697,6 +5058,30 @@ pub enum __anon_1 {
     assert_definition(&btf, &struct_foo, expected_output);
 }
 
+/// Check that anonymous types of global variables are named after the
+/// data section and the variable.
+#[test]
+fn test_btf_dump_anon_global_var() {
+    let prog_text = r#"
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+
+struct {
+    int x;
+} anon_global = {0};
+"#;
+
+    let mmap = build_btf_mmap(prog_text);
+    let btf = btf_from_mmap(&mmap);
+    let anon_global = find_type_in_btf!(btf, Var, "anon_global");
+
+    assert_eq!(
+        "data__anon_global",
+        btf.type_declaration(anon_global)
+            .expect("Failed to generate anon_global decl")
+    );
+}
+
 #[test]
 fn test_btf_dump_definition_int_encodings() {
     let prog_text = r#"
@@ -2720,7 +5105,7 @@ pub struct Foo {
     pub a: i32,
     pub b: u16,
     pub c: i16,
-    pub d: std::mem::MaybeUninit<bool>,
+    pub d: core::mem::MaybeUninit<bool>,
     pub e: i8,
 }
 impl Default for Foo {
@@ -2729,7 +5114,7 @@ impl Default for Foo {
             a: i32::default(),
             b: u16::default(),
             c: i16::default(),
-            d: std::mem::MaybeUninit::new(bool::default()),
+            d: core::mem::MaybeUninit::new(bool::default()),
             e: i8::default(),
         }
     }
@@ -2781,49 +5166,76 @@ struct bpf_sock_tuple_5_15 tup;
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
 pub struct bpf_sock_tuple_5_15 {
-    pub __anon_1: __anon_1,
+    pub __anon_1: bpf_sock_tuple_5_15__anon_1,
     pub __pad_36: [u8; 4],
-    pub __anon_2: __anon_2,
+    pub __anon_2: bpf_sock_tuple_5_15__anon_2,
 }
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_1 {
-    pub ipv4: __anon_3,
-    pub ipv6: __anon_4,
-}
-impl std::fmt::Debug for __anon_1 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+pub union bpf_sock_tuple_5_15__anon_1 {
+    pub ipv4: bpf_sock_tuple_5_15__anon_1__ipv4,
+    pub ipv6: bpf_sock_tuple_5_15__anon_1__ipv6,
+}
+impl core::fmt::Debug for bpf_sock_tuple_5_15__anon_1 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("bpf_sock_tuple_5_15__anon_1")
+            .field("ipv4", &self.ipv4())
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_1 {
+impl Default for bpf_sock_tuple_5_15__anon_1 {
     fn default() -> Self {
-        __anon_1 {
-            ipv4: __anon_3::default(),
+        bpf_sock_tuple_5_15__anon_1 {
+            ipv4: bpf_sock_tuple_5_15__anon_1__ipv4::default(),
         }
     }
 }
+impl bpf_sock_tuple_5_15__anon_1 {
+    pub fn ipv4(&self) -> bpf_sock_tuple_5_15__anon_1__ipv4 {
+        // SAFETY: All members of the union are plain old data and none is
+        //         smaller than this one, so it is always initialized.
+        unsafe { self.ipv4 }
+    }
+
+    pub fn set_ipv4(&mut self, value: bpf_sock_tuple_5_15__anon_1__ipv4) {
+        self.ipv4 = value;
+    }
+
+    pub fn set_ipv6(&mut self, value: bpf_sock_tuple_5_15__anon_1__ipv6) {
+        self.ipv6 = value;
+    }
+}
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub union __anon_2 {
+pub union bpf_sock_tuple_5_15__anon_2 {
     pub a: i32,
     pub b: *mut i8,
 }
-impl std::fmt::Debug for __anon_2 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(???)")
+impl core::fmt::Debug for bpf_sock_tuple_5_15__anon_2 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("bpf_sock_tuple_5_15__anon_2")
+            .finish_non_exhaustive()
     }
 }
-impl Default for __anon_2 {
+impl Default for bpf_sock_tuple_5_15__anon_2 {
     fn default() -> Self {
-        __anon_2 {
+        bpf_sock_tuple_5_15__anon_2 {
             a: i32::default(),
         }
     }
 }
+impl bpf_sock_tuple_5_15__anon_2 {
+    pub fn set_a(&mut self, value: i32) {
+        self.a = value;
+    }
+
+    pub fn set_b(&mut self, value: *mut i8) {
+        self.b = value;
+    }
+}
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_3 {
+pub struct bpf_sock_tuple_5_15__anon_1__ipv4 {
     pub saddr: u32,
     pub daddr: u32,
     pub sport: u16,
@@ -2831,7 +5243,7 @@ pub struct __anon_3 {
 }
 #[derive(Debug, Default, Copy, Clone)]
 #[repr(C)]
-pub struct __anon_4 {
+pub struct bpf_sock_tuple_5_15__anon_1__ipv6 {
     pub saddr: [u32; 4],
     pub daddr: [u32; 4],
     pub sport: u16,
@@ -2915,8 +5327,8 @@ pub struct bpf_dummy_ops {
 impl Default for bpf_dummy_ops {
     fn default() -> Self {
         bpf_dummy_ops {
-            test_1: std::ptr::null_mut(),
-            test_2: std::ptr::null_mut(),
+            test_1: core::ptr::null_mut(),
+            test_2: core::ptr::null_mut(),
         }
     }
 }
@@ -2952,3 +5364,432 @@ double d = 12.15;
         btf.type_declaration(d).expect("Failed to generate d decl")
     );
 }
+
+/// Check that generated skeletons can be formatted without `rustfmt`.
+#[test]
+fn test_skeleton_builder_formatting() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+
+            struct {
+                __uint(type, BPF_MAP_TYPE_ARRAY);
+                __uint(max_entries, 1);
+                __type(key, u32);
+                __type(value, u64);
+            } mymap SEC(".maps");
+
+            SEC("kprobe/foo")
+            int this_is_my_prog(u64 *ctx)
+            {
+                return 0;
+            }
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    add_vmlinux_header(&proj_dir);
+
+    let generate = |formatting| {
+        let skel = NamedTempFile::new().unwrap();
+        SkeletonBuilder::new()
+            .source(proj_dir.join("src/bpf/prog.bpf.c"))
+            .clang("clang")
+            .formatting(formatting)
+            .build_and_generate(skel.path())
+            .unwrap();
+        read_to_string(skel.path()).unwrap()
+    };
+
+    let unformatted = generate(Formatting::Disabled);
+    let builtin = generate(Formatting::Builtin);
+    assert_ne!(unformatted, builtin);
+    // The pretty-printer only touches whitespace.
+    let strip = |s: &str| s.split_whitespace().collect::<String>();
+    assert_eq!(strip(&unformatted), strip(&builtin));
+    assert!(builtin.contains("\npub struct ProgSkel<'a> {\n    "));
+    assert!(!builtin.contains("\n\n\n"));
+
+    // An explicitly provided `rustfmt` that does not exist is an error.
+    let skel = NamedTempFile::new().unwrap();
+    let result = SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .clang("clang")
+        .rustfmt(proj_dir.join("does-not-exist"))
+        .build_and_generate(skel.path());
+    assert!(result.is_err());
+}
+
+/// Check that documentation provided via BTF declaration tags ends up in
+/// the generated skeleton.
+#[test]
+fn test_skeleton_doc_comments() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+
+            #define __doc(x) __attribute__((btf_decl_tag("doc:" x)))
+
+            struct event {
+                u32 pid __doc("The PID of the process.");
+                u64 ts;
+            } __doc("An event reported to user space.");
+
+            struct {
+                __uint(type, BPF_MAP_TYPE_ARRAY);
+                __uint(max_entries, 1);
+                __type(key, u32);
+                __type(value, struct event);
+            } events SEC(".maps") __doc("The most recent event.");
+
+            SEC("kprobe/foo")
+            __doc("Record an event.\n\nCalled on every invocation of `foo`.")
+            int handle__foo(u64 *ctx)
+            {
+                return 0;
+            }
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    add_vmlinux_header(&proj_dir);
+
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .clang("clang")
+        .build_and_generate(skel.path())
+        .unwrap();
+    let skel = read_to_string(skel.path()).unwrap();
+
+    assert!(skel.contains("/// An event reported to user space.\n"));
+    assert!(skel.contains("/// The PID of the process.\n"));
+    assert!(skel.contains("/// The most recent event.\n"));
+    assert!(skel.contains("/// Record an event.\n"));
+    assert!(skel.contains("/// Called on every invocation of `foo`.\n"));
+}
+
+#[test]
+fn test_skeleton_object_data() {
+    let (_dir, proj_dir, _cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+
+            SEC("kprobe/foo")
+            int this_is_my_prog(u64 *ctx)
+            {
+                return 0;
+            }
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    add_vmlinux_header(&proj_dir);
+
+    let obj = proj_dir.join("prog.bpf.o");
+    let skel = proj_dir.join("src/bpf/prog.skel.rs");
+    let generate = |object_data| {
+        SkeletonBuilder::new()
+            .source(proj_dir.join("src/bpf/prog.bpf.c"))
+            .obj(&obj)
+            .object_data(object_data)
+            .build_and_generate(&skel)
+            .unwrap();
+        read_to_string(&skel).unwrap()
+    };
+
+    let skel = generate(ObjectData::Embed);
+    assert!(skel.contains("const DATA: &[u8] = &["));
+    assert!(!skel.contains("pub obj_path"));
+
+    // The object is referenced relative to the skeleton.
+    let skel = generate(ObjectData::Include);
+    assert!(skel.contains(r#"const DATA: &[u8] = include_bytes!("../../prog.bpf.o");"#));
+    assert!(!skel.contains("const DATA: &[u8] = &["));
+    assert!(!skel.contains("pub obj_path"));
+
+    let skel = generate(ObjectData::Runtime(PathBuf::from("/usr/lib/foo")));
+    assert!(skel.contains(r#"const OBJ_PATH: &str = "/usr/lib/foo/prog.bpf.o";"#));
+    assert!(skel.contains("pub obj_path: Option<std::path::PathBuf>,"));
+    assert!(skel.contains("from_owned(data)"));
+    assert!(!skel.contains("const DATA"));
+
+    // Subskeletons do not carry the object, so the setting is rejected.
+    let err = SkeletonBuilder::new()
+        .obj(&obj)
+        .subskeleton(true)
+        .object_data(ObjectData::Include)
+        .generate(&skel)
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("Subskeletons do not support object data settings"));
+}
+
+/// Check that map pinning and autoattach preferences from the package
+/// metadata are applied by generated skeletons.
+#[test]
+fn test_skeleton_metadata_defaults() {
+    let prog = r#"
+        #include "vmlinux.h"
+        #include <bpf/bpf_helpers.h>
+
+        struct {
+            __uint(type, BPF_MAP_TYPE_HASH);
+            __uint(max_entries, 1024);
+            __type(key, u32);
+            __type(value, u64);
+        } counts SEC(".maps");
+
+        struct {
+            __uint(type, BPF_MAP_TYPE_HASH);
+            __uint(max_entries, 1024);
+            __type(key, u32);
+            __type(value, u64);
+        } scratch SEC(".maps");
+
+        SEC("kprobe/foo")
+        int handle__foo(u64 *ctx)
+        {
+            return 0;
+        }
+
+        SEC("kprobe/bar")
+        int handle__bar(u64 *ctx)
+        {
+            return 0;
+        }
+    "#;
+
+    let setup = |metadata: &str| {
+        let (dir, proj_dir, cargo_toml) = setup_temp_project();
+        let mut cargo_toml_file = OpenOptions::new()
+            .append(true)
+            .open(&cargo_toml)
+            .expect("failed to open Cargo.toml");
+        writeln!(cargo_toml_file, "{metadata}").expect("write to Cargo.toml failed");
+
+        create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+        write(proj_dir.join("src/bpf/prog.bpf.c"), prog).expect("failed to write prog.bpf.c");
+        add_vmlinux_header(&proj_dir);
+
+        let result = make(
+            true,
+            Some(&cargo_toml),
+            None,
+            Vec::new(),
+            true,
+            true,
+            Vec::new(),
+            &Default::default(),
+            &BuildOpts::default(),
+        );
+        (dir, proj_dir, result)
+    };
+
+    let (_dir, proj_dir, result) = setup(
+        r#"
+        [package.metadata.libbpf.objects.prog]
+        pin_root = "/sys/fs/bpf/myapp"
+        maps.counts = { pin = true }
+        maps.scratch = { pin = false }
+        progs.handle__bar = { autoattach = false }
+        "#,
+    );
+    let () = result.unwrap();
+
+    let skel = read_to_string(proj_dir.join("src/bpf/prog.skel.rs")).unwrap();
+    assert!(skel.contains(r#"set_pin_path("/sys/fs/bpf/myapp/counts")"#));
+    assert!(!skel.contains("/sys/fs/bpf/myapp/scratch"));
+    assert!(skel.contains("set_autoattach(false)"));
+    assert_eq!(skel.matches("set_autoattach(").count(), 1);
+
+    // Maps are pinned in libbpf's default pin root if none is configured.
+    let (_dir, proj_dir, result) = setup(
+        r#"
+        [package.metadata.libbpf.objects.prog]
+        maps.scratch = { pin = true }
+        "#,
+    );
+    let () = result.unwrap();
+
+    let skel = read_to_string(proj_dir.join("src/bpf/prog.skel.rs")).unwrap();
+    assert!(skel.contains(r#"set_pin_path("/sys/fs/bpf/scratch")"#));
+
+    // Referencing maps or programs that don't exist is an error.
+    let (_dir, _proj_dir, result) = setup(
+        r#"
+        [package.metadata.libbpf.objects.prog]
+        maps.does_not_exist = { pin = true }
+        "#,
+    );
+    let _err = result.unwrap_err();
+
+    let (_dir, _proj_dir, result) = setup(
+        r#"
+        [package.metadata.libbpf.objects.prog]
+        progs.does_not_exist = { autoattach = true }
+        "#,
+    );
+    let _err = result.unwrap_err();
+}
+
+/// Check that the skeleton defaults of an object can be looked up from the
+/// package metadata, as done by `SkeletonBuilder` in build scripts.
+#[test]
+fn test_skeleton_builder_metadata_defaults() {
+    let (_dir, _proj_dir, cargo_toml) = setup_temp_project();
+    let mut cargo_toml_file = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+    writeln!(
+        cargo_toml_file,
+        r#"
+        [package.metadata.libbpf.objects.prog]
+        pin_root = "/sys/fs/bpf/myapp"
+        maps.counts = {{ pin = true }}
+        maps.scratch = {{ pin = false }}
+        progs.handle__bar = {{ autoattach = false }}
+        "#
+    )
+    .expect("write to Cargo.toml failed");
+
+    let defaults = metadata::skel_defaults(&cargo_toml, "prog").unwrap();
+    assert_eq!(defaults.pin_root, Some(PathBuf::from("/sys/fs/bpf/myapp")));
+    assert_eq!(defaults.pinned_maps, vec!["counts".to_string()]);
+    assert_eq!(
+        defaults.autoattach.into_iter().collect::<Vec<_>>(),
+        vec![("handle__bar".to_string(), false)]
+    );
+
+    let defaults = metadata::skel_defaults(&cargo_toml, "other").unwrap();
+    assert_eq!(defaults, Default::default());
+}
+
+/// Check that skeletons for objects containing `struct_ops` maps can be
+/// used for adjusting and attaching them.
+#[test]
+fn test_skeleton_builder_struct_ops() {
+    let (_dir, proj_dir, cargo_toml) = setup_temp_project();
+
+    create_dir(proj_dir.join("src/bpf")).expect("failed to create prog dir");
+    write(
+        proj_dir.join("src/bpf/prog.bpf.c"),
+        r#"
+            #include "vmlinux.h"
+            #include <bpf/bpf_helpers.h>
+            #include <bpf/bpf_tracing.h>
+
+            SEC("struct_ops/test_1")
+            int BPF_PROG(test_1, struct bpf_dummy_ops_state *state)
+            {
+                return 0;
+            }
+
+            SEC("struct_ops/test_2")
+            int BPF_PROG(test_2, struct bpf_dummy_ops_state *state)
+            {
+                return 1;
+            }
+
+            SEC(".struct_ops")
+            struct bpf_dummy_ops dummy_1 = {
+                .test_1 = (void *)test_1,
+            };
+
+            SEC(".struct_ops.link")
+            struct bpf_dummy_ops dummy_2 = {
+                .test_1 = (void *)test_1,
+            };
+
+            char LICENSE[] SEC("license") = "GPL";
+        "#,
+    )
+    .expect("failed to write prog.bpf.c");
+
+    add_vmlinux_header(&proj_dir);
+
+    let skel = NamedTempFile::new().unwrap();
+    SkeletonBuilder::new()
+        .source(proj_dir.join("src/bpf/prog.bpf.c"))
+        .build_and_generate(skel.path())
+        .unwrap();
+
+    let mut cargo = OpenOptions::new()
+        .append(true)
+        .open(&cargo_toml)
+        .expect("failed to open Cargo.toml");
+
+    writeln!(
+        cargo,
+        r#"
+        libbpf-rs = {{ path = "{}" }}
+        "#,
+        get_libbpf_rs_path().as_path().display()
+    )
+    .expect("failed to write to Cargo.toml");
+
+    write(
+        proj_dir.join("src/main.rs"),
+        format!(
+            r#"
+            #[path = "{skel_path}"]
+            mod skel;
+            use skel::*;
+            use libbpf_rs::skel::OpenSkel;
+            use libbpf_rs::skel::Skel;
+            use libbpf_rs::skel::SkelBuilder;
+            use libbpf_rs::AsRawLibbpf as _;
+
+            fn main() {{
+                let builder = ProgSkelBuilder::default();
+                let mut open_skel = builder
+                    .open()
+                    .expect("failed to open skel");
+
+                // Check that `struct_ops` maps can be adjusted before load.
+                let test_2 = open_skel.progs().test_2().as_libbpf_object().as_ptr();
+                open_skel.struct_ops.dummy_1_mut().test_1 = test_2;
+
+                let mut skel = open_skel
+                    .load()
+                    .expect("failed to load skel");
+                let _dummy_2 = skel.struct_ops().dummy_2();
+
+                // Check that `struct_ops` maps are attached along with
+                // programs and that their links are accessible.
+                skel.attach().expect("failed to attach skel");
+                let _link1: Option<libbpf_rs::Link> = skel.links.dummy_1.take();
+                let _link2: Option<libbpf_rs::Link> = skel.links.dummy_2.take();
+                let _report = skel.attach_partial();
+            }}
+            "#,
+            skel_path = skel.path().display(),
+        ),
+    )
+    .expect("failed to write to main.rs");
+
+    let status = Command::new("cargo")
+        .arg("build")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(cargo_toml.into_os_string())
+        .env("RUSTFLAGS", "-Dwarnings")
+        .status()
+        .expect("failed to spawn cargo-build");
+    assert!(status.success());
+}
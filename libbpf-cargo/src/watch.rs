@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use anyhow::Result;
+
+use crate::build;
+use crate::metadata;
+
+/// The interval at which watched files are checked for modifications.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The modification times of a set of files, `None` for files that do not
+/// exist (anymore).
+type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+/// Collect the files whose modification should trigger a rebuild.
+///
+/// These are the directories containing the project's BPF source files
+/// (so that added or removed files are noticed), all C source and header
+/// files inside of them, as well as all dependencies recorded in the
+/// dependency files of the last build.
+pub(crate) fn watched_files(debug: bool, manifest_path: Option<&PathBuf>) -> Result<Vec<PathBuf>> {
+    let (_target_dir, objs) = metadata::get(debug, manifest_path)?;
+    let mut files = BTreeSet::new();
+
+    for obj in objs {
+        if let Some(dir) = obj.path.parent() {
+            let entries =
+                fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                let is_source = matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("c" | "h")
+                );
+                if is_source {
+                    let _inserted = files.insert(path);
+                }
+            }
+            let _inserted = files.insert(dir.to_path_buf());
+        }
+
+        let depfile = build::object_path(&obj)?.with_extension("d");
+        if let Ok(contents) = fs::read_to_string(depfile) {
+            let () = files.extend(build::parse_depfile(&contents));
+        }
+        let _inserted = files.insert(obj.path);
+    }
+
+    Ok(files.into_iter().collect())
+}
+
+fn snapshot(files: &[PathBuf]) -> Snapshot {
+    files
+        .iter()
+        .map(|file| {
+            let modified = fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            (file.clone(), modified)
+        })
+        .collect()
+}
+
+/// Run `command` using the shell, reporting but otherwise ignoring
+/// failures.
+fn run(command: &str) {
+    match Command::new("sh").arg("-c").arg(command).status() {
+        Ok(status) if status.success() => (),
+        Ok(status) => eprintln!("`{command}` failed: {status}"),
+        Err(err) => eprintln!("Failed to run `{command}`: {err}"),
+    }
+}
+
+/// Invoke `rebuild` and, if provided and the rebuild succeeded, run
+/// `exec` using the shell. Repeat each time a BPF source file or any of
+/// the headers it includes changes.
+///
+/// Failures to rebuild or to determine the files to watch are reported,
+/// but do not end watching.
+pub fn watch<F>(
+    debug: bool,
+    manifest_path: Option<&PathBuf>,
+    exec: Option<&str>,
+    mut rebuild: F,
+) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    let mut files = Vec::new();
+    loop {
+        match rebuild() {
+            Ok(()) => {
+                if let Some(exec) = exec {
+                    let () = run(exec);
+                }
+            }
+            Err(err) => eprintln!("Error: {err:?}"),
+        }
+
+        files = match watched_files(debug, manifest_path) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                // Keep watching the files known from before as well as
+                // the manifest, which may very well be what is broken.
+                let manifest = manifest_path
+                    .cloned()
+                    .unwrap_or_else(|| PathBuf::from("Cargo.toml"));
+                let mut files = mem::take(&mut files);
+                if !files.contains(&manifest) {
+                    let () = files.push(manifest);
+                }
+                files
+            }
+        };
+        let before = snapshot(&files);
+        println!("Watching {} files for changes", files.len());
+
+        loop {
+            let () = thread::sleep(POLL_INTERVAL);
+            if snapshot(&files) != before {
+                break;
+            }
+        }
+
+        // Editors frequently write files in multiple steps. Give them a
+        // moment to finish before rebuilding.
+        let () = thread::sleep(POLL_INTERVAL);
+        if debug {
+            let changed = snapshot(&files)
+                .into_iter()
+                .filter(|(file, modified)| before.get(file) != Some(modified))
+                .map(|(file, _modified)| file)
+                .collect::<Vec<_>>();
+            println!("Detected changes to {changed:?}");
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_char;
+use std::ptr;
+use std::ptr::NonNull;
+
+use crate::util;
+use crate::util::create_bpf_entity_checked;
+use crate::Result;
+
+use super::types::FwdKind;
+use super::types::IntEncoding;
+use super::types::Linkage;
+use super::Btf;
+use super::DropPolicy;
+use super::TypeId;
+
+fn opt_str_to_cstring(name: Option<&str>) -> Result<Option<CString>> {
+    name.map(util::str_to_cstring).transpose()
+}
+
+fn opt_cstring_ptr(name: &Option<CString>) -> *const c_char {
+    name.as_ref().map_or(ptr::null(), |name| name.as_ptr())
+}
+
+fn parse_type_id(id: i32) -> Result<TypeId> {
+    util::parse_ret_i32(id).map(|id| TypeId::from(id as u32))
+}
+
+/// A builder for constructing BTF type information from scratch.
+///
+/// Each of the `add_*` methods appends a type to the BTF and returns
+/// its [`TypeId`], which can then be used for referring to it from
+/// types added later on. Members of composite types and values of
+/// enums are added to the most recently added struct, union, or enum
+/// type, respectively.
+///
+/// ```no_run
+/// # use libbpf_rs::btf::BtfBuilder;
+/// # use libbpf_rs::btf::types::IntEncoding;
+/// let mut builder = BtfBuilder::new()?;
+/// let int = builder.add_int("int", 4, IntEncoding::Signed)?;
+/// let point = builder.add_struct(Some("point"), 8)?;
+/// let () = builder.add_field(Some("x"), int, 0, 0)?;
+/// let () = builder.add_field(Some("y"), int, 32, 0)?;
+/// let btf = builder.build();
+/// # Ok::<(), libbpf_rs::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct BtfBuilder {
+    ptr: NonNull<libbpf_sys::btf>,
+}
+
+impl BtfBuilder {
+    /// Create a new builder for empty BTF.
+    pub fn new() -> Result<Self> {
+        let ptr = create_bpf_entity_checked(|| unsafe { libbpf_sys::btf__new_empty() })?;
+        Ok(Self { ptr })
+    }
+
+    /// Add an integer type with the given `name`, size in bytes, and
+    /// `encoding`.
+    pub fn add_int(&mut self, name: &str, size: usize, encoding: IntEncoding) -> Result<TypeId> {
+        let name = util::str_to_cstring(name)?;
+        let encoding = match encoding {
+            IntEncoding::None => 0,
+            IntEncoding::Signed => libbpf_sys::BTF_INT_SIGNED,
+            IntEncoding::Char => libbpf_sys::BTF_INT_CHAR,
+            IntEncoding::Bool => libbpf_sys::BTF_INT_BOOL,
+        };
+        let id = unsafe {
+            libbpf_sys::btf__add_int(
+                self.ptr.as_ptr(),
+                name.as_ptr(),
+                size as libbpf_sys::size_t,
+                encoding as i32,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a floating point type with the given `name` and size in bytes.
+    pub fn add_float(&mut self, name: &str, size: usize) -> Result<TypeId> {
+        let name = util::str_to_cstring(name)?;
+        let id = unsafe {
+            libbpf_sys::btf__add_float(self.ptr.as_ptr(), name.as_ptr(), size as libbpf_sys::size_t)
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a pointer to the type `ref_type`.
+    pub fn add_ptr(&mut self, ref_type: TypeId) -> Result<TypeId> {
+        let id = unsafe { libbpf_sys::btf__add_ptr(self.ptr.as_ptr(), u32::from(ref_type) as i32) };
+        parse_type_id(id)
+    }
+
+    /// Add an array of `len` elements of type `elem_type`, indexed by
+    /// `index_type`.
+    pub fn add_array(&mut self, index_type: TypeId, elem_type: TypeId, len: u32) -> Result<TypeId> {
+        let id = unsafe {
+            libbpf_sys::btf__add_array(
+                self.ptr.as_ptr(),
+                u32::from(index_type) as i32,
+                u32::from(elem_type) as i32,
+                len,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a struct type of the given size in bytes. Use
+    /// [`BtfBuilder::add_field`] to add members to it.
+    pub fn add_struct(&mut self, name: Option<&str>, size: u32) -> Result<TypeId> {
+        let name = opt_str_to_cstring(name)?;
+        let id =
+            unsafe { libbpf_sys::btf__add_struct(self.ptr.as_ptr(), opt_cstring_ptr(&name), size) };
+        parse_type_id(id)
+    }
+
+    /// Add a union type of the given size in bytes. Use
+    /// [`BtfBuilder::add_field`] to add members to it.
+    pub fn add_union(&mut self, name: Option<&str>, size: u32) -> Result<TypeId> {
+        let name = opt_str_to_cstring(name)?;
+        let id =
+            unsafe { libbpf_sys::btf__add_union(self.ptr.as_ptr(), opt_cstring_ptr(&name), size) };
+        parse_type_id(id)
+    }
+
+    /// Add a member of type `ty` to the most recently added struct or
+    /// union.
+    ///
+    /// `bit_offset` is the offset of the member from the start of the
+    /// composite type, in bits. `bit_size` should be zero unless the
+    /// member is a bitfield.
+    pub fn add_field(
+        &mut self,
+        name: Option<&str>,
+        ty: TypeId,
+        bit_offset: u32,
+        bit_size: u32,
+    ) -> Result<()> {
+        let name = opt_str_to_cstring(name)?;
+        let ret = unsafe {
+            libbpf_sys::btf__add_field(
+                self.ptr.as_ptr(),
+                opt_cstring_ptr(&name),
+                u32::from(ty) as i32,
+                bit_offset,
+                bit_size,
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Add an enum type of the given size in bytes. Use
+    /// [`BtfBuilder::add_enum_value`] to add values to it.
+    pub fn add_enum(&mut self, name: Option<&str>, size: u32) -> Result<TypeId> {
+        let name = opt_str_to_cstring(name)?;
+        let id =
+            unsafe { libbpf_sys::btf__add_enum(self.ptr.as_ptr(), opt_cstring_ptr(&name), size) };
+        parse_type_id(id)
+    }
+
+    /// Add a named value to the most recently added enum.
+    pub fn add_enum_value(&mut self, name: &str, value: i64) -> Result<()> {
+        let name = util::str_to_cstring(name)?;
+        let ret =
+            unsafe { libbpf_sys::btf__add_enum_value(self.ptr.as_ptr(), name.as_ptr(), value) };
+        util::parse_ret(ret)
+    }
+
+    /// Add a 64 bit enum type of the given size in bytes. Use
+    /// [`BtfBuilder::add_enum64_value`] to add values to it.
+    pub fn add_enum64(&mut self, name: Option<&str>, size: u32, is_signed: bool) -> Result<TypeId> {
+        let name = opt_str_to_cstring(name)?;
+        let id = unsafe {
+            libbpf_sys::btf__add_enum64(self.ptr.as_ptr(), opt_cstring_ptr(&name), size, is_signed)
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a named value to the most recently added 64 bit enum.
+    pub fn add_enum64_value(&mut self, name: &str, value: u64) -> Result<()> {
+        let name = util::str_to_cstring(name)?;
+        let ret =
+            unsafe { libbpf_sys::btf__add_enum64_value(self.ptr.as_ptr(), name.as_ptr(), value) };
+        util::parse_ret(ret)
+    }
+
+    /// Add a forward declaration of a struct or union.
+    pub fn add_fwd(&mut self, name: &str, kind: FwdKind) -> Result<TypeId> {
+        let name = util::str_to_cstring(name)?;
+        let kind = match kind {
+            FwdKind::Struct => libbpf_sys::BTF_FWD_STRUCT,
+            FwdKind::Union => libbpf_sys::BTF_FWD_UNION,
+        };
+        let id = unsafe { libbpf_sys::btf__add_fwd(self.ptr.as_ptr(), name.as_ptr(), kind) };
+        parse_type_id(id)
+    }
+
+    /// Add a typedef `name` for the type `ref_type`.
+    pub fn add_typedef(&mut self, name: &str, ref_type: TypeId) -> Result<TypeId> {
+        let name = util::str_to_cstring(name)?;
+        let id = unsafe {
+            libbpf_sys::btf__add_typedef(
+                self.ptr.as_ptr(),
+                name.as_ptr(),
+                u32::from(ref_type) as i32,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a `volatile` qualified version of the type `ref_type`.
+    pub fn add_volatile(&mut self, ref_type: TypeId) -> Result<TypeId> {
+        let id =
+            unsafe { libbpf_sys::btf__add_volatile(self.ptr.as_ptr(), u32::from(ref_type) as i32) };
+        parse_type_id(id)
+    }
+
+    /// Add a `const` qualified version of the type `ref_type`.
+    pub fn add_const(&mut self, ref_type: TypeId) -> Result<TypeId> {
+        let id =
+            unsafe { libbpf_sys::btf__add_const(self.ptr.as_ptr(), u32::from(ref_type) as i32) };
+        parse_type_id(id)
+    }
+
+    /// Add a `restrict` qualified version of the type `ref_type`.
+    pub fn add_restrict(&mut self, ref_type: TypeId) -> Result<TypeId> {
+        let id =
+            unsafe { libbpf_sys::btf__add_restrict(self.ptr.as_ptr(), u32::from(ref_type) as i32) };
+        parse_type_id(id)
+    }
+
+    /// Add a type tag `value` to the type `ref_type`.
+    pub fn add_type_tag(&mut self, value: &str, ref_type: TypeId) -> Result<TypeId> {
+        let value = util::str_to_cstring(value)?;
+        let id = unsafe {
+            libbpf_sys::btf__add_type_tag(
+                self.ptr.as_ptr(),
+                value.as_ptr(),
+                u32::from(ref_type) as i32,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a function prototype returning `ret_type`. Use
+    /// [`BtfBuilder::add_func_param`] to add parameters to it.
+    pub fn add_func_proto(&mut self, ret_type: TypeId) -> Result<TypeId> {
+        let id = unsafe {
+            libbpf_sys::btf__add_func_proto(self.ptr.as_ptr(), u32::from(ret_type) as i32)
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a parameter of type `ty` to the most recently added function
+    /// prototype.
+    pub fn add_func_param(&mut self, name: Option<&str>, ty: TypeId) -> Result<()> {
+        let name = opt_str_to_cstring(name)?;
+        let ret = unsafe {
+            libbpf_sys::btf__add_func_param(
+                self.ptr.as_ptr(),
+                opt_cstring_ptr(&name),
+                u32::from(ty) as i32,
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Add a function `name` with the prototype `proto`.
+    pub fn add_func(&mut self, name: &str, linkage: Linkage, proto: TypeId) -> Result<TypeId> {
+        let name = util::str_to_cstring(name)?;
+        let id = unsafe {
+            libbpf_sys::btf__add_func(
+                self.ptr.as_ptr(),
+                name.as_ptr(),
+                u32::from(linkage),
+                u32::from(proto) as i32,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Add a declaration tag `value` to the type `ref_type` or, if
+    /// `component_idx` is provided, to the member or parameter with that
+    /// index.
+    pub fn add_decl_tag(
+        &mut self,
+        value: &str,
+        ref_type: TypeId,
+        component_idx: Option<u32>,
+    ) -> Result<TypeId> {
+        let value = util::str_to_cstring(value)?;
+        let component_idx = component_idx.map_or(-1, |idx| idx as i32);
+        let id = unsafe {
+            libbpf_sys::btf__add_decl_tag(
+                self.ptr.as_ptr(),
+                value.as_ptr(),
+                u32::from(ref_type) as i32,
+                component_idx,
+            )
+        };
+        parse_type_id(id)
+    }
+
+    /// Finish construction, yielding the constructed [`Btf`].
+    pub fn build(self) -> Btf<'static> {
+        let ptr = self.ptr;
+        // Ownership of the `btf` object is transferred to the `Btf`.
+        let () = mem::forget(self);
+
+        Btf {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for BtfBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY: the btf pointer is valid and owned by us.
+            libbpf_sys::btf__free(self.ptr.as_ptr())
+        }
+    }
+}
+
+// SAFETY: The `btf` object is exclusively owned by the builder and not
+//         tied to any particular thread.
+unsafe impl Send for BtfBuilder {}
@@ -12,6 +12,9 @@
 //! [`Btf::type_by_kind`]). If you want to get a type independently of the kind, just make sure `K`
 //! binds to [`BtfType`].
 
+mod builder;
+#[cfg(feature = "serde")]
+mod ser;
 pub mod types;
 
 use std::ffi::CStr;
@@ -24,19 +27,24 @@ use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 use std::io;
 use std::marker::PhantomData;
+use std::mem;
 use std::mem::size_of;
 use std::num::NonZeroUsize;
 use std::ops::Deref;
+use std::os::raw::c_char;
 use std::os::raw::c_ulong;
 use std::os::raw::c_void;
 use std::os::unix::prelude::AsRawFd;
+use std::os::unix::prelude::BorrowedFd;
 use std::os::unix::prelude::FromRawFd;
 use std::os::unix::prelude::OsStrExt;
 use std::os::unix::prelude::OwnedFd;
 use std::path::Path;
 use std::ptr;
 use std::ptr::NonNull;
+use std::slice;
 
+use crate::util;
 use crate::util::create_bpf_entity_checked;
 use crate::util::create_bpf_entity_checked_opt;
 use crate::util::parse_ret_i32;
@@ -46,9 +54,122 @@ use crate::Result;
 
 use self::types::Composite;
 
+pub use self::builder::BtfBuilder;
+
+/// Options for rendering raw data according to its BTF type, as done by
+/// [`Btf::dump_data`].
+#[derive(Clone, Debug, Default)]
+pub struct DumpDataOpts {
+    /// Render the value on a single line.
+    pub compact: bool,
+    /// The string used for one level of indentation. If `None`, libbpf's
+    /// default (a tab) is used.
+    pub indent_str: Option<String>,
+    /// The indentation level to start at.
+    pub indent_level: usize,
+    /// Omit type and field names.
+    pub skip_names: bool,
+    /// Emit fields that are zero, which are skipped by default.
+    pub emit_zeroes: bool,
+    /// Render integer values in hexadecimal instead of decimal.
+    pub hex: bool,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+/// The location of a (possibly nested) member, as resolved by
+/// [`Btf::field_offset`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldOffset {
+    /// The offset of the member, in bits.
+    pub bit_offset: u32,
+    /// The size of the member in bits, if it is a bitfield.
+    pub bitfield_size: Option<u8>,
+    /// The type of the member.
+    pub ty: TypeId,
+}
+
+/// State shared with [`dump_printf_cb`] while dumping data.
+struct DumpCtx {
+    hex: bool,
+    output: Vec<u8>,
+    error: Option<io::Error>,
+}
+
+/// Rewrite a printf style format string such that all decimal integer
+/// conversions are emitted in (prefixed) hexadecimal form instead.
+fn hexify_format(fmt: &CStr) -> CString {
+    let mut result = Vec::with_capacity(fmt.to_bytes().len() + 4);
+    let mut bytes = fmt.to_bytes().iter().copied().peekable();
+
+    while let Some(b) = bytes.next() {
+        let () = result.push(b);
+        if b != b'%' {
+            continue;
+        }
+
+        // Collect the conversion specification up to (and excluding) the
+        // conversion character.
+        let mut spec = Vec::new();
+        while let Some(c) = bytes.next_if(|c| !c.is_ascii_alphabetic() || b"hljztL".contains(c)) {
+            let () = spec.push(c);
+            if c == b'%' {
+                break;
+            }
+        }
+
+        match bytes.next() {
+            Some(b'd' | b'i' | b'u') => {
+                if !spec.contains(&b'#') {
+                    let () = result.push(b'#');
+                }
+                let () = result.extend(spec);
+                let () = result.push(b'x');
+            }
+            Some(c) => {
+                let () = result.extend(spec);
+                let () = result.push(c);
+            }
+            None => {
+                let () = result.extend(spec);
+            }
+        }
+    }
+
+    // SANITY: `result` was created from a `CStr` and we never add NUL
+    //         bytes.
+    CString::new(result).unwrap()
+}
+
+unsafe extern "C" fn dump_printf_cb(ctx: *mut c_void, fmt: *const c_char, va_list: *mut c_void) {
+    // SAFETY: `ctx` is the `DumpCtx` pointer we provided to `btf_dump__new`
+    //         and it is valid for the duration of the dump.
+    let ctx = unsafe { &mut *ctx.cast::<DumpCtx>() };
+    if ctx.error.is_some() {
+        return;
+    }
+
+    let hex_fmt;
+    let fmt = if ctx.hex {
+        // SAFETY: libbpf always provides a valid format string.
+        hex_fmt = hexify_format(unsafe { CStr::from_ptr(fmt) });
+        hex_fmt.as_ptr()
+    } else {
+        fmt
+    };
+
+    // SAFETY: `fmt` is a valid format string and `va_list` the argument
+    //         list matching it.
+    match unsafe { vsprintf::vsprintf_raw(fmt, va_list) } {
+        Ok(bytes) => ctx.output.extend(bytes),
+        Err(err) => ctx.error = Some(err),
+    }
+}
+
 /// The various btf types.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BtfKind {
     /// [Void](types::Void)
     Void = 0,
@@ -126,6 +247,7 @@ impl TryFrom<u32> for BtfKind {
 
 /// The id of a btf type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TypeId(u32);
 
 impl From<u32> for TypeId {
@@ -151,6 +273,8 @@ enum DropPolicy {
     Nothing,
     SelfPtrOnly,
     ObjPtr(*mut libbpf_sys::bpf_object),
+    /// Free the btf object as well as the base btf it was split off of.
+    WithBase(NonNull<libbpf_sys::btf>),
 }
 
 /// The btf information of a bpf object.
@@ -195,6 +319,18 @@ impl Btf<'static> {
         })
     }
 
+    /// Load the btf information with the given kernel assigned id.
+    pub(crate) fn from_btf_id(id: u32) -> Result<Self> {
+        let ptr =
+            create_bpf_entity_checked(|| unsafe { libbpf_sys::btf__load_from_kernel_by_id(id) })?;
+
+        Ok(Self {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        })
+    }
+
     /// Load the btf information of an bpf object from a program id.
     pub fn from_prog_id(id: u32) -> Result<Self> {
         let fd = parse_ret_i32(unsafe { libbpf_sys::bpf_prog_get_fd_by_id(id) })?;
@@ -221,9 +357,69 @@ impl Btf<'static> {
             _marker: PhantomData,
         })
     }
+
+    /// Load the split btf information of the kernel module `name`, on top
+    /// of the vmlinux btf.
+    ///
+    /// The returned object provides access to the types of vmlinux as
+    /// well as those defined by the module.
+    pub fn from_module(name: &str) -> Result<Self> {
+        let name = util::str_to_cstring(name)?;
+        let base = Self::from_vmlinux()?;
+        let ptr = create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::btf__load_module_btf(name.as_ptr(), base.ptr.as_ptr())
+        })?;
+
+        // Ownership of the base is transferred to the split btf object.
+        let base_ptr = base.ptr;
+        let () = mem::forget(base);
+
+        Ok(Self {
+            ptr,
+            drop_policy: DropPolicy::WithBase(base_ptr),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Load btf information from raw BTF data, such as that produced by
+    /// [`Btf::to_raw`].
+    pub fn from_raw_bytes(data: &[u8]) -> Result<Self> {
+        let size = u32::try_from(data.len())
+            .map_err(|_| Error::with_invalid_data("raw BTF data is too large"))?;
+        let ptr = create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::btf__new(data.as_ptr().cast::<c_void>(), size)
+        })?;
+
+        Ok(Self {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        })
+    }
 }
 
 impl<'btf> Btf<'btf> {
+    /// Load split btf information from the specified path, on top of the
+    /// provided `base` btf.
+    ///
+    /// This is typically used for loading the btf of a kernel module (as
+    /// found in `/sys/kernel/btf/<module>`) with vmlinux btf as the base.
+    pub fn from_path_with_base<P: AsRef<Path>>(path: P, base: &'btf Btf<'_>) -> Result<Self> {
+        let path = util::path_to_cstring(path)?;
+        let ptr = create_bpf_entity_checked(|| unsafe {
+            // SAFETY: libbpf does not modify the base btf; it merely
+            //         references it for the lifetime of the split btf,
+            //         which is enforced by the `'btf` lifetime.
+            libbpf_sys::btf__parse_split(path.as_ptr(), base.ptr.as_ptr())
+        })?;
+
+        Ok(Self {
+            ptr,
+            drop_policy: DropPolicy::SelfPtrOnly,
+            _marker: PhantomData,
+        })
+    }
+
     /// Create a new `Btf` instance from the given [`libbpf_sys::bpf_object`].
     pub fn from_bpf_object(obj: &'btf libbpf_sys::bpf_object) -> Result<Option<Self>> {
         Self::from_bpf_object_raw(obj)
@@ -245,9 +441,9 @@ impl<'btf> Btf<'btf> {
 
     /// From raw bytes coming from an object file.
     pub fn from_raw(name: &'btf str, object_file: &'btf [u8]) -> Result<Option<Self>> {
-        let cname = CString::new(name)
-            .map_err(|_| Error::with_invalid_data(format!("invalid path {name:?}, has null bytes")))
-            .unwrap();
+        let cname = CString::new(name).map_err(|_| {
+            Error::with_invalid_data(format!("invalid name {name:?}, has null bytes"))
+        })?;
 
         let obj_opts = libbpf_sys::bpf_object_open_opts {
             sz: size_of::<libbpf_sys::bpf_object_open_opts>() as libbpf_sys::size_t,
@@ -287,7 +483,7 @@ impl<'btf> Btf<'btf> {
     /// Gets a string at a given offset.
     ///
     /// Returns [`None`] when the offset is out of bounds or if the name is empty.
-    fn name_at(&self, offset: u32) -> Option<&OsStr> {
+    pub(crate) fn name_at(&self, offset: u32) -> Option<&OsStr> {
         let name = unsafe {
             // SAFETY:
             // Assuming that btf is a valid pointer, this is always okay to call.
@@ -301,6 +497,54 @@ impl<'btf> Btf<'btf> {
             .filter(|s| !s.is_empty()) // treat empty strings as none
     }
 
+    /// Load this BTF into the kernel.
+    ///
+    /// Once loaded, the BTF can be referenced by maps created at runtime,
+    /// e.g., by setting [`libbpf_sys::bpf_map_create_opts::btf_fd`] to
+    /// [`Btf::fd`] before invoking [`MapHandle::create`][crate::MapHandle::create].
+    pub fn load_into_kernel(&mut self) -> Result<()> {
+        let ret = unsafe { libbpf_sys::btf__load_into_kernel(self.ptr.as_ptr()) };
+        util::parse_ret(ret)
+    }
+
+    /// Deduplicate the types contained in this BTF, merging identical
+    /// definitions and resolving forward declarations where possible.
+    ///
+    /// Note that deduplication may renumber types, invalidating any
+    /// [`TypeId`]s retrieved before.
+    pub fn dedup(&mut self) -> Result<()> {
+        let ret = unsafe { libbpf_sys::btf__dedup(self.ptr.as_ptr(), ptr::null()) };
+        util::parse_ret(ret)
+    }
+
+    /// Serialize this BTF into its raw binary representation, suitable
+    /// for being loaded again using [`Btf::from_raw_bytes`].
+    pub fn to_raw(&self) -> Result<Vec<u8>> {
+        let mut size = 0;
+        let data = unsafe { libbpf_sys::btf__raw_data(self.ptr.as_ptr(), &mut size) };
+        if data.is_null() {
+            return Err(Error::from(io::Error::last_os_error()));
+        }
+
+        // SAFETY: libbpf guarantees that the returned pointer refers to
+        //         `size` bytes of valid data, owned by the `btf` object.
+        let data = unsafe { slice::from_raw_parts(data.cast::<u8>(), size as usize) };
+        Ok(data.to_vec())
+    }
+
+    /// Retrieve the file descriptor of this BTF, if it has been loaded
+    /// into the kernel.
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        let fd = unsafe { libbpf_sys::btf__fd(self.ptr.as_ptr()) };
+        if fd < 0 {
+            None
+        } else {
+            // SAFETY: The file descriptor is owned by the `btf` object and
+            //         valid for as long as it is alive.
+            Some(unsafe { BorrowedFd::borrow_raw(fd) })
+        }
+    }
+
     /// Whether this btf instance has no types.
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -324,27 +568,220 @@ impl<'btf> Btf<'btf> {
 
     /// Find a btf type by name
     ///
-    /// # Panics
-    /// If `name` has null bytes.
-    pub fn type_by_name<'s, K>(&'s self, name: &str) -> Option<K>
+    /// An error is returned if `name` is not a valid type name, e.g.,
+    /// because it contains null bytes.
+    pub fn type_by_name<'s, K>(&'s self, name: &str) -> Result<Option<K>>
     where
         K: TryFrom<BtfType<'s>>,
     {
         let c_string = CString::new(name)
-            .map_err(|_| Error::with_invalid_data(format!("{name:?} contains null bytes")))
-            .unwrap();
+            .map_err(|_| Error::with_invalid_data(format!("{name:?} contains null bytes")))?;
         let ty = unsafe {
             // SAFETY: the btf pointer is valid and the c_string pointer was created from safe code
             // therefore it's also valid.
             libbpf_sys::btf__find_by_name(self.ptr.as_ptr(), c_string.as_ptr())
         };
         if ty < 0 {
-            None
+            Ok(None)
         } else {
-            self.type_by_id(TypeId(ty as _))
+            Ok(self.type_by_id(TypeId(ty as _)))
         }
     }
 
+    /// Resolve the location of the member identified by `path` inside
+    /// the type `ty`.
+    ///
+    /// `ty` is the name of a struct or union, optionally qualified with
+    /// its kind (e.g., `"struct task_struct"`), or of a typedef referring
+    /// to one. `path` is a dot-separated list of member names (e.g.,
+    /// `"mm.start_stack"`). Members of anonymous nested structs and
+    /// unions are found as they would be in C.
+    ///
+    /// Offsets accumulate across nested structs and unions. When a member
+    /// along the path is a pointer, the remainder of the path is resolved
+    /// in the pointed-to type and the offset is relative to it.
+    pub fn field_offset(&self, ty: &str, path: &str) -> Result<FieldOffset> {
+        let (kind, name) = if let Some(name) = ty.strip_prefix("struct ") {
+            (Some(libbpf_sys::BTF_KIND_STRUCT), name)
+        } else if let Some(name) = ty.strip_prefix("union ") {
+            (Some(libbpf_sys::BTF_KIND_UNION), name)
+        } else {
+            (None, ty)
+        };
+
+        let cname = util::str_to_cstring(name.trim())?;
+        let id = match kind {
+            Some(kind) => unsafe {
+                libbpf_sys::btf__find_by_name_kind(self.ptr.as_ptr(), cname.as_ptr(), kind)
+            },
+            None => unsafe { libbpf_sys::btf__find_by_name(self.ptr.as_ptr(), cname.as_ptr()) },
+        };
+        if id < 0 {
+            return Err(Error::with_io_error(
+                io::ErrorKind::NotFound,
+                format!("type `{ty}` not found"),
+            ));
+        }
+
+        let mut current = self
+            .type_by_id::<BtfType<'_>>(TypeId(id as _))
+            .ok_or_else(|| Error::with_invalid_data(format!("type `{ty}` not found")))?;
+        let mut location = FieldOffset {
+            bit_offset: 0,
+            bitfield_size: None,
+            ty: current.type_id(),
+        };
+
+        for component in path.split('.') {
+            let mut resolved = current.skip_mods_and_typedefs();
+            if resolved.kind() == BtfKind::Ptr {
+                // SANITY: Pointers always reference a type.
+                resolved = resolved.next_type().unwrap().skip_mods_and_typedefs();
+                location.bit_offset = 0;
+            }
+
+            let composite = Composite::try_from(resolved).map_err(|_| {
+                Error::with_invalid_data(format!(
+                    "cannot access member `{component}` of non-struct/union type"
+                ))
+            })?;
+            let (bit_offset, bitfield_size, member_ty) = find_member(self, &composite, component)
+                .ok_or_else(|| {
+                Error::with_io_error(
+                    io::ErrorKind::NotFound,
+                    format!("member `{component}` of `{path}` not found in type `{ty}`"),
+                )
+            })?;
+
+            location.bit_offset += bit_offset;
+            location.bitfield_size = bitfield_size;
+            location.ty = member_ty;
+            current = self.type_by_id::<BtfType<'_>>(member_ty).ok_or_else(|| {
+                Error::with_invalid_data(format!("type of member `{component}` not found"))
+            })?;
+        }
+
+        Ok(location)
+    }
+
+    /// Render `data` as a human readable value according to the type with
+    /// the given [`TypeId`].
+    ///
+    /// This is useful for displaying the raw contents of maps or ring
+    /// buffer samples, without having to provide Rust definitions of the
+    /// corresponding types.
+    ///
+    /// An error is reported if `data` is smaller than the type.
+    pub fn dump_data(&self, type_id: TypeId, data: &[u8], opts: &DumpDataOpts) -> Result<String> {
+        let DumpDataOpts {
+            compact,
+            indent_str,
+            indent_level,
+            skip_names,
+            emit_zeroes,
+            hex,
+            _non_exhaustive,
+        } = opts;
+
+        let indent_str = indent_str
+            .as_deref()
+            .map(util::str_to_cstring)
+            .transpose()?;
+        let dump_opts = libbpf_sys::btf_dump_type_data_opts {
+            sz: size_of::<libbpf_sys::btf_dump_type_data_opts>() as _,
+            indent_str: indent_str
+                .as_ref()
+                .map(|indent| indent.as_ptr())
+                .unwrap_or(ptr::null()),
+            indent_level: *indent_level as _,
+            compact: *compact,
+            skip_names: *skip_names,
+            emit_zeroes: *emit_zeroes,
+            ..Default::default()
+        };
+
+        let mut ctx = DumpCtx {
+            hex: *hex,
+            output: Vec::new(),
+            error: None,
+        };
+
+        // SAFETY: `dump_printf_cb` has the same signature as
+        //         `btf_dump_printf_fn_t`, except for using a void pointer
+        //         for the `va_list` (see `print.rs` for details).
+        #[allow(clippy::missing_transmute_annotations)]
+        let printf_fn: libbpf_sys::btf_dump_printf_fn_t =
+            unsafe { Some(mem::transmute(dump_printf_cb as *const ())) };
+        let dump = create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::btf_dump__new(
+                self.ptr.as_ptr(),
+                printf_fn,
+                ptr::addr_of_mut!(ctx).cast(),
+                ptr::null(),
+            )
+        })?;
+
+        // SAFETY: All pointers are valid and `data` is accessed only within
+        //         its bounds.
+        let ret = unsafe {
+            libbpf_sys::btf_dump__dump_type_data(
+                dump.as_ptr(),
+                type_id.into(),
+                data.as_ptr().cast(),
+                data.len() as _,
+                &dump_opts,
+            )
+        };
+        // SAFETY: `dump` is valid and not used afterwards.
+        let () = unsafe { libbpf_sys::btf_dump__free(dump.as_ptr()) };
+        let _count = parse_ret_i32(ret)?;
+
+        if let Some(err) = ctx.error {
+            return Err(Error::from(err));
+        }
+        Ok(String::from_utf8_lossy(&ctx.output).into_owned())
+    }
+
+    /// Render all types contained in this BTF as C declarations, in a
+    /// form suitable for inclusion in a header file such as `vmlinux.h`.
+    pub fn dump_c(&self) -> Result<String> {
+        let mut ctx = DumpCtx {
+            hex: false,
+            output: Vec::new(),
+            error: None,
+        };
+
+        // SAFETY: `dump_printf_cb` has the same signature as
+        //         `btf_dump_printf_fn_t`, except for using a void pointer
+        //         for the `va_list` (see `print.rs` for details).
+        #[allow(clippy::missing_transmute_annotations)]
+        let printf_fn: libbpf_sys::btf_dump_printf_fn_t =
+            unsafe { Some(mem::transmute(dump_printf_cb as *const ())) };
+        let dump = create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::btf_dump__new(
+                self.ptr.as_ptr(),
+                printf_fn,
+                ptr::addr_of_mut!(ctx).cast(),
+                ptr::null(),
+            )
+        })?;
+
+        // Type ID 0 is `void`, which is implicit.
+        let result = (1..self.len() as u32).try_for_each(|id| {
+            // SAFETY: `dump` is valid and `id` refers to an existing type.
+            let ret = unsafe { libbpf_sys::btf_dump__dump_type(dump.as_ptr(), id) };
+            util::parse_ret(ret)
+        });
+        // SAFETY: `dump` is valid and not used afterwards.
+        let () = unsafe { libbpf_sys::btf_dump__free(dump.as_ptr()) };
+        let () = result?;
+
+        if let Some(err) = ctx.error {
+            return Err(Error::from(err));
+        }
+        Ok(String::from_utf8_lossy(&ctx.output).into_owned())
+    }
+
     /// Find a type by it's [TypeId].
     pub fn type_by_id<'s, K>(&'s self, type_id: TypeId) -> Option<K>
     where
@@ -384,6 +821,121 @@ impl<'btf> Btf<'btf> {
             .filter_map(|id| self.type_by_id(id))
             .filter_map(|t| K::try_from(t).ok())
     }
+
+    /// Resolve `type_id` to the type it ultimately refers to, skipping
+    /// modifiers and typedefs.
+    fn resolve_type_id(&self, type_id: TypeId) -> Option<TypeId> {
+        self.type_by_id::<BtfType<'_>>(type_id)
+            .map(|ty| ty.skip_mods_and_typedefs().type_id())
+    }
+
+    /// Find all structs and unions that have a member of type `ty`.
+    ///
+    /// Types are compared after skipping modifiers and typedefs, i.e., a
+    /// member of type `const pid_t` is found when looking for `int`.
+    /// Members of anonymous nested structs and unions are considered as
+    /// well.
+    pub fn composites_with_member_type<'s>(
+        &'s self,
+        ty: TypeId,
+    ) -> impl Iterator<Item = Composite<'s>> + 's {
+        let target = self.resolve_type_id(ty);
+        self.type_by_kind::<Composite<'s>>()
+            .filter(move |composite| {
+                target.is_some_and(|target| has_member_type(self, composite, target))
+            })
+    }
+
+    /// Find all functions whose prototype returns `ret` and accepts
+    /// parameters of the types `params`, in order.
+    ///
+    /// Types are compared after skipping modifiers and typedefs. Use
+    /// [`TypeId`] `0` for a `void` return type.
+    pub fn funcs_with_signature<'s>(
+        &'s self,
+        ret: TypeId,
+        params: &[TypeId],
+    ) -> impl Iterator<Item = types::Func<'s>> + 's {
+        let ret = self.resolve_type_id(ret);
+        let params = params
+            .iter()
+            .map(|param| self.resolve_type_id(*param))
+            .collect::<Vec<_>>();
+
+        self.type_by_kind::<types::Func<'s>>().filter(move |func| {
+            let Ok(proto) = types::FuncProto::try_from(func.referenced_type()) else {
+                return false;
+            };
+            ret.is_some()
+                && self.resolve_type_id(proto.referenced_type_id()) == ret
+                && proto.len() == params.len()
+                && (0..proto.len())
+                    .filter_map(|idx| proto.get(idx))
+                    .zip(&params)
+                    .all(|(param, ty)| self.resolve_type_id(param.ty) == *ty)
+        })
+    }
+
+    /// Find all declaration tags attached to the type `ty`.
+    ///
+    /// This includes tags attached to individual members or parameters of
+    /// the type, as indicated by [`DeclTag::component_index`][types::DeclTag::component_index].
+    /// The tag's value is available as its name.
+    pub fn decl_tags<'s>(&'s self, ty: TypeId) -> impl Iterator<Item = types::DeclTag<'s>> + 's {
+        self.type_by_kind::<types::DeclTag<'s>>()
+            .filter(move |tag| tag.referenced_type_id() == ty)
+    }
+}
+
+/// Check whether `composite` has a member of the (resolved) type
+/// `target`, descending into anonymous members.
+fn has_member_type(btf: &Btf<'_>, composite: &Composite<'_>, target: TypeId) -> bool {
+    (0..composite.len())
+        .filter_map(|idx| composite.get(idx))
+        .any(|member| {
+            let Some(ty) = btf.type_by_id::<BtfType<'_>>(member.ty) else {
+                return false;
+            };
+            let ty = ty.skip_mods_and_typedefs();
+            if ty.type_id() == target {
+                return true;
+            }
+            member.name.is_none()
+                && Composite::try_from(ty)
+                    .map(|inner| has_member_type(btf, &inner, target))
+                    .unwrap_or(false)
+        })
+}
+
+/// Find the member `name` in `composite`, descending into anonymous
+/// members, and report its bit offset, bitfield size, and type.
+fn find_member(
+    btf: &Btf<'_>,
+    composite: &Composite<'_>,
+    name: &str,
+) -> Option<(u32, Option<u8>, TypeId)> {
+    let location = |attr: &types::MemberAttr| match *attr {
+        types::MemberAttr::Normal { offset } => (offset, None),
+        types::MemberAttr::BitField { size, offset } => (offset, Some(size)),
+    };
+
+    if let Some(member) = composite.member_by_name(name) {
+        let (offset, size) = location(&member.attr);
+        return Some((offset, size, member.ty));
+    }
+
+    (0..composite.len())
+        .filter_map(|idx| composite.get(idx))
+        .filter(|member| member.name.is_none())
+        .find_map(|member| {
+            let (offset, _size) = location(&member.attr);
+            let ty = btf
+                .type_by_id::<BtfType<'_>>(member.ty)?
+                .skip_mods_and_typedefs();
+            let inner = Composite::try_from(ty).ok()?;
+            let (inner_offset, size, ty) = find_member(btf, &inner, name)?;
+            Some((offset + inner_offset, size, ty))
+        })
 }
 
 impl AsRawLibbpf for Btf<'_> {
@@ -436,6 +988,14 @@ impl Drop for Btf<'_> {
                     libbpf_sys::bpf_object__close(obj)
                 }
             }
+            DropPolicy::WithBase(base) => {
+                unsafe {
+                    // SAFETY: both btf pointers are valid and the split btf
+                    //         is freed before the base it references.
+                    libbpf_sys::btf__free(self.ptr.as_ptr());
+                    libbpf_sys::btf__free(base.as_ptr())
+                }
+            }
         }
     }
 }
@@ -736,11 +1296,287 @@ mod tests {
 
     use std::mem::discriminant;
 
+    use self::types::IntEncoding;
+
     #[test]
     fn from_vmlinux() {
         assert!(Btf::from_vmlinux().is_ok());
     }
 
+    /// Check that looking up a type by an invalid name results in an
+    /// error instead of a panic.
+    #[test]
+    fn type_by_name_invalid() {
+        let btf = Btf::from_vmlinux().unwrap();
+        let ty = btf
+            .type_by_name::<types::Struct<'_>>("task_struct")
+            .unwrap();
+        assert!(ty.is_some());
+
+        let err = btf
+            .type_by_name::<types::Struct<'_>>("task\0struct")
+            .unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidData);
+    }
+
+    /// Check that we rewrite decimal conversions into hexadecimal ones.
+    #[test]
+    fn format_hexification() {
+        let hexify = |fmt: &str| {
+            let fmt = CString::new(fmt).unwrap();
+            hexify_format(&fmt).into_string().unwrap()
+        };
+
+        assert_eq!(hexify("%d"), "%#x");
+        assert_eq!(hexify("%s%lld, %hhu"), "%s%#llx, %#hhx");
+        assert_eq!(hexify("100%% %-5i"), "100%% %#-5x");
+        assert_eq!(hexify("%#x %c"), "%#x %c");
+    }
+
+    /// Check that we can render data according to a BTF type.
+    #[test]
+    fn data_dumping() {
+        let btf = Btf::from_vmlinux().unwrap();
+        let int = btf.type_by_name::<types::Int<'_>>("int").unwrap().unwrap();
+        let data = 42i32.to_ne_bytes();
+
+        let opts = DumpDataOpts::default();
+        let dump = btf.dump_data(int.type_id(), &data, &opts).unwrap();
+        assert_eq!(dump, "(int)42");
+
+        let opts = DumpDataOpts {
+            hex: true,
+            ..Default::default()
+        };
+        let dump = btf.dump_data(int.type_id(), &data, &opts).unwrap();
+        assert_eq!(dump, "(int)0x2a");
+
+        let opts = DumpDataOpts {
+            skip_names: true,
+            ..Default::default()
+        };
+        let dump = btf.dump_data(int.type_id(), &data, &opts).unwrap();
+        assert_eq!(dump, "42");
+
+        let _err = btf.dump_data(int.type_id(), &data[..2], &opts).unwrap_err();
+    }
+
+    /// Check that we can construct BTF from scratch and introspect it
+    /// afterwards.
+    #[test]
+    fn btf_building() {
+        let mut builder = BtfBuilder::new().unwrap();
+        let int = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let point = builder.add_struct(Some("point"), 8).unwrap();
+        let () = builder.add_field(Some("x"), int, 0, 0).unwrap();
+        let () = builder.add_field(Some("y"), int, 32, 0).unwrap();
+        let _ptr = builder.add_ptr(point).unwrap();
+        let _err = builder
+            .add_int("bad\0name", 4, IntEncoding::None)
+            .unwrap_err();
+        let btf = builder.build();
+
+        assert_eq!(btf.len(), 4);
+        let ty = btf
+            .type_by_name::<types::Struct<'_>>("point")
+            .unwrap()
+            .unwrap();
+        assert_eq!(ty.type_id(), point);
+        assert_eq!(ty.size(), 8);
+        let names = ty
+            .iter()
+            .map(|member| member.name.unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x", "y"]);
+
+        let data = [1, 0, 0, 0, 2, 0, 0, 0];
+        let opts = DumpDataOpts {
+            compact: true,
+            ..Default::default()
+        };
+        let dump = btf.dump_data(point, &data, &opts).unwrap();
+        assert_eq!(dump, "(struct point){.x = (int)1,.y = (int)2,}");
+    }
+
+    /// Check that we can render BTF as C declarations.
+    #[test]
+    fn c_dumping() {
+        let mut builder = BtfBuilder::new().unwrap();
+        let int = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let point = builder.add_struct(Some("point"), 8).unwrap();
+        let () = builder.add_field(Some("x"), int, 0, 0).unwrap();
+        let () = builder.add_field(Some("y"), int, 32, 0).unwrap();
+        let _typedef = builder.add_typedef("point_t", point).unwrap();
+        let btf = builder.build();
+
+        let dump = btf.dump_c().unwrap();
+        assert!(dump.contains("struct point {"), "{dump}");
+        assert!(dump.contains("\tint x;"), "{dump}");
+        assert!(dump.contains("typedef struct point point_t;"), "{dump}");
+    }
+
+    /// Check that loading split btf for a non-existent kernel module or
+    /// path fails gracefully.
+    #[test]
+    fn split_btf_loading_failure() {
+        let _err = Btf::from_module("does-not-exist").unwrap_err();
+
+        let base = Btf::from_vmlinux().unwrap();
+        let _err = Btf::from_path_with_base("/does/not/exist", &base).unwrap_err();
+    }
+
+    /// Check that we can resolve the offsets of nested members.
+    #[test]
+    fn field_offset_resolution() {
+        let mut builder = BtfBuilder::new().unwrap();
+        let int = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let inner = builder.add_struct(Some("inner"), 8).unwrap();
+        let () = builder.add_field(Some("a"), int, 0, 0).unwrap();
+        let () = builder.add_field(Some("b"), int, 32, 3).unwrap();
+        let inner_ptr = builder.add_ptr(inner).unwrap();
+        let anon = builder.add_union(None, 4).unwrap();
+        let () = builder.add_field(Some("c"), int, 0, 0).unwrap();
+        let outer = builder.add_struct(Some("outer"), 24).unwrap();
+        let () = builder.add_field(Some("x"), int, 0, 0).unwrap();
+        let () = builder.add_field(None, anon, 32, 0).unwrap();
+        let () = builder.add_field(Some("nested"), inner, 64, 0).unwrap();
+        let () = builder.add_field(Some("ptr"), inner_ptr, 128, 0).unwrap();
+        let typedef = builder.add_typedef("outer_t", outer).unwrap();
+        let btf = builder.build();
+
+        let composite = btf.type_by_id::<types::Composite<'_>>(outer).unwrap();
+        let member = composite.member_by_name("nested").unwrap();
+        assert_eq!(member.ty, inner);
+        assert!(composite.member_by_name("c").is_none());
+
+        let offset = btf.field_offset("struct outer", "x").unwrap();
+        assert_eq!(
+            offset,
+            FieldOffset {
+                bit_offset: 0,
+                bitfield_size: None,
+                ty: int
+            }
+        );
+        let offset = btf.field_offset("outer", "c").unwrap();
+        assert_eq!(offset.bit_offset, 32);
+        let offset = btf.field_offset("struct outer", "nested.b").unwrap();
+        assert_eq!(offset.bit_offset, 96);
+        assert_eq!(offset.bitfield_size, Some(3));
+        let offset = btf.field_offset("outer_t", "ptr.b").unwrap();
+        assert_eq!(offset.bit_offset, 32);
+        let offset = btf.field_offset("struct outer", "ptr").unwrap();
+        assert_eq!(offset.ty, inner_ptr);
+        let offset = btf.field_offset("outer_t", "nested").unwrap();
+        assert_eq!(offset.ty, inner);
+        assert_ne!(offset.ty, typedef);
+
+        let _err = btf.field_offset("union outer", "x").unwrap_err();
+        let _err = btf.field_offset("struct outer", "y").unwrap_err();
+        let _err = btf.field_offset("struct outer", "x.y").unwrap_err();
+
+        let btf = Btf::from_vmlinux().unwrap();
+        let offset = btf
+            .field_offset("struct task_struct", "mm.start_stack")
+            .unwrap();
+        assert_eq!(offset.bit_offset % 8, 0);
+    }
+
+    /// Check that we can deduplicate BTF and round trip it through its
+    /// raw representation.
+    #[test]
+    fn btf_dedup_and_raw_round_trip() {
+        let mut builder = BtfBuilder::new().unwrap();
+        let int1 = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let _ptr1 = builder.add_ptr(int1).unwrap();
+        let int2 = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let _ptr2 = builder.add_ptr(int2).unwrap();
+        let mut btf = builder.build();
+        assert_eq!(btf.len(), 5);
+
+        let () = btf.dedup().unwrap();
+        assert_eq!(btf.len(), 3);
+
+        let raw = btf.to_raw().unwrap();
+        let copy = Btf::from_raw_bytes(&raw).unwrap();
+        assert_eq!(copy.len(), btf.len());
+        assert_eq!(copy.to_raw().unwrap(), raw);
+        assert!(copy
+            .type_by_name::<types::Int<'_>>("int")
+            .unwrap()
+            .is_some());
+
+        let _err = Btf::from_raw_bytes(&raw[..raw.len() / 2]).unwrap_err();
+    }
+
+    /// Check that we can query types by their relationships.
+    #[test]
+    fn type_graph_queries() {
+        let mut builder = BtfBuilder::new().unwrap();
+        let int = builder.add_int("int", 4, IntEncoding::Signed).unwrap();
+        let long = builder.add_int("long", 8, IntEncoding::Signed).unwrap();
+        let pid_t = builder.add_typedef("pid_t", int).unwrap();
+        let const_pid_t = builder.add_const(pid_t).unwrap();
+        let anon = builder.add_union(None, 8).unwrap();
+        let () = builder.add_field(Some("l"), long, 0, 0).unwrap();
+        let task = builder.add_struct(Some("task"), 16).unwrap();
+        let () = builder.add_field(Some("pid"), const_pid_t, 0, 0).unwrap();
+        let () = builder.add_field(None, anon, 64, 0).unwrap();
+        let pair = builder.add_struct(Some("pair"), 16).unwrap();
+        let () = builder.add_field(Some("a"), long, 0, 0).unwrap();
+        let () = builder.add_field(Some("b"), long, 64, 0).unwrap();
+        let proto1 = builder.add_func_proto(int).unwrap();
+        let () = builder.add_func_param(Some("pid"), pid_t).unwrap();
+        let () = builder.add_func_param(Some("l"), long).unwrap();
+        let func1 = builder
+            .add_func("func1", types::Linkage::Global, proto1)
+            .unwrap();
+        let proto2 = builder.add_func_proto(TypeId::from(0)).unwrap();
+        let () = builder.add_func_param(None, int).unwrap();
+        let func2 = builder
+            .add_func("func2", types::Linkage::Static, proto2)
+            .unwrap();
+        let tag1 = builder.add_decl_tag("tag1", task, None).unwrap();
+        let tag2 = builder.add_decl_tag("tag2", task, Some(0)).unwrap();
+        let _tag3 = builder.add_decl_tag("tag3", func1, Some(1)).unwrap();
+        let btf = builder.build();
+
+        let found = btf
+            .composites_with_member_type(pid_t)
+            .map(|composite| composite.type_id())
+            .collect::<Vec<_>>();
+        assert_eq!(found, [task]);
+        let found = btf
+            .composites_with_member_type(long)
+            .map(|composite| composite.type_id())
+            .collect::<Vec<_>>();
+        assert_eq!(found, [anon, task, pair]);
+        assert_eq!(btf.composites_with_member_type(task).count(), 0);
+
+        let found = btf
+            .funcs_with_signature(int, &[int, long])
+            .map(|func| func.type_id())
+            .collect::<Vec<_>>();
+        assert_eq!(found, [func1]);
+        let found = btf
+            .funcs_with_signature(TypeId::from(0), &[const_pid_t])
+            .map(|func| func.type_id())
+            .collect::<Vec<_>>();
+        assert_eq!(found, [func2]);
+        assert_eq!(btf.funcs_with_signature(int, &[int]).count(), 0);
+        assert_eq!(btf.funcs_with_signature(int, &[long, int]).count(), 0);
+
+        let tags = btf.decl_tags(task).collect::<Vec<_>>();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].type_id(), tag1);
+        assert_eq!(tags[0].name(), Some(OsStr::new("tag1")));
+        assert_eq!(tags[0].component_index(), None);
+        assert_eq!(tags[1].type_id(), tag2);
+        assert_eq!(tags[1].component_index(), Some(0));
+        assert_eq!(btf.decl_tags(func1).count(), 1);
+        assert_eq!(btf.decl_tags(pair).count(), 0);
+    }
+
     #[test]
     fn btf_kind() {
         use BtfKind::*;
@@ -0,0 +1,152 @@
+//! `serde` support for btf types.
+
+use std::ffi::OsStr;
+
+use serde::ser::SerializeStruct as _;
+use serde::Serialize;
+use serde::Serializer;
+
+use super::types;
+use super::BtfKind;
+use super::BtfType;
+use super::TypeId;
+
+/// A member of a composite type, in serializable form.
+#[derive(Serialize)]
+struct Member {
+    name: Option<String>,
+    ty: TypeId,
+    attr: types::MemberAttr,
+}
+
+/// A variant of an enum type, in serializable form.
+#[derive(Serialize)]
+struct Variant<T> {
+    name: Option<String>,
+    value: T,
+}
+
+fn lossy(name: Option<&OsStr>) -> Option<String> {
+    name.map(|name| name.to_string_lossy().into_owned())
+}
+
+impl Serialize for BtfType<'_> {
+    /// Serialize the type's ID, kind, and name, along with kind specific
+    /// attributes such as its size, the type it references, or its
+    /// members.
+    ///
+    /// Referenced types are represented by their [`TypeId`] only.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("BtfType", 6)?;
+        let () = s.serialize_field("type_id", &self.type_id())?;
+        let () = s.serialize_field("kind", &self.kind())?;
+        let () = s.serialize_field("name", &lossy(self.name()))?;
+
+        match self.kind() {
+            BtfKind::Int
+            | BtfKind::Float
+            | BtfKind::DataSec
+            | BtfKind::Struct
+            | BtfKind::Union
+            | BtfKind::Enum
+            | BtfKind::Enum64 => {
+                // SAFETY: The type is of a kind that has a size.
+                let () = s.serialize_field("size", &unsafe { self.size_unchecked() })?;
+            }
+            BtfKind::Ptr
+            | BtfKind::Typedef
+            | BtfKind::Volatile
+            | BtfKind::Const
+            | BtfKind::Restrict
+            | BtfKind::Func
+            | BtfKind::FuncProto
+            | BtfKind::Var
+            | BtfKind::DeclTag
+            | BtfKind::TypeTag => {
+                // SAFETY: The type is of a kind that references another
+                //         type.
+                let ty = unsafe { self.referenced_type_id_unchecked() };
+                let () = s.serialize_field("referenced_type_id", &ty)?;
+            }
+            BtfKind::Void | BtfKind::Array | BtfKind::Fwd => (),
+        }
+
+        if let Ok(int) = types::Int::try_from(*self) {
+            let () = s.serialize_field("encoding", &int.encoding)?;
+            let () = s.serialize_field("bits", &int.bits)?;
+            let () = s.serialize_field("offset", &int.offset)?;
+        } else if let Ok(array) = types::Array::try_from(*self) {
+            let () = s.serialize_field("ty", &array.ty())?;
+            let () = s.serialize_field("index_ty", &array.index_ty())?;
+            let () = s.serialize_field("capacity", &array.capacity())?;
+        } else if let Ok(fwd) = types::Fwd::try_from(*self) {
+            let () = s.serialize_field("fwd_kind", &fwd.kind())?;
+        } else if let Ok(composite) = types::Composite::try_from(*self) {
+            let members = (0..composite.len())
+                .filter_map(|idx| composite.get(idx))
+                .map(|member| Member {
+                    name: lossy(member.name),
+                    ty: member.ty,
+                    attr: member.attr,
+                })
+                .collect::<Vec<_>>();
+            let () = s.serialize_field("members", &members)?;
+        } else if let Ok(enum_) = types::Enum::try_from(*self) {
+            let variants = (0..enum_.len())
+                .filter_map(|idx| enum_.get(idx))
+                .map(|variant| Variant {
+                    name: lossy(variant.name),
+                    value: variant.value,
+                })
+                .collect::<Vec<_>>();
+            let () = s.serialize_field("variants", &variants)?;
+        } else if let Ok(enum64) = types::Enum64::try_from(*self) {
+            let variants = (0..enum64.len())
+                .filter_map(|idx| enum64.get(idx))
+                .map(|variant| Variant {
+                    name: lossy(variant.name),
+                    value: variant.value,
+                })
+                .collect::<Vec<_>>();
+            let () = s.serialize_field("variants", &variants)?;
+        }
+
+        s.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util;
+    use crate::Btf;
+
+    /// Check that we can serialize btf types of various kinds.
+    #[test]
+    fn btf_type_serialization() {
+        let path = util::test_object_path("runqslower.bpf.o");
+        let btf = Btf::from_path(path).unwrap();
+
+        let event = btf.type_by_name::<BtfType<'_>>("event").unwrap();
+        let json = serde_json::to_value(event).unwrap();
+        assert_eq!(json["kind"], "Struct");
+        assert_eq!(json["name"], "event");
+        assert!(json["size"].as_u64().unwrap() > 0);
+
+        let members = json["members"].as_array().unwrap();
+        assert!(!members.is_empty());
+        assert!(members.iter().any(|member| member["name"] == "pid"));
+        assert!(members
+            .iter()
+            .all(|member| member["attr"]["Normal"]["offset"].is_u64()));
+
+        let id = members[0]["ty"].as_u64().unwrap() as u32;
+        let ty = btf.type_by_id::<BtfType<'_>>(TypeId::from(id)).unwrap();
+        let json = serde_json::to_value(ty).unwrap();
+        assert_eq!(json["type_id"], id);
+    }
+}
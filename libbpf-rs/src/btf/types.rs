@@ -239,6 +239,7 @@ macro_rules! gen_collection_concrete_type {
 
 /// The attributes of a member.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MemberAttr {
     /// Member is a normal field.
     Normal {
@@ -276,6 +277,7 @@ impl MemberAttr {
 /// The kind of linkage a variable of function can have.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Linkage {
     /// Static linkage
     Static = 0,
@@ -345,7 +347,8 @@ pub struct Int<'btf> {
 }
 
 /// The kinds of ways a btf [Int] can be encoded.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum IntEncoding {
     /// No encoding.
     None,
@@ -579,6 +582,19 @@ impl<'btf> TryFrom<Composite<'btf>> for Union<'btf> {
     }
 }
 
+impl<'btf> Composite<'btf> {
+    /// Look up a member of this composite type by name.
+    ///
+    /// Only direct members are considered, i.e., members of nested
+    /// anonymous structs or unions are not found.
+    pub fn member_by_name(&self, name: &str) -> Option<CompositeMember<'btf>> {
+        self.members
+            .iter()
+            .map(|member| self.c_to_rust_member(member))
+            .find(|member| member.name == Some(OsStr::new(name)))
+    }
+}
+
 // Composite
 gen_collection_members_concrete_type! {
     btf_member as Composite with HasSize;
@@ -642,6 +658,7 @@ impl Fwd<'_> {
 
 /// The kinds of types that can be forward declared.
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum FwdKind {
     /// A struct.
     Struct,
@@ -825,6 +842,22 @@ gen_collection_concrete_type! {
     }
 }
 
+impl Enum<'_> {
+    /// Whether the enum's values are signed.
+    #[inline]
+    pub fn is_signed(&self) -> bool {
+        self.source.kind_flag()
+    }
+}
+
+impl Enum64<'_> {
+    /// Whether the enum's values are signed.
+    #[inline]
+    pub fn is_signed(&self) -> bool {
+        self.source.kind_flag()
+    }
+}
+
 /// A macro that allows matching on the type of a [`BtfType`] as if it was an enum.
 ///
 /// Each pattern can be of two types.
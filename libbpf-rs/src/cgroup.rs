@@ -0,0 +1,123 @@
+//! Helpers for working with cgroups as attach targets.
+//!
+//! For example, to attach a program to every direct child of the
+//! cgroup v2 hierarchy's root:
+//! ```no_run
+//! # use libbpf_rs::Program;
+//! use libbpf_rs::cgroup;
+//!
+//! # fn attach(prog: &mut Program) -> libbpf_rs::Result<()> {
+//! let mut links = Vec::new();
+//! for child in cgroup::children(cgroup::CGROUP_ROOT)? {
+//!     let link = prog.attach_cgroup_path(child?)?;
+//!     let () = links.push(link);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::fs::ReadDir;
+use std::os::unix::fs::MetadataExt as _;
+use std::os::unix::fs::OpenOptionsExt as _;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Result;
+
+/// The default mount point of the cgroup v2 hierarchy.
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Open the cgroup at `path`, yielding a file descriptor suitable for
+/// attaching programs to it.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<OwnedFd> {
+    let path = path.as_ref();
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECTORY)
+        .open(path)
+        .with_context(|| format!("failed to open cgroup `{}`", path.display()))?;
+    Ok(OwnedFd::from(file))
+}
+
+/// Retrieve the ID of the cgroup at `path`, as reported by, e.g.,
+/// `bpf_get_current_cgroup_id`.
+pub fn id<P: AsRef<Path>>(path: P) -> Result<u64> {
+    let path = path.as_ref();
+    let metadata = fs::metadata(path)
+        .with_context(|| format!("failed to stat cgroup `{}`", path.display()))?;
+    if !metadata.is_dir() {
+        return Err(Error::with_invalid_data(format!(
+            "`{}` is not a cgroup",
+            path.display()
+        )));
+    }
+    // On cgroup v2 the inode number of a cgroup directory is its ID.
+    Ok(metadata.ino())
+}
+
+/// Iterate over the direct child cgroups of the cgroup at `path`.
+pub fn children<P: AsRef<Path>>(path: P) -> Result<Children> {
+    let path = path.as_ref();
+    let read_dir = fs::read_dir(path)
+        .with_context(|| format!("failed to read cgroup `{}`", path.display()))?;
+    Ok(Children { read_dir })
+}
+
+/// An iterator over the paths of the child cgroups of a cgroup, as
+/// created by [`children`].
+#[derive(Debug)]
+pub struct Children {
+    read_dir: ReadDir,
+}
+
+impl Iterator for Children {
+    type Item = Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.read_dir.next()? {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(Error::from(err))),
+            };
+
+            match entry.file_type() {
+                // Child cgroups are represented as directories, while
+                // control files are regular files.
+                Ok(ty) if ty.is_dir() => return Some(Ok(entry.path())),
+                Ok(_) => continue,
+                Err(err) => return Some(Err(Error::from(err))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    /// Check that only directories are reported as child cgroups.
+    #[test]
+    fn child_enumeration() {
+        let dir = tempdir().unwrap();
+        let () = fs::create_dir(dir.path().join("child")).unwrap();
+        let () = fs::write(dir.path().join("cgroup.procs"), b"").unwrap();
+
+        let children = children(dir.path())
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(children, vec![dir.path().join("child")]);
+
+        let _fd = open(&children[0]).unwrap();
+        assert!(id(&children[0]).is_ok());
+        assert!(open(dir.path().join("cgroup.procs")).is_err());
+        assert!(id(dir.path().join("cgroup.procs")).is_err());
+    }
+}
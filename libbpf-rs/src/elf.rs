@@ -0,0 +1,260 @@
+//! Minimal parsing support for 64 bit ELF files.
+
+use crate::Error;
+use crate::Result;
+
+/// Section type of symbol tables.
+const SHT_SYMTAB: u32 = 2;
+/// Section type of sections without file contents.
+const SHT_NOBITS: u32 = 8;
+/// Section type of dynamic symbol tables.
+const SHT_DYNSYM: u32 = 11;
+/// Symbol type of functions.
+const STT_FUNC: u8 = 2;
+/// The size of an `Elf64_Sym`.
+const SYM_SIZE: usize = 24;
+
+/// Segment type of loadable segments.
+pub(crate) const PT_LOAD: u32 = 1;
+
+/// Read a `u16` from `data` at `off`.
+pub(crate) fn read_u16(data: &[u8], off: usize, be: bool) -> Result<u16> {
+    let bytes = data
+        .get(off..off + 2)
+        .ok_or_else(|| Error::with_invalid_data("unexpected end of data"))?;
+    let bytes = [bytes[0], bytes[1]];
+    Ok(if be {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+/// Read a `u32` from `data` at `off`.
+pub(crate) fn read_u32(data: &[u8], off: usize, be: bool) -> Result<u32> {
+    let bytes = data
+        .get(off..off + 4)
+        .ok_or_else(|| Error::with_invalid_data("unexpected end of data"))?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Ok(if be {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+/// Read a `u64` from `data` at `off`.
+pub(crate) fn read_u64(data: &[u8], off: usize, be: bool) -> Result<u64> {
+    let lo = u64::from(read_u32(data, off, be)?);
+    let hi = u64::from(read_u32(data, off + 4, be)?);
+    Ok(if be { (lo << 32) | hi } else { (hi << 32) | lo })
+}
+
+/// Read the NUL terminated string at `off` in string table `strtab`.
+fn read_str(strtab: &[u8], off: usize) -> &[u8] {
+    strtab
+        .get(off..)
+        .and_then(|s| s.split(|b| *b == 0).next())
+        .unwrap_or_default()
+}
+
+/// A section of an ELF file.
+#[derive(Debug)]
+pub(crate) struct Section<'dat> {
+    /// The section's name.
+    pub name: &'dat [u8],
+    /// The section type.
+    pub type_: u32,
+    /// The index of the associated section, e.g., the string table
+    /// used by a symbol table.
+    pub link: u32,
+    /// The section's contents.
+    pub data: &'dat [u8],
+}
+
+/// A segment of an ELF file, as described by a program header.
+#[derive(Debug)]
+pub(crate) struct Segment {
+    /// The segment type.
+    pub type_: u32,
+    /// The offset of the segment in the file.
+    pub offset: u64,
+    /// The virtual address the segment is loaded at.
+    pub vaddr: u64,
+    /// The size of the segment in the file.
+    pub filesz: u64,
+}
+
+/// A function symbol.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FuncSymbol {
+    /// The symbol's name.
+    pub name: String,
+    /// The symbol's virtual address.
+    pub addr: u64,
+    /// The symbol's size.
+    pub size: u64,
+}
+
+/// A parsed 64 bit ELF file.
+#[derive(Debug)]
+pub(crate) struct Elf<'dat> {
+    data: &'dat [u8],
+    be: bool,
+}
+
+impl<'dat> Elf<'dat> {
+    /// Parse the 64 bit ELF file contained in `data`.
+    pub fn parse(data: &'dat [u8]) -> Result<Self> {
+        if data.get(0..4) != Some(b"\x7fELF") || data.get(4) != Some(&2) {
+            return Err(Error::with_invalid_data("not a 64 bit ELF file"));
+        }
+
+        let be = data.get(5) == Some(&2);
+        Ok(Self { data, be })
+    }
+
+    /// Check whether the file is big endian.
+    pub fn is_big_endian(&self) -> bool {
+        self.be
+    }
+
+    /// Retrieve all sections of the file.
+    pub fn sections(&self) -> Result<Vec<Section<'dat>>> {
+        let (data, be) = (self.data, self.be);
+        let shoff = read_u64(data, 0x28, be)? as usize;
+        let shentsize = usize::from(read_u16(data, 0x3a, be)?);
+        let shnum = usize::from(read_u16(data, 0x3c, be)?);
+        let shstrndx = usize::from(read_u16(data, 0x3e, be)?);
+
+        let section = |idx: usize| -> Result<(u32, u32, u32, &'dat [u8])> {
+            let hdr = shoff + idx * shentsize;
+            let name = read_u32(data, hdr, be)?;
+            let type_ = read_u32(data, hdr + 0x04, be)?;
+            let offset = read_u64(data, hdr + 0x18, be)? as usize;
+            let size = read_u64(data, hdr + 0x20, be)? as usize;
+            let link = read_u32(data, hdr + 0x28, be)?;
+            let data = if type_ == SHT_NOBITS {
+                &[][..]
+            } else {
+                data.get(offset..offset.saturating_add(size))
+                    .ok_or_else(|| Error::with_invalid_data("ELF section out of bounds"))?
+            };
+            Ok((name, type_, link, data))
+        };
+
+        let (_name, _type, _link, strtab) = section(shstrndx)?;
+        (0..shnum)
+            .map(|idx| {
+                let (name, type_, link, data) = section(idx)?;
+                Ok(Section {
+                    name: read_str(strtab, name as usize),
+                    type_,
+                    link,
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    /// Retrieve the contents of the section called `name`, if present.
+    pub fn section_data(&self, name: &[u8]) -> Result<Option<&'dat [u8]>> {
+        let data = self
+            .sections()?
+            .into_iter()
+            .find(|section| section.name == name)
+            .map(|section| section.data);
+        Ok(data)
+    }
+
+    /// Retrieve all segments of the file.
+    pub fn segments(&self) -> Result<Vec<Segment>> {
+        let (data, be) = (self.data, self.be);
+        let phoff = read_u64(data, 0x20, be)? as usize;
+        let phentsize = usize::from(read_u16(data, 0x36, be)?);
+        let phnum = usize::from(read_u16(data, 0x38, be)?);
+
+        (0..phnum)
+            .map(|idx| {
+                let hdr = phoff + idx * phentsize;
+                Ok(Segment {
+                    type_: read_u32(data, hdr, be)?,
+                    offset: read_u64(data, hdr + 0x08, be)?,
+                    vaddr: read_u64(data, hdr + 0x10, be)?,
+                    filesz: read_u64(data, hdr + 0x20, be)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Retrieve all function symbols from the file's symbol tables,
+    /// sorted by address.
+    pub fn func_symbols(&self) -> Result<Vec<FuncSymbol>> {
+        let sections = self.sections()?;
+        let mut syms = Vec::new();
+
+        for symtab in sections
+            .iter()
+            .filter(|section| matches!(section.type_, SHT_SYMTAB | SHT_DYNSYM))
+        {
+            let strtab = sections
+                .get(symtab.link as usize)
+                .ok_or_else(|| Error::with_invalid_data("invalid symbol string table"))?
+                .data;
+
+            for sym in symtab.data.chunks_exact(SYM_SIZE) {
+                let info = sym[4];
+                let addr = read_u64(sym, 8, self.be)?;
+                if info & 0xf != STT_FUNC || addr == 0 {
+                    continue;
+                }
+
+                let name = read_str(strtab, read_u32(sym, 0, self.be)? as usize);
+                let () = syms.push(FuncSymbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    addr,
+                    size: read_u64(sym, 16, self.be)?,
+                });
+            }
+        }
+
+        let () = syms.sort_by(|a, b| a.addr.cmp(&b.addr).then_with(|| a.name.cmp(&b.name)));
+        let () = syms.dedup_by(|a, b| a.addr == b.addr && a.name == b.name);
+        Ok(syms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env::current_exe;
+    use std::fs;
+
+    /// Check that we reject data that is not a 64 bit ELF file.
+    #[test]
+    fn invalid_elf() {
+        let err = Elf::parse(b"not an ELF file").unwrap_err();
+        assert!(err.to_string().contains("not a 64 bit ELF file"), "{err}");
+    }
+
+    /// Check that we can look up sections, segments, and symbols in
+    /// the running test binary.
+    #[test]
+    fn self_parsing() {
+        let data = fs::read(current_exe().unwrap()).unwrap();
+        let elf = Elf::parse(&data).unwrap();
+
+        assert!(elf.section_data(b".text").unwrap().is_some());
+        assert!(elf.section_data(b".does-not-exist").unwrap().is_none());
+        assert!(elf
+            .segments()
+            .unwrap()
+            .iter()
+            .any(|segment| segment.type_ == PT_LOAD));
+
+        let syms = elf.func_symbols().unwrap();
+        assert!(syms.iter().any(|sym| sym.name == "main"));
+        assert!(syms.windows(2).all(|w| w[0].addr <= w[1].addr));
+    }
+}
@@ -11,6 +11,8 @@ use std::mem::transmute;
 use std::ops::Deref;
 use std::result;
 
+use crate::ProgramLoadError;
+
 /// A result type using our [`Error`] by default.
 pub type Result<T, E = Error> = result::Result<T, E>;
 
@@ -112,10 +114,14 @@ enum ErrorImpl {
     },
 }
 
+/// The kernel internal `ENOTSUPP` error code, which may leak to user
+/// space from certain BPF subsystems.
+const ENOTSUPP: i32 = 524;
+
 impl ErrorImpl {
     fn kind(&self) -> ErrorKind {
         match self {
-            Self::Io(error) => match error.kind() {
+            Self::Io(error) => Self::errno_kind(error).unwrap_or_else(|| match error.kind() {
                 io::ErrorKind::NotFound => ErrorKind::NotFound,
                 io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
                 io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists,
@@ -129,13 +135,53 @@ impl ErrorImpl {
                 io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
                 io::ErrorKind::OutOfMemory => ErrorKind::OutOfMemory,
                 _ => ErrorKind::Other,
-            },
+            }),
             Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => {
                 source.deref().kind()
             }
         }
     }
 
+    /// Classify an I/O error based on its underlying OS error code, if
+    /// the default classification does not capture its meaning in the
+    /// context of BPF.
+    fn errno_kind(error: &io::Error) -> Option<ErrorKind> {
+        let (errno, verifier) = match error.get_ref() {
+            Some(inner) => match inner.downcast_ref::<ProgramLoadError>() {
+                Some(load_err) => (load_err.raw_os_error()?, true),
+                None => return None,
+            },
+            None => (error.raw_os_error()?, false),
+        };
+
+        match errno {
+            // The verifier reports rejected programs with `EACCES` or
+            // `EINVAL`, and uses `E2BIG` for overly complex ones.
+            libc::EACCES | libc::EINVAL | libc::E2BIG if verifier => {
+                Some(ErrorKind::VerifierRejected)
+            }
+            libc::EPERM | libc::EACCES => Some(ErrorKind::PermissionDenied),
+            // The kernel commonly reports unknown attributes or
+            // features with `E2BIG`.
+            libc::E2BIG | libc::EOPNOTSUPP | ENOTSUPP => Some(ErrorKind::Unsupported),
+            _ => None,
+        }
+    }
+
+    fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Self::Io(error) => error.raw_os_error().or_else(|| {
+                error
+                    .get_ref()?
+                    .downcast_ref::<ProgramLoadError>()?
+                    .raw_os_error()
+            }),
+            Self::ContextOwned { source, .. } | Self::ContextStatic { source, .. } => {
+                source.deref().raw_os_error()
+            }
+        }
+    }
+
     #[cfg(test)]
     fn is_owned(&self) -> Option<bool> {
         match self {
@@ -221,7 +267,12 @@ impl error::Error for ErrorImpl {
 ///
 /// The variants of this type partly resemble those of
 /// [`std::io::Error`], because these are the most common sources of
-/// error that the crate concerns itself with.
+/// error that the crate concerns itself with. Errors reported by the
+/// kernel are classified based on their `errno` value. E.g., `EPERM`
+/// maps to [`PermissionDenied`][ErrorKind::PermissionDenied] (typically
+/// indicating a lack of `CAP_BPF` or similar), while `EOPNOTSUPP` and
+/// `E2BIG` map to [`Unsupported`][ErrorKind::Unsupported] (typically
+/// indicating that the kernel is too old).
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[non_exhaustive]
 pub enum ErrorKind {
@@ -255,6 +306,9 @@ pub enum ErrorKind {
     /// An operation could not be completed, because it failed
     /// to allocate enough memory.
     OutOfMemory,
+    /// The BPF verifier rejected a program. Details are available
+    /// through the [`ProgramLoadError`] contained in the error.
+    VerifierRejected,
     /// A custom error that does not fall under any other I/O error
     /// kind.
     Other,
@@ -361,6 +415,41 @@ impl Error {
         self.error.kind()
     }
 
+    /// Retrieve the OS error code (typically `errno`) this error was
+    /// caused by, if any.
+    #[inline]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.error.raw_os_error()
+    }
+
+    /// Attempt to retrieve a reference to an error of type `T` from
+    /// the chain of errors making up this `Error`.
+    ///
+    /// This method can be used for accessing structured information
+    /// about errors, such as a [`ProgramLoadError`].
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: error::Error + 'static,
+    {
+        let mut error = self.error.deref();
+        loop {
+            match error {
+                ErrorImpl::Io(io) => {
+                    let io = io as &(dyn error::Error + 'static);
+                    if let Some(err) = io.downcast_ref::<T>() {
+                        break Some(err);
+                    }
+                    let io = io.downcast_ref::<io::Error>()?;
+                    break io.get_ref().and_then(|inner| inner.downcast_ref::<T>());
+                }
+                ErrorImpl::ContextOwned { source, .. }
+                | ErrorImpl::ContextStatic { source, .. } => {
+                    error = source.deref();
+                }
+            }
+        }
+    }
+
     /// Layer the provided context on top of this `Error`, creating a
     /// new one in the process.
     fn layer_context(self, context: Cow<'static, Str>) -> Self {
@@ -610,4 +699,63 @@ Caused by:
         assert_eq!(format!("{err:?}"), expected);
         assert_ne!(format!("{err:#?}"), "");
     }
+
+    /// Check that OS errors are classified as expected.
+    #[test]
+    fn errno_classification() {
+        let kind = |errno| Error::from_raw_os_error(errno).context("context").kind();
+
+        assert_eq!(kind(libc::EPERM), ErrorKind::PermissionDenied);
+        assert_eq!(kind(libc::EACCES), ErrorKind::PermissionDenied);
+        assert_eq!(kind(libc::E2BIG), ErrorKind::Unsupported);
+        assert_eq!(kind(libc::EOPNOTSUPP), ErrorKind::Unsupported);
+        assert_eq!(kind(ENOTSUPP), ErrorKind::Unsupported);
+        assert_eq!(kind(libc::ENOENT), ErrorKind::NotFound);
+        assert_eq!(kind(libc::EINVAL), ErrorKind::InvalidInput);
+
+        let load_err = |errno| {
+            let err = ProgramLoadError::new(
+                "prog".to_string(),
+                "section".to_string(),
+                String::new(),
+                io::Error::from_raw_os_error(errno),
+            );
+            Error::from(io::Error::new(io::ErrorKind::Other, err)).context("context")
+        };
+        let err = load_err(libc::EACCES);
+        assert_eq!(err.kind(), ErrorKind::VerifierRejected);
+        assert_eq!(err.raw_os_error(), Some(libc::EACCES));
+        assert_eq!(load_err(libc::E2BIG).kind(), ErrorKind::VerifierRejected);
+        assert_eq!(load_err(libc::EPERM).kind(), ErrorKind::PermissionDenied);
+    }
+
+    /// Check that we can retrieve typed errors from an error chain.
+    #[test]
+    fn error_downcasting() {
+        #[derive(Debug)]
+        struct Custom;
+
+        impl Display for Custom {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                f.write_str("custom error")
+            }
+        }
+
+        impl error::Error for Custom {}
+
+        let err = Error::from(io::Error::new(io::ErrorKind::Other, Custom));
+        let err = err
+            .context("inner context")
+            .context("outer context".to_string());
+        assert!(err.downcast_ref::<Custom>().is_some());
+        assert!(err.downcast_ref::<io::Error>().is_some());
+
+        let err = Error::from_raw_os_error(libc::EPERM).context("context");
+        assert!(err.downcast_ref::<Custom>().is_none());
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+        assert_eq!(
+            err.downcast_ref::<io::Error>().unwrap().raw_os_error(),
+            Some(libc::EPERM)
+        );
+    }
 }
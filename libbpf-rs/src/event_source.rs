@@ -0,0 +1,255 @@
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::time::Duration;
+
+use crate::Error;
+use crate::Map;
+use crate::MapType;
+use crate::OpenMap;
+use crate::PerfBuffer;
+use crate::PerfBufferBuilder;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferBuilder;
+
+type SampleCb<'cb> = Box<dyn FnMut(&[u8]) + 'cb>;
+type LostCb<'cb> = Box<dyn FnMut(i32, u64) + 'cb>;
+
+/// The transport backing an [`EventSource`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventSourceKind {
+    /// Events are transferred through a [`MapType::RingBuf`] map.
+    RingBuffer,
+    /// Events are transferred through a [`MapType::PerfEventArray`] map.
+    PerfBuffer,
+}
+
+impl EventSourceKind {
+    /// Determine the preferred event transport for the running kernel.
+    ///
+    /// Ring buffers are used if the kernel supports them, perf buffers
+    /// otherwise.
+    pub fn detect() -> Result<Self> {
+        if MapType::RingBuf.is_supported()? {
+            Ok(Self::RingBuffer)
+        } else {
+            Ok(Self::PerfBuffer)
+        }
+    }
+
+    /// The map type used by this transport.
+    pub fn map_type(&self) -> MapType {
+        match self {
+            Self::RingBuffer => MapType::RingBuf,
+            Self::PerfBuffer => MapType::PerfEventArray,
+        }
+    }
+
+    /// Configure `map`, one of a pair of ring buffer and perf buffer maps
+    /// declared for the same events, to only be created if it matches
+    /// this transport.
+    ///
+    /// This method should be invoked on both maps of such a pair before
+    /// the object is loaded.
+    pub fn configure(&self, map: &mut OpenMap) -> Result<()> {
+        let ty = map.map_type();
+        if ty != MapType::RingBuf && ty != MapType::PerfEventArray {
+            return Err(Error::with_invalid_data(format!(
+                "map `{}` of type {ty:?} cannot back an event source",
+                map.name().to_string_lossy(),
+            )));
+        }
+
+        map.set_autocreate(ty == self.map_type())
+    }
+}
+
+/// Builds [`EventSource`] instances.
+pub struct EventSourceBuilder<'a, 'cb> {
+    map: &'a Map,
+    pages: usize,
+    sample_cb: Option<SampleCb<'cb>>,
+    lost_cb: Option<LostCb<'cb>>,
+}
+
+impl<'a> EventSourceBuilder<'a, '_> {
+    /// Create a new `EventSourceBuilder` for the provided `map`, which
+    /// has to be either a [`MapType::RingBuf`] or a
+    /// [`MapType::PerfEventArray`] map.
+    pub fn new(map: &'a Map) -> Self {
+        Self {
+            map,
+            pages: 64,
+            sample_cb: None,
+            lost_cb: None,
+        }
+    }
+}
+
+impl<'cb> EventSourceBuilder<'_, 'cb> {
+    /// Callback to run when a sample is received, irrespective of the
+    /// underlying transport.
+    ///
+    /// The callback provides a raw byte slice. You may find libraries
+    /// such as [`plain`](https://crates.io/crates/plain) helpful.
+    pub fn sample_cb<F>(self, cb: F) -> Self
+    where
+        F: FnMut(&[u8]) + 'cb,
+    {
+        Self {
+            sample_cb: Some(Box::new(cb)),
+            ..self
+        }
+    }
+
+    /// Callback to run when samples got lost.
+    ///
+    /// Callback arguments are: `(cpu, lost_count)`. Ring buffers do not
+    /// report lost samples, so this callback is only ever invoked when
+    /// falling back to a perf buffer.
+    pub fn lost_cb<F>(self, cb: F) -> Self
+    where
+        F: FnMut(i32, u64) + 'cb,
+    {
+        Self {
+            lost_cb: Some(Box::new(cb)),
+            ..self
+        }
+    }
+
+    /// The number of pages to size each per-CPU buffer with, when falling
+    /// back to a perf buffer.
+    pub fn pages(self, pages: usize) -> Self {
+        Self { pages, ..self }
+    }
+
+    /// Build the [`EventSource`] object as configured.
+    pub fn build(self) -> Result<EventSource<'cb>> {
+        let Self {
+            map,
+            pages,
+            sample_cb,
+            lost_cb,
+        } = self;
+
+        let mut sample_cb = sample_cb.ok_or_else(|| {
+            Error::with_invalid_data("a sample callback is required for building an event source")
+        })?;
+
+        let inner = match map.map_type() {
+            MapType::RingBuf => {
+                let mut builder = RingBufferBuilder::new();
+                let _builder = builder.add(map, move |data| {
+                    let () = sample_cb(data);
+                    0
+                })?;
+                Inner::RingBuffer(builder.build()?)
+            }
+            MapType::PerfEventArray => {
+                let builder = PerfBufferBuilder::new(map)
+                    .pages(pages)
+                    .sample_cb(move |_cpu, data: &[u8]| sample_cb(data));
+                let builder = match lost_cb {
+                    Some(lost_cb) => builder.lost_cb(lost_cb),
+                    None => builder,
+                };
+                Inner::PerfBuffer(builder.build()?)
+            }
+            ty => {
+                return Err(Error::with_invalid_data(format!(
+                    "map `{}` of type {ty:?} cannot back an event source",
+                    map.name(),
+                )))
+            }
+        };
+
+        Ok(EventSource { inner })
+    }
+}
+
+impl Debug for EventSourceBuilder<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self {
+            map,
+            pages,
+            sample_cb,
+            lost_cb,
+        } = self;
+        f.debug_struct("EventSourceBuilder")
+            .field("map", map)
+            .field("pages", pages)
+            .field("sample_cb", &sample_cb.as_ref().map(|cb| &cb as *const _))
+            .field("lost_cb", &lost_cb.as_ref().map(|cb| &cb as *const _))
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+enum Inner<'cb> {
+    RingBuffer(RingBuffer<'cb>),
+    PerfBuffer(PerfBuffer<'cb>),
+}
+
+/// A source of events emitted by BPF programs, backed by either a ring
+/// buffer or, on kernels lacking ring buffer support, a perf buffer.
+///
+/// Together with [`EventSourceKind::configure`] this type allows for
+/// consuming events through a single code path, irrespective of the
+/// transport available on the running kernel.
+#[derive(Debug)]
+pub struct EventSource<'cb> {
+    inner: Inner<'cb>,
+}
+
+impl EventSource<'_> {
+    /// Retrieve the transport backing this event source.
+    pub fn kind(&self) -> EventSourceKind {
+        match &self.inner {
+            Inner::RingBuffer(_) => EventSourceKind::RingBuffer,
+            Inner::PerfBuffer(_) => EventSourceKind::PerfBuffer,
+        }
+    }
+
+    /// Poll for available events and invoke the sample callback for each
+    /// of them, waiting at most `timeout`.
+    pub fn poll(&self, timeout: Duration) -> Result<()> {
+        match &self.inner {
+            Inner::RingBuffer(ringbuf) => ringbuf.poll(timeout),
+            Inner::PerfBuffer(perfbuf) => perfbuf.poll(timeout),
+        }
+    }
+
+    /// Invoke the sample callback for all available events, without
+    /// waiting.
+    pub fn consume(&self) -> Result<()> {
+        match &self.inner {
+            Inner::RingBuffer(ringbuf) => ringbuf.consume(),
+            Inner::PerfBuffer(perfbuf) => perfbuf.consume(),
+        }
+    }
+
+    /// Retrieve an epoll file descriptor that becomes readable once events
+    /// are available.
+    pub fn epoll_fd(&self) -> i32 {
+        match &self.inner {
+            Inner::RingBuffer(ringbuf) => ringbuf.epoll_fd(),
+            Inner::PerfBuffer(perfbuf) => perfbuf.epoll_fd(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that [`EventSourceKind`] maps to the expected map types.
+    #[test]
+    fn kind_map_types() {
+        assert_eq!(EventSourceKind::RingBuffer.map_type(), MapType::RingBuf);
+        assert_eq!(
+            EventSourceKind::PerfBuffer.map_type(),
+            MapType::PerfEventArray
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Typed decoding of events submitted by BPF programs.
+//!
+//! BPF programs commonly report different kinds of events through a
+//! single ring buffer, each represented by a C struct and distinguished
+//! by an ID stored at a fixed location in the sample. An
+//! [`EventDecoder`] maps such samples to variants of a user defined
+//! type, removing the need for decoding them by hand:
+//! ```no_run
+//! # use libbpf_rs::MapHandle;
+//! use libbpf_rs::events::EventDecoder;
+//! use libbpf_rs::Pod;
+//! use libbpf_rs::RingBufferBuilder;
+//!
+//! #[derive(Clone, Copy)]
+//! #[repr(C)]
+//! struct Exec {
+//!     id: u32,
+//!     pid: u32,
+//! }
+//!
+//! // SAFETY: `Exec` is valid for any bit pattern.
+//! unsafe impl Pod for Exec {}
+//!
+//! #[derive(Clone, Copy)]
+//! #[repr(C)]
+//! struct Exit {
+//!     id: u32,
+//!     pid: u32,
+//!     code: i32,
+//! }
+//!
+//! // SAFETY: `Exit` is valid for any bit pattern.
+//! unsafe impl Pod for Exit {}
+//!
+//! enum Event {
+//!     Exec(Exec),
+//!     Exit(Exit),
+//! }
+//!
+//! # fn run(map: &MapHandle) -> libbpf_rs::Result<()> {
+//! let mut decoder = EventDecoder::new();
+//! decoder
+//!     .register(1, Event::Exec)
+//!     .register(2, Event::Exit);
+//! let (callback, events) = decoder.into_channel();
+//!
+//! let mut builder = RingBufferBuilder::new();
+//! builder.add(map, callback)?;
+//! let ringbuf = builder.build()?;
+//! let () = ringbuf.consume()?;
+//!
+//! for event in events.try_iter() {
+//!     match event? {
+//!         Event::Exec(exec) => println!("exec: {}", exec.pid),
+//!         Event::Exit(exit) => println!("exit: {} ({})", exit.pid, exit.code),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+
+use crate::pod::read_pod;
+use crate::Error;
+use crate::Pod;
+use crate::Result;
+
+/// A function decoding the raw bytes of a sample.
+type Decoder<E> = Box<dyn Fn(&[u8]) -> Result<E> + Send>;
+
+/// A type for decoding raw event samples into values of type `E`,
+/// based on an event ID contained in each sample.
+///
+/// The ID is a native endian `u32`, located at the start of the sample
+/// by default. Use [`EventDecoder::id_offset`] for a different
+/// location.
+pub struct EventDecoder<E> {
+    id_offset: usize,
+    decoders: HashMap<u32, Decoder<E>>,
+}
+
+impl<E> EventDecoder<E>
+where
+    E: 'static,
+{
+    /// Create a new `EventDecoder` without any registered event types.
+    pub fn new() -> Self {
+        Self {
+            id_offset: 0,
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Set the byte offset of the event ID within samples.
+    pub fn id_offset(&mut self, offset: usize) -> &mut Self {
+        self.id_offset = offset;
+        self
+    }
+
+    /// Register event type `T` for samples with ID `id`.
+    ///
+    /// Such samples are interpreted as a `T`, which then gets converted
+    /// into an `E` via `convert`. Registering an ID a second time
+    /// replaces the previous registration.
+    pub fn register<T, F>(&mut self, id: u32, convert: F) -> &mut Self
+    where
+        T: Pod,
+        F: Fn(T) -> E + Send + 'static,
+    {
+        let decoder = move |data: &[u8]| read_pod::<T>(data).map(&convert);
+        let _prev = self.decoders.insert(id, Box::new(decoder));
+        self
+    }
+
+    /// Decode a single sample.
+    pub fn decode(&self, data: &[u8]) -> Result<E> {
+        let id = data
+            .get(self.id_offset..)
+            .map(read_pod::<u32>)
+            .unwrap_or_else(|| Err(Error::with_invalid_data("sample does not contain event ID")))?;
+        let decoder = self
+            .decoders
+            .get(&id)
+            .ok_or_else(|| Error::with_invalid_data(format!("unknown event ID {id}")))?;
+        decoder(data)
+    }
+
+    /// Convert the decoder into a callback suitable for consuming
+    /// samples, e.g., via [`RingBufferBuilder::add`][crate::RingBufferBuilder::add],
+    /// and a channel receiving the decoded events.
+    ///
+    /// Samples that fail to decode are reported as errors through the
+    /// channel. Samples are silently discarded once the receiving end of
+    /// the channel has been dropped.
+    pub fn into_channel(self) -> (impl FnMut(&[u8]) -> i32 + Send, Receiver<Result<E>>)
+    where
+        E: Send,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let callback = move |data: &[u8]| {
+            let _result = sender.send(self.decode(data));
+            0
+        };
+        (callback, receiver)
+    }
+}
+
+impl<E> Default for EventDecoder<E>
+where
+    E: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Debug for EventDecoder<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self {
+            id_offset,
+            decoders,
+        } = self;
+
+        f.debug_struct("EventDecoder")
+            .field("id_offset", id_offset)
+            .field("ids", &decoders.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::mem::size_of;
+    use std::slice;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Small {
+        id: u32,
+        value: u32,
+    }
+
+    // SAFETY: `Small` is valid for any bit pattern.
+    unsafe impl Pod for Small {}
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct Large {
+        id: u32,
+        pad: u32,
+        value: u64,
+    }
+
+    // SAFETY: `Large` is valid for any bit pattern.
+    unsafe impl Pod for Large {}
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Small(Small),
+        Large(Large),
+    }
+
+    fn bytes<T: Pod>(value: &T) -> Vec<u8> {
+        // SAFETY: `value` is valid for reads of `size_of::<T>()` bytes.
+        unsafe { slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>()) }.to_vec()
+    }
+
+    /// Check that we can decode samples into the registered types.
+    #[test]
+    fn event_decoding() {
+        let mut decoder = EventDecoder::new();
+        decoder.register(1, Event::Small).register(2, Event::Large);
+
+        let small = Small { id: 1, value: 42 };
+        assert_eq!(decoder.decode(&bytes(&small)).unwrap(), Event::Small(small));
+
+        // Samples need not be aligned.
+        let large = Large {
+            id: 2,
+            pad: 0,
+            value: u64::MAX,
+        };
+        let mut data = vec![0];
+        data.extend(bytes(&large));
+        assert_eq!(decoder.decode(&data[1..]).unwrap(), Event::Large(large));
+
+        let err = decoder
+            .decode(&bytes(&Small { id: 3, value: 0 }))
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown event ID 3"), "{err}");
+
+        // A sample claiming to be `Large` but lacking data.
+        let err = decoder
+            .decode(&bytes(&Small { id: 2, value: 0 }))
+            .unwrap_err();
+        assert!(err.to_string().contains("too small"), "{err}");
+
+        let err = decoder.decode(&[1, 0]).unwrap_err();
+        assert!(err.to_string().contains("too small"), "{err}");
+    }
+
+    /// Check that we can use a non-default ID location and receive
+    /// events through a channel.
+    #[test]
+    fn event_channel() {
+        let mut decoder = EventDecoder::new();
+        decoder
+            .id_offset(size_of::<u32>())
+            .register(42, |small: Small| small.id);
+
+        let (mut callback, events) = decoder.into_channel();
+        assert_eq!(callback(&bytes(&Small { id: 7, value: 42 })), 0);
+        assert_eq!(callback(&bytes(&Small { id: 8, value: 43 })), 0);
+
+        assert_eq!(events.try_recv().unwrap().unwrap(), 7);
+        assert!(events.try_recv().unwrap().is_err());
+        assert!(events.try_recv().is_err());
+
+        drop(events);
+        assert_eq!(callback(&bytes(&Small { id: 9, value: 42 })), 0);
+    }
+}
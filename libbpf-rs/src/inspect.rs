@@ -0,0 +1,415 @@
+//! Offline inspection of BPF object files.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::btf::BtfType;
+use crate::btf::TypeId;
+use crate::elf::read_u32;
+use crate::elf::Elf;
+use crate::AsRawLibbpf as _;
+use crate::Btf;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapType;
+use crate::ObjectBuilder;
+use crate::OpenObject;
+use crate::ProgramAttachType;
+use crate::ProgramType;
+use crate::Result;
+
+/// `BPF_JMP | BPF_CALL`
+const BPF_CALL_OPCODE: u8 = (libbpf_sys::BPF_JMP | libbpf_sys::BPF_CALL) as u8;
+/// The read-only frame pointer register, `r10`.
+const BPF_REG_FP: u8 = 10;
+/// The `.BTF.ext` header size up to and including the CO-RE relocation
+/// fields.
+const BTF_EXT_CORE_HDR_LEN: usize = 32;
+
+/// The kind of a CO-RE relocation, see `enum bpf_core_relo_kind` in
+/// libbpf's `relo_core.h`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CoreRelocationKind {
+    /// Field byte offset.
+    FieldByteOffset,
+    /// Field size in bytes.
+    FieldByteSize,
+    /// Field existence in target kernel.
+    FieldExists,
+    /// Field signedness.
+    FieldSigned,
+    /// Bitfield-specific left bitshift.
+    FieldLshiftU64,
+    /// Bitfield-specific right bitshift.
+    FieldRshiftU64,
+    /// Type ID in local BPF object.
+    TypeIdLocal,
+    /// Type ID in target kernel.
+    TypeIdTarget,
+    /// Type existence in target kernel.
+    TypeExists,
+    /// Type size in bytes.
+    TypeSize,
+    /// Enum value existence in target kernel.
+    EnumvalExists,
+    /// Enum value integer value.
+    EnumvalValue,
+    /// Type match in target kernel.
+    TypeMatches,
+    /// A relocation kind unknown to this library.
+    Unknown(u32),
+}
+
+impl From<u32> for CoreRelocationKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            libbpf_sys::BPF_CORE_FIELD_BYTE_OFFSET => Self::FieldByteOffset,
+            libbpf_sys::BPF_CORE_FIELD_BYTE_SIZE => Self::FieldByteSize,
+            libbpf_sys::BPF_CORE_FIELD_EXISTS => Self::FieldExists,
+            libbpf_sys::BPF_CORE_FIELD_SIGNED => Self::FieldSigned,
+            libbpf_sys::BPF_CORE_FIELD_LSHIFT_U64 => Self::FieldLshiftU64,
+            libbpf_sys::BPF_CORE_FIELD_RSHIFT_U64 => Self::FieldRshiftU64,
+            libbpf_sys::BPF_CORE_TYPE_ID_LOCAL => Self::TypeIdLocal,
+            libbpf_sys::BPF_CORE_TYPE_ID_TARGET => Self::TypeIdTarget,
+            libbpf_sys::BPF_CORE_TYPE_EXISTS => Self::TypeExists,
+            libbpf_sys::BPF_CORE_TYPE_SIZE => Self::TypeSize,
+            libbpf_sys::BPF_CORE_ENUMVAL_EXISTS => Self::EnumvalExists,
+            libbpf_sys::BPF_CORE_ENUMVAL_VALUE => Self::EnumvalValue,
+            libbpf_sys::BPF_CORE_TYPE_MATCHES => Self::TypeMatches,
+            kind => Self::Unknown(kind),
+        }
+    }
+}
+
+/// A CO-RE relocation recorded in a BPF object file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoreRelocation {
+    /// The ELF section containing the relocated instruction.
+    pub section: String,
+    /// The byte offset of the relocated instruction within `section`.
+    pub insn_off: u32,
+    /// The ID of the type the relocation is relative to, in the object's
+    /// BTF.
+    pub type_id: TypeId,
+    /// The name of the type the relocation is relative to, if any.
+    pub type_name: Option<String>,
+    /// The access string describing the accessed member, e.g., `0:1:2`.
+    pub access: String,
+    /// The kind of relocation.
+    pub kind: CoreRelocationKind,
+}
+
+/// Information about a program contained in a BPF object file.
+#[derive(Clone, Debug)]
+pub struct InspectedProgram {
+    /// The program's name.
+    pub name: String,
+    /// The ELF section the program resides in.
+    pub section: String,
+    /// The program type, as derived from the section name.
+    pub prog_type: ProgramType,
+    /// The expected attach type, as derived from the section name.
+    pub attach_type: ProgramAttachType,
+    /// The number of instructions the program consists of.
+    pub insn_cnt: usize,
+    /// The IDs of the BPF helpers called by the program.
+    pub helpers: BTreeSet<u32>,
+    /// The approximate stack usage of the program in bytes, as derived
+    /// from the accesses relative to the frame pointer it performs.
+    ///
+    /// Stack usage of called BPF subprograms is not accounted for.
+    pub stack_size: u32,
+}
+
+/// Information about a map contained in a BPF object file.
+#[derive(Clone, Debug)]
+pub struct InspectedMap {
+    /// The map's name.
+    pub name: String,
+    /// The map type.
+    pub map_type: MapType,
+    /// The size of the map's keys.
+    pub key_size: u32,
+    /// The size of the map's values.
+    pub value_size: u32,
+    /// The maximum number of entries.
+    pub max_entries: u32,
+}
+
+/// A type for inspecting BPF object files without any kernel
+/// interaction.
+///
+/// In contrast to [`ObjectBuilder`] followed by [`OpenObject::load`],
+/// an `ObjectInspector` never issues `bpf()` system calls, making it
+/// suitable for validating BPF object files in environments where they
+/// cannot be loaded, e.g., as part of CI.
+#[derive(Clone, Debug)]
+pub struct ObjectInspector {
+    license: Option<String>,
+    sections: Vec<String>,
+    progs: Vec<InspectedProgram>,
+    maps: Vec<InspectedMap>,
+    core_relos: Vec<CoreRelocation>,
+}
+
+impl ObjectInspector {
+    /// Inspect the BPF object file at the provided path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data =
+            fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or("object");
+        Self::from_memory(name, &data)
+    }
+
+    /// Inspect the BPF object file contained in `mem`.
+    pub fn from_memory(name: &str, mem: &[u8]) -> Result<Self> {
+        let obj = ObjectBuilder::default().name(name)?.open_memory(mem)?;
+        let progs = inspect_progs(&obj);
+        let maps = inspect_maps(&obj);
+
+        // SAFETY: The object pointer is valid for as long as `obj` is
+        //         alive.
+        let btf = Btf::from_bpf_object(unsafe { obj.as_libbpf_object().as_ref() })?;
+        let elf = Elf::parse(mem)?;
+        let sections = elf
+            .sections()?
+            .into_iter()
+            .map(|section| String::from_utf8_lossy(section.name).into_owned())
+            .filter(|name| !name.is_empty())
+            .collect();
+        let license = elf.section_data(b"license")?.map(|data| {
+            let data = data.split(|b| *b == 0).next().unwrap_or_default();
+            String::from_utf8_lossy(data).into_owned()
+        });
+        let core_relos = match (btf, elf.section_data(b".BTF.ext")?) {
+            (Some(btf), Some(btf_ext)) => parse_core_relos(&btf, btf_ext, elf.is_big_endian())?,
+            _ => Vec::new(),
+        };
+
+        Ok(Self {
+            license,
+            sections,
+            progs,
+            maps,
+            core_relos,
+        })
+    }
+
+    /// Retrieve the license the object declares in its `license`
+    /// section, if any.
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Retrieve the names of all sections of the object's ELF file.
+    pub fn section_names(&self) -> &[String] {
+        &self.sections
+    }
+
+    /// Retrieve information about the programs contained in the object.
+    pub fn programs(&self) -> &[InspectedProgram] {
+        &self.progs
+    }
+
+    /// Retrieve information about the maps contained in the object.
+    pub fn maps(&self) -> &[InspectedMap] {
+        &self.maps
+    }
+
+    /// Retrieve the CO-RE relocations recorded in the object.
+    pub fn core_relocations(&self) -> &[CoreRelocation] {
+        &self.core_relos
+    }
+
+    /// Retrieve the IDs of all BPF helpers used by any program in the
+    /// object.
+    pub fn helpers(&self) -> BTreeSet<u32> {
+        self.progs
+            .iter()
+            .flat_map(|prog| prog.helpers.iter().copied())
+            .collect()
+    }
+
+    /// Retrieve the (deduplicated) types of all maps in the object.
+    pub fn map_types(&self) -> Vec<MapType> {
+        self.maps.iter().fold(Vec::new(), |mut types, map| {
+            if !types.contains(&map.map_type) {
+                let () = types.push(map.map_type);
+            }
+            types
+        })
+    }
+}
+
+fn inspect_progs(obj: &OpenObject) -> Vec<InspectedProgram> {
+    obj.progs_iter()
+        .map(|prog| {
+            // SAFETY: The program pointer is valid.
+            let attach_type = unsafe {
+                libbpf_sys::bpf_program__expected_attach_type(prog.as_libbpf_object().as_ptr())
+            };
+            let insns = prog.insns();
+            let helpers = insns
+                .iter()
+                .filter(|insn| insn.code == BPF_CALL_OPCODE && insn.src_reg() == 0)
+                .map(|insn| insn.imm as u32)
+                .collect();
+            let stack_size = stack_size(insns);
+
+            InspectedProgram {
+                name: prog.name().to_string_lossy().into_owned(),
+                section: prog.section().to_string_lossy().into_owned(),
+                prog_type: prog.prog_type(),
+                attach_type: ProgramAttachType::from(attach_type),
+                insn_cnt: insns.len(),
+                helpers,
+                stack_size,
+            }
+        })
+        .collect()
+}
+
+/// Approximate the stack usage of a program by looking at the loads
+/// and stores relative to the frame pointer it performs.
+fn stack_size(insns: &[libbpf_sys::bpf_insn]) -> u32 {
+    insns
+        .iter()
+        .filter(|insn| match u32::from(insn.code) & 0x07 {
+            libbpf_sys::BPF_LDX => insn.src_reg() == BPF_REG_FP,
+            libbpf_sys::BPF_ST | libbpf_sys::BPF_STX => insn.dst_reg() == BPF_REG_FP,
+            _ => false,
+        })
+        .filter(|insn| insn.off < 0)
+        .map(|insn| insn.off.unsigned_abs().into())
+        .max()
+        .unwrap_or(0)
+}
+
+fn inspect_maps(obj: &OpenObject) -> Vec<InspectedMap> {
+    obj.maps_iter()
+        .map(|map| {
+            // SAFETY: The map pointer is valid.
+            let max_entries =
+                unsafe { libbpf_sys::bpf_map__max_entries(map.as_libbpf_object().as_ptr()) };
+            InspectedMap {
+                name: map.name().to_string_lossy().into_owned(),
+                map_type: map.map_type(),
+                key_size: map.key_size(),
+                value_size: map.value_size(),
+                max_entries,
+            }
+        })
+        .collect()
+}
+
+/// Parse the CO-RE relocations out of a `.BTF.ext` section.
+fn parse_core_relos(btf: &Btf<'_>, btf_ext: &[u8], be: bool) -> Result<Vec<CoreRelocation>> {
+    let name = |off: u32| {
+        btf.name_at(off)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+
+    let hdr_len = read_u32(btf_ext, 4, be)? as usize;
+    if hdr_len < BTF_EXT_CORE_HDR_LEN {
+        // No CO-RE relocation information present.
+        return Ok(Vec::new());
+    }
+    let core_relo_off = read_u32(btf_ext, 24, be)? as usize;
+    let core_relo_len = read_u32(btf_ext, 28, be)? as usize;
+    if core_relo_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let start = hdr_len + core_relo_off;
+    let data = btf_ext
+        .get(start..start + core_relo_len)
+        .ok_or_else(|| Error::with_invalid_data("CO-RE relocations out of bounds"))?;
+    let rec_size = read_u32(data, 0, be)? as usize;
+    if rec_size < 16 {
+        return Err(Error::with_invalid_data(format!(
+            "invalid CO-RE relocation record size {rec_size}"
+        )));
+    }
+
+    let mut relos = Vec::new();
+    let mut off = 4;
+    while off < data.len() {
+        let section = name(read_u32(data, off, be)?);
+        let num_info = read_u32(data, off + 4, be)? as usize;
+        off += 8;
+
+        for _ in 0..num_info {
+            let insn_off = read_u32(data, off, be)?;
+            let type_id = read_u32(data, off + 4, be)?;
+            let access = name(read_u32(data, off + 8, be)?);
+            let kind = read_u32(data, off + 12, be)?;
+            let type_name = btf
+                .type_by_id::<BtfType<'_>>(TypeId::from(type_id))
+                .and_then(|ty| ty.name())
+                .map(|name| name.to_string_lossy().into_owned());
+
+            let () = relos.push(CoreRelocation {
+                section: section.clone(),
+                insn_off,
+                type_id: TypeId::from(type_id),
+                type_name,
+                access,
+                kind: CoreRelocationKind::from(kind),
+            });
+            off += rec_size;
+        }
+    }
+    Ok(relos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util::test_object_path;
+
+    /// Check that we can inspect a BPF object file.
+    #[test]
+    fn object_inspection() {
+        let inspector = ObjectInspector::from_path(test_object_path("runqslower.bpf.o")).unwrap();
+
+        let start = inspector
+            .maps()
+            .iter()
+            .find(|map| map.name == "start")
+            .unwrap();
+        assert_eq!(start.map_type, MapType::Hash);
+        assert_eq!(start.key_size, 4);
+        assert_eq!(start.value_size, 8);
+        assert!(inspector.map_types().contains(&MapType::Hash));
+
+        assert!(inspector.license().unwrap().contains("GPL"));
+        assert!(inspector.section_names().iter().any(|name| name == ".BTF"));
+        assert!(!inspector.programs().is_empty());
+        for prog in inspector.programs() {
+            assert!(!prog.section.is_empty());
+            assert!(prog.insn_cnt > 0);
+            assert!(prog.stack_size <= 512);
+        }
+        // The programs look up the `start` map, at the very least.
+        assert!(inspector
+            .helpers()
+            .contains(&libbpf_sys::BPF_FUNC_map_lookup_elem));
+
+        // `runqslower` reads task fields using CO-RE.
+        assert!(inspector.core_relocations().iter().any(|relo| {
+            relo.type_name.as_deref() == Some("task_struct")
+                && relo.kind == CoreRelocationKind::FieldByteOffset
+        }));
+        for relo in inspector.core_relocations() {
+            assert!(!relo.section.is_empty());
+            assert!(!relo.access.is_empty());
+        }
+    }
+}
@@ -20,7 +20,8 @@
 //!    that builds and generates a skeleton module using `libbpf_cargo::SkeletonBuilder`
 //! 5. Write your userspace code by importing and using the generated module. Import the
 //!    module by using the [path
-//!    attribute](https://doc.rust-lang.org/reference/items/modules.html#the-path-attribute).
+//!    attribute](https://doc.rust-lang.org/reference/items/modules.html#the-path-attribute)
+//!    or, if the skeleton got generated into `OUT_DIR`, by using [`include_skel!`].
 //!    Your userspace code goes in `$PROJ_PATH/src/` as it would in a normal rust project.
 //! 6. Continue regular rust workflow (ie `cargo build`, `cargo run`, etc)
 //!
@@ -79,19 +80,32 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 pub mod btf;
+pub mod cgroup;
+mod elf;
 mod error;
+mod event_source;
+pub mod events;
+mod inspect;
 mod iter;
+mod light_skel;
 mod link;
 mod linker;
 mod map;
+pub mod netfilter;
 mod object;
 mod perf_buffer;
+mod perf_event_array;
+mod pod;
+mod poll;
 mod print;
 mod program;
 pub mod query;
+mod reuseport;
 mod ringbuf;
 mod skeleton;
+pub mod stacktrace;
 mod tc;
+mod typed_map;
 mod user_ringbuf;
 mod util;
 mod xdp;
@@ -105,10 +119,27 @@ pub use crate::error::Error;
 pub use crate::error::ErrorExt;
 pub use crate::error::ErrorKind;
 pub use crate::error::Result;
+pub use crate::event_source::EventSource;
+pub use crate::event_source::EventSourceBuilder;
+pub use crate::event_source::EventSourceKind;
+pub use crate::inspect::CoreRelocation;
+pub use crate::inspect::CoreRelocationKind;
+pub use crate::inspect::InspectedMap;
+pub use crate::inspect::InspectedProgram;
+pub use crate::inspect::ObjectInspector;
 pub use crate::iter::Iter;
+pub use crate::light_skel::LightSkel;
+pub use crate::light_skel::LoadedLightSkel;
 pub use crate::link::Link;
 pub use crate::linker::Linker;
+pub use crate::linker::LinkerOptions;
+pub use crate::map::CpumapEntry;
+pub use crate::map::CpumapValue;
+pub use crate::map::DevmapEntry;
+pub use crate::map::DevmapValue;
 pub use crate::map::Map;
+pub use crate::map::MapCreateFlags;
+pub use crate::map::MapEntryGuard;
 pub use crate::map::MapFlags;
 pub use crate::map::MapHandle;
 pub use crate::map::MapInfo;
@@ -116,27 +147,59 @@ pub use crate::map::MapKeyIter;
 pub use crate::map::MapType;
 pub use crate::map::OpenMap;
 pub use crate::object::AsRawLibbpf;
+pub use crate::object::MemoryUsage;
 pub use crate::object::Object;
 pub use crate::object::ObjectBuilder;
 pub use crate::object::OpenObject;
+pub use crate::object::ProgramLoadError;
+pub use crate::object::ShutdownOpts;
 pub use crate::perf_buffer::PerfBuffer;
 pub use crate::perf_buffer::PerfBufferBuilder;
+pub use crate::perf_event_array::PerfEventArray;
+pub use crate::pod::Pod;
+pub use crate::poll::BpfPollable;
+pub use crate::poll::Poller;
+pub use crate::print::add_print_subscriber;
+#[cfg(feature = "log")]
+pub use crate::print::forward_to_log;
 pub use crate::print::get_print;
 pub use crate::print::set_print;
+pub use crate::print::with_print;
 pub use crate::print::PrintCallback;
 pub use crate::print::PrintLevel;
+pub use crate::print::PrintSubscription;
+pub use crate::program::CgroupIterOrder;
 pub use crate::program::Input as ProgramInput;
+pub use crate::program::IterOpts;
+pub use crate::program::KprobeOpts;
 pub use crate::program::OpenProgram;
 pub use crate::program::Output as ProgramOutput;
+pub use crate::program::ProgAttachment;
 pub use crate::program::Program;
 pub use crate::program::ProgramAttachType;
 pub use crate::program::ProgramType;
+pub use crate::program::SymbolList;
 pub use crate::program::TracepointOpts;
 pub use crate::program::UprobeOpts;
 pub use crate::program::UsdtOpts;
+pub use crate::reuseport::ReuseportSockArray;
+pub use crate::ringbuf::Ring;
 pub use crate::ringbuf::RingBuffer;
 pub use crate::ringbuf::RingBufferBuilder;
+pub use crate::ringbuf::RingBufferConsumer;
+pub use crate::ringbuf::RingBufferConsumerOpts;
+pub use crate::ringbuf::RingBufferDispatcher;
+pub use crate::ringbuf::RingBufferDispatcherBuilder;
+pub use crate::ringbuf::RingBufferDispatcherOpts;
+pub use crate::ringbuf::RingBufferReader;
+pub use crate::ringbuf::RingBufferSampleCtx;
+pub use crate::ringbuf::RingBufferSampleGuard;
+pub use crate::ringbuf::RingBufferSampleIter;
+#[cfg(feature = "async")]
+pub use crate::ringbuf::RingBufferStream;
+pub use crate::ringbuf::SampleHandler;
 pub use crate::tc::TcAttachPoint;
+pub use crate::tc::TcFilterInfo;
 pub use crate::tc::TcHook;
 pub use crate::tc::TcHookBuilder;
 pub use crate::tc::TC_CUSTOM;
@@ -146,10 +209,26 @@ pub use crate::tc::TC_H_INGRESS;
 pub use crate::tc::TC_H_MIN_EGRESS;
 pub use crate::tc::TC_H_MIN_INGRESS;
 pub use crate::tc::TC_INGRESS;
+pub use crate::typed_map::TypedMap;
 pub use crate::user_ringbuf::UserRingBuffer;
 pub use crate::user_ringbuf::UserRingBufferSample;
+pub use crate::user_ringbuf::UserRingBufferTypedSample;
+pub use crate::util::bump_memlock_rlimit;
+pub use crate::util::ifindex_from_name;
+pub use crate::util::ifname_from_index;
+pub use crate::util::is_memcg_accounting_supported;
+pub use crate::util::libbpf_version;
+pub use crate::util::libbpf_version_string;
+pub use crate::util::num_online_cpus;
 pub use crate::util::num_possible_cpus;
+pub use crate::util::online_cpu_ids;
+pub use crate::util::possible_cpu_ids;
+pub use crate::util::set_strict_mode;
+pub use crate::util::StrictMode;
 pub use crate::xdp::Xdp;
+pub use crate::xdp::XdpAttachment;
+pub use crate::xdp::XdpFeatureFlags;
+pub use crate::xdp::XdpFeatures;
 pub use crate::xdp::XdpFlags;
 
 /// Used for skeleton -- an end user may not consider this API stable
@@ -160,6 +239,7 @@ pub mod __internal_skel {
 
 /// Skeleton related definitions.
 pub mod skel {
+    pub use super::skeleton::AttachReport;
     pub use super::skeleton::OpenSkel;
     pub use super::skeleton::Skel;
     pub use super::skeleton::SkelBuilder;
@@ -0,0 +1,284 @@
+//! Support for "light skeletons", i.e., BPF objects that are loaded by
+//! a loader program running in the kernel.
+//!
+//! Instead of performing the individual steps of loading an object
+//! (creating maps, relocating and loading programs, ...) from user
+//! space, `libbpf` can record them in the form of a
+//! `BPF_PROG_TYPE_SYSCALL` program along with the data it operates on.
+//! Running this loader program then creates all maps and programs of
+//! the object. Because the loader program and its data are fixed, they
+//! lend themselves to being signed, e.g., for environments that only
+//! allow for loading signed programs.
+//!
+//! A [`LightSkel`] is generated from an [`OpenObject`][crate::OpenObject]
+//! using [`OpenObject::gen_loader`][crate::OpenObject::gen_loader].
+
+use std::ffi::CStr;
+use std::mem;
+use std::mem::size_of;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::OwnedFd;
+use std::ptr;
+
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::Result;
+
+/// The size of `struct bpf_loader_ctx`, which forms the start of the
+/// context passed to the loader program.
+const LOADER_CTX_SIZE: usize = 24;
+/// The size of `struct bpf_map_desc`, describing a map in the loader
+/// program's context.
+const MAP_DESC_SIZE: usize = 16;
+/// The size of `struct bpf_prog_desc`, describing a program in the
+/// loader program's context.
+const PROG_DESC_SIZE: usize = 4;
+
+/// File descriptors along with the names of the entities they refer to.
+type NamedFds = Vec<(String, OwnedFd)>;
+
+/// Read the file descriptor stored at `offset` in `ctx`.
+fn read_fd(ctx: &[u8], offset: usize) -> i32 {
+    // SANITY: The context is sized to contain all descriptors.
+    let bytes = ctx[offset..offset + size_of::<i32>()].try_into().unwrap();
+    i32::from_ne_bytes(bytes)
+}
+
+/// A loader program along with its data, which create the maps and
+/// programs of a BPF object when run.
+///
+/// Maps and programs are identified by their names, in the order in
+/// which the loader program creates them.
+#[derive(Clone, Debug)]
+pub struct LightSkel {
+    insns: Vec<libbpf_sys::bpf_insn>,
+    data: Vec<u8>,
+    maps: Vec<String>,
+    progs: Vec<String>,
+}
+
+impl LightSkel {
+    /// Create a `LightSkel` from the raw instructions and data of a
+    /// loader program, as well as the names of the maps and programs it
+    /// creates.
+    ///
+    /// This constructor is meant for loading a previously generated (and
+    /// potentially signed) loader program, as retrieved via
+    /// [`LightSkel::insns_bytes`] and [`LightSkel::data`].
+    pub fn new(insns: &[u8], data: &[u8], maps: Vec<String>, progs: Vec<String>) -> Result<Self> {
+        if insns.is_empty() || insns.len() % size_of::<libbpf_sys::bpf_insn>() != 0 {
+            return Err(Error::with_invalid_data(format!(
+                "loader program of {} bytes does not consist of whole instructions",
+                insns.len()
+            )));
+        }
+
+        let insns = insns
+            .chunks_exact(size_of::<libbpf_sys::bpf_insn>())
+            .map(|chunk| {
+                // SAFETY: `bpf_insn` is plain old data valid for any bit
+                //         pattern and `chunk` is of the proper size.
+                unsafe { ptr::read_unaligned(chunk.as_ptr().cast::<libbpf_sys::bpf_insn>()) }
+            })
+            .collect();
+
+        Ok(Self {
+            insns,
+            data: data.to_vec(),
+            maps,
+            progs,
+        })
+    }
+
+    /// Retrieve the instructions of the loader program.
+    pub fn insns(&self) -> &[libbpf_sys::bpf_insn] {
+        &self.insns
+    }
+
+    /// Retrieve the instructions of the loader program in their raw
+    /// form.
+    pub fn insns_bytes(&self) -> Vec<u8> {
+        self.insns
+            .iter()
+            .flat_map(|insn| {
+                // SAFETY: `bpf_insn` is plain old data without padding.
+                let bytes: [u8; size_of::<libbpf_sys::bpf_insn>()] =
+                    unsafe { mem::transmute(*insn) };
+                bytes
+            })
+            .collect()
+    }
+
+    /// Retrieve the data the loader program operates on.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Retrieve the names of the maps created by the loader program.
+    pub fn map_names(&self) -> &[String] {
+        &self.maps
+    }
+
+    /// Retrieve the names of the programs created by the loader program.
+    pub fn prog_names(&self) -> &[String] {
+        &self.progs
+    }
+
+    /// Load the loader program into the kernel and run it, creating the
+    /// maps and programs of the object.
+    pub fn load(&self) -> Result<LoadedLightSkel> {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            sz: size_of::<libbpf_sys::bpf_map_create_opts>() as _,
+            ..Default::default()
+        };
+        let data_map = MapHandle::create(
+            MapType::Array,
+            Some("__loader.map"),
+            size_of::<u32>() as _,
+            self.data.len() as _,
+            1,
+            &opts,
+        )?;
+        let () = data_map.update(&0u32.to_ne_bytes(), &self.data, MapFlags::ANY)?;
+
+        let map_fd = data_map.as_fd().as_raw_fd();
+        let mut opts = libbpf_sys::bpf_prog_load_opts {
+            sz: size_of::<libbpf_sys::bpf_prog_load_opts>() as _,
+            prog_flags: libbpf_sys::BPF_F_SLEEPABLE,
+            fd_array: &map_fd,
+            ..Default::default()
+        };
+        // SANITY: The strings are NUL terminated.
+        let name = CStr::from_bytes_with_nul(b"__loader.prog\0").unwrap();
+        let license = CStr::from_bytes_with_nul(b"Dual BSD/GPL\0").unwrap();
+        // SAFETY: All pointers are valid for the duration of the call.
+        let fd = unsafe {
+            libbpf_sys::bpf_prog_load(
+                libbpf_sys::BPF_PROG_TYPE_SYSCALL,
+                name.as_ptr(),
+                license.as_ptr(),
+                self.insns.as_ptr(),
+                self.insns.len() as _,
+                &mut opts,
+            )
+        };
+        let fd = util::parse_ret_i32(fd).context("failed to load loader program")?;
+        // SAFETY: `fd` is a freshly created file descriptor that we own.
+        let prog = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let prog_off = LOADER_CTX_SIZE + self.maps.len() * MAP_DESC_SIZE;
+        let ctx_size = prog_off + self.progs.len() * PROG_DESC_SIZE;
+        let mut ctx = vec![0u8; ctx_size];
+        let () = ctx[..size_of::<u32>()].copy_from_slice(&(ctx_size as u32).to_ne_bytes());
+
+        let mut opts = libbpf_sys::bpf_test_run_opts {
+            sz: size_of::<libbpf_sys::bpf_test_run_opts>() as _,
+            ctx_in: ctx.as_mut_ptr().cast(),
+            ctx_size_in: ctx.len() as _,
+            ..Default::default()
+        };
+        // SAFETY: `ctx` is valid for reads and writes of `ctx_size_in`
+        //         bytes, as the kernel writes back the context for
+        //         programs of type `BPF_PROG_TYPE_SYSCALL`.
+        let ret = unsafe { libbpf_sys::bpf_prog_test_run_opts(prog.as_raw_fd(), &mut opts) };
+        let () = util::parse_ret(ret).context("failed to run loader program")?;
+        let retval = opts.retval as i32;
+        if retval < 0 {
+            return Err(Error::from_raw_os_error(-retval).context("loader program failed"));
+        }
+
+        let fds = |names: &[String], offset: usize, size: usize| {
+            names
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| {
+                    let fd = read_fd(&ctx, offset + idx * size);
+                    // SAFETY: The loader program transferred ownership of
+                    //         the file descriptors it created to us.
+                    let fd = (fd > 0).then(|| unsafe { OwnedFd::from_raw_fd(fd) });
+                    (name.clone(), fd)
+                })
+                .collect::<Vec<_>>()
+        };
+        let maps = fds(&self.maps, LOADER_CTX_SIZE, MAP_DESC_SIZE);
+        let progs = fds(&self.progs, prog_off, PROG_DESC_SIZE);
+
+        let collect = |fds: Vec<(String, Option<OwnedFd>)>| {
+            fds.into_iter()
+                .map(|(name, fd)| {
+                    let fd = fd.ok_or_else(|| {
+                        Error::with_invalid_data(format!(
+                            "loader program did not report a file descriptor for `{name}`"
+                        ))
+                    })?;
+                    Ok((name, fd))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(LoadedLightSkel {
+            maps: collect(maps)?,
+            progs: collect(progs)?,
+        })
+    }
+}
+
+/// The maps and programs created by running a [`LightSkel`]'s loader
+/// program.
+#[derive(Debug)]
+pub struct LoadedLightSkel {
+    maps: NamedFds,
+    progs: NamedFds,
+}
+
+impl LoadedLightSkel {
+    /// Retrieve the file descriptor of the map with the given name.
+    pub fn map(&self, name: &str) -> Option<BorrowedFd<'_>> {
+        self.maps
+            .iter()
+            .find(|(map, _fd)| map == name)
+            .map(|(_map, fd)| fd.as_fd())
+    }
+
+    /// Retrieve the file descriptor of the program with the given name.
+    pub fn prog(&self, name: &str) -> Option<BorrowedFd<'_>> {
+        self.progs
+            .iter()
+            .find(|(prog, _fd)| prog == name)
+            .map(|(_prog, fd)| fd.as_fd())
+    }
+
+    /// Take ownership of the file descriptors of all maps and programs,
+    /// along with their names.
+    pub fn into_fds(self) -> (NamedFds, NamedFds) {
+        (self.maps, self.progs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that instructions round trip through their raw
+    /// representation and that malformed ones are rejected.
+    #[test]
+    fn insns_round_trip() {
+        let insns = (0..16u8).collect::<Vec<_>>();
+        let skel = LightSkel::new(&insns, &[1, 2], vec!["map".into()], Vec::new()).unwrap();
+        assert_eq!(skel.insns().len(), 2);
+        assert_eq!(skel.insns_bytes(), insns);
+        assert_eq!(skel.data(), [1, 2]);
+        assert_eq!(skel.map_names(), ["map"]);
+        assert!(skel.prog_names().is_empty());
+
+        let _err = LightSkel::new(&insns[..15], &[], Vec::new(), Vec::new()).unwrap_err();
+        let _err = LightSkel::new(&[], &[], Vec::new(), Vec::new()).unwrap_err();
+    }
+}
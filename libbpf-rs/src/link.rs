@@ -1,12 +1,22 @@
+use std::ffi::CString;
 use std::fmt::Debug;
+use std::fs;
+use std::mem;
+use std::mem::size_of_val;
 use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd as _;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
 use std::path::Path;
 use std::path::PathBuf;
 use std::ptr::NonNull;
 
 use crate::util;
 use crate::AsRawLibbpf;
+use crate::Error;
 use crate::Program;
 use crate::Result;
 
@@ -69,11 +79,76 @@ impl Link {
     /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// this link to bpffs.
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
         let ret = unsafe { libbpf_sys::bpf_link__pin(self.ptr.as_ptr(), path_ptr) };
-        util::parse_ret(ret)
+        util::parse_pin_ret(ret, path)
+    }
+
+    /// Pin this link in directory `dir` on bpffs, using the name of the
+    /// attached program as file name.
+    ///
+    /// Note that the kernel only keeps the first `BPF_OBJ_NAME_LEN - 1`
+    /// bytes of a program's name.
+    ///
+    /// Returns the path the link got pinned at.
+    pub fn pin_in_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<PathBuf> {
+        let path = self.pin_path_in_dir(dir.as_ref())?;
+        let () = self.pin(&path)?;
+        Ok(path)
+    }
+
+    /// Unpin this link from directory `dir` on bpffs, as pinned by
+    /// [`Link::pin_in_dir`].
+    pub fn unpin_from_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let path = self.pin_path_in_dir(dir.as_ref())?;
+        if self.pin_path().as_deref() == Some(path.as_path()) {
+            self.unpin()
+        } else {
+            // The link may have been pinned through a different `Link`
+            // object, in which case libbpf does not know about the pin.
+            fs::remove_file(&path).map_err(Error::from)
+        }
+    }
+
+    /// Construct the path for pinning this link in `dir`.
+    fn pin_path_in_dir(&self, dir: &Path) -> Result<PathBuf> {
+        let name = self.prog_name()?;
+        let name = name.to_str().map_err(Error::with_invalid_data)?;
+        util::pin_path_in_dir(dir, name)
+    }
+
+    /// Retrieve the name of the program attached via this link.
+    fn prog_name(&self) -> Result<CString> {
+        // SAFETY: `bpf_link_info` is valid for any bit pattern.
+        let mut link_info = unsafe { mem::zeroed::<libbpf_sys::bpf_link_info>() };
+        let mut len = size_of_val(&link_info) as u32;
+        // SAFETY: All pointers are derived from references and hence valid.
+        let ret = unsafe {
+            libbpf_sys::bpf_link_get_info_by_fd(self.as_fd().as_raw_fd(), &mut link_info, &mut len)
+        };
+        let () = util::parse_ret(ret)?;
+
+        // SAFETY: `bpf_prog_get_fd_by_id` is always safe to call.
+        let fd =
+            util::parse_ret_i32(unsafe { libbpf_sys::bpf_prog_get_fd_by_id(link_info.prog_id) })?;
+        // SAFETY: The file descriptor was just created by us and is valid.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: `bpf_prog_info` is valid for any bit pattern.
+        let mut prog_info = unsafe { mem::zeroed::<libbpf_sys::bpf_prog_info>() };
+        let mut len = size_of_val(&prog_info) as u32;
+        // SAFETY: All pointers are derived from references and hence valid.
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_get_info_by_fd(fd.as_raw_fd(), &mut prog_info, &mut len)
+        };
+        let () = util::parse_ret(ret)?;
+
+        let name = util::c_char_slice_to_cstr(&prog_info.name)
+            .ok_or_else(|| Error::with_invalid_data("program name is not NUL terminated"))?;
+        Ok(name.to_owned())
     }
 
     /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
@@ -103,6 +178,34 @@ impl Link {
         let ret = unsafe { libbpf_sys::bpf_link__detach(self.ptr.as_ptr()) };
         util::parse_ret(ret)
     }
+
+    /// Convert the link into an [`OwnedFd`], transferring ownership of the
+    /// underlying kernel link out of this object.
+    ///
+    /// The link stays attached for as long as the returned file descriptor
+    /// (or any duplicate of it) is kept open. This is useful when the file
+    /// descriptor has to outlive the `Link` object, e.g., because it got
+    /// registered with an `epoll` instance or is handed to another subsystem.
+    pub fn into_fd(self) -> Result<OwnedFd> {
+        let fd = self.into_raw_fd();
+        // SAFETY: `into_raw_fd` relinquished ownership of the still open
+        //         file descriptor.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+impl IntoRawFd for Link {
+    /// Consume the link without closing its file descriptor.
+    ///
+    /// The link stays attached until the file descriptor gets closed by
+    /// other means.
+    fn into_raw_fd(mut self) -> RawFd {
+        let fd = self.as_fd().as_raw_fd();
+        // A disconnected link neither gets detached nor has its file
+        // descriptor closed when destroyed.
+        let () = self.disconnect();
+        fd
+    }
 }
 
 impl AsRawLibbpf for Link {
@@ -1,14 +1,72 @@
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read as _;
+use std::io::Seek as _;
+use std::io::SeekFrom;
+use std::io::Write as _;
+use std::mem::size_of;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::FromRawFd as _;
 use std::path::Path;
-use std::ptr::null_mut;
+use std::path::PathBuf;
+use std::ptr::null;
 use std::ptr::NonNull;
 
 use crate::util;
 use crate::util::path_to_cstring;
+use crate::util::str_to_cstring;
 use crate::AsRawLibbpf;
 use crate::Error;
 use crate::ErrorExt as _;
 use crate::Result;
 
+/// Options to optionally be provided when creating a [`Linker`].
+#[derive(Clone, Debug, Default)]
+pub struct LinkerOptions {
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<&LinkerOptions> for libbpf_sys::bpf_linker_opts {
+    fn from(opts: &LinkerOptions) -> Self {
+        let LinkerOptions { _non_exhaustive } = opts;
+
+        #[allow(clippy::needless_update)]
+        libbpf_sys::bpf_linker_opts {
+            sz: size_of::<Self>() as _,
+            ..Default::default()
+        }
+    }
+}
+
+/// Create an anonymous, memory backed file.
+fn memfd(name: &str) -> Result<File> {
+    let name = str_to_cstring(name)?;
+    // SAFETY: `name` is a valid C string.
+    let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::from(io::Error::last_os_error())).context("failed to create memfd");
+    }
+    // SAFETY: `fd` is a freshly created file descriptor that we own.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Retrieve a path through which `file` can be opened.
+fn fd_path(file: &File) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()))
+}
+
+/// The location a [`Linker`] writes its output to.
+#[derive(Debug)]
+enum Output {
+    /// A file on the file system.
+    Path(PathBuf),
+    /// An anonymous memory backed file.
+    Memory(File),
+}
+
 /// A type used for linking multiple BPF object files into a single one.
 ///
 /// Please refer to
@@ -18,6 +76,8 @@ use crate::Result;
 pub struct Linker {
     /// The `libbpf` linker object.
     linker: NonNull<libbpf_sys::bpf_linker>,
+    /// The location the linked object is written to.
+    output: Output,
 }
 
 impl Linker {
@@ -26,13 +86,33 @@ impl Linker {
     where
         P: AsRef<Path>,
     {
-        let output = path_to_cstring(output)?;
+        Self::with_options(output, &LinkerOptions::default())
+    }
+
+    /// Instantiate a `Linker` object, using the provided options.
+    pub fn with_options<P>(output: P, opts: &LinkerOptions) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let output = output.as_ref();
+        Self::new_impl(output, opts, Output::Path(output.to_path_buf()))
+    }
+
+    /// Instantiate a `Linker` object that produces its output in
+    /// memory, to be retrieved via [`link_into_vec`](Self::link_into_vec).
+    pub fn in_memory(opts: &LinkerOptions) -> Result<Self> {
+        let file = memfd("libbpf-rs-linker")?;
+        Self::new_impl(&fd_path(&file), opts, Output::Memory(file))
+    }
+
+    fn new_impl(path: &Path, opts: &LinkerOptions, output: Output) -> Result<Self> {
+        let path = path_to_cstring(path)?;
+        let mut opts = libbpf_sys::bpf_linker_opts::from(opts);
         util::create_bpf_entity_checked(|| {
-            let opts = null_mut();
-            // SAFETY: `output` is a valid pointer and `opts` is accepted as NULL.
-            unsafe { libbpf_sys::bpf_linker__new(output.as_ptr(), opts) }
+            // SAFETY: `path` and `opts` are valid pointers.
+            unsafe { libbpf_sys::bpf_linker__new(path.as_ptr(), &mut opts) }
         })
-        .map(|linker| Self { linker })
+        .map(|linker| Self { linker, output })
     }
 
     /// Add a file to the set of files to link.
@@ -41,15 +121,29 @@ impl Linker {
         P: AsRef<Path>,
     {
         let file = path_to_cstring(file)?;
-        let opts = null_mut();
+        self.add_file_impl(&file)
+    }
+
+    /// Add an in-memory BPF object file to the set of files to link.
+    ///
+    /// `name` is used to refer to the object in diagnostics.
+    pub fn add_buf(&mut self, buf: &[u8], name: &str) -> Result<()> {
+        let mut file = memfd(name)?;
+        let () = file
+            .write_all(buf)
+            .context("failed to write BPF object to memfd")?;
+        let path = path_to_cstring(fd_path(&file))?;
+        // libbpf is done with the file once it returns, so it is fine
+        // for us to close it afterwards.
+        self.add_file_impl(&path)
+    }
+
+    fn add_file_impl(&mut self, file: &CString) -> Result<()> {
+        let opts = null();
         // SAFETY: `linker` and `file` are a valid pointers.
         let err =
             unsafe { libbpf_sys::bpf_linker__add_file(self.linker.as_ptr(), file.as_ptr(), opts) };
-        if err != 0 {
-            Err(Error::from_raw_os_error(err)).context("bpf_linker__add_file failed")
-        } else {
-            Ok(())
-        }
+        util::parse_ret(err).context("bpf_linker__add_file failed")
     }
 
     /// Link all BPF object files [added](Self::add_file) to this object into
@@ -57,10 +151,32 @@ impl Linker {
     pub fn link(&self) -> Result<()> {
         // SAFETY: `linker` is a valid pointer.
         let err = unsafe { libbpf_sys::bpf_linker__finalize(self.linker.as_ptr()) };
-        if err != 0 {
-            return Err(Error::from_raw_os_error(err)).context("bpf_linker__finalize failed");
+        util::parse_ret(err).context("bpf_linker__finalize failed")
+    }
+
+    /// Link all BPF object files [added](Self::add_file) to this object into
+    /// a single one and return its contents.
+    ///
+    /// For a linker created via [`in_memory`](Self::in_memory) this does
+    /// not involve the file system at all.
+    pub fn link_into_vec(&self) -> Result<Vec<u8>> {
+        let () = self.link()?;
+
+        match &self.output {
+            Output::Path(path) => fs::read(path)
+                .with_context(|| format!("failed to read linked object `{}`", path.display())),
+            Output::Memory(file) => {
+                let mut file = file;
+                let mut buf = Vec::new();
+                let _pos = file
+                    .seek(SeekFrom::Start(0))
+                    .context("failed to seek in linked object")?;
+                let _cnt = file
+                    .read_to_end(&mut buf)
+                    .context("failed to read linked object")?;
+                Ok(buf)
+            }
         }
-        Ok(())
     }
 }
 
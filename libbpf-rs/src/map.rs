@@ -15,6 +15,7 @@ use std::os::unix::io::FromRawFd;
 use std::os::unix::io::OwnedFd;
 use std::os::unix::io::RawFd;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
@@ -24,9 +25,13 @@ use bitflags::bitflags;
 use libbpf_sys::bpf_map_info;
 use libbpf_sys::bpf_obj_get_info_by_fd;
 
+use crate::btf::BtfType;
+use crate::btf::TypeId;
+use crate::query::OffloadDevice;
 use crate::util;
 use crate::util::parse_ret_i32;
 use crate::AsRawLibbpf;
+use crate::Btf;
 use crate::Error;
 use crate::ErrorExt as _;
 use crate::Link;
@@ -136,8 +141,14 @@ impl OpenMap {
         util::parse_ret(ret)
     }
 
-    pub fn set_map_flags(&mut self, flags: u32) -> Result<()> {
-        let ret = unsafe { libbpf_sys::bpf_map__set_map_flags(self.ptr.as_ptr(), flags) };
+    /// Retrieve the flags the map will be created with.
+    pub fn map_flags(&self) -> MapCreateFlags {
+        let flags = unsafe { libbpf_sys::bpf_map__map_flags(self.ptr.as_ptr()) };
+        MapCreateFlags::from_bits_retain(flags)
+    }
+
+    pub fn set_map_flags(&mut self, flags: MapCreateFlags) -> Result<()> {
+        let ret = unsafe { libbpf_sys::bpf_map__set_map_flags(self.ptr.as_ptr(), flags.bits()) };
         util::parse_ret(ret)
     }
 
@@ -170,12 +181,58 @@ impl OpenMap {
         util::parse_ret(ret)
     }
 
-    /// Reuse an fd for a BPF map
+    /// Retrieve the key size the map will be created with.
+    pub fn key_size(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__key_size(self.ptr.as_ptr()) }
+    }
+
+    /// Retrieve the value size the map will be created with.
+    pub fn value_size(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_map__value_size(self.ptr.as_ptr()) }
+    }
+
+    /// Reuse an already existing map, referenced by `fd`, for `self`
+    /// instead of creating a new one when the object gets loaded.
+    ///
+    /// This allows for a process to create maps (e.g., with a BPF
+    /// token) and hand them to others to load their objects with. The
+    /// existing map's type as well as key and value sizes have to match
+    /// those of `self`.
     pub fn reuse_fd(&self, fd: BorrowedFd<'_>) -> Result<()> {
+        let info = MapInfo::new(fd)?.info;
+        let () = self.check_reuse_compat(&info)?;
+
         let ret = unsafe { libbpf_sys::bpf_map__reuse_fd(self.ptr.as_ptr(), fd.as_raw_fd()) };
         util::parse_ret(ret)
     }
 
+    /// Check whether the map described by `info` can be used in place
+    /// of `self`.
+    fn check_reuse_compat(&self, info: &bpf_map_info) -> Result<()> {
+        let ty = MapType::from(info.type_);
+        if ty != self.map_type() {
+            return Err(Error::with_invalid_data(format!(
+                "map type mismatch: expected {:?}, got {ty:?}",
+                self.map_type()
+            )));
+        }
+        if info.key_size != self.key_size() {
+            return Err(Error::with_invalid_data(format!(
+                "map key size mismatch: expected {}, got {}",
+                self.key_size(),
+                info.key_size
+            )));
+        }
+        if info.value_size != self.value_size() {
+            return Err(Error::with_invalid_data(format!(
+                "map value size mismatch: expected {}, got {}",
+                self.value_size(),
+                info.value_size
+            )));
+        }
+        Ok(())
+    }
+
     /// Reuse an already-pinned map for `self`.
     pub fn reuse_pinned_map<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
         let cstring = util::path_to_cstring(path)?;
@@ -297,11 +354,22 @@ impl Map {
     /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// this map to bpffs.
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
         let ret = unsafe { libbpf_sys::bpf_map__pin(self.ptr.as_ptr(), path_ptr) };
-        util::parse_ret(ret)
+        util::parse_pin_ret(ret, path)
+    }
+
+    /// Pin this map in directory `dir` on bpffs, using the map's name as
+    /// file name.
+    ///
+    /// Returns the path the map got pinned at.
+    pub fn pin_in_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<PathBuf> {
+        let path = util::pin_path_in_dir(dir.as_ref(), self.name())?;
+        let () = self.pin(&path)?;
+        Ok(path)
     }
 
     /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
@@ -313,6 +381,13 @@ impl Map {
         util::parse_ret(ret)
     }
 
+    /// Unpin this map from directory `dir` on bpffs, as pinned by
+    /// [`pin_in_dir`][Self::pin_in_dir].
+    pub fn unpin_from_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let path = util::pin_path_in_dir(dir.as_ref(), self.name())?;
+        self.unpin(path)
+    }
+
     /// Attach a struct ops map
     pub fn attach_struct_ops(&self) -> Result<Link> {
         if self.map_type() != MapType::StructOps {
@@ -444,13 +519,35 @@ impl MapHandle {
         })
     }
 
-    /// Open a previously pinned map from its path.
+    /// Create a bpf map offloaded to the network device with index
+    /// `ifindex`, e.g., a SmartNIC.
     ///
-    /// # Panics
-    /// If the path contains null bytes.
+    /// This function behaves like [`MapHandle::create`], except that
+    /// `opts.map_ifindex` is overwritten with `ifindex`. Creation fails
+    /// unless the device's driver supports offloading maps of the given
+    /// type.
+    pub fn create_offloaded<T: AsRef<str>>(
+        map_type: MapType,
+        name: Option<T>,
+        key_size: u32,
+        value_size: u32,
+        max_entries: u32,
+        ifindex: u32,
+        opts: &libbpf_sys::bpf_map_create_opts,
+    ) -> Result<MapHandle> {
+        let opts = libbpf_sys::bpf_map_create_opts {
+            map_ifindex: ifindex,
+            ..*opts
+        };
+        Self::create(map_type, name, key_size, value_size, max_entries, &opts)
+    }
+
+    /// Open a previously pinned map from its path.
     pub fn from_pinned_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         fn inner(path: &Path) -> Result<MapHandle> {
-            let p = CString::new(path.as_os_str().as_bytes()).expect("path contained null bytes");
+            let p = CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+                Error::with_invalid_data(format!("invalid path {path:?}, has null bytes"))
+            })?;
             let fd = parse_ret_i32(unsafe {
                 // SAFETY
                 // p is never null since we allocated ourselves.
@@ -933,11 +1030,22 @@ impl MapHandle {
     /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// this map to bpffs.
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
         let ret = unsafe { libbpf_sys::bpf_obj_pin(self.fd.as_raw_fd(), path_ptr) };
-        util::parse_ret(ret)
+        util::parse_pin_ret(ret, path)
+    }
+
+    /// Pin this map in directory `dir` on bpffs, using the map's name as
+    /// file name.
+    ///
+    /// Returns the path the map got pinned at.
+    pub fn pin_in_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<PathBuf> {
+        let path = util::pin_path_in_dir(dir.as_ref(), self.name())?;
+        let () = self.pin(&path)?;
+        Ok(path)
     }
 
     /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
@@ -946,6 +1054,13 @@ impl MapHandle {
         remove_file(path).context("failed to remove pin map")
     }
 
+    /// Unpin this map from directory `dir` on bpffs, as pinned by
+    /// [`pin_in_dir`][Self::pin_in_dir].
+    pub fn unpin_from_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<()> {
+        let path = util::pin_path_in_dir(dir.as_ref(), self.name())?;
+        self.unpin(path)
+    }
+
     /// Returns an iterator over keys in this map
     ///
     /// Note that if the map is not stable (stable meaning no updates or deletes) during iteration,
@@ -954,6 +1069,125 @@ impl MapHandle {
     pub fn keys(&self) -> MapKeyIter<'_> {
         MapKeyIter::new(self, self.key_size())
     }
+
+    /// Insert an element that gets deleted again once the returned
+    /// [`MapEntryGuard`] is dropped.
+    ///
+    /// The element is inserted with [`MapFlags::NO_EXIST`], so that
+    /// existing elements, which the guard would otherwise remove
+    /// eventually, are never overwritten. This is useful for tying the
+    /// presence of an element to the lifetime of a user space object,
+    /// e.g., for registering the current process with a BPF program.
+    ///
+    /// `key` must have exactly [`MapHandle::key_size()`] elements. `value` must have exactly
+    /// [`MapHandle::value_size()`] elements.
+    pub fn insert_scoped(&self, key: &[u8], value: &[u8]) -> Result<MapEntryGuard<'_>> {
+        let () = self.update(key, value, MapFlags::NO_EXIST)?;
+        Ok(MapEntryGuard {
+            map: self,
+            key: Some(key.to_vec()),
+        })
+    }
+
+    /// Check that the map is of one of the given `types`.
+    fn ensure_map_type(&self, types: &[MapType]) -> Result<()> {
+        if !types.contains(&self.map_type()) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map `{}` of type {:?} does not support this operation",
+                    self.name(),
+                    self.map_type()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encode the value of a CPU or device map entry, made up of `first`
+    /// and an optional program to run on redirected frames.
+    fn redirect_value(&self, first: u32, prog: Option<BorrowedFd<'_>>) -> Result<Vec<u8>> {
+        let value_size = self.value_size() as usize;
+        let mut value = first.to_ne_bytes().to_vec();
+        if value_size >= 2 * mem::size_of::<u32>() {
+            // The kernel interprets a negative file descriptor as "no
+            // program".
+            let fd = prog.map_or(-1, |prog| prog.as_raw_fd());
+            let () = value.extend(fd.to_ne_bytes());
+        } else if prog.is_some() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "value of map `{}` is too small to hold a program",
+                    self.name()
+                ),
+            ));
+        }
+        let () = value.resize(value_size, 0);
+        Ok(value)
+    }
+
+    /// Decode the value of a CPU or device map entry, as reported by the
+    /// kernel, into its first member and the ID of the attached program,
+    /// if any.
+    fn parse_redirect_value(value: &[u8]) -> (u32, Option<u32>) {
+        let read = |idx: usize| {
+            let size = mem::size_of::<u32>();
+            value
+                .get(idx * size..(idx + 1) * size)
+                // SANITY: The slice is of the proper size.
+                .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+        };
+        (read(0).unwrap_or_default(), read(1).filter(|id| *id != 0))
+    }
+
+    /// Configure the queue of CPU `cpu` in a [`MapType::Cpumap`] map,
+    /// for redirecting XDP frames to it.
+    ///
+    /// Setting [`CpumapValue::prog`] requires the map's value to be
+    /// large enough to hold a `bpf_cpumap_val`.
+    pub fn update_cpumap(&self, cpu: u32, value: &CpumapValue<'_>) -> Result<()> {
+        let () = self.ensure_map_type(&[MapType::Cpumap])?;
+        let value = self.redirect_value(value.qsize, value.prog)?;
+        self.update(&cpu.to_ne_bytes(), &value, MapFlags::ANY)
+    }
+
+    /// Look up the configuration of CPU `cpu` in a [`MapType::Cpumap`]
+    /// map.
+    pub fn lookup_cpumap(&self, cpu: u32) -> Result<Option<CpumapEntry>> {
+        let () = self.ensure_map_type(&[MapType::Cpumap])?;
+        let entry = self
+            .lookup(&cpu.to_ne_bytes(), MapFlags::ANY)?
+            .map(|value| {
+                let (qsize, prog_id) = Self::parse_redirect_value(&value);
+                CpumapEntry { qsize, prog_id }
+            });
+        Ok(entry)
+    }
+
+    /// Set the network device at `key` in a [`MapType::Devmap`] or
+    /// [`MapType::DevmapHash`] map, for redirecting XDP frames to it.
+    ///
+    /// Setting [`DevmapValue::prog`] requires the map's value to be
+    /// large enough to hold a `bpf_devmap_val`.
+    pub fn update_devmap(&self, key: u32, value: &DevmapValue<'_>) -> Result<()> {
+        let () = self.ensure_map_type(&[MapType::Devmap, MapType::DevmapHash])?;
+        let value = self.redirect_value(value.ifindex, value.prog)?;
+        self.update(&key.to_ne_bytes(), &value, MapFlags::ANY)
+    }
+
+    /// Look up the network device at `key` in a [`MapType::Devmap`] or
+    /// [`MapType::DevmapHash`] map.
+    pub fn lookup_devmap(&self, key: u32) -> Result<Option<DevmapEntry>> {
+        let () = self.ensure_map_type(&[MapType::Devmap, MapType::DevmapHash])?;
+        let entry = self
+            .lookup(&key.to_ne_bytes(), MapFlags::ANY)?
+            .map(|value| {
+                let (ifindex, prog_id) = Self::parse_redirect_value(&value);
+                DevmapEntry { ifindex, prog_id }
+            });
+        Ok(entry)
+    }
 }
 
 impl AsFd for MapHandle {
@@ -963,6 +1197,71 @@ impl AsFd for MapHandle {
     }
 }
 
+impl TryFrom<MapHandle> for OwnedFd {
+    type Error = Error;
+
+    /// Transfer ownership of the handle's file descriptor out of it.
+    ///
+    /// This fails for handles not owning their file descriptor, e.g.,
+    /// because it is managed by an [`Object`][crate::Object].
+    fn try_from(handle: MapHandle) -> Result<Self> {
+        match handle.fd {
+            MapFd::Owned(o) => Ok(o),
+            MapFd::Borrowed(_) => Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map handle `{}` does not own its file descriptor",
+                    handle.name
+                ),
+            )),
+        }
+    }
+}
+
+/// The configuration of a CPU in a [`MapType::Cpumap`] map, as used by
+/// [`MapHandle::update_cpumap`].
+#[derive(Clone, Copy, Debug)]
+pub struct CpumapValue<'fd> {
+    /// The size of the queue of frames redirected to the CPU.
+    pub qsize: u32,
+    /// An XDP program (of attach type [`ProgramAttachType::XdpCpumap`][crate::ProgramAttachType::XdpCpumap])
+    /// to run on frames once they arrive on the CPU.
+    pub prog: Option<BorrowedFd<'fd>>,
+}
+
+/// The configuration of a CPU in a [`MapType::Cpumap`] map, as reported
+/// by [`MapHandle::lookup_cpumap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpumapEntry {
+    /// The size of the queue of frames redirected to the CPU.
+    pub qsize: u32,
+    /// The ID of the XDP program run on frames arriving on the CPU, if
+    /// any.
+    pub prog_id: Option<u32>,
+}
+
+/// A network device in a [`MapType::Devmap`] or [`MapType::DevmapHash`]
+/// map, as used by [`MapHandle::update_devmap`].
+#[derive(Clone, Copy, Debug)]
+pub struct DevmapValue<'fd> {
+    /// The index of the network device.
+    pub ifindex: u32,
+    /// An XDP program (of attach type [`ProgramAttachType::XdpDevmap`][crate::ProgramAttachType::XdpDevmap])
+    /// to run on frames before they are transmitted on the device.
+    pub prog: Option<BorrowedFd<'fd>>,
+}
+
+/// A network device in a [`MapType::Devmap`] or [`MapType::DevmapHash`]
+/// map, as reported by [`MapHandle::lookup_devmap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DevmapEntry {
+    /// The index of the network device.
+    pub ifindex: u32,
+    /// The ID of the XDP program run on frames before transmission, if
+    /// any.
+    pub prog_id: Option<u32>,
+}
+
 bitflags! {
     /// Flags to configure [`Map`] operations.
     #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
@@ -978,6 +1277,48 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Flags to configure the creation of a [`Map`].
+    ///
+    /// These flags can be set on an [`OpenMap`] before load or, converted
+    /// via [`MapCreateFlags::bits`], as part of the
+    /// [`bpf_map_create_opts`][libbpf_sys::bpf_map_create_opts] provided to
+    /// [`MapHandle::create`].
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+    pub struct MapCreateFlags: u32 {
+        /// See [`libbpf_sys::BPF_F_NO_PREALLOC`].
+        const NO_PREALLOC    = libbpf_sys::BPF_F_NO_PREALLOC as _;
+        /// See [`libbpf_sys::BPF_F_NO_COMMON_LRU`].
+        const NO_COMMON_LRU  = libbpf_sys::BPF_F_NO_COMMON_LRU as _;
+        /// See [`libbpf_sys::BPF_F_NUMA_NODE`].
+        const NUMA_NODE      = libbpf_sys::BPF_F_NUMA_NODE as _;
+        /// See [`libbpf_sys::BPF_F_RDONLY`].
+        const RDONLY         = libbpf_sys::BPF_F_RDONLY as _;
+        /// See [`libbpf_sys::BPF_F_WRONLY`].
+        const WRONLY         = libbpf_sys::BPF_F_WRONLY as _;
+        /// See [`libbpf_sys::BPF_F_STACK_BUILD_ID`].
+        const STACK_BUILD_ID = libbpf_sys::BPF_F_STACK_BUILD_ID as _;
+        /// See [`libbpf_sys::BPF_F_ZERO_SEED`].
+        const ZERO_SEED      = libbpf_sys::BPF_F_ZERO_SEED as _;
+        /// See [`libbpf_sys::BPF_F_RDONLY_PROG`].
+        const RDONLY_PROG    = libbpf_sys::BPF_F_RDONLY_PROG as _;
+        /// See [`libbpf_sys::BPF_F_WRONLY_PROG`].
+        const WRONLY_PROG    = libbpf_sys::BPF_F_WRONLY_PROG as _;
+        /// See [`libbpf_sys::BPF_F_CLONE`].
+        const CLONE          = libbpf_sys::BPF_F_CLONE as _;
+        /// See [`libbpf_sys::BPF_F_MMAPABLE`].
+        const MMAPABLE       = libbpf_sys::BPF_F_MMAPABLE as _;
+        /// See [`libbpf_sys::BPF_F_PRESERVE_ELEMS`].
+        const PRESERVE_ELEMS = libbpf_sys::BPF_F_PRESERVE_ELEMS as _;
+        /// See [`libbpf_sys::BPF_F_INNER_MAP`].
+        const INNER_MAP      = libbpf_sys::BPF_F_INNER_MAP as _;
+        /// See [`libbpf_sys::BPF_F_SEGV_ON_FAULT`].
+        const SEGV_ON_FAULT  = libbpf_sys::BPF_F_SEGV_ON_FAULT as _;
+        /// See [`libbpf_sys::BPF_F_NO_USER_CONV`].
+        const NO_USER_CONV   = libbpf_sys::BPF_F_NO_USER_CONV as _;
+    }
+}
+
 /// Type of a [`Map`]. Maps to `enum bpf_map_type` in kernel uapi.
 // If you add a new per-cpu map, also update `is_percpu`.
 #[non_exhaustive]
@@ -985,6 +1326,7 @@ bitflags! {
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MapType {
     Unspec = 0,
     Hash,
@@ -1038,7 +1380,7 @@ impl MapType {
 
     /// Returns if the map is keyless map type as per documentation of libbpf
     /// Keyless map types are: Queues, Stacks and Bloom Filters
-    fn is_keyless(&self) -> bool {
+    pub(crate) fn is_keyless(&self) -> bool {
         matches!(self, MapType::Queue | MapType::Stack | MapType::BloomFilter)
     }
 
@@ -1109,6 +1451,47 @@ impl From<MapType> for u32 {
     }
 }
 
+/// A guard deleting a map element once dropped, as created by
+/// [`MapHandle::insert_scoped`].
+///
+/// Errors deleting the element on drop are ignored. Use
+/// [`MapEntryGuard::delete`] to observe them.
+#[derive(Debug)]
+#[must_use = "the map element is deleted immediately if the guard is not used"]
+pub struct MapEntryGuard<'map> {
+    map: &'map MapHandle,
+    /// The element's key, `None` once the guard got disarmed.
+    key: Option<Vec<u8>>,
+}
+
+impl MapEntryGuard<'_> {
+    /// Retrieve the key of the guarded element.
+    pub fn key(&self) -> &[u8] {
+        // SANITY: The key is only removed when the guard is consumed.
+        self.key.as_deref().unwrap()
+    }
+
+    /// Delete the guarded element right away.
+    pub fn delete(mut self) -> Result<()> {
+        // SANITY: The key is only removed when the guard is consumed.
+        let key = self.key.take().unwrap();
+        self.map.delete(&key)
+    }
+
+    /// Disarm the guard, keeping the element in the map.
+    pub fn keep(mut self) {
+        self.key = None;
+    }
+}
+
+impl Drop for MapEntryGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            let _result = self.map.delete(key);
+        }
+    }
+}
+
 /// An iterator over the keys of a [`Map`].
 #[derive(Debug)]
 pub struct MapKeyIter<'a> {
@@ -1200,6 +1583,110 @@ impl MapInfo {
     pub fn flags(&self) -> MapFlags {
         MapFlags::from_bits_truncate(self.info.map_flags as u64)
     }
+
+    /// Get the map type specific extra data, e.g., the number of hash
+    /// functions used by a bloom filter.
+    #[inline]
+    pub fn map_extra(&self) -> u64 {
+        self.info.map_extra
+    }
+
+    /// Get the index of the network interface the map is offloaded to,
+    /// or zero if it is not offloaded.
+    #[inline]
+    pub fn ifindex(&self) -> u32 {
+        self.info.ifindex
+    }
+
+    /// Get the ID of the map's BTF, or zero if it has none.
+    #[inline]
+    pub fn btf_id(&self) -> u32 {
+        self.info.btf_id
+    }
+
+    /// Get the ID of the key's type in the map's BTF, or zero if not
+    /// available.
+    #[inline]
+    pub fn btf_key_type_id(&self) -> u32 {
+        self.info.btf_key_type_id
+    }
+
+    /// Get the ID of the value's type in the map's BTF, or zero if not
+    /// available.
+    #[inline]
+    pub fn btf_value_type_id(&self) -> u32 {
+        self.info.btf_value_type_id
+    }
+
+    /// Load the map's BTF from the kernel, if it has any.
+    ///
+    /// The BTF can be used for resolving the key and value types, via
+    /// [`MapInfo::key_btf`] and [`MapInfo::value_btf`].
+    pub fn btf(&self) -> Result<Option<Btf<'static>>> {
+        if self.info.btf_id == 0 {
+            return Ok(None);
+        }
+        Btf::from_btf_id(self.info.btf_id).map(Some)
+    }
+
+    /// Resolve the type of the map's keys in `btf`, as retrieved by
+    /// [`MapInfo::btf`].
+    pub fn key_btf<'btf>(&self, btf: &'btf Btf<'_>) -> Option<BtfType<'btf>> {
+        resolve_btf_type(btf, self.info.btf_key_type_id)
+    }
+
+    /// Resolve the type of the map's values in `btf`, as retrieved by
+    /// [`MapInfo::btf`].
+    pub fn value_btf<'btf>(&self, btf: &'btf Btf<'_>) -> Option<BtfType<'btf>> {
+        resolve_btf_type(btf, self.info.btf_value_type_id)
+    }
+
+    /// Retrieve the network device the map is offloaded to, if any.
+    pub fn offload_device(&self) -> Option<OffloadDevice> {
+        OffloadDevice::from_raw(self.info.ifindex, self.info.netns_dev, self.info.netns_ino)
+    }
+}
+
+/// Look up the type with ID `type_id` in `btf`, with an ID of zero
+/// indicating the absence of type information.
+pub(crate) fn resolve_btf_type<'btf>(btf: &'btf Btf<'_>, type_id: u32) -> Option<BtfType<'btf>> {
+    if type_id == 0 {
+        return None;
+    }
+    btf.type_by_id(TypeId::from(type_id))
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MapInfo {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct as _;
+
+        let info = &self.info;
+        let name = util::c_char_slice_to_cstr(&info.name)
+            .map(CStr::to_string_lossy)
+            .unwrap_or_default();
+
+        let mut s = serializer.serialize_struct("MapInfo", 15)?;
+        let () = s.serialize_field("name", &name)?;
+        let () = s.serialize_field("ty", &self.map_type())?;
+        let () = s.serialize_field("id", &info.id)?;
+        let () = s.serialize_field("key_size", &info.key_size)?;
+        let () = s.serialize_field("value_size", &info.value_size)?;
+        let () = s.serialize_field("max_entries", &info.max_entries)?;
+        let () = s.serialize_field("map_flags", &info.map_flags)?;
+        let () = s.serialize_field("ifindex", &info.ifindex)?;
+        let () = s.serialize_field("btf_vmlinux_value_type_id", &info.btf_vmlinux_value_type_id)?;
+        let () = s.serialize_field("netns_dev", &info.netns_dev)?;
+        let () = s.serialize_field("netns_ino", &info.netns_ino)?;
+        let () = s.serialize_field("btf_id", &info.btf_id)?;
+        let () = s.serialize_field("btf_key_type_id", &info.btf_key_type_id)?;
+        let () = s.serialize_field("btf_value_type_id", &info.btf_value_type_id)?;
+        let () = s.serialize_field("map_extra", &info.map_extra)?;
+        s.end()
+    }
 }
 
 #[cfg(test)]
@@ -1208,6 +1695,8 @@ mod tests {
 
     use std::mem::discriminant;
 
+    use crate::ObjectBuilder;
+
     #[test]
     fn map_type() {
         use MapType::*;
@@ -1251,4 +1740,35 @@ mod tests {
             assert_eq!(discriminant(&t), discriminant(&MapType::from(t as u32)));
         }
     }
+
+    /// Check that we detect incompatible maps when attempting to reuse
+    /// them.
+    #[test]
+    fn map_reuse_compat() {
+        let path = util::test_object_path("runqslower.bpf.o");
+        let mut open_obj = ObjectBuilder::default().open_file(path).unwrap();
+        let map = open_obj.map_mut("start").unwrap();
+        assert_eq!(map.map_type(), MapType::Hash);
+        assert_eq!(map.key_size(), 4);
+        assert_eq!(map.value_size(), 8);
+
+        // SAFETY: `bpf_map_info` is valid for any bit pattern.
+        let mut info = unsafe { mem::zeroed::<bpf_map_info>() };
+        info.type_ = MapType::Hash as _;
+        info.key_size = 4;
+        info.value_size = 8;
+        let () = map.check_reuse_compat(&info).unwrap();
+
+        info.value_size = 4;
+        let err = map.check_reuse_compat(&info).unwrap_err();
+        assert!(err.to_string().contains("value size"), "{err}");
+
+        info.key_size = 8;
+        let err = map.check_reuse_compat(&info).unwrap_err();
+        assert!(err.to_string().contains("key size"), "{err}");
+
+        info.type_ = MapType::Array as _;
+        let err = map.check_reuse_compat(&info).unwrap_err();
+        assert!(err.to_string().contains("type"), "{err}");
+    }
 }
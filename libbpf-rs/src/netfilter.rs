@@ -0,0 +1,146 @@
+//! Netfilter related definitions and functionality.
+//!
+//! BPF programs of type [`ProgramType::Netfilter`][crate::ProgramType::Netfilter]
+//! can be attached to netfilter hooks using
+//! [`Program::attach_netfilter_with_opts`][crate::Program::attach_netfilter_with_opts].
+//! Multiple programs may be attached to the same hook, in which case
+//! they are run in order of their priority. [`attached_links`] can be
+//! used for checking which priorities are already taken.
+
+use std::mem::size_of;
+
+use crate::query::LinkInfo;
+use crate::query::LinkInfoIter;
+use crate::query::LinkTypeInfo;
+
+/// Unspecified protocol family.
+pub const NFPROTO_UNSPEC: i32 = 0;
+/// Netfilter protocol family covering both IPv4 and IPv6.
+pub const NFPROTO_INET: i32 = 1;
+/// Netfilter protocol family for IPv4.
+pub const NFPROTO_IPV4: i32 = 2;
+/// Netfilter protocol family for ARP.
+pub const NFPROTO_ARP: i32 = 3;
+/// Netfilter protocol family for the netdev hooks.
+pub const NFPROTO_NETDEV: i32 = 5;
+/// Netfilter protocol family for bridging.
+pub const NFPROTO_BRIDGE: i32 = 7;
+/// Netfilter protocol family for IPv6.
+pub const NFPROTO_IPV6: i32 = 10;
+
+/// Netfilter hook for packets before routing decisions are made.
+pub const NF_INET_PRE_ROUTING: i32 = 0;
+/// Netfilter hook for packets destined to the local system.
+pub const NF_INET_LOCAL_IN: i32 = 1;
+/// Netfilter hook for packets being forwarded.
+pub const NF_INET_FORWARD: i32 = 2;
+/// Netfilter hook for locally generated packets.
+pub const NF_INET_LOCAL_OUT: i32 = 3;
+/// Netfilter hook for packets about to leave the system.
+pub const NF_INET_POST_ROUTING: i32 = 4;
+/// Netfilter hook for packets on ingress, before any other hook.
+pub const NF_INET_INGRESS: i32 = 5;
+
+/// The lowest priority value accepted for BPF programs. Netfilter
+/// reserves `i32::MIN` for its own purposes.
+pub const NF_BPF_PRI_FIRST: i32 = i32::MIN + 1;
+/// The highest priority value accepted for BPF programs. Netfilter
+/// reserves `i32::MAX` for its own purposes.
+pub const NF_BPF_PRI_LAST: i32 = i32::MAX - 1;
+
+/// Options to be provided when attaching a program to a netfilter hook.
+#[derive(Clone, Debug, Default)]
+pub struct NetfilterOpts {
+    /// Protocol family to attach to, e.g., [`NFPROTO_IPV4`].
+    pub protocol_family: i32,
+    /// The hook to attach to, e.g., [`NF_INET_LOCAL_IN`].
+    pub hooknum: i32,
+    /// The priority of the program relative to other netfilter hooks,
+    /// with lower values running earlier. No two BPF programs may be
+    /// attached to the same hook with the same priority.
+    pub priority: i32,
+    /// Enable IP packet defragmentation before the program is run.
+    pub ip_defrag: bool,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl From<NetfilterOpts> for libbpf_sys::bpf_netfilter_opts {
+    fn from(opts: NetfilterOpts) -> Self {
+        let NetfilterOpts {
+            protocol_family,
+            hooknum,
+            priority,
+            ip_defrag,
+            _non_exhaustive,
+        } = opts;
+
+        let flags = if ip_defrag {
+            libbpf_sys::BPF_F_NETFILTER_IP_DEFRAG
+        } else {
+            0
+        };
+
+        #[allow(clippy::needless_update)]
+        libbpf_sys::bpf_netfilter_opts {
+            sz: size_of::<Self>() as _,
+            pf: protocol_family as u32,
+            hooknum: hooknum as u32,
+            priority,
+            flags,
+            ..Default::default()
+        }
+    }
+}
+
+/// Retrieve information about all BPF links currently attached to the
+/// netfilter hook `hooknum` of the given protocol family, sorted by
+/// priority.
+///
+/// This function can be used for finding a priority not yet in use
+/// before attaching a program.
+pub fn attached_links(protocol_family: i32, hooknum: i32) -> Vec<LinkInfo> {
+    let mut links = LinkInfoIter::default()
+        .filter_map(|link| match &link.info {
+            LinkTypeInfo::Netfilter(info)
+                if info.protocol_family == protocol_family && info.hooknum == hooknum =>
+            {
+                Some((info.priority, link))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let () = links.sort_by_key(|(priority, _link)| *priority);
+    links.into_iter().map(|(_priority, link)| link).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that [`NetfilterOpts`] are converted to their `libbpf`
+    /// counterpart correctly.
+    #[test]
+    fn netfilter_opts_conversion() {
+        let opts = NetfilterOpts {
+            protocol_family: NFPROTO_IPV6,
+            hooknum: NF_INET_LOCAL_OUT,
+            priority: -42,
+            ip_defrag: true,
+            ..Default::default()
+        };
+        let opts = libbpf_sys::bpf_netfilter_opts::from(opts);
+        assert_eq!(
+            opts.sz,
+            size_of::<libbpf_sys::bpf_netfilter_opts>() as libbpf_sys::size_t
+        );
+        assert_eq!(opts.pf, NFPROTO_IPV6 as u32);
+        assert_eq!(opts.hooknum, NF_INET_LOCAL_OUT as u32);
+        assert_eq!(opts.priority, -42);
+        assert_eq!(opts.flags, libbpf_sys::BPF_F_NETFILTER_IP_DEFRAG);
+
+        let opts = libbpf_sys::bpf_netfilter_opts::from(NetfilterOpts::default());
+        assert_eq!(opts.flags, 0);
+    }
+}
@@ -1,23 +1,49 @@
 use core::ffi::c_void;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs::File;
+use std::io;
+use std::io::Read;
 use std::mem;
+use std::os::fd::AsFd as _;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
+use std::os::fd::FromRawFd as _;
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::FileExt as _;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr;
 use std::ptr::NonNull;
+use std::slice;
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(feature = "gzip")]
+use flate2::read::MultiGzDecoder;
 
 use crate::error::IntoError as _;
+use crate::query::LinkInfoIter;
 use crate::set_print;
 use crate::util;
 use crate::Btf;
 use crate::Error;
+use crate::ErrorExt as _;
+use crate::ErrorKind;
+use crate::LightSkel;
 use crate::Map;
 use crate::OpenMap;
 use crate::OpenProgram;
 use crate::PrintLevel;
 use crate::Program;
 use crate::Result;
+use crate::RingBuffer;
 
 /// A trait implemented for types that are thin wrappers around `libbpf` types.
 ///
@@ -39,6 +65,106 @@ pub trait AsRawLibbpf {
     fn as_libbpf_object(&self) -> NonNull<Self::LibbpfType>;
 }
 
+/// The size of the buffer used for capturing the verifier log of each
+/// program while loading an [`OpenObject`].
+const LOAD_LOG_BUF_SIZE: usize = 64 * 1024;
+
+/// An error describing a program that failed to load as part of an
+/// object, as reported by [`OpenObject::load`].
+///
+/// The error can be retrieved from the returned [`Error`] by means of
+/// [`Error::downcast_ref`]:
+/// ```no_run
+/// # use libbpf_rs::OpenObject;
+/// use libbpf_rs::ProgramLoadError;
+///
+/// # let open_obj: OpenObject = todo!();
+/// if let Err(err) = open_obj.load() {
+///     if let Some(prog_err) = err.downcast_ref::<ProgramLoadError>() {
+///         eprintln!("{prog_err}:\n{}", prog_err.log());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ProgramLoadError {
+    name: String,
+    section: String,
+    log: String,
+    source: io::Error,
+}
+
+impl ProgramLoadError {
+    pub(crate) fn new(name: String, section: String, log: String, source: io::Error) -> Self {
+        Self {
+            name,
+            section,
+            log,
+            source,
+        }
+    }
+
+    /// Retrieve the OS error code the load operation failed with.
+    pub(crate) fn raw_os_error(&self) -> Option<i32> {
+        self.source.raw_os_error()
+    }
+
+    /// Retrieve the name of the program that failed to load.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Retrieve the name of the ELF section the program is contained
+    /// in.
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    /// Retrieve the verifier log captured while loading the program.
+    ///
+    /// Overly long logs are truncated at the front by the kernel, so
+    /// that their tail, which typically contains the reason for the
+    /// rejection, is retained.
+    pub fn log(&self) -> &str {
+        &self.log
+    }
+}
+
+impl Display for ProgramLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "failed to load program `{}` (section `{}`)",
+            self.name, self.section
+        )
+    }
+}
+
+impl error::Error for ProgramLoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// The memory consumption of a loaded [`Object`], as reported by the
+/// kernel.
+///
+/// Values are in bytes and reflect what is charged against the memlock
+/// limit (or the memory cgroup) of the loading process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The memory used by all maps of the object.
+    pub maps: u64,
+    /// The memory used by all loaded programs of the object.
+    pub progs: u64,
+}
+
+impl MemoryUsage {
+    /// The total memory used by the object.
+    pub fn total(&self) -> u64 {
+        self.maps + self.progs
+    }
+}
+
 /// Builder for creating an [`OpenObject`]. Typically the entry point into libbpf-rs.
 #[derive(Debug)]
 pub struct ObjectBuilder {
@@ -144,6 +270,81 @@ impl ObjectBuilder {
         })
         .and_then(|ptr| unsafe { OpenObject::new(ptr) })
     }
+
+    /// Open an object from the contents of the file referred to by `fd`,
+    /// e.g., a sealed `memfd`.
+    ///
+    /// The contents are read starting at offset zero, irrespective of
+    /// the file descriptor's current position, which is left untouched.
+    /// Because no path is available, the object's name is derived by
+    /// `libbpf` unless set explicitly via [`ObjectBuilder::name`].
+    pub fn open_fd(&mut self, fd: BorrowedFd<'_>) -> Result<OpenObject> {
+        let file = File::from(
+            fd.try_clone_to_owned()
+                .context("failed to duplicate file descriptor")?,
+        );
+        let mut mem = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let count = match file.read_at(&mut buf, mem.len() as u64) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.context("failed to read object")),
+            };
+            let () = mem.extend_from_slice(&buf[..count]);
+        }
+        self.open_memory(&mem)
+    }
+
+    /// Open an object from the data provided by `reader`, e.g., an
+    /// embedded and compressed asset being decompressed on the fly.
+    ///
+    /// The reader is consumed until end of file and its data buffered
+    /// internally. Because no path is available, the object's name is
+    /// derived by `libbpf` unless set explicitly via
+    /// [`ObjectBuilder::name`].
+    pub fn open_reader<R: Read>(&mut self, mut reader: R) -> Result<OpenObject> {
+        let mut mem = Vec::new();
+        let _count = reader
+            .read_to_end(&mut mem)
+            .context("failed to read object")?;
+        self.open_memory(&mem)
+    }
+
+    /// Open an object from compressed memory, e.g., an asset embedded
+    /// via [`include_bytes!`].
+    ///
+    /// The compression format is detected based on the data's magic
+    /// number. Supported are gzip (with the `gzip` feature) and zstd
+    /// (with the `zstd` feature). Because no path is available, the
+    /// object's name is derived by `libbpf` unless set explicitly via
+    /// [`ObjectBuilder::name`].
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    pub fn open_compressed_memory(&mut self, mem: &[u8]) -> Result<OpenObject> {
+        const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+        const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+        match mem {
+            #[cfg(feature = "gzip")]
+            _ if mem.starts_with(GZIP_MAGIC) => self.open_reader(MultiGzDecoder::new(mem)),
+            #[cfg(feature = "zstd")]
+            _ if mem.starts_with(ZSTD_MAGIC) => {
+                let decoder =
+                    zstd::Decoder::with_buffer(mem).context("failed to create zstd decoder")?;
+                self.open_reader(decoder)
+            }
+            _ if mem.starts_with(GZIP_MAGIC) || mem.starts_with(ZSTD_MAGIC) => {
+                Err(Error::with_io_error(
+                    io::ErrorKind::Unsupported,
+                    "support for object compression format is not enabled",
+                ))
+            }
+            _ => Err(Error::with_invalid_data(
+                "object data is not in a known compression format",
+            )),
+        }
+    }
 }
 
 /// Represents an opened (but not yet loaded) BPF object file.
@@ -315,9 +516,119 @@ impl OpenObject {
         self.progs.values_mut()
     }
 
+    /// Generate a loader program for this object instead of loading it,
+    /// yielding a [`LightSkel`].
+    ///
+    /// Running the loader program (via [`LightSkel::load`]) creates the
+    /// object's maps and those programs set to be loaded automatically.
+    /// Configuration of the object, e.g., of map sizes or the initial
+    /// contents of global data, is captured in the loader program.
+    pub fn gen_loader(self) -> Result<LightSkel> {
+        let mut maps = Vec::new();
+        let mut map = ptr::null();
+        loop {
+            map = unsafe { libbpf_sys::bpf_object__next_map(self.ptr.as_ptr(), map) };
+            if map.is_null() {
+                break;
+            }
+            let name = unsafe { CStr::from_ptr(libbpf_sys::bpf_map__name(map)) };
+            let () = maps.push(name.to_string_lossy().into_owned());
+        }
+
+        let mut progs = Vec::new();
+        let mut prog = ptr::null_mut();
+        loop {
+            prog = unsafe { libbpf_sys::bpf_object__next_program(self.ptr.as_ptr(), prog) };
+            if prog.is_null() {
+                break;
+            }
+            if unsafe { libbpf_sys::bpf_program__autoload(prog) } {
+                let name = unsafe { CStr::from_ptr(libbpf_sys::bpf_program__name(prog)) };
+                let () = progs.push(name.to_string_lossy().into_owned());
+            }
+        }
+
+        let mut opts = libbpf_sys::gen_loader_opts {
+            sz: mem::size_of::<libbpf_sys::gen_loader_opts>() as _,
+            ..Default::default()
+        };
+        let ret = unsafe { libbpf_sys::bpf_object__gen_loader(self.ptr.as_ptr(), &mut opts) };
+        let () = util::parse_ret(ret).context("failed to enable loader generation")?;
+        let ret = unsafe { libbpf_sys::bpf_object__load(self.ptr.as_ptr()) };
+        let () = util::parse_ret(ret).context("failed to generate loader program")?;
+
+        // SAFETY: On success, `libbpf` points `insns` and `data` to
+        //         buffers of the reported sizes, which are owned by the
+        //         object and stay valid until it is closed.
+        let (insns, data) = unsafe {
+            (
+                slice::from_raw_parts(opts.insns.cast::<u8>(), opts.insns_sz as _),
+                slice::from_raw_parts(opts.data.cast::<u8>(), opts.data_sz as _),
+            )
+        };
+        LightSkel::new(insns, data, maps, progs)
+    }
+
     /// Load the maps and programs contained in this BPF object into the system.
+    ///
+    /// If a program fails to load, the returned error contains a
+    /// [`ProgramLoadError`] describing it.
     pub fn load(self) -> Result<Object> {
+        // Capture the verifier log of all programs that do not have a
+        // log buffer configured already, so that we can report details
+        // about a failing one. `libbpf` only populates it on failure,
+        // unless a log level was set explicitly.
+        let mut logs = Vec::new();
+        let mut prog = ptr::null_mut();
+        loop {
+            prog = unsafe { libbpf_sys::bpf_object__next_program(self.ptr.as_ptr(), prog) };
+            if prog.is_null() {
+                break;
+            }
+
+            let mut size = 0;
+            let buf = unsafe { libbpf_sys::bpf_program__log_buf(prog, &mut size) };
+            if !buf.is_null() || !unsafe { libbpf_sys::bpf_program__autoload(prog) } {
+                continue;
+            }
+
+            let mut buf = vec![0u8; LOAD_LOG_BUF_SIZE];
+            let ret = unsafe {
+                libbpf_sys::bpf_program__set_log_buf(prog, buf.as_mut_ptr().cast(), buf.len() as _)
+            };
+            let () = util::parse_ret(ret)?;
+            let () = logs.push((prog, buf));
+        }
+
         let ret = unsafe { libbpf_sys::bpf_object__load(self.ptr.as_ptr()) };
+
+        for (prog, _buf) in &logs {
+            // Don't leave dangling pointers to our buffers behind.
+            let _ret = unsafe { libbpf_sys::bpf_program__set_log_buf(*prog, ptr::null_mut(), 0) };
+        }
+
+        if ret < 0 {
+            // Programs are loaded in order and loading stops at the
+            // first failure, so the last program with a log is the one
+            // that failed.
+            let failed = logs.iter().rev().find(|(_prog, buf)| buf[0] != 0);
+            if let Some((prog, buf)) = failed {
+                let name = unsafe { CStr::from_ptr(libbpf_sys::bpf_program__name(*prog)) };
+                let section =
+                    unsafe { CStr::from_ptr(libbpf_sys::bpf_program__section_name(*prog)) };
+                let log = CStr::from_bytes_until_nul(buf).unwrap_or_default();
+                let source = io::Error::from_raw_os_error(-ret);
+                let kind = source.kind();
+                let err = ProgramLoadError::new(
+                    name.to_string_lossy().into_owned(),
+                    section.to_string_lossy().into_owned(),
+                    log.to_string_lossy().into_owned(),
+                    source,
+                );
+                let err = io::Error::new(kind, err);
+                return Err(Error::from(err).context("failed to load BPF object"));
+            }
+        }
         let () = util::parse_ret(ret)?;
 
         let obj = unsafe { Object::from_ptr(self.take_ptr())? };
@@ -344,6 +655,22 @@ impl Drop for OpenObject {
     }
 }
 
+/// The interval at which [`Object::shutdown`] checks whether ring
+/// buffers got drained.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Options for a coordinated teardown via [`Object::shutdown`].
+#[derive(Debug, Default)]
+pub struct ShutdownOpts<'rb> {
+    /// Ring buffers to drain once all programs got detached, i.e., to
+    /// consume remaining samples from.
+    pub ring_buffers: Vec<&'rb RingBuffer<'rb>>,
+    /// Whether to unpin all maps pinned at their pin path.
+    pub unpin_maps: bool,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
 /// Represents a loaded BPF object file.
 ///
 /// An `Object` is logically in charge of all the contained [`Program`]s and [`Map`]s as well as
@@ -353,6 +680,23 @@ impl Drop for OpenObject {
 ///
 /// Note that this is an explanation of the motivation -- Rust's lifetime system should already be
 /// enforcing this invariant.
+///
+/// # Teardown
+/// Dropping an `Object` closes the file descriptors of all its maps and
+/// programs. The kernel only destroys them once the last reference is
+/// gone, though: a [`Link`][crate::Link] keeps its program attached (and
+/// the program and the maps it uses alive) until the link is dropped or
+/// detached, irrespective of whether the `Object` is still around, and
+/// pinned entities survive the process altogether. Samples left in ring
+/// buffers are lost once the corresponding [`RingBuffer`] is dropped.
+///
+/// Hence, to stop all activity in an orderly fashion:
+/// 1. detach all programs, e.g., via [`Object::detach_all`]
+/// 2. consume samples still pending in ring buffers
+/// 3. unpin maps that should not outlive the process
+/// 4. drop links, ring buffers, and the `Object` itself, in any order
+///
+/// [`Object::shutdown`] takes care of the first three steps.
 #[derive(Debug)]
 pub struct Object {
     ptr: NonNull<libbpf_sys::bpf_object>,
@@ -431,6 +775,33 @@ impl Object {
         Btf::from_bpf_object(unsafe { &*self.ptr.as_ptr() })
     }
 
+    /// Retrieve the memory consumption of all maps and programs of this
+    /// object, as reported by the kernel.
+    ///
+    /// Programs that were not loaded are not taken into account.
+    pub fn memory_usage(&self) -> Result<MemoryUsage> {
+        let mut usage = MemoryUsage::default();
+        for map in self.maps.values() {
+            usage.maps += util::fd_memlock(map.as_fd())
+                .with_context(|| format!("failed to query memory usage of map `{}`", map.name()))?;
+        }
+
+        for prog in self.progs.values() {
+            let fd = unsafe { libbpf_sys::bpf_program__fd(prog.ptr.as_ptr()) };
+            if fd < 0 {
+                continue;
+            }
+
+            usage.progs += util::fd_memlock(prog.as_fd()).with_context(|| {
+                format!(
+                    "failed to query memory usage of program `{}`",
+                    prog.name().to_string_lossy()
+                )
+            })?;
+        }
+        Ok(usage)
+    }
+
     /// Get a reference to `Map` with the name `name`, if one exists.
     pub fn map<T: AsRef<str>>(&self, name: T) -> Option<&Map> {
         self.maps.get(name.as_ref())
@@ -474,6 +845,110 @@ impl Object {
     pub fn progs_iter_mut(&mut self) -> impl Iterator<Item = &mut Program> {
         self.progs.values_mut()
     }
+
+    /// Detach all BPF links referring to any of the object's programs,
+    /// returning the number of links detached.
+    ///
+    /// This covers links no matter who created them, including pinned
+    /// ones and those owned by [`Link`][crate::Link] objects, which stay
+    /// valid but become defunct. Links that do not support detachment,
+    /// such as those of iterators, are left alone.
+    pub fn detach_all(&self) -> Result<usize> {
+        let mut prog_ids = HashSet::new();
+        for prog in self.progs.values() {
+            let fd = unsafe { libbpf_sys::bpf_program__fd(prog.as_libbpf_object().as_ptr()) };
+            if fd < 0 {
+                continue;
+            }
+
+            let id = Program::get_id_by_fd(prog.as_fd())?;
+            let _inserted = prog_ids.insert(id);
+        }
+
+        let mut count = 0;
+        for link in LinkInfoIter::default() {
+            if !prog_ids.contains(&link.prog_id) {
+                continue;
+            }
+
+            // SAFETY: `bpf_link_get_fd_by_id` is always safe to call.
+            let fd = unsafe { libbpf_sys::bpf_link_get_fd_by_id(link.id) };
+            let fd = match util::parse_ret_i32(fd) {
+                Ok(fd) => fd,
+                // The link may have gone away in the meantime.
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.context(format!("failed to open link {}", link.id))),
+            };
+            // SAFETY: `fd` is a freshly created file descriptor that we own.
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            // SAFETY: `bpf_link_detach` is always safe to call.
+            let ret = unsafe { libbpf_sys::bpf_link_detach(fd.as_raw_fd()) };
+            match util::parse_ret(ret) {
+                Ok(()) => count += 1,
+                Err(err) if err.kind() == ErrorKind::Unsupported => (),
+                Err(err) => return Err(err.context(format!("failed to detach link {}", link.id))),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Tear down all activity of the object in a coordinated fashion.
+    ///
+    /// This method detaches all links of the object's programs (see
+    /// [`Object::detach_all`]), then consumes the samples still pending in
+    /// the ring buffers provided in `opts`, waiting at most `timeout` for
+    /// them to drain, and finally unpins maps, if requested.
+    ///
+    /// An error of kind [`ErrorKind::TimedOut`] is reported if ring
+    /// buffers still contain data after `timeout` expired, e.g., because
+    /// programs that were running while getting detached did not commit
+    /// records they reserved.
+    pub fn shutdown(&mut self, timeout: Duration, opts: ShutdownOpts<'_>) -> Result<()> {
+        let ShutdownOpts {
+            ring_buffers,
+            unpin_maps,
+            _non_exhaustive,
+        } = opts;
+
+        let deadline = Instant::now() + timeout;
+        let _count = self.detach_all()?;
+
+        for ringbuf in ring_buffers {
+            loop {
+                let () = ringbuf.consume()?;
+                let drained = (0..)
+                    .map_while(|idx| ringbuf.ring(idx))
+                    .all(|ring| ring.avail_data_size() == 0);
+                if drained {
+                    break;
+                }
+
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(Error::with_io_error(
+                        io::ErrorKind::TimedOut,
+                        "ring buffer did not drain in time",
+                    ));
+                }
+                let () = ringbuf.poll(remaining.min(DRAIN_POLL_INTERVAL))?;
+            }
+        }
+
+        if unpin_maps {
+            for map in self.maps.values_mut() {
+                if let Some(path) = map.get_pin_path().map(PathBuf::from) {
+                    let () = map.unpin(&path).with_context(|| {
+                        format!(
+                            "failed to unpin map `{}` from `{}`",
+                            map.name(),
+                            path.display()
+                        )
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl AsRawLibbpf for Object {
@@ -492,3 +967,64 @@ impl Drop for Object {
         }
     }
 }
+
+#[cfg(all(test, any(feature = "gzip", feature = "zstd")))]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::ErrorKind;
+
+    /// Read the contents of the test object with the given name.
+    fn test_object(name: &str) -> Vec<u8> {
+        fs::read(util::test_object_path(name)).unwrap()
+    }
+
+    /// Check that we can open a gzip compressed object.
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_object_opening() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write as _;
+
+        let data = test_object("runqslower.bpf.o");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let () = encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let obj = ObjectBuilder::default()
+            .open_compressed_memory(&compressed)
+            .unwrap();
+        assert!(obj.map("events").is_some());
+
+        // Truncated data can't be decompressed.
+        let _err = ObjectBuilder::default()
+            .open_compressed_memory(&compressed[..compressed.len() / 2])
+            .unwrap_err();
+    }
+
+    /// Check that we can open a zstd compressed object.
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_object_opening() {
+        let data = test_object("runqslower.bpf.o");
+        let compressed = zstd::encode_all(data.as_slice(), 0).unwrap();
+
+        let obj = ObjectBuilder::default()
+            .open_compressed_memory(&compressed)
+            .unwrap();
+        assert!(obj.map("events").is_some());
+    }
+
+    /// Check that uncompressed data is rejected.
+    #[test]
+    fn uncompressed_object_opening() {
+        let data = test_object("runqslower.bpf.o");
+        let err = ObjectBuilder::default()
+            .open_compressed_memory(&data)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
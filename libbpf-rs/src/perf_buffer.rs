@@ -1,7 +1,9 @@
 use core::ffi::c_void;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
+use std::mem::size_of;
 use std::os::unix::io::AsFd;
 use std::os::unix::prelude::AsRawFd;
 use std::ptr;
@@ -28,24 +30,66 @@ impl<T> LostCb for T where T: FnMut(i32, u64) {}
 struct CbStruct<'b> {
     sample_cb: Option<Box<dyn SampleCb + 'b>>,
     lost_cb: Option<Box<dyn LostCb + 'b>>,
+    /// The number of lost samples, per CPU.
+    lost: BTreeMap<i32, u64>,
 }
 
 impl Debug for CbStruct<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let Self { sample_cb, lost_cb } = self;
+        let Self {
+            sample_cb,
+            lost_cb,
+            lost,
+        } = self;
         f.debug_struct("CbStruct")
             .field("sample_cb", &sample_cb.as_ref().map(|cb| &cb as *const _))
             .field("lost_cb", &lost_cb.as_ref().map(|cb| &cb as *const _))
+            .field("lost", lost)
             .finish()
     }
 }
 
+/// The condition upon which a reader of a [`PerfBuffer`] is woken up.
+#[derive(Clone, Copy, Debug)]
+enum Wakeup {
+    /// Wake up after the given number of samples.
+    Events(u32),
+    /// Wake up once the given number of bytes is available.
+    Watermark(u32),
+}
+
+/// Create the attributes of the perf events backing a [`PerfBuffer`].
+fn perf_event_attr(wakeup: Wakeup) -> libbpf_sys::perf_event_attr {
+    // Mirror the attributes `libbpf` uses for regular perf buffers.
+    let mut attr = libbpf_sys::perf_event_attr {
+        type_: libbpf_sys::PERF_TYPE_SOFTWARE,
+        size: size_of::<libbpf_sys::perf_event_attr>() as _,
+        config: libbpf_sys::PERF_COUNT_SW_BPF_OUTPUT as _,
+        sample_type: libbpf_sys::PERF_SAMPLE_RAW as _,
+        ..Default::default()
+    };
+    attr.__bindgen_anon_1.sample_period = 1;
+
+    match wakeup {
+        Wakeup::Events(events) => {
+            attr.__bindgen_anon_2.wakeup_events = events;
+        }
+        Wakeup::Watermark(bytes) => {
+            let () = attr.set_watermark(1);
+            attr.__bindgen_anon_2.wakeup_watermark = bytes;
+        }
+    }
+    attr
+}
+
 /// Builds [`PerfBuffer`] instances.
 pub struct PerfBufferBuilder<'a, 'b> {
     map: &'a Map,
     pages: usize,
     sample_cb: Option<Box<dyn SampleCb + 'b>>,
     lost_cb: Option<Box<dyn LostCb + 'b>>,
+    cpus: Option<Vec<u32>>,
+    wakeup: Option<Wakeup>,
 }
 
 impl<'a> PerfBufferBuilder<'a, '_> {
@@ -56,6 +100,8 @@ impl<'a> PerfBufferBuilder<'a, '_> {
             pages: 64,
             sample_cb: None,
             lost_cb: None,
+            cpus: None,
+            wakeup: None,
         }
     }
 }
@@ -73,6 +119,8 @@ impl<'a, 'b> PerfBufferBuilder<'a, 'b> {
             pages: self.pages,
             sample_cb: Some(Box::new(cb)),
             lost_cb: self.lost_cb,
+            cpus: self.cpus,
+            wakeup: self.wakeup,
         }
     }
 
@@ -85,6 +133,8 @@ impl<'a, 'b> PerfBufferBuilder<'a, 'b> {
             pages: self.pages,
             sample_cb: self.sample_cb,
             lost_cb: Some(Box::new(cb)),
+            cpus: self.cpus,
+            wakeup: self.wakeup,
         }
     }
 
@@ -95,6 +145,65 @@ impl<'a, 'b> PerfBufferBuilder<'a, 'b> {
             pages,
             sample_cb: self.sample_cb,
             lost_cb: self.lost_cb,
+            cpus: self.cpus,
+            wakeup: self.wakeup,
+        }
+    }
+
+    /// Restrict the buffer to the provided set of CPUs.
+    ///
+    /// By default, a ring buffer is set up for every possible CPU. When
+    /// only a subset is of interest, restricting the buffer to it saves
+    /// memory and file descriptors. Each CPU is associated with the
+    /// slot of the same index in the underlying map, which is where
+    /// programs running on it are expected to submit their samples
+    /// (e.g., by using `BPF_F_CURRENT_CPU`).
+    pub fn cpus(self, cpus: &[u32]) -> PerfBufferBuilder<'a, 'b> {
+        PerfBufferBuilder {
+            map: self.map,
+            pages: self.pages,
+            sample_cb: self.sample_cb,
+            lost_cb: self.lost_cb,
+            cpus: Some(cpus.to_vec()),
+            wakeup: self.wakeup,
+        }
+    }
+
+    /// Only wake up readers after `events` samples have been submitted
+    /// to a per-CPU ring buffer.
+    ///
+    /// By default, readers are woken up for every sample. Batching
+    /// wakeups reduces overhead for high-throughput use cases, at the
+    /// cost of increased latency. Note that samples may remain
+    /// unreported until the threshold is reached; use
+    /// [`PerfBuffer::consume`] to process them regardless.
+    ///
+    /// This setting overrides any previously configured
+    /// [`wakeup_watermark`][Self::wakeup_watermark].
+    pub fn wakeup_events(self, events: u32) -> PerfBufferBuilder<'a, 'b> {
+        PerfBufferBuilder {
+            map: self.map,
+            pages: self.pages,
+            sample_cb: self.sample_cb,
+            lost_cb: self.lost_cb,
+            cpus: self.cpus,
+            wakeup: Some(Wakeup::Events(events)),
+        }
+    }
+
+    /// Only wake up readers once at least `bytes` bytes of data are
+    /// available in a per-CPU ring buffer.
+    ///
+    /// This setting overrides any previously configured
+    /// [`wakeup_events`][Self::wakeup_events].
+    pub fn wakeup_watermark(self, bytes: u32) -> PerfBufferBuilder<'a, 'b> {
+        PerfBufferBuilder {
+            map: self.map,
+            pages: self.pages,
+            sample_cb: self.sample_cb,
+            lost_cb: self.lost_cb,
+            cpus: self.cpus,
+            wakeup: Some(Wakeup::Watermark(bytes)),
         }
     }
 
@@ -114,31 +223,96 @@ impl<'a, 'b> PerfBufferBuilder<'a, 'b> {
             None
         };
 
-        let c_lost_cb: libbpf_sys::perf_buffer_lost_fn = if self.lost_cb.is_some() {
-            Some(Self::call_lost_cb)
-        } else {
-            None
-        };
+        // We always register the lost callback, in order to keep track
+        // of lost samples.
+        let c_lost_cb: libbpf_sys::perf_buffer_lost_fn = Some(Self::call_lost_cb);
 
         let callback_struct_ptr = Box::into_raw(Box::new(CbStruct {
             sample_cb: self.sample_cb,
             lost_cb: self.lost_cb,
+            lost: BTreeMap::new(),
         }));
 
-        util::create_bpf_entity_checked(|| unsafe {
-            libbpf_sys::perf_buffer__new(
+        let result = if self.cpus.is_some() || self.wakeup.is_some() {
+            Self::new_raw(
                 self.map.as_fd().as_raw_fd(),
-                self.pages as libbpf_sys::size_t,
-                c_sample_cb,
-                c_lost_cb,
+                self.pages,
+                self.cpus.as_deref(),
+                self.wakeup.unwrap_or(Wakeup::Events(1)),
+                callback_struct_ptr,
+            )
+        } else {
+            util::create_bpf_entity_checked(|| unsafe {
+                libbpf_sys::perf_buffer__new(
+                    self.map.as_fd().as_raw_fd(),
+                    self.pages as libbpf_sys::size_t,
+                    c_sample_cb,
+                    c_lost_cb,
+                    callback_struct_ptr as *mut _,
+                    ptr::null(),
+                )
+            })
+        };
+
+        // SAFETY: The pointer was created by `Box::into_raw` above and
+        //         ownership was never transferred elsewhere.
+        let cb_struct = unsafe { Box::from_raw(callback_struct_ptr) };
+        result.map(|ptr| PerfBuffer { ptr, cb_struct })
+    }
+
+    /// Create a perf buffer using `perf_buffer__new_raw`, which allows
+    /// for more fine grained control over the buffer's setup.
+    fn new_raw(
+        map_fd: i32,
+        pages: usize,
+        cpus: Option<&[u32]>,
+        wakeup: Wakeup,
+        callback_struct_ptr: *mut CbStruct<'b>,
+    ) -> Result<NonNull<libbpf_sys::perf_buffer>> {
+        if cpus.is_some_and(|cpus| cpus.is_empty()) {
+            return Err(Error::with_invalid_data("CPU list must not be empty"));
+        }
+
+        // Without an explicit CPU list `libbpf` sets up buffers for
+        // all online CPUs.
+        let mut cpus = cpus
+            .unwrap_or_default()
+            .iter()
+            .map(|cpu| *cpu as i32)
+            .collect::<Vec<_>>();
+        // Each CPU's ring buffer is stored at the map index matching
+        // the CPU number.
+        let mut map_keys = cpus.clone();
+
+        let mut attr = perf_event_attr(wakeup);
+
+        #[allow(clippy::needless_update)]
+        let opts = libbpf_sys::perf_buffer_raw_opts {
+            sz: size_of::<libbpf_sys::perf_buffer_raw_opts>() as _,
+            cpu_cnt: cpus.len() as _,
+            cpus: if cpus.is_empty() {
+                ptr::null_mut()
+            } else {
+                cpus.as_mut_ptr()
+            },
+            map_keys: if map_keys.is_empty() {
+                ptr::null_mut()
+            } else {
+                map_keys.as_mut_ptr()
+            },
+            ..Default::default()
+        };
+
+        util::create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::perf_buffer__new_raw(
+                map_fd,
+                pages as libbpf_sys::size_t,
+                &mut attr,
+                Some(Self::call_event_cb),
                 callback_struct_ptr as *mut _,
-                ptr::null(),
+                &opts,
             )
         })
-        .map(|ptr| PerfBuffer {
-            ptr,
-            _cb_struct: unsafe { Box::from_raw(callback_struct_ptr) },
-        })
     }
 
     unsafe extern "C" fn call_sample_cb(ctx: *mut c_void, cpu: i32, data: *mut c_void, size: u32) {
@@ -153,10 +327,43 @@ impl<'a, 'b> PerfBufferBuilder<'a, 'b> {
     unsafe extern "C" fn call_lost_cb(ctx: *mut c_void, cpu: i32, count: u64) {
         let callback_struct = ctx as *mut CbStruct<'_>;
 
+        let lost = unsafe { &mut (*callback_struct).lost };
+        *lost.entry(cpu).or_default() += count;
+
         if let Some(cb) = unsafe { &mut (*callback_struct).lost_cb } {
             cb(cpu, count);
         }
     }
+
+    /// Dispatch a raw perf event record to the sample or lost callback.
+    unsafe extern "C" fn call_event_cb(
+        ctx: *mut c_void,
+        cpu: i32,
+        event: *mut libbpf_sys::perf_event_header,
+    ) -> libbpf_sys::bpf_perf_event_ret {
+        // SAFETY: `libbpf` guarantees that `event` points to a valid
+        //         record of `header.size` bytes.
+        let header = unsafe { ptr::read_unaligned(event) };
+        let body = unsafe { (event as *const u8).add(size_of::<libbpf_sys::perf_event_header>()) };
+
+        match header.type_ {
+            libbpf_sys::PERF_RECORD_SAMPLE => {
+                // With `PERF_SAMPLE_RAW` the record body is a `u32`
+                // size followed by the sample data.
+                let size = unsafe { ptr::read_unaligned(body as *const u32) };
+                let data = unsafe { body.add(size_of::<u32>()) };
+                unsafe { Self::call_sample_cb(ctx, cpu, data as *mut _, size) }
+            }
+            libbpf_sys::PERF_RECORD_LOST => {
+                // The record body is a `u64` ID followed by a `u64`
+                // count of lost samples.
+                let count = unsafe { ptr::read_unaligned((body as *const u64).add(1)) };
+                unsafe { Self::call_lost_cb(ctx, cpu, count) }
+            }
+            _ => (),
+        }
+        libbpf_sys::LIBBPF_PERF_EVENT_CONT
+    }
 }
 
 impl Debug for PerfBufferBuilder<'_, '_> {
@@ -166,12 +373,16 @@ impl Debug for PerfBufferBuilder<'_, '_> {
             pages,
             sample_cb,
             lost_cb,
+            cpus,
+            wakeup,
         } = self;
         f.debug_struct("PerfBufferBuilder")
             .field("map", map)
             .field("pages", pages)
             .field("sample_cb", &sample_cb.as_ref().map(|cb| &cb as *const _))
             .field("lost_cb", &lost_cb.as_ref().map(|cb| &cb as *const _))
+            .field("cpus", cpus)
+            .field("wakeup", wakeup)
             .finish()
     }
 }
@@ -182,27 +393,35 @@ impl Debug for PerfBufferBuilder<'_, '_> {
 pub struct PerfBuffer<'b> {
     ptr: NonNull<libbpf_sys::perf_buffer>,
     // Hold onto the box so it'll get dropped when PerfBuffer is dropped
-    _cb_struct: Box<CbStruct<'b>>,
+    cb_struct: Box<CbStruct<'b>>,
 }
 
-// TODO: Document methods.
-#[allow(missing_docs)]
 impl PerfBuffer<'_> {
+    /// Retrieve the epoll file descriptor used for polling the
+    /// per-CPU ring buffers.
     pub fn epoll_fd(&self) -> i32 {
         unsafe { libbpf_sys::perf_buffer__epoll_fd(self.ptr.as_ptr()) }
     }
 
+    /// Wait for data to become available, for at most `timeout`, and
+    /// invoke the configured callbacks for all available samples.
     pub fn poll(&self, timeout: Duration) -> Result<()> {
         let ret =
             unsafe { libbpf_sys::perf_buffer__poll(self.ptr.as_ptr(), timeout.as_millis() as i32) };
         util::parse_ret(ret)
     }
 
+    /// Process all pending samples in all per-CPU ring buffers,
+    /// without waiting for new data to arrive.
     pub fn consume(&self) -> Result<()> {
         let ret = unsafe { libbpf_sys::perf_buffer__consume(self.ptr.as_ptr()) };
         util::parse_ret(ret)
     }
 
+    /// Process all pending samples in the per-CPU ring buffer with
+    /// index `buf_idx`, without waiting for new data to arrive.
+    ///
+    /// Valid indices are in the range `0..self.buffer_cnt()`.
     pub fn consume_buffer(&self, buf_idx: usize) -> Result<()> {
         let ret = unsafe {
             libbpf_sys::perf_buffer__consume_buffer(
@@ -213,10 +432,31 @@ impl PerfBuffer<'_> {
         util::parse_ret(ret)
     }
 
+    /// Retrieve the number of per-CPU ring buffers managed by this
+    /// object.
     pub fn buffer_cnt(&self) -> usize {
         unsafe { libbpf_sys::perf_buffer__buffer_cnt(self.ptr.as_ptr()) as usize }
     }
 
+    /// Retrieve the total number of samples lost so far, because the
+    /// per-CPU ring buffers were full.
+    ///
+    /// Only losses reported while processing buffers (e.g., via
+    /// [`poll`][Self::poll]) are accounted for.
+    pub fn lost_samples(&self) -> u64 {
+        self.cb_struct.lost.values().sum()
+    }
+
+    /// Retrieve the number of samples lost so far, per CPU.
+    ///
+    /// CPUs that have not lost any samples are not reported.
+    pub fn lost_samples_per_cpu(&self) -> BTreeMap<i32, u64> {
+        self.cb_struct.lost.clone()
+    }
+
+    /// Retrieve the perf event file descriptor of the per-CPU ring
+    /// buffer with index `buf_idx`, e.g., for adding it to a custom
+    /// epoll instance.
     pub fn buffer_fd(&self, buf_idx: usize) -> Result<i32> {
         let ret = unsafe {
             libbpf_sys::perf_buffer__buffer_fd(self.ptr.as_ptr(), buf_idx as libbpf_sys::size_t)
@@ -260,4 +500,74 @@ mod test {
 
         test::<PerfBuffer<'_>>();
     }
+
+    /// Check that wakeup settings are reflected in the perf event
+    /// attributes.
+    #[test]
+    fn wakeup_attr() {
+        let attr = perf_event_attr(Wakeup::Events(16));
+        assert_eq!(attr.watermark(), 0);
+        assert_eq!(unsafe { attr.__bindgen_anon_2.wakeup_events }, 16);
+        assert_eq!(unsafe { attr.__bindgen_anon_1.sample_period }, 1);
+
+        let attr = perf_event_attr(Wakeup::Watermark(4096));
+        assert_eq!(attr.watermark(), 1);
+        assert_eq!(unsafe { attr.__bindgen_anon_2.wakeup_watermark }, 4096);
+    }
+
+    /// Check that raw perf event records are dispatched to the correct
+    /// callbacks.
+    #[test]
+    fn raw_event_dispatch() {
+        fn record(ty: u32, body: &[u8]) -> Vec<u64> {
+            let header = libbpf_sys::perf_event_header {
+                type_: ty,
+                misc: 0,
+                size: (size_of::<libbpf_sys::perf_event_header>() + body.len()) as _,
+            };
+            // Use a `u64` backed buffer to get proper alignment.
+            let mut buf = vec![0u64; (header.size as usize + 7) / 8];
+            let ptr = buf.as_mut_ptr() as *mut u8;
+            unsafe {
+                ptr::write_unaligned(ptr as *mut libbpf_sys::perf_event_header, header);
+                ptr::copy_nonoverlapping(
+                    body.as_ptr(),
+                    ptr.add(size_of::<libbpf_sys::perf_event_header>()),
+                    body.len(),
+                );
+            }
+            buf
+        }
+
+        let mut samples = Vec::new();
+        let mut lost = Vec::new();
+        let mut cb_struct = CbStruct {
+            sample_cb: Some(Box::new(|cpu, data: &[u8]| {
+                samples.push((cpu, data.to_vec()))
+            })),
+            lost_cb: Some(Box::new(|cpu, count| lost.push((cpu, count)))),
+            lost: BTreeMap::new(),
+        };
+        let ctx = &mut cb_struct as *mut CbStruct<'_> as *mut c_void;
+
+        let mut sample = 3u32.to_ne_bytes().to_vec();
+        let () = sample.extend_from_slice(&[1, 2, 3]);
+        let mut buf = record(libbpf_sys::PERF_RECORD_SAMPLE, &sample);
+        let ret = unsafe { PerfBufferBuilder::call_event_cb(ctx, 2, buf.as_mut_ptr() as *mut _) };
+        assert_eq!(ret, libbpf_sys::LIBBPF_PERF_EVENT_CONT);
+
+        let mut body = 1337u64.to_ne_bytes().to_vec();
+        let () = body.extend_from_slice(&42u64.to_ne_bytes());
+        let mut buf = record(libbpf_sys::PERF_RECORD_LOST, &body);
+        let ret = unsafe { PerfBufferBuilder::call_event_cb(ctx, 5, buf.as_mut_ptr() as *mut _) };
+        assert_eq!(ret, libbpf_sys::LIBBPF_PERF_EVENT_CONT);
+
+        let ret = unsafe { PerfBufferBuilder::call_event_cb(ctx, 5, buf.as_mut_ptr() as *mut _) };
+        assert_eq!(ret, libbpf_sys::LIBBPF_PERF_EVENT_CONT);
+        assert_eq!(cb_struct.lost, BTreeMap::from([(5, 84)]));
+
+        drop(cb_struct);
+        assert_eq!(samples, vec![(2, vec![1, 2, 3])]);
+        assert_eq!(lost, vec![(5, 42), (5, 42)]);
+    }
 }
@@ -0,0 +1,125 @@
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsFd as _;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::OwnedFd;
+
+use crate::util;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::Result;
+
+/// Open the perf event described by `attr` for all processes on `cpu`.
+fn perf_event_open(attr: &libbpf_sys::perf_event_attr, cpu: usize) -> Result<OwnedFd> {
+    // SAFETY: `attr` is a valid `perf_event_attr` object.
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            attr as *const libbpf_sys::perf_event_attr,
+            -1 as libc::pid_t,
+            cpu as libc::c_int,
+            -1 as libc::c_int,
+            libbpf_sys::PERF_FLAG_FD_CLOEXEC as libc::c_ulong,
+        )
+    };
+    if fd < 0 {
+        return Err(
+            io::Error::last_os_error().context(format!("failed to open perf event on CPU {cpu}"))
+        );
+    }
+    // SAFETY: `fd` is a freshly created file descriptor that we own.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as _) })
+}
+
+/// A [`MapType::PerfEventArray`] map populated with perf events opened
+/// on a set of CPUs.
+///
+/// This is what a [`PerfBuffer`][crate::PerfBuffer] does internally,
+/// but with a custom event configuration. That allows, for example,
+/// for reading hardware counters from BPF programs via
+/// `bpf_perf_event_read_value`.
+///
+/// The event of each CPU is stored at the map index matching the CPU
+/// number. Entries are removed from the map again once the
+/// `PerfEventArray` is dropped.
+#[derive(Debug)]
+pub struct PerfEventArray<'map> {
+    map: &'map MapHandle,
+    events: Vec<(usize, OwnedFd)>,
+}
+
+impl<'map> PerfEventArray<'map> {
+    /// Open a perf event of type `ty` (e.g., `PERF_TYPE_HARDWARE`) with
+    /// the given `config` (e.g., `PERF_COUNT_HW_CPU_CYCLES`) on all
+    /// online CPUs and store them in `map`.
+    pub fn open(map: &'map MapHandle, ty: u32, config: u64) -> Result<Self> {
+        let attr = libbpf_sys::perf_event_attr {
+            type_: ty,
+            size: size_of::<libbpf_sys::perf_event_attr>() as _,
+            config,
+            ..Default::default()
+        };
+        let cpus = util::online_cpu_ids()?;
+        Self::open_with_attr(map, &attr, &cpus)
+    }
+
+    /// Open the perf event described by `attr` on each of `cpus` and
+    /// store them in `map`.
+    pub fn open_with_attr(
+        map: &'map MapHandle,
+        attr: &libbpf_sys::perf_event_attr,
+        cpus: &[usize],
+    ) -> Result<Self> {
+        if map.map_type() != MapType::PerfEventArray {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("map of type {:?} is not a perf event array", map.map_type()),
+            ));
+        }
+
+        let mut slf = Self {
+            map,
+            events: Vec::with_capacity(cpus.len()),
+        };
+        for cpu in cpus {
+            let fd = perf_event_open(attr, *cpu)?;
+            let key = (*cpu as u32).to_ne_bytes();
+            let value = fd.as_raw_fd().to_ne_bytes();
+            let () = map
+                .update(&key, &value, MapFlags::ANY)
+                .with_context(|| format!("failed to store perf event of CPU {cpu}"))?;
+            let () = slf.events.push((*cpu, fd));
+        }
+        Ok(slf)
+    }
+
+    /// Retrieve the file descriptor of the perf event opened on `cpu`,
+    /// if any.
+    ///
+    /// The file descriptor can be used for reading the event's counter
+    /// or, e.g., for enabling and disabling it via `ioctl`.
+    pub fn event_fd(&self, cpu: usize) -> Option<BorrowedFd<'_>> {
+        self.events
+            .iter()
+            .find(|(event_cpu, _fd)| *event_cpu == cpu)
+            .map(|(_cpu, fd)| fd.as_fd())
+    }
+
+    /// Retrieve the CPUs on which perf events got opened.
+    pub fn cpus(&self) -> impl Iterator<Item = usize> + '_ {
+        self.events.iter().map(|(cpu, _fd)| *cpu)
+    }
+}
+
+impl Drop for PerfEventArray<'_> {
+    fn drop(&mut self) {
+        for (cpu, _fd) in &self.events {
+            let _result = self.map.delete(&(*cpu as u32).to_ne_bytes());
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::mem::size_of;
+use std::ptr;
+
+use crate::Error;
+use crate::Result;
+
+/// A marker trait for "plain old data" types that can be exchanged with
+/// BPF programs by value, e.g., by writing them to a
+/// [`UserRingBuffer`][crate::UserRingBuffer] via
+/// [`UserRingBuffer::reserve_typed`][crate::UserRingBuffer::reserve_typed],
+/// accessing a [`TypedMap`][crate::TypedMap], or decoding them from
+/// samples by an [`EventDecoder`][crate::events::EventDecoder].
+///
+/// Skeletons generated by `libbpf-cargo` implement this trait for
+/// generated types that qualify.
+///
+/// # Safety
+/// Implementors must be valid for any bit pattern, including all zeros,
+/// and must have a defined memory layout (e.g., by using `#[repr(C)]`)
+/// that matches the one expected by the BPF program on the other end.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            // SAFETY: Primitive numeric types are valid for any bit
+            //         pattern.
+            unsafe impl Pod for $ty {}
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+// SAFETY: Arrays of `Pod` types are `Pod` themselves.
+unsafe impl<T, const N: usize> Pod for [T; N] where T: Pod {}
+
+/// Read a `T` from the start of `data`.
+pub(crate) fn read_pod<T: Pod>(data: &[u8]) -> Result<T> {
+    if data.len() < size_of::<T>() {
+        return Err(Error::with_invalid_data(format!(
+            "sample of {} bytes is too small for {} byte value",
+            data.len(),
+            size_of::<T>()
+        )));
+    }
+    // SAFETY: `data` is large enough and `T` is valid for any bit
+    //         pattern. We make no assumptions about alignment.
+    Ok(unsafe { ptr::read_unaligned(data.as_ptr().cast::<T>()) })
+}
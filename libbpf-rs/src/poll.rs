@@ -0,0 +1,230 @@
+use std::io;
+use std::mem;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd as _;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::OwnedFd;
+use std::time::Duration;
+
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::Map;
+use crate::MapHandle;
+use crate::PerfBuffer;
+use crate::Result;
+use crate::RingBuffer;
+use crate::RingBufferReader;
+
+/// The maximum number of events retrieved by a single
+/// [`Poller::wait`] call.
+const MAX_EVENTS: usize = 32;
+
+/// Convert `timeout` into milliseconds, as used by `poll` and
+/// `epoll_wait`, saturating at the maximum representable value.
+fn timeout_ms(timeout: Duration) -> i32 {
+    i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+}
+
+/// A BPF entity whose file descriptor can be waited on for becoming
+/// readable, e.g., via `poll(2)` or `epoll(7)`.
+///
+/// This trait allows for building custom event loops that treat all
+/// kinds of such entities uniformly, see [`Poller`].
+pub trait BpfPollable {
+    /// Retrieve the file descriptor to wait on.
+    fn poll_fd(&self) -> BorrowedFd<'_>;
+
+    /// Wait for at most `timeout` for the entity to become readable,
+    /// returning whether it did.
+    fn poll_readable(&self, timeout: Duration) -> Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.poll_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is a valid `pollfd` object.
+        let ret = unsafe { libc::poll(&mut pollfd, 1, timeout_ms(timeout)) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error().context("failed to poll file descriptor"));
+        }
+        Ok(ret > 0 && pollfd.revents & libc::POLLIN != 0)
+    }
+}
+
+/// Ring buffer maps become readable once data is available. Other map
+/// types do not support waiting.
+impl BpfPollable for MapHandle {
+    fn poll_fd(&self) -> BorrowedFd<'_> {
+        self.as_fd()
+    }
+}
+
+/// See the [`MapHandle` implementation](#impl-BpfPollable-for-MapHandle).
+impl BpfPollable for Map {
+    fn poll_fd(&self) -> BorrowedFd<'_> {
+        self.as_fd()
+    }
+}
+
+/// The ring buffer's epoll file descriptor becomes readable once any of
+/// its rings has data available.
+impl BpfPollable for RingBuffer<'_> {
+    fn poll_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The epoll file descriptor is valid for as long as the
+        //         ring buffer is alive.
+        unsafe { BorrowedFd::borrow_raw(self.epoll_fd()) }
+    }
+}
+
+impl BpfPollable for RingBufferReader {
+    fn poll_fd(&self) -> BorrowedFd<'_> {
+        self.as_fd()
+    }
+}
+
+/// The perf buffer's epoll file descriptor becomes readable once any of
+/// its per-CPU buffers has data available.
+impl BpfPollable for PerfBuffer<'_> {
+    fn poll_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The epoll file descriptor is valid for as long as the
+        //         perf buffer is alive.
+        unsafe { BorrowedFd::borrow_raw(self.epoll_fd()) }
+    }
+}
+
+/// An `epoll(7)` based waiter for multiple [`BpfPollable`] entities.
+///
+/// Entities are registered together with a token identifying them, which
+/// gets reported by [`Poller::wait`] once they become readable:
+/// ```no_run
+/// # use std::time::Duration;
+/// # use libbpf_rs::MapHandle;
+/// # use libbpf_rs::RingBufferReader;
+/// use libbpf_rs::Poller;
+///
+/// # fn run(events: &MapHandle, errors: &MapHandle) -> libbpf_rs::Result<()> {
+/// let events = RingBufferReader::new(events)?;
+/// let errors = RingBufferReader::new(errors)?;
+/// let poller = Poller::new()?;
+/// let () = poller.add(&events, 0)?;
+/// let () = poller.add(&errors, 1)?;
+///
+/// for token in poller.wait(Duration::from_millis(100))? {
+///     let reader = if token == 0 { &events } else { &errors };
+///     for sample in reader.consume_raw_iter() {
+///         println!("{token}: {} bytes", sample.len());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The `Poller` itself can be registered with other event loops, via its
+/// file descriptor.
+#[derive(Debug)]
+pub struct Poller {
+    fd: OwnedFd,
+}
+
+impl Poller {
+    /// Create a new `Poller` without any registered entities.
+    pub fn new() -> Result<Self> {
+        // SAFETY: `epoll_create1` is always safe to call.
+        let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().context("failed to create epoll instance"));
+        }
+        // SAFETY: `fd` is a freshly created file descriptor that we own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(Self { fd })
+    }
+
+    fn ctl(&self, op: i32, fd: BorrowedFd<'_>, token: u64) -> Result<()> {
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: token,
+        };
+        // SAFETY: Both file descriptors are valid and `event` is a valid
+        //         `epoll_event` object.
+        let ret = unsafe { libc::epoll_ctl(self.fd.as_raw_fd(), op, fd.as_raw_fd(), &mut event) };
+        if ret != 0 {
+            return Err(Error::from(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Register `pollable`, reporting it as `token` once it becomes
+    /// readable.
+    pub fn add<P>(&self, pollable: &P, token: u64) -> Result<()>
+    where
+        P: BpfPollable + ?Sized,
+    {
+        self.ctl(libc::EPOLL_CTL_ADD, pollable.poll_fd(), token)
+            .context("failed to register file descriptor with epoll")
+    }
+
+    /// Unregister a previously registered `pollable`.
+    pub fn remove<P>(&self, pollable: &P) -> Result<()>
+    where
+        P: BpfPollable + ?Sized,
+    {
+        self.ctl(libc::EPOLL_CTL_DEL, pollable.poll_fd(), 0)
+            .context("failed to unregister file descriptor from epoll")
+    }
+
+    /// Wait for at most `timeout` for any of the registered entities to
+    /// become readable, returning the tokens of those that did.
+    ///
+    /// An empty result indicates that the timeout expired.
+    pub fn wait(&self, timeout: Duration) -> Result<Vec<u64>> {
+        // SAFETY: `epoll_event` is valid for any bit pattern.
+        let mut events = [unsafe { mem::zeroed::<libc::epoll_event>() }; MAX_EVENTS];
+        // SAFETY: `events` provides storage for `MAX_EVENTS` events.
+        let ret = unsafe {
+            libc::epoll_wait(
+                self.fd.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as i32,
+                timeout_ms(timeout),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error().context("failed to wait for epoll events"));
+        }
+
+        let tokens = events[..ret as usize]
+            .iter()
+            .map(|event| event.u64)
+            .collect();
+        Ok(tokens)
+    }
+}
+
+impl AsFd for Poller {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that timeouts get converted into milliseconds as expected.
+    #[test]
+    fn timeout_conversion() {
+        assert_eq!(timeout_ms(Duration::ZERO), 0);
+        assert_eq!(timeout_ms(Duration::from_micros(1500)), 1);
+        assert_eq!(timeout_ms(Duration::from_secs(1)), 1000);
+        assert_eq!(timeout_ms(Duration::MAX), i32::MAX);
+    }
+
+    /// Check that waiting on a `Poller` without registered entities
+    /// times out.
+    #[test]
+    fn empty_poller() {
+        let poller = Poller::new().unwrap();
+        assert_eq!(poller.wait(Duration::ZERO).unwrap(), Vec::<u64>::new());
+    }
+}
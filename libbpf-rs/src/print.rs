@@ -1,9 +1,12 @@
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::ffi::c_char;
 use std::ffi::c_int;
 use std::ffi::c_void;
 use std::io;
 use std::io::Write;
 use std::mem;
+use std::sync::Arc;
 use std::sync::Mutex;
 
 use crate::util::LazyLock;
@@ -42,16 +45,66 @@ fn default_callback(_lvl: PrintLevel, msg: String) {
     let _ = io::stderr().write(msg.as_bytes());
 }
 
+/// The type of callbacks suitable for being registered via
+/// [`add_print_subscriber`].
+type Subscriber = Arc<dyn Fn(PrintLevel, &str) + Send + Sync>;
+
+/// The type of callbacks suitable for being used with [`with_print`],
+/// with their lifetime erased.
+type ScopedCallback = *mut (dyn FnMut(PrintLevel, &str) + 'static);
+
+thread_local! {
+    /// The stack of callbacks installed via [`with_print`] on the
+    /// current thread, each with its own minimum level.
+    static SCOPED: RefCell<Vec<(PrintLevel, ScopedCallback)>> = const { RefCell::new(Vec::new()) };
+    /// Whether the current thread is executing a scoped callback.
+    static IN_SCOPED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The state of the printing machinery.
+struct PrintState {
+    /// The callback managed via [`set_print`] & [`get_print`].
+    callback: Option<(PrintLevel, PrintCallback)>,
+    /// Additional subscribers, each with its own minimum level.
+    subscribers: Vec<(u64, PrintLevel, Subscriber)>,
+    /// The identifier to assign to the next subscriber.
+    next_id: u64,
+    /// The number of [`with_print`] scopes active across all threads.
+    scopes: usize,
+}
+
+impl PrintState {
+    /// Install or remove our libbpf print callback, depending on whether
+    /// anybody is interested in messages.
+    fn update_libbpf(&self) {
+        // # Safety
+        // outer_print_cb has the same function signature as libbpf_print_fn_t
+        #[allow(clippy::missing_transmute_annotations)]
+        let real_cb: libbpf_sys::libbpf_print_fn_t =
+            unsafe { Some(mem::transmute(outer_print_cb as *const ())) };
+        let active = self.callback.is_some() || !self.subscribers.is_empty() || self.scopes > 0;
+        let real_cb = if active { real_cb } else { None };
+        unsafe { libbpf_sys::libbpf_set_print(real_cb) };
+    }
+}
+
 // While we can't say that set_print is thread-safe, because we shouldn't assume that of
 // libbpf_set_print, we should still make sure that things are sane on the rust side of things.
-// Therefore we are using a lock to keep the log level and the callback in sync.
+// Therefore we are using a lock to keep the log level and the callbacks in sync.
 //
 // We don't do anything that can panic with the lock held, so we'll unconditionally unwrap() when
-// locking the mutex.
+// locking the mutex. Callbacks are never invoked with the lock held, so that they are free to
+// (un)register subscribers.
 //
 // Note that default print behavior ignores debug messages.
-static PRINT_CB: LazyLock<Mutex<Option<(PrintLevel, PrintCallback)>>> =
-    LazyLock::new(|| Mutex::new(Some((PrintLevel::Info, default_callback))));
+static PRINT_STATE: LazyLock<Mutex<PrintState>> = LazyLock::new(|| {
+    Mutex::new(PrintState {
+        callback: Some((PrintLevel::Info, default_callback)),
+        subscribers: Vec::new(),
+        next_id: 0,
+        scopes: 0,
+    })
+});
 
 extern "C" fn outer_print_cb(
     level: libbpf_sys::libbpf_print_level,
@@ -63,18 +116,70 @@ extern "C" fn outer_print_cb(
     va_list: *mut c_void,
 ) -> c_int {
     let level = level.into();
-    if let Some((min_level, func)) = { *PRINT_CB.lock().unwrap() } {
-        if level <= min_level {
-            let msg = match unsafe { vsprintf::vsprintf(fmtstr, va_list) } {
-                Ok(s) => s,
-                Err(e) => format!("Failed to parse libbpf output: {e}"),
-            };
-            func(level, msg);
-        }
+    // A callback installed via `with_print` on this thread takes the
+    // place of the global one.
+    let scoped = SCOPED.with(|scoped| scoped.borrow().last().copied());
+    let (callback, subscribers) = {
+        let state = PRINT_STATE.lock().unwrap();
+        let callback = state
+            .callback
+            .filter(|_| scoped.is_none())
+            .filter(|(min_level, _func)| level <= *min_level)
+            .map(|(_min_level, func)| func);
+        let subscribers = state
+            .subscribers
+            .iter()
+            .filter(|(_id, min_level, _subscriber)| level <= *min_level)
+            .map(|(_id, _min_level, subscriber)| subscriber.clone())
+            .collect::<Vec<_>>();
+        (callback, subscribers)
+    };
+
+    let scoped = scoped
+        .filter(|(min_level, _func)| level <= *min_level)
+        .map(|(_min_level, func)| func)
+        // Don't re-enter a scoped callback should it, directly or
+        // indirectly, cause libbpf to print something.
+        .filter(|_| !IN_SCOPED.with(Cell::get));
+
+    if callback.is_none() && subscribers.is_empty() && scoped.is_none() {
+        return 0;
+    }
+
+    let msg = match unsafe { vsprintf::vsprintf(fmtstr, va_list) } {
+        Ok(s) => s,
+        Err(e) => format!("Failed to parse libbpf output: {e}"),
+    };
+
+    for subscriber in subscribers {
+        let () = subscriber(level, &msg);
+    }
+
+    if let Some(func) = scoped {
+        let () = IN_SCOPED.with(|in_scoped| in_scoped.set(true));
+        let _guard = ScopedCallbackGuard;
+        // SAFETY: The callback is kept alive by the `with_print`
+        //         invocation that registered it, which is still
+        //         running on this very thread. It is not invoked
+        //         concurrently, because we never re-enter it.
+        let () = unsafe { (*func)(level, &msg) };
+    }
+
+    if let Some(func) = callback {
+        func(level, msg);
     }
     0 // return value is ignored by libbpf
 }
 
+/// A guard resetting the "in scoped callback" state on drop.
+struct ScopedCallbackGuard;
+
+impl Drop for ScopedCallbackGuard {
+    fn drop(&mut self) {
+        let () = IN_SCOPED.with(|in_scoped| in_scoped.set(false));
+    }
+}
+
 /// Set a callback to receive log messages from libbpf, instead of printing them to stderr.
 ///
 /// # Arguments
@@ -84,6 +189,14 @@ extern "C" fn outer_print_cb(
 ///
 /// This overrides (and is overridden by) [`ObjectBuilder::debug`][crate::ObjectBuilder::debug]
 ///
+/// # Thread Safety
+///
+/// The callback is stored process wide and the function can safely be
+/// called from any thread. Messages are delivered to the callback on
+/// the thread that caused libbpf to emit them. Libraries that only want
+/// to capture output for their own operations should use
+/// [`with_print`] instead, which leaves this callback untouched.
+///
 /// # Examples
 ///
 /// To pass all messages to the `log` crate:
@@ -121,14 +234,9 @@ extern "C" fn outer_print_cb(
 pub fn set_print(
     mut callback: Option<(PrintLevel, PrintCallback)>,
 ) -> Option<(PrintLevel, PrintCallback)> {
-    // # Safety
-    // outer_print_cb has the same function signature as libbpf_print_fn_t
-    #[allow(clippy::missing_transmute_annotations)]
-    let real_cb: libbpf_sys::libbpf_print_fn_t =
-        unsafe { Some(mem::transmute(outer_print_cb as *const ())) };
-    let real_cb: libbpf_sys::libbpf_print_fn_t = callback.as_ref().and(real_cb);
-    mem::swap(&mut callback, &mut *PRINT_CB.lock().unwrap());
-    unsafe { libbpf_sys::libbpf_set_print(real_cb) };
+    let mut state = PRINT_STATE.lock().unwrap();
+    mem::swap(&mut callback, &mut state.callback);
+    let () = state.update_libbpf();
     callback
 }
 
@@ -147,5 +255,253 @@ pub fn set_print(
 /// set_print(prev);
 /// ```
 pub fn get_print() -> Option<(PrintLevel, PrintCallback)> {
-    *PRINT_CB.lock().unwrap()
+    PRINT_STATE.lock().unwrap().callback
+}
+
+/// A registration of a print subscriber, as created by
+/// [`add_print_subscriber`].
+///
+/// The subscriber is unregistered when this object is dropped.
+#[must_use = "the subscriber is unregistered when the subscription is dropped"]
+#[derive(Debug)]
+pub struct PrintSubscription {
+    id: u64,
+}
+
+impl Drop for PrintSubscription {
+    fn drop(&mut self) {
+        let mut state = PRINT_STATE.lock().unwrap();
+        let () = state.subscribers.retain(|(id, ..)| *id != self.id);
+        let () = state.update_libbpf();
+    }
+}
+
+/// Register an additional callback to receive log messages from libbpf
+/// of level `min_level` or more severe.
+///
+/// In contrast to [`set_print`], which manages a single process wide
+/// callback, any number of subscribers can be registered and they do
+/// not interfere with each other or with the callback set via
+/// [`set_print`]. That makes this function suitable for libraries that
+/// want to observe libbpf output without clobbering the application's
+/// configuration.
+///
+/// The subscriber stays registered for as long as the returned
+/// [`PrintSubscription`] is alive.
+///
+/// # Examples
+///
+/// ```
+/// use libbpf_rs::add_print_subscriber;
+/// use libbpf_rs::PrintLevel;
+///
+/// let _subscription = add_print_subscriber(PrintLevel::Warn, |level, msg| {
+///     eprint!("[{level:?}] {msg}");
+/// });
+/// // libbpf warnings are reported to the subscriber until
+/// // `_subscription` goes out of scope.
+/// ```
+pub fn add_print_subscriber<F>(min_level: PrintLevel, callback: F) -> PrintSubscription
+where
+    F: Fn(PrintLevel, &str) + Send + Sync + 'static,
+{
+    let mut state = PRINT_STATE.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    let () = state.subscribers.push((id, min_level, Arc::new(callback)));
+    let () = state.update_libbpf();
+    PrintSubscription { id }
+}
+
+/// A guard unregistering the innermost scoped callback on drop.
+struct ScopeGuard;
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let _callback = SCOPED.with(|scoped| scoped.borrow_mut().pop());
+        let mut state = PRINT_STATE.lock().unwrap();
+        state.scopes -= 1;
+        let () = state.update_libbpf();
+    }
+}
+
+/// Run `f` with `callback` receiving libbpf messages of level
+/// `min_level` or more severe that are emitted on the current thread.
+///
+/// While `f` runs, the callback takes the place of the process wide
+/// one managed via [`set_print`] for messages originating from the
+/// calling thread; the process wide callback is neither modified nor
+/// invoked for them. Output caused by other threads is unaffected and
+/// subscribers registered via [`add_print_subscriber`] continue to
+/// receive all messages. Calls may be nested, in which case the
+/// innermost callback is used.
+///
+/// This function is meant for libraries that want to capture libbpf
+/// output for their own operations (e.g., to include it in error
+/// reports), without clobbering the hosting application's
+/// configuration.
+///
+/// # Examples
+///
+/// ```
+/// use libbpf_rs::with_print;
+/// use libbpf_rs::ObjectBuilder;
+/// use libbpf_rs::PrintLevel;
+///
+/// let mut output = String::new();
+/// let result = with_print(
+///     PrintLevel::Debug,
+///     |_level, msg| output.push_str(msg),
+///     || ObjectBuilder::default().open_file("/dev/null"),
+/// );
+/// assert!(result.is_err());
+/// assert!(output.contains("libbpf: "));
+/// ```
+pub fn with_print<C, F, R>(min_level: PrintLevel, mut callback: C, f: F) -> R
+where
+    C: FnMut(PrintLevel, &str),
+    F: FnOnce() -> R,
+{
+    let callback = &mut callback as &mut dyn FnMut(PrintLevel, &str);
+    // SAFETY: We only erase the lifetime of the callback. The pointer
+    //         is removed from the thread local stack by `ScopeGuard`
+    //         before `callback` goes out of scope, including on unwind.
+    let callback = unsafe {
+        mem::transmute::<*mut (dyn FnMut(PrintLevel, &str) + '_), ScopedCallback>(callback)
+    };
+
+    let () = SCOPED.with(|scoped| scoped.borrow_mut().push((min_level, callback)));
+    {
+        let mut state = PRINT_STATE.lock().unwrap();
+        state.scopes += 1;
+        let () = state.update_libbpf();
+    }
+    let _guard = ScopeGuard;
+    f()
+}
+
+/// The `log` target used for messages forwarded by [`forward_to_log`].
+#[cfg(feature = "log")]
+const LOG_TARGET: &str = "libbpf";
+
+/// Split a libbpf message into the component it originates from, the
+/// name of the object it concerns (if any), and the remaining text.
+///
+/// libbpf prefixes most of its messages with `libbpf: `, commonly
+/// followed by either `<component>: ` (e.g., `elf: `) or
+/// `<component> '<name>': ` (e.g., `prog 'handle__sched_wakeup': `).
+#[cfg(feature = "log")]
+fn split_component(msg: &str) -> (Option<&str>, Option<&str>, &str) {
+    fn is_ident(s: &str) -> bool {
+        !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    }
+
+    let msg = msg.trim_end_matches('\n');
+    let rest = match msg.strip_prefix("libbpf: ") {
+        Some(rest) => rest,
+        None => return (None, None, msg),
+    };
+
+    if let Some((component, tail)) = rest.split_once(" '") {
+        if let Some((name, text)) = tail.split_once("': ") {
+            if is_ident(component) {
+                return (Some(component), Some(name), text);
+            }
+        }
+    }
+
+    if let Some((component, text)) = rest.split_once(": ") {
+        if is_ident(component) {
+            return (Some(component), None, text);
+        }
+    }
+    (Some("libbpf"), None, rest)
+}
+
+#[cfg(feature = "log")]
+fn print_to_log(level: PrintLevel, msg: String) {
+    let level = match level {
+        PrintLevel::Debug => log::Level::Debug,
+        PrintLevel::Info => log::Level::Info,
+        PrintLevel::Warn => log::Level::Warn,
+    };
+    let (component, name, text) = split_component(&msg);
+    log::log!(target: LOG_TARGET, level, component = component, name = name; "{text}");
+}
+
+/// Forward libbpf output of level `min_level` or more severe to the
+/// [`log`](https://docs.rs/log) crate.
+///
+/// This is a convenience wrapper around [`set_print`], installing a
+/// callback that emits each message under the `libbpf` target, at the
+/// [`log::Level`] corresponding to its [`PrintLevel`]. The trailing
+/// newline as well as the `libbpf: ` prefix are stripped and, if
+/// present, the originating component (e.g., `prog` or `map`) and the
+/// name of the object concerned are attached as the `component` and
+/// `name` key-value pairs, respectively.
+///
+/// The previously installed callback is returned, allowing for it to
+/// be restored via [`set_print`].
+///
+/// # Examples
+///
+/// ```
+/// use libbpf_rs::forward_to_log;
+/// use libbpf_rs::PrintLevel;
+///
+/// let _prev = forward_to_log(PrintLevel::Info);
+/// ```
+#[cfg(feature = "log")]
+pub fn forward_to_log(min_level: PrintLevel) -> Option<(PrintLevel, PrintCallback)> {
+    set_print(Some((min_level, print_to_log)))
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use super::*;
+
+    /// Check that we correctly split libbpf messages into their
+    /// components.
+    #[test]
+    fn component_splitting() {
+        let msg =
+            "libbpf: prog 'handle__sched_wakeup': BPF program load failed: Permission denied\n";
+        assert_eq!(
+            split_component(msg),
+            (
+                Some("prog"),
+                Some("handle__sched_wakeup"),
+                "BPF program load failed: Permission denied"
+            )
+        );
+
+        let msg = "libbpf: map 'events': created successfully, fd=4\n";
+        assert_eq!(
+            split_component(msg),
+            (Some("map"), Some("events"), "created successfully, fd=4")
+        );
+
+        let msg = "libbpf: elf: skipping unrecognized data section(5) .rodata.str1.1\n";
+        assert_eq!(
+            split_component(msg),
+            (
+                Some("elf"),
+                None,
+                "skipping unrecognized data section(5) .rodata.str1.1"
+            )
+        );
+
+        let msg = "libbpf: loading object 'runqslower' from buffer\n";
+        assert_eq!(
+            split_component(msg),
+            (
+                Some("libbpf"),
+                None,
+                "loading object 'runqslower' from buffer"
+            )
+        );
+
+        let msg = "0: (b7) r0 = 0\n";
+        assert_eq!(split_component(msg), (None, None, "0: (b7) r0 = 0"));
+    }
 }
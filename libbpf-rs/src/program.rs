@@ -1,6 +1,11 @@
+use std::collections::HashSet;
 use std::ffi::c_void;
 use std::ffi::CStr;
 use std::ffi::OsStr;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::mem;
 use std::mem::size_of;
 use std::mem::size_of_val;
@@ -11,17 +16,33 @@ use std::os::unix::io::BorrowedFd;
 use std::os::unix::io::FromRawFd;
 use std::os::unix::io::OwnedFd;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr;
 use std::ptr::NonNull;
 use std::slice;
 
 use libbpf_sys::bpf_func_id;
 
+use crate::cgroup;
+use crate::error::IntoError as _;
+use crate::netfilter::NetfilterOpts;
+use crate::query::ChainEntry;
+use crate::query::ChainTarget;
+use crate::query::FuncInfo;
+use crate::query::LineInfo;
+use crate::query::ProgChain;
+use crate::query::ProgInfoQueryOptions;
+use crate::query::ProgramInfo;
 use crate::util;
 use crate::AsRawLibbpf;
 use crate::Error;
+use crate::ErrorExt as _;
+use crate::ErrorKind;
 use crate::Link;
+use crate::MapHandle;
+use crate::MapType;
 use crate::Result;
+use crate::XdpAttachment;
 
 /// Options to optionally be provided when attaching to a uprobe.
 #[derive(Clone, Debug, Default)]
@@ -45,6 +66,25 @@ pub struct UprobeOpts {
     pub _non_exhaustive: (),
 }
 
+/// Options to optionally be provided when attaching to a kprobe.
+#[derive(Clone, Debug, Default)]
+pub struct KprobeOpts {
+    /// Custom user-provided value accessible through `bpf_get_attach_cookie`.
+    pub cookie: u64,
+    /// Offset within the function to attach to.
+    pub offset: usize,
+    /// kprobe is return probe, invoked at function return time.
+    pub retprobe: bool,
+    /// The kernel module containing the function, if any.
+    ///
+    /// If set, the probe is attached to the module-qualified symbol
+    /// `<module>:<func_name>`, making it possible to trace functions
+    /// that reside in kernel modules.
+    pub module: Option<String>,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
 /// Options to optionally be provided when attaching to a USDT.
 #[derive(Clone, Debug, Default)]
 pub struct UsdtOpts {
@@ -70,6 +110,88 @@ impl From<UsdtOpts> for libbpf_sys::bpf_usdt_opts {
     }
 }
 
+/// The order in which a cgroup iterator visits cgroups.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CgroupIterOrder {
+    /// Let the kernel pick the order, which currently is the same as
+    /// [`CgroupIterOrder::DescendantsPre`].
+    #[default]
+    Unspec,
+    /// Only visit the starting cgroup itself.
+    SelfOnly,
+    /// Walk the descendants of the starting cgroup in pre-order.
+    DescendantsPre,
+    /// Walk the descendants of the starting cgroup in post-order.
+    DescendantsPost,
+    /// Walk the ancestors of the starting cgroup, up to the root.
+    AncestorsUp,
+}
+
+impl From<CgroupIterOrder> for libbpf_sys::bpf_cgroup_iter_order {
+    fn from(order: CgroupIterOrder) -> Self {
+        match order {
+            CgroupIterOrder::Unspec => libbpf_sys::BPF_CGROUP_ITER_ORDER_UNSPEC,
+            CgroupIterOrder::SelfOnly => libbpf_sys::BPF_CGROUP_ITER_SELF_ONLY,
+            CgroupIterOrder::DescendantsPre => libbpf_sys::BPF_CGROUP_ITER_DESCENDANTS_PRE,
+            CgroupIterOrder::DescendantsPost => libbpf_sys::BPF_CGROUP_ITER_DESCENDANTS_POST,
+            CgroupIterOrder::AncestorsUp => libbpf_sys::BPF_CGROUP_ITER_ANCESTORS_UP,
+        }
+    }
+}
+
+/// Parameters to provide when attaching to a
+/// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html),
+/// limiting the objects iterated over.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum IterOpts<'fd> {
+    /// Iterate over the elements of the referenced map, e.g., for
+    /// `bpf_map_elem` iterators.
+    Map(BorrowedFd<'fd>),
+    /// Iterate over cgroups, starting at the one referenced by the file
+    /// descriptor.
+    CgroupFd {
+        /// A file descriptor referencing the cgroup to start at.
+        fd: BorrowedFd<'fd>,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+    /// Iterate over cgroups, starting at the one with the given ID.
+    CgroupId {
+        /// The ID of the cgroup to start at.
+        id: u64,
+        /// The order in which to visit cgroups.
+        order: CgroupIterOrder,
+    },
+    /// Only iterate over the task (thread) with the given ID.
+    Tid(u32),
+    /// Only iterate over the tasks of the process with the given ID.
+    Pid(u32),
+    /// Only iterate over the tasks of the process referenced by the pidfd.
+    PidFd(BorrowedFd<'fd>),
+}
+
+impl From<IterOpts<'_>> for libbpf_sys::bpf_iter_link_info {
+    fn from(opts: IterOpts<'_>) -> Self {
+        let mut linkinfo = libbpf_sys::bpf_iter_link_info::default();
+        match opts {
+            IterOpts::Map(fd) => linkinfo.map.map_fd = fd.as_raw_fd() as _,
+            IterOpts::CgroupFd { fd, order } => {
+                linkinfo.cgroup.cgroup_fd = fd.as_raw_fd() as _;
+                linkinfo.cgroup.order = order.into();
+            }
+            IterOpts::CgroupId { id, order } => {
+                linkinfo.cgroup.cgroup_id = id;
+                linkinfo.cgroup.order = order.into();
+            }
+            IterOpts::Tid(tid) => linkinfo.task.tid = tid,
+            IterOpts::Pid(pid) => linkinfo.task.pid = pid,
+            IterOpts::PidFd(fd) => linkinfo.task.pid_fd = fd.as_raw_fd() as _,
+        }
+        linkinfo
+    }
+}
+
 /// Options to optionally be provided when attaching to a tracepoint.
 #[derive(Clone, Debug, Default)]
 pub struct TracepointOpts {
@@ -96,6 +218,174 @@ impl From<TracepointOpts> for libbpf_sys::bpf_tracepoint_opts {
     }
 }
 
+/// A list of symbols, e.g., for attaching a program to many kernel
+/// functions at once via [`Program::attach_kprobe_multi`].
+///
+/// Lists are typically read from a file in the format produced by
+/// tooling such as `bpftool` or found in tracefs'
+/// `available_filter_functions`: one symbol per line, optionally
+/// followed by additional whitespace separated columns, which are
+/// ignored. Empty lines and lines starting with `#` are skipped as
+/// well. Duplicate symbols are only retained once.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolList {
+    symbols: Vec<String>,
+    /// The symbols contained in `symbols`, for fast deduplication.
+    seen: HashSet<String>,
+}
+
+impl SymbolList {
+    /// Parse a symbol list from the provided reader.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut list = Self::default();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // SANITY: `line` is not empty and so there is at least one
+            //         token.
+            let sym = line.split_whitespace().next().unwrap();
+            let () = list.push(sym);
+        }
+        Ok(list)
+    }
+
+    /// Parse a symbol list from the file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("failed to open symbol list `{}`", path.display()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    fn push(&mut self, sym: &str) {
+        if !self.seen.contains(sym) {
+            let _inserted = self.seen.insert(sym.to_string());
+            let () = self.symbols.push(sym.to_string());
+        }
+    }
+
+    /// Retrieve the symbols contained in the list.
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    /// Check whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Retrieve the number of symbols in the list.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Resolve the symbols of this list against the kernel functions
+    /// available for tracing.
+    ///
+    /// The result is a list of all symbols that could be resolved and,
+    /// hence, are suitable for attachment, as well as the symbols that
+    /// failed to resolve. The latter would otherwise cause attachment
+    /// of the entire set to fail.
+    pub fn resolve_kernel(&self) -> Result<(SymbolList, Vec<String>)> {
+        const PATHS: [&str; 2] = [
+            "/sys/kernel/tracing/available_filter_functions",
+            "/sys/kernel/debug/tracing/available_filter_functions",
+        ];
+
+        let path = PATHS
+            .iter()
+            .find(|path| Path::new(path).exists())
+            .ok_or_error(io::ErrorKind::NotFound, || {
+                "available_filter_functions not found; is tracefs mounted?"
+            })?;
+        let available = Self::from_file(path)?;
+
+        let (resolved, unresolved) = self
+            .symbols
+            .iter()
+            .cloned()
+            .partition::<Vec<_>, _>(|sym| available.seen.contains(sym));
+        Ok((resolved.into_iter().collect(), unresolved))
+    }
+}
+
+impl<S> FromIterator<S> for SymbolList
+where
+    S: AsRef<str>,
+{
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let mut list = Self::default();
+        for sym in iter {
+            let () = list.push(sym.as_ref());
+        }
+        list
+    }
+}
+
+/// The `SO_DETACH_REUSEPORT_BPF` socket option, which `libc` does not
+/// provide for all architectures. Its value differs between
+/// architectures, mirroring the kernel's `asm/socket.h` headers.
+#[cfg(any(target_arch = "sparc", target_arch = "sparc64"))]
+const SO_DETACH_REUSEPORT_BPF: libc::c_int = 0x0047;
+#[cfg(not(any(target_arch = "sparc", target_arch = "sparc64")))]
+const SO_DETACH_REUSEPORT_BPF: libc::c_int = 68;
+
+/// Set the socket option `opt` on `socket` to the program file
+/// descriptor `prog_fd`, as is done for attaching and detaching
+/// programs to and from sockets.
+fn set_socket_prog(socket: BorrowedFd<'_>, opt: libc::c_int, prog_fd: i32) -> Result<()> {
+    // SAFETY: `prog_fd` is a valid `int` that outlives the call.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            opt,
+            (&prog_fd as *const i32).cast(),
+            size_of::<i32>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Resolve the virtual address `addr` to the file backing it and the
+/// corresponding offset within this file, based on the memory mappings
+/// listed in `maps` (in the format of `/proc/<pid>/maps`).
+///
+/// `proc_dir` is the `/proc/<pid>` directory of the process, used for
+/// referring to files that are no longer accessible through a regular
+/// path, e.g., because they were deleted or created via
+/// `memfd_create(2)`.
+fn resolve_file_offset<R: BufRead>(
+    maps: R,
+    proc_dir: &Path,
+    addr: usize,
+) -> Result<(PathBuf, usize)> {
+    let entry = util::parse_maps(maps)?
+        .into_iter()
+        .find(|entry| entry.range.contains(&addr))
+        .ok_or_else(|| {
+            Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!("address {addr:#x} is not mapped"),
+            )
+        })?;
+
+    let path = entry.file_path(proc_dir).ok_or_else(|| {
+        Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            format!("address {addr:#x} does not reside in a file backed mapping"),
+        )
+    })?;
+    let file_offset = addr - entry.range.start + entry.offset;
+    Ok((path, file_offset))
+}
+
 /// Represents a parsed but not yet loaded BPF program.
 ///
 /// This object exposes operations that need to happen before the program is loaded.
@@ -111,26 +401,44 @@ impl OpenProgram {
         Self { ptr }
     }
 
+    /// Set the type of the program, overriding the one inferred from its
+    /// section name.
     pub fn set_prog_type(&mut self, prog_type: ProgramType) {
-        unsafe {
-            libbpf_sys::bpf_program__set_type(self.ptr.as_ptr(), prog_type as u32);
-        }
+        // The call can only fail for programs that got loaded already,
+        // which an `OpenProgram` never is.
+        let _ret =
+            unsafe { libbpf_sys::bpf_program__set_type(self.ptr.as_ptr(), prog_type as u32) };
     }
 
-    // The `ProgramType` of this `OpenProgram`.
+    /// The `ProgramType` of this `OpenProgram`.
     pub fn prog_type(&self) -> ProgramType {
         ProgramType::from(unsafe { libbpf_sys::bpf_program__type(self.ptr.as_ptr()) })
     }
 
+    /// Set the attach type the program is expected to be attached with,
+    /// overriding the one inferred from its section name.
+    pub fn set_expected_attach_type(&mut self, attach_type: ProgramAttachType) {
+        // The call can only fail for programs that got loaded already,
+        // which an `OpenProgram` never is.
+        let _ret = unsafe {
+            libbpf_sys::bpf_program__set_expected_attach_type(self.ptr.as_ptr(), attach_type as u32)
+        };
+    }
+
+    /// Set the attach type the program is expected to be attached with.
+    #[deprecated(note = "use `OpenProgram::set_expected_attach_type` instead")]
     pub fn set_attach_type(&mut self, attach_type: ProgramAttachType) {
-        unsafe {
-            libbpf_sys::bpf_program__set_expected_attach_type(
-                self.ptr.as_ptr(),
-                attach_type as u32,
-            );
-        }
+        self.set_expected_attach_type(attach_type)
     }
 
+    /// The attach type the program is expected to be attached with.
+    pub fn expected_attach_type(&self) -> ProgramAttachType {
+        ProgramAttachType::from(unsafe {
+            libbpf_sys::bpf_program__expected_attach_type(self.ptr.as_ptr())
+        })
+    }
+
+    /// Set the index of the network interface to offload the program to.
     pub fn set_ifindex(&mut self, idx: u32) {
         unsafe {
             libbpf_sys::bpf_program__set_ifindex(self.ptr.as_ptr(), idx);
@@ -176,6 +484,54 @@ impl OpenProgram {
         util::parse_ret(ret)
     }
 
+    /// Return `true` if the bpf program is set to autoload, `false` otherwise.
+    pub fn autoload(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoload(self.ptr.as_ptr()) }
+    }
+
+    /// Set whether a bpf program should be automatically loaded, based on the
+    /// outcome of `cond`.
+    ///
+    /// This is a convenience wrapper around [`OpenProgram::set_autoload`]
+    /// intended to be used in conjunction with kernel feature probes, such as
+    /// [`ProgramType::is_supported`]. That allows objects to carry multiple
+    /// variants of a program (e.g., an `fentry` program and a `kprobe`
+    /// fallback), only one of which is picked at load time:
+    /// ```no_run
+    /// # use libbpf_rs::OpenProgram;
+    /// # use libbpf_rs::ProgramType;
+    /// # let fentry = || -> &mut OpenProgram { todo!() };
+    /// # let kprobe = || -> &mut OpenProgram { todo!() };
+    /// let loaded = fentry().load_if(|| ProgramType::Tracing.is_supported()).unwrap();
+    /// let _ = kprobe().set_autoload(!loaded).unwrap();
+    /// ```
+    ///
+    /// Returns the autoload state that was set.
+    pub fn load_if<F>(&mut self, cond: F) -> Result<bool>
+    where
+        F: FnOnce() -> Result<bool>,
+    {
+        let autoload = cond()?;
+        let () = self.set_autoload(autoload)?;
+        Ok(autoload)
+    }
+
+    /// Set whether a bpf program should be automatically attached by default
+    /// when the bpf object is attached through a skeleton.
+    ///
+    /// Only programs that are loaded can be attached. Disabling automatic
+    /// attachment can be used to defer the attachment of a program to a
+    /// later point in time or to make it optional altogether.
+    pub fn set_autoattach(&mut self, autoattach: bool) {
+        unsafe { libbpf_sys::bpf_program__set_autoattach(self.ptr.as_ptr(), autoattach) };
+    }
+
+    /// Return `true` if the bpf program is set to be auto-attached, `false`
+    /// otherwise.
+    pub fn autoattach(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoattach(self.ptr.as_ptr()) }
+    }
+
     pub fn set_attach_target(
         &mut self,
         attach_prog_fd: i32,
@@ -203,11 +559,20 @@ impl OpenProgram {
         util::parse_ret(ret)
     }
 
+    /// Set the `BPF_F_*` flags to load the program with, e.g.,
+    /// [`BPF_F_SLEEPABLE`][libbpf_sys::BPF_F_SLEEPABLE].
+    ///
+    /// The flags replace the ones inferred from the program's section name.
     pub fn set_flags(&self, flags: u32) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_program__set_flags(self.ptr.as_ptr(), flags) };
         util::parse_ret(ret)
     }
 
+    /// The `BPF_F_*` flags the program will be loaded with.
+    pub fn flags(&self) -> u32 {
+        unsafe { libbpf_sys::bpf_program__flags(self.ptr.as_ptr()) }
+    }
+
     /// Returns the number of instructions that form the program.
     ///
     /// Note: Keep in mind, libbpf can modify the program's instructions
@@ -232,6 +597,31 @@ impl OpenProgram {
         let ptr = unsafe { libbpf_sys::bpf_program__insns(self.ptr.as_ptr()) };
         unsafe { slice::from_raw_parts(ptr, count) }
     }
+
+    /// Replace the BPF program's underlying BPF instructions.
+    ///
+    /// The instructions are copied, so `insns` does not need to outlive
+    /// this call. This allows for patching a program's code in
+    /// userspace before it is loaded.
+    ///
+    /// Please note that libbpf performs its own rewriting of instructions
+    /// during load (see [`OpenProgram::insns`]). Replacing instructions
+    /// in a way that invalidates relocation information, e.g., by
+    /// inserting or removing instructions before a relocated one, will
+    /// likely result in load failures. Such transformations are best
+    /// performed as length-preserving rewrites.
+    pub fn set_insns(&mut self, insns: &[libbpf_sys::bpf_insn]) -> Result<()> {
+        // SAFETY: libbpf copies the instructions and never writes through
+        //         the provided pointer, despite it not being `const`.
+        let ret = unsafe {
+            libbpf_sys::bpf_program__set_insns(
+                self.ptr.as_ptr(),
+                insns.as_ptr().cast_mut(),
+                insns.len() as _,
+            )
+        };
+        util::parse_ret(ret)
+    }
 }
 
 impl AsRawLibbpf for OpenProgram {
@@ -246,9 +636,10 @@ impl AsRawLibbpf for OpenProgram {
 /// Type of a [`Program`]. Maps to `enum bpf_prog_type` in kernel uapi.
 #[non_exhaustive]
 #[repr(u32)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 // TODO: Document variants.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ProgramType {
     Unspec = 0,
     SocketFilter,
@@ -282,6 +673,7 @@ pub enum ProgramType {
     Lsm,
     SkLookup,
     Syscall,
+    Netfilter,
     /// See [`MapType::Unknown`][crate::MapType::Unknown]
     Unknown = u32::MAX,
 }
@@ -353,6 +745,7 @@ impl From<u32> for ProgramType {
             x if x == Lsm as u32 => Lsm,
             x if x == SkLookup as u32 => SkLookup,
             x if x == Syscall as u32 => Syscall,
+            x if x == Netfilter as u32 => Netfilter,
             _ => Unknown,
         }
     }
@@ -364,6 +757,7 @@ impl From<u32> for ProgramType {
 #[derive(Clone, Debug)]
 // TODO: Document variants.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ProgramAttachType {
     CgroupInetIngress,
     CgroupInetEgress,
@@ -407,10 +801,120 @@ pub enum ProgramAttachType {
     SkReuseportSelect,
     SkReuseportSelectOrMigrate,
     PerfEvent,
+    TraceKprobeMulti,
+    LsmCgroup,
+    StructOps,
+    Netfilter,
+    TcxIngress,
+    TcxEgress,
+    TraceUprobeMulti,
+    CgroupUnixConnect,
+    CgroupUnixSendmsg,
+    CgroupUnixRecvmsg,
+    CgroupUnixGetpeername,
+    CgroupUnixGetsockname,
+    NetkitPrimary,
+    NetkitPeer,
     /// See [`MapType::Unknown`][crate::MapType::Unknown]
     Unknown = u32::MAX,
 }
 
+impl ProgramAttachType {
+    /// Check whether programs with this attach type get attached to
+    /// cgroups.
+    pub fn is_cgroup(&self) -> bool {
+        use ProgramAttachType::*;
+
+        matches!(
+            self,
+            CgroupInetIngress
+                | CgroupInetEgress
+                | CgroupInetSockCreate
+                | CgroupSockOps
+                | CgroupDevice
+                | CgroupInet4Bind
+                | CgroupInet6Bind
+                | CgroupInet4Connect
+                | CgroupInet6Connect
+                | CgroupInet4PostBind
+                | CgroupInet6PostBind
+                | CgroupUdp4Sendmsg
+                | CgroupUdp6Sendmsg
+                | CgroupSysctl
+                | CgroupUdp4Recvmsg
+                | CgroupUdp6Recvmsg
+                | CgroupGetsockopt
+                | CgroupSetsockopt
+                | CgroupInet4Getpeername
+                | CgroupInet6Getpeername
+                | CgroupInet4Getsockname
+                | CgroupInet6Getsockname
+                | CgroupInetSockRelease
+                | LsmCgroup
+                | CgroupUnixConnect
+                | CgroupUnixSendmsg
+                | CgroupUnixRecvmsg
+                | CgroupUnixGetpeername
+                | CgroupUnixGetsockname
+        )
+    }
+
+    /// Retrieve the type programs using this attach type have to be of.
+    ///
+    /// `None` is returned for [`ProgramAttachType::Unknown`].
+    pub fn prog_type(&self) -> Option<ProgramType> {
+        use ProgramAttachType::*;
+
+        let prog_type = match self {
+            CgroupInetIngress | CgroupInetEgress => ProgramType::CgroupSkb,
+            CgroupInetSockCreate
+            | CgroupInet4PostBind
+            | CgroupInet6PostBind
+            | CgroupInetSockRelease => ProgramType::CgroupSock,
+            CgroupSockOps => ProgramType::SockOps,
+            SkSkbStreamParser | SkSkbStreamVerdict | SkSkbVerdict => ProgramType::SkSkb,
+            CgroupDevice => ProgramType::CgroupDevice,
+            SkMsgVerdict => ProgramType::SkMsg,
+            CgroupInet4Bind
+            | CgroupInet6Bind
+            | CgroupInet4Connect
+            | CgroupInet6Connect
+            | CgroupUdp4Sendmsg
+            | CgroupUdp6Sendmsg
+            | CgroupUdp4Recvmsg
+            | CgroupUdp6Recvmsg
+            | CgroupInet4Getpeername
+            | CgroupInet6Getpeername
+            | CgroupInet4Getsockname
+            | CgroupInet6Getsockname => ProgramType::CgroupSockAddr,
+            LircMode2 => ProgramType::LircMode2,
+            FlowDissector => ProgramType::FlowDissector,
+            CgroupSysctl => ProgramType::CgroupSysctl,
+            CgroupGetsockopt | CgroupSetsockopt => ProgramType::CgroupSockopt,
+            TraceRawTp | TraceFentry | TraceFexit | ModifyReturn | TraceIter => {
+                ProgramType::Tracing
+            }
+            LsmMac => ProgramType::Lsm,
+            XdpDevmap | XdpCpumap | Xdp => ProgramType::Xdp,
+            SkLookup => ProgramType::SkLookup,
+            SkReuseportSelect | SkReuseportSelectOrMigrate => ProgramType::SkReuseport,
+            PerfEvent => ProgramType::PerfEvent,
+            TraceKprobeMulti | TraceUprobeMulti => ProgramType::Kprobe,
+            LsmCgroup => ProgramType::Lsm,
+            StructOps => ProgramType::StructOps,
+            Netfilter => ProgramType::Netfilter,
+            TcxIngress | TcxEgress | NetkitPrimary | NetkitPeer => ProgramType::SchedCls,
+            CgroupUnixConnect
+            | CgroupUnixSendmsg
+            | CgroupUnixRecvmsg
+            | CgroupUnixGetpeername
+            | CgroupUnixGetsockname => ProgramType::CgroupSockAddr,
+            Unknown => return None,
+        };
+        Some(prog_type)
+    }
+}
+
 impl From<u32> for ProgramAttachType {
     fn from(value: u32) -> Self {
         use ProgramAttachType::*;
@@ -458,6 +962,20 @@ impl From<u32> for ProgramAttachType {
             x if x == SkReuseportSelect as u32 => SkReuseportSelect,
             x if x == SkReuseportSelectOrMigrate as u32 => SkReuseportSelectOrMigrate,
             x if x == PerfEvent as u32 => PerfEvent,
+            x if x == TraceKprobeMulti as u32 => TraceKprobeMulti,
+            x if x == LsmCgroup as u32 => LsmCgroup,
+            x if x == StructOps as u32 => StructOps,
+            x if x == Netfilter as u32 => Netfilter,
+            x if x == TcxIngress as u32 => TcxIngress,
+            x if x == TcxEgress as u32 => TcxEgress,
+            x if x == TraceUprobeMulti as u32 => TraceUprobeMulti,
+            x if x == CgroupUnixConnect as u32 => CgroupUnixConnect,
+            x if x == CgroupUnixSendmsg as u32 => CgroupUnixSendmsg,
+            x if x == CgroupUnixRecvmsg as u32 => CgroupUnixRecvmsg,
+            x if x == CgroupUnixGetpeername as u32 => CgroupUnixGetpeername,
+            x if x == CgroupUnixGetsockname as u32 => CgroupUnixGetsockname,
+            x if x == NetkitPrimary as u32 => NetkitPrimary,
+            x if x == NetkitPeer as u32 => NetkitPeer,
             _ => Unknown,
         }
     }
@@ -555,6 +1073,20 @@ impl Program {
         ProgramType::from(unsafe { libbpf_sys::bpf_program__type(self.ptr.as_ptr()) })
     }
 
+    /// Create an owned handle to the program by duplicating its file
+    /// descriptor.
+    ///
+    /// The program's own file descriptor is closed when the
+    /// [`Object`][crate::Object] it belongs to is dropped. The returned one
+    /// keeps the program loaded beyond that, e.g., when it got registered
+    /// with an `epoll` instance or is handed to another subsystem. Use
+    /// [`IntoRawFd::into_raw_fd`][std::os::unix::io::IntoRawFd::into_raw_fd]
+    /// on it to keep it open past its drop.
+    pub fn try_clone_fd(&self) -> Result<OwnedFd> {
+        let fd = self.as_fd().try_clone_to_owned()?;
+        Ok(fd)
+    }
+
     /// Returns program fd by id
     pub fn get_fd_by_id(id: u32) -> Result<OwnedFd> {
         let ret = unsafe { libbpf_sys::bpf_prog_get_fd_by_id(id) };
@@ -598,6 +1130,12 @@ impl Program {
         unsafe { libbpf_sys::bpf_program__autoload(self.ptr.as_ptr()) }
     }
 
+    /// Return `true` if the bpf program is set to be auto-attached, `false`
+    /// otherwise.
+    pub fn autoattach(&self) -> bool {
+        unsafe { libbpf_sys::bpf_program__autoattach(self.ptr.as_ptr()) }
+    }
+
     /// Return the bpf program's log level.
     pub fn log_level(&self) -> u32 {
         unsafe { libbpf_sys::bpf_program__log_level(self.ptr.as_ptr()) }
@@ -606,11 +1144,12 @@ impl Program {
     /// [Pin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
     /// this program to bpffs.
     pub fn pin<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
         let path_c = util::path_to_cstring(path)?;
         let path_ptr = path_c.as_ptr();
 
         let ret = unsafe { libbpf_sys::bpf_program__pin(self.ptr.as_ptr(), path_ptr) };
-        util::parse_ret(ret)
+        util::parse_pin_ret(ret, path)
     }
 
     /// [Unpin](https://facebookmicrosites.github.io/bpf/blog/2018/08/31/object-lifetime.html#bpffs)
@@ -636,7 +1175,27 @@ impl Program {
 
     /// Attach this program to a
     /// [cgroup](https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html).
+    ///
+    /// The program is attached with its [attach type][Program::attach_type],
+    /// which has to be a cgroup attach type (see
+    /// [`ProgramAttachType::is_cgroup`]) matching the program's type. For
+    /// example, programs of type [`ProgramType::CgroupSockopt`] can be
+    /// attached with [`ProgramAttachType::CgroupGetsockopt`] or
+    /// [`ProgramAttachType::CgroupSetsockopt`], as inferred from their
+    /// `cgroup/getsockopt` or `cgroup/setsockopt` section, respectively.
     pub fn attach_cgroup(&mut self, cgroup_fd: i32) -> Result<Link> {
+        let attach_type = self.attach_type();
+        if !attach_type.is_cgroup() || attach_type.prog_type() != Some(self.prog_type()) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "program `{}` of type {:?} cannot be attached to a cgroup with attach type {attach_type:?}",
+                    self.name().to_string_lossy(),
+                    self.prog_type(),
+                ),
+            ));
+        }
+
         util::create_bpf_entity_checked(|| unsafe {
             libbpf_sys::bpf_program__attach_cgroup(self.ptr.as_ptr(), cgroup_fd)
         })
@@ -646,6 +1205,16 @@ impl Program {
         })
     }
 
+    /// Attach this program to the
+    /// [cgroup](https://www.kernel.org/doc/html/latest/admin-guide/cgroup-v2.html)
+    /// at the given path, e.g., `/sys/fs/cgroup/foo`.
+    pub fn attach_cgroup_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Link> {
+        let cgroup = cgroup::open(path)?;
+        // The link keeps a reference to the cgroup, so there is no need
+        // to keep the file descriptor open afterwards.
+        self.attach_cgroup(cgroup.as_raw_fd())
+    }
+
     /// Attach this program to a [perf event](https://linux.die.net/man/2/perf_event_open).
     pub fn attach_perf_event(&mut self, pfd: i32) -> Result<Link> {
         util::create_bpf_entity_checked(|| unsafe {
@@ -703,13 +1272,22 @@ impl Program {
             _non_exhaustive,
         } = opts;
 
-        let func_name = util::str_to_cstring(&func_name)?;
+        // An empty function name means that `func_offset` is an
+        // absolute offset into the binary, which libbpf expects to be
+        // signaled by a NULL name.
+        let func_name = if func_name.is_empty() {
+            None
+        } else {
+            Some(util::str_to_cstring(&func_name)?)
+        };
         let opts = libbpf_sys::bpf_uprobe_opts {
             sz: size_of::<libbpf_sys::bpf_uprobe_opts>() as _,
             ref_ctr_offset: ref_ctr_offset as libbpf_sys::size_t,
             bpf_cookie: cookie,
             retprobe,
-            func_name: func_name.as_ptr(),
+            func_name: func_name
+                .as_ref()
+                .map_or(ptr::null(), |func_name| func_name.as_ptr()),
             ..Default::default()
         };
 
@@ -728,6 +1306,45 @@ impl Program {
         })
     }
 
+    /// Attach this program to a [userspace
+    /// probe](https://www.kernel.org/doc/html/latest/trace/uprobetracer.html)
+    /// at the virtual address `addr` in the address space of process
+    /// `pid` (`0` referring to the calling process).
+    ///
+    /// The address is resolved to the file backing it, and the offset
+    /// therein, based on the process' current memory mappings. That
+    /// makes it possible to probe code whose location is only known at
+    /// runtime, e.g., code emitted by a JIT compiler into a file backed
+    /// (including `memfd_create(2)` backed) mapping. Addresses in
+    /// anonymous mappings cannot be probed.
+    ///
+    /// `opts.func_name` has to be empty, as the probed location is
+    /// already fully determined by `addr`.
+    pub fn attach_uprobe_addr(&mut self, pid: i32, addr: usize, opts: UprobeOpts) -> Result<Link> {
+        if !opts.func_name.is_empty() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                "function name cannot be used when attaching by address",
+            ));
+        }
+
+        let proc_dir = match pid {
+            0 => PathBuf::from("/proc/self"),
+            pid if pid > 0 => PathBuf::from(format!("/proc/{pid}")),
+            _ => {
+                return Err(Error::with_io_error(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot resolve address for pid {pid}"),
+                ))
+            }
+        };
+        let maps_path = proc_dir.join("maps");
+        let maps = File::open(&maps_path)
+            .with_context(|| format!("failed to open `{}`", maps_path.display()))?;
+        let (path, file_offset) = resolve_file_offset(BufReader::new(maps), &proc_dir, addr)?;
+        self.attach_uprobe_with_opts(pid, path, file_offset, opts)
+    }
+
     /// Attach this program to a [kernel
     /// probe](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html).
     pub fn attach_kprobe<T: AsRef<str>>(&mut self, retprobe: bool, func_name: T) -> Result<Link> {
@@ -742,6 +1359,129 @@ impl Program {
         })
     }
 
+    /// Attach this program to a [kernel
+    /// probe](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html),
+    /// providing additional options.
+    pub fn attach_kprobe_with_opts<T: AsRef<str>>(
+        &mut self,
+        func_name: T,
+        opts: KprobeOpts,
+    ) -> Result<Link> {
+        let KprobeOpts {
+            cookie,
+            offset,
+            retprobe,
+            module,
+            _non_exhaustive,
+        } = opts;
+
+        let func_name = func_name.as_ref();
+        let func_name = match module {
+            Some(module) => util::str_to_cstring(&format!("{module}:{func_name}"))?,
+            None => util::str_to_cstring(func_name)?,
+        };
+        let func_name_ptr = func_name.as_ptr();
+        let opts = libbpf_sys::bpf_kprobe_opts {
+            sz: size_of::<libbpf_sys::bpf_kprobe_opts>() as _,
+            bpf_cookie: cookie,
+            offset: offset as libbpf_sys::size_t,
+            retprobe,
+            ..Default::default()
+        };
+
+        util::create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::bpf_program__attach_kprobe_opts(
+                self.ptr.as_ptr(),
+                func_name_ptr,
+                &opts as *const _,
+            )
+        })
+        .map(|ptr| unsafe {
+            // SAFETY: the pointer came from libbpf and has been checked for errors
+            Link::new(ptr)
+        })
+    }
+
+    /// Attach this program to multiple [kernel
+    /// probes](https://www.kernel.org/doc/html/latest/trace/kprobetrace.html)
+    /// at once.
+    ///
+    /// Attachment fails as a whole if any of the symbols can't be
+    /// resolved. Use [`SymbolList::resolve_kernel`] to filter out such
+    /// symbols beforehand.
+    pub fn attach_kprobe_multi<T: AsRef<str>>(
+        &mut self,
+        retprobe: bool,
+        symbols: &[T],
+    ) -> Result<Link> {
+        let symbols = symbols
+            .iter()
+            .map(|sym| util::str_to_cstring(sym.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut syms = symbols.iter().map(|sym| sym.as_ptr()).collect::<Vec<_>>();
+
+        let opts = libbpf_sys::bpf_kprobe_multi_opts {
+            sz: size_of::<libbpf_sys::bpf_kprobe_multi_opts>() as _,
+            syms: syms.as_mut_ptr(),
+            cnt: syms.len() as _,
+            retprobe,
+            ..Default::default()
+        };
+
+        util::create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::bpf_program__attach_kprobe_multi_opts(
+                self.ptr.as_ptr(),
+                ptr::null(),
+                &opts as *const _,
+            )
+        })
+        .map(|ptr| unsafe {
+            // SAFETY: the pointer came from libbpf and has been checked for errors
+            Link::new(ptr)
+        })
+    }
+
+    /// Attach this program to multiple [userspace
+    /// probes](https://www.kernel.org/doc/html/latest/trace/uprobetracer.html)
+    /// in the binary at `binary_path` at once.
+    pub fn attach_uprobe_multi<T: AsRef<str>>(
+        &mut self,
+        retprobe: bool,
+        pid: i32,
+        binary_path: impl AsRef<Path>,
+        symbols: &[T],
+    ) -> Result<Link> {
+        let path = util::path_to_cstring(binary_path)?;
+        let path_ptr = path.as_ptr();
+        let symbols = symbols
+            .iter()
+            .map(|sym| util::str_to_cstring(sym.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut syms = symbols.iter().map(|sym| sym.as_ptr()).collect::<Vec<_>>();
+
+        let opts = libbpf_sys::bpf_uprobe_multi_opts {
+            sz: size_of::<libbpf_sys::bpf_uprobe_multi_opts>() as _,
+            syms: syms.as_mut_ptr(),
+            cnt: syms.len() as _,
+            retprobe,
+            ..Default::default()
+        };
+
+        util::create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::bpf_program__attach_uprobe_multi(
+                self.ptr.as_ptr(),
+                pid,
+                path_ptr,
+                ptr::null(),
+                &opts as *const _,
+            )
+        })
+        .map(|ptr| unsafe {
+            // SAFETY: the pointer came from libbpf and has been checked for errors
+            Link::new(ptr)
+        })
+    }
+
     /// Attach this program to the specified syscall
     pub fn attach_ksyscall<T: AsRef<str>>(
         &mut self,
@@ -839,6 +1579,19 @@ impl Program {
         })
     }
 
+    /// Attach this program to a netfilter hook, as described by
+    /// `netfilter_opts`.
+    pub fn attach_netfilter_with_opts(&mut self, netfilter_opts: NetfilterOpts) -> Result<Link> {
+        let netfilter_opts = libbpf_sys::bpf_netfilter_opts::from(netfilter_opts);
+        util::create_bpf_entity_checked(|| unsafe {
+            libbpf_sys::bpf_program__attach_netfilter(self.ptr.as_ptr(), &netfilter_opts)
+        })
+        .map(|ptr| unsafe {
+            // SAFETY: the pointer came from libbpf and has been checked for errors
+            Link::new(ptr)
+        })
+    }
+
     /// Attach to an [LSM](https://en.wikipedia.org/wiki/Linux_Security_Modules) hook
     pub fn attach_lsm(&mut self) -> Result<Link> {
         util::create_bpf_entity_checked(|| unsafe {
@@ -861,6 +1614,102 @@ impl Program {
         })
     }
 
+    /// Attach this program, of type [`ProgramType::SkReuseport`], to the
+    /// `SO_REUSEPORT` group `socket` is part of, for selecting the socket
+    /// of the group that receives an incoming packet or connection.
+    ///
+    /// The attachment persists until it is undone via
+    /// [`Program::detach_sk_reuseport`] or all sockets of the group got
+    /// closed.
+    pub fn attach_sk_reuseport(&self, socket: &impl AsFd) -> Result<()> {
+        set_socket_prog(
+            socket.as_fd(),
+            libc::SO_ATTACH_REUSEPORT_EBPF,
+            self.as_fd().as_raw_fd(),
+        )
+        .context("failed to attach reuseport program")
+    }
+
+    /// Detach the program attached to the `SO_REUSEPORT` group `socket`
+    /// is part of, undoing a previous [`Program::attach_sk_reuseport`].
+    pub fn detach_sk_reuseport(socket: &impl AsFd) -> Result<()> {
+        set_socket_prog(socket.as_fd(), SO_DETACH_REUSEPORT_BPF, 0)
+            .context("failed to detach reuseport program")
+    }
+
+    /// Attach this program, of type [`ProgramType::SocketFilter`], to
+    /// `socket`, for filtering the packets it receives.
+    ///
+    /// Any filter previously attached to the socket gets replaced. The
+    /// attachment persists until it is undone via
+    /// [`Program::detach_socket_filter`] or the socket is closed.
+    ///
+    /// ```no_run
+    /// # use std::net::UdpSocket;
+    /// # use libbpf_rs::Object;
+    /// # fn run(obj: &Object) -> libbpf_rs::Result<()> {
+    /// let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// let prog = obj.prog("socket_filter").unwrap();
+    /// let () = prog.attach_socket_filter(&socket)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn attach_socket_filter(&self, socket: &impl AsFd) -> Result<()> {
+        set_socket_prog(
+            socket.as_fd(),
+            libc::SO_ATTACH_BPF,
+            self.as_fd().as_raw_fd(),
+        )
+        .context("failed to attach socket filter")
+    }
+
+    /// Detach the filter attached to `socket`, undoing a previous
+    /// [`Program::attach_socket_filter`].
+    pub fn detach_socket_filter(socket: &impl AsFd) -> Result<()> {
+        set_socket_prog(socket.as_fd(), libc::SO_DETACH_BPF, 0)
+            .context("failed to detach socket filter")
+    }
+
+    /// Attach this program, of type [`ProgramType::SockOps`], to the
+    /// cgroup referenced by `cgroup`.
+    ///
+    /// The program is attached with
+    /// [`BPF_F_ALLOW_MULTI`][libbpf_sys::BPF_F_ALLOW_MULTI], so that it
+    /// coexists with other programs attached to the cgroup, and is
+    /// detached again once the returned [`ProgAttachment`] is dropped.
+    pub fn attach_sockops(&self, cgroup: BorrowedFd<'_>) -> Result<ProgAttachment> {
+        ProgAttachment::new(
+            self,
+            cgroup,
+            ProgramAttachType::CgroupSockOps,
+            libbpf_sys::BPF_F_ALLOW_MULTI,
+        )
+        .context("failed to attach sockops program")
+    }
+
+    /// Attach this program, of type [`ProgramType::SkMsg`], as message
+    /// verdict program to `sockmap`, which has to be a
+    /// [`MapType::Sockmap`][crate::MapType::Sockmap] or
+    /// [`MapType::Sockhash`][crate::MapType::Sockhash].
+    ///
+    /// The program is detached again once the returned
+    /// [`ProgAttachment`] is dropped.
+    pub fn attach_sk_msg(&self, sockmap: &MapHandle) -> Result<ProgAttachment> {
+        if !matches!(sockmap.map_type(), MapType::Sockmap | MapType::Sockhash) {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map `{}` of type {:?} is not a sockmap or sockhash",
+                    sockmap.name(),
+                    sockmap.map_type()
+                ),
+            ));
+        }
+
+        ProgAttachment::new(self, sockmap.as_fd(), ProgramAttachType::SkMsgVerdict, 0)
+            .context("failed to attach sk_msg program")
+    }
+
     /// Attach a verdict/parser to a [sockmap/sockhash](https://lwn.net/Articles/731133/)
     pub fn attach_sockmap(&self, map_fd: i32) -> Result<()> {
         let err = unsafe {
@@ -874,6 +1723,187 @@ impl Program {
         util::parse_ret(err)
     }
 
+    /// Attach this program to `target` using the legacy
+    /// `BPF_PROG_ATTACH` command.
+    ///
+    /// Some attach points, such as cgroup device controllers or sockmap
+    /// parsers and verdicts, are only supported this way on some or all
+    /// kernels. Contrary to BPF links, the attachment is not tied to the
+    /// lifetime of any object and persists until it is explicitly
+    /// undone via [`Program::prog_detach`].
+    ///
+    /// `flags` are `BPF_F_*` flags such as
+    /// [`BPF_F_ALLOW_MULTI`][libbpf_sys::BPF_F_ALLOW_MULTI].
+    pub fn prog_attach(
+        &self,
+        target: BorrowedFd<'_>,
+        attach_type: ProgramAttachType,
+        flags: u32,
+    ) -> Result<()> {
+        // SAFETY: `bpf_prog_attach` is always safe to call.
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_attach(
+                self.as_fd().as_raw_fd(),
+                target.as_raw_fd(),
+                attach_type as u32,
+                flags,
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Detach this program from `target`, undoing a previous
+    /// [`Program::prog_attach`].
+    pub fn prog_detach(
+        &self,
+        target: BorrowedFd<'_>,
+        attach_type: ProgramAttachType,
+    ) -> Result<()> {
+        // SAFETY: `bpf_prog_detach2` is always safe to call.
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_detach2(
+                self.as_fd().as_raw_fd(),
+                target.as_raw_fd(),
+                attach_type as u32,
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Retrieve the IDs of the programs attached to `target` with the
+    /// given attach type.
+    pub fn prog_query(target: BorrowedFd<'_>, attach_type: ProgramAttachType) -> Result<Vec<u32>> {
+        let attach_type = attach_type as u32;
+        let mut ids = Vec::new();
+
+        loop {
+            let mut attach_flags = 0;
+            let mut cnt = ids.len() as u32;
+            let ids_ptr = if ids.is_empty() {
+                ptr::null_mut()
+            } else {
+                ids.as_mut_ptr()
+            };
+            // SAFETY: All pointers are valid and `ids_ptr` points to
+            //         storage for `cnt` IDs, if it is not NULL.
+            let ret = unsafe {
+                libbpf_sys::bpf_prog_query(
+                    target.as_raw_fd(),
+                    attach_type,
+                    0,
+                    &mut attach_flags,
+                    ids_ptr,
+                    &mut cnt,
+                )
+            };
+
+            match util::parse_ret(ret) {
+                // Without a buffer, the kernel just reports the number of
+                // attached programs.
+                Ok(()) if ids_ptr.is_null() && cnt > 0 => ids.resize(cnt as usize, 0),
+                Ok(()) => {
+                    let () = ids.truncate(cnt as usize);
+                    break Ok(ids);
+                }
+                // More programs got attached in the mean time.
+                Err(err) if err.raw_os_error() == Some(libc::ENOSPC) => {
+                    ids.resize(ids.len() * 2, 0)
+                }
+                Err(err) => break Err(err),
+            }
+        }
+    }
+
+    /// Retrieve the programs attached to the multi-attach point `target`
+    /// with the given attach type, in the order in which they are run.
+    ///
+    /// Contrary to [`Program::prog_query`], this method reports the
+    /// attach flags and links of the individual programs as well as the
+    /// attach point's revision, allowing for verifying that a program
+    /// ended up in the intended position, e.g., when it got attached
+    /// with [`BPF_F_BEFORE`][libbpf_sys::BPF_F_BEFORE] or
+    /// [`BPF_F_AFTER`][libbpf_sys::BPF_F_AFTER].
+    pub fn query_chain(
+        target: ChainTarget<'_>,
+        attach_type: ProgramAttachType,
+    ) -> Result<ProgChain> {
+        let target = match target {
+            ChainTarget::Fd(fd) => fd.as_raw_fd(),
+            ChainTarget::Ifindex(ifindex) => ifindex,
+        };
+        // Only tcx and netkit attach points report links; older kernels
+        // reject queries asking for them.
+        let with_links = matches!(
+            attach_type,
+            ProgramAttachType::TcxIngress
+                | ProgramAttachType::TcxEgress
+                | ProgramAttachType::NetkitPrimary
+                | ProgramAttachType::NetkitPeer
+        );
+        let attach_type = attach_type as u32;
+        let mut prog_ids = Vec::new();
+        let mut prog_flags = Vec::new();
+        let mut link_ids = Vec::new();
+        let mut link_flags = Vec::new();
+
+        loop {
+            let mut opts = libbpf_sys::bpf_prog_query_opts {
+                sz: size_of::<libbpf_sys::bpf_prog_query_opts>() as _,
+                ..Default::default()
+            };
+            opts.__bindgen_anon_1.count = prog_ids.len() as u32;
+            if !prog_ids.is_empty() {
+                opts.prog_ids = prog_ids.as_mut_ptr();
+                opts.prog_attach_flags = prog_flags.as_mut_ptr();
+                if with_links {
+                    opts.link_ids = link_ids.as_mut_ptr();
+                    opts.link_attach_flags = link_flags.as_mut_ptr();
+                }
+            }
+
+            // SAFETY: `opts` is valid and all buffers provide storage for
+            //         `count` entries, if set.
+            let ret = unsafe { libbpf_sys::bpf_prog_query_opts(target, attach_type, &mut opts) };
+            // SAFETY: Both union members are plain integers.
+            let cnt = unsafe { opts.__bindgen_anon_1.count } as usize;
+            let new_len = match util::parse_ret(ret) {
+                // Without buffers, the kernel just reports the number of
+                // attached programs.
+                Ok(()) if prog_ids.is_empty() && cnt > 0 => cnt,
+                Ok(()) => {
+                    let entries = (0..cnt)
+                        .map(|idx| {
+                            let link_id = (link_ids[idx] != 0).then_some(link_ids[idx]);
+                            ChainEntry {
+                                prog_id: prog_ids[idx],
+                                link_id,
+                                attach_flags: if link_id.is_some() {
+                                    link_flags[idx]
+                                } else {
+                                    prog_flags[idx]
+                                },
+                            }
+                        })
+                        .collect();
+                    let chain = ProgChain {
+                        revision: opts.revision,
+                        attach_flags: opts.attach_flags,
+                        entries,
+                    };
+                    break Ok(chain);
+                }
+                // More programs got attached in the mean time.
+                Err(err) if err.raw_os_error() == Some(libc::ENOSPC) => prog_ids.len() * 2,
+                Err(err) => break Err(err),
+            };
+
+            let () = prog_ids.resize(new_len, 0);
+            let () = prog_flags.resize(new_len, 0);
+            let () = link_ids.resize(new_len, 0);
+            let () = link_flags.resize(new_len, 0);
+        }
+    }
+
     /// Attach this program to [XDP](https://lwn.net/Articles/825998/)
     pub fn attach_xdp(&mut self, ifindex: i32) -> Result<Link> {
         util::create_bpf_entity_checked(|| unsafe {
@@ -885,6 +1915,22 @@ impl Program {
         })
     }
 
+    /// Attach this program to [XDP](https://lwn.net/Articles/825998/),
+    /// falling back to the legacy netlink based attachment on kernels
+    /// that do not support XDP BPF links.
+    ///
+    /// Similar to BPF links, the fallback fails if another program is
+    /// already attached to the interface.
+    pub fn attach_xdp_with_fallback(&mut self, ifindex: i32) -> Result<XdpAttachment> {
+        match self.attach_xdp(ifindex) {
+            Ok(link) => Ok(XdpAttachment::from_link(link)),
+            Err(err) if matches!(err.kind(), ErrorKind::InvalidInput | ErrorKind::Unsupported) => {
+                XdpAttachment::attach_legacy(ifindex, self.as_fd())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     /// Attach this program to [netns-based programs](https://lwn.net/Articles/819618/)
     pub fn attach_netns(&mut self, netns_fd: i32) -> Result<Link> {
         util::create_bpf_entity_checked(|| unsafe {
@@ -896,6 +1942,33 @@ impl Program {
         })
     }
 
+    /// Attach this [flow dissector](https://docs.kernel.org/bpf/prog_flow_dissector.html)
+    /// program to the network namespace referred to by `netns_fd`, e.g.,
+    /// an open `/proc/<pid>/ns/net` file.
+    ///
+    /// Only a single flow dissector program can be attached to a network
+    /// namespace at any time. The program stays attached until the
+    /// returned [`Link`] is detached or dropped.
+    pub fn attach_flow_dissector(&mut self, netns_fd: BorrowedFd<'_>) -> Result<Link> {
+        if self.prog_type() != ProgramType::FlowDissector {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "program `{}` is not a flow dissector",
+                    self.name().to_string_lossy()
+                ),
+            ));
+        }
+        self.attach_netns(netns_fd.as_raw_fd())
+    }
+
+    /// Retrieve the ID of the flow dissector program attached to the
+    /// network namespace referred to by `netns_fd`, if any.
+    pub fn query_flow_dissector(netns_fd: BorrowedFd<'_>) -> Result<Option<u32>> {
+        let ids = Self::prog_query(netns_fd, ProgramAttachType::FlowDissector)?;
+        Ok(ids.first().copied())
+    }
+
     fn attach_usdt_impl(
         &mut self,
         pid: i32,
@@ -975,9 +2048,16 @@ impl Program {
     /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html).
     /// The entry point of the program must be defined with `SEC("iter")` or `SEC("iter.s")`.
     pub fn attach_iter(&mut self, map_fd: BorrowedFd<'_>) -> Result<Link> {
+        self.attach_iter_with_opts(IterOpts::Map(map_fd))
+    }
+
+    /// Attach this program to a
+    /// [BPF Iterator](https://www.kernel.org/doc/html/latest/bpf/bpf_iterators.html),
+    /// providing additional parameters via `IterOpts`.
+    /// The entry point of the program must be defined with `SEC("iter")` or `SEC("iter.s")`.
+    pub fn attach_iter_with_opts(&mut self, opts: IterOpts<'_>) -> Result<Link> {
         util::create_bpf_entity_checked(|| unsafe {
-            let mut linkinfo = libbpf_sys::bpf_iter_link_info::default();
-            linkinfo.map.map_fd = map_fd.as_raw_fd() as _;
+            let mut linkinfo = libbpf_sys::bpf_iter_link_info::from(opts);
             let attach_opt = libbpf_sys::bpf_iter_attach_opts {
                 link_info: &mut linkinfo as *mut libbpf_sys::bpf_iter_link_info,
                 link_info_len: size_of::<libbpf_sys::bpf_iter_link_info>() as _,
@@ -1073,6 +2153,33 @@ impl Program {
         let ptr = unsafe { libbpf_sys::bpf_program__insns(self.ptr.as_ptr()) };
         unsafe { slice::from_raw_parts(ptr, count) }
     }
+
+    /// Retrieve the BTF function information of the loaded program,
+    /// describing the functions (the main program and any subprograms)
+    /// it is comprised of.
+    ///
+    /// Function names can be resolved using the BTF of the object the
+    /// program is part of, see [`Object::btf`][crate::Object::btf].
+    pub fn func_infos(&self) -> Result<Vec<FuncInfo>> {
+        let opts = ProgInfoQueryOptions::default().include_func_info(true);
+        let info = ProgramInfo::from_fd(self.as_fd(), &opts)?;
+        let infos = info.func_info.iter().map(FuncInfo::from).collect();
+        Ok(infos)
+    }
+
+    /// Retrieve the BTF line information of the loaded program, mapping
+    /// instruction offsets (as reported by the verifier, for example) to
+    /// source code locations.
+    ///
+    /// Entries are sorted by instruction offset; use [`LineInfo::find`]
+    /// for looking up the one describing a specific instruction. Source
+    /// file names and lines can be resolved using the BTF of the object
+    /// the program is part of, see [`Object::btf`][crate::Object::btf].
+    pub fn line_infos(&self) -> Result<Vec<LineInfo>> {
+        let opts = ProgInfoQueryOptions::default().include_line_info(true);
+        let info = ProgramInfo::from_fd(self.as_fd(), &opts)?;
+        Ok(info.line_info)
+    }
 }
 
 impl AsRawLibbpf for Program {
@@ -1084,11 +2191,114 @@ impl AsRawLibbpf for Program {
     }
 }
 
+/// A program attached to a target via the legacy `BPF_PROG_ATTACH`
+/// command, as created by, e.g., [`Program::attach_sockops`].
+///
+/// Contrary to an attachment made via [`Program::prog_attach`], the
+/// program is detached again once this object is dropped or explicitly
+/// detached.
+#[derive(Debug)]
+pub struct ProgAttachment {
+    prog_fd: OwnedFd,
+    target_fd: OwnedFd,
+    attach_type: ProgramAttachType,
+    detached: bool,
+}
+
+impl ProgAttachment {
+    fn new(
+        prog: &Program,
+        target: BorrowedFd<'_>,
+        attach_type: ProgramAttachType,
+        flags: u32,
+    ) -> Result<Self> {
+        let prog_fd = prog.as_fd().try_clone_to_owned()?;
+        let target_fd = target.try_clone_to_owned()?;
+        let () = prog.prog_attach(target, attach_type.clone(), flags)?;
+
+        let slf = Self {
+            prog_fd,
+            target_fd,
+            attach_type,
+            detached: false,
+        };
+        Ok(slf)
+    }
+
+    /// Retrieve the attach type used for the attachment.
+    pub fn attach_type(&self) -> ProgramAttachType {
+        self.attach_type.clone()
+    }
+
+    /// Retrieve the file descriptor of the target the program is
+    /// attached to.
+    pub fn target(&self) -> BorrowedFd<'_> {
+        self.target_fd.as_fd()
+    }
+
+    fn detach_impl(&mut self) -> Result<()> {
+        if self.detached {
+            return Ok(());
+        }
+        self.detached = true;
+
+        // SAFETY: `bpf_prog_detach2` is always safe to call.
+        let ret = unsafe {
+            libbpf_sys::bpf_prog_detach2(
+                self.prog_fd.as_raw_fd(),
+                self.target_fd.as_raw_fd(),
+                self.attach_type.clone() as u32,
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Detach the program from its target.
+    pub fn detach(mut self) -> Result<()> {
+        self.detach_impl()
+    }
+}
+
+impl Drop for ProgAttachment {
+    fn drop(&mut self) {
+        let _result = self.detach_impl();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use std::mem::discriminant;
+    use std::path::Path;
+
+    use crate::ObjectBuilder;
+
+    /// Check that we can override the load time attributes of an
+    /// `OpenProgram`.
+    #[test]
+    fn open_program_setters() {
+        let path = util::test_object_path("runqslower.bpf.o");
+        let mut open_obj = ObjectBuilder::default().open_file(path).unwrap();
+        let prog = open_obj.prog_mut("handle__sched_wakeup").unwrap();
+        assert_eq!(prog.prog_type(), ProgramType::Tracing);
+        assert!(matches!(
+            prog.expected_attach_type(),
+            ProgramAttachType::TraceRawTp
+        ));
+        assert_eq!(prog.flags(), 0);
+
+        let () = prog.set_prog_type(ProgramType::RawTracepoint);
+        assert_eq!(prog.prog_type(), ProgramType::RawTracepoint);
+        let () = prog.set_expected_attach_type(ProgramAttachType::TraceFentry);
+        assert!(matches!(
+            prog.expected_attach_type(),
+            ProgramAttachType::TraceFentry
+        ));
+        let () = prog.set_flags(libbpf_sys::BPF_F_SLEEPABLE).unwrap();
+        assert_eq!(prog.flags(), libbpf_sys::BPF_F_SLEEPABLE);
+        let () = prog.set_ifindex(1);
+    }
 
     #[test]
     fn program_type() {
@@ -1127,6 +2337,7 @@ mod tests {
             Lsm,
             SkLookup,
             Syscall,
+            Netfilter,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
@@ -1134,6 +2345,52 @@ mod tests {
         }
     }
 
+    /// Check that we can parse a symbol list.
+    #[test]
+    fn symbol_list_parsing() {
+        let input = b"# comment\n\nfoo\nbar [mod]\n  baz  \nfoo\n";
+        let list = SymbolList::from_reader(&input[..]).unwrap();
+        assert_eq!(list.symbols(), ["foo", "bar", "baz"]);
+        assert_eq!(list.len(), 3);
+
+        let list = ["a", "b", "a"].into_iter().collect::<SymbolList>();
+        assert_eq!(list.symbols(), ["a", "b"]);
+
+        let list = SymbolList::from_reader(&b""[..]).unwrap();
+        assert!(list.is_empty());
+    }
+
+    /// Check that we can resolve virtual addresses to file offsets.
+    #[test]
+    fn file_offset_resolution() {
+        let maps = b"\
+55d1c4a00000-55d1c4a28000 r--p 00000000 fd:01 1704138 /usr/bin/cat
+55d1c4a28000-55d1c4a3d000 r-xp 00028000 fd:01 1704138 /usr/bin/cat
+7f5b3c000000-7f5b3c021000 rw-p 00000000 00:00 0 
+7f5b3d000000-7f5b3d001000 r-xp 00001000 00:01 4242 /memfd:jit (deleted)
+7ffc8a7e1000-7ffc8a802000 rw-p 00000000 00:00 0                          [stack]
+";
+        let proc_dir = Path::new("/proc/1234");
+
+        let (path, offset) = resolve_file_offset(&maps[..], proc_dir, 0x55d1c4a28010).unwrap();
+        assert_eq!(path, Path::new("/usr/bin/cat"));
+        assert_eq!(offset, 0x28010);
+
+        let (path, offset) = resolve_file_offset(&maps[..], proc_dir, 0x7f5b3d000020).unwrap();
+        assert_eq!(
+            path,
+            Path::new("/proc/1234/map_files/7f5b3d000000-7f5b3d001000")
+        );
+        assert_eq!(offset, 0x1020);
+
+        let err = resolve_file_offset(&maps[..], proc_dir, 0x7f5b3c000010).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let err = resolve_file_offset(&maps[..], proc_dir, 0x7ffc8a7e1000).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let err = resolve_file_offset(&maps[..], proc_dir, 0x1000).unwrap_err();
+        assert!(err.to_string().contains("not mapped"), "{err}");
+    }
+
     #[test]
     fn program_attach_type() {
         use ProgramAttachType::*;
@@ -1181,6 +2438,20 @@ mod tests {
             SkReuseportSelect,
             SkReuseportSelectOrMigrate,
             PerfEvent,
+            TraceKprobeMulti,
+            LsmCgroup,
+            StructOps,
+            Netfilter,
+            TcxIngress,
+            TcxEgress,
+            TraceUprobeMulti,
+            CgroupUnixConnect,
+            CgroupUnixSendmsg,
+            CgroupUnixRecvmsg,
+            CgroupUnixGetpeername,
+            CgroupUnixGetsockname,
+            NetkitPrimary,
+            NetkitPeer,
             Unknown,
         ] {
             // check if discriminants match after a roundtrip conversion
@@ -1190,4 +2461,31 @@ mod tests {
             );
         }
     }
+
+    /// Check that attach types are classified as expected.
+    #[test]
+    fn attach_type_classification() {
+        assert!(ProgramAttachType::CgroupSetsockopt.is_cgroup());
+        assert!(ProgramAttachType::CgroupSockOps.is_cgroup());
+        assert!(!ProgramAttachType::SkMsgVerdict.is_cgroup());
+        assert!(!ProgramAttachType::Unknown.is_cgroup());
+
+        assert_eq!(
+            ProgramAttachType::CgroupGetsockopt.prog_type(),
+            Some(ProgramType::CgroupSockopt)
+        );
+        assert_eq!(
+            ProgramAttachType::CgroupSetsockopt.prog_type(),
+            Some(ProgramType::CgroupSockopt)
+        );
+        assert_eq!(
+            ProgramAttachType::CgroupSockOps.prog_type(),
+            Some(ProgramType::SockOps)
+        );
+        assert_eq!(
+            ProgramAttachType::CgroupInet6Connect.prog_type(),
+            Some(ProgramType::CgroupSockAddr)
+        );
+        assert_eq!(ProgramAttachType::Unknown.prog_type(), None);
+    }
 }
@@ -11,8 +11,15 @@
 //! ```
 
 use std::ffi::c_void;
+use std::ffi::CStr;
 use std::ffi::CString;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
 use std::io;
+use std::mem;
+use std::mem::size_of;
 use std::mem::size_of_val;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
@@ -23,12 +30,74 @@ use std::os::raw::c_char;
 use std::ptr;
 use std::time::Duration;
 
+use crate::btf::BtfType;
+use crate::btf::TypeId;
+use crate::map;
 use crate::util;
+use crate::Btf;
+use crate::Error;
 use crate::MapType;
 use crate::ProgramAttachType;
 use crate::ProgramType;
 use crate::Result;
 
+/// Helpers for serializing fields whose types do not implement
+/// `serde::Serialize` in a suitable way.
+#[cfg(feature = "serde")]
+mod ser {
+    use std::ffi::c_void;
+    use std::ffi::CStr;
+
+    use serde::ser::SerializeSeq as _;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    /// Serialize a C string as a (lossily converted) UTF-8 string.
+    pub(super) fn cstr<S>(s: &CStr, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&s.to_string_lossy())
+    }
+
+    /// Serialize kernel addresses as integers.
+    pub(super) fn addrs<S>(addrs: &[*const c_void], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(addrs.len()))?;
+        for addr in addrs {
+            let () = seq.serialize_element(&(*addr as usize))?;
+        }
+        seq.end()
+    }
+
+    /// Serialize function information records.
+    pub(super) fn func_info<S>(
+        infos: &[libbpf_sys::bpf_func_info],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct FuncInfo {
+            insn_off: u32,
+            type_id: u32,
+        }
+
+        let mut seq = serializer.serialize_seq(Some(infos.len()))?;
+        for info in infos {
+            let info = FuncInfo {
+                insn_off: info.insn_off,
+                type_id: info.type_id,
+            };
+            let () = seq.serialize_element(&info)?;
+        }
+        seq.end()
+    }
+}
+
 macro_rules! gen_info_impl {
     // This magic here allows us to embed doc comments into macro expansions
     ($(#[$attr:meta])*
@@ -92,6 +161,7 @@ macro_rules! gen_info_impl {
 
 /// BTF Line information
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LineInfo {
     /// Offset of instruction in vector
     pub insn_off: u32,
@@ -117,51 +187,282 @@ impl From<&libbpf_sys::bpf_line_info> for LineInfo {
     }
 }
 
+impl LineInfo {
+    /// Retrieve the name of the source file this line information refers
+    /// to, given the program's [`Btf`] (as retrieved by
+    /// [`ProgramInfo::btf`]).
+    pub fn file_name<'btf>(&self, btf: &'btf Btf<'_>) -> Option<&'btf OsStr> {
+        btf.name_at(self.file_name_off)
+    }
+
+    /// Retrieve the source code line this line information refers to,
+    /// given the program's [`Btf`] (as retrieved by [`ProgramInfo::btf`]).
+    pub fn source_line<'btf>(&self, btf: &'btf Btf<'_>) -> Option<&'btf OsStr> {
+        btf.name_at(self.line_off)
+    }
+}
+
+impl LineInfo {
+    /// Find the entry describing the instruction at offset `insn_off`
+    /// in `infos`, which is expected to be sorted by instruction offset
+    /// (as reported by the kernel).
+    ///
+    /// That is the last entry starting at or before `insn_off`.
+    pub fn find(infos: &[Self], insn_off: u32) -> Option<&Self> {
+        let idx = infos.partition_point(|info| info.insn_off <= insn_off);
+        idx.checked_sub(1).map(|idx| &infos[idx])
+    }
+}
+
+/// BTF function information
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FuncInfo {
+    /// Offset of the function's first instruction
+    pub insn_off: u32,
+    /// The function's BTF type
+    pub type_id: TypeId,
+}
+
+impl From<&libbpf_sys::bpf_func_info> for FuncInfo {
+    fn from(item: &libbpf_sys::bpf_func_info) -> Self {
+        FuncInfo {
+            insn_off: item.insn_off,
+            type_id: TypeId::from(item.type_id),
+        }
+    }
+}
+
+impl FuncInfo {
+    /// Retrieve the name of the function, given the program's [`Btf`].
+    pub fn name<'btf>(&self, btf: &'btf Btf<'_>) -> Option<&'btf OsStr> {
+        btf.type_by_id::<BtfType<'_>>(self.type_id)?.name()
+    }
+}
+
 /// Bpf identifier tag
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct Tag([u8; 8]);
 
+impl From<[u8; 8]> for Tag {
+    fn from(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Tag {
+    /// Retrieve the raw bytes making up the tag.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl Display for Tag {
+    /// Format the tag as a hexadecimal string, the way `bpftool` does.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tag {
+    /// Serialize the tag as a hexadecimal string, the same way it is
+    /// displayed.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// The network device a BPF program or map is offloaded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OffloadDevice {
+    /// The index of the network interface.
+    pub ifindex: u32,
+    /// The device of the network namespace the interface belongs to.
+    pub netns_dev: u64,
+    /// The inode of the network namespace the interface belongs to.
+    pub netns_ino: u64,
+}
+
+impl OffloadDevice {
+    /// Create an `OffloadDevice` from the raw information reported by
+    /// the kernel, if it indicates an offloaded entity.
+    pub(crate) fn from_raw(ifindex: u32, netns_dev: u64, netns_ino: u64) -> Option<Self> {
+        (ifindex != 0).then_some(Self {
+            ifindex,
+            netns_dev,
+            netns_ino,
+        })
+    }
+}
+
 /// Information about a BPF program
+///
+/// Vector members are only populated if requested through the
+/// [`ProgInfoQueryOptions`] in use.
 #[derive(Debug, Clone)]
-// TODO: Document members.
-#[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ProgramInfo {
+    /// The name of the program.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstr"))]
     pub name: CString,
+    /// The type of the program.
     pub ty: ProgramType,
+    /// The tag of the program, a hash of its instructions.
     pub tag: Tag,
+    /// The ID of the program.
     pub id: u32,
+    /// The JIT compiled machine code of the program.
     pub jited_prog_insns: Vec<u8>,
+    /// The translated BPF instructions of the program, as run by the
+    /// kernel. See [`ProgramInfo::xlated_insns`] for a typed view.
     pub xlated_prog_insns: Vec<u8>,
     /// Duration since system boot
     pub load_time: Duration,
+    /// The ID of the user that loaded the program.
     pub created_by_uid: u32,
+    /// The IDs of the maps used by the program.
     pub map_ids: Vec<u32>,
+    /// The index of the network interface the program is offloaded to.
     pub ifindex: u32,
+    /// Whether the program is licensed in a GPL compatible way.
     pub gpl_compatible: bool,
+    /// The device of the network namespace of an offloaded program.
     pub netns_dev: u64,
+    /// The inode of the network namespace of an offloaded program.
     pub netns_ino: u64,
+    /// The kernel addresses of the JIT compiled functions.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::addrs"))]
     pub jited_ksyms: Vec<*const c_void>,
+    /// The lengths of the JIT compiled functions.
     pub jited_func_lens: Vec<u32>,
+    /// The ID of the program's BTF.
     pub btf_id: u32,
+    /// The size of each function information record.
     pub func_info_rec_size: u32,
+    /// Function information, mapping instruction offsets to `Func` types
+    /// in the program's BTF.
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::func_info"))]
     pub func_info: Vec<libbpf_sys::bpf_func_info>,
+    /// Source line information corresponding to translated instructions.
     pub line_info: Vec<LineInfo>,
+    /// The kernel addresses corresponding to [`ProgramInfo::line_info`].
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::addrs"))]
     pub jited_line_info: Vec<*const c_void>,
+    /// The size of each line information record.
     pub line_info_rec_size: u32,
+    /// The size of each JIT compiled line information record.
     pub jited_line_info_rec_size: u32,
+    /// The tags of the program and its subprograms.
     pub prog_tags: Vec<Tag>,
+    /// The accumulated run time of the program, in nanoseconds.
+    ///
+    /// Only maintained while BPF statistics are enabled.
     pub run_time_ns: u64,
+    /// The number of times the program ran.
+    ///
+    /// Only maintained while BPF statistics are enabled.
     pub run_cnt: u64,
     /// Skipped BPF executions due to recursion or concurrent execution prevention.
     pub recursion_misses: u64,
 }
 
+impl ProgramInfo {
+    /// Retrieve the translated BPF instructions of the program.
+    ///
+    /// This method returns an empty vector unless translated instructions
+    /// were requested via [`ProgInfoQueryOptions::include_xlated_prog_insns`].
+    pub fn xlated_insns(&self) -> Vec<libbpf_sys::bpf_insn> {
+        self.xlated_prog_insns
+            .chunks_exact(size_of::<libbpf_sys::bpf_insn>())
+            .map(|chunk| {
+                // SAFETY: `bpf_insn` is plain old data valid for any bit
+                //         pattern and `chunk` is of the proper size.
+                unsafe { ptr::read_unaligned(chunk.as_ptr().cast::<libbpf_sys::bpf_insn>()) }
+            })
+            .collect()
+    }
+
+    /// Load the program's BTF from the kernel, if it has any.
+    ///
+    /// The BTF can be used for resolving [`ProgramInfo::func_info`] and
+    /// [`ProgramInfo::line_info`] entries.
+    pub fn btf(&self) -> Result<Option<Btf<'static>>> {
+        if self.btf_id == 0 {
+            return Ok(None);
+        }
+        Btf::from_btf_id(self.btf_id).map(Some)
+    }
+
+    /// Look up the first loaded program with the given name.
+    ///
+    /// Note that the kernel only keeps the first
+    /// `BPF_OBJ_NAME_LEN - 1` bytes of a program's name, so only that
+    /// many bytes of `name` are compared.
+    pub fn by_name(name: &str) -> Result<Option<Self>> {
+        Ok(ProgInfoIter::with_name(name)?.next())
+    }
+
+    /// Look up the first loaded program with the given tag.
+    pub fn by_tag(tag: &Tag) -> Option<Self> {
+        ProgInfoIter::with_tag(tag.clone()).next()
+    }
+
+    /// Retrieve the network device the program is offloaded to, if
+    /// any.
+    pub fn offload_device(&self) -> Option<OffloadDevice> {
+        OffloadDevice::from_raw(self.ifindex, self.netns_dev, self.netns_ino)
+    }
+}
+
+/// Convert `name` into the form the kernel reports it in, i.e.,
+/// truncated to `BPF_OBJ_NAME_LEN - 1` bytes.
+fn kernel_name(name: &str) -> Result<CString> {
+    let len = name.len().min(libbpf_sys::BPF_OBJ_NAME_LEN as usize - 1);
+    CString::new(&name.as_bytes()[..len]).map_err(|e| Error::with_invalid_data(e.to_string()))
+}
+
+/// Criteria that information about BPF objects is matched against,
+/// before retrieving it in full.
+#[derive(Clone, Debug)]
+struct InfoFilter<T> {
+    ty: Option<T>,
+    name: Option<CString>,
+    tag: Option<Tag>,
+}
+
+impl<T> Default for InfoFilter<T> {
+    fn default() -> Self {
+        Self {
+            ty: None,
+            name: None,
+            tag: None,
+        }
+    }
+}
+
+impl<T> InfoFilter<T>
+where
+    T: PartialEq,
+{
+    fn matches(&self, ty: &T, name: &CStr, tag: Option<&Tag>) -> bool {
+        self.ty.as_ref().map_or(true, |t| t == ty)
+            && self.name.as_deref().map_or(true, |n| n == name)
+            && self.tag.as_ref().map_or(true, |t| Some(t) == tag)
+    }
+}
+
 /// An iterator for the information of loaded bpf programs
 #[derive(Default, Debug)]
 pub struct ProgInfoIter {
     cur_id: u32,
     opts: ProgInfoQueryOptions,
+    filter: InfoFilter<ProgramType>,
 }
 
 /// Options to query the program info currently loaded
@@ -195,6 +496,38 @@ impl ProgInfoIter {
             ..Self::default()
         }
     }
+
+    /// Generate an iter only yielding programs of the given type.
+    pub fn with_type(ty: ProgramType) -> Self {
+        let mut iter = Self::default();
+        iter.filter.ty = Some(ty);
+        iter
+    }
+
+    /// Generate an iter only yielding programs with the given name.
+    ///
+    /// Note that the kernel only keeps the first
+    /// `BPF_OBJ_NAME_LEN - 1` bytes of a program's name, so only that
+    /// many bytes of `name` are compared.
+    pub fn with_name(name: &str) -> Result<Self> {
+        let mut iter = Self::default();
+        iter.filter.name = Some(kernel_name(name)?);
+        Ok(iter)
+    }
+
+    /// Generate an iter only yielding programs with the given tag.
+    pub fn with_tag(tag: Tag) -> Self {
+        let mut iter = Self::default();
+        iter.filter.tag = Some(tag);
+        iter
+    }
+
+    /// Set the query options to use for programs matching the
+    /// iterator's filter criteria.
+    pub fn query_opts(mut self, opts: ProgInfoQueryOptions) -> Self {
+        self.opts = opts;
+        self
+    }
 }
 
 impl ProgInfoQueryOptions {
@@ -269,7 +602,19 @@ impl ProgInfoQueryOptions {
 }
 
 impl ProgramInfo {
-    fn load_from_fd(fd: BorrowedFd<'_>, opts: &ProgInfoQueryOptions) -> Result<Self> {
+    /// Retrieve information about the program referred to by `fd`,
+    /// including the parts selected by `opts`.
+    pub(crate) fn from_fd(fd: BorrowedFd<'_>, opts: &ProgInfoQueryOptions) -> Result<Self> {
+        let info = Self::load_from_fd(fd, opts, &InfoFilter::default())?;
+        // SANITY: The default filter matches all programs.
+        Ok(info.unwrap())
+    }
+
+    fn load_from_fd(
+        fd: BorrowedFd<'_>,
+        opts: &ProgInfoQueryOptions,
+        filter: &InfoFilter<ProgramType>,
+    ) -> Result<Option<Self>> {
         let mut item = libbpf_sys::bpf_prog_info::default();
 
         let mut xlated_prog_insns: Vec<u8> = Vec::new();
@@ -293,6 +638,13 @@ impl ProgramInfo {
         // SANITY: `libbpf` should guarantee NUL termination.
         let name = util::c_char_slice_to_cstr(&item.name).unwrap();
         let ty = ProgramType::from(item.type_);
+        let tag = Tag(item.tag);
+
+        // Check the cheaply retrieved attributes first, so that we don't
+        // have to retrieve the remainder for programs not of interest.
+        if !filter.matches(&ty, name, Some(&tag)) {
+            return Ok(None);
+        }
 
         if opts.include_xlated_prog_insns {
             xlated_prog_insns.resize(item.xlated_prog_len as usize, 0u8);
@@ -368,10 +720,10 @@ impl ProgramInfo {
         };
         util::parse_ret(ret)?;
 
-        return Ok(ProgramInfo {
+        return Ok(Some(ProgramInfo {
             name: name.to_owned(),
             ty,
-            tag: Tag(item.tag),
+            tag,
             id: item.id,
             jited_prog_insns,
             xlated_prog_insns,
@@ -395,7 +747,7 @@ impl ProgramInfo {
             run_time_ns: item.run_time_ns,
             run_cnt: item.run_cnt,
             recursion_misses: item.recursion_misses,
-        });
+        }));
     }
 }
 
@@ -424,14 +776,17 @@ impl Iterator for ProgInfoIter {
     type Item = ProgramInfo;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let fd = self.next_valid_fd()?;
+        loop {
+            let fd = self.next_valid_fd()?;
 
-        let prog = ProgramInfo::load_from_fd(fd.as_fd(), &self.opts);
+            let prog = ProgramInfo::load_from_fd(fd.as_fd(), &self.opts, &self.filter);
 
-        match prog {
-            Ok(p) => Some(p),
-            // TODO: We should consider bubbling up errors properly.
-            Err(_err) => None,
+            match prog {
+                Ok(Some(p)) => return Some(p),
+                Ok(None) => continue,
+                // TODO: We should consider bubbling up errors properly.
+                Err(_err) => return None,
+            }
         }
     }
 }
@@ -440,7 +795,9 @@ impl Iterator for ProgInfoIter {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MapInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstr"))]
     pub name: CString,
     pub ty: MapType,
     pub id: u32,
@@ -455,6 +812,9 @@ pub struct MapInfo {
     pub btf_id: u32,
     pub btf_key_type_id: u32,
     pub btf_value_type_id: u32,
+    /// Map type specific extra data, e.g., the number of hash functions
+    /// used by a bloom filter.
+    pub map_extra: u64,
 }
 
 impl MapInfo {
@@ -478,31 +838,161 @@ impl MapInfo {
             btf_id: s.btf_id,
             btf_key_type_id: s.btf_key_type_id,
             btf_value_type_id: s.btf_value_type_id,
+            map_extra: s.map_extra,
         })
     }
+
+    /// Load the map's BTF from the kernel, if it has any.
+    ///
+    /// The BTF can be used for resolving the key and value types, via
+    /// [`MapInfo::key_btf`] and [`MapInfo::value_btf`].
+    pub fn btf(&self) -> Result<Option<Btf<'static>>> {
+        if self.btf_id == 0 {
+            return Ok(None);
+        }
+        Btf::from_btf_id(self.btf_id).map(Some)
+    }
+
+    /// Resolve the type of the map's keys in `btf`, as retrieved by
+    /// [`MapInfo::btf`].
+    pub fn key_btf<'btf>(&self, btf: &'btf Btf<'_>) -> Option<BtfType<'btf>> {
+        map::resolve_btf_type(btf, self.btf_key_type_id)
+    }
+
+    /// Resolve the type of the map's values in `btf`, as retrieved by
+    /// [`MapInfo::btf`].
+    pub fn value_btf<'btf>(&self, btf: &'btf Btf<'_>) -> Option<BtfType<'btf>> {
+        map::resolve_btf_type(btf, self.btf_value_type_id)
+    }
+
+    /// Retrieve the network device the map is offloaded to, if any.
+    pub fn offload_device(&self) -> Option<OffloadDevice> {
+        OffloadDevice::from_raw(self.ifindex, self.netns_dev, self.netns_ino)
+    }
 }
 
-gen_info_impl!(
-    /// Iterator that returns [`MapInfo`]s.
-    MapInfoIter,
-    MapInfo,
-    libbpf_sys::bpf_map_info,
-    libbpf_sys::bpf_map_get_next_id,
-    libbpf_sys::bpf_map_get_fd_by_id
-);
+/// Iterator that returns [`MapInfo`]s.
+#[derive(Default, Debug)]
+pub struct MapInfoIter {
+    cur_id: u32,
+    filter: InfoFilter<MapType>,
+}
+
+impl MapInfoIter {
+    /// Generate an iter only yielding maps of the given type.
+    pub fn with_type(ty: MapType) -> Self {
+        let mut iter = Self::default();
+        iter.filter.ty = Some(ty);
+        iter
+    }
+
+    /// Generate an iter only yielding maps with the given name.
+    ///
+    /// Note that the kernel only keeps the first
+    /// `BPF_OBJ_NAME_LEN - 1` bytes of a map's name, so only that
+    /// many bytes of `name` are compared.
+    pub fn with_name(name: &str) -> Result<Self> {
+        let mut iter = Self::default();
+        iter.filter.name = Some(kernel_name(name)?);
+        Ok(iter)
+    }
+
+    // Returns Some(next_valid_fd), None on none left
+    fn next_valid_fd(&mut self) -> Option<OwnedFd> {
+        loop {
+            if unsafe { libbpf_sys::bpf_map_get_next_id(self.cur_id, &mut self.cur_id) } != 0 {
+                return None;
+            }
+
+            let fd = unsafe { libbpf_sys::bpf_map_get_fd_by_id(self.cur_id) };
+            if fd < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::NotFound {
+                    continue;
+                }
+                return None;
+            }
+
+            return Some(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+    }
+}
+
+impl Iterator for MapInfoIter {
+    type Item = MapInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let fd = self.next_valid_fd()?;
+
+            // We need to use std::mem::zeroed() instead of just using
+            // ::default() because padding bytes need to be zero as well.
+            let mut item: libbpf_sys::bpf_map_info = unsafe { mem::zeroed() };
+            let item_ptr: *mut libbpf_sys::bpf_map_info = &mut item;
+            let mut len = size_of_val(&item) as u32;
+
+            let ret = unsafe {
+                libbpf_sys::bpf_obj_get_info_by_fd(
+                    fd.as_raw_fd(),
+                    item_ptr as *mut c_void,
+                    &mut len,
+                )
+            };
+            if ret != 0 {
+                return None;
+            }
+
+            let info = MapInfo::from_uapi(fd.as_fd(), item)?;
+            if self.filter.matches(&info.ty, &info.name, None) {
+                return Some(info);
+            }
+        }
+    }
+}
 
 /// Information about BPF type format
+///
+/// When serialized, the raw btf data is omitted.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BtfInfo {
     /// The name associated with this btf information in the kernel
+    #[cfg_attr(feature = "serde", serde(serialize_with = "ser::cstr"))]
     pub name: CString,
     /// The raw btf bytes from the kernel
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub btf: Vec<u8>,
     /// The btf id associated with this btf information in the kernel
     pub id: u32,
+    /// Whether this is btf information of the kernel itself or of a kernel
+    /// module, as opposed to that of a BPF object
+    pub kernel_btf: bool,
 }
 
 impl BtfInfo {
+    /// The size of the raw btf data, in bytes.
+    pub fn size(&self) -> usize {
+        self.btf.len()
+    }
+
+    /// Check whether this is the btf information of a kernel module.
+    pub fn is_module(&self) -> bool {
+        self.kernel_btf && self.name.as_bytes() != b"vmlinux"
+    }
+
+    /// Instantiate a [`Btf`] object for this btf information.
+    ///
+    /// Kernel module btf is split btf and is loaded on top of the vmlinux
+    /// btf, so that the types of both are accessible.
+    pub fn to_btf(&self) -> Result<Btf<'static>> {
+        if self.is_module() {
+            let name = self.name.to_str().map_err(Error::with_invalid_data)?;
+            Btf::from_module(name)
+        } else {
+            Btf::from_btf_id(self.id)
+        }
+    }
+
     fn load_from_fd(fd: BorrowedFd<'_>) -> Result<Self> {
         let mut item = libbpf_sys::bpf_btf_info::default();
         let mut btf: Vec<u8> = Vec::new();
@@ -537,6 +1027,7 @@ impl BtfInfo {
             name: CString::from_vec_with_nul(name).unwrap(),
             btf,
             id: item.id,
+            kernel_btf: item.kernel_btf != 0,
         })
     }
 }
@@ -589,6 +1080,7 @@ impl Iterator for BtfInfoIter {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RawTracepointLinkInfo {
     pub name: String,
 }
@@ -596,6 +1088,7 @@ pub struct RawTracepointLinkInfo {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TracingLinkInfo {
     pub attach_type: ProgramAttachType,
 }
@@ -603,6 +1096,7 @@ pub struct TracingLinkInfo {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CgroupLinkInfo {
     pub cgroup_id: u64,
     pub attach_type: ProgramAttachType,
@@ -611,20 +1105,40 @@ pub struct CgroupLinkInfo {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NetNsLinkInfo {
     pub ino: u32,
     pub attach_type: ProgramAttachType,
 }
 
+/// Information about a BPF link attached to a netfilter hook.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NetfilterLinkInfo {
+    /// The protocol family of the hook, e.g.,
+    /// [`NFPROTO_IPV4`][crate::netfilter::NFPROTO_IPV4].
+    pub protocol_family: i32,
+    /// The hook the link is attached to, e.g.,
+    /// [`NF_INET_LOCAL_IN`][crate::netfilter::NF_INET_LOCAL_IN].
+    pub hooknum: i32,
+    /// The priority of the link on the hook.
+    pub priority: i32,
+    /// The `BPF_F_NETFILTER_*` flags the link was created with.
+    pub flags: u32,
+}
+
 #[derive(Debug, Clone)]
 // TODO: Document variants.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
 pub enum LinkTypeInfo {
     RawTracepoint(RawTracepointLinkInfo),
     Tracing(TracingLinkInfo),
     Cgroup(CgroupLinkInfo),
     Iter,
     NetNs(NetNsLinkInfo),
+    Netfilter(NetfilterLinkInfo),
     Unknown,
 }
 
@@ -632,6 +1146,7 @@ pub enum LinkTypeInfo {
 #[derive(Debug, Clone)]
 // TODO: Document members.
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LinkInfo {
     pub info: LinkTypeInfo,
     pub id: u32,
@@ -684,6 +1199,15 @@ impl LinkInfo {
                     s.__bindgen_anon_1.netns.attach_type
                 }),
             }),
+            libbpf_sys::BPF_LINK_TYPE_NETFILTER => {
+                let netfilter = unsafe { s.__bindgen_anon_1.netfilter };
+                LinkTypeInfo::Netfilter(NetfilterLinkInfo {
+                    protocol_family: netfilter.pf as i32,
+                    hooknum: netfilter.hooknum as i32,
+                    priority: netfilter.priority,
+                    flags: netfilter.flags,
+                })
+            }
             _ => LinkTypeInfo::Unknown,
         };
 
@@ -703,3 +1227,168 @@ gen_info_impl!(
     libbpf_sys::bpf_link_get_next_id,
     libbpf_sys::bpf_link_get_fd_by_id
 );
+
+/// An attach point supporting multiple programs, as queried by
+/// [`Program::query_chain`][crate::Program::query_chain].
+#[derive(Clone, Copy, Debug)]
+pub enum ChainTarget<'fd> {
+    /// An attach point referenced by a file descriptor, e.g., a cgroup.
+    Fd(BorrowedFd<'fd>),
+    /// A network interface, referenced by its index, as used by tcx and
+    /// netkit attach points.
+    Ifindex(i32),
+}
+
+/// A program attached to a multi-attach point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChainEntry {
+    /// The ID of the program.
+    pub prog_id: u32,
+    /// The ID of the link the program is attached by, if any.
+    ///
+    /// Only reported for tcx and netkit attach points.
+    pub link_id: Option<u32>,
+    /// The `BPF_F_*` flags the program (or its link) was attached with.
+    pub attach_flags: u32,
+}
+
+/// The programs attached to a multi-attach point, as reported by
+/// [`Program::query_chain`][crate::Program::query_chain].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProgChain {
+    /// The revision of the attach point, which changes with every
+    /// modification of the chain.
+    ///
+    /// Only reported for tcx and netkit attach points, zero otherwise.
+    pub revision: u64,
+    /// The `BPF_F_*` flags of the attach point itself, e.g.,
+    /// [`BPF_F_ALLOW_MULTI`][libbpf_sys::BPF_F_ALLOW_MULTI] for cgroups.
+    pub attach_flags: u32,
+    /// The attached programs, in the order in which they are run.
+    pub entries: Vec<ChainEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that a [`Tag`] is formatted the way `bpftool` does it.
+    #[test]
+    fn tag_display() {
+        let tag = Tag::from([0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        assert_eq!(tag.to_string(), "0123456789abcdef");
+        assert_eq!(
+            tag.as_bytes(),
+            &[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]
+        );
+    }
+
+    /// Check that [`InfoFilter`] only matches on the criteria set.
+    #[test]
+    fn info_filter_matching() {
+        let name = CString::new("foo").unwrap();
+        let tag = Tag::from([1; 8]);
+
+        let filter = InfoFilter::<MapType>::default();
+        assert!(filter.matches(&MapType::Hash, &name, None));
+
+        let filter = InfoFilter {
+            ty: Some(MapType::Array),
+            ..Default::default()
+        };
+        assert!(filter.matches(&MapType::Array, &name, None));
+        assert!(!filter.matches(&MapType::Hash, &name, None));
+
+        let filter = InfoFilter::<MapType> {
+            name: Some(CString::new("bar").unwrap()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&MapType::Hash, &name, None));
+
+        let filter = InfoFilter::<MapType> {
+            name: Some(kernel_name("a_very_long_map_name").unwrap()),
+            ..Default::default()
+        };
+        let truncated = CString::new("a_very_long_map").unwrap();
+        assert!(filter.matches(&MapType::Hash, &truncated, None));
+
+        let filter = InfoFilter::<MapType> {
+            tag: Some(tag.clone()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&MapType::Hash, &name, Some(&tag)));
+        assert!(!filter.matches(&MapType::Hash, &name, Some(&Tag::default())));
+        assert!(!filter.matches(&MapType::Hash, &name, None));
+    }
+
+    /// Check that we only report an offload device for offloaded
+    /// entities.
+    #[test]
+    fn offload_device() {
+        assert_eq!(OffloadDevice::from_raw(0, 1, 2), None);
+        assert_eq!(
+            OffloadDevice::from_raw(3, 1, 2),
+            Some(OffloadDevice {
+                ifindex: 3,
+                netns_dev: 1,
+                netns_ino: 2,
+            })
+        );
+    }
+
+    /// Check that information types serialize into the expected JSON.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn info_serialization() {
+        let tag = Tag::from([0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        let json = serde_json::to_value(&tag).unwrap();
+        assert_eq!(json, serde_json::json!("0123456789abcdef"));
+
+        let info = MapInfo {
+            name: CString::new("counts").unwrap(),
+            ty: MapType::Hash,
+            id: 42,
+            key_size: 4,
+            value_size: 8,
+            max_entries: 1024,
+            map_flags: 0,
+            ifindex: 0,
+            btf_vmlinux_value_type_id: 0,
+            netns_dev: 0,
+            netns_ino: 0,
+            btf_id: 7,
+            btf_key_type_id: 1,
+            btf_value_type_id: 2,
+            map_extra: 0,
+        };
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["name"], "counts");
+        assert_eq!(json["ty"], "Hash");
+        assert_eq!(json["max_entries"], 1024);
+
+        let info = LinkInfo {
+            info: LinkTypeInfo::Cgroup(CgroupLinkInfo {
+                cgroup_id: 1,
+                attach_type: ProgramAttachType::CgroupInetIngress,
+            }),
+            id: 3,
+            prog_id: 4,
+        };
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "info": {
+                    "Cgroup": {
+                        "cgroup_id": 1,
+                        "attach_type": "CgroupInetIngress",
+                    },
+                },
+                "id": 3,
+                "prog_id": 4,
+            })
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+use std::mem::size_of;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd as _;
+
+use crate::Error;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::Result;
+
+/// A [`MapType::ReuseportSockarray`] map, holding sockets that an
+/// [`SkReuseport`][crate::ProgramType::SkReuseport] program can select
+/// from via `bpf_sk_select_reuseport`.
+///
+/// Sockets stored in the map have to be listening TCP or bound UDP
+/// sockets with `SO_REUSEPORT` set, all of which need to belong to the
+/// same reuseport group.
+#[derive(Debug)]
+pub struct ReuseportSockArray<'map> {
+    map: &'map MapHandle,
+}
+
+impl<'map> ReuseportSockArray<'map> {
+    /// Wrap `map`, which has to be of type
+    /// [`MapType::ReuseportSockarray`].
+    pub fn new(map: &'map MapHandle) -> Result<Self> {
+        if map.map_type() != MapType::ReuseportSockarray {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "map `{}` of type {:?} is not a reuseport socket array",
+                    map.name(),
+                    map.map_type()
+                ),
+            ));
+        }
+        Ok(Self { map })
+    }
+
+    /// Store `socket` at `index`, replacing any socket stored there
+    /// previously.
+    pub fn insert(&self, index: u32, socket: &impl AsFd) -> Result<()> {
+        let fd = socket.as_fd().as_raw_fd();
+        let value = if self.map.value_size() as usize == size_of::<u64>() {
+            u64::from(fd as u32).to_ne_bytes().to_vec()
+        } else {
+            fd.to_ne_bytes().to_vec()
+        };
+        self.map.update(&index.to_ne_bytes(), &value, MapFlags::ANY)
+    }
+
+    /// Remove the socket stored at `index`.
+    pub fn remove(&self, index: u32) -> Result<()> {
+        self.map.delete(&index.to_ne_bytes())
+    }
+
+    /// Retrieve the cookie (as reported by the `SO_COOKIE` socket option)
+    /// of the socket stored at `index`, if any.
+    ///
+    /// This requires the map's values to be 64 bit wide.
+    pub fn socket_cookie(&self, index: u32) -> Result<Option<u64>> {
+        if self.map.value_size() as usize != size_of::<u64>() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Unsupported,
+                "socket cookies can only be retrieved from maps with 64 bit values",
+            ));
+        }
+
+        let cookie = self
+            .map
+            .lookup(&index.to_ne_bytes(), MapFlags::ANY)?
+            // SANITY: The value size was checked above.
+            .map(|value| u64::from_ne_bytes(value.try_into().unwrap()));
+        Ok(cookie)
+    }
+}
@@ -1,44 +1,111 @@
 use core::ffi::c_void;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
-use std::ops::Deref as _;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::os::raw::c_int;
 use std::os::raw::c_ulong;
 use std::os::unix::io::AsFd;
+#[cfg(feature = "async")]
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
 use std::os::unix::prelude::AsRawFd;
 use std::os::unix::prelude::BorrowedFd;
+#[cfg(feature = "async")]
+use std::pin::Pin;
 use std::ptr::null_mut;
 use std::ptr::NonNull;
 use std::slice;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::sync_channel;
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::sync::Condvar;
+use std::sync::Mutex;
+#[cfg(feature = "async")]
+use std::task::Context;
+#[cfg(feature = "async")]
+use std::task::Poll;
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::thread;
+use std::thread::JoinHandle;
 use std::time::Duration;
 
+#[cfg(feature = "async")]
+use futures_core::Stream;
+
+#[cfg(feature = "async")]
+use crate::pod::read_pod;
 use crate::util;
 use crate::AsRawLibbpf;
 use crate::Error;
 use crate::MapHandle;
 use crate::MapType;
+#[cfg(feature = "async")]
+use crate::Pod;
 use crate::Result;
 
-type Cb<'a> = Box<dyn FnMut(&[u8]) -> i32 + 'a>;
+// The `ring__*` accessors are part of `libbpf`'s public API, but are
+// not (yet) covered by the `libbpf-sys` bindings.
+extern "C" {
+    fn ring__consumer_pos(r: *const libbpf_sys::ring) -> c_ulong;
+    fn ring__producer_pos(r: *const libbpf_sys::ring) -> c_ulong;
+    fn ring__avail_data_size(r: *const libbpf_sys::ring) -> libbpf_sys::size_t;
+    fn ring__size(r: *const libbpf_sys::ring) -> libbpf_sys::size_t;
+    fn ring__map_fd(r: *const libbpf_sys::ring) -> c_int;
+}
+
+type Cb<'a> = Box<dyn FnMut(&RingBufferSampleCtx, &[u8]) -> i32 + 'a>;
+
+/// Information about a sample passed to a callback registered via
+/// [`RingBufferBuilder::add_with_id`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RingBufferSampleCtx {
+    /// The ID the originating ring was registered with.
+    pub id: u64,
+    /// The index of the originating ring, i.e., the position at which it
+    /// was added to the [`RingBufferBuilder`]. It can be used with
+    /// [`RingBuffer::ring`].
+    pub ring_idx: usize,
+    /// The length of the sample, in bytes.
+    pub len: usize,
+}
 
 struct RingBufferCallback<'a> {
     cb: Cb<'a>,
+    id: u64,
+    ring_idx: usize,
 }
 
 impl<'a> RingBufferCallback<'a> {
-    fn new<F>(cb: F) -> Self
+    fn new<F>(cb: F, id: u64, ring_idx: usize) -> Self
     where
-        F: FnMut(&[u8]) -> i32 + 'a,
+        F: FnMut(&RingBufferSampleCtx, &[u8]) -> i32 + 'a,
     {
-        RingBufferCallback { cb: Box::new(cb) }
+        RingBufferCallback {
+            cb: Box::new(cb),
+            id,
+            ring_idx,
+        }
     }
 }
 
 impl Debug for RingBufferCallback<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        let Self { cb } = self;
+        let Self { cb, id, ring_idx } = self;
         f.debug_struct("RingBufferCallback")
             .field("cb", &(cb.deref() as *const _))
+            .field("id", id)
+            .field("ring_idx", ring_idx)
             .finish()
     }
 }
@@ -69,15 +136,40 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
     ///
     /// The callback provides a raw byte slice. You may find libraries such as
     /// [`plain`](https://crates.io/crates/plain) helpful.
-    pub fn add<NewF>(&mut self, map: &'slf MapHandle, callback: NewF) -> Result<&mut Self>
+    pub fn add<NewF>(&mut self, map: &'slf MapHandle, mut callback: NewF) -> Result<&mut Self>
     where
         NewF: FnMut(&[u8]) -> i32 + 'cb,
+    {
+        let id = self.fd_callbacks.len() as u64;
+        self.add_with_id(map, id, move |_ctx: &RingBufferSampleCtx, data: &[u8]| {
+            callback(data)
+        })
+    }
+
+    /// Add a new ringbuf `map` and associated `callback` to this ring buffer
+    /// manager, tagging it with the user provided `id`.
+    ///
+    /// In addition to the sample's raw bytes, the callback receives a
+    /// [`RingBufferSampleCtx`] identifying the ring the sample originated
+    /// from. That allows for a single callback to demultiplex samples from
+    /// multiple rings.
+    ///
+    /// Non-zero return values in the callback will stop ring buffer consumption early.
+    pub fn add_with_id<NewF>(
+        &mut self,
+        map: &'slf MapHandle,
+        id: u64,
+        callback: NewF,
+    ) -> Result<&mut Self>
+    where
+        NewF: FnMut(&RingBufferSampleCtx, &[u8]) -> i32 + 'cb,
     {
         if map.map_type() != MapType::RingBuf {
             return Err(Error::with_invalid_data("Must use a RingBuf map"));
         }
+        let ring_idx = self.fd_callbacks.len();
         self.fd_callbacks
-            .push((map.as_fd(), RingBufferCallback::new(callback)));
+            .push((map.as_fd(), RingBufferCallback::new(callback, id, ring_idx)));
         Ok(self)
     }
 
@@ -131,11 +223,15 @@ impl<'slf, 'cb: 'slf> RingBufferBuilder<'slf, 'cb> {
     }
 
     unsafe extern "C" fn call_sample_cb(ctx: *mut c_void, data: *mut c_void, size: c_ulong) -> i32 {
-        let callback_struct = ctx as *mut RingBufferCallback<'_>;
-        let callback = unsafe { (*callback_struct).cb.as_mut() };
+        let callback_struct = unsafe { &mut *(ctx as *mut RingBufferCallback<'_>) };
         let slice = unsafe { slice::from_raw_parts(data as *const u8, size as usize) };
+        let ctx = RingBufferSampleCtx {
+            id: callback_struct.id,
+            ring_idx: callback_struct.ring_idx,
+            len: slice.len(),
+        };
 
-        callback(slice)
+        (callback_struct.cb)(&ctx, slice)
     }
 }
 
@@ -199,6 +295,903 @@ impl RingBuffer<'_> {
     pub fn epoll_fd(&self) -> i32 {
         unsafe { libbpf_sys::ring_buffer__epoll_fd(self.ptr.as_ptr()) }
     }
+
+    /// Retrieve the ring with index `idx`, i.e., the ringbuf map that
+    /// was added `idx`-th to the [`RingBufferBuilder`].
+    ///
+    /// The returned [`Ring`] can be used for inspecting the ring's fill
+    /// level, e.g., to detect backpressure.
+    pub fn ring(&self, idx: usize) -> Option<Ring<'_>> {
+        let idx = u32::try_from(idx).ok()?;
+        let ptr = unsafe { libbpf_sys::ring_buffer__ring(self.ptr.as_ptr(), idx) };
+        NonNull::new(ptr).map(|ptr| Ring {
+            ptr,
+            _ringbuf: PhantomData,
+        })
+    }
+}
+
+/// A single ring managed by a [`RingBuffer`], as returned by
+/// [`RingBuffer::ring`].
+#[derive(Debug)]
+pub struct Ring<'rb> {
+    ptr: NonNull<libbpf_sys::ring>,
+    _ringbuf: PhantomData<&'rb RingBuffer<'rb>>,
+}
+
+impl Ring<'_> {
+    /// Retrieve the current consumer position, i.e., the logical offset
+    /// up to which data has been consumed.
+    pub fn consumer_pos(&self) -> u64 {
+        unsafe { ring__consumer_pos(self.ptr.as_ptr()) as u64 }
+    }
+
+    /// Retrieve the current producer position, i.e., the logical offset
+    /// up to which data has been reserved by producers.
+    pub fn producer_pos(&self) -> u64 {
+        unsafe { ring__producer_pos(self.ptr.as_ptr()) as u64 }
+    }
+
+    /// Retrieve the number of bytes currently available for
+    /// consumption.
+    pub fn avail_data_size(&self) -> usize {
+        unsafe { ring__avail_data_size(self.ptr.as_ptr()) as usize }
+    }
+
+    /// Retrieve the total size of the ring's data area, in bytes.
+    pub fn size(&self) -> usize {
+        unsafe { ring__size(self.ptr.as_ptr()) as usize }
+    }
+
+    /// Retrieve the file descriptor of the underlying ringbuf map.
+    pub fn map_fd(&self) -> BorrowedFd<'_> {
+        let fd = unsafe { ring__map_fd(self.ptr.as_ptr()) };
+        // SAFETY: The map file descriptor is kept open for as long as
+        //         the owning `RingBuffer` exists.
+        unsafe { BorrowedFd::borrow_raw(fd) }
+    }
+}
+
+/// A sample length flag indicating that the record is still being
+/// written by a producer.
+const RINGBUF_BUSY_BIT: u32 = 1 << 31;
+/// A sample length flag indicating that the record was discarded.
+const RINGBUF_DISCARD_BIT: u32 = 1 << 30;
+/// The size of the header preceding each record.
+const RINGBUF_HDR_SZ: usize = 8;
+
+/// Bookkeeping of a [`RingBufferReader`] about handed out samples.
+#[derive(Debug, Default)]
+struct ReaderState {
+    /// The position of the next record to read.
+    next_pos: u64,
+    /// The end positions of all records read but not yet released, in
+    /// order, along with an indication of whether they got released.
+    pending: VecDeque<(u64, bool)>,
+}
+
+/// A zero-copy reader for a single `ringbuf` map.
+///
+/// Contrary to [`RingBuffer`], which hands out samples to a callback and
+/// reclaims their memory as soon as it returns, samples read through
+/// this type are handed out as [`RingBufferSampleGuard`] objects,
+/// which keep the sample's memory reserved until they are dropped.
+/// That allows for handing samples off to processing threads without
+/// copying them first.
+///
+/// Note that the kernel reclaims memory strictly in order, meaning
+/// that a single retained sample prevents the memory of all
+/// subsequent samples from being reused. Producers may start to drop
+/// data once the ring runs full.
+///
+/// A ring buffer map must only be consumed by a single reader or
+/// [`RingBuffer`] at a time.
+#[derive(Debug)]
+pub struct RingBufferReader {
+    /// The ringbuf map's file descriptor, used for waiting for data.
+    fd: OwnedFd,
+    /// The read-write mapped consumer position page.
+    consumer: NonNull<c_void>,
+    /// The read-only mapped producer position page followed by the
+    /// (double mapped) data area.
+    producer: NonNull<c_void>,
+    /// The size of a page, which is the size of the consumer mapping
+    /// and the offset of the data area in the producer mapping.
+    page_size: usize,
+    /// The size of the data area. Always a power of two.
+    size: usize,
+    state: Mutex<ReaderState>,
+}
+
+impl RingBufferReader {
+    /// Create a new reader for ringbuf `map`.
+    pub fn new(map: &MapHandle) -> Result<Self> {
+        if map.map_type() != MapType::RingBuf {
+            return Err(Error::with_invalid_data("Must use a RingBuf map"));
+        }
+
+        let size = map.info()?.info.max_entries as usize;
+        // SAFETY: `sysconf` is always safe to call.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let fd = map.as_fd().try_clone_to_owned()?;
+
+        let mmap = |len, prot, offset| {
+            // SAFETY: We map a fresh region of memory, so no existing
+            //         memory can be affected.
+            let ptr = unsafe {
+                libc::mmap(
+                    null_mut(),
+                    len,
+                    prot,
+                    libc::MAP_SHARED,
+                    fd.as_raw_fd(),
+                    offset as libc::off_t,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                Err(Error::from(io::Error::last_os_error()))
+            } else {
+                // SANITY: `mmap` never returns NULL on success for a
+                //         NULL address hint.
+                Ok(NonNull::new(ptr).unwrap())
+            }
+        };
+
+        let consumer = mmap(page_size, libc::PROT_READ | libc::PROT_WRITE, 0)?;
+        // The kernel maps the data area twice in a row, so that records
+        // wrapping around its end can be accessed contiguously.
+        let producer = match mmap(page_size + 2 * size, libc::PROT_READ, page_size) {
+            Ok(producer) => producer,
+            Err(err) => {
+                // SAFETY: `consumer` was mapped above with this size.
+                let _ = unsafe { libc::munmap(consumer.as_ptr(), page_size) };
+                return Err(err);
+            }
+        };
+
+        let state = ReaderState {
+            next_pos: 0,
+            pending: VecDeque::new(),
+        };
+        let slf = Self {
+            fd,
+            consumer,
+            producer,
+            page_size,
+            size,
+            state: Mutex::new(state),
+        };
+        // Pick up where any previous consumer left off.
+        slf.state.lock().unwrap().next_pos = slf.consumer_pos().load(Ordering::Acquire);
+        Ok(slf)
+    }
+
+    fn consumer_pos(&self) -> &AtomicU64 {
+        // SAFETY: The consumer position is located at the start of the
+        //         consumer page, which is suitably aligned.
+        unsafe { &*(self.consumer.as_ptr() as *const AtomicU64) }
+    }
+
+    fn producer_pos(&self) -> &AtomicU64 {
+        // SAFETY: The producer position is located at the start of the
+        //         producer page, which is suitably aligned.
+        unsafe { &*(self.producer.as_ptr() as *const AtomicU64) }
+    }
+
+    /// Retrieve a pointer to the record header at logical position
+    /// `pos`.
+    fn record(&self, pos: u64) -> *const u8 {
+        let offset = pos as usize & (self.size - 1);
+        // SAFETY: The offset is within the data area, which starts
+        //         after the producer page.
+        unsafe { (self.producer.as_ptr() as *const u8).add(self.page_size + offset) }
+    }
+
+    /// Return an iterator over all samples currently available.
+    ///
+    /// Each sample remains reserved until the corresponding
+    /// [`RingBufferSampleGuard`] is dropped.
+    pub fn consume_raw_iter(&self) -> RingBufferSampleIter<'_> {
+        RingBufferSampleIter { reader: self }
+    }
+
+    /// Read the next available sample, if any.
+    fn next_sample(&self) -> Option<RingBufferSampleGuard<'_>> {
+        // We never panic with the lock held, so the lock cannot be
+        // poisoned.
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let prod_pos = self.producer_pos().load(Ordering::Acquire);
+            let pos = state.next_pos;
+            if pos >= prod_pos {
+                return None;
+            }
+
+            let hdr = self.record(pos);
+            // SAFETY: Record headers are 8 byte aligned and located
+            //         within the data area.
+            let len = unsafe { &*(hdr as *const AtomicU32) }.load(Ordering::Acquire);
+            if len & RINGBUF_BUSY_BIT != 0 {
+                return None;
+            }
+
+            let data_len = (len & !(RINGBUF_BUSY_BIT | RINGBUF_DISCARD_BIT)) as usize;
+            let end = pos + util::roundup(data_len + RINGBUF_HDR_SZ, 8) as u64;
+            state.next_pos = end;
+
+            if len & RINGBUF_DISCARD_BIT != 0 {
+                let () = state.pending.push_back((end, true));
+                let () = self.release(&mut state);
+                continue;
+            }
+
+            let () = state.pending.push_back((end, false));
+            // SAFETY: The sample data follows the header and is
+            //         contiguous thanks to the double mapping.
+            let data = unsafe { hdr.add(RINGBUF_HDR_SZ) };
+            return Some(RingBufferSampleGuard {
+                reader: self,
+                // SANITY: A pointer into a mapping is never NULL.
+                data: NonNull::new(data as *mut u8).unwrap(),
+                len: data_len,
+                end,
+            });
+        }
+    }
+
+    /// Mark the sample ending at `end` as released and advance the
+    /// consumer position past all leading released samples.
+    fn release_sample(&self, end: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Ok(idx) = state.pending.binary_search_by_key(&end, |(end, _)| *end) {
+            state.pending[idx].1 = true;
+        }
+        let () = self.release(&mut state);
+    }
+
+    fn release(&self, state: &mut ReaderState) {
+        let mut consumer_pos = None;
+        while let Some((end, true)) = state.pending.front() {
+            consumer_pos = Some(*end);
+            let _entry = state.pending.pop_front();
+        }
+
+        if let Some(pos) = consumer_pos {
+            let () = self.consumer_pos().store(pos, Ordering::Release);
+        }
+    }
+}
+
+impl AsFd for RingBufferReader {
+    /// Retrieve the ringbuf map's file descriptor, which can be polled
+    /// for data to become available.
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+// SAFETY: The mappings can be accessed from any thread and all mutable
+//         state is synchronized.
+unsafe impl Send for RingBufferReader {}
+// SAFETY: See above.
+unsafe impl Sync for RingBufferReader {}
+
+impl Drop for RingBufferReader {
+    fn drop(&mut self) {
+        // SAFETY: Both regions were mapped in `new` with these sizes.
+        let _ = unsafe { libc::munmap(self.consumer.as_ptr(), self.page_size) };
+        let _ = unsafe { libc::munmap(self.producer.as_ptr(), self.page_size + 2 * self.size) };
+    }
+}
+
+/// An iterator over the samples available in a [`RingBufferReader`], as
+/// created by [`RingBufferReader::consume_raw_iter`].
+#[derive(Debug)]
+pub struct RingBufferSampleIter<'rb> {
+    reader: &'rb RingBufferReader,
+}
+
+impl<'rb> Iterator for RingBufferSampleIter<'rb> {
+    type Item = RingBufferSampleGuard<'rb>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_sample()
+    }
+}
+
+/// A sample borrowed from a [`RingBufferReader`].
+///
+/// The sample's memory is handed back to the kernel once the guard is
+/// dropped.
+#[derive(Debug)]
+pub struct RingBufferSampleGuard<'rb> {
+    reader: &'rb RingBufferReader,
+    data: NonNull<u8>,
+    len: usize,
+    /// The end position of the sample's record.
+    end: u64,
+}
+
+impl Deref for RingBufferSampleGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The sample's memory stays valid and unmodified until
+        //         the guard is dropped.
+        unsafe { slice::from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+}
+
+// SAFETY: The guard only provides shared access to memory that is
+//         reserved for it.
+unsafe impl Send for RingBufferSampleGuard<'_> {}
+// SAFETY: See above.
+unsafe impl Sync for RingBufferSampleGuard<'_> {}
+
+impl Drop for RingBufferSampleGuard<'_> {
+    fn drop(&mut self) {
+        let () = self.reader.release_sample(self.end);
+    }
+}
+
+impl RingBuffer<'static> {
+    /// Spawn a dedicated thread continuously polling this ring buffer,
+    /// invoking the registered callbacks as data arrives.
+    ///
+    /// Polling stops once the returned [`RingBufferConsumer`] is
+    /// stopped or dropped, or when a callback returns a negative value.
+    pub fn spawn_consumer(self, opts: RingBufferConsumerOpts) -> Result<RingBufferConsumer> {
+        let RingBufferConsumerOpts {
+            cpus,
+            poll_timeout,
+            _non_exhaustive,
+        } = opts;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let (tx, rx) = sync_channel(1);
+
+        let thread = thread::Builder::new()
+            .name("ringbuf-consumer".to_string())
+            .spawn(move || -> Result<()> {
+                let result = set_cpu_affinity(&cpus);
+                let failed = result.is_err();
+                let _ = tx.send(result);
+                if failed {
+                    return Ok(());
+                }
+
+                while !stop_clone.load(Ordering::Relaxed) {
+                    let ret = self.poll_raw(poll_timeout);
+                    if ret < 0 && ret != -libc::EINTR {
+                        return Err(Error::from_raw_os_error(-ret));
+                    }
+                }
+                Ok(())
+            })?;
+
+        // Wait for the thread to report back whether it could be pinned as
+        // requested.
+        match rx.recv() {
+            Ok(Ok(())) => Ok(RingBufferConsumer {
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(Error::with_io_error(
+                    io::ErrorKind::Other,
+                    "ring buffer consumer thread terminated unexpectedly",
+                ))
+            }
+        }
+    }
+}
+
+/// State shared between a [`RingBufferStream`] and the thread waiting
+/// for data on its behalf.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+struct StreamState {
+    /// The waker of the task waiting for the next sample, if any.
+    waker: Option<Waker>,
+    /// An error encountered while waiting for data.
+    error: Option<Error>,
+    /// Whether the waiting thread should exit.
+    stop: bool,
+}
+
+#[cfg(feature = "async")]
+#[derive(Debug)]
+struct StreamShared {
+    state: Mutex<StreamState>,
+    cond: Condvar,
+    /// An event file descriptor used for interrupting the waiting
+    /// thread's `poll`.
+    stop_fd: OwnedFd,
+}
+
+#[cfg(feature = "async")]
+impl StreamShared {
+    /// Wait for data to become available on `fd` whenever a task
+    /// registered interest in it, waking the task once it is.
+    fn wait(&self, fd: OwnedFd) {
+        loop {
+            // We never panic with the lock held, so the lock cannot be
+            // poisoned.
+            let mut state = self.state.lock().unwrap();
+            while state.waker.is_none() && !state.stop {
+                state = self.cond.wait(state).unwrap();
+            }
+            if state.stop {
+                return;
+            }
+            let () = drop(state);
+
+            let mut fds = [
+                libc::pollfd {
+                    fd: fd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: self.stop_fd.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            // SAFETY: `fds` is a valid array of `pollfd` objects of the
+            //         provided length.
+            let rc = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            let mut state = self.state.lock().unwrap();
+            if rc < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                state.error = Some(Error::from(err));
+            } else if fds[1].revents != 0 {
+                return;
+            }
+
+            if let Some(waker) = state.waker.take() {
+                let () = waker.wake();
+            }
+            if state.error.is_some() {
+                return;
+            }
+        }
+    }
+}
+
+/// An asynchronous [`Stream`] of the samples submitted to a ring buffer,
+/// decoded as values of type `T`.
+///
+/// Samples are read directly from the ring buffer's memory whenever the
+/// stream is polled, meaning that no samples are buffered on the
+/// consumer side and producers observe a full ring buffer if the
+/// stream is not drained fast enough. When no sample is available, a
+/// helper thread waits for the ring buffer's file descriptor to become
+/// readable and wakes up the polling task once it is. Samples smaller
+/// than `T` are reported as errors.
+///
+/// The stream never terminates on its own; dropping it stops
+/// consumption.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct RingBufferStream<T> {
+    reader: RingBufferReader,
+    shared: Arc<StreamShared>,
+    thread: Option<JoinHandle<()>>,
+    _type: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "async")]
+impl<T> RingBufferStream<T>
+where
+    T: Pod,
+{
+    /// Create a new stream of the samples submitted to ringbuf `map`.
+    pub fn new(map: &MapHandle) -> Result<Self> {
+        let reader = RingBufferReader::new(map)?;
+        let fd = reader.as_fd().try_clone_to_owned()?;
+        // SAFETY: `eventfd` is always safe to call.
+        let stop_fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if stop_fd < 0 {
+            return Err(Error::from(io::Error::last_os_error()));
+        }
+        // SAFETY: `stop_fd` is a valid file descriptor that we own.
+        let stop_fd = unsafe { OwnedFd::from_raw_fd(stop_fd) };
+
+        let shared = Arc::new(StreamShared {
+            state: Mutex::new(StreamState::default()),
+            cond: Condvar::new(),
+            stop_fd,
+        });
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("ringbuf-stream".to_string())
+            .spawn(move || thread_shared.wait(fd))?;
+
+        Ok(Self {
+            reader,
+            shared,
+            thread: Some(thread),
+            _type: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Stream for RingBufferStream<T>
+where
+    T: Pod,
+{
+    type Item = Result<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(sample) = self.reader.consume_raw_iter().next() {
+            return Poll::Ready(Some(read_pod::<T>(&sample)));
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(err) = state.error.take() {
+            return Poll::Ready(Some(Err(err)));
+        }
+        // The waiting thread polls the ring buffer's file descriptor in
+        // level-triggered fashion, so samples arriving between the check
+        // above and this point still cause a wake up.
+        state.waker = Some(cx.waker().clone());
+        let () = self.shared.cond.notify_one();
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Drop for RingBufferStream<T> {
+    fn drop(&mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.stop = true;
+        }
+        let () = self.shared.cond.notify_one();
+
+        let value = 1u64;
+        // SAFETY: `value` is a valid eight byte buffer.
+        let _ = unsafe {
+            libc::write(
+                self.shared.stop_fd.as_raw_fd(),
+                &value as *const u64 as *const c_void,
+                mem::size_of_val(&value),
+            )
+        };
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Pin the calling thread to the provided set of CPUs. An empty set
+/// leaves the thread's affinity untouched.
+fn set_cpu_affinity(cpus: &[usize]) -> Result<()> {
+    if cpus.is_empty() {
+        return Ok(());
+    }
+
+    // SAFETY: `cpu_set_t` is valid for any bit pattern.
+    let mut set = unsafe { mem::zeroed::<libc::cpu_set_t>() };
+    for cpu in cpus {
+        if *cpu >= libc::CPU_SETSIZE as usize {
+            return Err(Error::with_invalid_data(format!(
+                "CPU {cpu} exceeds maximum supported CPU number"
+            )));
+        }
+        // SAFETY: We checked that `cpu` is within bounds.
+        let () = unsafe { libc::CPU_SET(*cpu, &mut set) };
+    }
+
+    // SAFETY: `set` is a valid `cpu_set_t` and its size is correct.
+    let rc = unsafe { libc::sched_setaffinity(0, mem::size_of_val(&set), &set) };
+    if rc != 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Options for configuring a ring buffer consumer thread, as spawned by
+/// [`RingBuffer::spawn_consumer`].
+#[derive(Clone, Debug)]
+pub struct RingBufferConsumerOpts {
+    /// The CPUs to pin the consumer thread to. If empty, the thread's
+    /// CPU affinity is left untouched.
+    pub cpus: Vec<usize>,
+    /// The timeout for each individual poll. It also bounds the time it
+    /// takes for the consumer to notice that it got stopped.
+    pub poll_timeout: Duration,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for RingBufferConsumerOpts {
+    fn default() -> Self {
+        Self {
+            cpus: Vec::new(),
+            poll_timeout: Duration::from_millis(100),
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// A handle to a thread consuming a [`RingBuffer`], as created by
+/// [`RingBuffer::spawn_consumer`].
+///
+/// The thread is stopped when the handle is dropped.
+#[derive(Debug)]
+pub struct RingBufferConsumer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl RingBufferConsumer {
+    /// Stop the consumer thread and wait for it to exit, reporting any
+    /// error it encountered while polling.
+    pub fn stop(mut self) -> Result<()> {
+        self.stop_impl()
+    }
+
+    fn stop_impl(&mut self) -> Result<()> {
+        let () = self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|_| {
+                Err(Error::with_io_error(
+                    io::ErrorKind::Other,
+                    "ring buffer consumer thread panicked",
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RingBufferConsumer {
+    fn drop(&mut self) {
+        let _ = self.stop_impl();
+    }
+}
+
+/// A handler for the samples of a ring, as registered with a
+/// [`RingBufferDispatcherBuilder`].
+///
+/// Handlers are invoked concurrently from multiple worker threads.
+pub trait SampleHandler: Send + Sync + 'static {
+    /// Process a single sample.
+    fn handle(&self, data: &[u8]);
+}
+
+impl<F> SampleHandler for F
+where
+    F: Fn(&[u8]) + Send + Sync + 'static,
+{
+    fn handle(&self, data: &[u8]) {
+        self(data)
+    }
+}
+
+/// Options for configuring a [`RingBufferDispatcher`].
+#[derive(Clone, Debug)]
+pub struct RingBufferDispatcherOpts {
+    /// The number of worker threads invoking handlers.
+    pub workers: usize,
+    /// The maximum number of samples queued up for the workers. Once
+    /// the queue is full, consumption of the ring buffer stalls until
+    /// workers catch up.
+    pub queue_depth: usize,
+    /// The timeout for each individual poll. It also bounds the time it
+    /// takes for the dispatcher to notice that it got shut down.
+    pub poll_timeout: Duration,
+    #[doc(hidden)]
+    pub _non_exhaustive: (),
+}
+
+impl Default for RingBufferDispatcherOpts {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            queue_depth: 1024,
+            poll_timeout: Duration::from_millis(100),
+            _non_exhaustive: (),
+        }
+    }
+}
+
+/// Builds [`RingBufferDispatcher`] instances.
+#[derive(Default)]
+pub struct RingBufferDispatcherBuilder<'slf> {
+    maps: Vec<&'slf MapHandle>,
+    handlers: Vec<Arc<dyn SampleHandler>>,
+}
+
+impl<'slf> RingBufferDispatcherBuilder<'slf> {
+    /// Create a new `RingBufferDispatcherBuilder` object.
+    pub fn new() -> Self {
+        Self {
+            maps: Vec::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Add a new ringbuf `map` along with the `handler` to dispatch its
+    /// samples to.
+    pub fn add<H>(&mut self, map: &'slf MapHandle, handler: H) -> Result<&mut Self>
+    where
+        H: SampleHandler,
+    {
+        if map.map_type() != MapType::RingBuf {
+            return Err(Error::with_invalid_data("Must use a RingBuf map"));
+        }
+        let () = self.maps.push(map);
+        let () = self.handlers.push(Arc::new(handler));
+        Ok(self)
+    }
+
+    /// Build a new [`RingBufferDispatcher`] and start dispatching
+    /// samples. Must have added at least one ringbuf.
+    pub fn build(self, opts: RingBufferDispatcherOpts) -> Result<RingBufferDispatcher> {
+        let RingBufferDispatcherOpts {
+            workers,
+            queue_depth,
+            poll_timeout,
+            _non_exhaustive,
+        } = opts;
+
+        if workers == 0 {
+            return Err(Error::with_invalid_data(
+                "dispatcher requires at least one worker",
+            ));
+        }
+
+        let (tx, rx) = sync_channel::<(usize, Vec<u8>)>(queue_depth);
+        let mut builder = RingBufferBuilder::new();
+        for (idx, map) in self.maps.into_iter().enumerate() {
+            let tx = tx.clone();
+            let _builder = builder.add(map, move |data: &[u8]| {
+                // Sending only fails once all workers are gone, in which
+                // case there is nothing left to do.
+                match tx.send((idx, data.to_vec())) {
+                    Ok(()) => 0,
+                    Err(_) => -1,
+                }
+            })?;
+        }
+        // Only the ring buffer callbacks should keep the channel open.
+        drop(tx);
+        let ringbuf = builder.build()?;
+
+        let rx = Arc::new(Mutex::new(rx));
+        let handlers = Arc::new(self.handlers);
+        let worker_threads = (0..workers)
+            .map(|idx| {
+                let rx = Arc::clone(&rx);
+                let handlers = Arc::clone(&handlers);
+                thread::Builder::new()
+                    .name(format!("ringbuf-worker-{idx}"))
+                    .spawn(move || loop {
+                        // The lock is released before the sample gets
+                        // handled, allowing for concurrent processing.
+                        let result = rx.lock().unwrap().recv();
+                        match result {
+                            Ok((idx, data)) => handlers[idx].handle(&data),
+                            // The poller exited and the queue is drained.
+                            Err(_) => break,
+                        }
+                    })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = Arc::clone(&stop);
+        let poller = thread::Builder::new()
+            .name("ringbuf-poller".to_string())
+            .spawn(move || -> Result<()> {
+                while !stop_clone.load(Ordering::Relaxed) {
+                    let ret = ringbuf.poll_raw(poll_timeout);
+                    if ret < 0 && ret != -libc::EINTR {
+                        return Err(Error::from_raw_os_error(-ret));
+                    }
+                }
+                // Pick up whatever got submitted since the last poll
+                // before shutting down.
+                let _ret = ringbuf.consume_raw();
+                Ok(())
+            })?;
+
+        Ok(RingBufferDispatcher {
+            stop,
+            poller: Some(poller),
+            workers: worker_threads,
+        })
+    }
+}
+
+impl Debug for RingBufferDispatcherBuilder<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let Self { maps, handlers } = self;
+        f.debug_struct("RingBufferDispatcherBuilder")
+            .field("maps", maps)
+            .field(
+                "handlers",
+                &handlers
+                    .iter()
+                    .map(|handler| Arc::as_ptr(handler) as *const ())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// A pool of threads consuming a set of ring buffers and dispatching
+/// their samples to per-ring handlers, as created by
+/// [`RingBufferDispatcherBuilder::build`].
+///
+/// A single thread polls the ring buffers, while a configurable number
+/// of workers invoke the registered [`SampleHandler`]s. Dropping the
+/// dispatcher shuts it down.
+#[derive(Debug)]
+pub struct RingBufferDispatcher {
+    stop: Arc<AtomicBool>,
+    poller: Option<JoinHandle<Result<()>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl RingBufferDispatcher {
+    /// Shut down the dispatcher gracefully.
+    ///
+    /// Samples submitted up to this point are still consumed and all
+    /// queued samples are handled before this method returns. Errors
+    /// encountered while polling are reported.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.shutdown_impl()
+    }
+
+    fn shutdown_impl(&mut self) -> Result<()> {
+        let () = self.stop.store(true, Ordering::Relaxed);
+        let result = match self.poller.take() {
+            Some(poller) => poller.join().unwrap_or_else(|_| {
+                Err(Error::with_io_error(
+                    io::ErrorKind::Other,
+                    "ring buffer poller thread panicked",
+                ))
+            }),
+            None => Ok(()),
+        };
+
+        // With the poller gone the queue is closed, causing workers to
+        // exit once they drained it.
+        let mut panicked = false;
+        for worker in self.workers.drain(..) {
+            panicked |= worker.join().is_err();
+        }
+
+        let () = result?;
+        if panicked {
+            return Err(Error::with_io_error(
+                io::ErrorKind::Other,
+                "ring buffer worker thread panicked",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RingBufferDispatcher {
+    fn drop(&mut self) {
+        let _ = self.shutdown_impl();
+    }
 }
 
 impl AsRawLibbpf for RingBuffer<'_> {
@@ -225,6 +1218,9 @@ impl Drop for RingBuffer<'_> {
 mod test {
     use super::*;
 
+    use std::fs::File;
+    use std::ptr;
+
     /// Check that `RingBuffer` is `Send`.
     #[test]
     fn ringbuffer_is_send() {
@@ -236,4 +1232,124 @@ mod test {
 
         test::<RingBuffer<'_>>();
     }
+
+    /// Check that `RingBufferStream` can be moved between tasks running on
+    /// different threads.
+    #[cfg(feature = "async")]
+    #[test]
+    fn ringbuffer_stream_is_send() {
+        fn test<T>()
+        where
+            T: Send,
+        {
+        }
+
+        test::<RingBufferStream<u64>>();
+    }
+
+    /// Check that samples are dispatched along with information about the
+    /// ring they originate from.
+    #[test]
+    fn sample_ctx() {
+        let mut received = Vec::new();
+        let mut callback = RingBufferCallback::new(
+            |ctx: &RingBufferSampleCtx, data: &[u8]| {
+                received.push((*ctx, data.to_vec()));
+                0
+            },
+            42,
+            1,
+        );
+
+        let mut data = [1u8, 2, 3];
+        let ret = unsafe {
+            RingBufferBuilder::call_sample_cb(
+                &mut callback as *mut _ as *mut c_void,
+                data.as_mut_ptr() as *mut c_void,
+                data.len() as c_ulong,
+            )
+        };
+        assert_eq!(ret, 0);
+
+        drop(callback);
+        let ctx = RingBufferSampleCtx {
+            id: 42,
+            ring_idx: 1,
+            len: 3,
+        };
+        assert_eq!(received, vec![(ctx, vec![1, 2, 3])]);
+    }
+
+    /// Check that samples read through a `RingBufferReader` are only
+    /// reclaimed once all preceding samples got released as well.
+    #[test]
+    fn reader_in_order_release() {
+        fn anon_mmap(len: usize) -> NonNull<c_void> {
+            let ptr = unsafe {
+                libc::mmap(
+                    null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            assert_ne!(ptr, libc::MAP_FAILED);
+            NonNull::new(ptr).unwrap()
+        }
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = 4096;
+        let reader = RingBufferReader {
+            fd: File::open("/dev/null").unwrap().into(),
+            consumer: anon_mmap(page_size),
+            producer: anon_mmap(page_size + 2 * size),
+            page_size,
+            size,
+            state: Mutex::new(ReaderState::default()),
+        };
+
+        // Write a couple of records: a regular one, a discarded one, a
+        // regular one, and one still busy.
+        let records = [
+            (3, 0, [1u8, 2, 3]),
+            (3, RINGBUF_DISCARD_BIT, [0; 3]),
+            (3, 0, [4, 5, 6]),
+            (3, RINGBUF_BUSY_BIT, [0; 3]),
+        ];
+        let mut pos = 0;
+        for (len, flags, data) in records {
+            let hdr = reader.record(pos) as *mut u8;
+            unsafe {
+                ptr::write(hdr as *mut u32, len | flags);
+                ptr::copy_nonoverlapping(data.as_ptr(), hdr.add(RINGBUF_HDR_SZ), data.len());
+            }
+            pos += 16;
+        }
+        let () = reader.producer_pos().store(pos, Ordering::Release);
+
+        let mut samples = reader.consume_raw_iter().collect::<Vec<_>>();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(&*samples[0], &[1, 2, 3]);
+        assert_eq!(&*samples[1], &[4, 5, 6]);
+        assert_eq!(reader.consumer_pos().load(Ordering::Acquire), 0);
+
+        // Releasing the second sample does not advance the consumer
+        // position, because the first one is still retained.
+        let second = samples.pop().unwrap();
+        drop(second);
+        assert_eq!(reader.consumer_pos().load(Ordering::Acquire), 0);
+
+        let first = samples.pop().unwrap();
+        drop(first);
+        assert_eq!(reader.consumer_pos().load(Ordering::Acquire), 48);
+    }
+
+    /// Check that we fail pinning a thread to an out-of-range CPU.
+    #[test]
+    fn cpu_affinity_out_of_range() {
+        let err = set_cpu_affinity(&[libc::CPU_SETSIZE as usize]).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidData);
+    }
 }
@@ -2,6 +2,7 @@ use core::ffi::c_void;
 use std::alloc::alloc_zeroed;
 use std::alloc::dealloc;
 use std::alloc::Layout;
+use std::borrow::Cow;
 use std::ffi::CString;
 use std::mem::size_of;
 use std::os::raw::c_char;
@@ -14,15 +15,20 @@ use libbpf_sys::bpf_map;
 use libbpf_sys::bpf_map_skeleton;
 use libbpf_sys::bpf_object;
 use libbpf_sys::bpf_object_skeleton;
+use libbpf_sys::bpf_object_subskeleton;
 use libbpf_sys::bpf_prog_skeleton;
 use libbpf_sys::bpf_program;
+use libbpf_sys::bpf_var_skeleton;
 
 use crate::error::IntoError as _;
 use crate::util;
 use crate::Error;
+use crate::Link;
+use crate::Map;
 use crate::Object;
 use crate::ObjectBuilder;
 use crate::OpenObject;
+use crate::Program;
 use crate::Result;
 
 #[derive(Debug)]
@@ -42,7 +48,7 @@ struct ProgSkelConfig {
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub struct ObjectSkeletonConfigBuilder<'a> {
-    data: &'a [u8],
+    data: Cow<'a, [u8]>,
     p: Box<*mut bpf_object>,
     name: Option<String>,
     maps: Vec<MapSkelConfig>,
@@ -57,6 +63,72 @@ fn str_to_cstring_and_pool(s: &str, pool: &mut Vec<CString>) -> Result<*const c_
     Ok(p)
 }
 
+/// Convert the names of the provided entities into C strings, failing
+/// if any of them is invalid.
+fn pool_names<'n, I>(names: I, string_pool: &mut Vec<CString>) -> Result<Vec<*const c_char>>
+where
+    I: IntoIterator<Item = &'n str>,
+{
+    names
+        .into_iter()
+        .map(|name| str_to_cstring_and_pool(name, string_pool))
+        .collect()
+}
+
+fn build_maps(
+    maps: &mut [MapSkelConfig],
+    names: &[*const c_char],
+) -> Option<(*mut bpf_map_skeleton, Layout)> {
+    if maps.is_empty() {
+        return None;
+    }
+
+    let layout = Layout::array::<bpf_map_skeleton>(maps.len())
+        .expect("Failed to allocate memory for maps skeleton");
+
+    unsafe {
+        let skel_maps = alloc_zeroed(layout) as *mut bpf_map_skeleton;
+        for (i, map) in maps.iter_mut().enumerate() {
+            let current_map = skel_maps.add(i);
+
+            (*current_map).name = names[i];
+            (*current_map).map = &mut *map.p;
+            (*current_map).mmaped = if let Some(ref mut mmaped) = map.mmaped {
+                &mut **mmaped
+            } else {
+                ptr::null_mut()
+            };
+        }
+
+        Some((skel_maps, layout))
+    }
+}
+
+fn build_progs(
+    progs: &mut [ProgSkelConfig],
+    names: &[*const c_char],
+) -> Option<(*mut bpf_prog_skeleton, Layout)> {
+    if progs.is_empty() {
+        return None;
+    }
+
+    let layout = Layout::array::<bpf_prog_skeleton>(progs.len())
+        .expect("Failed to allocate memory for progs skeleton");
+
+    unsafe {
+        let skel_progs = alloc_zeroed(layout) as *mut bpf_prog_skeleton;
+        for (i, prog) in progs.iter_mut().enumerate() {
+            let current_prog = skel_progs.add(i);
+
+            (*current_prog).name = names[i];
+            (*current_prog).prog = &mut *prog.p;
+            (*current_prog).link = &mut *prog.link;
+        }
+
+        Some((skel_progs, layout))
+    }
+}
+
 impl<'a> ObjectSkeletonConfigBuilder<'a> {
     /// Construct a new instance
     ///
@@ -65,8 +137,21 @@ impl<'a> ObjectSkeletonConfigBuilder<'a> {
     /// `p` is a reference to the pointer where `libbpf_sys::bpf_object` should be
     /// stored/retrieved
     pub fn new(object_data: &'a [u8]) -> Self {
+        Self::with_data(Cow::Borrowed(object_data))
+    }
+
+    /// Construct a new instance taking ownership of `object_data`, the
+    /// contents of the `.o` from clang
+    ///
+    /// This is useful for object files that are read at runtime, as
+    /// opposed to being embedded into the program.
+    pub fn from_owned(object_data: Vec<u8>) -> Self {
+        Self::with_data(Cow::Owned(object_data))
+    }
+
+    fn with_data(data: Cow<'a, [u8]>) -> Self {
         Self {
-            data: object_data,
+            data,
             p: Box::new(ptr::null_mut()),
             name: None,
             maps: Vec::new(),
@@ -110,73 +195,6 @@ impl<'a> ObjectSkeletonConfigBuilder<'a> {
         self
     }
 
-    fn build_maps(
-        maps: &mut [MapSkelConfig],
-        s: &mut bpf_object_skeleton,
-        string_pool: &mut Vec<CString>,
-    ) -> Option<Layout> {
-        if maps.is_empty() {
-            return None;
-        }
-
-        s.map_cnt = maps.len() as i32;
-        s.map_skel_sz = size_of::<bpf_map_skeleton>() as i32;
-
-        let layout = Layout::array::<bpf_map_skeleton>(maps.len())
-            .expect("Failed to allocate memory for maps skeleton");
-
-        unsafe {
-            s.maps = alloc_zeroed(layout) as *mut bpf_map_skeleton;
-            for (i, map) in maps.iter_mut().enumerate() {
-                let current_map = s.maps.add(i);
-
-                // Opt to panic on error here. We've already allocated memory and we'd rather not
-                // leak. Extremely unlikely to have invalid unicode anyways.
-                (*current_map).name = str_to_cstring_and_pool(&map.name, string_pool)
-                    .expect("Invalid unicode in map name");
-                (*current_map).map = &mut *map.p;
-                (*current_map).mmaped = if let Some(ref mut mmaped) = map.mmaped {
-                    &mut **mmaped
-                } else {
-                    ptr::null_mut()
-                };
-            }
-        }
-
-        Some(layout)
-    }
-
-    fn build_progs(
-        progs: &mut [ProgSkelConfig],
-        s: &mut bpf_object_skeleton,
-        string_pool: &mut Vec<CString>,
-    ) -> Option<Layout> {
-        if progs.is_empty() {
-            return None;
-        }
-
-        s.prog_cnt = progs.len() as i32;
-        s.prog_skel_sz = size_of::<bpf_prog_skeleton>() as i32;
-
-        let layout = Layout::array::<bpf_prog_skeleton>(progs.len())
-            .expect("Failed to allocate memory for progs skeleton");
-
-        unsafe {
-            s.progs = alloc_zeroed(layout) as *mut bpf_prog_skeleton;
-            for (i, prog) in progs.iter_mut().enumerate() {
-                let current_prog = s.progs.add(i);
-
-                // See above for `expect()` rationale
-                (*current_prog).name = str_to_cstring_and_pool(&prog.name, string_pool)
-                    .expect("Invalid unicode in prog name");
-                (*current_prog).prog = &mut *prog.p;
-                (*current_prog).link = &mut *prog.link;
-            }
-        }
-
-        Some(layout)
-    }
-
     #[allow(missing_docs)]
     pub fn build(mut self) -> Result<ObjectSkeletonConfig<'a>> {
         // Holds `CString`s alive so pointers to them stay valid
@@ -195,11 +213,27 @@ impl<'a> ObjectSkeletonConfigBuilder<'a> {
         s.data = self.data.as_ptr() as *mut c_void;
         s.data_sz = self.data.len() as c_ulong;
 
-        // Give s ownership over the box
+        // Convert all names up front, so that we do not have to bail out
+        // with memory already allocated.
+        let map_names = pool_names(self.maps.iter().map(|m| m.name.as_str()), &mut string_pool)?;
+        let prog_names = pool_names(self.progs.iter().map(|p| p.name.as_str()), &mut string_pool)?;
+
+        // Give s ownership over the box. Only do so once nothing can fail
+        // anymore, as it would be leaked otherwise.
         s.obj = Box::into_raw(self.p);
 
-        let maps_layout = Self::build_maps(&mut self.maps, &mut s, &mut string_pool);
-        let progs_layout = Self::build_progs(&mut self.progs, &mut s, &mut string_pool);
+        let maps_layout = build_maps(&mut self.maps, &map_names).map(|(maps, layout)| {
+            s.maps = maps;
+            s.map_cnt = self.maps.len() as i32;
+            s.map_skel_sz = size_of::<bpf_map_skeleton>() as i32;
+            layout
+        });
+        let progs_layout = build_progs(&mut self.progs, &prog_names).map(|(progs, layout)| {
+            s.progs = progs;
+            s.prog_cnt = self.progs.len() as i32;
+            s.prog_skel_sz = size_of::<bpf_prog_skeleton>() as i32;
+            layout
+        });
 
         Ok(ObjectSkeletonConfig {
             inner: s,
@@ -229,8 +263,9 @@ pub struct ObjectSkeletonConfig<'a> {
     maps_layout: Option<Layout>,
     /// Same as above
     progs_layout: Option<Layout>,
-    /// Hold this reference so that compiler guarantees buffer lives as long as us
-    _data: &'a [u8],
+    /// Hold this reference (or the data itself) so that compiler guarantees
+    /// buffer lives as long as us
+    _data: Cow<'a, [u8]>,
     /// Hold strings alive so pointers to them stay valid
     _string_pool: Vec<CString>,
 }
@@ -337,6 +372,288 @@ impl Drop for ObjectSkeletonConfig<'_> {
     }
 }
 
+#[derive(Debug)]
+struct VarSkelConfig {
+    name: String,
+    map_index: usize,
+    addr: Box<*mut c_void>,
+}
+
+/// Builder for an [`ObjectSubskeletonConfig`].
+///
+/// A subskeleton describes a subset of the maps, programs, and global
+/// variables of a BPF object, typically the parts contributed by a BPF
+/// library that got statically linked into the final object.
+#[derive(Debug)]
+pub struct ObjectSubskeletonConfigBuilder {
+    obj: NonNull<bpf_object>,
+    maps: Vec<MapSkelConfig>,
+    progs: Vec<ProgSkelConfig>,
+    vars: Vec<VarSkelConfig>,
+}
+
+impl ObjectSubskeletonConfigBuilder {
+    /// Construct a new instance
+    ///
+    /// `obj` is the (already opened) object that the subskeleton describes a
+    /// part of.
+    pub fn new(obj: NonNull<bpf_object>) -> Self {
+        Self {
+            obj,
+            maps: Vec::new(),
+            progs: Vec::new(),
+            vars: Vec::new(),
+        }
+    }
+
+    /// Adds a map to the config
+    ///
+    /// Set `mmaped` to `true` if the map is mmap'able to userspace
+    pub fn map<T: AsRef<str>>(&mut self, name: T, mmaped: bool) -> &mut Self {
+        let m = if mmaped {
+            Some(Box::new(ptr::null_mut()))
+        } else {
+            None
+        };
+
+        self.maps.push(MapSkelConfig {
+            name: name.as_ref().to_string(),
+            p: Box::new(ptr::null_mut()),
+            mmaped: m,
+        });
+
+        self
+    }
+
+    /// Adds a prog to the config
+    pub fn prog<T: AsRef<str>>(&mut self, name: T) -> &mut Self {
+        self.progs.push(ProgSkelConfig {
+            name: name.as_ref().to_string(),
+            p: Box::new(ptr::null_mut()),
+            link: Box::new(ptr::null_mut()),
+        });
+
+        self
+    }
+
+    /// Adds a global variable to the config
+    ///
+    /// `map_index` is the index of the (data section) map containing the
+    /// variable, as determined by the order in which maps were passed to
+    /// [`ObjectSubskeletonConfigBuilder::map`].
+    pub fn var<T: AsRef<str>>(&mut self, name: T, map_index: usize) -> &mut Self {
+        self.vars.push(VarSkelConfig {
+            name: name.as_ref().to_string(),
+            map_index,
+            addr: Box::new(ptr::null_mut()),
+        });
+
+        self
+    }
+
+    fn build_vars(
+        vars: &mut [VarSkelConfig],
+        maps: &mut [MapSkelConfig],
+        names: &[*const c_char],
+    ) -> Option<(*mut bpf_var_skeleton, Layout)> {
+        if vars.is_empty() {
+            return None;
+        }
+
+        let layout = Layout::array::<bpf_var_skeleton>(vars.len())
+            .expect("Failed to allocate memory for vars skeleton");
+
+        unsafe {
+            let skel_vars = alloc_zeroed(layout) as *mut bpf_var_skeleton;
+            for (i, var) in vars.iter_mut().enumerate() {
+                let current_var = skel_vars.add(i);
+
+                (*current_var).name = names[i];
+                (*current_var).map = &mut *maps[var.map_index].p;
+                (*current_var).addr = &mut *var.addr;
+            }
+
+            Some((skel_vars, layout))
+        }
+    }
+
+    #[allow(missing_docs)]
+    pub fn build(mut self) -> Result<ObjectSubskeletonConfig> {
+        if let Some(var) = self
+            .vars
+            .iter()
+            .find(|var| var.map_index >= self.maps.len())
+        {
+            return Err(Error::with_invalid_data(format!(
+                "Invalid map index for var `{}`: {}",
+                var.name, var.map_index
+            )));
+        }
+
+        // Holds `CString`s alive so pointers to them stay valid
+        let mut string_pool = Vec::new();
+
+        let mut s = libbpf_sys::bpf_object_subskeleton {
+            sz: size_of::<bpf_object_subskeleton>() as _,
+            obj: self.obj.as_ptr(),
+            ..Default::default()
+        };
+
+        // Convert all names up front, so that we do not have to bail out
+        // with memory already allocated.
+        let map_names = pool_names(self.maps.iter().map(|m| m.name.as_str()), &mut string_pool)?;
+        let prog_names = pool_names(self.progs.iter().map(|p| p.name.as_str()), &mut string_pool)?;
+        let var_names = pool_names(self.vars.iter().map(|v| v.name.as_str()), &mut string_pool)?;
+
+        let maps_layout = build_maps(&mut self.maps, &map_names).map(|(maps, layout)| {
+            s.maps = maps;
+            s.map_cnt = self.maps.len() as i32;
+            s.map_skel_sz = size_of::<bpf_map_skeleton>() as i32;
+            layout
+        });
+        let progs_layout = build_progs(&mut self.progs, &prog_names).map(|(progs, layout)| {
+            s.progs = progs;
+            s.prog_cnt = self.progs.len() as i32;
+            s.prog_skel_sz = size_of::<bpf_prog_skeleton>() as i32;
+            layout
+        });
+        let vars_layout =
+            Self::build_vars(&mut self.vars, &mut self.maps, &var_names).map(|(vars, layout)| {
+                s.vars = vars;
+                s.var_cnt = self.vars.len() as i32;
+                s.var_skel_sz = size_of::<bpf_var_skeleton>() as i32;
+                layout
+            });
+
+        Ok(ObjectSubskeletonConfig {
+            inner: s,
+            maps: self.maps,
+            _progs: self.progs,
+            vars: self.vars,
+            maps_layout,
+            progs_layout,
+            vars_layout,
+            _string_pool: string_pool,
+        })
+    }
+}
+
+/// Helper struct that wraps a `libbpf_sys::bpf_object_subskeleton`.
+///
+/// Contrary to [`ObjectSkeletonConfig`], a subskeleton does not own the
+/// object it refers to. It merely resolves pointers to parts of it, once
+/// [`libbpf_sys::bpf_object__open_subskeleton`] was called on it.
+///
+/// Upon drop, all allocated resources will be freed
+#[derive(Debug)]
+pub struct ObjectSubskeletonConfig {
+    inner: bpf_object_subskeleton,
+    maps: Vec<MapSkelConfig>,
+    /// Keep the program slots alive, as `inner` points into them
+    _progs: Vec<ProgSkelConfig>,
+    vars: Vec<VarSkelConfig>,
+    /// Layout necessary to `dealloc` memory
+    maps_layout: Option<Layout>,
+    /// Same as above
+    progs_layout: Option<Layout>,
+    /// Same as above
+    vars_layout: Option<Layout>,
+    /// Hold strings alive so pointers to them stay valid
+    _string_pool: Vec<CString>,
+}
+
+impl ObjectSubskeletonConfig {
+    #[allow(missing_docs)]
+    pub fn get(&mut self) -> &mut bpf_object_subskeleton {
+        &mut self.inner
+    }
+
+    /// Returns the `mmaped` pointer for a map at the specified `index`.
+    ///
+    /// The index is determined by the order in which the map was passed to
+    /// `ObjectSubskeletonConfigBuilder::map`. Index starts at 0.
+    ///
+    /// Warning: the returned pointer is only valid while the
+    /// `ObjectSubskeletonConfig` and the underlying object are alive.
+    pub fn map_mmap_ptr(&self, index: usize) -> Result<*const c_void> {
+        if index >= self.maps.len() {
+            return Err(Error::with_invalid_data(format!(
+                "Invalid map index: {index}"
+            )));
+        }
+
+        let p = self.maps[index]
+            .mmaped
+            .as_ref()
+            .ok_or_invalid_data(|| "Map does not have mmaped ptr")?;
+        Ok(**p)
+    }
+
+    /// Returns the address of a global variable at the specified `index`.
+    ///
+    /// The index is determined by the order in which the var was passed to
+    /// `ObjectSubskeletonConfigBuilder::var`. Index starts at 0.
+    ///
+    /// Warning: the returned pointer is only valid while the
+    /// `ObjectSubskeletonConfig` and the underlying object are alive.
+    pub fn var_ptr(&self, index: usize) -> Result<*const c_void> {
+        if index >= self.vars.len() {
+            return Err(Error::with_invalid_data(format!(
+                "Invalid var index: {index}"
+            )));
+        }
+
+        let var = &self.vars[index];
+        if var.addr.is_null() {
+            return Err(Error::with_invalid_data(format!(
+                "Var `{}` has not been resolved",
+                var.name
+            )));
+        }
+        Ok(*var.addr)
+    }
+
+    /// Returns the address of a global variable at the specified `index`.
+    ///
+    /// The index is determined by the order in which the var was passed to
+    /// `ObjectSubskeletonConfigBuilder::var`. Index starts at 0.
+    ///
+    /// Warning: the returned pointer is only valid while the
+    /// `ObjectSubskeletonConfig` and the underlying object are alive.
+    pub fn var_ptr_mut(&mut self, index: usize) -> Result<*mut c_void> {
+        self.var_ptr(index).map(|p| p.cast_mut())
+    }
+}
+
+impl Drop for ObjectSubskeletonConfig {
+    // Note we do *not* run `libbpf_sys::bpf_object__destroy_subskeleton`
+    // here, because the `libbpf_sys::bpf_object_subskeleton` is not heap
+    // allocated.
+    fn drop(&mut self) {
+        assert_eq!(self.maps_layout.is_none(), self.inner.maps.is_null());
+        assert_eq!(self.progs_layout.is_none(), self.inner.progs.is_null());
+        assert_eq!(self.vars_layout.is_none(), self.inner.vars.is_null());
+
+        if let Some(layout) = self.maps_layout {
+            unsafe {
+                dealloc(self.inner.maps as _, layout);
+            }
+        }
+
+        if let Some(layout) = self.progs_layout {
+            unsafe {
+                dealloc(self.inner.progs as _, layout);
+            }
+        }
+
+        if let Some(layout) = self.vars_layout {
+            unsafe {
+                dealloc(self.inner.vars as _, layout);
+            }
+        }
+    }
+}
+
 /// A trait for skeleton builder.
 pub trait SkelBuilder<'a> {
     /// Define that when BPF object is opened, the returned type should implement the [`OpenSkel`] trait
@@ -399,15 +716,145 @@ pub trait OpenSkel {
     fn open_object_mut(&mut self) -> &mut OpenObject;
 }
 
+/// A report about the outcome of attaching the programs and `struct_ops`
+/// maps of a skeleton individually, as produced by
+/// [`Skel::attach_partial`].
+#[derive(Debug, Default)]
+pub struct AttachReport {
+    /// The names of the programs and maps that were attached
+    /// successfully.
+    pub attached: Vec<String>,
+    /// The names of the programs and maps that were skipped, because they
+    /// are either not loaded, not set to be auto-attached, of a type not
+    /// supporting auto-attachment (e.g., XDP or `struct_ops` programs), or
+    /// already attached.
+    pub skipped: Vec<String>,
+    /// The names of the programs and maps that failed to attach, along
+    /// with the corresponding error.
+    pub failed: Vec<(String, Error)>,
+}
+
+impl AttachReport {
+    /// Check whether all programs that were meant to be attached got
+    /// attached successfully.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Attach `prog` on behalf of a skeleton, recording the outcome in
+/// `report`.
+///
+/// `link` is the skeleton's link slot for the program. Programs that
+/// already have a link are skipped, as are programs that libbpf cannot
+/// attach automatically, mirroring `bpf_object__attach_skeleton`.
+#[doc(hidden)]
+pub fn attach_prog_partial(report: &mut AttachReport, prog: &mut Program, link: &mut Option<Link>) {
+    let name = prog.name().to_string_lossy().into_owned();
+    if link.is_some() || !prog.autoload() || !prog.autoattach() {
+        let () = report.skipped.push(name);
+        return;
+    }
+
+    match prog.attach() {
+        Ok(new) => {
+            *link = Some(new);
+            let () = report.attached.push(name);
+        }
+        // libbpf reports `EOPNOTSUPP` for programs whose section does not
+        // provide enough information for attaching them automatically,
+        // such as XDP, TC, or `struct_ops` programs.
+        Err(err) if err.raw_os_error() == Some(libc::EOPNOTSUPP) => {
+            let () = report.skipped.push(name);
+        }
+        Err(err) => {
+            let () = report.failed.push((name, err));
+        }
+    }
+}
+
+/// Attach the `struct_ops` map `map` on behalf of a skeleton, recording
+/// the outcome in `report`.
+///
+/// `link` is the skeleton's link slot for the map. Maps that already have
+/// a link are skipped.
+#[doc(hidden)]
+pub fn attach_struct_ops_partial(report: &mut AttachReport, map: &Map, link: &mut Option<Link>) {
+    let name = map.name().to_string();
+    if link.is_some() {
+        let () = report.skipped.push(name);
+        return;
+    }
+
+    match map.attach_struct_ops() {
+        Ok(new) => {
+            *link = Some(new);
+            let () = report.attached.push(name);
+        }
+        Err(err) => {
+            let () = report.failed.push((name, err));
+        }
+    }
+}
+
 /// A trait for loaded skeleton.
 pub trait Skel {
     /// Attach BPF object.
     fn attach(&mut self) -> Result<()> {
         unimplemented!()
     }
+
+    /// Attach the programs of the BPF object individually.
+    ///
+    /// Contrary to [`Skel::attach`], a failure to attach one program does
+    /// not prevent the attachment of the others. Programs that are not
+    /// loaded, that have auto-attachment disabled (see
+    /// [`OpenProgram::set_autoattach`][crate::OpenProgram::set_autoattach]),
+    /// or that libbpf cannot attach automatically (e.g., XDP programs) are
+    /// skipped, just like with [`Skel::attach`]. That makes it possible to
+    /// carry optional programs, e.g., for newer kernels, in a single object
+    /// file. `struct_ops` maps are attached the same way.
+    fn attach_partial(&mut self) -> AttachReport;
+
     /// Get a reference to [`Object`].
     fn object(&self) -> &Object;
 
     /// Get a mutable reference to [`Object`].
     fn object_mut(&mut self) -> &mut Object;
 }
+
+/// Include a skeleton generated into `OUT_DIR`.
+///
+/// This macro is meant to be used together with
+/// `libbpf_cargo::SkeletonBuilder::build_and_generate_out_dir`, which
+/// places the skeleton for `<NAME>.bpf.c` at `$OUT_DIR/<NAME>.skel.rs`,
+/// so that generated code does not have to live in the source tree.
+///
+/// Provided an identifier, optionally preceded by a visibility, the
+/// skeleton is wrapped in a module of the same name:
+///
+/// ```ignore
+/// libbpf_rs::include_skel!(runqslower);
+///
+/// use runqslower::*;
+/// ```
+///
+/// Provided a string literal, the skeleton's contents are included
+/// in place, leaving any module wrapping up to the caller:
+///
+/// ```ignore
+/// mod skel {
+///     libbpf_rs::include_skel!("runqslower");
+/// }
+/// ```
+#[macro_export]
+macro_rules! include_skel {
+    ($name:literal) => {
+        include!(concat!(env!("OUT_DIR"), "/", $name, ".skel.rs"));
+    };
+    ($vis:vis $name:ident) => {
+        $vis mod $name {
+            include!(concat!(env!("OUT_DIR"), "/", stringify!($name), ".skel.rs"));
+        }
+    };
+}
@@ -0,0 +1,382 @@
+//! Helpers for reading and symbolizing stack traces.
+//!
+//! Stack traces are typically captured by BPF programs via the
+//! `bpf_get_stackid` helper into a map of type
+//! [`MapType::StackTrace`]. The raw addresses can be read with
+//! [`read_stack`] and converted into human readable [`Frame`]s by means
+//! of a [`KernelSymbolizer`] or [`UserSymbolizer`], respectively:
+//! ```no_run
+//! # use libbpf_rs::MapHandle;
+//! use libbpf_rs::stacktrace;
+//! use libbpf_rs::stacktrace::KernelSymbolizer;
+//!
+//! # fn print(map: &MapHandle, stack_id: u32) -> libbpf_rs::Result<()> {
+//! let symbolizer = KernelSymbolizer::new()?;
+//! if let Some(addrs) = stacktrace::read_stack(map, stack_id)? {
+//!     for frame in symbolizer.symbolize(&addrs) {
+//!         println!("{frame}");
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::mem::size_of;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::elf::Elf;
+use crate::elf::FuncSymbol;
+use crate::elf::Segment;
+use crate::elf::PT_LOAD;
+use crate::util;
+use crate::util::MapsEntry;
+use crate::Error;
+use crate::ErrorExt as _;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::MapType;
+use crate::Result;
+
+/// Read the stack trace with ID `stack_id` from `map`, which has to be
+/// of type [`MapType::StackTrace`].
+///
+/// The result contains the addresses of the individual frames, with the
+/// innermost frame first. `None` is returned if no stack trace with the
+/// given ID exists.
+pub fn read_stack(map: &MapHandle, stack_id: u32) -> Result<Option<Vec<u64>>> {
+    if map.map_type() != MapType::StackTrace {
+        return Err(Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            format!("map `{}` is not a stack trace map", map.name()),
+        ));
+    }
+
+    let stack = map.lookup(&stack_id.to_ne_bytes(), MapFlags::ANY)?;
+    let addrs = stack.map(|stack| {
+        stack
+            .chunks_exact(size_of::<u64>())
+            // SANITY: `chunks_exact` guarantees slices of the correct
+            //         length.
+            .map(|addr| u64::from_ne_bytes(addr.try_into().unwrap()))
+            .take_while(|addr| *addr != 0)
+            .collect()
+    });
+    Ok(addrs)
+}
+
+/// A symbolized stack frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The address of the frame.
+    pub addr: u64,
+    /// The name of the function containing the address, if it could be
+    /// determined.
+    pub symbol: Option<String>,
+    /// The offset of `addr` from the start of `symbol`.
+    pub offset: u64,
+    /// The kernel module or binary containing the address, if known.
+    pub module: Option<String>,
+}
+
+impl Frame {
+    fn unknown(addr: u64) -> Self {
+        Self {
+            addr,
+            symbol: None,
+            offset: 0,
+            module: None,
+        }
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:#018x}", self.addr)?;
+        match &self.symbol {
+            Some(symbol) => write!(f, " {symbol}+{:#x}", self.offset)?,
+            None => write!(f, " <unknown>")?,
+        }
+        if let Some(module) = &self.module {
+            write!(f, " [{module}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// A kernel symbol, as listed in `/proc/kallsyms`.
+#[derive(Debug)]
+struct KernelSymbol {
+    addr: u64,
+    name: String,
+    module: Option<String>,
+}
+
+/// A type for symbolizing kernel addresses, based on `/proc/kallsyms`.
+///
+/// Note that symbol addresses are only visible to sufficiently
+/// privileged users, depending on the `kernel.kptr_restrict` sysctl.
+#[derive(Debug)]
+pub struct KernelSymbolizer {
+    /// Function symbols, sorted by address.
+    syms: Vec<KernelSymbol>,
+}
+
+impl KernelSymbolizer {
+    /// Create a `KernelSymbolizer` for the running kernel.
+    pub fn new() -> Result<Self> {
+        let path = "/proc/kallsyms";
+        let file = File::open(path).with_context(|| format!("failed to open `{path}`"))?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Create a `KernelSymbolizer` from symbols in the format of
+    /// `/proc/kallsyms`.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut syms = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let mut fields = line.split_whitespace();
+            let (addr, ty, name, module) =
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(addr), Some(ty), Some(name), module) => (addr, ty, name, module),
+                    _ => {
+                        return Err(Error::with_invalid_data(format!(
+                            "invalid kallsyms entry: `{line}`"
+                        )))
+                    }
+                };
+            // Only functions are of interest.
+            if !matches!(ty, "t" | "T" | "w" | "W") {
+                continue;
+            }
+            let addr = u64::from_str_radix(addr, 16).map_err(|_| {
+                Error::with_invalid_data(format!("invalid kallsyms address `{addr}`"))
+            })?;
+            let module = module
+                .and_then(|module| module.strip_prefix('['))
+                .and_then(|module| module.strip_suffix(']'))
+                .map(str::to_string);
+
+            let () = syms.push(KernelSymbol {
+                addr,
+                name: name.to_string(),
+                module,
+            });
+        }
+
+        let () = syms.sort_by_key(|sym| sym.addr);
+        Ok(Self { syms })
+    }
+
+    /// Symbolize a single kernel address.
+    pub fn symbolize_addr(&self, addr: u64) -> Frame {
+        let idx = self.syms.partition_point(|sym| sym.addr <= addr);
+        match idx.checked_sub(1).map(|idx| &self.syms[idx]) {
+            // Addresses are all zero if we lack the privileges to see
+            // them, in which case no meaningful symbolization is
+            // possible.
+            Some(sym) if sym.addr != 0 => Frame {
+                addr,
+                symbol: Some(sym.name.clone()),
+                offset: addr - sym.addr,
+                module: sym.module.clone(),
+            },
+            _ => Frame::unknown(addr),
+        }
+    }
+
+    /// Symbolize a list of kernel addresses, e.g., as retrieved via
+    /// [`read_stack`].
+    pub fn symbolize(&self, addrs: &[u64]) -> Vec<Frame> {
+        addrs
+            .iter()
+            .map(|addr| self.symbolize_addr(*addr))
+            .collect()
+    }
+}
+
+/// The symbolization relevant parts of an ELF file.
+#[derive(Debug)]
+struct ElfSymbols {
+    /// Loadable segments.
+    segments: Vec<Segment>,
+    /// Function symbols, sorted by address.
+    syms: Vec<FuncSymbol>,
+}
+
+impl ElfSymbols {
+    fn load(path: &Path) -> Result<Self> {
+        let data =
+            fs::read(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let elf = Elf::parse(&data)?;
+        let segments = elf
+            .segments()?
+            .into_iter()
+            .filter(|segment| segment.type_ == PT_LOAD)
+            .collect();
+        let syms = elf.func_symbols()?;
+        Ok(Self { segments, syms })
+    }
+
+    /// Find the symbol containing the given file offset, along with the
+    /// offset of the address from the symbol's start.
+    fn find(&self, file_offset: u64) -> Option<(&FuncSymbol, u64)> {
+        let segment = self.segments.iter().find(|segment| {
+            (segment.offset..segment.offset + segment.filesz).contains(&file_offset)
+        })?;
+        let vaddr = file_offset - segment.offset + segment.vaddr;
+
+        let idx = self.syms.partition_point(|sym| sym.addr <= vaddr);
+        let sym = &self.syms[idx.checked_sub(1)?];
+        if sym.size != 0 && vaddr >= sym.addr + sym.size {
+            return None;
+        }
+        Some((sym, vaddr - sym.addr))
+    }
+}
+
+/// A type for symbolizing addresses in the address space of a user
+/// space process, based on its memory mappings and the symbol tables of
+/// the mapped ELF files.
+///
+/// Symbol tables are loaded lazily and cached. Because memory mappings
+/// are captured at construction time, a new `UserSymbolizer` should be
+/// created if the process' mappings may have changed.
+#[derive(Debug)]
+pub struct UserSymbolizer {
+    proc_dir: PathBuf,
+    maps: Vec<MapsEntry>,
+    /// Symbols of already loaded files, `None` for files that could
+    /// not be loaded.
+    cache: HashMap<PathBuf, Option<ElfSymbols>>,
+}
+
+impl UserSymbolizer {
+    /// Create a `UserSymbolizer` for the process with ID `pid`, with
+    /// `0` referring to the calling process.
+    pub fn new(pid: u32) -> Result<Self> {
+        let proc_dir = match pid {
+            0 => PathBuf::from("/proc/self"),
+            pid => PathBuf::from(format!("/proc/{pid}")),
+        };
+        let path = proc_dir.join("maps");
+        let file =
+            File::open(&path).with_context(|| format!("failed to open `{}`", path.display()))?;
+        let maps = util::parse_maps(BufReader::new(file))?;
+
+        Ok(Self {
+            proc_dir,
+            maps,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// Symbolize a single address.
+    pub fn symbolize_addr(&mut self, addr: u64) -> Frame {
+        let entry = match usize::try_from(addr)
+            .ok()
+            .and_then(|addr| self.maps.iter().find(|entry| entry.range.contains(&addr)))
+        {
+            Some(entry) => entry,
+            None => return Frame::unknown(addr),
+        };
+        let path = match entry.file_path(&self.proc_dir) {
+            Some(path) => path,
+            None => return Frame::unknown(addr),
+        };
+        let file_offset = addr - entry.range.start as u64 + entry.offset as u64;
+        let module = Some(entry.path.clone());
+
+        let syms = self
+            .cache
+            .entry(path)
+            .or_insert_with_key(|path| ElfSymbols::load(path).ok());
+        match syms.as_ref().and_then(|syms| syms.find(file_offset)) {
+            Some((sym, offset)) => Frame {
+                addr,
+                symbol: Some(sym.name.clone()),
+                offset,
+                module,
+            },
+            None => Frame {
+                module,
+                ..Frame::unknown(addr)
+            },
+        }
+    }
+
+    /// Symbolize a list of addresses, e.g., as retrieved via
+    /// [`read_stack`].
+    pub fn symbolize(&mut self, addrs: &[u64]) -> Vec<Frame> {
+        addrs
+            .iter()
+            .map(|addr| self.symbolize_addr(*addr))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::hint::black_box;
+
+    /// Check that we can symbolize kernel addresses.
+    #[test]
+    fn kernel_symbolization() {
+        let kallsyms = b"\
+ffffffff81000000 T _text
+ffffffff81001000 T do_one_initcall
+ffffffff81002000 D some_data
+ffffffffc0a01000 t foo_init\t[foo]
+";
+        let symbolizer = KernelSymbolizer::from_reader(&kallsyms[..]).unwrap();
+        let frames = symbolizer.symbolize(&[0xffffffff81001010, 0xffffffffc0a01008, 0x1000]);
+        assert_eq!(frames[0].symbol.as_deref(), Some("do_one_initcall"));
+        assert_eq!(frames[0].offset, 0x10);
+        assert_eq!(frames[0].module, None);
+        assert_eq!(
+            frames[0].to_string(),
+            "0xffffffff81001010 do_one_initcall+0x10"
+        );
+        assert_eq!(frames[1].symbol.as_deref(), Some("foo_init"));
+        assert_eq!(frames[1].module.as_deref(), Some("foo"));
+        assert_eq!(frames[2], Frame::unknown(0x1000));
+
+        // Zeroed out addresses can't be used for symbolization.
+        let kallsyms = b"0000000000000000 T _text\n";
+        let symbolizer = KernelSymbolizer::from_reader(&kallsyms[..]).unwrap();
+        let frame = symbolizer.symbolize_addr(0xffffffff81001010);
+        assert_eq!(frame, Frame::unknown(0xffffffff81001010));
+    }
+
+    #[inline(never)]
+    fn symbolization_target() -> usize {
+        black_box(42)
+    }
+
+    /// Check that we can symbolize addresses in our own process.
+    #[test]
+    fn user_symbolization() {
+        let addr = black_box(symbolization_target as *const () as u64);
+        let mut symbolizer = UserSymbolizer::new(0).unwrap();
+        let frame = symbolizer.symbolize_addr(addr + 1);
+        let symbol = frame.symbol.unwrap();
+        assert!(symbol.contains("symbolization_target"), "{symbol}");
+        assert_eq!(frame.offset, 1);
+        assert!(frame.module.is_some());
+
+        let frame = symbolizer.symbolize_addr(0);
+        assert_eq!(frame, Frame::unknown(0));
+    }
+}
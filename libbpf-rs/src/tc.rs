@@ -1,9 +1,16 @@
 use std::io;
+use std::iter;
 use std::mem::size_of;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::slice;
 
+use crate::util;
 use crate::Error;
+use crate::Program;
 use crate::Result;
 
 /// See [`libbpf_sys::bpf_tc_attach_point`].
@@ -33,6 +40,246 @@ pub const TC_H_MAJ_MASK: u32 = 0xFFFF0000;
 #[allow(missing_docs)]
 pub const TC_H_MIN_MASK: u32 = 0x0000FFFF;
 
+// from kernel @ include/uapi/linux/rtnetlink.h and include/uapi/linux/pkt_cls.h
+const TCA_KIND: u16 = 1;
+const TCA_OPTIONS: u16 = 2;
+const TCA_CHAIN: u16 = 11;
+const TCA_BPF_FD: u16 = 6;
+const TCA_BPF_NAME: u16 = 7;
+const TCA_BPF_FLAGS: u16 = 8;
+const TCA_BPF_FLAGS_GEN: u16 = 9;
+const TCA_BPF_ID: u16 = 11;
+const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1 << 0;
+const TCA_CLS_FLAGS_SKIP_HW: u32 = 1 << 0;
+const TCA_CLS_FLAGS_SKIP_SW: u32 = 1 << 1;
+// from kernel @ include/uapi/linux/if_ether.h
+const ETH_P_ALL: u16 = 0x0003;
+const NLA_F_NESTED: u16 = 1 << 15;
+const NLA_TYPE_MASK: u16 = !(NLA_F_NESTED | 1 << 14);
+
+/// `struct tcmsg` from kernel @ include/uapi/linux/rtnetlink.h
+#[repr(C)]
+#[derive(Default)]
+struct TcMsg {
+    family: u8,
+    _pad1: u8,
+    _pad2: u16,
+    ifindex: i32,
+    handle: u32,
+    parent: u32,
+    info: u32,
+}
+
+/// Information about a BPF filter attached to a TC hook, as reported
+/// by [`TcHook::query_filters`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcFilterInfo {
+    /// The ID of the attached program.
+    pub prog_id: u32,
+    /// The handle of the filter.
+    pub handle: u32,
+    /// The priority of the filter.
+    pub priority: u32,
+    /// The name of the filter, as derived by the kernel from the program.
+    pub name: String,
+}
+
+fn nl_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_ne_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Iterate over the netlink attributes (`struct nlattr`) in `data`,
+/// yielding their type and payload.
+fn nl_attrs(mut data: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    iter::from_fn(move || {
+        let len = usize::from(read_u16(data, 0)?);
+        let ty = read_u16(data, 2)? & NLA_TYPE_MASK;
+        let payload = data.get(4..len)?;
+        data = data.get(nl_align(len)..).unwrap_or(&[]);
+        Some((ty, payload))
+    })
+}
+
+fn nl_str(payload: &[u8]) -> String {
+    let payload = payload.split(|b| *b == 0).next().unwrap_or(&[]);
+    String::from_utf8_lossy(payload).into_owned()
+}
+
+/// Parse a `RTM_NEWTFILTER` message payload, returning information about
+/// the filter if it is a BPF one.
+fn parse_filter(msg: &[u8]) -> Option<TcFilterInfo> {
+    let handle = read_u32(msg, 8)?;
+    let info = read_u32(msg, 16)?;
+    let attrs = msg.get(size_of::<TcMsg>()..)?;
+
+    let mut is_bpf = false;
+    let mut prog_id = None;
+    let mut name = String::new();
+    for (ty, payload) in nl_attrs(attrs) {
+        match ty {
+            TCA_KIND => is_bpf = nl_str(payload) == "bpf",
+            TCA_OPTIONS => {
+                for (ty, payload) in nl_attrs(payload) {
+                    match ty {
+                        TCA_BPF_ID => prog_id = read_u32(payload, 0),
+                        TCA_BPF_NAME => name = nl_str(payload),
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // The kernel also reports the classifier instance itself, without a
+    // program attached.
+    if !is_bpf {
+        return None;
+    }
+
+    Some(TcFilterInfo {
+        prog_id: prog_id?,
+        handle,
+        priority: (info & TC_H_MAJ_MASK) >> 16,
+        name,
+    })
+}
+
+/// Append a netlink attribute of type `ty` with the given payload to
+/// `buf`.
+fn nl_push_attr(buf: &mut Vec<u8>, ty: u16, payload: &[u8]) {
+    let len = 4 + payload.len();
+    let () = buf.extend_from_slice(&(len as u16).to_ne_bytes());
+    let () = buf.extend_from_slice(&ty.to_ne_bytes());
+    let () = buf.extend_from_slice(payload);
+    let () = buf.resize(nl_align(buf.len()), 0);
+}
+
+/// Send a traffic control request of type `ty` via rtnetlink and collect
+/// the information about BPF filters reported back.
+///
+/// Requests are expected to either be dumps or to request an
+/// acknowledgement, so that we know when all replies have been received.
+fn tc_request(ty: u16, flags: u16, tcmsg: TcMsg, attrs: &[u8]) -> Result<Vec<TcFilterInfo>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    // SAFETY: `fd` is a valid file descriptor that we own.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let hdr_len = size_of::<libc::nlmsghdr>();
+    let seq = 1;
+    let hdr = libc::nlmsghdr {
+        nlmsg_len: (hdr_len + size_of::<TcMsg>() + attrs.len()) as u32,
+        nlmsg_type: ty,
+        nlmsg_flags: libc::NLM_F_REQUEST as u16 | flags,
+        nlmsg_seq: seq,
+        nlmsg_pid: 0,
+    };
+
+    let mut req = Vec::with_capacity(hdr.nlmsg_len as usize);
+    // SAFETY: Both types are plain old data without padding bytes.
+    unsafe {
+        req.extend_from_slice(slice::from_raw_parts(
+            (&hdr as *const libc::nlmsghdr).cast::<u8>(),
+            hdr_len,
+        ));
+        req.extend_from_slice(slice::from_raw_parts(
+            (&tcmsg as *const TcMsg).cast::<u8>(),
+            size_of::<TcMsg>(),
+        ));
+    }
+    let () = req.extend_from_slice(attrs);
+
+    let ret = unsafe { libc::send(fd.as_raw_fd(), req.as_ptr().cast(), req.len(), 0) };
+    if ret < 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+
+    let mut filters = Vec::new();
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let ret = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(Error::from(err));
+        }
+
+        let mut data = &buf[..ret as usize];
+        while data.len() >= hdr_len {
+            let invalid = || Error::with_invalid_data("received malformed netlink message");
+            let len = read_u32(data, 0).ok_or_else(invalid)? as usize;
+            let ty = read_u16(data, 4).ok_or_else(invalid)?;
+            let msg_seq = read_u32(data, 8).ok_or_else(invalid)?;
+            let payload = data.get(hdr_len..len).ok_or_else(invalid)?;
+            data = data.get(nl_align(len)..).unwrap_or(&[]);
+
+            if msg_seq != seq {
+                continue;
+            }
+
+            match i32::from(ty) {
+                libc::NLMSG_DONE => return Ok(filters),
+                libc::NLMSG_ERROR => {
+                    // An error code of zero constitutes an acknowledgement.
+                    let err = read_u32(payload, 0).ok_or_else(invalid)? as i32;
+                    if err != 0 {
+                        return Err(Error::from_raw_os_error(-err));
+                    }
+                    return Ok(filters);
+                }
+                _ if ty == libc::RTM_NEWTFILTER => {
+                    if let Some(filter) = parse_filter(payload) {
+                        let () = filters.push(filter);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Retrieve the name `libbpf` would assign to a filter for the program
+/// referenced by `fd`.
+fn filter_name(fd: i32) -> Result<String> {
+    let mut info = libbpf_sys::bpf_prog_info::default();
+    let mut len = size_of::<libbpf_sys::bpf_prog_info>() as u32;
+    let ret = unsafe {
+        libbpf_sys::bpf_obj_get_info_by_fd(
+            fd,
+            (&mut info as *mut libbpf_sys::bpf_prog_info).cast(),
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+
+    // SANITY: The kernel guarantees NUL termination.
+    let name = util::c_char_slice_to_cstr(&info.name).unwrap();
+    Ok(format!("{}:[{}]", name.to_string_lossy(), info.id))
+}
+
 /// Represents a location where a TC-BPF filter can be attached.
 ///
 /// The BPF TC subsystem has different control paths from other BPF programs.
@@ -51,6 +298,11 @@ pub const TC_H_MIN_MASK: u32 = 0x0000FFFF;
 pub struct TcHook {
     hook: libbpf_sys::bpf_tc_hook,
     opts: libbpf_sys::bpf_tc_opts,
+    // Options not supported by `libbpf`. If any of them deviates from
+    // its default, filters are managed via rtnetlink directly.
+    chain: u32,
+    protocol: u16,
+    flags_gen: u32,
 }
 
 impl TcHook {
@@ -60,6 +312,9 @@ impl TcHook {
         let mut tc_hook = TcHook {
             hook: libbpf_sys::bpf_tc_hook::default(),
             opts: libbpf_sys::bpf_tc_opts::default(),
+            chain: 0,
+            protocol: ETH_P_ALL,
+            flags_gen: 0,
         };
 
         tc_hook.hook.sz = size_of::<libbpf_sys::bpf_tc_hook>() as libbpf_sys::size_t;
@@ -98,6 +353,12 @@ impl TcHook {
         self
     }
 
+    /// Set the interface to attach to by name, e.g., `eth0`.
+    pub fn ifname(&mut self, name: &str) -> Result<&mut Self> {
+        self.hook.ifindex = util::ifindex_from_name(name)?;
+        Ok(self)
+    }
+
     /// Set what type of TC point to attach onto
     ///
     /// `TC_EGRESS`, `TC_INGRESS`, or `TC_CUSTOM`
@@ -165,8 +426,90 @@ impl TcHook {
         self.opts.priority
     }
 
+    /// Set the index of the filter chain to attach to
+    ///
+    /// Filters are attached to chain 0 by default
+    pub fn chain_index(&mut self, chain: u32) -> &mut Self {
+        self.chain = chain;
+        self
+    }
+
+    /// Set the protocol, in host byte order, that the filter applies to
+    ///
+    /// Filters apply to all protocols (`ETH_P_ALL`) by default. Use, for
+    /// example, `libc::ETH_P_IPV6` to only process IPv6 packets
+    pub fn protocol(&mut self, protocol: u16) -> &mut Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set whether the filter should not be run in software, i.e., only be
+    /// offloaded to hardware
+    pub fn skip_sw(&mut self, skip_sw: bool) -> &mut Self {
+        if skip_sw {
+            self.flags_gen |= TCA_CLS_FLAGS_SKIP_SW;
+        } else {
+            self.flags_gen &= !TCA_CLS_FLAGS_SKIP_SW;
+        }
+        self
+    }
+
+    /// Set whether the filter should not be offloaded to hardware
+    pub fn skip_hw(&mut self, skip_hw: bool) -> &mut Self {
+        if skip_hw {
+            self.flags_gen |= TCA_CLS_FLAGS_SKIP_HW;
+        } else {
+            self.flags_gen &= !TCA_CLS_FLAGS_SKIP_HW;
+        }
+        self
+    }
+
+    /// Check whether the hook uses options that `libbpf` does not support.
+    fn uses_extended_opts(&self) -> bool {
+        self.chain != 0 || self.protocol != ETH_P_ALL || self.flags_gen != 0
+    }
+
+    /// Retrieve the qdisc handle filters of this hook are attached to.
+    fn filter_parent(&self) -> Result<u32> {
+        match self.hook.attach_point {
+            TC_INGRESS => Ok(TC_H_CLSACT | TC_H_MIN_INGRESS),
+            TC_EGRESS => Ok(TC_H_CLSACT | TC_H_MIN_EGRESS),
+            TC_CUSTOM => Ok(self.hook.parent),
+            _ => Err(Error::with_invalid_data(
+                "filters can only be managed for a single attach point",
+            )),
+        }
+    }
+
+    /// Create the rtnetlink request for the filter of this hook, along
+    /// with the attributes identifying it.
+    fn filter_request(&self) -> Result<(TcMsg, Vec<u8>)> {
+        let msg = TcMsg {
+            family: libc::AF_UNSPEC as u8,
+            ifindex: self.hook.ifindex,
+            handle: self.opts.handle,
+            parent: self.filter_parent()?,
+            info: (self.opts.priority << 16) | u32::from(self.protocol.to_be()),
+            ..Default::default()
+        };
+
+        let mut attrs = Vec::new();
+        let () = nl_push_attr(&mut attrs, TCA_KIND, b"bpf\0");
+        let () = nl_push_attr(&mut attrs, TCA_CHAIN, &self.chain.to_ne_bytes());
+        Ok((msg, attrs))
+    }
+
     /// Query a hook to inspect the program identifier (prog_id)
     pub fn query(&mut self) -> Result<u32> {
+        if self.uses_extended_opts() {
+            let (msg, attrs) = self.filter_request()?;
+            let filters = tc_request(libc::RTM_GETTFILTER, libc::NLM_F_ACK as u16, msg, &attrs)?;
+            return filters
+                .first()
+                .map(|filter| filter.prog_id)
+                .ok_or_else(|| Error::from_raw_os_error(libc::ENOENT));
+        }
+
         let mut opts = self.opts;
         opts.prog_id = 0;
         opts.prog_fd = 0;
@@ -180,6 +523,23 @@ impl TcHook {
         }
     }
 
+    /// Query all BPF filters attached to the hook, irrespective of the
+    /// handle and priority set on it.
+    ///
+    /// This method can be used for inspecting existing filters, e.g., to
+    /// take over one of them by attaching with the same handle and
+    /// priority and `replace` set. Custom hooks report the filters
+    /// attached to their parent.
+    pub fn query_filters(&self) -> Result<Vec<TcFilterInfo>> {
+        let msg = TcMsg {
+            family: libc::AF_UNSPEC as u8,
+            ifindex: self.hook.ifindex,
+            parent: self.filter_parent()?,
+            ..Default::default()
+        };
+        tc_request(libc::RTM_GETTFILTER, libc::NLM_F_DUMP as u16, msg, &[])
+    }
+
     /// Attach a filter to the TcHook so that the program starts processing
     ///
     /// Once the hook is processing, changing the values will have no effect unless the hook is
@@ -192,6 +552,11 @@ impl TcHook {
     /// NOTE: Once a [`TcHook`] is attached, it, and the maps it uses, will outlive the userspace
     /// application that spawned them Make sure to detach if this is not desired
     pub fn attach(&mut self) -> Result<Self> {
+        if self.uses_extended_opts() {
+            let () = self.attach_extended()?;
+            return Ok(*self);
+        }
+
         self.opts.prog_id = 0;
         let err =
             unsafe { libbpf_sys::bpf_tc_attach(&self.hook as *const _, &mut self.opts as *mut _) };
@@ -202,8 +567,59 @@ impl TcHook {
         }
     }
 
+    /// Attach a filter using options not supported by `libbpf`.
+    fn attach_extended(&mut self) -> Result<()> {
+        let (msg, mut attrs) = self.filter_request()?;
+
+        let mut options = Vec::new();
+        let prog_fd = self.opts.prog_fd;
+        let name = filter_name(prog_fd)?;
+        let () = nl_push_attr(&mut options, TCA_BPF_FD, &(prog_fd as u32).to_ne_bytes());
+        let () = nl_push_attr(&mut options, TCA_BPF_NAME, format!("{name}\0").as_bytes());
+        let () = nl_push_attr(
+            &mut options,
+            TCA_BPF_FLAGS,
+            &TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes(),
+        );
+        if self.flags_gen != 0 {
+            let () = nl_push_attr(
+                &mut options,
+                TCA_BPF_FLAGS_GEN,
+                &self.flags_gen.to_ne_bytes(),
+            );
+        }
+        let () = nl_push_attr(&mut attrs, TCA_OPTIONS | NLA_F_NESTED, &options);
+
+        let exclusive = if self.opts.flags & BPF_TC_F_REPLACE != 0 {
+            libc::NLM_F_REPLACE
+        } else {
+            libc::NLM_F_EXCL
+        };
+        let flags = libc::NLM_F_ECHO | libc::NLM_F_ACK | libc::NLM_F_CREATE | exclusive;
+        let filters = tc_request(libc::RTM_NEWTFILTER, flags as u16, msg, &attrs)?;
+        let filter = filters.first().ok_or_else(|| {
+            Error::with_invalid_data("kernel did not report information about attached filter")
+        })?;
+
+        self.opts.handle = filter.handle;
+        self.opts.priority = filter.priority;
+        self.opts.prog_id = filter.prog_id;
+        Ok(())
+    }
+
     /// Detach a filter from a [`TcHook`]
     pub fn detach(&mut self) -> Result<()> {
+        if self.uses_extended_opts() {
+            if self.opts.handle == 0 || self.opts.priority == 0 {
+                return Err(Error::from_raw_os_error(libc::EINVAL));
+            }
+
+            let (msg, attrs) = self.filter_request()?;
+            let _filters = tc_request(libc::RTM_DELTFILTER, libc::NLM_F_ACK as u16, msg, &attrs)?;
+            self.opts.prog_id = 0;
+            return Ok(());
+        }
+
         let mut opts = self.opts;
         opts.prog_id = 0;
         opts.prog_fd = 0;
@@ -241,6 +657,23 @@ impl TcHook {
     }
 }
 
+/// The file descriptor of the program a [`TcHookBuilder`] creates hooks
+/// for.
+#[derive(Debug)]
+enum ProgFd<'fd> {
+    Borrowed(BorrowedFd<'fd>),
+    Owned(OwnedFd),
+}
+
+impl AsFd for ProgFd<'_> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            Self::Borrowed(fd) => *fd,
+            Self::Owned(fd) => fd.as_fd(),
+        }
+    }
+}
+
 /// Builds [`TcHook`] instances.
 ///
 /// [`TcHookBuilder`] is a way to ergonomically create multiple `TcHook`s,
@@ -250,19 +683,27 @@ impl TcHook {
 /// be adjusted before [`TcHook::attach()`] is called.
 #[derive(Debug)]
 pub struct TcHookBuilder<'fd> {
-    fd: BorrowedFd<'fd>,
+    fd: ProgFd<'fd>,
     ifindex: i32,
     parent_maj: u32,
     parent_min: u32,
     replace: bool,
     handle: u32,
     priority: u32,
+    chain: u32,
+    protocol: u16,
+    skip_sw: bool,
+    skip_hw: bool,
 }
 
 impl<'fd> TcHookBuilder<'fd> {
     /// Create a new `TcHookBuilder` with fd
     /// this fd should come from a loaded [`Program`][crate::Program]
     pub fn new(fd: BorrowedFd<'fd>) -> Self {
+        Self::with_prog_fd(ProgFd::Borrowed(fd))
+    }
+
+    fn with_prog_fd(fd: ProgFd<'fd>) -> Self {
         TcHookBuilder {
             fd,
             ifindex: 0,
@@ -271,6 +712,10 @@ impl<'fd> TcHookBuilder<'fd> {
             replace: false,
             handle: 0,
             priority: 0,
+            chain: 0,
+            protocol: ETH_P_ALL,
+            skip_sw: false,
+            skip_hw: false,
         }
     }
 
@@ -280,6 +725,13 @@ impl<'fd> TcHookBuilder<'fd> {
         self
     }
 
+    /// Set the initial interface to attach the hook on by name, e.g.,
+    /// `eth0`.
+    pub fn ifname(&mut self, name: &str) -> Result<&mut Self> {
+        self.ifindex = util::ifindex_from_name(name)?;
+        Ok(self)
+    }
+
     /// Set the initial parent of a hook
     pub fn parent(&mut self, maj: u32, min: u32) -> &mut Self {
         self.parent_maj = maj;
@@ -305,19 +757,176 @@ impl<'fd> TcHookBuilder<'fd> {
         self
     }
 
+    /// Set the initial filter chain index for a hook
+    pub fn chain_index(&mut self, chain: u32) -> &mut Self {
+        self.chain = chain;
+        self
+    }
+
+    /// Set the initial protocol, in host byte order, for a hook
+    pub fn protocol(&mut self, protocol: u16) -> &mut Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set whether created hooks should not run filters in software
+    pub fn skip_sw(&mut self, skip_sw: bool) -> &mut Self {
+        self.skip_sw = skip_sw;
+        self
+    }
+
+    /// Set whether created hooks should not offload filters to hardware
+    pub fn skip_hw(&mut self, skip_hw: bool) -> &mut Self {
+        self.skip_hw = skip_hw;
+        self
+    }
+
     /// Create a [`TcHook`] given the values previously set
     ///
     /// Once a hook is created, the values can still be changed on the `TcHook`
     /// by calling the `TcHooks` setter methods
     pub fn hook(&self, attach_point: TcAttachPoint) -> TcHook {
-        let mut hook = TcHook::new(self.fd);
+        let mut hook = TcHook::new(self.fd.as_fd());
         hook.ifindex(self.ifindex)
             .handle(self.handle)
             .priority(self.priority)
             .parent(self.parent_maj, self.parent_min)
             .replace(self.replace)
+            .chain_index(self.chain)
+            .protocol(self.protocol)
+            .skip_sw(self.skip_sw)
+            .skip_hw(self.skip_hw)
             .attach_point(attach_point);
 
         hook
     }
 }
+
+impl TcHookBuilder<'static> {
+    /// Create a new `TcHookBuilder` for the already loaded program with
+    /// the given ID, e.g., as reported by [`TcHook::query_filters`].
+    ///
+    /// The builder keeps a file descriptor referencing the program open,
+    /// so it has to outlive any attach operations of hooks created by it.
+    pub fn from_prog_id(id: u32) -> Result<Self> {
+        let fd = Program::get_fd_by_id(id)?;
+        Ok(Self::with_prog_fd(ProgFd::Owned(fd)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    fn nl_attr(ty: u16, payload: &[u8]) -> Vec<u8> {
+        let len = 4 + payload.len();
+        let mut attr = Vec::new();
+        attr.extend_from_slice(&(len as u16).to_ne_bytes());
+        attr.extend_from_slice(&ty.to_ne_bytes());
+        attr.extend_from_slice(payload);
+        attr.resize(nl_align(len), 0);
+        attr
+    }
+
+    fn tcmsg(handle: u32, info: u32) -> Vec<u8> {
+        let mut msg = vec![0; 8];
+        msg.extend_from_slice(&handle.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&info.to_ne_bytes());
+        msg
+    }
+
+    /// Check that we can parse filter information as reported by the
+    /// kernel.
+    #[test]
+    fn filter_parsing() {
+        let options = [
+            nl_attr(TCA_BPF_NAME, b"tc.bpf.o:[tc]\0"),
+            nl_attr(TCA_BPF_ID, &42u32.to_ne_bytes()),
+        ]
+        .concat();
+        let msg = [
+            tcmsg(1, 3 << 16 | 0x0300),
+            nl_attr(TCA_KIND, b"bpf\0"),
+            nl_attr(TCA_OPTIONS | 1 << 15, &options),
+        ]
+        .concat();
+
+        let filter = parse_filter(&msg).unwrap();
+        assert_eq!(
+            filter,
+            TcFilterInfo {
+                prog_id: 42,
+                handle: 1,
+                priority: 3,
+                name: "tc.bpf.o:[tc]".to_string(),
+            }
+        );
+
+        // Classifier instances without program are not reported.
+        let msg = [tcmsg(0, 3 << 16), nl_attr(TCA_KIND, b"bpf\0")].concat();
+        assert_eq!(parse_filter(&msg), None);
+
+        // Neither are non-BPF filters.
+        let msg = [
+            tcmsg(1, 3 << 16),
+            nl_attr(TCA_KIND, b"u32\0"),
+            nl_attr(TCA_OPTIONS, &options),
+        ]
+        .concat();
+        assert_eq!(parse_filter(&msg), None);
+    }
+
+    /// Check that we can configure hooks by interface name.
+    #[test]
+    fn hook_ifname() {
+        let file = File::open("/dev/null").unwrap();
+        let ifindex = util::ifindex_from_name("lo").unwrap();
+
+        let mut builder = TcHookBuilder::new(file.as_fd());
+        let hook = builder.ifname("lo").unwrap().hook(TC_INGRESS);
+        assert_eq!(hook.hook.ifindex, ifindex);
+
+        let mut hook = TcHook::new(file.as_fd());
+        let _hook = hook.ifname("lo").unwrap();
+        assert_eq!(hook.hook.ifindex, ifindex);
+        assert!(hook.ifname("does-not-exist").is_err());
+    }
+
+    /// Check that the rtnetlink request identifying a filter reflects the
+    /// hook's configuration.
+    #[test]
+    fn filter_request_encoding() {
+        let file = File::open("/dev/null").unwrap();
+        let mut hook = TcHook::new(file.as_fd());
+        hook.ifindex(1)
+            .attach_point(TC_EGRESS)
+            .handle(2)
+            .priority(3)
+            .protocol(libc::ETH_P_IPV6 as u16)
+            .chain_index(4);
+        assert!(hook.uses_extended_opts());
+
+        let (msg, attrs) = hook.filter_request().unwrap();
+        assert_eq!(msg.ifindex, 1);
+        assert_eq!(msg.handle, 2);
+        assert_eq!(msg.parent, TC_H_CLSACT | TC_H_MIN_EGRESS);
+        assert_eq!(msg.info, 3 << 16 | u32::from(0x86dd_u16.to_be()));
+
+        let attrs = nl_attrs(&attrs).collect::<Vec<_>>();
+        assert_eq!(
+            attrs,
+            vec![
+                (TCA_KIND, b"bpf\0".as_slice()),
+                (TCA_CHAIN, 4u32.to_ne_bytes().as_slice())
+            ]
+        );
+
+        hook.protocol(ETH_P_ALL).chain_index(0).skip_hw(true);
+        assert!(hook.uses_extended_opts());
+        hook.skip_hw(false);
+        assert!(!hook.uses_extended_opts());
+    }
+}
@@ -0,0 +1,151 @@
+use std::ffi::c_void;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::mem::MaybeUninit;
+use std::os::unix::io::AsFd as _;
+use std::os::unix::io::AsRawFd as _;
+use std::ptr;
+
+use crate::util;
+use crate::Error;
+use crate::MapFlags;
+use crate::MapHandle;
+use crate::Pod;
+use crate::Result;
+
+/// A typed view of a map, with keys of type `K` and values of type `V`.
+///
+/// Keys and values are exchanged with the kernel in their in-memory
+/// representation, which has to match the one used by the BPF program.
+/// Skeletons generated by `libbpf-cargo` provide such views for maps
+/// based on their key and value types as described by BTF.
+///
+/// Per-CPU maps as well as maps without keys (queues, stacks, and bloom
+/// filters) are not supported.
+#[derive(Debug)]
+pub struct TypedMap<'map, K, V> {
+    map: &'map MapHandle,
+    _types: PhantomData<(K, V)>,
+}
+
+impl<'map, K, V> TypedMap<'map, K, V>
+where
+    K: Pod,
+    V: Pod,
+{
+    /// Create a typed view of `map`.
+    ///
+    /// The key and value sizes of `map` have to match the sizes of `K` and
+    /// `V`, respectively.
+    pub fn new(map: &'map MapHandle) -> Result<Self> {
+        let map_type = map.map_type();
+        if map_type.is_percpu() || map_type.is_keyless() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "typed access is not supported for map `{}` of type {map_type:?}",
+                    map.name()
+                ),
+            ));
+        }
+
+        if map.key_size() as usize != size_of::<K>() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "key size {} of map `{}` does not match key type size {}",
+                    map.key_size(),
+                    map.name(),
+                    size_of::<K>()
+                ),
+            ));
+        }
+
+        if map.value_size() as usize != size_of::<V>() {
+            return Err(Error::with_io_error(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "value size {} of map `{}` does not match value type size {}",
+                    map.value_size(),
+                    map.name(),
+                    size_of::<V>()
+                ),
+            ));
+        }
+
+        Ok(Self {
+            map,
+            _types: PhantomData,
+        })
+    }
+
+    /// Retrieve the underlying map.
+    pub fn map(&self) -> &'map MapHandle {
+        self.map
+    }
+
+    /// Look up the value stored for `key`, if any.
+    pub fn lookup(&self, key: &K) -> Result<Option<V>> {
+        let mut value = MaybeUninit::<V>::uninit();
+        // SAFETY: `key` and `value` are valid for reads and writes,
+        //         respectively, of the map's key and value sizes, as
+        //         checked during construction.
+        let ret = unsafe {
+            libbpf_sys::bpf_map_lookup_elem(
+                self.map.as_fd().as_raw_fd(),
+                (key as *const K).cast::<c_void>(),
+                value.as_mut_ptr().cast::<c_void>(),
+            )
+        };
+
+        if ret == 0 {
+            // SAFETY: The kernel initialized the value and `V` is valid
+            //         for any bit pattern.
+            Ok(Some(unsafe { value.assume_init() }))
+        } else {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::NotFound {
+                Ok(None)
+            } else {
+                Err(Error::from(err))
+            }
+        }
+    }
+
+    /// Store `value` for `key`.
+    pub fn update(&self, key: &K, value: &V, flags: MapFlags) -> Result<()> {
+        // SAFETY: `key` and `value` are valid for reads of the map's key
+        //         and value sizes, respectively.
+        let ret = unsafe {
+            libbpf_sys::bpf_map_update_elem(
+                self.map.as_fd().as_raw_fd(),
+                (key as *const K).cast::<c_void>(),
+                (value as *const V).cast::<c_void>(),
+                flags.bits(),
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Delete the entry for `key`.
+    pub fn delete(&self, key: &K) -> Result<()> {
+        // SAFETY: `key` is valid for reads of the map's key size.
+        let ret = unsafe {
+            libbpf_sys::bpf_map_delete_elem(
+                self.map.as_fd().as_raw_fd(),
+                (key as *const K).cast::<c_void>(),
+            )
+        };
+        util::parse_ret(ret)
+    }
+
+    /// Iterate over the keys of the map.
+    pub fn keys(&self) -> impl Iterator<Item = K> + 'map {
+        self.map.keys().map(|key| {
+            // SAFETY: `key` is of the map's key size, which matches that
+            //         of `K`, and `K` is valid for any bit pattern.
+            unsafe { ptr::read_unaligned(key.as_ptr().cast::<K>()) }
+        })
+    }
+}
@@ -1,21 +1,27 @@
 use libc::E2BIG;
 use libc::ENOSPC;
 use std::io;
+use std::marker::PhantomData;
+use std::mem::align_of;
+use std::mem::size_of;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
 use std::os::raw::c_uint;
 use std::os::raw::c_void;
+use std::ptr;
 use std::ptr::null_mut;
 use std::ptr::NonNull;
 use std::slice::from_raw_parts;
 use std::slice::from_raw_parts_mut;
+use std::time::Duration;
 
 use crate::AsRawLibbpf;
 use crate::Error;
 use crate::MapHandle;
 use crate::MapType;
+use crate::Pod;
 use crate::Result;
 
 /// A mutable reference to sample from a [`UserRingBuffer`].
@@ -66,6 +72,59 @@ impl Drop for UserRingBufferSample<'_> {
     }
 }
 
+/// A mutable, typed reference to a sample from a [`UserRingBuffer`], as
+/// created by [`UserRingBuffer::reserve_typed`].
+///
+/// The sample dereferences to `T` and can be submitted just like an
+/// untyped [`UserRingBufferSample`].
+#[derive(Debug)]
+pub struct UserRingBufferTypedSample<'slf, T> {
+    sample: UserRingBufferSample<'slf>,
+    _type: PhantomData<T>,
+}
+
+impl<T> Deref for UserRingBufferTypedSample<'_, T>
+where
+    T: Pod,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The sample is large enough and suitably aligned for a
+        //         `T`, as checked during reservation, and it got
+        //         initialized back then.
+        unsafe { &*(self.sample.ptr.as_ptr() as *const T) }
+    }
+}
+
+impl<T> DerefMut for UserRingBufferTypedSample<'_, T>
+where
+    T: Pod,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: See `Deref` implementation.
+        unsafe { &mut *(self.sample.ptr.as_ptr() as *mut T) }
+    }
+}
+
+impl<'slf, T> From<UserRingBufferTypedSample<'slf, T>> for UserRingBufferSample<'slf> {
+    fn from(sample: UserRingBufferTypedSample<'slf, T>) -> Self {
+        sample.sample
+    }
+}
+
+/// Map the current `errno` value, as set by a failed reservation
+/// attempt, to an [`Error`].
+fn reserve_error() -> Error {
+    // Fetch the current value of errno to determine the type of error.
+    let errno = io::Error::last_os_error();
+    match errno.raw_os_error() {
+        Some(E2BIG) => Error::with_invalid_data("requested size is too large"),
+        Some(ENOSPC) => Error::with_invalid_data("not enough space in the ring buffer"),
+        _ => Error::from(errno),
+    }
+}
+
 /// Represents a user ring buffer. This is a special kind of map that is used to
 /// transfer data between user space and kernel space.
 #[derive(Debug)]
@@ -112,15 +171,7 @@ impl UserRingBuffer {
         let sample_ptr =
             unsafe { libbpf_sys::user_ring_buffer__reserve(self.ptr.as_ptr(), size as c_uint) };
 
-        let ptr = NonNull::new(sample_ptr).ok_or_else(|| {
-            // Fetch the current value of errno to determine the type of error.
-            let errno = io::Error::last_os_error();
-            match errno.raw_os_error() {
-                Some(E2BIG) => Error::with_invalid_data("requested size is too large"),
-                Some(ENOSPC) => Error::with_invalid_data("not enough space in the ring buffer"),
-                _ => Error::from(errno),
-            }
-        })?;
+        let ptr = NonNull::new(sample_ptr).ok_or_else(reserve_error)?;
 
         Ok(UserRingBufferSample {
             ptr,
@@ -130,6 +181,76 @@ impl UserRingBuffer {
         })
     }
 
+    /// Reserve a sample in the user ring buffer, waiting for at most
+    /// `timeout` for enough space to become available. If `timeout` is
+    /// `Duration::MAX`, this will block indefinitely.
+    ///
+    /// Space is freed up as the BPF program drains the ring buffer. Note
+    /// that blocked producers only get woken up if the program's call to
+    /// `bpf_user_ringbuf_drain` does not pass `BPF_RB_NO_WAKEUP`; with
+    /// `BPF_RB_FORCE_WAKEUP` they are notified unconditionally.
+    ///
+    /// This function is *not* thread-safe. It is necessary to synchronize
+    /// amongst multiple producers when invoking this function.
+    pub fn reserve_blocking(
+        &self,
+        size: usize,
+        timeout: Duration,
+    ) -> Result<UserRingBufferSample<'_>> {
+        let timeout_ms = if timeout == Duration::MAX {
+            -1
+        } else {
+            timeout.as_millis().min(i32::MAX as u128) as i32
+        };
+
+        let sample_ptr = unsafe {
+            libbpf_sys::user_ring_buffer__reserve_blocking(
+                self.ptr.as_ptr(),
+                size as c_uint,
+                timeout_ms,
+            )
+        };
+        let ptr = NonNull::new(sample_ptr).ok_or_else(reserve_error)?;
+
+        Ok(UserRingBufferSample {
+            ptr,
+            size,
+            submitted: false,
+            rb: self,
+        })
+    }
+
+    /// Reserve a sample for an object of type `T` in the user ring
+    /// buffer.
+    ///
+    /// The sample is zero-initialized and dereferences to `T`. Just like
+    /// an untyped sample, it must be submitted via
+    /// [`UserRingBuffer::submit`] before it is dropped.
+    ///
+    /// This function is *not* thread-safe. It is necessary to synchronize
+    /// amongst multiple producers when invoking this function.
+    pub fn reserve_typed<T>(&self) -> Result<UserRingBufferTypedSample<'_, T>>
+    where
+        T: Pod,
+    {
+        // Samples are guaranteed to be 8 byte aligned.
+        if align_of::<T>() > 8 {
+            return Err(Error::with_invalid_data(
+                "type alignment exceeds sample alignment",
+            ));
+        }
+
+        let sample = self.reserve(size_of::<T>())?;
+        // SAFETY: The sample is valid for writes of `size_of::<T>()`
+        //         bytes.
+        let () = unsafe { ptr::write_bytes(sample.ptr.as_ptr() as *mut u8, 0, size_of::<T>()) };
+
+        Ok(UserRingBufferTypedSample {
+            sample,
+            _type: PhantomData,
+        })
+    }
+
     /// Submit a sample to the user ring buffer.
     ///
     /// This function takes ownership of the sample and submits it to the ring
@@ -138,7 +259,11 @@ impl UserRingBuffer {
     ///
     /// This function is thread-safe. It is *not* necessary to synchronize
     /// amongst multiple producers when invoking this function.
-    pub fn submit(&self, mut sample: UserRingBufferSample<'_>) -> Result<()> {
+    pub fn submit<'slf, S>(&'slf self, sample: S) -> Result<()>
+    where
+        S: Into<UserRingBufferSample<'slf>>,
+    {
+        let mut sample = sample.into();
         unsafe {
             libbpf_sys::user_ring_buffer__submit(self.ptr.as_ptr(), sample.ptr.as_ptr());
         }
@@ -1,15 +1,28 @@
 use std::any::type_name;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::fs;
+use std::fs::File;
 use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::mem;
 use std::mem::transmute;
 use std::ops::Deref;
+use std::ops::Range;
+use std::os::fd::AsRawFd as _;
+use std::os::fd::BorrowedFd;
 use std::os::raw::c_char;
 use std::path::Path;
+use std::path::PathBuf;
 use std::ptr::NonNull;
 use std::sync::OnceLock;
 
+use bitflags::bitflags;
+
 use crate::Error;
+use crate::ErrorExt as _;
+use crate::ProgramType;
 use crate::Result;
 
 pub fn str_to_cstring(s: &str) -> Result<CString> {
@@ -63,6 +76,285 @@ pub fn num_possible_cpus() -> Result<usize> {
     parse_ret(ret).map(|()| ret as usize)
 }
 
+/// Parse a CPU list in the format used by sysfs, e.g., `0-3,5,7-8`,
+/// into the individual CPU IDs.
+fn parse_cpu_list(list: &str) -> Result<Vec<usize>> {
+    let parse = |cpu: &str| {
+        cpu.parse::<usize>()
+            .map_err(|err| Error::with_invalid_data(format!("invalid CPU ID `{cpu}`: {err}")))
+    };
+
+    let mut cpus = Vec::new();
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(Error::with_invalid_data(format!(
+                        "invalid CPU range `{range}`"
+                    )));
+                }
+                let () = cpus.extend(start..=end);
+            }
+            None => {
+                let () = cpus.push(parse(range)?);
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+/// Read a CPU list from the sysfs file at `path`.
+fn read_cpu_list(path: &str) -> Result<Vec<usize>> {
+    let list = fs::read_to_string(path).with_context(|| format!("failed to read `{path}`"))?;
+    parse_cpu_list(&list)
+}
+
+/// Get the IDs of all possible CPUs in the system.
+///
+/// Per-cpu maps contain one value for each of these CPUs. Note that
+/// IDs are not necessarily contiguous.
+pub fn possible_cpu_ids() -> Result<Vec<usize>> {
+    read_cpu_list("/sys/devices/system/cpu/possible")
+}
+
+/// Get the IDs of all CPUs that are currently online.
+///
+/// This is the set of CPUs on which BPF programs can actually run, e.g.,
+/// when deciding which per-cpu perf buffers to consume.
+pub fn online_cpu_ids() -> Result<Vec<usize>> {
+    read_cpu_list("/sys/devices/system/cpu/online")
+}
+
+/// Get the number of CPUs that are currently online.
+///
+/// Contrary to [`num_possible_cpus`], this number may change at runtime,
+/// as CPUs are hotplugged.
+pub fn num_online_cpus() -> Result<usize> {
+    online_cpu_ids().map(|cpus| cpus.len())
+}
+
+/// Look up the index of the network interface called `name`, e.g.,
+/// `eth0`.
+pub fn ifindex_from_name(name: &str) -> Result<i32> {
+    let name_c = str_to_cstring(name)?;
+    // SAFETY: `name_c` is a valid NUL terminated string.
+    let ifindex = unsafe { libc::if_nametoindex(name_c.as_ptr()) };
+    if ifindex == 0 {
+        return Err(io::Error::last_os_error()
+            .context(format!("failed to find network interface `{name}`")));
+    }
+    i32::try_from(ifindex)
+        .map_err(|_| Error::with_invalid_data(format!("interface index {ifindex} out of range")))
+}
+
+/// Look up the name of the network interface with index `ifindex`.
+pub fn ifname_from_index(ifindex: i32) -> Result<String> {
+    let index = u32::try_from(ifindex).map_err(|_| {
+        Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            format!("invalid interface index {ifindex}"),
+        )
+    })?;
+    let mut name = [0; libc::IF_NAMESIZE];
+    // SAFETY: `name` is large enough to hold any interface name,
+    //         including the terminating NUL byte.
+    let ptr = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if ptr.is_null() {
+        return Err(io::Error::last_os_error().context(format!(
+            "failed to find network interface with index {ifindex}"
+        )));
+    }
+    // SANITY: `if_indextoname` NUL terminates the name on success.
+    let name = c_char_slice_to_cstr(&name).unwrap();
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Check whether the kernel charges memory used by BPF maps and
+/// programs to the memory cgroup of the creating process, as opposed to
+/// accounting it against `RLIMIT_MEMLOCK`.
+///
+/// Memory cgroup based accounting was introduced with Linux 5.11. Its
+/// presence is detected by probing for the `bpf_ktime_get_coarse_ns`
+/// helper, which got added in the same release. Probing requires
+/// sufficient privileges to load a BPF program.
+pub fn is_memcg_accounting_supported() -> Result<bool> {
+    ProgramType::SocketFilter.is_helper_supported(libbpf_sys::BPF_FUNC_ktime_get_coarse_ns)
+}
+
+/// Lift the `RLIMIT_MEMLOCK` limit of the calling process, if
+/// necessary to create BPF maps and load BPF programs.
+///
+/// On kernels using memory cgroup based accounting (see
+/// [`is_memcg_accounting_supported`]) the limit is irrelevant and this
+/// function does nothing. Otherwise (or if detection fails), the soft
+/// and hard limits are raised to `RLIM_INFINITY`, which typically
+/// requires `CAP_SYS_RESOURCE`. The function returns whether the limit
+/// got adjusted.
+pub fn bump_memlock_rlimit() -> Result<bool> {
+    if let Ok(true) = is_memcg_accounting_supported() {
+        return Ok(false);
+    }
+
+    let rlimit = libc::rlimit {
+        rlim_cur: libc::RLIM_INFINITY,
+        rlim_max: libc::RLIM_INFINITY,
+    };
+    // SAFETY: `rlimit` is a valid `rlimit` object.
+    let ret = unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error().context("failed to raise RLIMIT_MEMLOCK"));
+    }
+    Ok(true)
+}
+
+bitflags! {
+    /// libbpf behavior flags, as used by [`set_strict_mode`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct StrictMode: u32 {
+        /// Enable all strict behaviors.
+        const ALL                   = libbpf_sys::LIBBPF_STRICT_ALL;
+        /// See [`libbpf_sys::LIBBPF_STRICT_CLEAN_PTRS`].
+        const CLEAN_PTRS            = libbpf_sys::LIBBPF_STRICT_CLEAN_PTRS;
+        /// See [`libbpf_sys::LIBBPF_STRICT_DIRECT_ERRS`].
+        const DIRECT_ERRS           = libbpf_sys::LIBBPF_STRICT_DIRECT_ERRS;
+        /// See [`libbpf_sys::LIBBPF_STRICT_SEC_NAME`].
+        const SEC_NAME              = libbpf_sys::LIBBPF_STRICT_SEC_NAME;
+        /// See [`libbpf_sys::LIBBPF_STRICT_NO_OBJECT_LIST`].
+        const NO_OBJECT_LIST        = libbpf_sys::LIBBPF_STRICT_NO_OBJECT_LIST;
+        /// See [`libbpf_sys::LIBBPF_STRICT_AUTO_RLIMIT_MEMLOCK`].
+        const AUTO_RLIMIT_MEMLOCK   = libbpf_sys::LIBBPF_STRICT_AUTO_RLIMIT_MEMLOCK;
+        /// See [`libbpf_sys::LIBBPF_STRICT_MAP_DEFINITIONS`].
+        const MAP_DEFINITIONS       = libbpf_sys::LIBBPF_STRICT_MAP_DEFINITIONS;
+    }
+}
+
+/// Configure libbpf's strict mode, i.e., opt into behaviors that
+/// were not the default before libbpf 1.0.
+///
+/// Starting with libbpf 1.0 all of these behaviors are always enabled
+/// and the function does not have any effect beyond validating the
+/// provided flags. It is mostly useful for asserting that the linked
+/// library behaves as expected.
+pub fn set_strict_mode(mode: StrictMode) -> Result<()> {
+    let ret = unsafe { libbpf_sys::libbpf_set_strict_mode(mode.bits()) };
+    parse_ret(ret)
+}
+
+/// Retrieve the version of the linked libbpf, as a `(major, minor)`
+/// tuple.
+pub fn libbpf_version() -> (u32, u32) {
+    let major = unsafe { libbpf_sys::libbpf_major_version() };
+    let minor = unsafe { libbpf_sys::libbpf_minor_version() };
+    (major, minor)
+}
+
+/// Retrieve the version of the linked libbpf in textual form, e.g.,
+/// `v1.4`.
+pub fn libbpf_version_string() -> &'static str {
+    // SAFETY: libbpf returns a pointer to a static, NUL terminated
+    //         string.
+    let version = unsafe { CStr::from_ptr(libbpf_sys::libbpf_version_string()) };
+    // The version string is pure ASCII.
+    version.to_str().unwrap_or_default()
+}
+
+/// An entry of a `/proc/<pid>/maps` file, describing a memory mapping.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct MapsEntry {
+    /// The virtual address range covered by the mapping.
+    pub range: Range<usize>,
+    /// The offset of the mapping in the backing file.
+    pub offset: usize,
+    /// The inode of the backing file, `0` for anonymous mappings.
+    pub inode: u64,
+    /// The path of the backing file or a pseudo path such as `[stack]`.
+    pub path: String,
+}
+
+impl MapsEntry {
+    /// Parse a single line of a `/proc/<pid>/maps` file.
+    fn parse(line: &str) -> Result<Self> {
+        let invalid = || Error::with_invalid_data(format!("invalid maps entry: `{line}`"));
+
+        let mut fields = line.splitn(6, ' ');
+        let (range, offset, inode, path) = match (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) {
+            (Some(range), Some(_perms), Some(offset), Some(_dev), Some(inode), path) => {
+                (range, offset, inode, path.unwrap_or("").trim())
+            }
+            _ => return Err(invalid()),
+        };
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start = usize::from_str_radix(start, 16).map_err(|_| invalid())?;
+        let end = usize::from_str_radix(end, 16).map_err(|_| invalid())?;
+        let offset = usize::from_str_radix(offset, 16).map_err(|_| invalid())?;
+        let inode = inode.parse().map_err(|_| invalid())?;
+
+        Ok(Self {
+            range: start..end,
+            offset,
+            inode,
+            path: path.to_string(),
+        })
+    }
+
+    /// Retrieve a path through which the file backing the mapping can
+    /// be accessed, if any.
+    ///
+    /// `proc_dir` is the `/proc/<pid>` directory of the process, used
+    /// for referring to files that are no longer accessible through a
+    /// regular path, e.g., because they were deleted or created via
+    /// `memfd_create(2)`.
+    pub fn file_path(&self, proc_dir: &Path) -> Option<PathBuf> {
+        if self.inode == 0 {
+            None
+        } else if self.path.starts_with('/') && !self.path.ends_with(" (deleted)") {
+            Some(PathBuf::from(&self.path))
+        } else {
+            let range = format!("{:x}-{:x}", self.range.start, self.range.end);
+            Some(proc_dir.join("map_files").join(range))
+        }
+    }
+}
+
+/// Parse the memory mappings listed in `maps`, in the format of
+/// `/proc/<pid>/maps`.
+pub(crate) fn parse_maps<R: BufRead>(maps: R) -> Result<Vec<MapsEntry>> {
+    maps.lines().map(|line| MapsEntry::parse(&line?)).collect()
+}
+
+/// Parse the `memlock` entry out of the contents of a
+/// `/proc/<pid>/fdinfo/<fd>` file.
+pub fn parse_fdinfo_memlock<R: BufRead>(reader: R) -> Result<u64> {
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(value) = line.strip_prefix("memlock:") {
+            return value.trim().parse().map_err(|err| {
+                Error::with_invalid_data(format!("invalid memlock value `{value}`: {err}"))
+            });
+        }
+    }
+
+    Err(Error::with_invalid_data(
+        "fdinfo does not contain memlock entry",
+    ))
+}
+
+/// Retrieve the amount of memory charged to the BPF entity (program or
+/// map) referenced by `fd`, as reported by the kernel.
+pub fn fd_memlock(fd: BorrowedFd<'_>) -> Result<u64> {
+    let path = format!("/proc/self/fdinfo/{}", fd.as_raw_fd());
+    let file = File::open(&path)?;
+    parse_fdinfo_memlock(BufReader::new(file))
+}
+
 pub fn parse_ret(ret: i32) -> Result<()> {
     if ret < 0 {
         // Error code is returned negative, flip to positive to match errno
@@ -76,6 +368,57 @@ pub fn parse_ret_i32(ret: i32) -> Result<i32> {
     parse_ret(ret).map(|()| ret)
 }
 
+/// Check whether `path` resides on a BPF file system (bpffs).
+pub(crate) fn is_bpffs(path: &Path) -> Result<bool> {
+    let path = path_to_cstring(path)?;
+    // SAFETY: `statfs` is valid for any bit pattern.
+    let mut buf = unsafe { mem::zeroed::<libc::statfs>() };
+    // SAFETY: Both pointers are valid.
+    let rc = unsafe { libc::statfs(path.as_ptr(), &mut buf) };
+    if rc != 0 {
+        return Err(Error::from(io::Error::last_os_error()));
+    }
+    Ok(buf.f_type as libc::c_long == libc::BPF_FS_MAGIC)
+}
+
+/// Parse the return value of a pin operation for `path`.
+///
+/// The kernel reports attempts to pin outside of a BPF file system with
+/// a rather generic error, which we replace with a more descriptive one.
+pub(crate) fn parse_pin_ret(ret: i32, path: &Path) -> Result<()> {
+    let err = match parse_ret(ret) {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match is_bpffs(dir) {
+        Ok(false) => Err(Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "cannot pin to `{}`: `{}` is not on a BPF file system (bpffs)",
+                path.display(),
+                dir.display()
+            ),
+        )),
+        Ok(true) | Err(_) => Err(err),
+    }
+}
+
+/// Construct the path for pinning an object called `name` in `dir`.
+pub(crate) fn pin_path_in_dir(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') {
+        return Err(Error::with_io_error(
+            io::ErrorKind::InvalidInput,
+            format!("cannot derive pin path from object name `{name}`"),
+        ));
+    }
+    Ok(dir.join(name))
+}
+
 pub fn create_bpf_entity_checked<B: 'static, F: FnOnce() -> *mut B>(f: F) -> Result<NonNull<B>> {
     create_bpf_entity_checked_opt(f).and_then(|ptr| {
         ptr.ok_or_else(|| {
@@ -135,10 +478,34 @@ impl<T> Deref for LazyLock<T> {
     }
 }
 
+/// Retrieve the path to the test object file with the given name.
+#[cfg(test)]
+pub(crate) fn test_object_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("bin")
+        .join(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Check that the reported libbpf version is consistent.
+    #[test]
+    fn libbpf_version_reporting() {
+        let (major, minor) = libbpf_version();
+        assert!(major >= 1);
+        assert_eq!(libbpf_version_string(), format!("v{major}.{minor}"));
+    }
+
+    /// Check that we can set libbpf's strict mode.
+    #[test]
+    fn strict_mode_setting() {
+        let () = set_strict_mode(StrictMode::ALL).unwrap();
+        let () = set_strict_mode(StrictMode::CLEAN_PTRS | StrictMode::DIRECT_ERRS).unwrap();
+    }
+
     #[test]
     fn test_roundup() {
         for i in 1..=256 {
@@ -156,12 +523,68 @@ mod tests {
         }
     }
 
+    /// Check that we can extract the `memlock` value from fdinfo
+    /// contents.
+    #[test]
+    fn fdinfo_memlock_parsing() {
+        let fdinfo = "pos:\t0\nflags:\t02000002\nmnt_id:\t15\nino:\t1057\nmap_type:\t1\nmemlock:\t4096\nmap_id:\t42\n";
+        assert_eq!(parse_fdinfo_memlock(fdinfo.as_bytes()).unwrap(), 4096);
+
+        let fdinfo = "pos:\t0\nflags:\t02000002\n";
+        let err = parse_fdinfo_memlock(fdinfo.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_num_possible_cpus() {
         let num = num_possible_cpus().unwrap();
         assert!(num > 0);
     }
 
+    /// Check that we can translate between network interface names and
+    /// indices.
+    #[test]
+    fn interface_name_resolution() {
+        let ifindex = ifindex_from_name("lo").unwrap();
+        assert_eq!(ifname_from_index(ifindex).unwrap(), "lo");
+
+        let err = ifindex_from_name("does-not-exist").unwrap_err();
+        assert!(err.to_string().contains("does-not-exist"), "{err}");
+        let _err = ifindex_from_name("lo\0").unwrap_err();
+
+        let err = ifname_from_index(-1).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidInput);
+        let _err = ifname_from_index(i32::MAX).unwrap_err();
+    }
+
+    /// Check that we can parse CPU lists as exposed by sysfs.
+    #[test]
+    fn cpu_list_parsing() {
+        assert_eq!(parse_cpu_list("0\n").unwrap(), vec![0]);
+        assert_eq!(
+            parse_cpu_list("0-3,5,7-8\n").unwrap(),
+            vec![0, 1, 2, 3, 5, 7, 8]
+        );
+        assert_eq!(parse_cpu_list("").unwrap(), Vec::<usize>::new());
+
+        let err = parse_cpu_list("3-1").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidData);
+        let err = parse_cpu_list("0-x").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::InvalidData);
+    }
+
+    /// Check that the system's CPU topology is consistent.
+    #[test]
+    fn cpu_topology() {
+        let possible = possible_cpu_ids().unwrap();
+        assert_eq!(possible.len(), num_possible_cpus().unwrap());
+
+        let online = online_cpu_ids().unwrap();
+        assert_eq!(online.len(), num_online_cpus().unwrap());
+        assert!(!online.is_empty());
+        assert!(online.iter().all(|cpu| possible.contains(cpu)));
+    }
+
     /// Check that we can convert a `[c_char]` into a `CStr`.
     #[test]
     fn c_char_slice_conversion() {
@@ -1,10 +1,13 @@
 use std::mem::size_of;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
+use std::ptr;
 
 use bitflags::bitflags;
 
 use crate::util;
+use crate::Link;
 use crate::Result;
 
 bitflags! {
@@ -30,6 +33,60 @@ bitflags! {
 
 }
 
+bitflags! {
+    /// XDP features supported by a network device, see
+    /// `enum netdev_xdp_act` in the kernel's `include/uapi/linux/netdev.h`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct XdpFeatureFlags: u64 {
+        /// Basic XDP support, i.e., `XDP_PASS`, `XDP_DROP`, `XDP_ABORTED`,
+        /// and `XDP_TX`, in native driver mode.
+        const BASIC        = 1 << 0;
+        /// Support for `XDP_REDIRECT`.
+        const REDIRECT     = 1 << 1;
+        /// Support for being the target of `XDP_REDIRECT`.
+        const NDO_XMIT     = 1 << 2;
+        /// Support for AF_XDP sockets in zero-copy mode.
+        const XSK_ZEROCOPY = 1 << 3;
+        /// Support for offloading XDP programs to hardware.
+        const HW_OFFLOAD   = 1 << 4;
+        /// Support for non-linear (multi-buffer) frames on receive.
+        const RX_SG        = 1 << 5;
+        /// Support for being the target of `XDP_REDIRECT` with
+        /// non-linear frames.
+        const NDO_XMIT_SG  = 1 << 6;
+    }
+}
+
+/// XDP related capabilities of a network device, as reported by
+/// [`Xdp::query_features`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XdpFeatures {
+    /// The XDP features supported by the device.
+    ///
+    /// Kernels before 6.3 do not report features, in which case this
+    /// set is empty.
+    pub flags: XdpFeatureFlags,
+    /// The maximum number of fragments supported for AF_XDP zero-copy
+    /// transmission.
+    pub zc_max_segs: u32,
+}
+
+impl XdpFeatures {
+    /// Determine the most efficient attach mode supported by the device:
+    /// [`XdpFlags::HW_MODE`] if programs can be offloaded,
+    /// [`XdpFlags::DRV_MODE`] if the driver supports XDP natively, and
+    /// [`XdpFlags::SKB_MODE`] otherwise.
+    pub fn best_mode(&self) -> XdpFlags {
+        if self.flags.contains(XdpFeatureFlags::HW_OFFLOAD) {
+            XdpFlags::HW_MODE
+        } else if self.flags.contains(XdpFeatureFlags::BASIC) {
+            XdpFlags::DRV_MODE
+        } else {
+            XdpFlags::SKB_MODE
+        }
+    }
+}
+
 /// Represents a XDP program.
 ///
 /// This struct exposes operations to attach, detach and query a XDP program
@@ -72,12 +129,26 @@ impl<'fd> Xdp<'fd> {
         util::parse_ret(ret)
     }
 
+    /// Attach the XDP program to the interface called `name`, e.g.,
+    /// `eth0`.
+    ///
+    /// See [`Xdp::attach`] for details.
+    pub fn attach_ifname(&self, name: &str, flags: XdpFlags) -> Result<()> {
+        self.attach(util::ifindex_from_name(name)?, flags)
+    }
+
     /// Detach the XDP program from the interface
     pub fn detach(&self, ifindex: i32, flags: XdpFlags) -> Result<()> {
         let ret = unsafe { libbpf_sys::bpf_xdp_detach(ifindex, flags.bits(), &self.attach_opts) };
         util::parse_ret(ret)
     }
 
+    /// Detach the XDP program from the interface called `name`, e.g.,
+    /// `eth0`.
+    pub fn detach_ifname(&self, name: &str, flags: XdpFlags) -> Result<()> {
+        self.detach(util::ifindex_from_name(name)?, flags)
+    }
+
     /// Query to inspect the program
     pub fn query(&self, ifindex: i32, flags: XdpFlags) -> Result<libbpf_sys::bpf_xdp_query_opts> {
         let mut opts = self.query_opts;
@@ -93,6 +164,22 @@ impl<'fd> Xdp<'fd> {
         util::parse_ret(err).map(|()| prog_id)
     }
 
+    /// Query the XDP features supported by the network device with the
+    /// given interface index.
+    pub fn query_features(ifindex: i32) -> Result<XdpFeatures> {
+        let mut opts = libbpf_sys::bpf_xdp_query_opts {
+            sz: size_of::<libbpf_sys::bpf_xdp_query_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        let err = unsafe { libbpf_sys::bpf_xdp_query(ifindex, 0, &mut opts) };
+        let () = util::parse_ret(err)?;
+
+        Ok(XdpFeatures {
+            flags: XdpFeatureFlags::from_bits_retain(opts.feature_flags),
+            zc_max_segs: opts.xdp_zc_max_segs,
+        })
+    }
+
     /// Replace an existing xdp program (identified by old_prog_fd) with this xdp program
     pub fn replace(&self, ifindex: i32, old_prog_fd: BorrowedFd<'_>) -> Result<()> {
         let mut opts = self.attach_opts;
@@ -108,3 +195,127 @@ impl<'fd> Xdp<'fd> {
         util::parse_ret(ret)
     }
 }
+
+#[derive(Debug)]
+enum XdpAttachmentInner {
+    Link(Link),
+    Legacy { ifindex: i32, prog_fd: OwnedFd },
+}
+
+/// An XDP program attached to a network interface, as created by
+/// [`Program::attach_xdp_with_fallback`][crate::Program::attach_xdp_with_fallback].
+///
+/// If supported by the kernel, the program is attached via a BPF link,
+/// which is detached automatically once the last reference to it is
+/// closed, including when the process crashes. On older kernels, the
+/// legacy netlink based attachment is used instead. In this case, the
+/// program is only detached when this object is dropped or explicitly
+/// detached.
+#[derive(Debug)]
+pub struct XdpAttachment {
+    inner: Option<XdpAttachmentInner>,
+}
+
+impl XdpAttachment {
+    pub(crate) fn from_link(link: Link) -> Self {
+        Self {
+            inner: Some(XdpAttachmentInner::Link(link)),
+        }
+    }
+
+    /// Attach the program referenced by `prog_fd` using the legacy API,
+    /// refusing to replace any already attached program.
+    pub(crate) fn attach_legacy(ifindex: i32, prog_fd: BorrowedFd<'_>) -> Result<Self> {
+        let prog_fd = prog_fd.try_clone_to_owned()?;
+        let ret = unsafe {
+            libbpf_sys::bpf_xdp_attach(
+                ifindex,
+                prog_fd.as_raw_fd(),
+                XdpFlags::UPDATE_IF_NOEXIST.bits(),
+                ptr::null(),
+            )
+        };
+        let () = util::parse_ret(ret)?;
+
+        Ok(Self {
+            inner: Some(XdpAttachmentInner::Legacy { ifindex, prog_fd }),
+        })
+    }
+
+    /// Check whether the program is attached via a BPF link.
+    pub fn is_link(&self) -> bool {
+        matches!(self.inner, Some(XdpAttachmentInner::Link(..)))
+    }
+
+    /// Retrieve the BPF link the program is attached by, if any.
+    pub fn link(&self) -> Option<&Link> {
+        match &self.inner {
+            Some(XdpAttachmentInner::Link(link)) => Some(link),
+            _ => None,
+        }
+    }
+
+    /// Detach the program from the network interface.
+    pub fn detach(mut self) -> Result<()> {
+        match self.inner.take() {
+            Some(XdpAttachmentInner::Link(link)) => link.detach(),
+            Some(XdpAttachmentInner::Legacy { ifindex, prog_fd }) => {
+                Self::detach_legacy(ifindex, &prog_fd)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn detach_legacy(ifindex: i32, prog_fd: &OwnedFd) -> Result<()> {
+        // Only detach the program if it is still the one attached, so
+        // that we do not accidentally remove a program someone else
+        // attached in the meantime.
+        let mut opts = libbpf_sys::bpf_xdp_attach_opts {
+            sz: size_of::<libbpf_sys::bpf_xdp_attach_opts>() as libbpf_sys::size_t,
+            ..Default::default()
+        };
+        opts.old_prog_fd = prog_fd.as_raw_fd();
+        let ret = unsafe { libbpf_sys::bpf_xdp_detach(ifindex, XdpFlags::REPLACE.bits(), &opts) };
+        util::parse_ret(ret)
+    }
+}
+
+impl Drop for XdpAttachment {
+    fn drop(&mut self) {
+        if let Some(XdpAttachmentInner::Legacy { ifindex, prog_fd }) = &self.inner {
+            let _result = Self::detach_legacy(*ifindex, prog_fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the best attach mode is derived from device features
+    /// as expected.
+    #[test]
+    fn best_mode_selection() {
+        let features = |flags| XdpFeatures {
+            flags,
+            zc_max_segs: 0,
+        };
+
+        assert_eq!(
+            features(XdpFeatureFlags::empty()).best_mode().bits(),
+            XdpFlags::SKB_MODE.bits()
+        );
+        assert_eq!(
+            features(XdpFeatureFlags::BASIC | XdpFeatureFlags::REDIRECT)
+                .best_mode()
+                .bits(),
+            XdpFlags::DRV_MODE.bits()
+        );
+        assert_eq!(
+            features(XdpFeatureFlags::BASIC | XdpFeatureFlags::HW_OFFLOAD)
+                .best_mode()
+                .bits(),
+            XdpFlags::HW_MODE.bits()
+        );
+    }
+}
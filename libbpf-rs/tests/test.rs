@@ -1,29 +1,69 @@
 #![allow(clippy::let_unit_value)]
 #![warn(clippy::absolute_paths)]
 
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::env::current_exe;
 use std::ffi::c_int;
 use std::ffi::c_void;
+use std::ffi::CString;
+use std::ffi::OsStr;
 use std::fs;
+use std::fs::File;
 use std::hint;
 use std::io;
 use std::io::Read;
 use std::mem::size_of;
+use std::mem::size_of_val;
+use std::mem::zeroed;
+use std::net::Ipv4Addr;
+use std::net::UdpSocket;
 use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd as _;
+use std::os::unix::io::IntoRawFd as _;
+use std::os::unix::io::OwnedFd;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
 use std::ptr;
 use std::ptr::addr_of;
+use std::ptr::NonNull;
 use std::slice;
 use std::sync::mpsc::channel;
+use std::sync::Mutex;
+use std::thread;
 use std::time::Duration;
 
+use libbpf_rs::btf::types::IntEncoding;
+use libbpf_rs::btf::types::Struct;
+use libbpf_rs::btf::BtfBuilder;
+use libbpf_rs::btf::TypeId;
 use libbpf_rs::num_possible_cpus;
+use libbpf_rs::query::BtfInfoIter;
+use libbpf_rs::query::ChainTarget;
+use libbpf_rs::query::LineInfo;
+use libbpf_rs::query::LinkInfoIter;
+use libbpf_rs::query::LinkTypeInfo;
+use libbpf_rs::query::MapInfoIter;
+use libbpf_rs::query::NetNsLinkInfo;
+use libbpf_rs::stacktrace;
 use libbpf_rs::AsRawLibbpf;
+use libbpf_rs::BpfPollable as _;
+use libbpf_rs::CpumapEntry;
+use libbpf_rs::CpumapValue;
+use libbpf_rs::DevmapEntry;
+use libbpf_rs::DevmapValue;
+use libbpf_rs::ErrorKind;
 use libbpf_rs::Iter;
+use libbpf_rs::IterOpts;
+use libbpf_rs::KprobeOpts;
+use libbpf_rs::LightSkel;
+use libbpf_rs::Link;
 use libbpf_rs::Linker;
+use libbpf_rs::LinkerOptions;
 use libbpf_rs::Map;
+use libbpf_rs::MapCreateFlags;
 use libbpf_rs::MapFlags;
 use libbpf_rs::MapHandle;
 use libbpf_rs::MapInfo;
@@ -31,16 +71,31 @@ use libbpf_rs::MapType;
 use libbpf_rs::Object;
 use libbpf_rs::ObjectBuilder;
 use libbpf_rs::OpenObject;
+use libbpf_rs::PerfEventArray;
+use libbpf_rs::Pod;
+use libbpf_rs::Poller;
 use libbpf_rs::Program;
+use libbpf_rs::ProgramAttachType;
 use libbpf_rs::ProgramInput;
+use libbpf_rs::ProgramLoadError;
 use libbpf_rs::ProgramType;
+use libbpf_rs::ReuseportSockArray;
+use libbpf_rs::RingBufferBuilder;
+use libbpf_rs::RingBufferDispatcherBuilder;
+use libbpf_rs::RingBufferDispatcherOpts;
+use libbpf_rs::RingBufferReader;
+use libbpf_rs::RingBufferSampleCtx;
+use libbpf_rs::ShutdownOpts;
+use libbpf_rs::SymbolList;
 use libbpf_rs::TracepointOpts;
+use libbpf_rs::TypedMap;
 use libbpf_rs::UprobeOpts;
 use libbpf_rs::UsdtOpts;
 use libbpf_rs::UserRingBuffer;
 use plain::Plain;
 use probe::probe;
 use scopeguard::defer;
+use tempfile::tempdir;
 use tempfile::NamedTempFile;
 use test_tag::tag;
 
@@ -71,19 +126,40 @@ pub fn get_test_object(filename: &str) -> Object {
         .expect("failed to load object")
 }
 
-pub fn bump_rlimit_mlock() {
-    let rlimit = libc::rlimit {
-        rlim_cur: 128 << 20,
-        rlim_max: 128 << 20,
-    };
+/// Load the `handle_tc` program of `tc-unit.bpf.o` as a program of type
+/// `prog_type`, with its instructions replaced by a sequence merely
+/// returning `ret`.
+pub fn get_trivial_test_object(
+    prog_type: ProgramType,
+    attach_type: Option<ProgramAttachType>,
+    ret: i32,
+) -> Object {
+    let mut open_obj = open_test_object("tc-unit.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle_tc")
+        .expect("failed to find program");
+    let () = prog.set_prog_type(prog_type);
+    if let Some(attach_type) = attach_type {
+        let () = prog.set_expected_attach_type(attach_type);
+    }
+    // `r0 = ret; exit`
+    let insns = [
+        libbpf_sys::bpf_insn {
+            code: (libbpf_sys::BPF_ALU64 | libbpf_sys::BPF_MOV | libbpf_sys::BPF_K) as _,
+            imm: ret,
+            ..Default::default()
+        },
+        libbpf_sys::bpf_insn {
+            code: (libbpf_sys::BPF_JMP | libbpf_sys::BPF_EXIT) as _,
+            ..Default::default()
+        },
+    ];
+    let () = prog.set_insns(&insns).unwrap();
+    open_obj.load().expect("failed to load object")
+}
 
-    let ret = unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &rlimit) };
-    assert_eq!(
-        ret,
-        0,
-        "Setting RLIMIT_MEMLOCK failed with errno: {}",
-        io::Error::last_os_error()
-    );
+pub fn bump_rlimit_mlock() {
+    let _bumped = libbpf_rs::bump_memlock_rlimit().expect("failed to bump RLIMIT_MEMLOCK");
 }
 
 /// A helper function for instantiating a `RingBuffer` with a callback meant to
@@ -148,6 +224,54 @@ fn test_object_build_from_memory_empty_name() {
     assert!(name.is_empty());
 }
 
+/// Check that we can open an object from a file descriptor, irrespective
+/// of its current position.
+#[test]
+fn test_object_build_from_fd() {
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let mut file = File::open(obj_path).expect("failed to open object file");
+    let mut buf = [0; 16];
+    let () = file.read_exact(&mut buf).unwrap();
+
+    let obj = ObjectBuilder::default()
+        .name("fd name")
+        .unwrap()
+        .open_fd(file.as_fd())
+        .expect("failed to build object");
+    assert_eq!(obj.name().unwrap(), "fd name");
+    assert!(obj.map("events").is_some());
+
+    // The file position is left untouched.
+    let mut rest = Vec::new();
+    let _count = file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest.len() as u64 + 16, file.metadata().unwrap().len());
+}
+
+/// Check that we can open an object from a reader.
+#[test]
+fn test_object_build_from_reader() {
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let file = File::open(obj_path).expect("failed to open object file");
+    let obj = ObjectBuilder::default()
+        .open_reader(file)
+        .expect("failed to build object");
+    assert!(obj.map("events").is_some());
+
+    let _err = ObjectBuilder::default()
+        .open_reader(&b"not an ELF file"[..])
+        .unwrap_err();
+}
+
+/// Check that we can detect memory cgroup based accounting and bump
+/// `RLIMIT_MEMLOCK` as necessary.
+#[tag(root)]
+#[test]
+fn test_bump_memlock_rlimit() {
+    let memcg = libbpf_rs::is_memcg_accounting_supported().unwrap();
+    let bumped = libbpf_rs::bump_memlock_rlimit().unwrap();
+    assert_eq!(bumped, !memcg);
+}
+
 /// Check that loading an object from an empty file fails as expected.
 #[tag(root)]
 #[test]
@@ -180,6 +304,19 @@ fn test_object_maps() {
     assert!(obj.map("asdf").is_none());
 }
 
+/// Check that we can retrieve the memory usage of a loaded object.
+#[tag(root)]
+#[test]
+fn test_object_memory_usage() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let usage = obj.memory_usage().unwrap();
+    assert!(usage.maps > 0);
+    assert!(usage.progs > 0);
+    assert_eq!(usage.total(), usage.maps + usage.progs);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_maps_iter() {
@@ -209,6 +346,399 @@ fn test_object_map_key_value_size() {
         .is_err());
 }
 
+/// Check that we can access a map through a typed view.
+#[tag(root)]
+#[test]
+fn test_object_typed_map() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let start = obj.map("start").expect("failed to find map");
+
+    // Key and value sizes have to match.
+    assert!(TypedMap::<u64, u64>::new(start).is_err());
+    assert!(TypedMap::<u32, u32>::new(start).is_err());
+
+    let start = TypedMap::<u32, u64>::new(start).expect("failed to create typed map");
+    assert_eq!(start.lookup(&1).expect("failed to look up key"), None);
+
+    let () = start
+        .update(&1, &42, MapFlags::empty())
+        .expect("failed to update map");
+    assert_eq!(start.lookup(&1).expect("failed to look up key"), Some(42));
+    assert_eq!(start.keys().collect::<Vec<_>>(), vec![1]);
+
+    let () = start.delete(&1).expect("failed to delete key");
+    assert_eq!(start.lookup(&1).expect("failed to look up key"), None);
+}
+
+/// Check that we can configure the creation flags of a map before load.
+#[tag(root)]
+#[test]
+fn test_object_map_create_flags() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("runqslower.bpf.o");
+    let start = open_obj.map_mut("start").expect("failed to find map");
+    assert!(!start.map_flags().contains(MapCreateFlags::NO_PREALLOC));
+
+    let () = start
+        .set_map_flags(MapCreateFlags::NO_PREALLOC)
+        .expect("failed to set map flags");
+    assert_eq!(start.map_flags(), MapCreateFlags::NO_PREALLOC);
+
+    let obj = open_obj.load().expect("failed to load object");
+    let start = obj.map("start").expect("failed to find map");
+    let info = start.info().expect("failed to retrieve map info");
+    assert_eq!(info.info.map_flags, MapCreateFlags::NO_PREALLOC.bits());
+}
+
+/// Check that map offloading is reflected in the map information and
+/// that it fails for devices not supporting it.
+#[tag(root)]
+#[test]
+fn test_map_create_offloaded() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(MapType::Hash, Some("simple_map"), 8, 64, 1024, &opts).unwrap();
+    let info = map.info().unwrap();
+    assert_eq!(info.offload_device(), None);
+    let info = MapInfoIter::default()
+        .find(|info| info.id == map.info().unwrap().info.id)
+        .unwrap();
+    assert_eq!(info.offload_device(), None);
+
+    // The loopback device does not support offloading.
+    let ifindex = libbpf_rs::ifindex_from_name("lo").unwrap() as u32;
+    let _err = MapHandle::create_offloaded(
+        MapType::Hash,
+        Some("offload_map"),
+        8,
+        64,
+        1024,
+        ifindex,
+        &opts,
+    )
+    .unwrap_err();
+}
+
+/// Check that we can populate a perf event array with custom perf
+/// events.
+#[tag(root)]
+#[test]
+fn test_perf_event_array() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(
+        MapType::PerfEventArray,
+        Some("perf_events"),
+        4,
+        4,
+        num_possible_cpus().unwrap() as u32,
+        &opts,
+    )
+    .unwrap();
+
+    let events = PerfEventArray::open(
+        &map,
+        libbpf_sys::PERF_TYPE_SOFTWARE,
+        libbpf_sys::PERF_COUNT_SW_CPU_CLOCK as u64,
+    )
+    .expect("failed to open perf events");
+    let cpus = events.cpus().collect::<Vec<_>>();
+    assert_eq!(cpus, libbpf_rs::online_cpu_ids().unwrap());
+    for cpu in cpus {
+        assert!(events.event_fd(cpu).is_some());
+    }
+    assert!(events.event_fd(usize::MAX).is_none());
+    drop(events);
+
+    let map = MapHandle::create(MapType::Array, Some("array"), 4, 4, 1, &opts).unwrap();
+    let err = PerfEventArray::open(&map, libbpf_sys::PERF_TYPE_SOFTWARE, 0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that we can configure CPU and device maps for XDP redirects.
+#[tag(root)]
+#[test]
+fn test_map_cpumap_devmap() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("xdp.bpf.o");
+    let prog = open_obj.prog_mut("xdp_filter").unwrap();
+    let () = prog.set_expected_attach_type(ProgramAttachType::XdpCpumap);
+    let obj = open_obj.load().expect("failed to load object");
+    let prog = obj.prog("xdp_filter").unwrap();
+    let prog_id = Program::get_id_by_fd(prog.as_fd()).unwrap();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let cpus = num_possible_cpus().unwrap() as u32;
+    let cpumap = MapHandle::create(MapType::Cpumap, Some("cpumap"), 4, 8, cpus, &opts).unwrap();
+    let value = CpumapValue {
+        qsize: 192,
+        prog: None,
+    };
+    let () = cpumap.update_cpumap(0, &value).unwrap();
+    let entry = cpumap.lookup_cpumap(0).unwrap().unwrap();
+    assert_eq!(
+        entry,
+        CpumapEntry {
+            qsize: 192,
+            prog_id: None
+        }
+    );
+
+    let value = CpumapValue {
+        qsize: 256,
+        prog: Some(prog.as_fd()),
+    };
+    let () = cpumap.update_cpumap(0, &value).unwrap();
+    let entry = cpumap.lookup_cpumap(0).unwrap().unwrap();
+    assert_eq!(entry.qsize, 256);
+    assert_eq!(entry.prog_id, Some(prog_id));
+
+    let devmap = MapHandle::create(MapType::DevmapHash, Some("devmap"), 4, 4, 8, &opts).unwrap();
+    assert_eq!(devmap.lookup_devmap(42).unwrap(), None);
+    let value = DevmapValue {
+        ifindex: 1,
+        prog: None,
+    };
+    let () = devmap.update_devmap(42, &value).unwrap();
+    let entry = devmap.lookup_devmap(42).unwrap().unwrap();
+    assert_eq!(
+        entry,
+        DevmapEntry {
+            ifindex: 1,
+            prog_id: None
+        }
+    );
+
+    // The map's value is too small to hold a program.
+    let value = DevmapValue {
+        ifindex: 1,
+        prog: Some(prog.as_fd()),
+    };
+    let err = devmap.update_devmap(42, &value).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let err = devmap.lookup_cpumap(0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Create a UDP socket bound to the loopback device with `SO_REUSEPORT`
+/// set.
+fn reuseport_udp_socket() -> OwnedFd {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    assert!(fd >= 0, "{}", io::Error::last_os_error());
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let one = 1i32;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_REUSEPORT,
+            (&one as *const i32).cast(),
+            size_of::<i32>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+
+    let addr = libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: 0,
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(Ipv4Addr::LOCALHOST).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    let ret = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&addr as *const libc::sockaddr_in).cast(),
+            size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+    assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+    socket
+}
+
+/// Check that we can manage a reuseport socket array and attach a
+/// reuseport program to a socket.
+#[tag(root)]
+#[test]
+fn test_reuseport_sock_array() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let map = MapHandle::create(
+        MapType::ReuseportSockarray,
+        Some("reuseport"),
+        4,
+        8,
+        4,
+        &opts,
+    )
+    .unwrap();
+    let array = ReuseportSockArray::new(&map).unwrap();
+    let socket = reuseport_udp_socket();
+
+    assert_eq!(array.socket_cookie(1).unwrap(), None);
+    let () = array.insert(1, &socket).unwrap();
+    let mut cookie = 0u64;
+    let mut len = size_of::<u64>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_COOKIE,
+            (&mut cookie as *mut u64).cast(),
+            &mut len,
+        )
+    };
+    assert_eq!(ret, 0);
+    assert_eq!(array.socket_cookie(1).unwrap(), Some(cookie));
+    let () = array.remove(1).unwrap();
+    assert_eq!(array.socket_cookie(1).unwrap(), None);
+
+    let map = MapHandle::create(MapType::Array, Some("array"), 4, 8, 4, &opts).unwrap();
+    let err = ReuseportSockArray::new(&map).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    // Use a program consisting of a `r0 = 1 (SK_PASS); exit` sequence.
+    let obj = get_trivial_test_object(
+        ProgramType::SkReuseport,
+        Some(ProgramAttachType::SkReuseportSelect),
+        1,
+    );
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+    let () = prog.attach_sk_reuseport(&socket).unwrap();
+    let () = Program::detach_sk_reuseport(&socket).unwrap();
+    let err = Program::detach_sk_reuseport(&socket).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+/// Check that we can attach a socket filter to and detach it from a
+/// socket.
+#[tag(root)]
+#[test]
+fn test_socket_filter_attach_detach() {
+    bump_rlimit_mlock();
+
+    // Use a program consisting of a `r0 = 0; exit` sequence, dropping all
+    // packets.
+    let obj = get_trivial_test_object(ProgramType::SocketFilter, None, 0);
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+
+    let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let () = receiver
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = receiver.local_addr().unwrap();
+    let mut buf = [0; 4];
+
+    let () = prog.attach_socket_filter(&receiver).unwrap();
+    let _cnt = sender.send_to(b"drop", addr).unwrap();
+    let _err = receiver.recv(&mut buf).unwrap_err();
+
+    let () = Program::detach_socket_filter(&receiver).unwrap();
+    let _cnt = sender.send_to(b"pass", addr).unwrap();
+    let cnt = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..cnt], b"pass");
+
+    let err = Program::detach_socket_filter(&receiver).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
+/// Check that we can attach an `sk_msg` program to a sockmap and that it
+/// gets detached once the attachment is dropped.
+#[tag(root)]
+#[test]
+fn test_object_sk_msg_attach() {
+    bump_rlimit_mlock();
+
+    // Use a program consisting of a `r0 = 1 (SK_PASS); exit` sequence.
+    let obj = get_trivial_test_object(ProgramType::SkMsg, Some(ProgramAttachType::SkMsgVerdict), 1);
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let sockmap = MapHandle::create(MapType::Sockmap, Some("sockmap"), 4, 4, 1, &opts).unwrap();
+    let attachment = prog
+        .attach_sk_msg(&sockmap)
+        .expect("failed to attach program");
+    assert!(matches!(
+        attachment.attach_type(),
+        ProgramAttachType::SkMsgVerdict
+    ));
+    let attached = Program::prog_query(sockmap.as_fd(), ProgramAttachType::SkMsgVerdict)
+        .expect("failed to query programs");
+    assert_eq!(attached.len(), 1);
+
+    let () = drop(attachment);
+    let attached = Program::prog_query(sockmap.as_fd(), ProgramAttachType::SkMsgVerdict)
+        .expect("failed to query programs");
+    assert_eq!(attached, Vec::<u32>::new());
+
+    let attachment = prog.attach_sk_msg(&sockmap).unwrap();
+    let () = attachment.detach().unwrap();
+
+    let array = MapHandle::create(MapType::Array, Some("array"), 4, 4, 1, &opts).unwrap();
+    let err = prog.attach_sk_msg(&array).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that attaching a program to a cgroup fails early if its attach
+/// type does not fit.
+#[tag(root)]
+#[test]
+fn test_object_attach_cgroup_type_mismatch() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("tc-unit.bpf.o");
+    let prog = obj.prog_mut("handle_tc").expect("failed to find program");
+    assert_eq!(prog.prog_type(), ProgramType::SchedCls);
+
+    let cgroup = File::open("/sys/fs/cgroup").unwrap();
+    let err = prog.attach_cgroup(cgroup.as_raw_fd()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that we can enumerate kernel btf and instantiate `Btf` objects
+/// from the reported information.
+#[tag(root)]
+#[test]
+fn test_btf_info_iter() {
+    let vmlinux = BtfInfoIter::default()
+        .find(|info| info.kernel_btf && !info.is_module())
+        .expect("failed to find vmlinux btf");
+    assert_eq!(vmlinux.name.to_bytes(), b"vmlinux");
+    assert!(vmlinux.size() > 0);
+
+    let btf = vmlinux.to_btf().expect("failed to instantiate btf");
+    let task_struct = btf
+        .type_by_name::<Struct<'_>>("task_struct")
+        .expect("failed to look up type");
+    assert!(task_struct.is_some());
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_update_batch() {
@@ -383,6 +913,36 @@ pub fn test_map_info() {
     assert_eq!(map_info.ifindex, 0);
 }
 
+/// Check that we can resolve the BTF key and value types of a map.
+#[tag(root)]
+#[test]
+fn test_map_info_btf() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let map = obj.map("start").expect("failed to find map");
+    let info = MapInfo::new(map.as_fd()).unwrap();
+    let id = info.info.id;
+    assert_eq!(info.map_extra(), 0);
+    assert_eq!(info.ifindex(), 0);
+    assert_ne!(info.btf_id(), 0);
+    assert_ne!(info.btf_key_type_id(), 0);
+    assert_ne!(info.btf_value_type_id(), 0);
+
+    let btf = info.btf().unwrap().unwrap();
+    let key = info.key_btf(&btf).unwrap();
+    let value = info.value_btf(&btf).unwrap();
+    assert_eq!(key.type_id(), TypeId::from(info.btf_key_type_id()));
+    assert_eq!(value.type_id(), TypeId::from(info.btf_value_type_id()));
+    // The key is a `u32` and the value a `u64`.
+    assert_eq!(key.skip_mods_and_typedefs().alignment().unwrap().get(), 4);
+    assert_eq!(value.skip_mods_and_typedefs().alignment().unwrap().get(), 8);
+
+    let info = MapInfoIter::default().find(|info| info.id == id).unwrap();
+    let key = info.key_btf(&btf).unwrap();
+    assert_eq!(key.type_id(), TypeId::from(info.btf_key_type_id));
+}
+
 #[tag(root)]
 #[test]
 fn test_object_percpu_lookup() {
@@ -671,6 +1231,37 @@ fn test_object_map_mutation() {
         .is_none());
 }
 
+/// Check that scoped insertions get undone once their guard is dropped.
+#[tag(root)]
+#[test]
+fn test_object_map_insert_scoped() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let start = obj.map("start").expect("failed to find map");
+    let lookup = |key: &[u8]| start.lookup(key, MapFlags::ANY).unwrap();
+
+    {
+        let guard = start
+            .insert_scoped(&[1, 2, 3, 4], &[1, 2, 3, 4, 5, 6, 7, 8])
+            .expect("failed to insert");
+        assert_eq!(guard.key(), &[1, 2, 3, 4]);
+        assert_eq!(lookup(&[1, 2, 3, 4]).unwrap(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Existing elements are not overwritten.
+        assert!(start.insert_scoped(&[1, 2, 3, 4], &[0; 8]).is_err());
+    }
+    assert_eq!(lookup(&[1, 2, 3, 4]), None);
+
+    let guard = start.insert_scoped(&[1, 2, 3, 4], &[0; 8]).unwrap();
+    let () = guard.delete().unwrap();
+    assert_eq!(lookup(&[1, 2, 3, 4]), None);
+
+    let guard = start.insert_scoped(&[1, 2, 3, 4], &[0; 8]).unwrap();
+    let () = guard.keep();
+    assert_eq!(lookup(&[1, 2, 3, 4]).unwrap(), &[0; 8]);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_lookup_flags() {
@@ -821,6 +1412,33 @@ fn test_object_programs_iter_mut() {
     assert!(obj.progs_iter().count() == 3);
 }
 
+/// Check that we can conditionally disable loading of a program.
+#[tag(root)]
+#[test]
+fn test_object_program_load_if() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("runqslower.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__sched_wakeup")
+        .expect("failed to find program");
+    assert!(prog.autoload());
+
+    let loaded = prog.load_if(|| Ok(false)).expect("failed to set autoload");
+    assert!(!loaded);
+    assert!(!prog.autoload());
+
+    let obj = open_obj.load().expect("failed to load object");
+    let prog = obj
+        .prog("handle__sched_wakeup")
+        .expect("failed to find program");
+    assert!(!prog.autoload());
+    let prog = obj
+        .prog("handle__sched_wakeup_new")
+        .expect("failed to find program");
+    assert!(prog.autoload());
+}
+
 #[tag(root)]
 #[test]
 fn test_object_program_pin() {
@@ -882,6 +1500,60 @@ fn test_object_link_pin() {
     assert!(!Path::new(path).exists());
 }
 
+/// Check that we can pin a link in a directory, named after its
+/// program.
+#[tag(root)]
+#[test]
+fn test_object_link_pin_in_dir() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sched_wakeup")
+        .expect("failed to find program");
+    let mut link = prog.attach().expect("failed to attach prog");
+
+    let path = link.pin_in_dir("/sys/fs/bpf").expect("failed to pin link");
+    defer! {
+        let _ = fs::remove_file(&path);
+    }
+    // The kernel truncates program names to 15 characters.
+    assert_eq!(path, Path::new("/sys/fs/bpf/handle__sched_w"));
+    assert_eq!(link.pin_path().as_deref(), Some(path.as_path()));
+    assert!(path.exists());
+
+    link.unpin_from_dir("/sys/fs/bpf")
+        .expect("failed to unpin link");
+    assert!(!path.exists());
+}
+
+/// Check that a `Link` converted into an `OwnedFd` keeps the program
+/// attached.
+#[tag(root)]
+#[test]
+fn test_object_link_into_fd() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sched_wakeup")
+        .expect("failed to find program");
+    let link = prog.attach().expect("failed to attach prog");
+    let fd = link.into_fd().expect("failed to convert link into fd");
+
+    let path = "/sys/fs/bpf/mylink_into_fd";
+    defer! {
+        let _ = fs::remove_file(path);
+    }
+
+    // The link should still be alive and referenced by our fd, meaning
+    // that we can pin it.
+    let path_c = CString::new(path).unwrap();
+    let ret = unsafe { libbpf_sys::bpf_obj_pin(fd.as_raw_fd(), path_c.as_ptr()) };
+    assert_eq!(ret, 0);
+    assert!(Path::new(path).exists());
+}
+
 #[tag(root)]
 #[test]
 fn test_object_reuse_pined_map() {
@@ -933,16 +1605,408 @@ fn test_object_reuse_pined_map() {
     assert!(!Path::new(path).exists());
 }
 
+/// Check that we report which program failed to load as part of an
+/// object.
 #[tag(root)]
 #[test]
-fn test_object_ringbuf_raw() {
+fn test_object_load_program_error() {
     bump_rlimit_mlock();
 
-    let mut obj = get_test_object("ringbuf.bpf.o");
-    let prog = obj
-        .prog_mut("handle__sys_enter_getpid")
+    let obj_path = get_test_object_path("ksyscall.bpf.o");
+    let mut open_obj = ObjectBuilder::default()
+        .open_file(obj_path)
+        .expect("failed to open object");
+    let prog = open_obj
+        .prog_mut("handle__ksyscall")
         .expect("failed to find program");
-    let _link = prog.attach().expect("failed to attach prog");
+    // A lone `exit` instruction without `r0` being set is rejected by
+    // the verifier.
+    let insn = libbpf_sys::bpf_insn {
+        code: (libbpf_sys::BPF_JMP | libbpf_sys::BPF_EXIT) as _,
+        ..Default::default()
+    };
+    let () = prog.set_insns(&[insn]).unwrap();
+
+    let err = open_obj.load().unwrap_err();
+    let prog_err = err
+        .downcast_ref::<ProgramLoadError>()
+        .expect("no program load error reported");
+    assert_eq!(prog_err.name(), "handle__ksyscall");
+    assert_eq!(prog_err.section(), "ksyscall/kill");
+    assert_eq!(err.kind(), ErrorKind::VerifierRejected);
+    assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+    assert!(prog_err.log().contains("processed"), "{}", prog_err.log());
+    assert!(
+        err.to_string().contains("failed to load BPF object"),
+        "{err}"
+    );
+}
+
+/// Check that we can attach a program to a cgroup via
+/// `BPF_PROG_ATTACH`, query it, and detach it again.
+#[tag(root)]
+#[test]
+fn test_object_prog_attach_detach() {
+    bump_rlimit_mlock();
+
+    // Use a program consisting of a `r0 = 1; exit` sequence, letting all
+    // packets pass.
+    let obj = get_trivial_test_object(
+        ProgramType::CgroupSkb,
+        Some(ProgramAttachType::CgroupInetIngress),
+        1,
+    );
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+    let cgroup = File::open("/sys/fs/cgroup").unwrap();
+
+    let before = Program::prog_query(cgroup.as_fd(), ProgramAttachType::CgroupInetIngress)
+        .expect("failed to query programs");
+    let () = prog
+        .prog_attach(
+            cgroup.as_fd(),
+            ProgramAttachType::CgroupInetIngress,
+            libbpf_sys::BPF_F_ALLOW_MULTI,
+        )
+        .expect("failed to attach program");
+    defer! {
+        let _ = prog.prog_detach(cgroup.as_fd(), ProgramAttachType::CgroupInetIngress);
+    }
+
+    let attached = Program::prog_query(cgroup.as_fd(), ProgramAttachType::CgroupInetIngress)
+        .expect("failed to query programs");
+    assert_eq!(attached.len(), before.len() + 1);
+
+    let () = prog
+        .prog_detach(cgroup.as_fd(), ProgramAttachType::CgroupInetIngress)
+        .expect("failed to detach program");
+    let after = Program::prog_query(cgroup.as_fd(), ProgramAttachType::CgroupInetIngress)
+        .expect("failed to query programs");
+    assert_eq!(after, before);
+}
+
+/// Check that we can attach a flow dissector to a network namespace and
+/// query it.
+#[tag(root)]
+#[test]
+fn test_object_flow_dissector() {
+    bump_rlimit_mlock();
+
+    // Use a program consisting of a `r0 = 0 (BPF_OK); exit` sequence.
+    let mut obj = get_trivial_test_object(
+        ProgramType::FlowDissector,
+        Some(ProgramAttachType::FlowDissector),
+        0,
+    );
+    let prog = obj.prog_mut("handle_tc").expect("failed to find program");
+    let prog_id = Program::get_id_by_fd(prog.as_fd()).unwrap();
+    let netns = File::open("/proc/self/ns/net").unwrap();
+
+    assert_eq!(Program::query_flow_dissector(netns.as_fd()).unwrap(), None);
+    let link = prog
+        .attach_flow_dissector(netns.as_fd())
+        .expect("failed to attach flow dissector");
+    assert_eq!(
+        Program::query_flow_dissector(netns.as_fd()).unwrap(),
+        Some(prog_id)
+    );
+
+    let () = link.detach().expect("failed to detach flow dissector");
+    assert_eq!(Program::query_flow_dissector(netns.as_fd()).unwrap(), None);
+
+    // Programs of other types are rejected.
+    let mut obj = get_test_object("runqslower.bpf.o");
+    let prog = obj.prog_mut("handle__sched_wakeup").unwrap();
+    let err = prog.attach_flow_dissector(netns.as_fd()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Attach `prog` to the tcx ingress hook of the loopback device with the
+/// given options.
+fn attach_tcx(prog: &Program, opts: &libbpf_sys::bpf_tcx_opts) -> Link {
+    let ptr =
+        unsafe { libbpf_sys::bpf_program__attach_tcx(prog.as_libbpf_object().as_ptr(), 1, opts) };
+    let ptr = NonNull::new(ptr).expect("failed to attach tcx program");
+    unsafe { Link::from_ptr(ptr) }
+}
+
+/// Check that we can query the order of programs attached to a
+/// multi-attach point.
+#[tag(root)]
+#[test]
+fn test_object_query_chain() {
+    bump_rlimit_mlock();
+
+    let load = || {
+        let mut open_obj = open_test_object("tc-unit.bpf.o");
+        let prog = open_obj
+            .prog_mut("handle_tc")
+            .expect("failed to find program");
+        let () = prog.set_expected_attach_type(ProgramAttachType::TcxIngress);
+        open_obj.load().expect("failed to load object")
+    };
+    let obj1 = load();
+    let prog1 = obj1.prog("handle_tc").expect("failed to find program");
+    let id1 = Program::get_id_by_fd(prog1.as_fd()).unwrap();
+    let obj2 = load();
+    let prog2 = obj2.prog("handle_tc").expect("failed to find program");
+    let id2 = Program::get_id_by_fd(prog2.as_fd()).unwrap();
+
+    let target = ChainTarget::Ifindex(1);
+    let before = Program::query_chain(target, ProgramAttachType::TcxIngress).unwrap();
+
+    let opts = libbpf_sys::bpf_tcx_opts {
+        sz: size_of::<libbpf_sys::bpf_tcx_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let _link1 = attach_tcx(prog1, &opts);
+    // Attach the second program to run right before the first one.
+    let opts = libbpf_sys::bpf_tcx_opts {
+        sz: size_of::<libbpf_sys::bpf_tcx_opts>() as libbpf_sys::size_t,
+        flags: libbpf_sys::BPF_F_BEFORE,
+        relative_fd: prog1.as_fd().as_raw_fd() as u32,
+        ..Default::default()
+    };
+    let _link2 = attach_tcx(prog2, &opts);
+
+    let chain = Program::query_chain(target, ProgramAttachType::TcxIngress).unwrap();
+    assert!(chain.revision > before.revision);
+    let pos = |id| {
+        chain
+            .entries
+            .iter()
+            .position(|entry| entry.prog_id == id)
+            .expect("failed to find program")
+    };
+    let (pos1, pos2) = (pos(id1), pos(id2));
+    assert_eq!(pos2 + 1, pos1);
+    assert!(chain.entries[pos1].link_id.is_some());
+    assert!(chain.entries[pos2].link_id.is_some());
+}
+
+/// Check that `Object::detach_all` detaches the links of the object's
+/// programs.
+#[tag(root)]
+#[test]
+fn test_object_detach_all() {
+    bump_rlimit_mlock();
+
+    // Use a program consisting of a `r0 = 1 (SK_PASS); exit` sequence.
+    let mut obj =
+        get_trivial_test_object(ProgramType::SkLookup, Some(ProgramAttachType::SkLookup), 1);
+    assert_eq!(obj.detach_all().unwrap(), 0);
+
+    let netns = File::open("/proc/self/ns/net").unwrap();
+    let prog = obj.prog_mut("handle_tc").expect("failed to find program");
+    let link = prog
+        .attach_netns(netns.as_raw_fd())
+        .expect("failed to attach program");
+    let info = LinkInfoIter::default()
+        .find(|info| info.prog_id == Program::get_id_by_fd(prog.as_fd()).unwrap())
+        .expect("failed to find link");
+    assert!(matches!(info.info, LinkTypeInfo::NetNs(NetNsLinkInfo { ino, .. }) if ino != 0));
+
+    assert_eq!(obj.detach_all().unwrap(), 1);
+    let info = LinkInfoIter::default()
+        .find(|link| link.id == info.id)
+        .expect("failed to find link");
+    assert!(matches!(
+        info.info,
+        LinkTypeInfo::NetNs(NetNsLinkInfo { ino: 0, .. })
+    ));
+    // Detaching the now defunct link is fine.
+    let () = link.detach().unwrap();
+}
+
+/// Check that `Object::shutdown` drains ring buffers.
+#[tag(root)]
+#[test]
+fn test_object_shutdown() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("ringbuf.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    // Turn the program into a socket filter, so that we can trigger it
+    // via `test_run`.
+    let () = prog.set_prog_type(ProgramType::SocketFilter);
+    let mut obj = open_obj.load().expect("failed to load object");
+
+    let samples = RefCell::new(Vec::new());
+    let callback = |data: &[u8]| {
+        let () = samples.borrow_mut().push(data.to_vec());
+        0
+    };
+    let mut builder = RingBufferBuilder::new();
+    let _builder = builder.add(obj.map("ringbuf1").unwrap(), callback).unwrap();
+    let ringbuf1 = builder.build().unwrap();
+    let mut builder = RingBufferBuilder::new();
+    let _builder = builder.add(obj.map("ringbuf2").unwrap(), callback).unwrap();
+    let ringbuf2 = builder.build().unwrap();
+
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let data = [0; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).expect("failed to run program");
+    assert!(samples.borrow().is_empty());
+
+    let opts = ShutdownOpts {
+        ring_buffers: vec![&ringbuf1, &ringbuf2],
+        ..Default::default()
+    };
+    let () = obj.shutdown(Duration::from_secs(1), opts).unwrap();
+    assert_eq!(
+        *samples.borrow(),
+        vec![1i32.to_ne_bytes().to_vec(), 2i32.to_ne_bytes().to_vec()]
+    );
+}
+
+/// Check that we can generate a light skeleton for an object and load
+/// it.
+#[tag(root)]
+#[test]
+fn test_object_light_skel() {
+    bump_rlimit_mlock();
+
+    let open_obj = open_test_object("ringbuf.bpf.o");
+    let skel = open_obj.gen_loader().expect("failed to generate loader");
+    assert_eq!(skel.map_names(), ["ringbuf1", "ringbuf2"]);
+    assert_eq!(skel.prog_names(), ["handle__sys_enter_getpid"]);
+
+    // Round trip the loader through its raw representation, as would be
+    // done when shipping it separately.
+    let skel = LightSkel::new(
+        &skel.insns_bytes(),
+        skel.data(),
+        skel.map_names().to_vec(),
+        skel.prog_names().to_vec(),
+    )
+    .unwrap();
+
+    let loaded = skel.load().expect("failed to load light skeleton");
+    let map = loaded.map("ringbuf2").unwrap();
+    let info = MapInfo::new(map).unwrap();
+    assert_eq!(info.map_type(), MapType::RingBuf);
+    assert_eq!(info.name().unwrap(), "ringbuf2");
+    assert!(loaded.map("ringbuf3").is_none());
+
+    let prog = loaded.prog("handle__sys_enter_getpid").unwrap();
+    let _id = Program::get_id_by_fd(prog).unwrap();
+
+    let (maps, progs) = loaded.into_fds();
+    assert_eq!(maps.len(), 2);
+    assert_eq!(progs.len(), 1);
+}
+
+/// Check that we can retrieve the function and line information of a
+/// loaded program.
+#[tag(root)]
+#[test]
+fn test_object_func_line_infos() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("tc-unit.bpf.o");
+    let prog = obj.prog("handle_tc").expect("failed to find program");
+    let btf = obj.btf().unwrap().expect("object has no BTF");
+
+    let func_infos = prog.func_infos().unwrap();
+    assert_eq!(func_infos.len(), 1);
+    assert_eq!(func_infos[0].insn_off, 0);
+    assert_eq!(func_infos[0].name(&btf), Some(OsStr::new("handle_tc")));
+
+    let line_infos = prog.line_infos().unwrap();
+    assert!(!line_infos.is_empty());
+    assert!(line_infos
+        .windows(2)
+        .all(|infos| infos[0].insn_off <= infos[1].insn_off));
+    let file_name = line_infos[0].file_name(&btf).unwrap();
+    assert!(file_name.to_string_lossy().ends_with("tc-unit.bpf.c"));
+
+    let last = line_infos.last().unwrap();
+    let info = LineInfo::find(&line_infos, last.insn_off + 1).unwrap();
+    assert_eq!(info.insn_off, last.insn_off);
+    let info = LineInfo::find(&line_infos, 0).unwrap();
+    assert_eq!(info.line_num, line_infos[0].line_num);
+}
+
+/// Check that we can read stack traces from a stack trace map.
+#[tag(root)]
+#[test]
+fn test_stacktrace_read_stack() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let value_size = 127 * size_of::<u64>() as u32;
+    let map = MapHandle::create(
+        MapType::StackTrace,
+        Some("stacks"),
+        4,
+        value_size,
+        16,
+        &opts,
+    )
+    .expect("failed to create stack trace map");
+    assert_eq!(stacktrace::read_stack(&map, 0).unwrap(), None);
+
+    let map = MapHandle::create(MapType::Hash, Some("hash"), 4, 8, 16, &opts)
+        .expect("failed to create hash map");
+    let err = stacktrace::read_stack(&map, 0).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that we can reuse a pre-created map via its file descriptor, as
+/// long as it is compatible.
+#[tag(root)]
+#[test]
+fn test_object_reuse_map_fd() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let compat = MapHandle::create(MapType::Hash, Some("start"), 4, 8, 10240, &opts).unwrap();
+    let incompat = MapHandle::create(MapType::Hash, Some("start"), 4, 4, 10240, &opts).unwrap();
+    let key = 1u32.to_ne_bytes();
+    let val = 42u64.to_ne_bytes();
+    compat.update(&key, &val, MapFlags::ANY).unwrap();
+
+    let obj_path = get_test_object_path("runqslower.bpf.o");
+    let mut open_obj = ObjectBuilder::default()
+        .open_file(obj_path)
+        .expect("failed to open object");
+    let start = open_obj.map_mut("start").expect("failed to find map");
+    let err = start.reuse_fd(incompat.as_fd()).unwrap_err();
+    assert!(err.to_string().contains("value size"), "{err}");
+    start.reuse_fd(compat.as_fd()).expect("failed to reuse map");
+
+    let obj = open_obj.load().expect("failed to load object");
+    let start = obj.map("start").expect("failed to find map");
+    let found = start
+        .lookup(&key, MapFlags::ANY)
+        .expect("failed to read map")
+        .expect("failed to find key");
+    assert_eq!(found, val);
+}
+
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_raw() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
 
     static mut V1: i32 = 0;
     static mut V2: i32 = 0;
@@ -1161,6 +2225,220 @@ fn test_object_ringbuf() {
     unsafe { assert_eq!(V2, 2) };
 }
 
+/// Check that a `RingBufferDispatcher` hands samples to the handlers
+/// of the rings they originate from.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_dispatcher() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let (sender1, receiver1) = channel();
+    let sender1 = Mutex::new(sender1);
+    let (sender2, receiver2) = channel();
+    let sender2 = Mutex::new(sender2);
+
+    let mut builder = RingBufferDispatcherBuilder::new();
+    let map1 = obj.map("ringbuf1").expect("Failed to get ringbuf1 map");
+    builder
+        .add(map1, move |data: &[u8]| {
+            let () = sender1.lock().unwrap().send(data.to_vec()).unwrap();
+        })
+        .expect("Failed to add ringbuf");
+    let map2 = obj.map("ringbuf2").expect("Failed to get ringbuf2 map");
+    builder
+        .add(map2, move |data: &[u8]| {
+            let () = sender2.lock().unwrap().send(data.to_vec()).unwrap();
+        })
+        .expect("Failed to add ringbuf");
+
+    let opts = RingBufferDispatcherOpts {
+        workers: 2,
+        ..Default::default()
+    };
+    let dispatcher = builder.build(opts).expect("Failed to build");
+
+    // Call getpid to ensure the BPF program runs
+    unsafe { libc::getpid() };
+
+    // Shutting down consumes and handles all outstanding samples.
+    let () = dispatcher.shutdown().unwrap();
+    assert_eq!(receiver1.try_recv().unwrap(), 1i32.to_ne_bytes());
+    assert_eq!(receiver2.try_recv().unwrap(), 2i32.to_ne_bytes());
+}
+
+/// Check that samples can be retained past the consumption of
+/// subsequent ones when using a `RingBufferReader`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_reader() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let map = obj.map("ringbuf1").expect("Failed to get ringbuf1 map");
+    let reader = RingBufferReader::new(map).expect("failed to create reader");
+
+    // Call getpid twice to ensure the BPF program runs
+    unsafe { libc::getpid() };
+    unsafe { libc::getpid() };
+
+    let mut samples = reader.consume_raw_iter().collect::<Vec<_>>();
+    assert_eq!(samples.len(), 2);
+
+    // Hand off the first sample to a different thread without copying
+    // it.
+    let first = samples.remove(0);
+    let () = thread::scope(|scope| {
+        let handle = scope.spawn(move || {
+            assert_eq!(&*first, 1i32.to_ne_bytes());
+        });
+        handle.join().unwrap()
+    });
+    assert_eq!(&*samples[0], 1i32.to_ne_bytes());
+    assert!(reader.consume_raw_iter().next().is_none());
+}
+
+/// Check that we can wait for ring buffers to become readable, both
+/// individually and through a `Poller`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_poll() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("ringbuf.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    // Turn the program into a socket filter, so that we can trigger it
+    // via `test_run`.
+    let () = prog.set_prog_type(ProgramType::SocketFilter);
+    let mut obj = open_obj.load().expect("failed to load object");
+
+    let ringbuf1 = obj.map("ringbuf1").expect("failed to find map");
+    let ringbuf2 = obj.map("ringbuf2").expect("failed to find map");
+    let reader = RingBufferReader::new(ringbuf2).expect("failed to create reader");
+    let poller = Poller::new().unwrap();
+    let () = poller.add(ringbuf1, 1).unwrap();
+    let () = poller.add(&reader, 2).unwrap();
+
+    assert!(!ringbuf1.poll_readable(Duration::ZERO).unwrap());
+    assert!(!reader.poll_readable(Duration::ZERO).unwrap());
+    assert_eq!(poller.wait(Duration::ZERO).unwrap(), Vec::<u64>::new());
+
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let data = [0; 64];
+    let input = ProgramInput {
+        data_in: Some(&data),
+        ..Default::default()
+    };
+    let _output = prog.test_run(input).expect("failed to run program");
+
+    let ringbuf1 = obj.map("ringbuf1").expect("failed to find map");
+    assert!(ringbuf1.poll_readable(Duration::from_secs(1)).unwrap());
+    assert!(reader.poll_readable(Duration::from_secs(1)).unwrap());
+    let mut tokens = poller.wait(Duration::from_secs(1)).unwrap();
+    let () = tokens.sort();
+    assert_eq!(tokens, vec![1, 2]);
+
+    // Once consumed, the reader is no longer reported.
+    assert_eq!(reader.consume_raw_iter().count(), 1);
+    let () = poller.remove(ringbuf1).unwrap();
+    assert_eq!(poller.wait(Duration::ZERO).unwrap(), Vec::<u64>::new());
+}
+
+/// Check that a single callback can demultiplex samples from multiple
+/// rings.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_with_id() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let samples = RefCell::new(Vec::new());
+    let callback = |ctx: &RingBufferSampleCtx, data: &[u8]| -> i32 {
+        let mut value: i32 = 0;
+        plain::copy_from_bytes(&mut value, data).expect("Wrong size");
+        assert_eq!(ctx.len, data.len());
+        samples.borrow_mut().push((ctx.id, ctx.ring_idx, value));
+        0
+    };
+
+    let mut builder = libbpf_rs::RingBufferBuilder::new();
+    let map1 = obj.map("ringbuf1").expect("Failed to get ringbuf1 map");
+    builder
+        .add_with_id(map1, 10, callback)
+        .expect("Failed to add ringbuf");
+    let map2 = obj.map("ringbuf2").expect("Failed to get ringbuf2 map");
+    builder
+        .add_with_id(map2, 20, callback)
+        .expect("Failed to add ringbuf");
+    let mgr = builder.build().expect("Failed to build");
+
+    // Call getpid to ensure the BPF program runs
+    unsafe { libc::getpid() };
+
+    mgr.consume().expect("Failed to consume ringbuf");
+    drop(mgr);
+
+    let mut samples = samples.into_inner();
+    let () = samples.sort();
+    assert_eq!(samples, vec![(10, 0, 1), (20, 1, 2)]);
+}
+
+/// Check that we can inspect the fill level of the individual rings of
+/// a `RingBuffer`.
+#[tag(root)]
+#[test]
+fn test_object_ringbuf_ring() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
+
+    let mut builder = libbpf_rs::RingBufferBuilder::new();
+    let map1 = obj.map("ringbuf1").expect("Failed to get ringbuf1 map");
+    builder.add(map1, |_| 0).expect("Failed to add ringbuf");
+    let map2 = obj.map("ringbuf2").expect("Failed to get ringbuf2 map");
+    builder.add(map2, |_| 0).expect("Failed to add ringbuf");
+    let mgr = builder.build().expect("Failed to build");
+
+    let ring = mgr.ring(0).expect("failed to retrieve ring");
+    assert_eq!(ring.avail_data_size(), 0);
+    assert_eq!(ring.size(), map1.info().unwrap().info.max_entries as usize);
+    assert!(mgr.ring(1).is_some());
+    assert!(mgr.ring(2).is_none());
+
+    // Call getpid to ensure the BPF program runs
+    unsafe { libc::getpid() };
+
+    assert_ne!(ring.avail_data_size(), 0);
+    assert!(ring.producer_pos() > ring.consumer_pos());
+
+    mgr.consume().expect("Failed to consume ringbuf");
+    assert_eq!(ring.avail_data_size(), 0);
+    assert_eq!(ring.producer_pos(), ring.consumer_pos());
+}
+
 #[tag(root)]
 #[test]
 fn test_object_ringbuf_closure() {
@@ -1282,14 +2560,69 @@ fn test_object_ringbuf_with_closed_map() {
 
 #[tag(root)]
 #[test]
-fn test_object_user_ringbuf() {
+fn test_object_user_ringbuf() {
+    #[repr(C)]
+    struct MyStruct {
+        key: u32,
+        value: u32,
+    }
+
+    unsafe impl Plain for MyStruct {}
+
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("user_ringbuf.bpf.o");
+    let prog = obj
+        .prog_mut("handle__sys_enter_getpid")
+        .expect("failed to find program");
+    let _link = prog.attach().expect("failed to attach prog");
+    let urb_map = obj
+        .map("user_ringbuf")
+        .expect("failed to find user ringbuf map");
+    let user_ringbuf = UserRingBuffer::new(urb_map).expect("failed to create user ringbuf");
+    let mut urb_sample = user_ringbuf
+        .reserve(size_of::<MyStruct>())
+        .expect("failed to reserve space");
+    let bytes = urb_sample.as_mut();
+    let my_struct = plain::from_mut_bytes::<MyStruct>(bytes).expect("failed to convert bytes");
+    my_struct.key = 42;
+    my_struct.value = 1337;
+    user_ringbuf
+        .submit(urb_sample)
+        .expect("failed to submit sample");
+
+    // Trigger BPF program.
+    let _pid = unsafe { libc::getpid() };
+
+    // At this point, the BPF program should have run and consumed the sample in
+    // the user ring buffer, and stored the key/value in the samples map.
+    let samples_map = obj.map("samples").expect("failed to find map");
+    let key: u32 = 42;
+    let value: u32 = 1337;
+    let res = samples_map
+        .lookup(&key.to_ne_bytes(), MapFlags::ANY)
+        .expect("failed to lookup")
+        .expect("failed to find value for key");
+
+    // The value in the samples map should be the same as the value we submitted
+    assert_eq!(res.len(), size_of::<u32>());
+    let mut array = [0; size_of::<u32>()];
+    array.copy_from_slice(&res[..]);
+    assert_eq!(u32::from_ne_bytes(array), value);
+}
+
+/// Check that we can submit typed samples to a user ring buffer.
+#[tag(root)]
+#[test]
+fn test_object_user_ringbuf_typed() {
     #[repr(C)]
+    #[derive(Clone, Copy)]
     struct MyStruct {
         key: u32,
         value: u32,
     }
 
-    unsafe impl Plain for MyStruct {}
+    unsafe impl Pod for MyStruct {}
 
     bump_rlimit_mlock();
 
@@ -1303,12 +2636,11 @@ fn test_object_user_ringbuf() {
         .expect("failed to find user ringbuf map");
     let user_ringbuf = UserRingBuffer::new(urb_map).expect("failed to create user ringbuf");
     let mut urb_sample = user_ringbuf
-        .reserve(size_of::<MyStruct>())
+        .reserve_typed::<MyStruct>()
         .expect("failed to reserve space");
-    let bytes = urb_sample.as_mut();
-    let my_struct = plain::from_mut_bytes::<MyStruct>(bytes).expect("failed to convert bytes");
-    my_struct.key = 42;
-    my_struct.value = 1337;
+    assert_eq!(urb_sample.key, 0);
+    urb_sample.key = 42;
+    urb_sample.value = 1337;
     user_ringbuf
         .submit(urb_sample)
         .expect("failed to submit sample");
@@ -1316,21 +2648,19 @@ fn test_object_user_ringbuf() {
     // Trigger BPF program.
     let _pid = unsafe { libc::getpid() };
 
-    // At this point, the BPF program should have run and consumed the sample in
-    // the user ring buffer, and stored the key/value in the samples map.
     let samples_map = obj.map("samples").expect("failed to find map");
-    let key: u32 = 42;
-    let value: u32 = 1337;
     let res = samples_map
-        .lookup(&key.to_ne_bytes(), MapFlags::ANY)
+        .lookup(&42u32.to_ne_bytes(), MapFlags::ANY)
         .expect("failed to lookup")
         .expect("failed to find value for key");
+    assert_eq!(res, 1337u32.to_ne_bytes());
 
-    // The value in the samples map should be the same as the value we submitted
-    assert_eq!(res.len(), size_of::<u32>());
-    let mut array = [0; size_of::<u32>()];
-    array.copy_from_slice(&res[..]);
-    assert_eq!(u32::from_ne_bytes(array), value);
+    // The BPF program drained the ring buffer, so a blocking
+    // reservation should succeed right away.
+    let urb_sample = user_ringbuf
+        .reserve_blocking(size_of::<MyStruct>(), Duration::from_secs(1))
+        .expect("failed to reserve space");
+    drop(urb_sample);
 }
 
 #[tag(root)]
@@ -1415,6 +2745,41 @@ fn test_object_task_iter() {
     assert!(items.iter().any(|&item| item.pid == 1));
 }
 
+/// Check that we can limit a task iterator to the tasks of a single
+/// process.
+#[tag(root)]
+#[test]
+fn test_object_task_iter_with_opts() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("taskiter.bpf.o");
+    let prog = obj.prog_mut("dump_pid").expect("Failed to find program");
+    let pid = process::id();
+    let link = prog
+        .attach_iter_with_opts(IterOpts::Pid(pid))
+        .expect("Failed to attach prog");
+    let mut iter = Iter::new(&link).expect("Failed to create iterator");
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IndexPidPair {
+        i: u32,
+        pid: i32,
+    }
+
+    unsafe impl Plain for IndexPidPair {}
+
+    let mut buf = Vec::new();
+    let _bytes_read = iter
+        .read_to_end(&mut buf)
+        .expect("Failed to read from iterator");
+
+    let items: &[IndexPidPair] =
+        plain::slice_from_bytes(buf.as_slice()).expect("Input slice cannot satisfy length");
+    assert!(!items.is_empty());
+    assert!(items.iter().all(|item| item.pid == pid as i32));
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_iter() {
@@ -1423,7 +2788,7 @@ fn test_object_map_iter() {
     // Create a map for iteration test.
     let opts = libbpf_sys::bpf_map_create_opts {
         sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
-        map_flags: libbpf_sys::BPF_F_NO_PREALLOC,
+        map_flags: MapCreateFlags::NO_PREALLOC.bits(),
         ..Default::default()
     };
     let map = MapHandle::create(
@@ -1474,7 +2839,7 @@ fn test_object_map_create_and_pin() {
 
     let opts = libbpf_sys::bpf_map_create_opts {
         sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
-        map_flags: libbpf_sys::BPF_F_NO_PREALLOC,
+        map_flags: MapCreateFlags::NO_PREALLOC.bits(),
         ..Default::default()
     };
 
@@ -1513,6 +2878,73 @@ fn test_object_map_create_and_pin() {
     assert!(!Path::new(path).exists());
 }
 
+/// Check that we can pin a map in a directory, named after the map,
+/// and that we get a descriptive error when pinning outside of bpffs.
+#[tag(root)]
+#[test]
+fn test_object_map_pin_in_dir() {
+    bump_rlimit_mlock();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        ..Default::default()
+    };
+    let mut map = MapHandle::create(MapType::Array, Some("mymap_pin_in_dir"), 4, 8, 1, &opts)
+        .expect("failed to create map");
+
+    let path = map.pin_in_dir("/sys/fs/bpf").expect("failed to pin map");
+    defer! {
+        let _ = fs::remove_file(&path);
+    }
+    assert_eq!(path, Path::new("/sys/fs/bpf/mymap_pin_in_dir"));
+    assert!(path.exists());
+    map.unpin_from_dir("/sys/fs/bpf")
+        .expect("failed to unpin map");
+    assert!(!path.exists());
+
+    let dir = tempdir().unwrap();
+    let err = map.pin_in_dir(dir.path()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("bpffs"), "{err}");
+
+    let mut map = MapHandle::create(MapType::Array, None::<&str>, 4, 8, 1, &opts)
+        .expect("failed to create map");
+    let err = map.pin_in_dir("/sys/fs/bpf").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+/// Check that we can create a map described by BTF constructed at
+/// runtime.
+#[tag(root)]
+#[test]
+fn test_object_map_create_with_btf() {
+    bump_rlimit_mlock();
+
+    let mut builder = BtfBuilder::new().unwrap();
+    let key = builder.add_int("u32", 4, IntEncoding::None).unwrap();
+    let value = builder.add_struct(Some("value"), 8).unwrap();
+    let () = builder.add_field(Some("a"), key, 0, 0).unwrap();
+    let () = builder.add_field(Some("b"), key, 32, 0).unwrap();
+    let mut btf = builder.build();
+    let () = btf.load_into_kernel().unwrap();
+    let btf_fd = btf.fd().unwrap();
+
+    let opts = libbpf_sys::bpf_map_create_opts {
+        sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
+        btf_fd: btf_fd.as_raw_fd() as u32,
+        btf_key_type_id: key.into(),
+        btf_value_type_id: value.into(),
+        ..Default::default()
+    };
+
+    let map = MapHandle::create(MapType::Hash, Some("btf_map"), 4, 8, 8, &opts)
+        .expect("failed to create map");
+    let info = map.info().unwrap();
+    assert_ne!(info.info.btf_id, 0);
+    assert_eq!(info.info.btf_key_type_id, u32::from(key));
+    assert_eq!(info.info.btf_value_type_id, u32::from(value));
+}
+
 #[tag(root)]
 #[test]
 fn test_object_map_create_without_name() {
@@ -1521,7 +2953,7 @@ fn test_object_map_create_without_name() {
     #[allow(clippy::needless_update)]
     let opts = libbpf_sys::bpf_map_create_opts {
         sz: size_of::<libbpf_sys::bpf_map_create_opts>() as libbpf_sys::size_t,
-        map_flags: libbpf_sys::BPF_F_NO_PREALLOC,
+        map_flags: MapCreateFlags::NO_PREALLOC.bits(),
         btf_fd: 0,
         btf_key_type_id: 0,
         btf_value_type_id: 0,
@@ -1578,6 +3010,56 @@ fn test_object_map_handle_clone() {
     );
 }
 
+/// Check that we can transfer ownership of a `MapHandle`'s file descriptor
+/// out of the handle.
+#[tag(root)]
+#[test]
+fn test_object_map_handle_into_fd() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let map = obj.map("events").expect("failed to find map");
+    let handle = MapHandle::try_clone(map).expect("failed to create handle from Map");
+    let id = handle.info().expect("failed to get map info").info.id;
+
+    let fd = OwnedFd::try_from(handle).expect("failed to convert handle into fd");
+    let info = MapInfo::new(fd.as_fd()).expect("failed to get map info from fd");
+    assert_eq!(info.info.id, id);
+}
+
+/// Check that a `MapHandle`'s file descriptor can be relinquished without
+/// closing it and that a program's file descriptor can outlive its object.
+#[tag(root)]
+#[test]
+fn test_object_map_handle_into_raw_fd() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("runqslower.bpf.o");
+    let map = obj.map("events").expect("failed to find map");
+    let handle = MapHandle::try_clone(map).expect("failed to create handle from Map");
+    let id = handle.info().expect("failed to get map info").info.id;
+
+    let fd = OwnedFd::try_from(handle)
+        .expect("failed to convert handle into fd")
+        .into_raw_fd();
+    // SAFETY: `into_raw_fd` relinquished ownership of the open file
+    //         descriptor.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let info = MapInfo::new(fd.as_fd()).expect("failed to get map info from fd");
+    assert_eq!(info.info.id, id);
+
+    let prog = obj
+        .prog("handle__sched_wakeup")
+        .expect("failed to find program");
+    let prog_id = Program::get_id_by_fd(prog.as_fd()).expect("failed to get program id");
+    let prog_fd = prog.try_clone_fd().expect("failed to duplicate program fd");
+    drop(obj);
+
+    // The program should stay loaded for as long as our duplicate is open.
+    let id = Program::get_id_by_fd(prog_fd.as_fd()).expect("failed to get program id");
+    assert_eq!(id, prog_id);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_usdt() {
@@ -1701,6 +3183,37 @@ fn test_object_open_program_insns() {
     assert!(!insns.is_empty());
 }
 
+/// Check that we can replace the instructions of a program before
+/// load.
+#[tag(root)]
+#[test]
+fn test_object_open_program_set_insns() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("usdt.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__usdt")
+        .expect("Failed to find program");
+
+    let mut insns = prog.insns().to_vec();
+    let cnt = insns.len();
+    assert!(cnt > 1);
+
+    // Truncate the program to a single `r0 = 0; exit` sequence.
+    insns.truncate(2);
+    insns[0] = libbpf_sys::bpf_insn {
+        code: (libbpf_sys::BPF_ALU64 | libbpf_sys::BPF_MOV | libbpf_sys::BPF_K) as _,
+        ..Default::default()
+    };
+    insns[1] = libbpf_sys::bpf_insn {
+        code: (libbpf_sys::BPF_JMP | libbpf_sys::BPF_EXIT) as _,
+        ..Default::default()
+    };
+    let () = prog.set_insns(&insns).unwrap();
+    assert_eq!(prog.insn_cnt(), 2);
+    assert_eq!(prog.insns().len(), 2);
+}
+
 #[tag(root)]
 #[test]
 fn test_object_program_insns() {
@@ -1805,6 +3318,40 @@ fn test_object_uprobe_with_opts() {
     assert_eq!(result, 1);
 }
 
+/// Check that we can attach a BPF program to a uprobe at a virtual
+/// address.
+#[tag(root)]
+#[test]
+fn test_object_uprobe_addr() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("uprobe.bpf.o");
+    let prog = obj
+        .prog_mut("handle__uprobe")
+        .expect("Failed to find program");
+
+    // Addresses in anonymous mappings can't be probed.
+    let value = Box::new(0u64);
+    let addr = &*value as *const u64 as usize;
+    let err = prog
+        .attach_uprobe_addr(0, addr, UprobeOpts::default())
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let addr = uprobe_target as *const () as usize;
+    let _link = prog
+        .attach_uprobe_addr(0, addr, UprobeOpts::default())
+        .expect("Failed to attach prog");
+
+    let map = obj.map("ringbuf").expect("Failed to get ringbuf map");
+    let action = || {
+        let _ = uprobe_target();
+    };
+    let result = with_ringbuffer(map, action);
+
+    assert_eq!(result, 1);
+}
+
 /// Check that we can attach a BPF program to a uprobe and access the cookie
 /// provided during attach.
 #[tag(root)]
@@ -1866,6 +3413,29 @@ fn test_object_link_files() {
     test(vec![obj_path1, obj_path2]);
 }
 
+/// Check that we can link in-memory object files into an in-memory
+/// output.
+#[test]
+fn test_object_link_in_memory() {
+    let obj1 = fs::read(get_test_object_path("usdt.bpf.o")).unwrap();
+    let obj2 = fs::read(get_test_object_path("ringbuf.bpf.o")).unwrap();
+
+    let mut linker = Linker::in_memory(&LinkerOptions::default()).unwrap();
+    let () = linker.add_buf(&obj1, "usdt.bpf.o").unwrap();
+    let () = linker.add_buf(&obj2, "ringbuf.bpf.o").unwrap();
+    let linked = linker.link_into_vec().unwrap();
+    assert!(linked.starts_with(b"\x7fELF"));
+
+    let object = ObjectBuilder::default().open_memory(&linked).unwrap();
+    assert!(object.map("ringbuf").is_some());
+
+    let mut linker = Linker::in_memory(&LinkerOptions::default()).unwrap();
+    let err = linker
+        .add_buf(b"not an ELF file", "bogus.bpf.o")
+        .unwrap_err();
+    assert!(err.to_string().contains("bpf_linker__add_file"), "{err}");
+}
+
 /// Get access to the underlying per-cpu ring buffer data.
 fn buffer<'a>(perf: &'a libbpf_rs::PerfBuffer, buf_idx: usize) -> &'a [u8] {
     let perf_buff_ptr = perf.as_libbpf_object();
@@ -1927,6 +3497,55 @@ fn test_object_perf_buffer_raw() {
     assert!(found_cookie);
 }
 
+/// Check that we can restrict a perf buffer to a subset of CPUs and
+/// consume samples from it without polling.
+#[tag(root)]
+#[test]
+fn test_object_perf_buffer_cpus() {
+    bump_rlimit_mlock();
+
+    let cookie_val = 42u16;
+    let mut obj = get_test_object("tracepoint.bpf.o");
+    let prog = obj
+        .prog_mut("handle__tracepoint_with_cookie_pb")
+        .expect("Failed to find program");
+
+    let opts = TracepointOpts {
+        cookie: cookie_val.into(),
+        ..TracepointOpts::default()
+    };
+    let _link = prog
+        .attach_tracepoint_with_opts("syscalls", "sys_enter_getpid", opts)
+        .expect("Failed to attach prog");
+
+    // Pin ourselves to the current CPU, so that the sample is guaranteed
+    // to end up in the buffer we listen on.
+    let cpu = unsafe { libc::sched_getcpu() };
+    assert!(cpu >= 0);
+    let mut set = unsafe { zeroed::<libc::cpu_set_t>() };
+    let () = unsafe { libc::CPU_SET(cpu as usize, &mut set) };
+    let rc = unsafe { libc::sched_setaffinity(0, size_of_val(&set), &set) };
+    assert_eq!(rc, 0);
+
+    let map = obj.map("pb").expect("Failed to get perf-buffer map");
+    let samples = RefCell::new(Vec::new());
+    let perf = libbpf_rs::PerfBufferBuilder::new(map)
+        .cpus(&[cpu as u32])
+        .sample_cb(|cpu, data: &[u8]| {
+            let value = i32::from_ne_bytes(data[..4].try_into().unwrap());
+            samples.borrow_mut().push((cpu, value))
+        })
+        .build()
+        .expect("Failed to build");
+    assert_eq!(perf.buffer_cnt(), 1);
+
+    let _pid = unsafe { libc::getpid() };
+
+    let () = perf.consume_buffer(0).expect("Failed to consume buffer");
+    drop(perf);
+    assert_eq!(samples.into_inner(), vec![(cpu, i32::from(cookie_val))]);
+}
+
 /// Check that we can get map pin status and map pin path
 #[tag(root)]
 #[test]
@@ -2060,6 +3679,114 @@ fn test_attach_ksyscall() {
     assert_eq!(result, 1);
 }
 
+/// Check that we can attach a kprobe with additional options.
+#[cfg(target_arch = "x86_64")]
+#[tag(root)]
+#[test]
+fn test_attach_kprobe_with_opts() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("ksyscall.bpf.o");
+    let prog = obj
+        .prog_mut("handle__ksyscall")
+        .expect("Failed to find program");
+
+    // Functions in non-existent kernel modules can't be attached to.
+    let opts = KprobeOpts {
+        module: Some("libbpf_rs_does_not_exist".to_string()),
+        ..Default::default()
+    };
+    let _err = prog
+        .attach_kprobe_with_opts("__x64_sys_kill", opts)
+        .unwrap_err();
+
+    let opts = KprobeOpts {
+        cookie: 42,
+        offset: 0,
+        ..Default::default()
+    };
+    let _link = prog
+        .attach_kprobe_with_opts("__x64_sys_kill", opts)
+        .expect("Failed to attach prog");
+
+    let map = obj.map("ringbuf").expect("Failed to get ringbuf map");
+    let action = || {
+        // Send `SIGCHLD`, which is ignored by default, to our process.
+        let ret = unsafe { libc::kill(libc::getpid(), libc::SIGCHLD) };
+        assert!(ret == 0, "kill failed: {}", io::Error::last_os_error());
+    };
+    let result = with_ringbuffer(map, action);
+
+    assert_eq!(result, 1);
+}
+
+/// Check that we can attach a program to multiple kprobes at once.
+#[cfg(target_arch = "x86_64")]
+#[tag(root)]
+#[test]
+fn test_attach_kprobe_multi() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("ksyscall.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__ksyscall")
+        .expect("Failed to find program");
+    let () = prog.set_expected_attach_type(ProgramAttachType::TraceKprobeMulti);
+    let mut obj = open_obj.load().expect("failed to load object");
+    let prog = obj
+        .prog_mut("handle__ksyscall")
+        .expect("Failed to find program");
+
+    let symbols = ["__x64_sys_kill", "__x64_sys_kill", "__x64_sys_tkill"]
+        .into_iter()
+        .collect::<SymbolList>();
+    assert_eq!(symbols.len(), 2);
+    let _link = prog
+        .attach_kprobe_multi(false, symbols.symbols())
+        .expect("Failed to attach prog");
+
+    let map = obj.map("ringbuf").expect("Failed to get ringbuf map");
+    let action = || {
+        // Send `SIGCHLD`, which is ignored by default, to our process.
+        let ret = unsafe { libc::kill(libc::getpid(), libc::SIGCHLD) };
+        assert!(ret == 0, "kill failed: {}", io::Error::last_os_error());
+    };
+    let result = with_ringbuffer(map, action);
+
+    assert_eq!(result, 1);
+}
+
+/// Check that we can attach a program to multiple uprobes at once.
+#[tag(root)]
+#[test]
+fn test_attach_uprobe_multi() {
+    bump_rlimit_mlock();
+
+    let mut open_obj = open_test_object("uprobe.bpf.o");
+    let prog = open_obj
+        .prog_mut("handle__uprobe")
+        .expect("Failed to find program");
+    let () = prog.set_expected_attach_type(ProgramAttachType::TraceUprobeMulti);
+    let mut obj = open_obj.load().expect("failed to load object");
+    let prog = obj
+        .prog_mut("handle__uprobe")
+        .expect("Failed to find program");
+
+    let pid = unsafe { libc::getpid() };
+    let path = current_exe().expect("Failed to find executable name");
+    let _link = prog
+        .attach_uprobe_multi(false, pid, path, &["uprobe_target"])
+        .expect("Failed to attach prog");
+
+    let map = obj.map("ringbuf").expect("Failed to get ringbuf map");
+    let action = || {
+        let _ = uprobe_target();
+    };
+    let result = with_ringbuffer(map, action);
+
+    assert_eq!(result, 1);
+}
+
 /// Check that we can invoke a program directly.
 #[tag(root)]
 #[test]
@@ -6,10 +6,14 @@
 //! For the same reason, all tests here must run serially.
 
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
+use libbpf_rs::add_print_subscriber;
 use libbpf_rs::get_print;
 use libbpf_rs::set_print;
+use libbpf_rs::with_print;
 use libbpf_rs::ObjectBuilder;
 use libbpf_rs::PrintCallback;
 use libbpf_rs::PrintLevel;
@@ -78,3 +82,109 @@ fn test_set_and_save_print() {
     let prev = set_print(None);
     assert_eq!(prev, Some((PrintLevel::Debug, callback2 as PrintCallback)));
 }
+
+#[test]
+#[serial]
+fn test_print_subscribers() {
+    static PRIMARY: AtomicUsize = AtomicUsize::new(0);
+
+    fn callback(_: PrintLevel, _: String) {
+        PRIMARY.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let warn = Arc::new(AtomicUsize::new(0));
+    let debug = Arc::new(AtomicUsize::new(0));
+
+    set_print(Some((PrintLevel::Warn, callback)));
+    let warn_sub = {
+        let warn = warn.clone();
+        add_print_subscriber(PrintLevel::Warn, move |level, msg| {
+            assert_eq!(level, PrintLevel::Warn);
+            assert!(msg.starts_with("libbpf: "));
+            warn.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+    let debug_sub = {
+        let debug = debug.clone();
+        add_print_subscriber(PrintLevel::Debug, move |_level, _msg| {
+            debug.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+
+    let obj = ObjectBuilder::default().open_file("/dev/null");
+    assert!(obj.is_err(), "Successfully loaded /dev/null?");
+
+    let primary_cnt = PRIMARY.load(Ordering::Relaxed);
+    let warn_cnt = warn.load(Ordering::Relaxed);
+    let debug_cnt = debug.load(Ordering::Relaxed);
+    assert!(warn_cnt > 0, "Did not capture a warning");
+    assert_eq!(primary_cnt, warn_cnt);
+    assert!(debug_cnt >= warn_cnt);
+
+    // Subscribers stay active without a primary callback, but stop
+    // receiving messages once unregistered.
+    set_print(None);
+    drop(warn_sub);
+    let obj = ObjectBuilder::default().open_file("/dev/null");
+    assert!(obj.is_err(), "Successfully loaded /dev/null?");
+
+    assert_eq!(PRIMARY.load(Ordering::Relaxed), primary_cnt);
+    assert_eq!(warn.load(Ordering::Relaxed), warn_cnt);
+    assert!(debug.load(Ordering::Relaxed) > debug_cnt);
+
+    drop(debug_sub);
+    assert_eq!(get_print(), None);
+}
+
+#[test]
+#[serial]
+fn test_with_print() {
+    static PRIMARY: AtomicUsize = AtomicUsize::new(0);
+
+    fn callback(_: PrintLevel, _: String) {
+        PRIMARY.fetch_add(1, Ordering::Relaxed);
+    }
+
+    set_print(Some((PrintLevel::Debug, callback)));
+
+    let mut outer = Vec::new();
+    let mut inner = Vec::new();
+    let () = with_print(
+        PrintLevel::Warn,
+        |level, msg| outer.push((level, msg.to_string())),
+        || {
+            let obj = ObjectBuilder::default().open_file("/dev/null");
+            assert!(obj.is_err(), "Successfully loaded /dev/null?");
+
+            let () = with_print(
+                PrintLevel::Debug,
+                |_level, msg| inner.push(msg.to_string()),
+                || {
+                    let obj = ObjectBuilder::default().open_file("/dev/null");
+                    assert!(obj.is_err(), "Successfully loaded /dev/null?");
+                },
+            );
+        },
+    );
+
+    // Scoped callbacks replace the global one for the duration of the
+    // call, without modifying it.
+    assert_eq!(PRIMARY.load(Ordering::Relaxed), 0);
+    assert_eq!(
+        get_print(),
+        Some((PrintLevel::Debug, callback as PrintCallback))
+    );
+
+    assert!(!outer.is_empty(), "Did not capture a warning");
+    assert!(outer
+        .iter()
+        .all(|(level, msg)| *level == PrintLevel::Warn && msg.starts_with("libbpf: ")));
+    assert!(inner.len() >= outer.len());
+
+    // Once the scope has ended, the global callback is used again.
+    let obj = ObjectBuilder::default().open_file("/dev/null");
+    assert!(obj.is_err(), "Successfully loaded /dev/null?");
+    assert!(PRIMARY.load(Ordering::Relaxed) > 0);
+
+    set_print(None);
+}
@@ -9,6 +9,7 @@ use test::bump_rlimit_mlock;
 use test::get_test_object;
 
 use libbpf_rs::ErrorKind;
+use libbpf_rs::Program;
 use libbpf_rs::Result;
 use libbpf_rs::TcHook;
 use libbpf_rs::TcHookBuilder;
@@ -377,3 +378,81 @@ fn test_tc_double_create() {
 
     assert!(clear_clsact(fd).is_ok());
 }
+
+/// Check that we can list the filters attached to a hook and attach
+/// programs by their ID.
+#[tag(root)]
+#[test]
+#[serial]
+fn test_tc_query_filters() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("tc-unit.bpf.o");
+    let fd = obj.prog("handle_tc").unwrap().as_fd();
+    let prog_id = Program::get_id_by_fd(fd).unwrap();
+
+    let mut tc_builder = TcHookBuilder::new(fd);
+    tc_builder
+        .ifindex(LO_IFINDEX)
+        .replace(true)
+        .handle(1)
+        .priority(1);
+    assert!(clear_clsact(fd).is_ok());
+
+    let mut ingress = tc_builder.hook(TC_INGRESS);
+    assert!(ingress.create().is_ok());
+    assert_eq!(ingress.query_filters().unwrap(), Vec::new());
+    assert!(ingress.attach().is_ok());
+
+    let filters = ingress.query_filters().unwrap();
+    assert_eq!(filters.len(), 1);
+    assert_eq!(filters[0].prog_id, prog_id);
+    assert_eq!(filters[0].handle, 1);
+    assert_eq!(filters[0].priority, 1);
+
+    let mut by_id = TcHookBuilder::from_prog_id(prog_id).unwrap();
+    by_id.ifindex(LO_IFINDEX).handle(2).priority(2);
+    let mut ingress2 = by_id.hook(TC_INGRESS);
+    assert!(ingress2.attach().is_ok());
+
+    let filters = ingress.query_filters().unwrap();
+    assert_eq!(filters.len(), 2);
+    assert!(filters.iter().all(|filter| filter.prog_id == prog_id));
+
+    assert!(clear_clsact(fd).is_ok());
+}
+
+/// Check that we can attach, query, and detach filters on non-default
+/// chains and for specific protocols.
+#[tag(root)]
+#[test]
+#[serial]
+fn test_tc_chain_and_protocol() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("tc-unit.bpf.o");
+    let fd = obj.prog("handle_tc").unwrap().as_fd();
+
+    let mut tc_builder = TcHookBuilder::new(fd);
+    tc_builder
+        .ifindex(LO_IFINDEX)
+        .replace(true)
+        .handle(1)
+        .priority(1)
+        .chain_index(1)
+        .protocol(0x86dd /* ETH_P_IPV6 */)
+        .skip_hw(true);
+    assert!(clear_clsact(fd).is_ok());
+
+    let mut ingress = tc_builder.hook(TC_INGRESS);
+    assert!(ingress.create().is_ok());
+    assert!(ingress.attach().is_ok());
+    assert_eq!(ingress.get_handle(), 1);
+    assert_eq!(ingress.get_priority(), 1);
+    assert_eq!(ingress.query().unwrap(), Program::get_id_by_fd(fd).unwrap());
+
+    assert!(ingress.detach().is_ok());
+    assert!(ingress.query().is_err());
+
+    assert!(clear_clsact(fd).is_ok());
+}
@@ -8,7 +8,9 @@ mod test;
 use test::bump_rlimit_mlock;
 use test::get_test_object;
 
+use libbpf_rs::Program;
 use libbpf_rs::Xdp;
+use libbpf_rs::XdpFeatureFlags;
 use libbpf_rs::XdpFlags;
 
 const LO_IFINDEX: i32 = 1;
@@ -62,3 +64,65 @@ fn test_xdp() {
         .detach(LO_IFINDEX, XdpFlags::UPDATE_IF_NOEXIST)
         .is_ok());
 }
+
+/// Check that we can attach an XDP program via a link, with fallback to
+/// the legacy API, and that it gets detached once dropped.
+#[tag(root)]
+#[test]
+fn test_xdp_attach_with_fallback() {
+    bump_rlimit_mlock();
+
+    let mut obj = get_test_object("xdp.bpf.o");
+    let prog = obj.prog_mut("xdp_filter").unwrap();
+    let prog_id = Program::get_id_by_fd(prog.as_fd()).unwrap();
+    let fd = prog.as_fd().try_clone_to_owned().unwrap();
+    let xdp_prog = Xdp::new(fd.as_fd());
+
+    let attachment = prog.attach_xdp_with_fallback(LO_IFINDEX).unwrap();
+    assert_eq!(attachment.is_link(), attachment.link().is_some());
+    assert_eq!(
+        xdp_prog.query_id(LO_IFINDEX, XdpFlags::NONE).unwrap(),
+        prog_id
+    );
+
+    // A second attachment should fail, irrespective of the mechanism used.
+    assert!(prog.attach_xdp_with_fallback(LO_IFINDEX).is_err());
+
+    let () = drop(attachment);
+    assert_eq!(xdp_prog.query_id(LO_IFINDEX, XdpFlags::NONE).unwrap(), 0);
+}
+
+/// Check that we can query the XDP features of a network device.
+#[test]
+fn test_xdp_query_features() {
+    let features = Xdp::query_features(LO_IFINDEX).unwrap();
+    // The loopback device does not support offloading programs.
+    assert!(!features.flags.contains(XdpFeatureFlags::HW_OFFLOAD));
+    assert_ne!(features.best_mode().bits(), XdpFlags::HW_MODE.bits());
+}
+
+/// Check that we can attach and detach an XDP program by interface
+/// name.
+#[tag(root)]
+#[test]
+fn test_xdp_ifname() {
+    bump_rlimit_mlock();
+
+    let obj = get_test_object("xdp.bpf.o");
+    let prog = obj.prog("xdp_filter").unwrap();
+    let prog_id = Program::get_id_by_fd(prog.as_fd()).unwrap();
+    let xdp_prog = Xdp::new(prog.as_fd());
+
+    let () = xdp_prog.attach_ifname("lo", XdpFlags::NONE).unwrap();
+    assert_eq!(
+        xdp_prog.query_id(LO_IFINDEX, XdpFlags::NONE).unwrap(),
+        prog_id
+    );
+    let () = xdp_prog.detach_ifname("lo", XdpFlags::NONE).unwrap();
+    assert_eq!(xdp_prog.query_id(LO_IFINDEX, XdpFlags::NONE).unwrap(), 0);
+
+    let err = xdp_prog
+        .attach_ifname("does-not-exist", XdpFlags::NONE)
+        .unwrap_err();
+    assert!(err.to_string().contains("does-not-exist"), "{err}");
+}